@@ -285,7 +285,7 @@ fn benchmark_vector_bulk_insert(c: &mut Criterion) {
 // ============================================
 
 fn benchmark_compression_ratio(c: &mut Criterion) {
-    use keradb::vector::compression::{CompressedVectorStore, DeltaCompressor};
+    use keradb::vector::compression::CompressedVectorStore;
     
     let mut group = c.benchmark_group("compression_ratio");
     
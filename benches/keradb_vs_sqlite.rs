@@ -5,12 +5,15 @@
 //! - Bulk inserts
 //! - Point queries (by ID)
 //! - Range queries
+//! - Filtered queries, prepared once
 //! - Updates
 //! - Deletes
 //! - JSON document operations
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId, Throughput};
-use keradb::Database;
+use keradb::query::Filter;
+use keradb::vector::FilterCondition;
+use keradb::{Database, FindQuery};
 use rusqlite::{Connection, params};
 use serde_json::json;
 use tempfile::tempdir;
@@ -271,7 +274,7 @@ fn benchmark_range_query(c: &mut Criterion) {
     for i in 0..5000 {
         sqlite_conn.execute(
             "INSERT INTO users (name, age, email) VALUES (?1, ?2, ?3)",
-            params![format!("User {}", i), 25 + (i as i32 % 50), format!("user{}@example.com", i)],
+            params![format!("User {}", i), 25 + (i % 50), format!("user{}@example.com", i)],
         ).unwrap();
     }
     sqlite_conn.execute("COMMIT", []).unwrap();
@@ -304,6 +307,64 @@ fn benchmark_range_query(c: &mut Criterion) {
     group.finish();
 }
 
+fn benchmark_filtered_query(c: &mut Criterion) {
+    let mut group = c.benchmark_group("filtered_query");
+    group.measurement_time(Duration::from_secs(5));
+    group.sample_size(50);
+
+    let (_kera_dir, kera_db) = setup_keradb();
+    for i in 0..5000 {
+        kera_db.insert("users", json!({
+            "name": format!("User {}", i),
+            "age": 25 + (i % 50),
+            "email": format!("user{}@example.com", i)
+        })).unwrap();
+    }
+    kera_db.create_columns("users", &["age"]).unwrap();
+
+    let (_sqlite_dir, sqlite_conn) = setup_sqlite_with_json();
+    sqlite_conn.execute("BEGIN TRANSACTION", []).unwrap();
+    for i in 0..5000 {
+        sqlite_conn.execute(
+            "INSERT INTO users (name, age, email) VALUES (?1, ?2, ?3)",
+            params![format!("User {}", i), 25 + (i % 50), format!("user{}@example.com", i)],
+        ).unwrap();
+    }
+    sqlite_conn.execute("COMMIT", []).unwrap();
+
+    // Both prepare the query once and run it repeatedly
+    let query = FindQuery {
+        collection: "users".to_string(),
+        id: None,
+        filter: Filter { conditions: vec![("age".to_string(), FilterCondition::Eq(json!(30)))], collation: None },
+        sort: None,
+        limit: None,
+        skip: 0,
+    };
+    let prepared = kera_db.prepare(query);
+    group.bench_function("keradb_prepared", |b| {
+        b.iter(|| {
+            black_box(prepared.run(&kera_db).unwrap());
+        });
+    });
+
+    group.bench_function("sqlite_prepared", |b| {
+        b.iter(|| {
+            let mut stmt = sqlite_conn.prepare_cached("SELECT * FROM users WHERE age = ?1").unwrap();
+            let results: Vec<(i32, String, i32, String)> = stmt
+                .query_map([30], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+                })
+                .unwrap()
+                .filter_map(|r| r.ok())
+                .collect();
+            black_box(results);
+        });
+    });
+
+    group.finish();
+}
+
 // ============================================================
 // Update Benchmarks
 // ============================================================
@@ -554,7 +615,7 @@ fn benchmark_read_heavy_workload(c: &mut Criterion) {
             "INSERT INTO users (name, age, email, data) VALUES (?1, ?2, ?3, ?4)",
             params![
                 format!("User {}", i),
-                25 + (i as i32 % 50),
+                25 + (i % 50),
                 format!("user{}@example.com", i),
                 json!({"score": i * 10}).to_string()
             ],
@@ -606,7 +667,7 @@ fn benchmark_read_heavy_workload(c: &mut Criterion) {
                     params![idx],
                     |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
                 );
-                black_box(result);
+                let _ = black_box(result);
             }
         });
     });
@@ -696,6 +757,7 @@ criterion_group!(
     benchmark_bulk_insert,
     benchmark_point_query,
     benchmark_range_query,
+    benchmark_filtered_query,
     benchmark_update,
     benchmark_delete,
     benchmark_json_operations,
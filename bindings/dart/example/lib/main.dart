@@ -0,0 +1,175 @@
+// Notes kept in a KeraDB file in the app's documents directory. Tapping a
+// note lists the most similar ones, found by vector search.
+//
+// Embeddings here are hashed character trigrams so the example needs no
+// model; a real app would use a sentence embedding model instead.
+
+import 'dart:math';
+
+import 'package:flutter/material.dart';
+import 'package:keradb/keradb.dart';
+import 'package:path_provider/path_provider.dart';
+
+const dimensions = 64;
+
+Future<void> main() async {
+  WidgetsFlutterBinding.ensureInitialized();
+  final dir = await getApplicationDocumentsDirectory();
+  final path = '${dir.path}/notes.ndb';
+  KeraDB db;
+  try {
+    db = KeraDB.open(path);
+  } on KeraDBException catch (e) {
+    if (e.code != KeraDBErrorCode.databaseNotFound) rethrow;
+    db = KeraDB.create(path);
+    db.createVectorCollection('note_embeddings', dimensions);
+  }
+  runApp(NotesApp(db: db));
+}
+
+List<double> embed(String text) {
+  final vector = List<double>.filled(dimensions, 0);
+  final padded = '  ${text.toLowerCase()} ';
+  for (var i = 0; i + 3 <= padded.length; i++) {
+    // FNV-1a, since String.hashCode may change between runs
+    var hash = 0x811c9dc5;
+    for (final unit in padded.codeUnits.sublist(i, i + 3)) {
+      hash = ((hash ^ unit) * 0x01000193) & 0xffffffff;
+    }
+    vector[hash % dimensions] += 1;
+  }
+  final norm = sqrt(vector.fold<double>(0, (sum, x) => sum + x * x));
+  return [for (final x in vector) norm == 0 ? 0 : x / norm];
+}
+
+class NotesApp extends StatelessWidget {
+  const NotesApp({super.key, required this.db});
+
+  final KeraDB db;
+
+  @override
+  Widget build(BuildContext context) {
+    return MaterialApp(
+      title: 'KeraDB Notes',
+      theme: ThemeData(useMaterial3: true),
+      home: NotesPage(db: db),
+    );
+  }
+}
+
+class NotesPage extends StatefulWidget {
+  const NotesPage({super.key, required this.db});
+
+  final KeraDB db;
+
+  @override
+  State<NotesPage> createState() => _NotesPageState();
+}
+
+class _NotesPageState extends State<NotesPage> with WidgetsBindingObserver {
+  final _input = TextEditingController();
+  List<Map<String, dynamic>> _notes = [];
+
+  KeraDB get db => widget.db;
+
+  @override
+  void initState() {
+    super.initState();
+    WidgetsBinding.instance.addObserver(this);
+    _reload();
+  }
+
+  @override
+  void dispose() {
+    WidgetsBinding.instance.removeObserver(this);
+    _input.dispose();
+    super.dispose();
+  }
+
+  @override
+  void didChangeAppLifecycleState(AppLifecycleState state) {
+    // Mobile apps can be killed at any time once in the background
+    if (state == AppLifecycleState.paused) db.sync();
+  }
+
+  void _reload() => setState(() => _notes = db.findAll('notes'));
+
+  void _add() {
+    final text = _input.text.trim();
+    if (text.isEmpty) return;
+    // Each note is keyed by its embedding's vector ID, so search hits lead
+    // straight back to notes
+    final vector = db.insertVector('note_embeddings', embed(text));
+    db.insert('notes', {
+      '_id': 'note-$vector',
+      'vector': vector,
+      'text': text,
+      'created': DateTime.now().toIso8601String(),
+    });
+    _input.clear();
+    _reload();
+  }
+
+  void _delete(Map<String, dynamic> note) {
+    db.deleteVector('note_embeddings', note['vector'] as int);
+    db.delete('notes', note['_id'] as String);
+    _reload();
+  }
+
+  void _showSimilar(Map<String, dynamic> note) {
+    final hits = db.vectorSearch('note_embeddings', embed(note['text'] as String), k: 4);
+    final similar = [
+      for (final hit in hits)
+        if (hit.entry.id != note['vector']) (db.findById('notes', 'note-${hit.entry.id}'), hit.score),
+    ];
+    showModalBottomSheet<void>(
+      context: context,
+      builder: (context) => ListView(
+        children: [
+          const ListTile(title: Text('Similar notes')),
+          for (final (doc, score) in similar)
+            if (doc != null)
+              ListTile(title: Text(doc['text'] as String), trailing: Text(score.toStringAsFixed(2))),
+        ],
+      ),
+    );
+  }
+
+  @override
+  Widget build(BuildContext context) {
+    return Scaffold(
+      appBar: AppBar(title: const Text('KeraDB Notes')),
+      body: Column(
+        children: [
+          Padding(
+            padding: const EdgeInsets.all(12),
+            child: TextField(
+              controller: _input,
+              decoration: InputDecoration(
+                hintText: 'New note',
+                suffixIcon: IconButton(icon: const Icon(Icons.add), onPressed: _add),
+              ),
+              onSubmitted: (_) => _add(),
+            ),
+          ),
+          Expanded(
+            child: ListView(
+              children: [
+                for (final note in _notes)
+                  ListTile(
+                    title: Text(note['text'] as String),
+                    subtitle: Text(note['created'] as String),
+                    onTap: () => _showSimilar(note),
+                    trailing: IconButton(
+                      icon: const Icon(Icons.delete_outline),
+                      onPressed: () => _delete(note),
+                    ),
+                  ),
+              ],
+            ),
+          ),
+        ],
+      ),
+    );
+  }
+}
@@ -0,0 +1,5 @@
+/// Dart and Flutter bindings for KeraDB, over its C API.
+library keradb;
+
+export 'src/database.dart' show KeraDB, VectorHit, VectorEntry;
+export 'src/errors.dart' show KeraDBException, KeraDBErrorCode;
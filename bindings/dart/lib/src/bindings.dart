@@ -0,0 +1,98 @@
+// Declarations of the C API in include/keradb.h, looked up in the native
+// library. Keep them in step with the header.
+
+import 'dart:ffi';
+import 'dart:io';
+
+import 'package:ffi/ffi.dart';
+
+final class KeraDBHandle extends Opaque {}
+
+typedef _ErrorCodeC = Int32 Function();
+typedef _ErrorCode = int Function();
+typedef _StringC = Pointer<Utf8> Function();
+typedef _FreeStringC = Void Function(Pointer<Utf8>);
+typedef _FreeString = void Function(Pointer<Utf8>);
+typedef _OpenC = Pointer<KeraDBHandle> Function(Pointer<Utf8>);
+typedef _CloseC = Void Function(Pointer<KeraDBHandle>);
+typedef _Close = void Function(Pointer<KeraDBHandle>);
+typedef _Db2StrC = Pointer<Utf8> Function(Pointer<KeraDBHandle>, Pointer<Utf8>, Pointer<Utf8>);
+typedef _Db3StrC = Pointer<Utf8> Function(Pointer<KeraDBHandle>, Pointer<Utf8>, Pointer<Utf8>, Pointer<Utf8>);
+typedef _Db2IntC = Int Function(Pointer<KeraDBHandle>, Pointer<Utf8>, Pointer<Utf8>);
+typedef _Db2Int = int Function(Pointer<KeraDBHandle>, Pointer<Utf8>, Pointer<Utf8>);
+typedef _FindAllC = Pointer<Utf8> Function(Pointer<KeraDBHandle>, Pointer<Utf8>, Int, Int);
+typedef _FindAll = Pointer<Utf8> Function(Pointer<KeraDBHandle>, Pointer<Utf8>, int, int);
+typedef _CountC = Int Function(Pointer<KeraDBHandle>, Pointer<Utf8>);
+typedef _Count = int Function(Pointer<KeraDBHandle>, Pointer<Utf8>);
+typedef _DbStrC = Pointer<Utf8> Function(Pointer<KeraDBHandle>);
+typedef _DbIntC = Int Function(Pointer<KeraDBHandle>);
+typedef _DbInt = int Function(Pointer<KeraDBHandle>);
+typedef _CreateVectorsC = Int Function(Pointer<KeraDBHandle>, Pointer<Utf8>, Size, Pointer<Utf8>);
+typedef _CreateVectors = int Function(Pointer<KeraDBHandle>, Pointer<Utf8>, int, Pointer<Utf8>);
+typedef _InsertVectorC = Int Function(
+    Pointer<KeraDBHandle>, Pointer<Utf8>, Pointer<Float>, Size, Pointer<Utf8>, Pointer<Uint64>);
+typedef _InsertVector = int Function(
+    Pointer<KeraDBHandle>, Pointer<Utf8>, Pointer<Float>, int, Pointer<Utf8>, Pointer<Uint64>);
+typedef _VectorSearchC = Pointer<Utf8> Function(Pointer<KeraDBHandle>, Pointer<Utf8>, Pointer<Float>, Size, Size);
+typedef _VectorSearch = Pointer<Utf8> Function(Pointer<KeraDBHandle>, Pointer<Utf8>, Pointer<Float>, int, int);
+typedef _GetVectorC = Pointer<Utf8> Function(Pointer<KeraDBHandle>, Pointer<Utf8>, Uint64);
+typedef _GetVector = Pointer<Utf8> Function(Pointer<KeraDBHandle>, Pointer<Utf8>, int);
+typedef _DeleteVectorC = Int Function(Pointer<KeraDBHandle>, Pointer<Utf8>, Uint64);
+typedef _DeleteVector = int Function(Pointer<KeraDBHandle>, Pointer<Utf8>, int);
+
+/// The native library's functions
+class Bindings {
+  Bindings(DynamicLibrary lib)
+      : lastErrorCode = lib.lookupFunction<_ErrorCodeC, _ErrorCode>('keradb_last_error_code'),
+        lastError = lib.lookupFunction<_StringC, _StringC>('keradb_last_error'),
+        freeString = lib.lookupFunction<_FreeStringC, _FreeString>('keradb_free_string'),
+        create = lib.lookupFunction<_OpenC, _OpenC>('keradb_create'),
+        open = lib.lookupFunction<_OpenC, _OpenC>('keradb_open'),
+        close = lib.lookupFunction<_CloseC, _Close>('keradb_close'),
+        insert = lib.lookupFunction<_Db2StrC, _Db2StrC>('keradb_insert'),
+        findById = lib.lookupFunction<_Db2StrC, _Db2StrC>('keradb_find_by_id'),
+        update = lib.lookupFunction<_Db3StrC, _Db3StrC>('keradb_update'),
+        delete = lib.lookupFunction<_Db2IntC, _Db2Int>('keradb_delete'),
+        findAll = lib.lookupFunction<_FindAllC, _FindAll>('keradb_find_all'),
+        count = lib.lookupFunction<_CountC, _Count>('keradb_count'),
+        listCollections = lib.lookupFunction<_DbStrC, _DbStrC>('keradb_list_collections'),
+        sync = lib.lookupFunction<_DbIntC, _DbInt>('keradb_sync'),
+        createVectorCollection =
+            lib.lookupFunction<_CreateVectorsC, _CreateVectors>('keradb_create_vector_collection'),
+        insertVector = lib.lookupFunction<_InsertVectorC, _InsertVector>('keradb_insert_vector'),
+        vectorSearch = lib.lookupFunction<_VectorSearchC, _VectorSearch>('keradb_vector_search'),
+        getVector = lib.lookupFunction<_GetVectorC, _GetVector>('keradb_get_vector'),
+        deleteVector = lib.lookupFunction<_DeleteVectorC, _DeleteVector>('keradb_delete_vector'),
+        listVectorCollections = lib.lookupFunction<_DbStrC, _DbStrC>('keradb_list_vector_collections');
+
+  final _ErrorCode lastErrorCode;
+  final Pointer<Utf8> Function() lastError;
+  final _FreeString freeString;
+  final Pointer<KeraDBHandle> Function(Pointer<Utf8>) create;
+  final Pointer<KeraDBHandle> Function(Pointer<Utf8>) open;
+  final _Close close;
+  final Pointer<Utf8> Function(Pointer<KeraDBHandle>, Pointer<Utf8>, Pointer<Utf8>) insert;
+  final Pointer<Utf8> Function(Pointer<KeraDBHandle>, Pointer<Utf8>, Pointer<Utf8>) findById;
+  final Pointer<Utf8> Function(Pointer<KeraDBHandle>, Pointer<Utf8>, Pointer<Utf8>, Pointer<Utf8>) update;
+  final _Db2Int delete;
+  final _FindAll findAll;
+  final _Count count;
+  final Pointer<Utf8> Function(Pointer<KeraDBHandle>) listCollections;
+  final _DbInt sync;
+  final _CreateVectors createVectorCollection;
+  final _InsertVector insertVector;
+  final _VectorSearch vectorSearch;
+  final _GetVector getVector;
+  final _DeleteVector deleteVector;
+  final Pointer<Utf8> Function(Pointer<KeraDBHandle>) listVectorCollections;
+
+  /// The library at [path], or the platform's default: bundled with the app
+  /// on Android, Linux and Windows, and linked into the app on iOS
+  static Bindings load([String? path]) {
+    if (path != null) return Bindings(DynamicLibrary.open(path));
+    if (Platform.isIOS) return Bindings(DynamicLibrary.process());
+    if (Platform.isMacOS) return Bindings(DynamicLibrary.open('libkeradb.dylib'));
+    if (Platform.isWindows) return Bindings(DynamicLibrary.open('keradb.dll'));
+    return Bindings(DynamicLibrary.open('libkeradb.so'));
+  }
+}
@@ -0,0 +1,227 @@
+import 'dart:convert';
+import 'dart:ffi';
+import 'dart:typed_data';
+
+import 'package:ffi/ffi.dart';
+
+import 'bindings.dart';
+import 'errors.dart';
+
+/// A vector stored in a collection
+class VectorEntry {
+  VectorEntry._(Map<String, dynamic> json)
+      : id = json['id'] as int,
+        embedding = json['embedding'] == null
+            ? null
+            : Float32List.fromList([for (final x in json['embedding'] as List) (x as num).toDouble()]),
+        metadata = json['metadata'];
+
+  final int id;
+
+  /// Null for vectors stored as text only
+  final Float32List? embedding;
+
+  /// Whatever was inserted with the vector, decoded from JSON
+  final Object? metadata;
+}
+
+/// One result of [KeraDB.vectorSearch]
+class VectorHit {
+  VectorHit._(Map<String, dynamic> json)
+      : entry = VectorEntry._(json['document'] as Map<String, dynamic>),
+        score = (json['score'] as num).toDouble(),
+        rank = json['rank'] as int;
+
+  final VectorEntry entry;
+
+  /// Distance from the query; lower is nearer
+  final double score;
+
+  /// Position in the results, starting at 0
+  final int rank;
+}
+
+/// A KeraDB database
+///
+/// Documents are JSON objects, passed and returned as `Map<String, dynamic>`.
+/// Calls are synchronous and run on the calling isolate; in Flutter, run
+/// large imports or searches with `Isolate.run` to keep the UI responsive.
+/// Close the database when done with it, or the file stays locked.
+class KeraDB {
+  KeraDB._(this._native, this._handle);
+
+  static Bindings? _bindings;
+
+  /// Load the native library from [path] instead of the platform default
+  ///
+  /// Call before opening any database, e.g. to use a library built with
+  /// `cargo build` in tests.
+  static void loadLibrary(String path) {
+    _bindings = Bindings.load(path);
+  }
+
+  static Bindings get _lib => _bindings ??= Bindings.load();
+
+  final Bindings _native;
+  Pointer<KeraDBHandle> _handle;
+
+  /// Create a new database file at [path]
+  factory KeraDB.create(String path) => _connect(path, _lib.create);
+
+  /// Open the existing database file at [path]
+  factory KeraDB.open(String path) => _connect(path, _lib.open);
+
+  static KeraDB _connect(String path, Pointer<KeraDBHandle> Function(Pointer<Utf8>) connect) {
+    final lib = _lib;
+    final handle = using((arena) => connect(path.toNativeUtf8(allocator: arena)));
+    if (handle == nullptr) throw _error(lib);
+    return KeraDB._(lib, handle);
+  }
+
+  /// Whether [close] has been called
+  bool get isClosed => _handle == nullptr;
+
+  /// Close the database; later calls throw
+  void close() {
+    if (isClosed) return;
+    _native.close(_handle);
+    _handle = nullptr;
+  }
+
+  /// Insert [document] into [collection] and return its ID
+  ///
+  /// An `_id` string in the document is used as its ID.
+  String insert(String collection, Map<String, dynamic> document) => using((arena) {
+        return _string(_native.insert(_db, _str(collection, arena), _str(jsonEncode(document), arena)));
+      });
+
+  /// The document with [id], or null if there is none
+  Map<String, dynamic>? findById(String collection, String id) => using((arena) {
+        final result = _native.findById(_db, _str(collection, arena), _str(id, arena));
+        if (result == nullptr && _native.lastErrorCode() == KeraDBErrorCode.documentNotFound.value) return null;
+        return _document(_string(result));
+      });
+
+  /// Replace the data of the document with [id] and return the result
+  Map<String, dynamic> update(String collection, String id, Map<String, dynamic> document) => using((arena) {
+        final result = _native.update(_db, _str(collection, arena), _str(id, arena), _str(jsonEncode(document), arena));
+        return _document(_string(result));
+      });
+
+  /// Delete the document with [id]; returns false if there was none
+  bool delete(String collection, String id) => using((arena) {
+        if (_native.delete(_db, _str(collection, arena), _str(id, arena)) == 1) return true;
+        if (_native.lastErrorCode() == KeraDBErrorCode.documentNotFound.value) return false;
+        throw _error(_native);
+      });
+
+  /// Documents in [collection], skipping [skip] and returning at most [limit]
+  List<Map<String, dynamic>> findAll(String collection, {int? limit, int? skip}) => using((arena) {
+        final result = _native.findAll(_db, _str(collection, arena), limit ?? -1, skip ?? -1);
+        return [for (final doc in jsonDecode(_string(result)) as List) _document(doc as Map<String, dynamic>)];
+      });
+
+  /// Number of documents in [collection]
+  int count(String collection) => using((arena) {
+        final count = _native.count(_db, _str(collection, arena));
+        if (count < 0) throw _error(_native);
+        return count;
+      });
+
+  /// Collection names and their document counts
+  Map<String, int> collections() => _pairs(_native.listCollections(_db));
+
+  /// Flush everything written so far to disk
+  void sync() {
+    if (_native.sync(_db) != 1) throw _error(_native);
+  }
+
+  /// Create a vector collection for [dimensions]-long embeddings
+  ///
+  /// [distance] is `cosine` (the default), `euclidean`, `dot` or `manhattan`.
+  void createVectorCollection(String name, int dimensions, {String? distance}) => using((arena) {
+        final metric = distance == null ? nullptr : _str(distance, arena);
+        if (_native.createVectorCollection(_db, _str(name, arena), dimensions, metric) != 1) throw _error(_native);
+      });
+
+  /// Insert [embedding] into [collection] with optional JSON [metadata] and
+  /// return its ID
+  int insertVector(String collection, List<double> embedding, {Object? metadata}) => using((arena) {
+        final vector = _floats(embedding, arena);
+        final json = metadata == null ? nullptr : _str(jsonEncode(metadata), arena);
+        final id = arena<Uint64>();
+        if (_native.insertVector(_db, _str(collection, arena), vector, embedding.length, json, id) != 1) {
+          throw _error(_native);
+        }
+        return id.value;
+      });
+
+  /// The [k] vectors in [collection] nearest [query], nearest first
+  List<VectorHit> vectorSearch(String collection, List<double> query, {int k = 10}) => using((arena) {
+        final result = _native.vectorSearch(_db, _str(collection, arena), _floats(query, arena), query.length, k);
+        return [for (final hit in jsonDecode(_string(result)) as List) VectorHit._(hit as Map<String, dynamic>)];
+      });
+
+  /// The vector with [id], or null if there is none
+  VectorEntry? getVector(String collection, int id) => using((arena) {
+        final result = _native.getVector(_db, _str(collection, arena), id);
+        if (result == nullptr && _native.lastErrorCode() == KeraDBErrorCode.notFound.value) return null;
+        return VectorEntry._(jsonDecode(_string(result)) as Map<String, dynamic>);
+      });
+
+  /// Delete the vector with [id]; returns false if there was none
+  bool deleteVector(String collection, int id) => using((arena) {
+        final deleted = _native.deleteVector(_db, _str(collection, arena), id);
+        if (deleted < 0) throw _error(_native);
+        return deleted == 1;
+      });
+
+  /// Vector collection names and their vector counts
+  Map<String, int> vectorCollections() => _pairs(_native.listVectorCollections(_db));
+
+  Pointer<KeraDBHandle> get _db {
+    if (isClosed) throw StateError('Database is closed');
+    return _handle;
+  }
+
+  /// Take ownership of a string KeraDB returned, throwing if it is NULL
+  /// because the call failed
+  String _string(Pointer<Utf8> result) {
+    if (result == nullptr) throw _error(_native);
+    try {
+      return result.toDartString();
+    } finally {
+      _native.freeString(result);
+    }
+  }
+
+  Map<String, int> _pairs(Pointer<Utf8> result) {
+    final pairs = jsonDecode(_string(result)) as List;
+    return {for (final pair in pairs) pair[0] as String: pair[1] as int};
+  }
+
+  static Pointer<Utf8> _str(String s, Arena arena) => s.toNativeUtf8(allocator: arena);
+
+  static Pointer<Float> _floats(List<double> values, Arena arena) {
+    final floats = arena<Float>(values.isEmpty ? 1 : values.length);
+    floats.asTypedList(values.length).setAll(0, values);
+    return floats;
+  }
+
+  /// A document's fields and `_id`, without the collection KeraDB adds
+  static Map<String, dynamic> _document(Object? json) {
+    if (json is String) json = jsonDecode(json);
+    return (json as Map<String, dynamic>)..remove('_collection');
+  }
+
+  static KeraDBException _error(Bindings lib) {
+    final code = KeraDBErrorCode.fromValue(lib.lastErrorCode());
+    final message = lib.lastError();
+    if (message == nullptr) return KeraDBException(code, 'Unknown error');
+    try {
+      return KeraDBException(code, message.toDartString());
+    } finally {
+      lib.freeString(message);
+    }
+  }
+}
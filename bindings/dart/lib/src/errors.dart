@@ -0,0 +1,53 @@
+/// Why a call failed; mirrors `KeraDBErrorCode` in include/keradb.h
+enum KeraDBErrorCode {
+  ok(0),
+  nullArgument(1),
+  invalidUtf8(2),
+  invalidJson(3),
+  panic(4),
+  other(5),
+  io(10),
+  serialization(11),
+  databaseNotFound(12),
+  collectionNotFound(13),
+  documentNotFound(14),
+  invalidFormat(15),
+  versionMismatch(16),
+  checksumMismatch(17),
+  locked(18),
+  readOnly(19),
+  config(20),
+  invalidQuery(21),
+  invalidDocument(22),
+  duplicateKey(23),
+  index(24),
+  transaction(25),
+  storage(26),
+  parse(27),
+  collectionExists(28),
+  notFound(29),
+  notImplemented(30),
+  vector(31),
+  embedding(32),
+  oplogTruncated(33);
+
+  const KeraDBErrorCode(this.value);
+
+  final int value;
+
+  /// The code for [value]; codes added to the C API after this package map
+  /// to [other]
+  static KeraDBErrorCode fromValue(int value) =>
+      values.firstWhere((code) => code.value == value, orElse: () => other);
+}
+
+/// A failed KeraDB call
+class KeraDBException implements Exception {
+  KeraDBException(this.code, this.message);
+
+  final KeraDBErrorCode code;
+  final String message;
+
+  @override
+  String toString() => 'KeraDBException(${code.name}): $message';
+}
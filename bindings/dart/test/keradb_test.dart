@@ -0,0 +1,61 @@
+import 'dart:io';
+
+import 'package:keradb/keradb.dart';
+import 'package:test/test.dart';
+
+/// Run with the library from `cargo build` at the repository root, or set
+/// KERADB_LIB to another build
+void main() {
+  final ext = Platform.isMacOS ? 'dylib' : (Platform.isWindows ? 'dll' : 'so');
+  final name = Platform.isWindows ? 'keradb.dll' : 'libkeradb.$ext';
+  KeraDB.loadLibrary(Platform.environment['KERADB_LIB'] ?? '../../target/debug/$name');
+
+  late Directory dir;
+  setUp(() => dir = Directory.systemTemp.createTempSync('keradb'));
+  tearDown(() => dir.deleteSync(recursive: true));
+
+  test('documents', () {
+    final path = '${dir.path}/test.ndb';
+    final db = KeraDB.create(path);
+    final alice = db.insert('users', {'name': 'Alice', 'age': 30, 'tags': ['a', 'b']});
+    db.insert('users', {'_id': 'bob', 'name': 'Bob', 'age': 17});
+
+    expect(db.findById('users', alice), {'_id': alice, 'name': 'Alice', 'age': 30, 'tags': ['a', 'b']});
+    expect(db.findById('users', 'nobody'), isNull);
+    expect(db.findAll('users', limit: 1, skip: 1), hasLength(1));
+    expect(db.count('users'), 2);
+    expect(db.update('users', 'bob', {'name': 'Bob', 'age': 18})['age'], 18);
+    expect(db.delete('users', 'bob'), isTrue);
+    expect(db.delete('users', 'bob'), isFalse);
+    expect(db.collections(), {'users': 1});
+    db.close();
+
+    expect(() => db.count('users'), throwsStateError);
+    expect(() => KeraDB.create(path),
+        throwsA(isA<KeraDBException>().having((e) => e.code, 'code', KeraDBErrorCode.invalidFormat)));
+    final reopened = KeraDB.open(path);
+    expect(reopened.count('users'), 1);
+    reopened.close();
+  });
+
+  test('vectors', () {
+    final db = KeraDB.create('${dir.path}/vectors.ndb');
+    db.createVectorCollection('embeddings', 2, distance: 'euclidean');
+    final near = db.insertVector('embeddings', [1.0, 0.0], metadata: {'title': 'near'});
+    final far = db.insertVector('embeddings', [0.0, 5.0]);
+
+    final hits = db.vectorSearch('embeddings', [0.9, 0.1], k: 1);
+    expect(hits.single.entry.id, near);
+    expect(hits.single.entry.metadata, {'title': 'near'});
+    expect(hits.single.rank, 0);
+
+    expect(db.getVector('embeddings', far)!.embedding, [0.0, 5.0]);
+    expect(db.deleteVector('embeddings', far), isTrue);
+    expect(db.getVector('embeddings', far), isNull);
+    expect(db.vectorCollections(), {'embeddings': 1});
+    expect(() => db.insertVector('embeddings', [1.0]), throwsA(isA<KeraDBException>()));
+    expect(() => db.createVectorCollection('bad', 2, distance: 'hamming'),
+        throwsA(isA<KeraDBException>().having((e) => e.code, 'code', KeraDBErrorCode.invalidQuery)));
+    db.close();
+  });
+}
@@ -0,0 +1,146 @@
+"""KeraDB as a LangChain vector store
+
+``KeraDBVectorStore`` keeps LangChain documents in a KeraDB vector
+collection, using the ``keradb-py`` bindings::
+
+    import keradb
+    from langchain_keradb import KeraDBVectorStore
+
+    db = keradb.open("rag.ndb")
+    store = KeraDBVectorStore(db, "docs", embeddings)
+    store.add_texts(["Refunds take 5 days"], metadatas=[{"source": "handbook.pdf"}])
+    store.similarity_search("How do refunds work?", k=4, filter={"source": {"eq": "handbook.pdf"}})
+    retriever = store.as_retriever()
+
+Each document is one vector, whose metadata holds the document's metadata
+and its text (under ``page_content``, see ``text_key``). Filters take the
+same conditions as ``Database.find``. IDs are the vector IDs KeraDB assigns,
+as strings; IDs passed to ``add_texts`` are ignored.
+"""
+
+from __future__ import annotations
+
+from typing import Any, Iterable, List, Optional, Sequence, Tuple, Type
+
+import keradb
+from langchain_core.documents import Document
+from langchain_core.embeddings import Embeddings
+from langchain_core.vectorstores import VectorStore
+
+__all__ = ["KeraDBVectorStore"]
+
+
+class KeraDBVectorStore(VectorStore):
+    """A LangChain vector store over a KeraDB vector collection
+
+    The collection is created on the first insert, sized to the embeddings,
+    with ``distance`` (``cosine`` by default). Scores from
+    ``similarity_search_with_score`` are KeraDB distances, lower being
+    nearer; ``similarity_search_with_relevance_scores`` maps them to 0-1.
+    """
+
+    def __init__(
+        self,
+        db: keradb.Database,
+        collection: str,
+        embedding: Embeddings,
+        *,
+        text_key: str = "page_content",
+        distance: str = "cosine",
+    ) -> None:
+        self.db = db
+        self.collection = collection
+        self._embedding = embedding
+        self.text_key = text_key
+        self.distance = distance
+
+    @property
+    def embeddings(self) -> Embeddings:
+        return self._embedding
+
+    def add_texts(
+        self,
+        texts: Iterable[str],
+        metadatas: Optional[List[dict]] = None,
+        *,
+        ids: Optional[List[str]] = None,
+        **kwargs: Any,
+    ) -> List[str]:
+        texts = list(texts)
+        if not texts:
+            return []
+        metadatas = metadatas or [{} for _ in texts]
+        if len(metadatas) != len(texts):
+            raise ValueError(f"Got {len(texts)} texts but {len(metadatas)} metadatas")
+        vectors = self._embedding.embed_documents(texts)
+        self._ensure_collection(len(vectors[0]))
+        metadata = [{**meta, self.text_key: text} for text, meta in zip(texts, metadatas)]
+        return [str(id) for id in self.db.insert_vectors(self.collection, vectors, metadata=metadata)]
+
+    def delete(self, ids: Optional[List[str]] = None, **kwargs: Any) -> Optional[bool]:
+        """Delete documents by ID; returns whether any existed"""
+        if ids is None:
+            return False
+        return any([self.db.delete_vector(self.collection, int(id)) for id in ids])
+
+    def get_by_ids(self, ids: Sequence[str], /) -> List[Document]:
+        docs = []
+        for id in ids:
+            entry = self.db.get_vector(self.collection, int(id))
+            if entry is not None:
+                docs.append(self._document(entry))
+        return docs
+
+    def similarity_search(
+        self, query: str, k: int = 4, filter: Optional[dict] = None, **kwargs: Any
+    ) -> List[Document]:
+        return [doc for doc, _ in self.similarity_search_with_score(query, k, filter=filter)]
+
+    def similarity_search_with_score(
+        self, query: str, k: int = 4, filter: Optional[dict] = None, **kwargs: Any
+    ) -> List[Tuple[Document, float]]:
+        return self._search(self._embedding.embed_query(query), k, filter)
+
+    def similarity_search_by_vector(
+        self, embedding: List[float], k: int = 4, filter: Optional[dict] = None, **kwargs: Any
+    ) -> List[Document]:
+        return [doc for doc, _ in self._search(embedding, k, filter)]
+
+    @classmethod
+    def from_texts(
+        cls: Type[KeraDBVectorStore],
+        texts: List[str],
+        embedding: Embeddings,
+        metadatas: Optional[List[dict]] = None,
+        *,
+        db: keradb.Database,
+        collection: str = "langchain",
+        **kwargs: Any,
+    ) -> KeraDBVectorStore:
+        store = cls(db, collection, embedding, **kwargs)
+        store.add_texts(texts, metadatas)
+        return store
+
+    def _select_relevance_score_fn(self):
+        if self.distance == "cosine":
+            return self._cosine_relevance_score_fn
+        if self.distance in ("euclidean", "l2"):
+            return self._euclidean_relevance_score_fn
+        if self.distance in ("dot", "dot_product", "inner"):
+            return self._max_inner_product_relevance_score_fn
+        raise ValueError(f"No relevance score for {self.distance} distance")
+
+    def _ensure_collection(self, dimensions: int) -> None:
+        if all(name != self.collection for name, _ in self.db.vector_collections()):
+            self.db.create_vector_collection(self.collection, dimensions, distance=self.distance)
+
+    def _search(self, embedding: List[float], k: int, filter: Optional[dict]) -> List[Tuple[Document, float]]:
+        if all(name != self.collection for name, _ in self.db.vector_collections()):
+            return []
+        hits = self.db.vector_search(self.collection, embedding, k=k, filter=filter)
+        return [(self._document(hit), hit["score"]) for hit in hits]
+
+    def _document(self, entry: dict) -> Document:
+        metadata = dict(entry["metadata"] or {})
+        text = metadata.pop(self.text_key, "")
+        return Document(page_content=text, metadata=metadata, id=str(entry["id"]))
@@ -0,0 +1,43 @@
+import keradb
+from langchain_core.embeddings import Embeddings
+
+from langchain_keradb import KeraDBVectorStore
+
+
+class WordEmbeddings(Embeddings):
+    """Counts of a few words, so related texts land near each other"""
+
+    def embed_documents(self, texts):
+        return [self.embed_query(text) for text in texts]
+
+    def embed_query(self, text):
+        return [text.count(word) + 0.01 for word in ("cat", "dog", "rust", "database")]
+
+
+def test_add_and_search(tmp_path):
+    with keradb.create(str(tmp_path / "rag.ndb")) as db:
+        store = KeraDBVectorStore(db, "docs", WordEmbeddings())
+        assert store.similarity_search("cat") == []
+
+        ids = store.add_texts(
+            ["the cat sat on the cat mat", "a dog chased a dog", "rust database internals"],
+            metadatas=[{"topic": "pets"}, {"topic": "pets"}, {"topic": "tech"}],
+        )
+        assert db.vector_collections() == [("docs", 3)]
+
+        [hit] = store.similarity_search("my cat", k=1)
+        assert hit.page_content == "the cat sat on the cat mat"
+        assert hit.metadata == {"topic": "pets"}
+        assert hit.id == ids[0]
+
+        [(_, relevance)] = store.similarity_search_with_relevance_scores("my cat", k=1)
+        assert relevance > 0.9
+
+        hits = store.similarity_search("my cat", k=3, filter={"topic": {"eq": "tech"}})
+        assert [doc.page_content for doc in hits] == ["rust database internals"]
+
+        retriever = store.as_retriever(search_kwargs={"k": 2})
+        assert len(retriever.invoke("dog")) == 2
+
+        assert store.delete([ids[0]])
+        assert store.get_by_ids(ids)[0].page_content == "a dog chased a dog"
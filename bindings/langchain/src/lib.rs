@@ -0,0 +1,245 @@
+//! KeraDB as a langchain-rust vector store
+//!
+//! [`KeraDBStore`] keeps LangChain documents in a KeraDB vector collection,
+//! so a database file can back a retriever in an existing RAG chain:
+//!
+//! ```ignore
+//! let db = Arc::new(Database::open("rag.ndb")?);
+//! let store = KeraDBStore::new(db, "docs", Arc::new(OpenAiEmbedder::default()));
+//!
+//! store.add_documents(&documents, &VecStoreOptions::default()).await?;
+//!
+//! let opt = VecStoreOptions::default().with_filters(json!({"source": {"eq": "handbook.pdf"}}));
+//! let hits = store.similarity_search("How do refunds work?", 4, &opt).await?;
+//! ```
+//!
+//! Each document is stored as one vector, with its metadata and its
+//! `page_content` (under `page_content`, see [`KeraDBStore::with_text_key`])
+//! as the vector's metadata. Filters take the same conditions as the REST
+//! server. The collection is created on the first insert, sized to the
+//! embedder's output.
+
+use async_trait::async_trait;
+use keradb::{Database, Distance, KeraDBError, MetadataFilter, VectorId, VectorSearchResult};
+use langchain_rust::embedding::Embedder;
+use langchain_rust::schemas::Document;
+use langchain_rust::vectorstore::{VecStoreOptions, VectorStore};
+use serde_json::{Map, Value};
+use std::error::Error;
+use std::sync::Arc;
+
+/// A LangChain vector store over a KeraDB vector collection
+///
+/// `opt.name_space` picks another collection for a call, and
+/// `opt.embedder` another embedder. Search results are scored `1 -
+/// distance`, which for the default cosine distance is the cosine
+/// similarity; `opt.score_threshold` drops results scoring below it.
+pub struct KeraDBStore {
+    db: Arc<Database>,
+    collection: String,
+    embedder: Arc<dyn Embedder>,
+    text_key: String,
+    distance: Distance,
+}
+
+impl KeraDBStore {
+    /// A store over `collection` in `db`, embedding with `embedder`
+    pub fn new(db: Arc<Database>, collection: &str, embedder: Arc<dyn Embedder>) -> Self {
+        Self {
+            db,
+            collection: collection.to_string(),
+            embedder,
+            text_key: "page_content".to_string(),
+            distance: Distance::Cosine,
+        }
+    }
+
+    /// Store `page_content` under this metadata key instead
+    pub fn with_text_key(mut self, key: &str) -> Self {
+        self.text_key = key.to_string();
+        self
+    }
+
+    /// Set the distance metric used when the store creates its collection
+    pub fn with_distance(mut self, distance: Distance) -> Self {
+        self.distance = distance;
+        self
+    }
+
+    /// The database the store writes to
+    pub fn database(&self) -> &Arc<Database> {
+        &self.db
+    }
+
+    /// Delete documents by the IDs [`VectorStore::add_documents`] returned;
+    /// returns how many existed
+    pub fn delete(&self, ids: &[String], opt: &VecStoreOptions) -> Result<usize, Box<dyn Error>> {
+        let collection = self.collection_name(opt);
+        let mut deleted = 0;
+        for id in ids {
+            let id: VectorId = id.parse().map_err(|_| format!("Invalid document ID: {}", id))?;
+            if self.db.delete_vector(collection, id)? {
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+
+    fn collection_name<'a>(&'a self, opt: &'a VecStoreOptions) -> &'a str {
+        opt.name_space.as_deref().unwrap_or(&self.collection)
+    }
+
+    fn embedder<'a>(&'a self, opt: &'a VecStoreOptions) -> &'a Arc<dyn Embedder> {
+        opt.embedder.as_ref().unwrap_or(&self.embedder)
+    }
+
+    fn ensure_collection(&self, name: &str, dimensions: usize) -> keradb::error::Result<()> {
+        let config = self.db.config().vector_config(dimensions).with_distance(self.distance);
+        match self.db.create_vector_collection(name, config) {
+            Err(KeraDBError::CollectionExists(_)) => Ok(()),
+            result => result,
+        }
+    }
+
+    fn document(&self, result: VectorSearchResult) -> Document {
+        let mut metadata = match result.document.metadata {
+            Value::Object(map) => map,
+            _ => Map::new(),
+        };
+        let text = match metadata.remove(&self.text_key) {
+            Some(Value::String(text)) => text,
+            _ => String::new(),
+        };
+        let mut doc = Document::new(text).with_metadata(metadata.into_iter().collect());
+        doc.score = 1.0 - result.score as f64;
+        doc
+    }
+}
+
+/// A filter like `{"source": {"eq": "handbook.pdf"}}`, as the REST server
+/// takes
+fn to_filter(filters: Option<&Value>) -> Result<MetadataFilter, Box<dyn Error>> {
+    match filters {
+        None | Some(Value::Null) => Ok(MetadataFilter::new()),
+        Some(filters) => Ok(MetadataFilter {
+            filters: serde_json::from_value(filters.clone()).map_err(|e| format!("Invalid filter: {}", e))?,
+        }),
+    }
+}
+
+fn to_f32(vector: &[f64]) -> Vec<f32> {
+    vector.iter().map(|&x| x as f32).collect()
+}
+
+#[async_trait]
+impl VectorStore for KeraDBStore {
+    async fn add_documents(&self, docs: &[Document], opt: &VecStoreOptions) -> Result<Vec<String>, Box<dyn Error>> {
+        let texts: Vec<String> = docs.iter().map(|doc| doc.page_content.clone()).collect();
+        let vectors = self.embedder(opt).embed_documents(&texts).await?;
+        if vectors.len() != docs.len() {
+            return Err(format!("Embedder returned {} vectors for {} documents", vectors.len(), docs.len()).into());
+        }
+
+        let collection = self.collection_name(opt);
+        if let Some(vector) = vectors.first() {
+            self.ensure_collection(collection, vector.len())?;
+        }
+        let mut ids = Vec::with_capacity(docs.len());
+        for (doc, vector) in docs.iter().zip(vectors) {
+            let mut metadata: Map<String, Value> = doc.metadata.clone().into_iter().collect();
+            metadata.insert(self.text_key.clone(), Value::String(doc.page_content.clone()));
+            let id = self.db.insert_vector(collection, to_f32(&vector), Some(Value::Object(metadata)))?;
+            ids.push(id.to_string());
+        }
+        Ok(ids)
+    }
+
+    async fn similarity_search(
+        &self,
+        query: &str,
+        limit: usize,
+        opt: &VecStoreOptions,
+    ) -> Result<Vec<Document>, Box<dyn Error>> {
+        let filter = to_filter(opt.filters.as_ref())?;
+        let query = to_f32(&self.embedder(opt).embed_query(query).await?);
+        let collection = self.collection_name(opt);
+        let results = if filter.filters.is_empty() {
+            self.db.vector_search(collection, &query, limit)
+        } else {
+            self.db.vector_search_filtered(collection, &query, limit, &filter)
+        };
+        let results = match results {
+            // Nothing has been added yet
+            Err(KeraDBError::CollectionNotFound(_)) => Vec::new(),
+            results => results?,
+        };
+        Ok(results
+            .into_iter()
+            .map(|result| self.document(result))
+            .filter(|doc| opt.score_threshold.map_or(true, |threshold| doc.score >= threshold as f64))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use langchain_rust::embedding::EmbedderError;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    /// Counts of a few words, so related texts land near each other
+    struct WordEmbedder;
+
+    #[async_trait]
+    impl Embedder for WordEmbedder {
+        async fn embed_documents(&self, documents: &[String]) -> Result<Vec<Vec<f64>>, EmbedderError> {
+            Ok(documents.iter().map(|doc| embed(doc)).collect())
+        }
+
+        async fn embed_query(&self, text: &str) -> Result<Vec<f64>, EmbedderError> {
+            Ok(embed(text))
+        }
+    }
+
+    fn embed(text: &str) -> Vec<f64> {
+        ["cat", "dog", "rust", "database"]
+            .iter()
+            .map(|word| text.matches(word).count() as f64 + 0.01)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_add_and_search() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(Database::create(dir.path().join("rag.ndb")).unwrap());
+        let store = KeraDBStore::new(db.clone(), "docs", Arc::new(WordEmbedder));
+        let opt = VecStoreOptions::default();
+
+        assert!(store.similarity_search("cat", 2, &opt).await.unwrap().is_empty());
+
+        let docs = [
+            ("the cat sat on the cat mat", "pets"),
+            ("a dog chased a dog", "pets"),
+            ("rust database internals", "tech"),
+        ]
+        .map(|(text, topic)| Document::new(text).with_metadata(HashMap::from([("topic".to_string(), json!(topic))])));
+        let ids = store.add_documents(&docs, &opt).await.unwrap();
+        assert_eq!(ids.len(), 3);
+        assert_eq!(db.list_vector_collections(), vec![("docs".to_string(), 3)]);
+
+        let hits = store.similarity_search("my cat", 1, &opt).await.unwrap();
+        assert_eq!(hits[0].page_content, "the cat sat on the cat mat");
+        assert_eq!(hits[0].metadata, HashMap::from([("topic".to_string(), json!("pets"))]));
+        assert!(hits[0].score > 0.9);
+
+        let opt = VecStoreOptions::default().with_filters(json!({"topic": {"eq": "tech"}}));
+        let hits = store.similarity_search("my cat", 3, &opt).await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].page_content, "rust database internals");
+
+        assert_eq!(store.delete(&ids[..1], &VecStoreOptions::default()).unwrap(), 1);
+        let hits = store.similarity_search("my cat", 1, &VecStoreOptions::default()).await.unwrap();
+        assert_ne!(hits[0].page_content, "the cat sat on the cat mat");
+    }
+}
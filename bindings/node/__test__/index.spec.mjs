@@ -0,0 +1,57 @@
+import assert from 'node:assert/strict'
+import { mkdtempSync } from 'node:fs'
+import { tmpdir } from 'node:os'
+import { join } from 'node:path'
+import test from 'node:test'
+
+import keradb from '../index.js'
+
+const scratch = (name) => join(mkdtempSync(join(tmpdir(), 'keradb-')), name)
+
+test('documents', async () => {
+  const path = scratch('test.ndb')
+  const db = await keradb.create(path)
+  const alice = await db.insert('users', { name: 'alice', age: 30, tags: ['a', 'b'] })
+  await db.insertMany('users', [{ name: 'bob', age: 17 }, { name: 'carol', age: 42 }])
+
+  assert.deepEqual(await db.findById('users', alice), { _id: alice, name: 'alice', age: 30, tags: ['a', 'b'] })
+  assert.deepEqual((await db.find('users', { age: { gte: 21 } })).map((d) => d.name), ['alice', 'carol'])
+  assert.deepEqual((await db.find('users', null, { limit: 1, skip: 1 })).map((d) => d.name), ['bob'])
+  assert.equal(await db.count('users', { name: { starts_with: 'c' } }), 1)
+  assert.equal((await db.update('users', alice, { name: 'alice', age: 31 })).age, 31)
+  assert.equal((await db.delete('users', alice)).name, 'alice')
+  assert.deepEqual(db.collections(), [{ name: 'users', count: 2 }])
+
+  await assert.rejects(db.findById('users', alice))
+  await assert.rejects(db.find('users', { age: { bogus: 1 } }), { code: 'InvalidArg' })
+
+  await db.close()
+  await assert.rejects(db.count('users'), /closed/)
+  const reader = await keradb.open(path, { readOnly: true })
+  assert.equal(await reader.count('users'), 2)
+  await reader.close()
+})
+
+test('vectors', async () => {
+  const db = await keradb.create(scratch('vectors.ndb'))
+  db.createVectorCollection('embeddings', 4, 'euclidean')
+  const rows = [0, 1, 2, 3].map((i) => Float32Array.from({ length: 4 }, (_, j) => (i === j ? 1 : 0)))
+  const ids = await db.insertVectors('embeddings', rows, rows.map((_, n) => ({ n })))
+  const extra = await db.insertVector('embeddings', Buffer.from(new Float32Array([0.9, 0.1, 0, 0]).buffer), { n: 4 })
+
+  const hits = await db.vectorSearch('embeddings', [1, 0, 0, 0], 2)
+  assert.deepEqual(hits.map((h) => [h.id, h.rank]), [[ids[0], 0], [extra, 1]])
+  assert.ok(hits[0].embedding instanceof Float32Array)
+
+  const filtered = await db.vectorSearch('embeddings', rows[0], 2, { n: { gt: 2 } })
+  assert.deepEqual(filtered.map((h) => h.metadata.n), [4, 3])
+
+  assert.deepEqual(db.getVector('embeddings', extra).metadata, { n: 4 })
+  assert.ok(db.deleteVector('embeddings', extra))
+  assert.equal(db.getVector('embeddings', extra), null)
+  assert.deepEqual(db.vectorCollections(), [{ name: 'embeddings', count: 4 }])
+
+  await assert.rejects(db.insertVector('embeddings', Buffer.alloc(3)), { code: 'InvalidArg' })
+  assert.throws(() => db.createVectorCollection('bad', 4, 'hamming'))
+  await db.close()
+})
@@ -0,0 +1,375 @@
+//! Node.js bindings for KeraDB
+//!
+//! Built with napi-rs into the `keradb` npm package, for server-side Node
+//! and Electron apps. Every database call returns a promise and runs on the
+//! blocking thread pool, so scans, bulk inserts and searches never stall the
+//! event loop (or an Electron main process).
+//!
+//! Documents and metadata are plain JS values, converted through
+//! `serde_json::Value`. Vectors can be passed as a `Float32Array`, a
+//! `Buffer` of little-endian `f32`s, or an array of numbers, and come back
+//! as `Float32Array`s.
+
+use keradb::vector::FilterCondition;
+use keradb::{Database as Inner, Distance, KeraDBError, MetadataFilter, VectorDocument};
+use napi::bindgen_prelude::{Buffer, Either3, Float32Array};
+use napi::{Error, Result, Status};
+use napi_derive::napi;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// A vector as JS passes it
+type VectorInput = Either3<Float32Array, Buffer, Vec<f64>>;
+
+/// Reject with a KeraDB error; the status becomes the JS error's `code`
+fn to_js_err(err: KeraDBError) -> Error {
+    let status = match err {
+        KeraDBError::InvalidQuery(_) | KeraDBError::InvalidDocument(_) | KeraDBError::ParseError(_) => {
+            Status::InvalidArg
+        }
+        _ => Status::GenericFailure,
+    };
+    Error::new(status, err.to_string())
+}
+
+/// Run a database call on the blocking pool
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> keradb::error::Result<T> + Send + 'static) -> Result<T> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| Error::from_reason(format!("KeraDB task failed: {}", e)))?
+        .map_err(to_js_err)
+}
+
+fn to_embedding(input: VectorInput) -> Result<Vec<f32>> {
+    match input {
+        Either3::A(array) => Ok(array.to_vec()),
+        Either3::B(buffer) => {
+            if buffer.len() % 4 != 0 {
+                return Err(Error::new(
+                    Status::InvalidArg,
+                    format!("A vector Buffer holds 4-byte floats, but this one is {} bytes", buffer.len()),
+                ));
+            }
+            Ok(buffer.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect())
+        }
+        Either3::C(values) => Ok(values.into_iter().map(|x| x as f32).collect()),
+    }
+}
+
+/// A filter like `{ age: { gte: 21 } }`, as the REST server takes
+fn to_filter(filter: Option<Value>) -> Result<MetadataFilter> {
+    let Some(filter) = filter else { return Ok(MetadataFilter::new()) };
+    let filters: HashMap<String, FilterCondition> = serde_json::from_value(filter)
+        .map_err(|e| Error::new(Status::InvalidArg, format!("Invalid filter: {}", e)))?;
+    Ok(MetadataFilter { filters })
+}
+
+fn to_distance(name: Option<String>) -> Result<Distance> {
+    match name {
+        None => Ok(Distance::Cosine),
+        Some(name) => Distance::from_name(&name)
+            .ok_or_else(|| Error::new(Status::InvalidArg, format!("Unknown distance metric: {}", name))),
+    }
+}
+
+#[napi(object)]
+pub struct OpenOptions {
+    /// Open without taking the write lock; writes fail
+    pub read_only: Option<bool>,
+}
+
+#[napi(object)]
+pub struct FindOptions {
+    pub limit: Option<u32>,
+    pub skip: Option<u32>,
+}
+
+/// A document collection and how many documents it holds
+#[napi(object)]
+pub struct CollectionInfo {
+    pub name: String,
+    pub count: u32,
+}
+
+/// A stored vector
+#[napi(object)]
+pub struct VectorEntry {
+    pub id: i64,
+    pub external_id: Option<String>,
+    pub metadata: Value,
+    pub embedding: Option<Float32Array>,
+}
+
+/// A vector search result; `rank` starts at 0 for the best match
+#[napi(object)]
+pub struct SearchHit {
+    pub id: i64,
+    pub score: f64,
+    pub rank: u32,
+    pub external_id: Option<String>,
+    pub metadata: Value,
+    pub embedding: Option<Float32Array>,
+}
+
+fn vector_entry(doc: VectorDocument) -> VectorEntry {
+    VectorEntry {
+        id: doc.id as i64,
+        external_id: doc.external_id,
+        metadata: doc.metadata,
+        embedding: doc.embedding.map(Float32Array::new),
+    }
+}
+
+fn collection_infos(collections: Vec<(String, usize)>) -> Vec<CollectionInfo> {
+    collections.into_iter().map(|(name, count)| CollectionInfo { name, count: count as u32 }).collect()
+}
+
+/// A KeraDB database file
+///
+/// Get one from `open(path)` or `create(path)`, and `close()` it to release
+/// the file lock.
+#[napi]
+pub struct Database {
+    inner: Mutex<Option<Arc<Inner>>>,
+}
+
+impl Database {
+    fn new(db: Inner) -> Self {
+        Self { inner: Mutex::new(Some(Arc::new(db))) }
+    }
+
+    fn db(&self) -> Result<Arc<Inner>> {
+        self.inner
+            .lock()
+            .map_err(|_| Error::from_reason("Database lock poisoned"))?
+            .clone()
+            .ok_or_else(|| Error::from_reason("Database is closed"))
+    }
+}
+
+#[napi]
+impl Database {
+    /// Insert a document and resolve to its ID
+    #[napi]
+    pub async fn insert(&self, collection: String, document: Value) -> Result<String> {
+        let db = self.db()?;
+        blocking(move || db.insert(&collection, document)).await
+    }
+
+    /// Insert several documents and resolve to their IDs
+    #[napi]
+    pub async fn insert_many(&self, collection: String, documents: Vec<Value>) -> Result<Vec<String>> {
+        let db = self.db()?;
+        blocking(move || documents.into_iter().map(|doc| db.insert(&collection, doc)).collect()).await
+    }
+
+    /// The document with this ID; rejects if there is none
+    #[napi]
+    pub async fn find_by_id(&self, collection: String, id: String) -> Result<Value> {
+        let db = self.db()?;
+        blocking(move || Ok(db.find_by_id(&collection, &id)?.to_value())).await
+    }
+
+    /// Documents in `collection` that match `filter`, in insertion order
+    ///
+    /// `filter` maps fields to conditions, e.g.
+    /// `{ age: { gte: 21 }, tags: { contains: "rust" } }`.
+    #[napi]
+    pub async fn find(&self, collection: String, filter: Option<Value>, options: Option<FindOptions>) -> Result<Vec<Value>> {
+        let (db, filter) = (self.db()?, to_filter(filter)?);
+        let limit = options.as_ref().and_then(|o| o.limit).map(|n| n as usize);
+        let skip = options.as_ref().and_then(|o| o.skip).unwrap_or(0) as usize;
+        blocking(move || {
+            if filter.filters.is_empty() {
+                return Ok(db.find_all(&collection, limit, Some(skip))?.iter().map(|d| d.to_value()).collect());
+            }
+            Ok(db
+                .find_all(&collection, None, None)?
+                .iter()
+                .map(|d| d.to_value())
+                .filter(|d| filter.matches(d))
+                .skip(skip)
+                .take(limit.unwrap_or(usize::MAX))
+                .collect())
+        })
+        .await
+    }
+
+    /// Replace a document's data and resolve to the updated document
+    #[napi]
+    pub async fn update(&self, collection: String, id: String, document: Value) -> Result<Value> {
+        let db = self.db()?;
+        blocking(move || Ok(db.update(&collection, &id, document)?.to_value())).await
+    }
+
+    /// Delete a document and resolve to it
+    #[napi]
+    pub async fn delete(&self, collection: String, id: String) -> Result<Value> {
+        let db = self.db()?;
+        blocking(move || Ok(db.delete(&collection, &id)?.to_value())).await
+    }
+
+    /// Number of documents in `collection`, or of those matching `filter`
+    #[napi]
+    pub async fn count(&self, collection: String, filter: Option<Value>) -> Result<u32> {
+        let (db, filter) = (self.db()?, to_filter(filter)?);
+        blocking(move || {
+            if filter.filters.is_empty() {
+                return Ok(db.count(&collection) as u32);
+            }
+            let docs = db.find_all(&collection, None, None)?;
+            Ok(docs.iter().filter(|d| filter.matches(&d.to_value())).count() as u32)
+        })
+        .await
+    }
+
+    #[napi]
+    pub fn collections(&self) -> Result<Vec<CollectionInfo>> {
+        Ok(collection_infos(self.db()?.list_collections()))
+    }
+
+    /// Create a vector collection
+    ///
+    /// `distance` is one of `cosine` (the default), `euclidean`,
+    /// `dot_product` or `manhattan`; the other settings come from the
+    /// database's config.
+    #[napi]
+    pub fn create_vector_collection(&self, name: String, dimensions: u32, distance: Option<String>) -> Result<()> {
+        let db = self.db()?;
+        let config = db.config().vector_config(dimensions as usize).with_distance(to_distance(distance)?);
+        db.create_vector_collection(&name, config).map_err(to_js_err)
+    }
+
+    /// Insert a vector, with optional metadata, and resolve to its ID
+    #[napi]
+    pub async fn insert_vector(&self, collection: String, vector: VectorInput, metadata: Option<Value>) -> Result<i64> {
+        let (db, vector) = (self.db()?, to_embedding(vector)?);
+        blocking(move || Ok(db.insert_vector(&collection, vector, metadata)? as i64)).await
+    }
+
+    /// Insert several vectors and resolve to their IDs; `metadata`, if
+    /// given, has one entry per vector
+    #[napi]
+    pub async fn insert_vectors(
+        &self,
+        collection: String,
+        vectors: Vec<VectorInput>,
+        metadata: Option<Vec<Value>>,
+    ) -> Result<Vec<i64>> {
+        let db = self.db()?;
+        let vectors = vectors.into_iter().map(to_embedding).collect::<Result<Vec<_>>>()?;
+        let metadata = match metadata {
+            Some(metadata) if metadata.len() != vectors.len() => {
+                return Err(Error::new(
+                    Status::InvalidArg,
+                    format!("Got {} vectors but {} metadata entries", vectors.len(), metadata.len()),
+                ))
+            }
+            Some(metadata) => metadata.into_iter().map(Some).collect(),
+            None => vec![None; vectors.len()],
+        };
+        blocking(move || {
+            vectors
+                .into_iter()
+                .zip(metadata)
+                .map(|(vector, metadata)| Ok(db.insert_vector(&collection, vector, metadata)? as i64))
+                .collect()
+        })
+        .await
+    }
+
+    /// The `k` nearest neighbours of `query` (10 by default), best first;
+    /// `filter` restricts results by metadata, as in `find`
+    #[napi]
+    pub async fn vector_search(
+        &self,
+        collection: String,
+        query: VectorInput,
+        k: Option<u32>,
+        filter: Option<Value>,
+    ) -> Result<Vec<SearchHit>> {
+        let (db, query, filter) = (self.db()?, to_embedding(query)?, to_filter(filter)?);
+        let k = k.unwrap_or(10) as usize;
+        let results = blocking(move || {
+            if filter.filters.is_empty() {
+                db.vector_search(&collection, &query, k)
+            } else {
+                db.vector_search_filtered(&collection, &query, k, &filter)
+            }
+        })
+        .await?;
+        Ok(results
+            .into_iter()
+            .map(|result| {
+                let entry = vector_entry(result.document);
+                SearchHit {
+                    id: entry.id,
+                    score: result.score as f64,
+                    rank: result.rank as u32,
+                    external_id: entry.external_id,
+                    metadata: entry.metadata,
+                    embedding: entry.embedding,
+                }
+            })
+            .collect())
+    }
+
+    /// The vector with this ID, or `null`
+    #[napi]
+    pub fn get_vector(&self, collection: String, id: i64) -> Result<Option<VectorEntry>> {
+        let doc = self.db()?.get_vector(&collection, id as u64).map_err(to_js_err)?;
+        Ok(doc.map(vector_entry))
+    }
+
+    /// Delete a vector; returns whether it existed
+    #[napi]
+    pub fn delete_vector(&self, collection: String, id: i64) -> Result<bool> {
+        self.db()?.delete_vector(&collection, id as u64).map_err(to_js_err)
+    }
+
+    #[napi]
+    pub fn vector_collections(&self) -> Result<Vec<CollectionInfo>> {
+        Ok(collection_infos(self.db()?.list_vector_collections()))
+    }
+
+    /// Drop a vector collection; returns whether it existed
+    #[napi]
+    pub fn drop_vector_collection(&self, name: String) -> Result<bool> {
+        self.db()?.drop_vector_collection(&name).map_err(to_js_err)
+    }
+
+    /// Flush everything to disk
+    #[napi]
+    pub async fn sync(&self) -> Result<()> {
+        let db = self.db()?;
+        blocking(move || db.sync()).await
+    }
+
+    /// Flush and close the database; later calls reject
+    ///
+    /// The file lock is released once calls already running finish.
+    #[napi]
+    pub async fn close(&self) -> Result<()> {
+        let db = self.inner.lock().map_err(|_| Error::from_reason("Database lock poisoned"))?.take();
+        match db {
+            Some(db) => blocking(move || db.sync()).await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Create a database file at `path`
+#[napi]
+pub async fn create(path: String) -> Result<Database> {
+    blocking(move || Inner::create(path)).await.map(Database::new)
+}
+
+/// Open an existing database file
+#[napi]
+pub async fn open(path: String, options: Option<OpenOptions>) -> Result<Database> {
+    let read_only = options.and_then(|o| o.read_only).unwrap_or(false);
+    blocking(move || if read_only { Inner::open_read_only(path) } else { Inner::open(path) })
+        .await
+        .map(Database::new)
+}
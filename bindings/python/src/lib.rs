@@ -0,0 +1,431 @@
+//! Python bindings for KeraDB
+//!
+//! Built with maturin into the `keradb` Python module, published as
+//! `keradb-py`. Documents and metadata cross into Python as dicts, lists and
+//! scalars by way of `serde_json::Value`. Embeddings can be passed as lists
+//! of floats or as numpy arrays (`float32` is used as is, `float64` is
+//! converted), and they come back as `float32` numpy arrays.
+//!
+//! Database calls release the GIL while they run, so other Python threads
+//! keep going during long scans, bulk inserts and searches.
+
+use keradb::vector::FilterCondition;
+use keradb::{Database as Inner, Distance, KeraDBError, MetadataFilter, VectorDocument, VectorId};
+use numpy::{PyArray1, PyArray2, PyArrayMethods};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyKeyError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyInt, PyList, PyString, PyTuple};
+use serde_json::{Map, Number, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+create_exception!(keradb, Error, PyException, "A KeraDB operation failed");
+
+/// Raise a KeraDB error as the closest Python exception
+fn to_py_err(err: KeraDBError) -> PyErr {
+    match err {
+        KeraDBError::DocumentNotFound(_) | KeraDBError::CollectionNotFound(_) | KeraDBError::NotFound(_) => {
+            PyKeyError::new_err(err.to_string())
+        }
+        KeraDBError::InvalidQuery(_) | KeraDBError::InvalidDocument(_) | KeraDBError::ParseError(_) => {
+            PyValueError::new_err(err.to_string())
+        }
+        _ => Error::new_err(err.to_string()),
+    }
+}
+
+/// Convert a Python value to JSON
+///
+/// numpy arrays and scalars are converted through their `tolist()`.
+fn to_json(obj: &Bound<'_, PyAny>) -> PyResult<Value> {
+    if obj.is_none() {
+        return Ok(Value::Null);
+    }
+    // bool is a subclass of int, so it has to be checked first
+    if let Ok(b) = obj.downcast::<PyBool>() {
+        return Ok(Value::Bool(b.is_true()));
+    }
+    if obj.is_instance_of::<PyInt>() {
+        if let Ok(i) = obj.extract::<i64>() {
+            return Ok(i.into());
+        }
+        return obj
+            .extract::<u64>()
+            .map(Value::from)
+            .map_err(|_| PyValueError::new_err("Integer is too large to store"));
+    }
+    if let Ok(f) = obj.downcast::<PyFloat>() {
+        return Number::from_f64(f.value())
+            .map(Value::Number)
+            .ok_or_else(|| PyValueError::new_err("NaN and infinity cannot be stored"));
+    }
+    if obj.is_instance_of::<PyString>() {
+        return Ok(Value::String(obj.extract()?));
+    }
+    if let Ok(dict) = obj.downcast::<PyDict>() {
+        let mut map = Map::new();
+        for (key, value) in dict.iter() {
+            let key: String = key.extract().map_err(|_| PyTypeError::new_err("Keys must be strings"))?;
+            map.insert(key, to_json(&value)?);
+        }
+        return Ok(Value::Object(map));
+    }
+    if obj.is_instance_of::<PyList>() || obj.is_instance_of::<PyTuple>() {
+        return obj.iter()?.map(|item| to_json(&item?)).collect::<PyResult<_>>().map(Value::Array);
+    }
+    if obj.hasattr("tolist")? {
+        return to_json(&obj.call_method0("tolist")?);
+    }
+    Err(PyTypeError::new_err(format!("Cannot store a value of {}", obj.get_type())))
+}
+
+/// Convert JSON to a Python value
+fn to_py(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
+    Ok(match value {
+        Value::Null => py.None(),
+        Value::Bool(b) => (*b).into_py(py),
+        Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => i.into_py(py),
+            (None, Some(u)) => u.into_py(py),
+            _ => n.as_f64().unwrap_or(f64::NAN).into_py(py),
+        },
+        Value::String(s) => s.as_str().into_py(py),
+        Value::Array(items) => {
+            let items = items.iter().map(|item| to_py(py, item)).collect::<PyResult<Vec<_>>>()?;
+            PyList::new_bound(py, items).into_py(py)
+        }
+        Value::Object(map) => {
+            let dict = PyDict::new_bound(py);
+            for (key, item) in map {
+                dict.set_item(key, to_py(py, item)?)?;
+            }
+            dict.into_py(py)
+        }
+    })
+}
+
+/// A filter like `{"age": {"gte": 21}}`, as the REST server takes
+fn to_filter(filter: Option<&Bound<'_, PyAny>>) -> PyResult<MetadataFilter> {
+    let Some(filter) = filter else { return Ok(MetadataFilter::new()) };
+    let filters: HashMap<String, FilterCondition> = serde_json::from_value(to_json(filter)?)
+        .map_err(|e| PyValueError::new_err(format!("Invalid filter: {}", e)))?;
+    Ok(MetadataFilter { filters })
+}
+
+/// One embedding, from a 1-D numpy array or a sequence of floats
+fn embedding(obj: &Bound<'_, PyAny>) -> PyResult<Vec<f32>> {
+    if let Ok(array) = obj.downcast::<PyArray1<f32>>() {
+        return Ok(array.readonly().as_array().to_vec());
+    }
+    if let Ok(array) = obj.downcast::<PyArray1<f64>>() {
+        return Ok(array.readonly().as_array().iter().map(|&x| x as f32).collect());
+    }
+    obj.extract()
+}
+
+/// Several embeddings, from a 2-D numpy array (one per row) or a sequence
+fn embeddings(obj: &Bound<'_, PyAny>) -> PyResult<Vec<Vec<f32>>> {
+    if let Ok(array) = obj.downcast::<PyArray2<f32>>() {
+        return Ok(array.readonly().as_array().rows().into_iter().map(|row| row.to_vec()).collect());
+    }
+    if let Ok(array) = obj.downcast::<PyArray2<f64>>() {
+        let array = array.readonly();
+        return Ok(array.as_array().rows().into_iter().map(|row| row.iter().map(|&x| x as f32).collect()).collect());
+    }
+    obj.iter()?.map(|row| embedding(&row?)).collect()
+}
+
+fn vector_document<'py>(py: Python<'py>, doc: &VectorDocument) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new_bound(py);
+    dict.set_item("id", doc.id)?;
+    dict.set_item("external_id", &doc.external_id)?;
+    dict.set_item("metadata", to_py(py, &doc.metadata)?)?;
+    dict.set_item("embedding", doc.embedding.clone().map(|e| PyArray1::from_vec_bound(py, e)))?;
+    Ok(dict)
+}
+
+/// A KeraDB database file
+///
+/// Open one with `keradb.open(path)` or create one with
+/// `keradb.create(path)`. Use it as a context manager, or call `close()`,
+/// to release the file lock.
+#[pyclass(module = "keradb")]
+struct Database {
+    inner: Option<Inner>,
+}
+
+impl Database {
+    fn db(&self) -> PyResult<&Inner> {
+        self.inner.as_ref().ok_or_else(|| Error::new_err("Database is closed"))
+    }
+}
+
+#[pymethods]
+impl Database {
+    /// Create a database file at `path`
+    #[staticmethod]
+    fn create(py: Python<'_>, path: PathBuf) -> PyResult<Self> {
+        let db = py.allow_threads(|| Inner::create(path)).map_err(to_py_err)?;
+        Ok(Self { inner: Some(db) })
+    }
+
+    /// Open an existing database file
+    #[staticmethod]
+    #[pyo3(signature = (path, read_only = false))]
+    fn open(py: Python<'_>, path: PathBuf, read_only: bool) -> PyResult<Self> {
+        let db = py
+            .allow_threads(|| if read_only { Inner::open_read_only(path) } else { Inner::open(path) })
+            .map_err(to_py_err)?;
+        Ok(Self { inner: Some(db) })
+    }
+
+    /// Insert a document (a dict) and return its ID
+    fn insert(&self, py: Python<'_>, collection: &str, document: &Bound<'_, PyAny>) -> PyResult<String> {
+        let (db, data) = (self.db()?, to_json(document)?);
+        py.allow_threads(|| db.insert(collection, data)).map_err(to_py_err)
+    }
+
+    /// Insert several documents and return their IDs
+    fn insert_many(&self, py: Python<'_>, collection: &str, documents: &Bound<'_, PyAny>) -> PyResult<Vec<String>> {
+        let db = self.db()?;
+        let documents = documents.iter()?.map(|doc| to_json(&doc?)).collect::<PyResult<Vec<_>>>()?;
+        py.allow_threads(|| documents.into_iter().map(|data| db.insert(collection, data)).collect::<Result<_, _>>())
+            .map_err(to_py_err)
+    }
+
+    /// The document with this ID; raises `KeyError` if there is none
+    fn find_by_id(&self, py: Python<'_>, collection: &str, id: &str) -> PyResult<PyObject> {
+        let db = self.db()?;
+        let doc = py.allow_threads(|| db.find_by_id(collection, id)).map_err(to_py_err)?;
+        to_py(py, &doc.to_value())
+    }
+
+    /// Documents in `collection` that match `filter`, in insertion order
+    ///
+    /// `filter` maps fields to conditions, e.g.
+    /// `{"age": {"gte": 21}, "tags": {"contains": "rust"}}`.
+    #[pyo3(signature = (collection, filter = None, limit = None, skip = 0))]
+    fn find(
+        &self,
+        py: Python<'_>,
+        collection: &str,
+        filter: Option<&Bound<'_, PyAny>>,
+        limit: Option<usize>,
+        skip: usize,
+    ) -> PyResult<Vec<PyObject>> {
+        let (db, filter) = (self.db()?, to_filter(filter)?);
+        let docs = py
+            .allow_threads(|| -> keradb::error::Result<Vec<Value>> {
+                if filter.filters.is_empty() {
+                    return Ok(db.find_all(collection, limit, Some(skip))?.iter().map(|d| d.to_value()).collect());
+                }
+                Ok(db
+                    .find_all(collection, None, None)?
+                    .iter()
+                    .map(|d| d.to_value())
+                    .filter(|d| filter.matches(d))
+                    .skip(skip)
+                    .take(limit.unwrap_or(usize::MAX))
+                    .collect())
+            })
+            .map_err(to_py_err)?;
+        docs.iter().map(|doc| to_py(py, doc)).collect()
+    }
+
+    /// Replace a document's data and return the updated document
+    fn update(&self, py: Python<'_>, collection: &str, id: &str, document: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        let (db, data) = (self.db()?, to_json(document)?);
+        let doc = py.allow_threads(|| db.update(collection, id, data)).map_err(to_py_err)?;
+        to_py(py, &doc.to_value())
+    }
+
+    /// Delete a document and return it
+    fn delete(&self, py: Python<'_>, collection: &str, id: &str) -> PyResult<PyObject> {
+        let db = self.db()?;
+        let doc = py.allow_threads(|| db.delete(collection, id)).map_err(to_py_err)?;
+        to_py(py, &doc.to_value())
+    }
+
+    /// Number of documents in `collection`, or of those matching `filter`
+    #[pyo3(signature = (collection, filter = None))]
+    fn count(&self, py: Python<'_>, collection: &str, filter: Option<&Bound<'_, PyAny>>) -> PyResult<usize> {
+        let (db, filter) = (self.db()?, to_filter(filter)?);
+        if filter.filters.is_empty() {
+            return Ok(db.count(collection));
+        }
+        py.allow_threads(|| -> keradb::error::Result<usize> {
+            Ok(db.find_all(collection, None, None)?.iter().filter(|d| filter.matches(&d.to_value())).count())
+        })
+        .map_err(to_py_err)
+    }
+
+    /// Document collections, as `(name, count)` pairs
+    fn collections(&self) -> PyResult<Vec<(String, usize)>> {
+        Ok(self.db()?.list_collections())
+    }
+
+    /// Create a vector collection
+    ///
+    /// `distance` is one of `cosine`, `euclidean`, `dot_product` or
+    /// `manhattan`; the other settings come from the database's config.
+    #[pyo3(signature = (name, dimensions, distance = "cosine"))]
+    fn create_vector_collection(&self, name: &str, dimensions: usize, distance: &str) -> PyResult<()> {
+        let db = self.db()?;
+        let distance = Distance::from_name(distance)
+            .ok_or_else(|| PyValueError::new_err(format!("Unknown distance metric: {}", distance)))?;
+        db.create_vector_collection(name, db.config().vector_config(dimensions).with_distance(distance))
+            .map_err(to_py_err)
+    }
+
+    /// Insert an embedding, with optional metadata, and return its ID
+    #[pyo3(signature = (collection, vector, metadata = None))]
+    fn insert_vector(
+        &self,
+        py: Python<'_>,
+        collection: &str,
+        vector: &Bound<'_, PyAny>,
+        metadata: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<VectorId> {
+        let db = self.db()?;
+        let (vector, metadata) = (embedding(vector)?, metadata.map(to_json).transpose()?);
+        py.allow_threads(|| db.insert_vector(collection, vector, metadata)).map_err(to_py_err)
+    }
+
+    /// Insert the rows of a 2-D array (or a sequence of embeddings) and
+    /// return their IDs; `metadata`, if given, has one entry per row
+    #[pyo3(signature = (collection, vectors, metadata = None))]
+    fn insert_vectors(
+        &self,
+        py: Python<'_>,
+        collection: &str,
+        vectors: &Bound<'_, PyAny>,
+        metadata: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Vec<VectorId>> {
+        let db = self.db()?;
+        let vectors = embeddings(vectors)?;
+        let metadata = match metadata {
+            Some(metadata) => metadata.iter()?.map(|m| to_json(&m?).map(Some)).collect::<PyResult<Vec<_>>>()?,
+            None => vec![None; vectors.len()],
+        };
+        if metadata.len() != vectors.len() {
+            return Err(PyValueError::new_err(format!(
+                "Got {} vectors but {} metadata entries",
+                vectors.len(),
+                metadata.len()
+            )));
+        }
+        py.allow_threads(|| {
+            vectors
+                .into_iter()
+                .zip(metadata)
+                .map(|(vector, metadata)| db.insert_vector(collection, vector, metadata))
+                .collect::<Result<_, _>>()
+        })
+        .map_err(to_py_err)
+    }
+
+    /// The `k` nearest neighbours of `query`, best first
+    ///
+    /// Each result is a dict with `id`, `score`, `rank`, `metadata`,
+    /// `external_id` and `embedding`. `filter` restricts results by metadata,
+    /// as in `find`.
+    #[pyo3(signature = (collection, query, k = 10, filter = None))]
+    fn vector_search<'py>(
+        &self,
+        py: Python<'py>,
+        collection: &str,
+        query: &Bound<'py, PyAny>,
+        k: usize,
+        filter: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        let (db, query, filter) = (self.db()?, embedding(query)?, to_filter(filter)?);
+        let results = py
+            .allow_threads(|| {
+                if filter.filters.is_empty() {
+                    db.vector_search(collection, &query, k)
+                } else {
+                    db.vector_search_filtered(collection, &query, k, &filter)
+                }
+            })
+            .map_err(to_py_err)?;
+        results
+            .iter()
+            .map(|result| {
+                let dict = vector_document(py, &result.document)?;
+                dict.set_item("score", result.score)?;
+                dict.set_item("rank", result.rank)?;
+                Ok(dict)
+            })
+            .collect()
+    }
+
+    /// The vector with this ID, or `None`
+    fn get_vector<'py>(&self, py: Python<'py>, collection: &str, id: VectorId) -> PyResult<Option<Bound<'py, PyDict>>> {
+        let doc = self.db()?.get_vector(collection, id).map_err(to_py_err)?;
+        doc.map(|doc| vector_document(py, &doc)).transpose()
+    }
+
+    /// Delete a vector; returns whether it existed
+    fn delete_vector(&self, collection: &str, id: VectorId) -> PyResult<bool> {
+        self.db()?.delete_vector(collection, id).map_err(to_py_err)
+    }
+
+    /// Vector collections, as `(name, count)` pairs
+    fn vector_collections(&self) -> PyResult<Vec<(String, usize)>> {
+        Ok(self.db()?.list_vector_collections())
+    }
+
+    /// Drop a vector collection; returns whether it existed
+    fn drop_vector_collection(&self, name: &str) -> PyResult<bool> {
+        self.db()?.drop_vector_collection(name).map_err(to_py_err)
+    }
+
+    /// Flush everything to disk
+    fn sync(&self, py: Python<'_>) -> PyResult<()> {
+        let db = self.db()?;
+        py.allow_threads(|| db.sync()).map_err(to_py_err)
+    }
+
+    /// Flush and close the database; later calls raise `keradb.Error`
+    fn close(&mut self, py: Python<'_>) -> PyResult<()> {
+        match self.inner.take() {
+            Some(db) => py.allow_threads(|| db.sync()).map_err(to_py_err),
+            None => Ok(()),
+        }
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    #[pyo3(signature = (*_args))]
+    fn __exit__(&mut self, py: Python<'_>, _args: &Bound<'_, PyTuple>) -> PyResult<bool> {
+        self.close(py)?;
+        Ok(false)
+    }
+}
+
+/// Create a database file at `path`
+#[pyfunction]
+fn create(py: Python<'_>, path: PathBuf) -> PyResult<Database> {
+    Database::create(py, path)
+}
+
+/// Open an existing database file
+#[pyfunction]
+#[pyo3(signature = (path, read_only = false))]
+fn open(py: Python<'_>, path: PathBuf, read_only: bool) -> PyResult<Database> {
+    Database::open(py, path, read_only)
+}
+
+#[pymodule]
+#[pyo3(name = "keradb")]
+fn keradb_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Database>()?;
+    m.add_function(wrap_pyfunction!(create, m)?)?;
+    m.add_function(wrap_pyfunction!(open, m)?)?;
+    m.add("Error", m.py().get_type_bound::<Error>())?;
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    Ok(())
+}
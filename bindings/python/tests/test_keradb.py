@@ -0,0 +1,61 @@
+import numpy as np
+import pytest
+
+import keradb
+
+
+def test_documents(tmp_path):
+    path = str(tmp_path / "test.ndb")
+    with keradb.create(path) as db:
+        alice = db.insert("users", {"name": "alice", "age": 30, "tags": ["a", "b"], "score": 1.5, "ok": True})
+        db.insert_many("users", [{"name": "bob", "age": 17}, {"name": "carol", "age": 42, "nested": {"x": None}}])
+
+        assert db.find_by_id("users", alice) == {
+            "_id": alice, "name": "alice", "age": 30, "tags": ["a", "b"], "score": 1.5, "ok": True,
+        }
+        assert [d["name"] for d in db.find("users", {"age": {"gte": 21}})] == ["alice", "carol"]
+        assert [d["name"] for d in db.find("users", limit=1, skip=1)] == ["bob"]
+        assert db.count("users") == 3
+        assert db.count("users", {"name": {"starts_with": "c"}}) == 1
+        assert db.update("users", alice, {"name": "alice", "age": 31})["age"] == 31
+        assert db.delete("users", alice)["name"] == "alice"
+        assert db.collections() == [("users", 2)]
+
+        with pytest.raises(KeyError):
+            db.find_by_id("users", alice)
+        with pytest.raises(ValueError):
+            db.find("users", {"age": {"bogus": 1}})
+        with pytest.raises(TypeError):
+            db.insert("users", {"when": object()})
+
+    with pytest.raises(keradb.Error):
+        db.count("users")
+    with keradb.open(path, read_only=True) as db:
+        assert db.count("users") == 2
+
+
+def test_vectors(tmp_path):
+    with keradb.create(str(tmp_path / "vectors.ndb")) as db:
+        db.create_vector_collection("embeddings", 4, distance="euclidean")
+        rows = np.eye(4, dtype=np.float32)
+        ids = db.insert_vectors("embeddings", rows, metadata=[{"n": i} for i in range(4)])
+        extra = db.insert_vector("embeddings", [0.9, 0.1, 0.0, 0.0], {"n": 4})
+
+        hits = db.vector_search("embeddings", np.array([1.0, 0.0, 0.0, 0.0]), k=2)
+        assert [h["id"] for h in hits] == [ids[0], extra]
+        assert [h["rank"] for h in hits] == [0, 1]
+        assert hits[0]["embedding"].dtype == np.float32
+        np.testing.assert_allclose(hits[0]["embedding"], rows[0], atol=1e-3)
+
+        filtered = db.vector_search("embeddings", rows[0], k=2, filter={"n": {"gt": 2}})
+        assert [h["metadata"]["n"] for h in filtered] == [4, 3]
+
+        assert db.get_vector("embeddings", extra)["metadata"] == {"n": 4}
+        assert db.delete_vector("embeddings", extra)
+        assert db.get_vector("embeddings", extra) is None
+        assert db.vector_collections() == [("embeddings", 4)]
+
+        with pytest.raises(ValueError):
+            db.insert_vectors("embeddings", rows, metadata=[{}])
+        with pytest.raises(ValueError):
+            db.create_vector_collection("bad", 4, distance="hamming")
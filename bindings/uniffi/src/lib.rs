@@ -0,0 +1,368 @@
+//! Swift and Kotlin bindings for KeraDB
+//!
+//! uniffi generates the Swift and Kotlin sources from this crate (see the
+//! README), so iOS and Android apps use `Database` like a native class.
+//! Documents, metadata and filters cross the boundary as JSON strings, which
+//! Swift's `Codable` and Kotlin's serialization libraries map to app types;
+//! `swift/KeraDB+Codable.swift` adds `Codable` overloads. Embeddings are
+//! `[Float]` / `List<Float>`.
+//!
+//! Calls block, so run them off the main thread (a Swift actor or Task, a
+//! Kotlin coroutine on `Dispatchers.IO`). A `Database` can be shared between
+//! threads.
+
+use keradb::vector::FilterCondition;
+use keradb::{Database as Inner, Distance, KeraDBError, MetadataFilter, VectorDocument};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+uniffi::setup_scaffolding!("keradb");
+
+/// Why a call failed
+///
+/// Each case carries a message for humans.
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum DatabaseError {
+    /// No such document, collection or vector
+    #[error("{message}")]
+    NotFound { message: String },
+    /// A bad argument: malformed JSON, a filter or distance that does not
+    /// exist, an embedding of the wrong length
+    #[error("{message}")]
+    InvalidArgument { message: String },
+    /// Another handle, in this process or another, has the file open
+    #[error("{message}")]
+    Locked { message: String },
+    /// A write on a database opened read-only
+    #[error("{message}")]
+    ReadOnly { message: String },
+    /// The database was closed with `close()`
+    #[error("Database is closed")]
+    Closed,
+    #[error("{message}")]
+    Other { message: String },
+}
+
+impl From<KeraDBError> for DatabaseError {
+    fn from(err: KeraDBError) -> Self {
+        let message = err.to_string();
+        match err {
+            KeraDBError::DocumentNotFound(_)
+            | KeraDBError::CollectionNotFound(_)
+            | KeraDBError::DatabaseNotFound(_)
+            | KeraDBError::NotFound(_) => Self::NotFound { message },
+            KeraDBError::InvalidQuery(_)
+            | KeraDBError::InvalidDocument(_)
+            | KeraDBError::InvalidFormat(_)
+            | KeraDBError::ParseError(_) => Self::InvalidArgument { message },
+            KeraDBError::Locked(_) => Self::Locked { message },
+            KeraDBError::ReadOnly => Self::ReadOnly { message },
+            _ => Self::Other { message },
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, DatabaseError>;
+
+fn invalid(message: String) -> DatabaseError {
+    DatabaseError::InvalidArgument { message }
+}
+
+fn parse_json(json: &str, what: &str) -> Result<Value> {
+    serde_json::from_str(json).map_err(|e| invalid(format!("Invalid {} JSON: {}", what, e)))
+}
+
+fn to_json(value: &Value) -> String {
+    value.to_string()
+}
+
+/// A document's fields and `_id`, without the collection name KeraDB keeps
+fn document_value(doc: &keradb::Document) -> Value {
+    let mut value = doc.to_value();
+    if let Value::Object(ref mut map) = value {
+        map.remove("_collection");
+    }
+    value
+}
+
+/// A filter like `{"age": {"gte": 21}}`, as the REST server takes
+fn parse_filter(filter: Option<String>) -> Result<MetadataFilter> {
+    let Some(filter) = filter else { return Ok(MetadataFilter::new()) };
+    let filters: HashMap<String, FilterCondition> =
+        serde_json::from_value(parse_json(&filter, "filter")?).map_err(|e| invalid(format!("Invalid filter: {}", e)))?;
+    Ok(MetadataFilter { filters })
+}
+
+/// A document collection and how many documents it holds
+#[derive(uniffi::Record)]
+pub struct CollectionInfo {
+    pub name: String,
+    pub count: u64,
+}
+
+fn collection_infos(collections: Vec<(String, usize)>) -> Vec<CollectionInfo> {
+    collections.into_iter().map(|(name, count)| CollectionInfo { name, count: count as u64 }).collect()
+}
+
+/// A stored vector
+#[derive(uniffi::Record)]
+pub struct VectorEntry {
+    pub id: u64,
+    pub external_id: Option<String>,
+    /// `nil`/`null` for vectors stored as text only
+    pub embedding: Option<Vec<f32>>,
+    /// The metadata inserted with the vector, as JSON
+    pub metadata_json: String,
+}
+
+impl From<VectorDocument> for VectorEntry {
+    fn from(doc: VectorDocument) -> Self {
+        Self {
+            id: doc.id,
+            external_id: doc.external_id,
+            embedding: doc.embedding,
+            metadata_json: to_json(&doc.metadata),
+        }
+    }
+}
+
+/// One result of `vectorSearch`
+#[derive(uniffi::Record)]
+pub struct SearchHit {
+    pub entry: VectorEntry,
+    /// Distance from the query; lower is nearer
+    pub score: f32,
+    /// Position in the results, starting at 0
+    pub rank: u32,
+}
+
+/// A KeraDB database
+///
+/// Create one with `Database.create(path:)`, open one with
+/// `Database.open(path:readOnly:)`, or use `Database.inMemory()`. Call
+/// `close()` to release the file lock before the object is freed.
+#[derive(uniffi::Object)]
+pub struct Database {
+    inner: RwLock<Option<Arc<Inner>>>,
+}
+
+impl Database {
+    fn wrap(db: Inner) -> Arc<Self> {
+        Arc::new(Self { inner: RwLock::new(Some(Arc::new(db))) })
+    }
+
+    fn db(&self) -> Result<Arc<Inner>> {
+        self.inner.read().unwrap_or_else(|e| e.into_inner()).clone().ok_or(DatabaseError::Closed)
+    }
+}
+
+#[uniffi::export]
+impl Database {
+    /// Create a database file at `path`
+    #[uniffi::constructor]
+    pub fn create(path: String) -> Result<Arc<Self>> {
+        Ok(Self::wrap(Inner::create(path)?))
+    }
+
+    /// Open an existing database file
+    #[uniffi::constructor(default(read_only = false))]
+    pub fn open(path: String, read_only: bool) -> Result<Arc<Self>> {
+        let db = if read_only { Inner::open_read_only(path)? } else { Inner::open(path)? };
+        Ok(Self::wrap(db))
+    }
+
+    /// A database that lives only in memory; save it with `persistTo`
+    #[uniffi::constructor]
+    pub fn in_memory() -> Result<Arc<Self>> {
+        Ok(Self::wrap(Inner::in_memory()?))
+    }
+
+    /// Insert a JSON object and return its ID
+    pub fn insert(&self, collection: String, document_json: String) -> Result<String> {
+        Ok(self.db()?.insert(&collection, parse_json(&document_json, "document")?)?)
+    }
+
+    /// The document with this ID as JSON, or `nil`/`null` if there is none
+    pub fn find_by_id(&self, collection: String, id: String) -> Result<Option<String>> {
+        match self.db()?.find_by_id(&collection, &id) {
+            Ok(doc) => Ok(Some(to_json(&document_value(&doc)))),
+            Err(KeraDBError::DocumentNotFound(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Documents in `collection` matching `filter_json`, as JSON, in
+    /// insertion order
+    ///
+    /// The filter maps fields to conditions, e.g.
+    /// `{"age": {"gte": 21}, "tags": {"contains": "rust"}}`.
+    #[uniffi::method(default(filter_json = None, limit = None, skip = 0))]
+    pub fn find(
+        &self,
+        collection: String,
+        filter_json: Option<String>,
+        limit: Option<u32>,
+        skip: u32,
+    ) -> Result<Vec<String>> {
+        let (db, filter) = (self.db()?, parse_filter(filter_json)?);
+        let limit = limit.map_or(usize::MAX, |l| l as usize);
+        Ok(db
+            .find_all(&collection, None, None)?
+            .iter()
+            .map(document_value)
+            .filter(|d| filter.matches(d))
+            .skip(skip as usize)
+            .take(limit)
+            .map(|d| to_json(&d))
+            .collect())
+    }
+
+    /// Replace a document's data and return the updated document as JSON
+    pub fn update(&self, collection: String, id: String, document_json: String) -> Result<String> {
+        let doc = self.db()?.update(&collection, &id, parse_json(&document_json, "document")?)?;
+        Ok(to_json(&document_value(&doc)))
+    }
+
+    /// Delete a document; returns whether it existed
+    pub fn delete(&self, collection: String, id: String) -> Result<bool> {
+        match self.db()?.delete(&collection, &id) {
+            Ok(_) => Ok(true),
+            Err(KeraDBError::DocumentNotFound(_)) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Number of documents in `collection`
+    pub fn count(&self, collection: String) -> Result<u64> {
+        Ok(self.db()?.count(&collection) as u64)
+    }
+
+    /// Document collections and their sizes
+    pub fn collections(&self) -> Result<Vec<CollectionInfo>> {
+        Ok(collection_infos(self.db()?.list_collections()))
+    }
+
+    /// Create a vector collection
+    ///
+    /// `distance` is `cosine` (the default), `euclidean`, `dot_product` or
+    /// `manhattan`; the other settings come from the database's config.
+    #[uniffi::method(default(distance = None))]
+    pub fn create_vector_collection(&self, name: String, dimensions: u32, distance: Option<String>) -> Result<()> {
+        let db = self.db()?;
+        let mut config = db.config().vector_config(dimensions as usize);
+        if let Some(distance) = distance {
+            let metric = Distance::from_name(&distance)
+                .ok_or_else(|| invalid(format!("Unknown distance metric: {}", distance)))?;
+            config = config.with_distance(metric);
+        }
+        Ok(db.create_vector_collection(&name, config)?)
+    }
+
+    /// Insert an embedding, with optional JSON metadata, and return its ID
+    #[uniffi::method(default(metadata_json = None))]
+    pub fn insert_vector(&self, collection: String, vector: Vec<f32>, metadata_json: Option<String>) -> Result<u64> {
+        let metadata = metadata_json.map(|m| parse_json(&m, "metadata")).transpose()?;
+        Ok(self.db()?.insert_vector(&collection, vector, metadata)?)
+    }
+
+    /// The `k` nearest neighbours of `query`, best first, optionally only
+    /// among vectors whose metadata matches `filter_json`
+    #[uniffi::method(default(k = 10, filter_json = None))]
+    pub fn vector_search(
+        &self,
+        collection: String,
+        query: Vec<f32>,
+        k: u32,
+        filter_json: Option<String>,
+    ) -> Result<Vec<SearchHit>> {
+        let (db, filter) = (self.db()?, parse_filter(filter_json)?);
+        let results = if filter.filters.is_empty() {
+            db.vector_search(&collection, &query, k as usize)?
+        } else {
+            db.vector_search_filtered(&collection, &query, k as usize, &filter)?
+        };
+        Ok(results
+            .into_iter()
+            .map(|result| SearchHit { entry: result.document.into(), score: result.score, rank: result.rank as u32 })
+            .collect())
+    }
+
+    /// The vector with this ID, or `nil`/`null` if there is none
+    pub fn get_vector(&self, collection: String, id: u64) -> Result<Option<VectorEntry>> {
+        Ok(self.db()?.get_vector(&collection, id)?.map(VectorEntry::from))
+    }
+
+    /// Delete a vector; returns whether it existed
+    pub fn delete_vector(&self, collection: String, id: u64) -> Result<bool> {
+        Ok(self.db()?.delete_vector(&collection, id)?)
+    }
+
+    /// Vector collections and their sizes
+    pub fn vector_collections(&self) -> Result<Vec<CollectionInfo>> {
+        Ok(collection_infos(self.db()?.list_vector_collections()))
+    }
+
+    /// Drop a vector collection; returns whether it existed
+    pub fn drop_vector_collection(&self, name: String) -> Result<bool> {
+        Ok(self.db()?.drop_vector_collection(&name)?)
+    }
+
+    /// Copy the database to a new file at `path`
+    pub fn persist_to(&self, path: String) -> Result<()> {
+        Ok(self.db()?.persist_to(path)?)
+    }
+
+    /// Flush everything to disk
+    pub fn sync(&self) -> Result<()> {
+        Ok(self.db()?.sync()?)
+    }
+
+    /// Flush and close the database; later calls fail with `Closed`
+    ///
+    /// Calls already running on other threads finish first.
+    pub fn close(&self) -> Result<()> {
+        let db = self.inner.write().unwrap_or_else(|e| e.into_inner()).take();
+        match db {
+            Some(db) => Ok(db.sync()?),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_documents_and_vectors() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.ndb").to_string_lossy().into_owned();
+        let db = Database::create(path.clone()).unwrap();
+
+        let alice = db.insert("users".into(), r#"{"name":"Alice","age":30}"#.into()).unwrap();
+        db.insert("users".into(), r#"{"name":"Bob","age":17}"#.into()).unwrap();
+        let adults = db.find("users".into(), Some(r#"{"age":{"gte":21}}"#.into()), None, 0).unwrap();
+        let adult: Value = serde_json::from_str(&adults[0]).unwrap();
+        assert_eq!(adults.len(), 1);
+        assert_eq!(adult, serde_json::json!({"_id": alice, "name": "Alice", "age": 30}));
+        assert!(db.find_by_id("users".into(), "nobody".into()).unwrap().is_none());
+        assert!(db.delete("users".into(), alice.clone()).unwrap());
+        assert!(!db.delete("users".into(), alice).unwrap());
+        assert!(matches!(db.insert("users".into(), "{".into()), Err(DatabaseError::InvalidArgument { .. })));
+
+        db.create_vector_collection("embeddings".into(), 2, Some("euclidean".into())).unwrap();
+        let near = db.insert_vector("embeddings".into(), vec![1.0, 0.0], Some(r#"{"n":1}"#.into())).unwrap();
+        db.insert_vector("embeddings".into(), vec![0.0, 5.0], None).unwrap();
+        let hits = db.vector_search("embeddings".into(), vec![0.9, 0.1], 1, None).unwrap();
+        assert_eq!((hits[0].entry.id, hits[0].entry.metadata_json.as_str()), (near, r#"{"n":1}"#));
+
+        assert!(matches!(Database::open(path.clone(), false), Err(DatabaseError::Locked { .. })));
+        db.close().unwrap();
+        assert!(matches!(db.count("users".into()), Err(DatabaseError::Closed)));
+        let reopened = Database::open(path, true).unwrap();
+        assert_eq!(reopened.count("users".into()).unwrap(), 1);
+        assert!(matches!(reopened.insert("users".into(), "{}".into()), Err(DatabaseError::ReadOnly { .. })));
+    }
+}
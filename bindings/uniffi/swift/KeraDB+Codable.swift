@@ -0,0 +1,37 @@
+// Codable overloads for the generated Database class. Add this file to the
+// app or package alongside the generated KeraDB.swift.
+
+import Foundation
+
+public extension Database {
+    /// Insert an Encodable value (which must encode to a JSON object) and
+    /// return its ID
+    func insert<T: Encodable>(_ collection: String, _ document: T) throws -> String {
+        try insert(collection: collection, documentJson: String(decoding: JSONEncoder().encode(document), as: UTF8.self))
+    }
+
+    /// The document with this ID decoded as `T`, or nil if there is none
+    func findById<T: Decodable>(_ collection: String, _ id: String, as type: T.Type = T.self) throws -> T? {
+        try findById(collection: collection, id: id).map { try JSONDecoder().decode(T.self, from: Data($0.utf8)) }
+    }
+
+    /// Documents in `collection` decoded as `T`
+    func find<T: Decodable>(_ collection: String, as type: T.Type = T.self, limit: UInt32? = nil) throws -> [T] {
+        try find(collection: collection, filterJson: nil, limit: limit, skip: 0).map {
+            try JSONDecoder().decode(T.self, from: Data($0.utf8))
+        }
+    }
+
+    /// Insert an embedding with Encodable metadata and return its ID
+    func insertVector<M: Encodable>(_ collection: String, _ vector: [Float], metadata: M) throws -> UInt64 {
+        let json = String(decoding: try JSONEncoder().encode(metadata), as: UTF8.self)
+        return try insertVector(collection: collection, vector: vector, metadataJson: json)
+    }
+}
+
+public extension VectorEntry {
+    /// The metadata decoded as `M`
+    func metadata<M: Decodable>(as type: M.Type = M.self) throws -> M {
+        try JSONDecoder().decode(M.self, from: Data(metadataJson.utf8))
+    }
+}
@@ -0,0 +1,275 @@
+#ifndef KERADB_H
+#define KERADB_H
+
+/* Generated by cbindgen from src/ffi.rs. Do not edit; run `make header`. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define KERADB_VERSION "0.1.0"
+
+/* A database handle from keradb_create() or keradb_open() */
+typedef struct KeraDB KeraDB;
+
+/**
+ * Why the last call on this thread failed
+ *
+ * The values are part of the C API: they never change, and new codes are
+ * only ever added.
+ */
+typedef enum KeraDBErrorCode {
+  KeraDBErrorCode_Ok = 0,
+  /**
+   * A required pointer argument was `NULL`
+   */
+  KeraDBErrorCode_NullArgument = 1,
+  /**
+   * A string argument was not valid UTF-8
+   */
+  KeraDBErrorCode_InvalidUtf8 = 2,
+  /**
+   * A JSON argument did not parse
+   */
+  KeraDBErrorCode_InvalidJson = 3,
+  /**
+   * KeraDB panicked; the database may need to be reopened
+   */
+  KeraDBErrorCode_Panic = 4,
+  /**
+   * Anything not covered by another code
+   */
+  KeraDBErrorCode_Other = 5,
+  KeraDBErrorCode_Io = 10,
+  KeraDBErrorCode_Serialization = 11,
+  KeraDBErrorCode_DatabaseNotFound = 12,
+  KeraDBErrorCode_CollectionNotFound = 13,
+  KeraDBErrorCode_DocumentNotFound = 14,
+  KeraDBErrorCode_InvalidFormat = 15,
+  KeraDBErrorCode_VersionMismatch = 16,
+  KeraDBErrorCode_ChecksumMismatch = 17,
+  KeraDBErrorCode_Locked = 18,
+  KeraDBErrorCode_ReadOnly = 19,
+  KeraDBErrorCode_Config = 20,
+  KeraDBErrorCode_InvalidQuery = 21,
+  KeraDBErrorCode_InvalidDocument = 22,
+  KeraDBErrorCode_DuplicateKey = 23,
+  KeraDBErrorCode_Index = 24,
+  KeraDBErrorCode_Transaction = 25,
+  KeraDBErrorCode_Storage = 26,
+  KeraDBErrorCode_Parse = 27,
+  KeraDBErrorCode_CollectionExists = 28,
+  KeraDBErrorCode_NotFound = 29,
+  KeraDBErrorCode_NotImplemented = 30,
+  KeraDBErrorCode_Vector = 31,
+  KeraDBErrorCode_Embedding = 32,
+  KeraDBErrorCode_OplogTruncated = 33,
+  KeraDBErrorCode_DocumentTooLarge = 34,
+  KeraDBErrorCode_QuotaExceeded = 35,
+  KeraDBErrorCode_ReferenceViolation = 36,
+  KeraDBErrorCode_EmbeddingUnavailable = 37,
+} KeraDBErrorCode;
+
+/**
+ * Embeds `text` by writing `dimensions` floats to `out`, returning 0 on
+ * success or anything else on failure; `user_data` is the pointer given to
+ * [`keradb_set_embedding_callback`]
+ */
+typedef int (*KeraDBEmbedFn)(const char *text, float *out, size_t dimensions, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Why the last call on this thread failed, or `Ok` if it succeeded
+ */
+KeraDBErrorCode keradb_last_error_code(void);
+
+/**
+ * Why the last call on this thread failed, or `NULL` if it succeeded
+ *
+ * Each call returns a new copy of the message, which the caller owns and
+ * must release with [`keradb_free_string`].
+ */
+char *keradb_last_error(void);
+
+/**
+ * Free a string returned by KeraDB functions
+ *
+ * # Safety
+ * The pointer must be a valid pointer returned by a KeraDB function,
+ * and must not have been freed before.
+ */
+void keradb_free_string(char *s);
+
+/**
+ * Create a database file at `path`
+ *
+ * Returns a handle to release with [`keradb_close`], or `NULL` on error.
+ */
+KeraDB *keradb_create(const char *path);
+
+/**
+ * Open the existing database file at `path`
+ *
+ * Returns a handle to release with [`keradb_close`], or `NULL` on error.
+ */
+KeraDB *keradb_open(const char *path);
+
+/**
+ * Close a database and free its handle; `NULL` is ignored
+ */
+void keradb_close(KeraDB *db);
+
+/**
+ * Insert a JSON object into `collection`
+ *
+ * Returns the new document's ID, or `NULL` on error.
+ */
+char *keradb_insert(KeraDB *db, const char *collection, const char *json_data);
+
+/**
+ * Find a document by ID
+ *
+ * Returns the document as JSON, or `NULL` on error, including when there
+ * is no such document (`DocumentNotFound`).
+ */
+char *keradb_find_by_id(KeraDB *db, const char *collection, const char *doc_id);
+
+/**
+ * Replace a document's data with a JSON object
+ *
+ * Returns the updated document as JSON, or `NULL` on error.
+ */
+char *keradb_update(KeraDB *db, const char *collection, const char *doc_id, const char *json_data);
+
+/**
+ * Delete a document by ID
+ *
+ * Returns 1 if it was deleted, or 0 on error.
+ */
+int keradb_delete(KeraDB *db, const char *collection, const char *doc_id);
+
+/**
+ * Documents in `collection`, skipping `skip` and returning at most
+ * `limit`; pass -1 for either to not skip or not limit
+ *
+ * Returns a JSON array, or `NULL` on error.
+ */
+char *keradb_find_all(KeraDB *db, const char *collection, int limit, int skip);
+
+/**
+ * Number of documents in `collection`, or -1 on error
+ */
+int keradb_count(KeraDB *db, const char *collection);
+
+/**
+ * Collections and their document counts
+ *
+ * Returns a JSON array of `[name, count]` pairs, or `NULL` on error.
+ */
+char *keradb_list_collections(KeraDB *db);
+
+/**
+ * Flush the database to disk
+ *
+ * Returns 1 on success, or 0 on error.
+ */
+int keradb_sync(KeraDB *db);
+
+/**
+ * Create a vector collection for `dimensions`-long embeddings
+ *
+ * `distance` is `"cosine"`, `"euclidean"`, `"dot"` or `"manhattan"`, or
+ * `NULL` for cosine. Returns 1 on success, or 0 on error.
+ */
+int keradb_create_vector_collection(KeraDB *db,
+                                    const char *name,
+                                    size_t dimensions,
+                                    const char *distance);
+
+/**
+ * Insert the `dimensions` floats at `embedding` into `collection`, with
+ * optional JSON `metadata` (`NULL` for none)
+ *
+ * Stores the new vector's ID in `*id` and returns 1, or returns 0 on error.
+ */
+int keradb_insert_vector(KeraDB *db,
+                         const char *collection,
+                         const float *embedding,
+                         size_t dimensions,
+                         const char *metadata,
+                         uint64_t *id);
+
+/**
+ * The `k` vectors in `collection` nearest the `dimensions` floats at `query`
+ *
+ * Returns a JSON array of `{"document", "score", "rank"}` objects, nearest
+ * first, or `NULL` on error.
+ */
+char *keradb_vector_search(KeraDB *db,
+                           const char *collection,
+                           const float *query,
+                           size_t dimensions,
+                           size_t k);
+
+/**
+ * Find a vector by ID
+ *
+ * Returns it as JSON, or `NULL` on error, including when there is no such
+ * vector (`NotFound`).
+ */
+char *keradb_get_vector(KeraDB *db, const char *collection, uint64_t id);
+
+/**
+ * Delete a vector by ID
+ *
+ * Returns 1 if it was deleted, 0 if there was no such vector, or -1 on error.
+ */
+int keradb_delete_vector(KeraDB *db, const char *collection, uint64_t id);
+
+/**
+ * Vector collections and their vector counts
+ *
+ * Returns a JSON array of `[name, count]` pairs, or `NULL` on error.
+ */
+char *keradb_list_vector_collections(KeraDB *db);
+
+/**
+ * Embed text with `embed`, a model the application runs itself, for vector
+ * collections created after this call and those already open
+ *
+ * `model` names the model in the collections it builds. `embed` may be
+ * called from any thread, until the database is closed or another provider
+ * is set. Returns 1 on success, or 0 on error.
+ */
+int keradb_set_embedding_callback(KeraDB *db,
+                                  const char *model,
+                                  size_t dimensions,
+                                  KeraDBEmbedFn embed,
+                                  void *user_data);
+
+/**
+ * Embed `text` and insert it into `collection`, with optional JSON
+ * `metadata` (`NULL` for none)
+ *
+ * Stores the new vector's ID in `*id` and returns 1, or returns 0 on error.
+ */
+int keradb_insert_text(KeraDB *db,
+                       const char *collection,
+                       const char *text,
+                       const char *metadata,
+                       uint64_t *id);
+
+/**
+ * The `k` vectors in `collection` nearest the embedding of `query`
+ *
+ * Returns a JSON array like [`keradb_vector_search`]'s, or `NULL` on error.
+ */
+char *keradb_vector_search_text(KeraDB *db, const char *collection, const char *query, size_t k);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* KERADB_H */
@@ -0,0 +1,193 @@
+//! Index suggestions from the queries a database has served
+//!
+//! Every filtered `find` or `count` that scans its whole collection is
+//! recorded with the index that would have let it read fewer documents: a
+//! [multikey index](crate::Database::create_multikey_index) when an `=`, `in`
+//! or `contains` condition met arrays, and otherwise a
+//! [column projection](crate::Database::create_columns) of the filtered
+//! fields. [`Database::index_suggestions`](crate::Database::index_suggestions)
+//! ranks them by the document reads they would have saved.
+//!
+//! Only the counts are kept, in memory; they start over when the database is
+//! opened.
+
+use crate::execution::Executor;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+
+/// An index a query could have used instead of a full scan
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SuggestedIndex {
+    /// A column projection of these fields, see [`Database::create_columns`](crate::Database::create_columns)
+    Columns { fields: Vec<String> },
+    /// A multikey index, see [`Database::create_multikey_index`](crate::Database::create_multikey_index)
+    Multikey { field: String },
+}
+
+/// A suggested index, with the full scans it would have avoided
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IndexSuggestion {
+    pub collection: String,
+    pub index: SuggestedIndex,
+    /// Full scans that could have used the index
+    pub scans: u64,
+    /// Documents those scans read
+    pub documents_examined: u64,
+    /// Documents that matched their filters
+    pub documents_matched: u64,
+    /// Documents such a query would read with the index, at the collection's
+    /// current size and the filters' selectivity so far
+    pub estimated_reads_per_query: u64,
+}
+
+impl IndexSuggestion {
+    /// Document reads the index would have saved so far
+    pub fn reads_saved(&self) -> u64 {
+        self.documents_examined - self.documents_matched
+    }
+
+    /// Fraction of the documents read that matched
+    pub fn selectivity(&self) -> f64 {
+        if self.documents_examined == 0 {
+            return 1.0;
+        }
+        self.documents_matched as f64 / self.documents_examined as f64
+    }
+}
+
+impl fmt::Display for SuggestedIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SuggestedIndex::Columns { fields } => write!(f, "column index on {}", fields.join(", ")),
+            SuggestedIndex::Multikey { field } => write!(f, "multikey index on {}", field),
+        }
+    }
+}
+
+impl fmt::Display for IndexSuggestion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} ({} full scan(s), {:.1}% matched, ~{} reads saved)",
+            self.collection,
+            self.index,
+            self.scans,
+            self.selectivity() * 100.0,
+            self.reads_saved()
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct ScanCounts {
+    scans: u64,
+    examined: u64,
+    matched: u64,
+}
+
+/// Full scans seen, by collection and the index each could have used
+#[derive(Default)]
+pub(crate) struct Advisor {
+    scans: Mutex<HashMap<(String, SuggestedIndex), ScanCounts>>,
+}
+
+impl Advisor {
+    /// Record a full scan of a collection that read `examined` documents,
+    /// of which `matched` met the filter
+    pub(crate) fn record(&self, collection: &str, index: SuggestedIndex, examined: usize, matched: usize) {
+        let mut scans = self.scans.lock();
+        let counts = scans.entry((collection.to_string(), index)).or_default();
+        counts.scans += 1;
+        counts.examined += examined as u64;
+        counts.matched += matched as u64;
+    }
+
+    /// Suggestions that would have saved reads, best first, leaving out
+    /// indexes already in place
+    pub(crate) fn suggestions(&self, executor: &Executor) -> Vec<IndexSuggestion> {
+        let scans = self.scans.lock();
+        let mut suggestions: Vec<IndexSuggestion> = scans
+            .iter()
+            .filter(|((collection, index), counts)| counts.matched < counts.examined && !exists(executor, collection, index))
+            .map(|((collection, index), counts)| {
+                let documents = executor.count(collection) as u64;
+                IndexSuggestion {
+                    collection: collection.clone(),
+                    index: index.clone(),
+                    scans: counts.scans,
+                    documents_examined: counts.examined,
+                    documents_matched: counts.matched,
+                    estimated_reads_per_query: (documents * counts.matched).div_ceil(counts.examined),
+                }
+            })
+            .collect();
+        suggestions.sort_by(|a, b| {
+            b.reads_saved().cmp(&a.reads_saved()).then_with(|| (&a.collection, &a.index).cmp(&(&b.collection, &b.index)))
+        });
+        suggestions
+    }
+}
+
+fn exists(executor: &Executor, collection: &str, index: &SuggestedIndex) -> bool {
+    match index {
+        SuggestedIndex::Columns { fields } => {
+            let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+            executor.columns_cover(collection, &fields)
+        }
+        SuggestedIndex::Multikey { field } => executor.multikey_fields(collection).contains(field),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SuggestedIndex;
+    use crate::query::{Filter, FindQuery};
+    use crate::vector::FilterCondition;
+    use crate::Database;
+    use serde_json::json;
+
+    #[test]
+    fn test_index_suggestions() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::create(dir.path().join("advisor.ndb")).unwrap();
+        for i in 0..100 {
+            let status = if i % 10 == 0 { "open" } else { "closed" };
+            let tags = if i % 4 == 0 { json!(["bug", "ui"]) } else { json!(["feature"]) };
+            db.insert("issues", json!({"n": i, "status": status, "tags": tags})).unwrap();
+        }
+        let filter = |conditions: Vec<(&str, FilterCondition)>| Filter {
+            conditions: conditions.into_iter().map(|(f, c)| (f.to_string(), c)).collect(),
+            collation: None,
+        };
+        let find = |conditions| {
+            let query = FindQuery { collection: "issues".to_string(), id: None, filter: filter(conditions), sort: None, limit: None, skip: 0 };
+            query.run(&db).unwrap().len()
+        };
+        let open = || vec![("status", FilterCondition::Eq(json!("open")))];
+        assert_eq!(find(open()), 10);
+        assert_eq!(filter(open()).count(&db, "issues").unwrap(), 10);
+        assert_eq!(find(vec![("tags", FilterCondition::Contains("bug".to_string())), ("n", FilterCondition::Gte(json!(50)))]), 12);
+        // Unfiltered scans, and filters matching everything, have nothing to suggest
+        assert_eq!(find(vec![]), 100);
+        assert_eq!(find(vec![("n", FilterCondition::Gte(json!(0)))]), 100);
+
+        let suggestions = db.index_suggestions();
+        let indexes: Vec<_> = suggestions.iter().map(|s| s.index.clone()).collect();
+        assert_eq!(
+            indexes,
+            [SuggestedIndex::Columns { fields: vec!["status".to_string()] }, SuggestedIndex::Multikey { field: "tags".to_string() }]
+        );
+        assert_eq!((suggestions[0].scans, suggestions[0].reads_saved()), (2, 180));
+        assert_eq!(suggestions[0].estimated_reads_per_query, 10);
+        assert_eq!(suggestions[0].to_string(), "issues: column index on status (2 full scan(s), 10.0% matched, ~180 reads saved)");
+
+        // Suggestions already taken are left out
+        db.create_columns("issues", &["status"]).unwrap();
+        assert_eq!(db.index_suggestions().len(), 1);
+        db.create_multikey_index("issues", "tags").unwrap();
+        assert!(db.index_suggestions().is_empty());
+    }
+}
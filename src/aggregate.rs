@@ -0,0 +1,422 @@
+//! Aggregation pipelines
+//!
+//! A [`Pipeline`] runs a collection's documents through stages, much like a
+//! MongoDB `aggregate`: [`Stage::Match`] keeps the documents passing a
+//! filter, [`Stage::Group`] folds them into one row per key with
+//! accumulators such as sums and averages, and [`Stage::Sort`] and
+//! [`Stage::Limit`] order and cut the result. Field names may be dotted to
+//! reach into nested objects.
+//!
+//! Pipelines that group over fields of a collection's
+//! [column projection](crate::Database::create_columns) read the projected
+//! values instead of deserializing every document.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let pipeline = Pipeline::new()
+//!     .matching(MetadataFilter::new().eq("status", json!("paid")))
+//!     .group(Group::by("customer").count("orders").sum("spent", "total"))
+//!     .sort("spent", true)
+//!     .limit(10);
+//! for row in db.aggregate("orders", &pipeline)? {
+//!     println!("{} spent {} over {} orders", row["_id"], row["spent"], row["orders"]);
+//! }
+//! ```
+
+use crate::vector::types::compare_values;
+use crate::vector::MetadataFilter;
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Inputs smaller than this are aggregated on the calling thread
+pub(crate) const PARALLEL_THRESHOLD: usize = 1024;
+
+/// One step of a [`Pipeline`]
+#[derive(Debug, Clone)]
+pub enum Stage {
+    /// Keep the rows matching every condition
+    Match(MetadataFilter),
+    /// Fold rows into one per key
+    Group(Group),
+    /// Order rows by a field; rows without it go last
+    Sort { field: String, descending: bool },
+    /// Keep the first rows
+    Limit(usize),
+}
+
+/// How a [`Stage::Group`] folds rows
+#[derive(Debug, Clone)]
+pub struct Group {
+    /// Rows with equal values here share a group; `None` makes one group
+    pub key: Option<String>,
+    /// Output fields and how each is computed
+    pub accumulators: Vec<(String, Accumulator)>,
+}
+
+/// A value computed over the rows of a group
+#[derive(Debug, Clone, PartialEq)]
+pub enum Accumulator {
+    /// Number of rows
+    Count,
+    /// Sum of a numeric field; other values are skipped
+    Sum(String),
+    /// Mean of a numeric field, or null if no row has one
+    Avg(String),
+    /// Smallest number or string in a field
+    Min(String),
+    /// Largest number or string in a field
+    Max(String),
+}
+
+impl Accumulator {
+    fn field(&self) -> Option<&str> {
+        match self {
+            Accumulator::Count => None,
+            Accumulator::Sum(field) | Accumulator::Avg(field) | Accumulator::Min(field) | Accumulator::Max(field) => {
+                Some(field)
+            }
+        }
+    }
+}
+
+impl Group {
+    /// One group per distinct value of `field`
+    pub fn by(field: &str) -> Self {
+        Self { key: Some(field.to_string()), accumulators: Vec::new() }
+    }
+
+    /// A single group of every row
+    pub fn all() -> Self {
+        Self { key: None, accumulators: Vec::new() }
+    }
+
+    pub fn count(self, name: &str) -> Self {
+        self.with(name, Accumulator::Count)
+    }
+
+    pub fn sum(self, name: &str, field: &str) -> Self {
+        self.with(name, Accumulator::Sum(field.to_string()))
+    }
+
+    pub fn avg(self, name: &str, field: &str) -> Self {
+        self.with(name, Accumulator::Avg(field.to_string()))
+    }
+
+    pub fn min(self, name: &str, field: &str) -> Self {
+        self.with(name, Accumulator::Min(field.to_string()))
+    }
+
+    pub fn max(self, name: &str, field: &str) -> Self {
+        self.with(name, Accumulator::Max(field.to_string()))
+    }
+
+    /// Add an output field
+    pub fn with(mut self, name: &str, accumulator: Accumulator) -> Self {
+        self.accumulators.push((name.to_string(), accumulator));
+        self
+    }
+
+    fn fields(&self) -> impl Iterator<Item = &str> {
+        self.key.as_deref().into_iter().chain(self.accumulators.iter().filter_map(|(_, acc)| acc.field()))
+    }
+}
+
+/// Stages to run a collection through; see [`Database::aggregate`](crate::Database::aggregate)
+#[derive(Debug, Clone, Default)]
+pub struct Pipeline {
+    pub stages: Vec<Stage>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn matching(self, filter: MetadataFilter) -> Self {
+        self.stage(Stage::Match(filter))
+    }
+
+    pub fn group(self, group: Group) -> Self {
+        self.stage(Stage::Group(group))
+    }
+
+    pub fn sort(self, field: &str, descending: bool) -> Self {
+        self.stage(Stage::Sort { field: field.to_string(), descending })
+    }
+
+    pub fn limit(self, limit: usize) -> Self {
+        self.stage(Stage::Limit(limit))
+    }
+
+    pub fn stage(mut self, stage: Stage) -> Self {
+        self.stages.push(stage);
+        self
+    }
+
+    /// The document fields read up to the first group, or `None` if the
+    /// pipeline does not group and so returns whole documents
+    pub(crate) fn input_fields(&self) -> Option<Vec<&str>> {
+        let mut fields = Vec::new();
+        for stage in &self.stages {
+            match stage {
+                Stage::Match(filter) => fields.extend(filter.filters.keys().map(String::as_str)),
+                Stage::Sort { field, .. } => fields.push(field),
+                Stage::Limit(_) => {}
+                Stage::Group(group) => {
+                    fields.extend(group.fields());
+                    fields.sort_unstable();
+                    fields.dedup();
+                    return Some(fields);
+                }
+            }
+        }
+        None
+    }
+
+    /// How many leading stages run separately on each part of the input:
+    /// the matches, and the group after them if there is one
+    fn split(&self) -> (usize, Option<&Group>) {
+        let matches = self.stages.iter().take_while(|stage| matches!(stage, Stage::Match(_))).count();
+        match self.stages.get(matches) {
+            Some(Stage::Group(group)) => (matches + 1, Some(group)),
+            _ => (matches, None),
+        }
+    }
+
+    /// Run the leading matches and group over one part of the input
+    pub(crate) fn run_part<R: Row>(&self, rows: impl IntoIterator<Item = R>) -> Partial {
+        let (end, group) = self.split();
+        let matches = &self.stages[..end - group.is_some() as usize];
+        let rows = rows.into_iter().filter(|row| matches.iter().all(|stage| passes(stage, row)));
+        match group {
+            Some(group) => Partial { rows: Vec::new(), groups: fold(group, rows) },
+            None => Partial { rows: rows.map(Row::into_value).collect(), groups: Groups::new() },
+        }
+    }
+
+    /// Combine the parts and run the remaining stages
+    pub(crate) fn merge(&self, parts: Vec<Partial>) -> Vec<Value> {
+        let (end, group) = self.split();
+        let mut rows = match group {
+            Some(group) => {
+                let mut groups = Groups::new();
+                for part in parts {
+                    merge_groups(&mut groups, part.groups);
+                }
+                finish(group, groups)
+            }
+            None => parts.into_iter().flat_map(|part| part.rows).collect(),
+        };
+        for stage in &self.stages[end..] {
+            match stage {
+                Stage::Group(group) => rows = finish(group, fold(group, std::mem::take(&mut rows))),
+                stage => apply(stage, &mut rows),
+            }
+        }
+        rows
+    }
+}
+
+/// Groups by their key's JSON text
+type Groups = HashMap<String, (Value, Vec<State>)>;
+
+/// What the leading stages of a pipeline made of one part of its input
+pub(crate) struct Partial {
+    /// Matching rows, if the pipeline does not start by grouping them
+    rows: Vec<Value>,
+    groups: Groups,
+}
+
+/// A row a pipeline reads fields from
+pub(crate) trait Row {
+    fn get(&self, field: &str) -> Option<&Value>;
+    fn into_value(self) -> Value;
+}
+
+impl Row for Value {
+    fn get(&self, field: &str) -> Option<&Value> {
+        field.split('.').try_fold(self, |value, key| value.get(key))
+    }
+
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+/// Whether a row passes a match stage
+fn passes<R: Row>(stage: &Stage, row: &R) -> bool {
+    match stage {
+        Stage::Match(filter) => filter.filters.iter().all(|(field, condition)| condition.matches(row.get(field))),
+        _ => true,
+    }
+}
+
+/// Run a stage other than a group
+fn apply(stage: &Stage, rows: &mut Vec<Value>) {
+    match stage {
+        Stage::Match(_) => rows.retain(|row| passes(stage, row)),
+        Stage::Sort { field, descending } => rows.sort_by(|a, b| match (Row::get(a, field), Row::get(b, field)) {
+            (Some(a), Some(b)) => {
+                let order = compare_values(a, b).unwrap_or(Ordering::Equal);
+                if *descending { order.reverse() } else { order }
+            }
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }),
+        Stage::Limit(limit) => rows.truncate(*limit),
+        Stage::Group(_) => unreachable!("groups are run by Pipeline::merge"),
+    }
+}
+
+/// Running state of an accumulator
+#[derive(Debug, Clone)]
+struct State {
+    count: u64,
+    numbers: u64,
+    sum: f64,
+    /// The exact sum while every number is an integer
+    integer_sum: Option<i64>,
+    min: Option<Value>,
+    max: Option<Value>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self { count: 0, numbers: 0, sum: 0.0, integer_sum: Some(0), min: None, max: None }
+    }
+}
+
+impl State {
+    fn add(&mut self, value: Option<&Value>) {
+        self.count += 1;
+        let Some(value) = value else {
+            return;
+        };
+        if let Value::Number(number) = value {
+            self.numbers += 1;
+            self.sum += number.as_f64().unwrap_or(0.0);
+            self.integer_sum = self.integer_sum.zip(number.as_i64()).and_then(|(sum, n)| sum.checked_add(n));
+        }
+        if matches!(value, Value::Number(_) | Value::String(_)) {
+            if self.min.as_ref().is_none_or(|min| compare_values(value, min) == Some(Ordering::Less)) {
+                self.min = Some(value.clone());
+            }
+            if self.max.as_ref().is_none_or(|max| compare_values(value, max) == Some(Ordering::Greater)) {
+                self.max = Some(value.clone());
+            }
+        }
+    }
+
+    /// Fold in the state of the same accumulator over other rows
+    fn merge(&mut self, other: State) {
+        self.count += other.count;
+        self.numbers += other.numbers;
+        self.sum += other.sum;
+        self.integer_sum = self.integer_sum.zip(other.integer_sum).and_then(|(a, b)| a.checked_add(b));
+        if let Some(min) = other.min {
+            if self.min.as_ref().is_none_or(|current| compare_values(&min, current) == Some(Ordering::Less)) {
+                self.min = Some(min);
+            }
+        }
+        if let Some(max) = other.max {
+            if self.max.as_ref().is_none_or(|current| compare_values(&max, current) == Some(Ordering::Greater)) {
+                self.max = Some(max);
+            }
+        }
+    }
+
+    fn finish(self, accumulator: &Accumulator) -> Value {
+        match accumulator {
+            Accumulator::Count => Value::from(self.count),
+            Accumulator::Sum(_) => match self.integer_sum {
+                Some(sum) => Value::from(sum),
+                None => Value::from(self.sum),
+            },
+            Accumulator::Avg(_) if self.numbers == 0 => Value::Null,
+            Accumulator::Avg(_) => Value::from(self.sum / self.numbers as f64),
+            Accumulator::Min(_) => self.min.unwrap_or(Value::Null),
+            Accumulator::Max(_) => self.max.unwrap_or(Value::Null),
+        }
+    }
+}
+
+/// Fold rows into groups
+fn fold<R: Row>(group: &Group, rows: impl IntoIterator<Item = R>) -> Groups {
+    let mut groups = Groups::new();
+    for row in rows {
+        let key = group.key.as_deref().and_then(|field| row.get(field)).cloned().unwrap_or(Value::Null);
+        let (_, states) = groups
+            .entry(key.to_string())
+            .or_insert_with(|| (key, vec![State::default(); group.accumulators.len()]));
+        for ((_, accumulator), state) in group.accumulators.iter().zip(states) {
+            state.add(accumulator.field().and_then(|field| row.get(field)));
+        }
+    }
+    groups
+}
+
+fn merge_groups(groups: &mut Groups, other: Groups) {
+    for (text, (key, states)) in other {
+        match groups.get_mut(&text) {
+            Some((_, current)) => current.iter_mut().zip(states).for_each(|(current, state)| current.merge(state)),
+            None => {
+                groups.insert(text, (key, states));
+            }
+        }
+    }
+}
+
+/// Turn groups into `{"_id": key, <accumulators>}` rows, ordered by key
+fn finish(group: &Group, groups: Groups) -> Vec<Value> {
+    let mut groups: Vec<(Value, Vec<State>)> = groups.into_values().collect();
+    groups.sort_by(|(a, _), (b, _)| compare_values(a, b).unwrap_or_else(|| a.to_string().cmp(&b.to_string())));
+    groups
+        .into_iter()
+        .map(|(key, states)| {
+            let mut row = Map::new();
+            row.insert("_id".to_string(), key);
+            for ((name, accumulator), state) in group.accumulators.iter().zip(states) {
+                row.insert(name.clone(), state.finish(accumulator));
+            }
+            Value::Object(row)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_pipeline_stages() {
+        let rows = vec![
+            json!({"_id": "a", "city": {"name": "Oslo"}, "temp": 3}),
+            json!({"_id": "b", "city": {"name": "Rome"}, "temp": 18.5}),
+            json!({"_id": "c", "city": {"name": "Oslo"}, "temp": -1}),
+            json!({"_id": "d", "city": {"name": "Rome"}}),
+        ];
+        let warmest = Pipeline::new().sort("temp", true).limit(2);
+        let run = |pipeline: &Pipeline, rows: &[Value]| pipeline.merge(vec![pipeline.run_part(rows.to_vec())]);
+        let ids: Vec<Value> = run(&warmest, &rows).into_iter().map(|row| row["_id"].clone()).collect();
+        assert_eq!(ids, [json!("b"), json!("a")]);
+        assert_eq!(warmest.input_fields(), None);
+
+        let by_city = Pipeline::new()
+            .matching(MetadataFilter::new().gt("temp", json!(0)))
+            .group(Group::by("city.name").count("days").avg("mean", "temp"))
+            .group(Group::all().count("cities").max("hottest", "mean"));
+        assert_eq!(by_city.input_fields(), Some(vec!["city.name", "temp"]));
+        let expected = vec![json!({"_id": null, "cities": 2, "hottest": 18.5})];
+        assert_eq!(run(&by_city, &rows), expected);
+
+        // Parts of the input merge into the same result
+        let parts = rows.chunks(1).map(|part| by_city.run_part(part.to_vec())).collect();
+        assert_eq!(by_city.merge(parts), expected);
+        let parts = rows.chunks(3).map(|part| warmest.run_part(part.to_vec())).collect();
+        assert_eq!(warmest.merge(parts), run(&warmest, &rows));
+    }
+}
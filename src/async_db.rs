@@ -0,0 +1,313 @@
+//! Async API for tokio applications (feature `async`)
+//!
+//! [`AsyncDatabase`] wraps a [`Database`] and runs every operation that may
+//! touch the disk or an embedding provider on tokio's blocking thread pool,
+//! so it can be awaited from axum handlers and other async code without
+//! stalling the runtime's worker threads.
+//!
+//! The handle is cheap to clone; all clones share the same database.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let db = AsyncDatabase::open("app.ndb").await?;
+//! let id = db.insert("users", json!({"name": "Alice"})).await?;
+//! let user = db.find_by_id("users", &id).await?;
+//! db.sync().await?;
+//! ```
+
+use crate::error::{KeraDBError, Result};
+use crate::types::{Config, Document, DocumentId, ScanOptions};
+use crate::vector::async_embedding::{AsyncEmbeddingProvider, BlockOnProvider};
+use crate::vector::{
+    Embedding, MetadataFilter, VectorCollectionStats, VectorConfig, VectorDocument, VectorId, VectorSearchResult,
+};
+use crate::Database;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Async handle to a [`Database`]
+#[derive(Clone)]
+pub struct AsyncDatabase {
+    db: Arc<Database>,
+}
+
+impl AsyncDatabase {
+    /// Create a new database file
+    pub async fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::create_with_config(path, Config::default()).await
+    }
+
+    /// Create a new database with custom configuration
+    pub async fn create_with_config<P: AsRef<Path>>(path: P, config: Config) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        Ok(Self::from(blocking(move || Database::create_with_config(path, config)).await?))
+    }
+
+    /// Open an existing database file
+    pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with_config(path, Config::default()).await
+    }
+
+    /// Open an existing database with custom configuration
+    pub async fn open_with_config<P: AsRef<Path>>(path: P, config: Config) -> Result<Self> {
+        let path: PathBuf = path.as_ref().to_path_buf();
+        Ok(Self::from(blocking(move || Database::open_with_config(path, config)).await?))
+    }
+
+    /// Open an existing database file for reading only
+    pub async fn open_read_only<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        Ok(Self::from(blocking(move || Database::open_read_only(path)).await?))
+    }
+
+    /// The underlying synchronous database
+    ///
+    /// Useful for cheap in-memory calls such as [`Database::watch`]; avoid
+    /// calling I/O-bound methods on it from async code.
+    pub fn inner(&self) -> &Arc<Database> {
+        &self.db
+    }
+
+    /// Run `f` against the database on the blocking thread pool
+    async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Database) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let db = self.db.clone();
+        blocking(move || f(&db)).await
+    }
+
+    /// Insert a document into a collection
+    pub async fn insert(&self, collection: &str, data: Value) -> Result<DocumentId> {
+        let collection = collection.to_string();
+        self.run(move |db| db.insert(&collection, data)).await
+    }
+
+    /// Find a document by ID
+    pub async fn find_by_id(&self, collection: &str, doc_id: &str) -> Result<Document> {
+        let (collection, doc_id) = (collection.to_string(), doc_id.to_string());
+        self.run(move |db| db.find_by_id(&collection, &doc_id)).await
+    }
+
+    /// Update a document
+    pub async fn update(&self, collection: &str, doc_id: &str, data: Value) -> Result<Document> {
+        let (collection, doc_id) = (collection.to_string(), doc_id.to_string());
+        self.run(move |db| db.update(&collection, &doc_id, data)).await
+    }
+
+    /// Delete a document
+    pub async fn delete(&self, collection: &str, doc_id: &str) -> Result<Document> {
+        let (collection, doc_id) = (collection.to_string(), doc_id.to_string());
+        self.run(move |db| db.delete(&collection, &doc_id)).await
+    }
+
+    /// Find all documents in a collection
+    pub async fn find_all(&self, collection: &str, limit: Option<usize>, skip: Option<usize>) -> Result<Vec<Document>> {
+        let collection = collection.to_string();
+        self.run(move |db| db.find_all(&collection, limit, skip)).await
+    }
+
+    /// Read the documents in a collection with explicit scan options
+    pub async fn scan(&self, collection: &str, options: ScanOptions) -> Result<Vec<Document>> {
+        let collection = collection.to_string();
+        self.run(move |db| db.scan(&collection, &options)).await
+    }
+
+    /// Count documents in a collection
+    pub async fn count(&self, collection: &str) -> Result<usize> {
+        let collection = collection.to_string();
+        self.run(move |db| Ok(db.count(&collection))).await
+    }
+
+    /// List all collections with their document counts
+    pub async fn list_collections(&self) -> Result<Vec<(String, usize)>> {
+        self.run(|db| Ok(db.list_collections())).await
+    }
+
+    /// Flush all changes, including vector collections, to disk
+    pub async fn sync(&self) -> Result<()> {
+        self.run(|db| db.sync()).await
+    }
+
+    /// Create a new vector collection
+    pub async fn create_vector_collection(&self, name: &str, config: VectorConfig) -> Result<()> {
+        let name = name.to_string();
+        self.run(move |db| db.create_vector_collection(&name, config)).await
+    }
+
+    /// Insert a vector with optional metadata
+    pub async fn insert_vector(&self, collection: &str, vector: Embedding, metadata: Option<Value>) -> Result<VectorId> {
+        let collection = collection.to_string();
+        self.run(move |db| db.insert_vector(&collection, vector, metadata)).await
+    }
+
+    /// Insert or replace a vector under an external ID
+    pub async fn insert_vector_with_id(
+        &self,
+        collection: &str,
+        external_id: &str,
+        vector: Embedding,
+        metadata: Option<Value>,
+    ) -> Result<VectorId> {
+        let (collection, external_id) = (collection.to_string(), external_id.to_string());
+        self.run(move |db| db.insert_vector_with_id(&collection, &external_id, vector, metadata)).await
+    }
+
+    /// Embed `text` with the collection's embedding provider and insert it
+    pub async fn insert_text(&self, collection: &str, text: &str, metadata: Option<Value>) -> Result<VectorId> {
+        let (collection, text) = (collection.to_string(), text.to_string());
+        self.run(move |db| db.insert_text(&collection, &text, metadata)).await
+    }
+
+    /// Embed several texts in one batch with the collection's embedding
+    /// provider and insert them, returning their IDs in order
+    pub async fn insert_texts(&self, collection: &str, texts: Vec<(String, Option<Value>)>) -> Result<Vec<VectorId>> {
+        let collection = collection.to_string();
+        self.run(move |db| db.insert_texts(&collection, texts)).await
+    }
+
+    /// Embed text with an async provider, for new vector collections and
+    /// those already open
+    /// 
+    /// The provider's calls are awaited on the current runtime while text
+    /// operations wait on the blocking thread pool, so a slow network provider
+    /// never stalls the runtime's worker threads. Async providers are not
+    /// stored in the database, so set it again after each open.
+    pub async fn set_embedding_provider(&self, provider: Arc<dyn AsyncEmbeddingProvider>) -> Result<()> {
+        let provider = Arc::new(BlockOnProvider::new(provider, tokio::runtime::Handle::current()));
+        self.run(move |db| {
+            db.attach_embedding_provider(provider);
+            db.store_embedding_provider(None)
+        })
+        .await
+    }
+
+    /// Find the `k` nearest neighbors of `query`
+    pub async fn vector_search(&self, collection: &str, query: Embedding, k: usize) -> Result<Vec<VectorSearchResult>> {
+        let collection = collection.to_string();
+        self.run(move |db| db.vector_search(&collection, &query, k)).await
+    }
+
+    /// Nearest-neighbor search re-ranked by maximal marginal relevance
+    pub async fn vector_search_mmr(
+        &self,
+        collection: &str,
+        query: Embedding,
+        k: usize,
+        lambda: f32,
+    ) -> Result<Vec<VectorSearchResult>> {
+        let collection = collection.to_string();
+        self.run(move |db| db.vector_search_mmr(&collection, &query, k, lambda)).await
+    }
+
+    /// Embed `query` with the collection's embedding provider and search
+    pub async fn vector_search_text(&self, collection: &str, query: &str, k: usize) -> Result<Vec<VectorSearchResult>> {
+        let (collection, query) = (collection.to_string(), query.to_string());
+        self.run(move |db| db.vector_search_text(&collection, &query, k)).await
+    }
+
+    /// Nearest-neighbor search restricted to vectors matching `filter`
+    pub async fn vector_search_filtered(
+        &self,
+        collection: &str,
+        query: Embedding,
+        k: usize,
+        filter: MetadataFilter,
+    ) -> Result<Vec<VectorSearchResult>> {
+        let collection = collection.to_string();
+        self.run(move |db| db.vector_search_filtered(&collection, &query, k, &filter)).await
+    }
+
+    /// Get a vector by ID
+    pub async fn get_vector(&self, collection: &str, id: VectorId) -> Result<Option<VectorDocument>> {
+        let collection = collection.to_string();
+        self.run(move |db| db.get_vector(&collection, id)).await
+    }
+
+    /// Delete a vector by ID
+    pub async fn delete_vector(&self, collection: &str, id: VectorId) -> Result<bool> {
+        let collection = collection.to_string();
+        self.run(move |db| db.delete_vector(&collection, id)).await
+    }
+
+    /// Drop a vector collection
+    pub async fn drop_vector_collection(&self, name: &str) -> Result<bool> {
+        let name = name.to_string();
+        self.run(move |db| db.drop_vector_collection(&name)).await
+    }
+
+    /// List all vector collections with their vector counts
+    pub async fn list_vector_collections(&self) -> Result<Vec<(String, usize)>> {
+        self.run(|db| Ok(db.list_vector_collections())).await
+    }
+
+    /// Get vector collection statistics
+    pub async fn vector_stats(&self, collection: &str) -> Result<VectorCollectionStats> {
+        let collection = collection.to_string();
+        self.run(move |db| db.vector_stats(&collection)).await
+    }
+}
+
+impl From<Database> for AsyncDatabase {
+    /// Wrap a database that has already been configured, e.g. with
+    /// [`Database::set_embedding_provider`]
+    fn from(db: Database) -> Self {
+        Self { db: Arc::new(db) }
+    }
+}
+
+impl From<Arc<Database>> for AsyncDatabase {
+    fn from(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+}
+
+/// Run `f` on the blocking thread pool
+pub(crate) async fn blocking<T, F>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| KeraDBError::StorageError(format!("Blocking task failed: {}", e)))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_async_documents_and_vectors() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.ndb");
+        let db = AsyncDatabase::create(&path).await.unwrap();
+
+        let id = db.insert("users", json!({"name": "Alice"})).await.unwrap();
+        db.update("users", &id, json!({"name": "Alicia"})).await.unwrap();
+        let doc = db.find_by_id("users", &id).await.unwrap();
+        assert_eq!(doc.get("name"), Some(json!("Alicia")));
+        assert_eq!(db.count("users").await.unwrap(), 1);
+
+        db.create_vector_collection("embeddings", VectorConfig::new(3)).await.unwrap();
+        let vid = db.insert_vector("embeddings", vec![1.0, 0.0, 0.0], None).await.unwrap();
+        db.insert_vector("embeddings", vec![0.0, 1.0, 0.0], None).await.unwrap();
+        let results = db.vector_search("embeddings", vec![0.9, 0.1, 0.0], 1).await.unwrap();
+        assert_eq!(results[0].document.id, vid);
+
+        // Clones share the database; the last one dropped closes it
+        let other = db.clone();
+        tokio::spawn(async move { other.delete("users", &id).await.unwrap() }).await.unwrap();
+        db.sync().await.unwrap();
+        drop(db);
+
+        let db = AsyncDatabase::open(&path).await.unwrap();
+        assert_eq!(db.count("users").await.unwrap(), 0);
+        assert_eq!(db.vector_stats("embeddings").await.unwrap().vector_count, 2);
+    }
+}
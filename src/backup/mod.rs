@@ -0,0 +1,311 @@
+//! Full and incremental backups to object storage
+//!
+//! A backup target is anything implementing [`BackupStore`]: a local
+//! directory ([`DirectoryStore`]) or, with the `s3` feature, an S3-compatible
+//! bucket ([`S3Store`]). Each target holds a `manifest.json` listing its
+//! backups in order.
+//!
+//! The first backup to a target is a full dump of every document. Later
+//! backups taken by the same process upload only the changes recorded in the
+//! [oplog](crate::oplog) since the previous one, as long as the oplog still
+//! holds all of them; otherwise a new full backup is taken. The vector sidecar
+//! file is uploaded whenever it differs from the copy in the previous backup.
+//!
+//! Restoring replays the most recent full backup followed by every later
+//! incremental backup.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let store = DirectoryStore::new("/mnt/backups/app");
+//! db.backup_to(&store)?;                  // full
+//! db.insert("users", json!({"n": 1}))?;
+//! db.backup_to(&store)?;                  // incremental
+//!
+//! let restored = Database::restore_from(&store, "restored.ndb")?;
+//! ```
+
+#[cfg(feature = "s3")]
+pub mod s3;
+
+#[cfg(feature = "s3")]
+pub use s3::S3Store;
+
+use crate::error::{KeraDBError, Result};
+use crate::oplog::{apply_change, upsert_document, ChangeEvent};
+use crate::vector::store::VectorStore;
+use crate::Database;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MANIFEST_KEY: &str = "manifest.json";
+
+/// A place backups can be written to and read back from
+pub trait BackupStore {
+    /// Store `data` under `key`, replacing any existing object
+    fn put(&self, key: &str, data: &[u8]) -> Result<()>;
+
+    /// Fetch the object stored under `key`, or `None` if there is none
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+}
+
+/// Stores backups as files in a local directory
+pub struct DirectoryStore {
+    root: PathBuf,
+}
+
+impl DirectoryStore {
+    /// Use `root` (created on first write) as the backup target
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self { root: root.as_ref().to_path_buf() }
+    }
+}
+
+impl BackupStore for DirectoryStore {
+    fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        let path = self.root.join(key);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Write then rename so a crash never leaves a partial manifest
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.root.join(key)) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Whether a backup holds every document or only recent changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupKind {
+    Full,
+    Incremental,
+}
+
+/// One entry in a backup manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupInfo {
+    pub kind: BackupKind,
+    /// Object holding the documents (full) or changes (incremental)
+    pub key: String,
+    /// Object holding the vector sidecar file, if it changed in this backup
+    pub vectors_key: Option<String>,
+    /// CRC32 of the vector sidecar file at backup time (0 if there was none)
+    pub vectors_crc: u32,
+    /// Oplog epoch the sequence numbers belong to
+    pub epoch: String,
+    /// Oplog position the backup starts after (0 for full backups)
+    pub from_seq: u64,
+    /// Oplog position the backup is complete up to
+    pub to_seq: u64,
+    /// Number of documents (full) or changes (incremental)
+    pub records: usize,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    backups: Vec<BackupInfo>,
+}
+
+/// A document as stored in a full backup
+#[derive(Serialize, Deserialize)]
+struct DumpedDocument {
+    collection: String,
+    document: Value,
+}
+
+fn load_manifest(store: &dyn BackupStore) -> Result<Manifest> {
+    match store.get(MANIFEST_KEY)? {
+        Some(data) => Ok(serde_json::from_slice(&data)?),
+        None => Ok(Manifest::default()),
+    }
+}
+
+fn to_jsonl<T: Serialize>(records: &[T]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    for record in records {
+        serde_json::to_writer(&mut out, record)?;
+        out.push(b'\n');
+    }
+    Ok(out)
+}
+
+fn from_jsonl<T: for<'de> Deserialize<'de>>(data: &[u8]) -> Result<Vec<T>> {
+    data.split(|&b| b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| Ok(serde_json::from_slice(line)?))
+        .collect()
+}
+
+/// Back up `db` to `store`; see [`Database::backup_to`]
+pub(crate) fn backup(db: &Database, store: &dyn BackupStore) -> Result<BackupInfo> {
+    let mut manifest = load_manifest(store)?;
+    let oplog = db.oplog();
+
+    // Make sure the vector sidecar file reflects the current collections
+    db.sync()?;
+    let vectors = db.vectors.read()?;
+    let vectors_crc = vectors.as_deref().map_or(0, crc32fast::hash);
+
+    let previous = manifest.backups.last();
+    let incremental_base = previous.filter(|b| b.epoch == oplog.epoch() && oplog.can_resume_from(b.to_seq));
+    let index = manifest.backups.len();
+
+    let (kind, from_seq, to_seq, key, records) = match incremental_base {
+        Some(base) => {
+            let changes: Vec<ChangeEvent> = oplog.since(base.to_seq)?;
+            if changes.is_empty() && base.vectors_crc == vectors_crc {
+                // Nothing changed since the last backup
+                return Ok(base.clone());
+            }
+            let to_seq = changes.last().map_or(base.to_seq, |c| c.seq);
+            let key = format!("{:06}-incremental.jsonl", index);
+            store.put(&key, &to_jsonl(&changes)?)?;
+            (BackupKind::Incremental, base.to_seq, to_seq, key, changes.len())
+        }
+        None => {
+            // Changes racing with the dump are replayed by the next incremental backup
+            let to_seq = oplog.latest_seq();
+            let mut documents = Vec::new();
+            for (collection, _) in db.list_collections() {
+                for doc in db.find_all(&collection, None, None)? {
+                    documents.push(DumpedDocument { collection: collection.clone(), document: doc.to_value() });
+                }
+            }
+            let key = format!("{:06}-full.jsonl", index);
+            store.put(&key, &to_jsonl(&documents)?)?;
+            (BackupKind::Full, 0, to_seq, key, documents.len())
+        }
+    };
+
+    let vectors_changed = kind == BackupKind::Full || previous.is_none_or(|b| b.vectors_crc != vectors_crc);
+    let vectors_key = match vectors {
+        Some(data) if vectors_changed => {
+            let key = format!("{:06}-vectors.bin", index);
+            store.put(&key, &data)?;
+            Some(key)
+        }
+        _ => None,
+    };
+
+    let info = BackupInfo {
+        kind,
+        key,
+        vectors_key,
+        vectors_crc,
+        epoch: oplog.epoch().to_string(),
+        from_seq,
+        to_seq,
+        records,
+        created_at: Utc::now(),
+    };
+    manifest.backups.push(info.clone());
+    // The manifest is written last, so an interrupted backup is simply ignored
+    store.put(MANIFEST_KEY, &serde_json::to_vec_pretty(&manifest)?)?;
+    tracing::info!("{:?} backup {} written ({} records)", info.kind, info.key, info.records);
+    Ok(info)
+}
+
+/// Restore the latest backup chain in `store` to a new database at `path`;
+/// see [`Database::restore_from`]
+pub(crate) fn restore(store: &dyn BackupStore, path: &Path) -> Result<Database> {
+    let manifest = load_manifest(store)?;
+    let start = manifest
+        .backups
+        .iter()
+        .rposition(|b| b.kind == BackupKind::Full)
+        .ok_or_else(|| KeraDBError::NotFound("No full backup in backup store".to_string()))?;
+    let chain = &manifest.backups[start..];
+
+    let fetch = |key: &str| {
+        store
+            .get(key)?
+            .ok_or_else(|| KeraDBError::NotFound(format!("Backup object '{}' is missing", key)))
+    };
+
+    let db = Database::create(path)?;
+    for backup in chain {
+        let data = fetch(&backup.key)?;
+        match backup.kind {
+            BackupKind::Full => {
+                for dumped in from_jsonl::<DumpedDocument>(&data)? {
+                    upsert_document(&db, &dumped.collection, dumped.document)?;
+                }
+            }
+            BackupKind::Incremental => {
+                for change in from_jsonl::<ChangeEvent>(&data)? {
+                    apply_change(&db, &change)?;
+                }
+            }
+        }
+    }
+    db.sync()?;
+    drop(db);
+
+    // Vector collections are loaded from the sidecar file on open. A CRC of 0
+    // means the database had no vector collections at the last backup.
+    let has_vectors = chain.last().is_some_and(|b| b.vectors_crc != 0);
+    if let Some(key) = chain.iter().rev().find_map(|b| b.vectors_key.as_deref()).filter(|_| has_vectors) {
+        VectorStore::File(Database::vector_data_path(path)).write(&fetch(key)?)?;
+    }
+    Database::open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::VectorConfig;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_full_then_incremental_backup_and_restore() {
+        let dir = tempdir().unwrap();
+        let store = DirectoryStore::new(dir.path().join("backups"));
+        let db = Database::create(dir.path().join("test.ndb")).unwrap();
+
+        let alice = db.insert("users", json!({"name": "Alice"})).unwrap();
+        let bob = db.insert("users", json!({"name": "Bob"})).unwrap();
+        db.create_vector_collection("embeddings", VectorConfig::new(3)).unwrap();
+        db.insert_vector("embeddings", vec![1.0, 0.0, 0.0], None).unwrap();
+
+        let full = db.backup_to(&store).unwrap();
+        assert_eq!(full.kind, BackupKind::Full);
+        assert_eq!(full.records, 2);
+        assert!(full.vectors_key.is_some());
+
+        db.update("users", &alice, json!({"name": "Alicia"})).unwrap();
+        db.delete("users", &bob).unwrap();
+        db.insert("orders", json!({"total": 12})).unwrap();
+
+        let incremental = db.backup_to(&store).unwrap();
+        assert_eq!(incremental.kind, BackupKind::Incremental);
+        assert_eq!(incremental.records, 3);
+        assert_eq!(incremental.from_seq, full.to_seq);
+        assert!(incremental.vectors_key.is_none());
+
+        // Nothing changed, so nothing new is written
+        let unchanged = db.backup_to(&store).unwrap();
+        assert_eq!(unchanged.key, incremental.key);
+
+        let restored = Database::restore_from(&store, dir.path().join("restored.ndb")).unwrap();
+        assert_eq!(restored.find_by_id("users", &alice).unwrap().get("name"), Some(json!("Alicia")));
+        assert!(restored.find_by_id("users", &bob).is_err());
+        assert_eq!(restored.count("orders"), 1);
+        assert_eq!(restored.vector_stats("embeddings").unwrap().vector_count, 1);
+    }
+}
@@ -0,0 +1,263 @@
+//! Built-in benchmark workload
+//!
+//! [`run`] creates a scratch database and times a fixed workload against
+//! it: document inserts, point reads by ID, full collection scans, vector
+//! inserts and vector searches. Each operation is timed on its own, so the
+//! report gives latency percentiles as well as throughput. `keradb bench`
+//! prints the report; the criterion suite in `benches/` remains the place
+//! for comparing code changes.
+//!
+//! Data is generated from a fixed seed, so runs on different machines do
+//! the same work.
+
+use crate::error::Result;
+use crate::types::{Config, ScanOptions};
+use crate::Database;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use serde_json::json;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const DOCUMENTS: &str = "bench_documents";
+const VECTORS: &str = "bench_vectors";
+const SEED: u64 = 0x6b65_7261;
+
+/// How much work to do
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    /// Documents to insert
+    pub docs: usize,
+    /// Documents to read back by ID
+    pub reads: usize,
+    /// Full scans of the document collection
+    pub scans: usize,
+    /// Vectors to insert
+    pub vectors: usize,
+    pub dimensions: usize,
+    /// Vector searches to run
+    pub searches: usize,
+    /// Results per vector search
+    pub k: usize,
+    /// Leave the database behind instead of deleting it
+    pub keep: bool,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self { docs: 100_000, reads: 10_000, scans: 10, vectors: 10_000, dimensions: 384, searches: 1_000, k: 10, keep: false }
+    }
+}
+
+/// Throughput and latency of one kind of operation
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseReport {
+    pub operation: &'static str,
+    pub count: usize,
+    /// Wall time, including the sync that ends a write phase
+    pub seconds: f64,
+    pub per_second: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl PhaseReport {
+    fn new(operation: &'static str, mut latencies: Vec<Duration>, elapsed: Duration) -> Self {
+        latencies.sort_unstable();
+        let count = latencies.len();
+        // Nearest-rank percentile
+        let percentile = |p: f64| {
+            let rank = ((p * count as f64).ceil() as usize).clamp(1, count.max(1));
+            latencies.get(rank - 1).map_or(0.0, |d| d.as_secs_f64() * 1000.0)
+        };
+        let seconds = elapsed.as_secs_f64();
+        Self {
+            operation,
+            count,
+            seconds,
+            per_second: if seconds > 0.0 { count as f64 / seconds } else { 0.0 },
+            p50_ms: percentile(0.50),
+            p95_ms: percentile(0.95),
+            p99_ms: percentile(0.99),
+            max_ms: percentile(1.0),
+        }
+    }
+}
+
+/// What a benchmark run measured
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchReport {
+    pub path: PathBuf,
+    pub docs: usize,
+    pub vectors: usize,
+    pub dimensions: usize,
+    pub phases: Vec<PhaseReport>,
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Benchmark on {}: {} documents, {} vectors of {} dimensions",
+            self.path.display(),
+            self.docs,
+            self.vectors,
+            self.dimensions
+        )?;
+        writeln!(f)?;
+        writeln!(f, "{:<14} {:>9} {:>12} {:>10} {:>10} {:>10} {:>10}", "operation", "count", "ops/s", "p50", "p95", "p99", "max")?;
+        for phase in &self.phases {
+            writeln!(
+                f,
+                "{:<14} {:>9} {:>12.0} {:>10} {:>10} {:>10} {:>10}",
+                phase.operation,
+                phase.count,
+                phase.per_second,
+                latency(phase.p50_ms),
+                latency(phase.p95_ms),
+                latency(phase.p99_ms),
+                latency(phase.max_ms)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A latency in milliseconds, in the unit that reads best
+fn latency(ms: f64) -> String {
+    if ms < 1.0 {
+        format!("{:.1}µs", ms * 1000.0)
+    } else if ms < 1000.0 {
+        format!("{:.2}ms", ms)
+    } else {
+        format!("{:.2}s", ms / 1000.0)
+    }
+}
+
+/// Time `count` calls of `op`, then `finish` (a sync, for writes)
+fn phase(
+    operation: &'static str,
+    count: usize,
+    mut op: impl FnMut(usize) -> Result<()>,
+    finish: impl FnOnce() -> Result<()>,
+) -> Result<PhaseReport> {
+    let mut latencies = Vec::with_capacity(count);
+    let start = Instant::now();
+    for i in 0..count {
+        let began = Instant::now();
+        op(i)?;
+        latencies.push(began.elapsed());
+    }
+    finish()?;
+    Ok(PhaseReport::new(operation, latencies, start.elapsed()))
+}
+
+fn random_vector(rng: &mut StdRng, dimensions: usize) -> Vec<f32> {
+    (0..dimensions).map(|_| rng.gen_range(-1.0..1.0)).collect()
+}
+
+/// Create a database at `path`, run the workload against it and report
+///
+/// `progress` is called with each operation's name as it starts. The
+/// database and its vector file are deleted afterwards unless
+/// [`BenchOptions::keep`] is set.
+pub fn run(path: &Path, config: Config, options: &BenchOptions, mut progress: impl FnMut(&str)) -> Result<BenchReport> {
+    let db = Database::create_with_config(path, config)?;
+    let result = run_phases(&db, options, &mut progress);
+    drop(db);
+    if !options.keep {
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(Database::vector_data_path(path));
+    }
+
+    Ok(BenchReport {
+        path: path.to_path_buf(),
+        docs: options.docs,
+        vectors: options.vectors,
+        dimensions: options.dimensions,
+        phases: result?,
+    })
+}
+
+fn run_phases(db: &Database, options: &BenchOptions, progress: &mut impl FnMut(&str)) -> Result<Vec<PhaseReport>> {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let mut phases = Vec::new();
+
+    if options.docs > 0 {
+        progress("insert");
+        let mut ids = Vec::with_capacity(options.docs);
+        let scores: Vec<f64> = (0..options.docs).map(|_| rng.gen()).collect();
+        phases.push(phase(
+            "insert",
+            options.docs,
+            |i| {
+                let document = json!({ "n": i, "name": format!("user{}", i), "group": i % 100, "score": scores[i] });
+                ids.push(db.insert(DOCUMENTS, document)?);
+                Ok(())
+            },
+            || db.sync(),
+        )?);
+
+        progress("find_by_id");
+        let picks: Vec<usize> = (0..options.reads).map(|_| rng.gen_range(0..ids.len())).collect();
+        phases.push(phase("find_by_id", options.reads, |i| db.find_by_id(DOCUMENTS, &ids[picks[i]]).map(drop), || Ok(()))?);
+
+        progress("scan");
+        let scan = ScanOptions::default();
+        phases.push(phase("scan", options.scans, |_| db.scan(DOCUMENTS, &scan).map(drop), || Ok(()))?);
+    }
+
+    if options.vectors > 0 {
+        progress("vector_insert");
+        db.create_vector_collection(VECTORS, db.config().vector_config(options.dimensions))?;
+        let vectors: Vec<Vec<f32>> = (0..options.vectors).map(|_| random_vector(&mut rng, options.dimensions)).collect();
+        let mut vectors = vectors.into_iter();
+        phases.push(phase(
+            "vector_insert",
+            options.vectors,
+            |i| {
+                let vector = vectors.next().unwrap_or_default();
+                db.insert_vector(VECTORS, vector, Some(json!({ "n": i }))).map(drop)
+            },
+            || db.sync(),
+        )?);
+
+        progress("vector_search");
+        let queries: Vec<Vec<f32>> = (0..options.searches).map(|_| random_vector(&mut rng, options.dimensions)).collect();
+        phases.push(phase(
+            "vector_search",
+            options.searches,
+            |i| db.vector_search(VECTORS, &queries[i], options.k).map(drop),
+            || Ok(()),
+        )?);
+    }
+
+    Ok(phases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_bench_run() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("bench.ndb");
+        let options = BenchOptions { docs: 50, reads: 20, scans: 2, vectors: 30, dimensions: 8, searches: 5, k: 3, keep: false };
+
+        let mut started = Vec::new();
+        let report = run(&path, Config::default(), &options, |name| started.push(name.to_string())).unwrap();
+        let counts: Vec<(&str, usize)> = report.phases.iter().map(|p| (p.operation, p.count)).collect();
+        assert_eq!(counts, [("insert", 50), ("find_by_id", 20), ("scan", 2), ("vector_insert", 30), ("vector_search", 5)]);
+        assert_eq!(started, ["insert", "find_by_id", "scan", "vector_insert", "vector_search"]);
+        assert!(report.phases.iter().all(|p| p.p50_ms <= p.p99_ms && p.p99_ms <= p.max_ms));
+        assert!(report.to_string().contains("vector_search"));
+        assert!(!path.exists());
+    }
+}
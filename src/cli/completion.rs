@@ -0,0 +1,146 @@
+//! Tab completion for shell commands
+//!
+//! [`complete`] suggests command names for the first word, collection names
+//! for the second (document or vector collections, depending on the command)
+//! and document IDs or query keywords after that. [`ShellHelper`] plugs it
+//! into rustyline, and also keeps Enter from submitting a command while a
+//! JSON object or array is still open.
+
+use super::parser;
+use crate::types::ScanOptions;
+use crate::Database;
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Context, Helper};
+use std::sync::Arc;
+
+/// Every shell command
+pub const COMMANDS: &[&str] = &[
+    "collections", "count", "delete", "exit", "explain", "find", "help", "insert", "quit", "stats", "sync", "update",
+    "vcollections", "vcreate", "vdrop", "vinsert", "voptimize", "vsearch", "vstats",
+];
+
+/// Commands whose first argument is a document collection
+const DOCUMENT_COMMANDS: &[&str] = &["count", "delete", "find", "insert", "update"];
+/// Commands whose first argument is a vector collection
+const VECTOR_COMMANDS: &[&str] = &["vdrop", "vinsert", "voptimize", "vsearch", "vstats"];
+/// Commands whose second argument is a document ID
+const ID_COMMANDS: &[&str] = &["delete", "find", "update"];
+
+const QUERY_KEYWORDS: &[&str] = &["and", "asc", "collate", "desc", "limit", "skip", "sort", "where"];
+
+/// Documents read when looking for matching IDs
+const ID_SCAN_LIMIT: usize = 10_000;
+/// Most candidates offered at once
+const MAX_CANDIDATES: usize = 100;
+
+/// Complete the word before `pos` in `line`
+///
+/// Returns the byte offset where that word starts and the sorted candidates
+/// that begin with it. Only the first few thousand documents of a collection
+/// are searched for IDs.
+pub fn complete(db: &Database, line: &str, pos: usize) -> (usize, Vec<String>) {
+    let before = &line[..pos];
+    let start = before.rfind([' ', '\t', '\n']).map_or(0, |i| i + 1);
+    let prefix = &before[start..];
+    let words: Vec<&str> = before[..start].split_whitespace().collect();
+
+    // `explain find ...` completes like `find ...`
+    let explain = words.first() == Some(&"explain");
+    let words = if explain { &words[1..] } else { &words[..] };
+
+    let names = |collections: Vec<(String, usize)>| collections.into_iter().map(|(name, _)| name).collect();
+    let keywords = || QUERY_KEYWORDS.iter().map(|k| k.to_string());
+    let mut candidates: Vec<String> = match words {
+        [] if explain => vec!["find".to_string()],
+        [] => COMMANDS.iter().map(|c| c.to_string()).collect(),
+        [command] if DOCUMENT_COMMANDS.contains(command) => names(db.list_collections()),
+        [command] if VECTOR_COMMANDS.contains(command) => names(db.list_vector_collections()),
+        [command, collection] if ID_COMMANDS.contains(command) => {
+            let mut ids = document_ids(db, collection, prefix);
+            if *command == "find" {
+                ids.extend(keywords());
+            }
+            ids
+        }
+        ["find" | "count" | "vsearch", _, ..] => keywords().collect(),
+        _ => Vec::new(),
+    };
+
+    candidates.retain(|c| c.starts_with(prefix));
+    candidates.sort();
+    candidates.dedup();
+    candidates.truncate(MAX_CANDIDATES);
+    (start, candidates)
+}
+
+fn document_ids(db: &Database, collection: &str, prefix: &str) -> Vec<String> {
+    let options = ScanOptions { limit: Some(ID_SCAN_LIMIT), ordered: true, ..Default::default() };
+    db.scan(collection, &options)
+        .map(|docs| docs.into_iter().map(|doc| doc.id).filter(|id| id.starts_with(prefix)).collect())
+        .unwrap_or_default()
+}
+
+/// rustyline helper completing against an open database
+pub struct ShellHelper {
+    db: Arc<Database>,
+}
+
+impl ShellHelper {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(&self.db, line, pos))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {
+    fn validate(&self, ctx: &mut ValidationContext<'_>) -> rustyline::Result<ValidationResult> {
+        Ok(if parser::is_incomplete(ctx.input()) { ValidationResult::Incomplete } else { ValidationResult::Valid(None) })
+    }
+}
+
+impl Helper for ShellHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::VectorConfig;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_complete() {
+        let dir = tempdir().unwrap();
+        let db = Database::create(dir.path().join("test.ndb")).unwrap();
+        db.insert("users", json!({"_id": "alice"})).unwrap();
+        db.insert("users", json!({"_id": "bob"})).unwrap();
+        db.insert("orders", json!({})).unwrap();
+        db.create_vector_collection("embeddings", VectorConfig::new(3)).unwrap();
+
+        let complete = |line: &str| complete(&db, line, line.len());
+        assert_eq!(complete("vc"), (0, vec!["vcollections".to_string(), "vcreate".to_string()]));
+        assert_eq!(complete("find u"), (5, vec!["users".to_string()]));
+        assert_eq!(complete("vsearch "), (8, vec!["embeddings".to_string()]));
+        assert_eq!(complete("delete users a"), (13, vec!["alice".to_string()]));
+        assert_eq!(complete("find users b"), (11, vec!["bob".to_string()]));
+        assert_eq!(complete("explain f"), (8, vec!["find".to_string()]));
+        assert_eq!(complete("explain find u"), (13, vec!["users".to_string()]));
+        assert_eq!(complete("find users where age > 3 s").1, ["skip", "sort"]);
+        assert!(complete("insert users {").1.is_empty());
+    }
+}
@@ -1,7 +1,11 @@
+pub mod completion;
+pub mod output;
+pub mod parser;
 pub mod repl;
 pub mod tui;
 pub mod system_db;
 
+pub use output::OutputFormat;
 pub use repl::Repl;
 pub use tui::TuiApp;
 pub use system_db::SystemDatabase;
@@ -0,0 +1,220 @@
+//! Output formats for CLI results
+//!
+//! Every `keradb` subcommand and the shell take `--output table|json|ndjson`.
+//! `table` is meant for people; `json` prints each result as one pretty JSON
+//! document and `ndjson` prints one compact JSON value per line (one per
+//! element for lists), so results can be piped into `jq`.
+//!
+//! In the JSON formats errors are written to stderr as
+//! `{"error": "...", "kind": "not_found", "code": 3}`, where `code` is also
+//! the process exit status:
+//!
+//! | code | kind                     | cause                                   |
+//! |------|--------------------------|-----------------------------------------|
+//! | 1    | `error`, `io`            | anything else                           |
+//! | 2    | `invalid_input`          | bad query, document or configuration    |
+//! | 3    | `not_found`              | missing database, collection or document|
+//! | 4    | `conflict`               | collection or key already exists        |
+//! | 5    | `locked`, `read_only`    | database cannot be written              |
+
+use crate::error::KeraDBError;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+
+/// Longest cell printed in a table, in characters
+const MAX_CELL: usize = 60;
+
+/// How results are printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Ndjson,
+}
+
+impl OutputFormat {
+    /// Print `value` as JSON, or call `table` to print it for people
+    ///
+    /// JSON is written without panicking on a closed pipe, so a failed write
+    /// (e.g. to `head`) comes back as an [`std::io::Error`].
+    pub fn print<T: Serialize>(self, value: &T, table: impl FnOnce()) -> anyhow::Result<()> {
+        let mut out = std::io::stdout().lock();
+        match self {
+            OutputFormat::Table => table(),
+            OutputFormat::Json => writeln!(out, "{}", serde_json::to_string_pretty(value)?)?,
+            OutputFormat::Ndjson => match serde_json::to_value(value)? {
+                Value::Array(items) => {
+                    for item in items {
+                        writeln!(out, "{}", item)?;
+                    }
+                }
+                value => writeln!(out, "{}", value)?,
+            },
+        }
+        Ok(())
+    }
+
+    /// Print `value`, as a table from [`render_table`] in the table format
+    pub fn print_value<T: Serialize>(self, value: &T) -> anyhow::Result<()> {
+        let rendered = match self {
+            OutputFormat::Table => render_table(&serde_json::to_value(value)?),
+            _ => String::new(),
+        };
+        self.print(value, || println!("{}", rendered))
+    }
+
+    /// Print an error to stderr
+    ///
+    /// A closed stdout is not reported: the reader has stopped listening.
+    pub fn print_error(self, err: &anyhow::Error) {
+        if is_broken_pipe(err) {
+            return;
+        }
+        match self {
+            OutputFormat::Table => eprintln!("Error: {:#}", err),
+            _ => {
+                let (code, kind) = classify(err);
+                eprintln!("{}", json!({ "error": format!("{:#}", err), "kind": kind, "code": code }));
+            }
+        }
+    }
+}
+
+/// Process exit status for a failed command
+pub fn exit_code(err: &anyhow::Error) -> i32 {
+    if is_broken_pipe(err) {
+        return 0;
+    }
+    classify(err).0
+}
+
+fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe)
+}
+
+fn classify(err: &anyhow::Error) -> (i32, &'static str) {
+    let Some(err) = err.downcast_ref::<KeraDBError>() else {
+        return match err.downcast_ref::<std::io::Error>() {
+            Some(_) => (1, "io"),
+            None => (1, "error"),
+        };
+    };
+    match err {
+        KeraDBError::InvalidQuery(_)
+        | KeraDBError::InvalidDocument(_)
+        | KeraDBError::ParseError(_)
+        | KeraDBError::Config(_) => (2, "invalid_input"),
+        KeraDBError::DocumentTooLarge { .. } | KeraDBError::QuotaExceeded(_) => (6, "quota_exceeded"),
+        KeraDBError::CollectionNotFound(_)
+        | KeraDBError::DocumentNotFound(_)
+        | KeraDBError::DatabaseNotFound(_)
+        | KeraDBError::NotFound(_) => (3, "not_found"),
+        KeraDBError::CollectionExists(_) | KeraDBError::DuplicateKey(_) | KeraDBError::ReferenceViolation(_) => {
+            (4, "conflict")
+        }
+        KeraDBError::Locked(_) => (5, "locked"),
+        KeraDBError::ReadOnly => (5, "read_only"),
+        KeraDBError::Io(_) => (1, "io"),
+        _ => (1, "error"),
+    }
+}
+
+/// Render a JSON value as a plain-text table
+///
+/// A list of objects gets a row per object and a column per field, with
+/// `_id` first; an object gets a row per field. Strings are printed without
+/// quotes, nested values as compact JSON, and long cells are cut short.
+pub fn render_table(value: &Value) -> String {
+    match value {
+        Value::Array(rows) if rows.is_empty() => "(none)".to_string(),
+        Value::Array(rows) if rows.iter().all(Value::is_object) => {
+            // Sorted rather than in map order, which is insertion order when
+            // serde_json's preserve_order is enabled (bson enables it)
+            let keys: BTreeSet<&str> = rows
+                .iter()
+                .filter_map(Value::as_object)
+                .flat_map(|row| row.keys().map(String::as_str))
+                .collect();
+            let mut columns: Vec<&str> = keys.into_iter().collect();
+            if let Some(i) = columns.iter().position(|c| *c == "_id") {
+                let id = columns.remove(i);
+                columns.insert(0, id);
+            }
+            let body = rows.iter().map(|row| columns.iter().map(|c| cell(row.get(*c))).collect()).collect();
+            layout(Some(columns.iter().map(|c| c.to_string()).collect()), body)
+        }
+        Value::Array(items) => items.iter().map(|v| cell(Some(v))).collect::<Vec<_>>().join("\n"),
+        Value::Object(fields) => {
+            let sorted: BTreeMap<&String, &Value> = fields.iter().collect();
+            layout(None, sorted.into_iter().map(|(k, v)| vec![k.clone(), cell(Some(v))]).collect())
+        }
+        other => cell(Some(other)),
+    }
+}
+
+fn cell(value: Option<&Value>) -> String {
+    let text = match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    };
+    if text.chars().count() > MAX_CELL {
+        format!("{}…", text.chars().take(MAX_CELL - 1).collect::<String>())
+    } else {
+        text
+    }
+}
+
+/// Align `rows` into columns under an optional header
+fn layout(header: Option<Vec<String>>, rows: Vec<Vec<String>>) -> String {
+    let all: Vec<&Vec<String>> = header.iter().chain(&rows).collect();
+    let columns = all.iter().map(|row| row.len()).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|i| all.iter().filter_map(|row| row.get(i)).map(|c| c.chars().count()).max().unwrap_or(0))
+        .collect();
+
+    let line = |row: &[String]| {
+        let cells: Vec<String> = row.iter().zip(&widths).map(|(c, w)| format!("{:<w$}", c, w = *w)).collect();
+        cells.join("  ").trim_end().to_string()
+    };
+    let mut lines = Vec::new();
+    if let Some(header) = &header {
+        lines.push(line(header));
+        lines.push(widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>().join("  "));
+    }
+    lines.extend(rows.iter().map(|row| line(row)));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_table() {
+        let rows = json!([
+            {"name": "Alice", "_id": "a1", "age": 30},
+            {"_id": "b2", "name": "Bob", "tags": ["x"]},
+        ]);
+        let table = render_table(&rows);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0].split_whitespace().collect::<Vec<_>>(), ["_id", "age", "name", "tags"]);
+        assert_eq!(lines[2], "a1   30   Alice");
+        assert_eq!(lines[3], "b2        Bob    [\"x\"]");
+
+        assert_eq!(render_table(&json!({"count": 2, "collection": "users"})), "collection  users\ncount       2");
+        assert_eq!(render_table(&json!([])), "(none)");
+    }
+
+    #[test]
+    fn test_errors_map_to_exit_codes() {
+        let code = |err: KeraDBError| exit_code(&anyhow::Error::from(err));
+        assert_eq!(code(KeraDBError::ParseError("x".into())), 2);
+        assert_eq!(code(KeraDBError::DocumentNotFound("x".into())), 3);
+        assert_eq!(code(KeraDBError::ReadOnly), 5);
+        assert_eq!(exit_code(&anyhow::anyhow!("other")), 1);
+    }
+}
@@ -0,0 +1,564 @@
+//! Query language shared by the shell, the TUI and `keradb query`
+//!
+//! ```text
+//! find <collection> [<id>] [where <cond> [and <cond>]...] [sort <field> [asc|desc]] [limit <n>] [skip <n>] [collate <collation>]
+//! count <collection> [where ...] [collate <collation>]
+//! explain find <collection> ...
+//! insert <collection> <json>
+//! update <collection> <id> <json>
+//! delete <collection> <id>
+//! collections | sync | stats | help
+//!
+//! vcreate <name> <dimensions> [distance]
+//! vinsert <collection> <json-vector> [json-metadata]
+//! vsearch <collection> (<json-vector> | "text") [k] [where ...]
+//! vcollections | vstats [<collection>] | voptimize <collection> | vdrop <collection>
+//! ```
+//!
+//! A condition is `<field> <op> <value>` with `op` one of `=`, `!=`, `>`,
+//! `>=`, `<`, `<=`, `in`, `not in`, `contains`, `startswith` or `endswith`.
+//! Fields may be dotted paths into nested objects. Values are JSON literals;
+//! a bare word that is not valid JSON is taken as a string, so
+//! `find users where name = Alice and age > 30` needs no quotes.
+//!
+//! `collate binary|nocase|unicode` sets how strings compare in the conditions
+//! and the sort; see [`crate::collation`].
+
+use crate::collation::Collation;
+use crate::error::{KeraDBError, Result};
+use crate::vector::{Distance, Embedding, FilterCondition, VectorSearchResult};
+use crate::Database;
+use serde_json::Value;
+
+pub use crate::query::{Filter, FindQuery};
+
+/// A parsed command
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Help,
+    Collections,
+    Sync,
+    Stats,
+    Find(FindQuery),
+    /// Show how a `find` would run, without running it
+    Explain(FindQuery),
+    Count { collection: String, filter: Filter },
+    Insert { collection: String, document: Value },
+    Update { collection: String, id: String, document: Value },
+    Delete { collection: String, id: String },
+    VectorCreate { name: String, dimensions: usize, distance: Option<Distance> },
+    VectorInsert { collection: String, vector: Embedding, metadata: Option<Value> },
+    VectorSearch { collection: String, query: VectorQuery, k: usize, filter: Filter },
+    VectorCollections,
+    VectorStats { collection: Option<String> },
+    VectorOptimize { collection: String },
+    VectorDrop { collection: String },
+}
+
+/// What a `vsearch` searches for
+#[derive(Debug, Clone, PartialEq)]
+pub enum VectorQuery {
+    Vector(Embedding),
+    /// Embedded with the collection's embedding provider
+    Text(String),
+}
+
+impl VectorQuery {
+    /// Find the `k` nearest neighbors in `collection` that match `filter`
+    pub fn search(&self, db: &Database, collection: &str, k: usize, filter: &Filter) -> Result<Vec<VectorSearchResult>> {
+        match self {
+            VectorQuery::Vector(vector) if filter.is_empty() => db.vector_search(collection, vector, k),
+            VectorQuery::Vector(vector) => db.vector_search_filtered(collection, vector, k, &filter.to_metadata_filter()?),
+            VectorQuery::Text(text) if filter.is_empty() => db.vector_search_text(collection, text, k),
+            VectorQuery::Text(_) => Err(KeraDBError::InvalidQuery(
+                "Text searches cannot be filtered; search with a vector instead".to_string(),
+            )),
+        }
+    }
+}
+
+/// Default number of `vsearch` results
+const DEFAULT_K: usize = 10;
+
+/// Parse one command
+pub fn parse(input: &str) -> Result<Command> {
+    let mut parser = Parser { tokens: tokenize(input)?, pos: 0 };
+    let command = parser.command()?;
+    match parser.tokens.get(parser.pos) {
+        Some(token) => Err(parse_error(format!("Unexpected {}", token))),
+        None => Ok(command),
+    }
+}
+
+/// Whether `input` leaves a JSON object or array open
+///
+/// Interactive front ends keep reading lines until this is false, so
+/// pretty-printed JSON can span several lines.
+pub fn is_incomplete(input: &str) -> bool {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in input.chars() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' if depth > 0 => in_string = true,
+            '{' | '[' => depth += 1,
+            // A stray closing bracket is a parse error, not a reason to wait
+            '}' | ']' if depth == 0 => return false,
+            '}' | ']' => depth -= 1,
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+fn parse_error(message: impl Into<String>) -> KeraDBError {
+    KeraDBError::ParseError(message.into())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    /// A quoted string
+    Str(String),
+    /// A JSON object or array
+    Json(Value),
+    /// A comparison operator
+    Op(&'static str),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Word(w) => write!(f, "'{}'", w),
+            Token::Str(s) => write!(f, "\"{}\"", s),
+            Token::Json(v) => write!(f, "{}", v),
+            Token::Op(op) => write!(f, "'{}'", op),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = input.trim_start();
+
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            '"' | '\'' => {
+                let end = rest[1..]
+                    .find(c)
+                    .ok_or_else(|| parse_error(format!("Unterminated string: {}", rest)))?;
+                tokens.push(Token::Str(rest[1..1 + end].to_string()));
+                end + 2
+            }
+            '{' | '[' => {
+                let len = json_len(rest)?;
+                let value = serde_json::from_str(&rest[..len])
+                    .map_err(|e| parse_error(format!("Invalid JSON {}: {}", &rest[..len], e)))?;
+                tokens.push(Token::Json(value));
+                len
+            }
+            '=' | '!' | '<' | '>' => {
+                let len = rest.find(|c| !"=!<>".contains(c)).unwrap_or(rest.len());
+                let op = match &rest[..len] {
+                    "=" | "==" => "=",
+                    "!=" => "!=",
+                    ">" => ">",
+                    ">=" => ">=",
+                    "<" => "<",
+                    "<=" => "<=",
+                    other => return Err(parse_error(format!("Unknown operator '{}'", other))),
+                };
+                tokens.push(Token::Op(op));
+                len
+            }
+            _ => {
+                let len = rest
+                    .find(|c: char| c.is_whitespace() || "=!<>\"'{[".contains(c))
+                    .unwrap_or(rest.len());
+                tokens.push(Token::Word(rest[..len].to_string()));
+                len
+            }
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Length of the JSON object or array at the start of `s`
+fn json_len(s: &str) -> Result<usize> {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    Err(parse_error(format!("Unterminated JSON: {}", s)))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Consume the next token if it is the keyword `keyword`
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.tokens.get(self.pos) {
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    /// A name or ID, quoted or not
+    fn word(&mut self, what: &str) -> Result<String> {
+        match self.next() {
+            Some(Token::Word(w)) | Some(Token::Str(w)) => Ok(w),
+            Some(token) => Err(parse_error(format!("Expected {}, got {}", what, token))),
+            None => Err(parse_error(format!("Expected {}", what))),
+        }
+    }
+
+    fn optional_word(&mut self) -> Option<String> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Word(_)) | Some(Token::Str(_)) => self.word("").ok(),
+            _ => None,
+        }
+    }
+
+    fn number(&mut self, what: &str) -> Result<usize> {
+        let word = self.word(what)?;
+        word.parse().map_err(|_| parse_error(format!("Expected {}, got '{}'", what, word)))
+    }
+
+    fn json(&mut self, what: &str) -> Result<Value> {
+        match self.next() {
+            Some(Token::Json(value)) => Ok(value),
+            Some(token) => Err(parse_error(format!("Expected {} as JSON, got {}", what, token))),
+            None => Err(parse_error(format!("Expected {} as JSON", what))),
+        }
+    }
+
+    fn object(&mut self, what: &str) -> Result<Value> {
+        match self.json(what)? {
+            value @ Value::Object(_) => Ok(value),
+            value => Err(parse_error(format!("Expected {} as a JSON object, got {}", what, value))),
+        }
+    }
+
+    fn vector(&mut self) -> Result<Embedding> {
+        serde_json::from_value(self.json("a vector")?).map_err(|e| parse_error(format!("Invalid vector: {}", e)))
+    }
+
+    /// A literal: JSON, a quoted string, or a bare word (JSON if it parses, else a string)
+    fn value(&mut self) -> Result<Value> {
+        match self.next() {
+            Some(Token::Json(value)) => Ok(value),
+            Some(Token::Str(s)) => Ok(Value::String(s)),
+            Some(Token::Word(w)) => Ok(serde_json::from_str(&w).unwrap_or(Value::String(w))),
+            Some(token) => Err(parse_error(format!("Expected a value, got {}", token))),
+            None => Err(parse_error("Expected a value")),
+        }
+    }
+
+    fn command(&mut self) -> Result<Command> {
+        let name = self.word("a command")?.to_lowercase();
+        let command = match name.as_str() {
+            "help" => Command::Help,
+            "collections" | "list" => Command::Collections,
+            "sync" => Command::Sync,
+            "stats" => Command::Stats,
+            "find" => Command::Find(self.find()?),
+            "explain" if self.keyword("find") => Command::Explain(self.find()?),
+            "explain" => return Err(parse_error("Expected 'find' after 'explain'")),
+            "count" => {
+                let collection = self.word("a collection")?;
+                let mut filter = self.optional_where()?;
+                if self.keyword("collate") {
+                    filter.collation = Some(self.collation()?);
+                }
+                Command::Count { collection, filter }
+            }
+            "insert" => Command::Insert { collection: self.word("a collection")?, document: self.object("a document")? },
+            "update" => Command::Update {
+                collection: self.word("a collection")?,
+                id: self.word("a document ID")?,
+                document: self.object("a document")?,
+            },
+            "delete" => Command::Delete { collection: self.word("a collection")?, id: self.word("a document ID")? },
+            "vcreate" => {
+                let name = self.word("a collection")?;
+                let dimensions = self.number("dimensions")?;
+                let distance = match self.optional_word() {
+                    Some(d) => Some(Distance::from_name(&d).ok_or_else(|| parse_error(format!("Unknown distance metric '{}'", d)))?),
+                    None => None,
+                };
+                Command::VectorCreate { name, dimensions, distance }
+            }
+            "vinsert" => {
+                let collection = self.word("a collection")?;
+                let vector = self.vector()?;
+                let metadata = if self.at_end() { None } else { Some(self.object("metadata")?) };
+                Command::VectorInsert { collection, vector, metadata }
+            }
+            "vsearch" => self.vector_search()?,
+            "vcollections" => Command::VectorCollections,
+            "vstats" => Command::VectorStats { collection: self.optional_word() },
+            "voptimize" => Command::VectorOptimize { collection: self.word("a collection")? },
+            "vdrop" => Command::VectorDrop { collection: self.word("a collection")? },
+            _ => return Err(parse_error(format!("Unknown command '{}'", name))),
+        };
+        Ok(command)
+    }
+
+    fn find(&mut self) -> Result<FindQuery> {
+        let collection = self.word("a collection")?;
+        let id = match self.tokens.get(self.pos) {
+            Some(Token::Word(w)) if !["where", "sort", "limit", "skip", "collate"].contains(&w.to_lowercase().as_str()) => {
+                self.optional_word()
+            }
+            Some(Token::Str(_)) => self.optional_word(),
+            _ => None,
+        };
+
+        let mut query = FindQuery { collection, id, filter: Filter::default(), sort: None, limit: None, skip: 0 };
+        while !self.at_end() {
+            if self.keyword("where") {
+                query.filter.conditions.extend(self.conditions()?.conditions);
+            } else if self.keyword("sort") {
+                let field = self.word("a field to sort by")?;
+                let descending = if self.keyword("desc") {
+                    true
+                } else {
+                    self.keyword("asc");
+                    false
+                };
+                query.sort = Some((field, descending));
+            } else if self.keyword("limit") {
+                query.limit = Some(self.number("a limit")?);
+            } else if self.keyword("skip") {
+                query.skip = self.number("a number to skip")?;
+            } else if self.keyword("collate") {
+                query.filter.collation = Some(self.collation()?);
+            } else {
+                break;
+            }
+        }
+        Ok(query)
+    }
+
+    fn collation(&mut self) -> Result<Collation> {
+        let name = self.word("a collation")?;
+        Collation::from_name(&name)
+            .ok_or_else(|| parse_error(format!("Unknown collation '{}'; use binary, nocase or unicode", name)))
+    }
+
+    fn vector_search(&mut self) -> Result<Command> {
+        let collection = self.word("a collection")?;
+        // `--text` is accepted for compatibility with earlier `keradb query` syntax
+        let text = self.keyword("--text");
+        let query = match self.tokens.get(self.pos) {
+            _ if text => VectorQuery::Text(self.word("query text")?),
+            Some(Token::Json(_)) => VectorQuery::Vector(self.vector()?),
+            Some(Token::Str(_)) => VectorQuery::Text(self.word("query text")?),
+            _ => return Err(parse_error("Expected a JSON vector or quoted text to search for")),
+        };
+        let k = match self.tokens.get(self.pos) {
+            Some(Token::Word(w)) if !w.eq_ignore_ascii_case("where") => self.number("k")?,
+            _ => DEFAULT_K,
+        };
+        Ok(Command::VectorSearch { collection, query, k, filter: self.optional_where()? })
+    }
+
+    fn optional_where(&mut self) -> Result<Filter> {
+        if self.keyword("where") {
+            self.conditions()
+        } else {
+            Ok(Filter::default())
+        }
+    }
+
+    fn conditions(&mut self) -> Result<Filter> {
+        let mut filter = Filter::default();
+        loop {
+            filter.conditions.push(self.condition()?);
+            if !self.keyword("and") {
+                return Ok(filter);
+            }
+        }
+    }
+
+    fn condition(&mut self) -> Result<(String, FilterCondition)> {
+        let field = self.word("a field")?;
+        let list = |value: Value| match value {
+            Value::Array(values) => Ok(values),
+            other => Err(parse_error(format!("Expected a JSON array after 'in', got {}", other))),
+        };
+        let text = |value: Value| match value {
+            Value::String(s) => s,
+            other => other.to_string(),
+        };
+
+        let condition = match self.next() {
+            Some(Token::Op(op)) => {
+                let value = self.value()?;
+                match op {
+                    "=" => FilterCondition::Eq(value),
+                    "!=" => FilterCondition::Ne(value),
+                    ">" => FilterCondition::Gt(value),
+                    ">=" => FilterCondition::Gte(value),
+                    "<" => FilterCondition::Lt(value),
+                    _ => FilterCondition::Lte(value),
+                }
+            }
+            Some(Token::Word(w)) => match w.to_lowercase().as_str() {
+                "in" => FilterCondition::In(list(self.value()?)?),
+                "not" if self.keyword("in") => FilterCondition::NotIn(list(self.value()?)?),
+                "contains" => FilterCondition::Contains(text(self.value()?)),
+                "startswith" => FilterCondition::StartsWith(text(self.value()?)),
+                "endswith" => FilterCondition::EndsWith(text(self.value()?)),
+                _ => return Err(parse_error(format!("Expected an operator after '{}', got '{}'", field, w))),
+            },
+            Some(token) => return Err(parse_error(format!("Expected an operator after '{}', got {}", field, token))),
+            None => return Err(parse_error(format!("Expected an operator after '{}'", field))),
+        };
+        Ok((field, condition))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_commands() {
+        let Command::Find(query) = parse("find users where age > 30 and name != 'Bob' sort age desc limit 10 skip 5").unwrap()
+        else {
+            panic!("expected find");
+        };
+        assert_eq!(query.collection, "users");
+        assert_eq!(query.id, None);
+        assert_eq!(
+            query.filter.conditions,
+            vec![
+                ("age".to_string(), FilterCondition::Gt(json!(30))),
+                ("name".to_string(), FilterCondition::Ne(json!("Bob"))),
+            ]
+        );
+        assert_eq!((query.sort, query.limit, query.skip), (Some(("age".to_string(), true)), Some(10), 5));
+
+        assert_eq!(
+            parse(r#"insert users {"name": "Alice Smith", "tags": ["a b"]}"#).unwrap(),
+            Command::Insert { collection: "users".into(), document: json!({"name": "Alice Smith", "tags": ["a b"]}) }
+        );
+        assert_eq!(
+            parse(r#"vsearch docs "hello world" 3 where lang in ["en", "de"]"#).unwrap(),
+            Command::VectorSearch {
+                collection: "docs".into(),
+                query: VectorQuery::Text("hello world".into()),
+                k: 3,
+                filter: Filter {
+                    conditions: vec![("lang".into(), FilterCondition::In(vec![json!("en"), json!("de")]))],
+                    collation: None,
+                },
+            }
+        );
+        assert!(matches!(parse("vsearch docs [1, 0.5]").unwrap(), Command::VectorSearch { k: DEFAULT_K, .. }));
+        assert!(matches!(
+            parse("vsearch docs --text hello 5").unwrap(),
+            Command::VectorSearch { query: VectorQuery::Text(_), k: 5, .. }
+        ));
+
+        let pasted = "insert users {\n  \"name\": \"a } b\",\n  \"tags\": [\n    \"x\"\n  ]\n}";
+        assert!(is_incomplete(&pasted[..pasted.len() - 1]));
+        assert!(!is_incomplete(pasted) && !is_incomplete("find users"));
+        assert_eq!(
+            parse(pasted).unwrap(),
+            Command::Insert { collection: "users".into(), document: json!({"name": "a } b", "tags": ["x"]}) }
+        );
+
+        let Command::Explain(query) = parse("explain find users where age > 3 limit 3").unwrap() else {
+            panic!("expected explain");
+        };
+        assert_eq!((query.collection.as_str(), query.filter.conditions.len(), query.limit), ("users", 1, Some(3)));
+        assert!(parse("explain count users").is_err());
+
+        let Command::Find(query) = parse("find users where name = bob collate nocase limit 1").unwrap() else {
+            panic!("expected find");
+        };
+        assert_eq!((query.filter.collation, query.limit), (Some(Collation::NoCase), Some(1)));
+        assert!(matches!(
+            parse("count users where name = é collate unicode").unwrap(),
+            Command::Count { filter: Filter { collation: Some(Collation::Unicode), .. }, .. }
+        ));
+        assert!(parse("find users collate klingon").is_err());
+
+        assert!(parse("find users where age >> 3").is_err());
+        assert!(parse("find users where").is_err());
+        assert!(parse("count users extra").is_err());
+        assert!(parse("frobnicate").is_err());
+    }
+
+    #[test]
+    fn test_find_filters_and_sorts() {
+        let dir = tempdir().unwrap();
+        let db = Database::create(dir.path().join("test.ndb")).unwrap();
+        for (name, age) in [("Alice", 30), ("Bob", 25), ("Carol", 41), ("Dan", 35)] {
+            db.insert("users", json!({"name": name, "age": age, "address": {"city": "Oslo"}})).unwrap();
+        }
+
+        let Command::Find(query) = parse("find users where age >= 30 and address.city = Oslo sort age desc limit 2").unwrap()
+        else {
+            panic!("expected find");
+        };
+        let names: Vec<_> = query.run(&db).unwrap().iter().map(|d| d.get("name").unwrap()).collect();
+        assert_eq!(names, vec![json!("Carol"), json!("Dan")]);
+
+        let Command::Count { collection, filter } = parse("count users where name startswith A").unwrap() else {
+            panic!("expected count");
+        };
+        assert_eq!(filter.count(&db, &collection).unwrap(), 1);
+    }
+}
@@ -1,30 +1,69 @@
 use crate::Database;
-use crate::vector::{VectorConfig, Distance};
+use crate::types::Config;
+use crate::vector::Distance;
+use super::completion::ShellHelper;
+use super::output::OutputFormat;
+use super::parser::{self, Command, Filter, FindQuery, VectorQuery};
+use super::system_db::keradb_home;
+use serde_json::json;
 use rustyline::error::ReadlineError;
-use rustyline::DefaultEditor;
-use std::path::Path;
+use rustyline::history::FileHistory;
+use rustyline::{CompletionType, Editor};
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Lines kept in the history file
+const HISTORY_SIZE: usize = 1000;
 
 pub struct Repl {
-    db: Database,
-    editor: DefaultEditor,
+    db: Arc<Database>,
+    editor: Editor<ShellHelper, FileHistory>,
+    output: OutputFormat,
 }
 
 impl Repl {
     pub fn new<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Self::with_config(path, Config::default())
+    }
+
+    /// Open (or create) the database at `path` with custom configuration
+    pub fn with_config<P: AsRef<Path>>(path: P, config: Config) -> anyhow::Result<Self> {
         let db = if path.as_ref().exists() {
-            Database::open(path)?
+            Database::open_with_config(path, config)?
         } else {
-            Database::create(path)?
+            Database::create_with_config(path, config)?
         };
 
-        let editor = DefaultEditor::new()?;
+        let db = Arc::new(db);
+        let editor_config = rustyline::Config::builder()
+            .max_history_size(HISTORY_SIZE)?
+            .auto_add_history(false)
+            .completion_type(CompletionType::List)
+            .build();
+        let mut editor = Editor::with_config(editor_config)?;
+        editor.set_helper(Some(ShellHelper::new(db.clone())));
+
+        Ok(Self { db, editor, output: OutputFormat::default() })
+    }
 
-        Ok(Self { db, editor })
+    /// Print results in `output` format instead of as text
+    pub fn with_output(mut self, output: OutputFormat) -> Self {
+        self.output = output;
+        self
     }
 
     pub fn run(&mut self) -> anyhow::Result<()> {
-        println!("NoSQLite Interactive Shell");
-        println!("Type 'help' for commands, 'exit' to quit\n");
+        if self.output == OutputFormat::Table {
+            println!("NoSQLite Interactive Shell");
+            println!("Type 'help' for commands, 'exit' to quit\n");
+        }
+
+        // A missing or unreadable history file just means starting afresh
+        let history = history_path();
+        if let Some(path) = &history {
+            let _ = self.editor.load_history(path);
+        }
 
         loop {
             let readline = self.editor.readline("nosqlite> ");
@@ -37,8 +76,11 @@ impl Repl {
 
                     self.editor.add_history_entry(line)?;
 
+                    if is_exit(line) {
+                        break;
+                    }
                     if let Err(e) = self.execute_command(line) {
-                        eprintln!("Error: {}", e);
+                        self.output.print_error(&e);
                     }
                 }
                 Err(ReadlineError::Interrupted) => {
@@ -46,7 +88,9 @@ impl Repl {
                     continue;
                 }
                 Err(ReadlineError::Eof) => {
-                    println!("exit");
+                    if self.output == OutputFormat::Table {
+                        println!("exit");
+                    }
                     break;
                 }
                 Err(err) => {
@@ -56,39 +100,116 @@ impl Repl {
             }
         }
 
+        if let Some(path) = &history {
+            if let Err(e) = self.editor.save_history(path) {
+                eprintln!("Warning: could not save history to {}: {}", path.display(), e);
+            }
+        }
+        self.db.sync()?;
         Ok(())
     }
 
-    fn execute_command(&self, line: &str) -> anyhow::Result<()> {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        
-        if parts.is_empty() {
-            return Ok(());
+    /// Run commands read from a script or pipe, one per line
+    ///
+    /// Blank lines and lines starting with `#` or `//` are skipped, and
+    /// `exit`/`quit` ends the script early. A command whose JSON is left open
+    /// continues on the following lines. The first failing command stops the
+    /// script and its error is returned, tagged with the line it starts on;
+    /// with `keep_going` every failure is printed as it happens and the
+    /// script runs to the end before reporting how many commands failed.
+    pub fn run_script<R: BufRead>(&mut self, reader: R, keep_going: bool) -> anyhow::Result<()> {
+        let mut failed = 0;
+        let mut lines = reader.lines().enumerate();
+        while let Some((i, line)) = lines.next() {
+            let mut command = line?;
+            if command.trim().is_empty() || command.trim().starts_with('#') || command.trim().starts_with("//") {
+                continue;
+            }
+            while parser::is_incomplete(&command) {
+                let Some((_, line)) = lines.next() else { break };
+                command.push('\n');
+                command.push_str(&line?);
+            }
+
+            let command = command.trim();
+            if is_exit(command) {
+                break;
+            }
+            if let Err(e) = self.execute_command(command) {
+                let e = e.context(format!("line {}", i + 1));
+                if !keep_going {
+                    self.db.sync()?;
+                    return Err(e);
+                }
+                self.output.print_error(&e);
+                failed += 1;
+            }
+        }
+
+        self.db.sync()?;
+        if failed > 0 {
+            anyhow::bail!("{} command(s) failed", failed);
         }
+        Ok(())
+    }
 
-        match parts[0] {
-            "help" => self.show_help(),
-            "exit" | "quit" => std::process::exit(0),
-            "collections" => self.list_collections()?,
-            "insert" => self.insert(&parts[1..])?,
-            "find" => self.find(&parts[1..])?,
-            "update" => self.update(&parts[1..])?,
-            "delete" => self.delete(&parts[1..])?,
-            "count" => self.count(&parts[1..])?,
-            "sync" => {
+    fn execute_command(&self, line: &str) -> anyhow::Result<()> {
+        let output = self.output;
+        match parser::parse(line)? {
+            Command::Help => self.show_help(),
+            Command::Collections => self.list_collections()?,
+            Command::Insert { collection, document } => {
+                let id = self.db.insert(&collection, document)?;
+                output.print(&json!({ "_id": id }), || println!("Inserted document with ID: {}", id))?;
+            }
+            Command::Find(mut query) => {
+                query.limit.get_or_insert(10);
+                self.find(&query)?
+            }
+            Command::Explain(mut query) => {
+                // Explain the query `find` would run
+                if query.id.is_none() {
+                    query.limit.get_or_insert(10);
+                }
+                let plan = self.db.explain(&query);
+                output.print(&plan, || print!("{}", plan))?;
+            }
+            Command::Update { collection, id, document } => {
+                let updated = self.db.update(&collection, &id, document)?.to_value();
+                output.print(&updated, || println!("Updated document:\n{:#}", updated))?;
+            }
+            Command::Delete { collection, id } => {
+                let deleted = self.db.delete(&collection, &id)?.to_value();
+                output.print(&deleted, || println!("Deleted document:\n{:#}", deleted))?;
+            }
+            Command::Count { collection, filter } => {
+                let count = filter.count(&self.db, &collection)?;
+                output.print(&json!({ "collection": collection, "count": count }), || {
+                    println!("{} documents in collection '{}'", count, collection)
+                })?;
+            }
+            Command::Sync => {
                 self.db.sync()?;
-                println!("Database synced to disk");
+                output.print(&json!({ "synced": true }), || println!("Database synced to disk"))?;
+            }
+            Command::Stats => {
+                let stats = self.db.stats()?;
+                output.print(&stats, || print!("{}", stats))?;
             }
             // Vector database commands
-            "vcreate" => self.vector_create(&parts[1..])?,
-            "vinsert" => self.vector_insert(&parts[1..])?,
-            "vsearch" => self.vector_search(&parts[1..])?,
-            "vcollections" => self.list_vector_collections()?,
-            "vstats" => self.vector_stats(&parts[1..])?,
-            "vdrop" => self.vector_drop(&parts[1..])?,
-            _ => {
-                println!("Unknown command: {}. Type 'help' for available commands.", parts[0]);
+            Command::VectorCreate { name, dimensions, distance } => self.vector_create(&name, dimensions, distance)?,
+            Command::VectorInsert { collection, vector, metadata } => {
+                let id = self.db.insert_vector(&collection, vector, metadata)?;
+                output.print(&json!({ "id": id }), || println!("Inserted vector with ID: {}", id))?;
+            }
+            Command::VectorSearch { collection, query, k, filter } => {
+                self.vector_search(&collection, &query, k, &filter)?
             }
+            Command::VectorCollections => self.list_vector_collections()?,
+            Command::VectorStats { collection: Some(collection) } => self.vector_stats(&collection)?,
+            Command::VectorStats { collection: None } => self.list_vector_collections()?,
+            Command::VectorOptimize { collection } => self.vector_optimize(&collection)?,
+            Command::VectorDrop { collection } => self.vector_drop(&collection)?,
         }
 
         Ok(())
@@ -101,18 +222,25 @@ impl Repl {
         println!("  help                                  - Show this help message");
         println!("  collections                           - List all collections");
         println!("  insert <collection> <json>            - Insert a document");
-        println!("  find <collection> [id]                - Find document(s)");
+        println!("  find <collection> [id] [where <field> <op> <value> [and ...]]");
+        println!("       [sort <field> [asc|desc]] [limit <n>] [skip <n>] [collate <collation>]");
+        println!("                                        - Find document(s)");
+        println!("  explain find <collection> ...         - Show how a find would run");
         println!("  update <collection> <id> <json>       - Update a document");
         println!("  delete <collection> <id>              - Delete a document");
-        println!("  count <collection>                    - Count documents in collection");
+        println!("  count <collection> [where ...] [collate <collation>]");
+        println!("                                        - Count documents in collection");
         println!("  sync                                  - Sync database to disk");
+        println!("  stats                                 - Show database statistics");
         println!();
         println!("  === Vector Database ===");
         println!("  vcreate <name> <dims> [distance]      - Create vector collection");
         println!("  vinsert <collection> <vector> [json]  - Insert a vector");
-        println!("  vsearch <collection> <vector> [k]     - Search for similar vectors");
+        println!("  vsearch <collection> <vector|\"text\"> [k] [where ...]");
+        println!("                                        - Search for similar vectors");
         println!("  vcollections                          - List vector collections");
         println!("  vstats <collection>                   - Show vector collection stats");
+        println!("  voptimize <collection>                - Rebalance vector index");
         println!("  vdrop <collection>                    - Drop vector collection");
         println!();
         println!("  exit/quit                             - Exit the shell");
@@ -120,273 +248,198 @@ impl Repl {
         println!("Examples:");
         println!("  insert users {{\"name\":\"Alice\",\"age\":30}}");
         println!("  find users abc123");
+        println!("  find users where age > 30 and name != \"Bob\" sort age desc limit 5");
+        println!("  explain find users where age > 30 sort age desc limit 5");
+        println!("  count users where tags contains admin");
         println!();
         println!("  vcreate embeddings 384 cosine");
         println!("  vinsert embeddings [0.1,0.2,0.3,...] {{\"source\":\"doc1\"}}");
-        println!("  vsearch embeddings [0.1,0.2,0.3,...] 10");
+        println!("  vsearch embeddings [0.1,0.2,0.3,...] 10 where source = doc1");
+        println!();
+        println!("Operators: = != > >= < <= in [..] not in [..] contains startswith endswith");
+        println!("Collations: binary (default), nocase (ASCII case), unicode (case, accents)");
     }
 
     fn list_collections(&self) -> anyhow::Result<()> {
         let collections = self.db.list_collections();
-        
-        if collections.is_empty() {
-            println!("No collections found");
-            return Ok(());
-        }
-
-        println!("Collections:");
-        for (name, count) in collections {
-            println!("  {} ({} documents)", name, count);
-        }
-        
-        Ok(())
-    }
+        let rows: Vec<_> = collections.iter().map(|(name, count)| json!({ "name": name, "documents": count })).collect();
 
-    fn insert(&self, args: &[&str]) -> anyhow::Result<()> {
-        if args.len() < 2 {
-            println!("Usage: insert <collection> <json>");
-            return Ok(());
-        }
-
-        let collection = args[0];
-        let json_str = args[1..].join(" ");
-        let data: serde_json::Value = serde_json::from_str(&json_str)?;
-
-        let id = self.db.insert(collection, data)?;
-        println!("Inserted document with ID: {}", id);
+        self.output.print(&rows, || {
+            if collections.is_empty() {
+                println!("No collections found");
+                return;
+            }
 
-        Ok(())
+            println!("Collections:");
+            for (name, count) in &collections {
+                println!("  {} ({} documents)", name, count);
+            }
+        })
     }
 
-    fn find(&self, args: &[&str]) -> anyhow::Result<()> {
-        if args.is_empty() {
-            println!("Usage: find <collection> [id]");
-            return Ok(());
-        }
+    fn find(&self, query: &FindQuery) -> anyhow::Result<()> {
+        let docs: Vec<_> = query.run(&self.db)?.iter().map(|doc| doc.to_value()).collect();
 
-        let collection = args[0];
+        if query.id.is_some() {
+            if let [doc] = docs.as_slice() {
+                return self.output.print(doc, || println!("{:#}", doc));
+            }
+        }
 
-        if args.len() == 1 {
-            // Find all
-            let docs = self.db.find_all(collection, Some(10), None)?;
-            
+        self.output.print(&docs, || {
             if docs.is_empty() {
                 println!("No documents found");
-                return Ok(());
+                return;
             }
 
             println!("Found {} document(s):", docs.len());
-            for doc in docs {
-                let json = serde_json::to_string_pretty(&doc.to_value())?;
-                println!("{}", json);
+            for doc in &docs {
+                println!("{:#}", doc);
             }
-        } else {
-            // Find by ID
-            let doc_id = args[1];
-            let doc = self.db.find_by_id(collection, doc_id)?;
-            let json = serde_json::to_string_pretty(&doc.to_value())?;
-            println!("{}", json);
-        }
-
-        Ok(())
-    }
-
-    fn update(&self, args: &[&str]) -> anyhow::Result<()> {
-        if args.len() < 3 {
-            println!("Usage: update <collection> <id> <json>");
-            return Ok(());
-        }
-
-        let collection = args[0];
-        let doc_id = args[1];
-        let json_str = args[2..].join(" ");
-        let data: serde_json::Value = serde_json::from_str(&json_str)?;
-
-        let updated = self.db.update(collection, doc_id, data)?;
-        println!("Updated document:");
-        let json = serde_json::to_string_pretty(&updated.to_value())?;
-        println!("{}", json);
-
-        Ok(())
-    }
-
-    fn delete(&self, args: &[&str]) -> anyhow::Result<()> {
-        if args.len() < 2 {
-            println!("Usage: delete <collection> <id>");
-            return Ok(());
-        }
-
-        let collection = args[0];
-        let doc_id = args[1];
-
-        let deleted = self.db.delete(collection, doc_id)?;
-        println!("Deleted document:");
-        let json = serde_json::to_string_pretty(&deleted.to_value())?;
-        println!("{}", json);
-
-        Ok(())
-    }
-
-    fn count(&self, args: &[&str]) -> anyhow::Result<()> {
-        if args.is_empty() {
-            println!("Usage: count <collection>");
-            return Ok(());
-        }
-
-        let collection = args[0];
-        let count = self.db.count(collection);
-        println!("{} documents in collection '{}'", count, collection);
-
-        Ok(())
+        })
     }
 
     // ============================================================
     // Vector Database Commands
     // ============================================================
 
-    fn vector_create(&self, args: &[&str]) -> anyhow::Result<()> {
-        if args.len() < 2 {
-            println!("Usage: vcreate <name> <dimensions> [distance]");
-            println!("  distance: cosine (default), euclidean, dot_product, manhattan");
-            return Ok(());
+    fn vector_create(&self, name: &str, dimensions: usize, distance: Option<Distance>) -> anyhow::Result<()> {
+        let mut config = self.db.config().vector_config(dimensions);
+        if let Some(distance) = distance {
+            config.distance = distance;
         }
+        let distance = config.distance;
 
-        let name = args[0];
-        let dimensions: usize = args[1].parse()
-            .map_err(|_| anyhow::anyhow!("Invalid dimensions: {}", args[1]))?;
-
-        let distance = if args.len() > 2 {
-            match args[2].to_lowercase().as_str() {
-                "cosine" => Distance::Cosine,
-                "euclidean" | "l2" => Distance::Euclidean,
-                "dot" | "dot_product" | "inner" => Distance::DotProduct,
-                "manhattan" | "l1" => Distance::Manhattan,
-                _ => {
-                    println!("Unknown distance metric: {}. Using cosine.", args[2]);
-                    Distance::Cosine
-                }
-            }
-        } else {
-            Distance::Cosine
-        };
-
-        let config = VectorConfig::new(dimensions).with_distance(distance);
         self.db.create_vector_collection(name, config)?;
-        
-        println!("Created vector collection '{}' with {} dimensions ({} distance)", 
-                 name, dimensions, distance.name());
 
-        Ok(())
+        let created = json!({ "name": name, "dimensions": dimensions, "distance": distance.name() });
+        self.output.print(&created, || {
+            println!("Created vector collection '{}' with {} dimensions ({} distance)",
+                     name, dimensions, distance.name())
+        })
     }
 
-    fn vector_insert(&self, args: &[&str]) -> anyhow::Result<()> {
-        if args.len() < 2 {
-            println!("Usage: vinsert <collection> <vector_json> [metadata_json]");
-            println!("  Example: vinsert embeddings [0.1,0.2,0.3] {{\"source\":\"doc1\"}}");
-            return Ok(());
-        }
-
-        let collection = args[0];
-        let vector: Vec<f32> = serde_json::from_str(args[1])
-            .map_err(|e| anyhow::anyhow!("Invalid vector JSON: {}", e))?;
-
-        let metadata = if args.len() > 2 {
-            let json_str = args[2..].join(" ");
-            Some(serde_json::from_str(&json_str)?)
-        } else {
-            None
-        };
+    fn vector_search(&self, collection: &str, query: &VectorQuery, k: usize, filter: &Filter) -> anyhow::Result<()> {
+        let results = query.search(&self.db, collection, k, filter)?;
 
-        let id = self.db.insert_vector(collection, vector, metadata)?;
-        println!("Inserted vector with ID: {}", id);
-
-        Ok(())
-    }
-
-    fn vector_search(&self, args: &[&str]) -> anyhow::Result<()> {
-        if args.len() < 2 {
-            println!("Usage: vsearch <collection> <query_vector_json> [k]");
-            println!("  Example: vsearch embeddings [0.1,0.2,0.3] 10");
-            return Ok(());
-        }
-
-        let collection = args[0];
-        let query: Vec<f32> = serde_json::from_str(args[1])
-            .map_err(|e| anyhow::anyhow!("Invalid query vector JSON: {}", e))?;
-
-        let k: usize = if args.len() > 2 {
-            args[2].parse().unwrap_or(10)
-        } else {
-            10
-        };
-
-        let results = self.db.vector_search(collection, &query, k)?;
-
-        if results.is_empty() {
-            println!("No results found");
-            return Ok(());
-        }
-
-        println!("Found {} result(s):", results.len());
-        for result in results {
-            println!("  ID: {}, Score: {:.6}", result.document.id, result.score);
-            if result.document.metadata != serde_json::Value::Null {
-                println!("    Metadata: {}", result.document.metadata);
+        self.output.print(&results, || {
+            if results.is_empty() {
+                println!("No results found");
+                return;
             }
-        }
 
-        Ok(())
+            println!("Found {} result(s):", results.len());
+            for result in &results {
+                println!("  ID: {}, Score: {:.6}", result.document.id, result.score);
+                if result.document.metadata != serde_json::Value::Null {
+                    println!("    Metadata: {}", result.document.metadata);
+                }
+            }
+        })
     }
 
     fn list_vector_collections(&self) -> anyhow::Result<()> {
         let collections = self.db.list_vector_collections();
+        let rows: Vec<_> = collections.iter().map(|(name, count)| json!({ "name": name, "vectors": count })).collect();
 
-        if collections.is_empty() {
-            println!("No vector collections found");
-            return Ok(());
-        }
-
-        println!("Vector Collections:");
-        for (name, count) in collections {
-            println!("  {} ({} vectors)", name, count);
-        }
+        self.output.print(&rows, || {
+            if collections.is_empty() {
+                println!("No vector collections found");
+                return;
+            }
 
-        Ok(())
+            println!("Vector Collections:");
+            for (name, count) in &collections {
+                println!("  {} ({} vectors)", name, count);
+            }
+        })
     }
 
-    fn vector_stats(&self, args: &[&str]) -> anyhow::Result<()> {
-        if args.is_empty() {
-            println!("Usage: vstats <collection>");
-            return Ok(());
-        }
-
-        let collection = args[0];
+    fn vector_stats(&self, collection: &str) -> anyhow::Result<()> {
         let stats = self.db.vector_stats(collection)?;
 
-        println!("Vector Collection: {}", stats.name);
-        println!("  Vectors:      {}", stats.vector_count);
-        println!("  Dimensions:   {}", stats.dimensions);
-        println!("  Distance:     {}", stats.distance.name());
-        println!("  Memory (est): {} KB", stats.memory_bytes / 1024);
-        println!("  HNSW M:       {}", stats.hnsw_layers);
-        println!("  Lazy Mode:    {}", stats.lazy_embedding);
+        self.output.print(&stats, || {
+            println!("Vector Collection: {}", stats.name);
+            println!("  Vectors:      {}", stats.vector_count);
+            println!("  Dimensions:   {}", stats.dimensions);
+            println!("  Distance:     {}", stats.distance.name());
+            println!("  Memory (est): {} KB", stats.memory_bytes / 1024);
+            println!("    Graph:      {} KB", stats.graph_bytes / 1024);
+            println!("    Vectors:    {} KB", stats.vector_bytes / 1024);
+            println!("    Metadata:   {} KB", stats.metadata_bytes / 1024);
+            println!("  HNSW M:       {}", stats.hnsw_m);
+            println!("  HNSW Layers:  {} {:?}", stats.hnsw_layers, stats.layer_histogram);
+            println!("  Out-degree:   {:.1} avg, {} max", stats.avg_out_degree, stats.max_out_degree);
+            println!("  Isolated:     {}", stats.isolated_nodes);
+            println!("  Reachable:    {:.1}%", stats.reachable_ratio * 100.0);
+            println!("  Lazy Mode:    {}", stats.lazy_embedding);
+        })
+    }
 
-        Ok(())
+    fn vector_optimize(&self, collection: &str) -> anyhow::Result<()> {
+        let report = self.db.optimize_vector_collection(collection)?;
+
+        self.output.print(&report, || {
+            println!("Optimized vector collection '{}'", collection);
+            println!("  Connections:  {} -> {}", report.before.total_connections, report.after.total_connections);
+            println!("  Max layer:    {} -> {}", report.before.max_layer, report.after.max_layer);
+            println!("  Relinked:     {} nodes", report.relinked_nodes);
+            println!("  Links pruned: {}", report.dangling_links_removed);
+            println!("  Compacted:    {} nodes", report.compacted_ids);
+            if let Some(compression) = &report.compression {
+                println!(
+                    "  Compression:  {:.1}% ({} anchors, {} deltas)",
+                    compression.compression_ratio * 100.0,
+                    compression.anchor_count,
+                    compression.delta_count
+                );
+            }
+        })
     }
 
-    fn vector_drop(&self, args: &[&str]) -> anyhow::Result<()> {
-        if args.is_empty() {
-            println!("Usage: vdrop <collection>");
-            return Ok(());
-        }
+    fn vector_drop(&self, collection: &str) -> anyhow::Result<()> {
+        let dropped = self.db.drop_vector_collection(collection)?;
 
-        let collection = args[0];
-        if self.db.drop_vector_collection(collection)? {
-            println!("Dropped vector collection '{}'", collection);
-        } else {
-            println!("Vector collection '{}' not found", collection);
-        }
+        self.output.print(&json!({ "collection": collection, "dropped": dropped }), || {
+            if dropped {
+                println!("Dropped vector collection '{}'", collection);
+            } else {
+                println!("Vector collection '{}' not found", collection);
+            }
+        })
+    }
+}
 
-        Ok(())
+/// Where shell history is kept: `~/.keradb/history`
+fn history_path() -> Option<PathBuf> {
+    keradb_home().ok().map(|dir| dir.join("history"))
+}
+
+fn is_exit(line: &str) -> bool {
+    matches!(line, "exit" | "quit")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::KeraDBError;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_run_script() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.ndb");
+        let mut repl = Repl::new(&path).unwrap().with_output(OutputFormat::Json);
+
+        let script = "# seed data\ninsert users {\n  \"_id\": \"a\",\n  \"n\": 1\n}\nfind users nope\ninsert users {\"_id\": \"b\"}\n";
+        let err = repl.run_script(script.as_bytes(), false).unwrap_err();
+        assert_eq!(format!("{:#}", err).split(':').next(), Some("line 6"));
+        assert!(matches!(err.downcast_ref::<KeraDBError>(), Some(KeraDBError::DocumentNotFound(_))));
+        assert_eq!(repl.db.count("users"), 1);
+
+        // Later commands still run, and `exit` stops the script
+        assert!(repl.run_script(script.as_bytes(), true).is_err());
+        repl.run_script("insert users {\"_id\": \"c\"}\nexit\ninsert users {}".as_bytes(), false).unwrap();
+        assert_eq!(repl.db.count("users"), 3);
     }
 }
@@ -1,10 +1,76 @@
-use chrono::{DateTime, Utc};
+//! Per-user history of opened databases
+//!
+//! `~/.keradb/.keradb_system.db` remembers each database the TUI has opened,
+//! keyed by its path, with the settings it was created with and samples of
+//! its collection counts. A [`HistoryPolicy`] keeps it from growing without
+//! bound: whenever a database is opened, connections beyond the limit or not
+//! opened for too long are forgotten. `keradb connections` lists and cleans
+//! the history by hand.
+
+use chrono::{DateTime, Duration, Utc};
+use crate::types::{Config, Durability};
 use crate::Database;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
 const SYSTEM_DB_NAME: &str = ".keradb_system.db";
 const CONNECTIONS_COLLECTION: &str = "connections";
+const COUNTS_COLLECTION: &str = "collection_counts";
+
+/// Count samples kept per database; the oldest are dropped
+const MAX_COUNT_SAMPLES: usize = 500;
+/// Samples taken closer together than this replace each other
+const SAMPLE_INTERVAL_SECS: i64 = 60;
+
+/// Limits on the connection history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryPolicy {
+    /// Most connections kept; the least recently opened are dropped first
+    pub max_connections: Option<usize>,
+    /// Connections not opened for this many days are dropped
+    pub max_age_days: Option<u32>,
+}
+
+impl Default for HistoryPolicy {
+    fn default() -> Self {
+        Self { max_connections: Some(100), max_age_days: Some(365) }
+    }
+}
+
+impl HistoryPolicy {
+    /// The defaults, overridden by `KERADB_HISTORY_MAX_CONNECTIONS` and
+    /// `KERADB_HISTORY_MAX_AGE_DAYS`; 0 means no limit
+    pub fn from_env() -> anyhow::Result<Self> {
+        let limit = |var: &str| -> anyhow::Result<Option<Option<u64>>> {
+            match std::env::var(var) {
+                Ok(value) => {
+                    let n: u64 = value.trim().parse().map_err(|_| anyhow::anyhow!("{}: expected a number, got '{}'", var, value))?;
+                    Ok(Some((n > 0).then_some(n)))
+                }
+                Err(_) => Ok(None),
+            }
+        };
+        let mut policy = Self::default();
+        if let Some(max) = limit("KERADB_HISTORY_MAX_CONNECTIONS")? {
+            policy.max_connections = max.map(|n| n as usize);
+        }
+        if let Some(days) = limit("KERADB_HISTORY_MAX_AGE_DAYS")? {
+            policy.max_age_days = days.map(|n| n.min(u32::MAX as u64) as u32);
+        }
+        Ok(policy)
+    }
+
+    pub fn with_max_connections(mut self, max_connections: Option<usize>) -> Self {
+        self.max_connections = max_connections;
+        self
+    }
+
+    pub fn with_max_age_days(mut self, max_age_days: Option<u32>) -> Self {
+        self.max_age_days = max_age_days;
+        self
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DatabaseConnection {
@@ -16,9 +82,27 @@ pub struct DatabaseConnection {
     pub access_count: u64,
     pub collections_count: usize,
     pub total_documents: usize,
+    /// Page cache size chosen when the database was created
+    #[serde(default)]
+    pub cache_size: Option<usize>,
+    /// "normal" or "full", chosen when the database was created
+    #[serde(default)]
+    pub durability: Option<String>,
 }
 
 impl DatabaseConnection {
+    /// Apply the settings stored for this database on top of `config`
+    pub fn apply_settings(&self, config: &mut Config) {
+        if let Some(cache_size) = self.cache_size {
+            config.cache_size = cache_size;
+        }
+        match self.durability.as_deref() {
+            Some("full") => config.durability = Durability::Full,
+            Some("normal") => config.durability = Durability::Normal,
+            _ => {}
+        }
+    }
+
     pub fn format_last_accessed(&self) -> String {
         let now = Utc::now();
         let diff = now.signed_duration_since(self.last_accessed);
@@ -41,33 +125,47 @@ impl DatabaseConnection {
     }
 }
 
+/// Document counts of one database's collections at one time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CountSample {
+    path: String,
+    at: DateTime<Utc>,
+    counts: HashMap<String, usize>,
+}
+
 pub struct SystemDatabase {
     db: Database,
     path: PathBuf,
+    policy: HistoryPolicy,
+}
+
+/// The per-user `~/.keradb` directory, created if missing
+pub fn keradb_home() -> anyhow::Result<PathBuf> {
+    let home = if cfg!(target_os = "windows") {
+        std::env::var("USERPROFILE")
+            .or_else(|_| std::env::var("HOMEDRIVE")
+                .and_then(|drive| std::env::var("HOMEPATH")
+                    .map(|path| format!("{}{}", drive, path))))
+            .map_err(|_| anyhow::anyhow!("Could not determine user home directory"))?
+    } else {
+        std::env::var("HOME")
+            .map_err(|_| anyhow::anyhow!("Could not determine user home directory"))?
+    };
+
+    let mut path = PathBuf::from(home);
+    path.push(".keradb");
+
+    // Create the directory if it doesn't exist
+    if !path.exists() {
+        std::fs::create_dir_all(&path)?;
+    }
+    Ok(path)
 }
 
 impl SystemDatabase {
     /// Get the system database path in an OS-agnostic way
-    fn get_system_db_path() -> anyhow::Result<PathBuf> {
-        let home = if cfg!(target_os = "windows") {
-            std::env::var("USERPROFILE")
-                .or_else(|_| std::env::var("HOMEDRIVE")
-                    .and_then(|drive| std::env::var("HOMEPATH")
-                        .map(|path| format!("{}{}", drive, path))))
-                .map_err(|_| anyhow::anyhow!("Could not determine user home directory"))?
-        } else {
-            std::env::var("HOME")
-                .map_err(|_| anyhow::anyhow!("Could not determine user home directory"))?
-        };
-
-        let mut path = PathBuf::from(home);
-        path.push(".keradb");
-        
-        // Create the directory if it doesn't exist
-        if !path.exists() {
-            std::fs::create_dir_all(&path)?;
-        }
-        
+    pub fn get_system_db_path() -> anyhow::Result<PathBuf> {
+        let mut path = keradb_home()?;
         path.push(SYSTEM_DB_NAME);
         Ok(path)
     }
@@ -82,7 +180,15 @@ impl SystemDatabase {
             Database::create(&db_path)?
         };
 
-        Ok(Self { db, path: db_path })
+        let system = Self { db, path: db_path, policy: HistoryPolicy::from_env()? };
+        system.rekey_connections()?;
+        Ok(system)
+    }
+
+    /// Use `policy` instead of the one from the environment
+    pub fn with_policy(mut self, policy: HistoryPolicy) -> Self {
+        self.policy = policy;
+        self
     }
 
     /// Get the system database path
@@ -90,7 +196,8 @@ impl SystemDatabase {
         &self.path
     }
 
-    /// Register a new database connection or update existing
+    /// Register a new database connection or update existing, then apply
+    /// the [`HistoryPolicy`]
     pub fn register_connection(&self, path: &str) -> anyhow::Result<String> {
         let now = Utc::now();
         
@@ -101,43 +208,30 @@ impl SystemDatabase {
             .unwrap_or("unknown")
             .to_string();
         
-        // Check if connection already exists
-        if let Ok((id, existing)) = self.find_connection_by_path(path) {
-            // Update existing connection
-            let updated = DatabaseConnection {
-                id: id.clone(),
-                path: path.to_string(),
+        let connection = match self.find_connection_by_path(path) {
+            Some(existing) => DatabaseConnection {
                 name,
-                created_at: existing.created_at,
                 last_accessed: now,
                 access_count: existing.access_count + 1,
-                collections_count: existing.collections_count,
-                total_documents: existing.total_documents,
-            };
-            
-            let doc = serde_json::to_value(&updated)?;
-            self.db.update(CONNECTIONS_COLLECTION, &id, doc)?;
-            self.db.sync()?;
-            return Ok(id);
-        }
-
-        // Create new connection
-        let connection = DatabaseConnection {
-            id: String::new(),
-            path: path.to_string(),
-            name,
-            created_at: now,
-            last_accessed: now,
-            access_count: 1,
-            collections_count: 0,
-            total_documents: 0,
+                ..existing
+            },
+            None => DatabaseConnection {
+                id: path.to_string(),
+                path: path.to_string(),
+                name,
+                created_at: now,
+                last_accessed: now,
+                access_count: 1,
+                collections_count: 0,
+                total_documents: 0,
+                cache_size: None,
+                durability: None,
+            },
         };
+        self.save_connection(&connection)?;
+        self.prune()?;
 
-        let doc = serde_json::to_value(&connection)?;
-        let id = self.db.insert(CONNECTIONS_COLLECTION, doc)?;
-        self.db.sync()?;
-        
-        Ok(id)
+        Ok(connection.id)
     }
 
     /// Update database statistics
@@ -147,38 +241,80 @@ impl SystemDatabase {
         collections_count: usize,
         total_documents: usize,
     ) -> anyhow::Result<()> {
-        if let Ok((id, mut conn)) = self.find_connection_by_path(path) {
+        if let Some(mut conn) = self.find_connection_by_path(path) {
             conn.collections_count = collections_count;
             conn.total_documents = total_documents;
             conn.last_accessed = Utc::now();
             
-            let doc = serde_json::to_value(&conn)?;
-            self.db.update(CONNECTIONS_COLLECTION, &id, doc)?;
+            self.save_connection(&conn)?;
             self.db.sync()?;
         }
         Ok(())
     }
 
-    /// Find connection by database path
-    fn find_connection_by_path(&self, path: &str) -> anyhow::Result<(String, DatabaseConnection)> {
-        let docs = self.db.find_all(CONNECTIONS_COLLECTION, None, None)?;
-        
-        for doc in docs {
-            let doc_value = doc.to_value();
-            
-            let doc_id = doc_value.get("_id")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| anyhow::anyhow!("Missing _id field"))?
-                .to_string();
-            
-            let conn: DatabaseConnection = serde_json::from_value(doc_value)?;
-            
-            if conn.path == path {
-                return Ok((doc_id, conn));
+    /// Remember the cache size and durability a database is opened with
+    pub fn set_connection_settings(&self, path: &str, config: &Config) -> anyhow::Result<()> {
+        if let Some(mut conn) = self.find_connection_by_path(path) {
+            conn.cache_size = Some(config.cache_size);
+            conn.durability = Some(match config.durability {
+                Durability::Normal => "normal".to_string(),
+                Durability::Full => "full".to_string(),
+            });
+
+            self.save_connection(&conn)?;
+            self.db.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Find connection by database path, which is its document ID
+    fn find_connection_by_path(&self, path: &str) -> Option<DatabaseConnection> {
+        let doc = self.db.find_by_id(CONNECTIONS_COLLECTION, path).ok()?;
+        serde_json::from_value(doc.to_value()).ok()
+    }
+
+    /// Insert or replace a connection, under its path
+    fn save_connection(&self, conn: &DatabaseConnection) -> anyhow::Result<()> {
+        let mut doc = serde_json::to_value(DatabaseConnection { id: conn.path.clone(), ..conn.clone() })?;
+        if self.db.find_by_id(CONNECTIONS_COLLECTION, &conn.path).is_ok() {
+            self.db.update(CONNECTIONS_COLLECTION, &conn.path, doc)?;
+        } else {
+            doc["_id"] = conn.path.clone().into();
+            self.db.insert(CONNECTIONS_COLLECTION, doc)?;
+        }
+        Ok(())
+    }
+
+    /// Move connections saved under random IDs, as older versions did, to
+    /// their path, merging the duplicates that allowed
+    fn rekey_connections(&self) -> anyhow::Result<()> {
+        let mut by_path: HashMap<String, Vec<(String, DatabaseConnection)>> = HashMap::new();
+        for doc in self.db.find_all(CONNECTIONS_COLLECTION, None, None)? {
+            if let Ok(conn) = serde_json::from_value::<DatabaseConnection>(doc.to_value()) {
+                by_path.entry(conn.path.clone()).or_default().push((doc.id, conn));
             }
         }
-        
-        Err(anyhow::anyhow!("Connection not found"))
+
+        let mut changed = false;
+        for (path, mut records) in by_path {
+            if records.iter().all(|(id, _)| *id == path) {
+                continue;
+            }
+            // Keep the latest record's details and the combined history
+            records.sort_by_key(|(_, conn)| conn.last_accessed);
+            let created_at = records.iter().map(|(_, conn)| conn.created_at).min().unwrap_or_else(Utc::now);
+            let access_count = records.iter().map(|(_, conn)| conn.access_count).sum();
+            for (id, _) in &records {
+                self.db.delete(CONNECTIONS_COLLECTION, id)?;
+            }
+            let Some((_, latest)) = records.pop() else { continue };
+            self.save_connection(&DatabaseConnection { created_at, access_count, ..latest })?;
+            changed = true;
+        }
+        if changed {
+            self.db.sync()?;
+        }
+        Ok(())
     }
 
     /// Get all registered connections
@@ -195,23 +331,197 @@ impl SystemDatabase {
         }
         
         // Sort by last accessed (most recent first)
-        connections.sort_by(|a, b| b.last_accessed.cmp(&a.last_accessed));
+        connections.sort_by_key(|c| std::cmp::Reverse(c.last_accessed));
         
         Ok(connections)
     }
 
     /// Remove a connection from the system database
     pub fn remove_connection(&self, path: &str) -> anyhow::Result<()> {
-        if let Ok((id, _conn)) = self.find_connection_by_path(path) {
-            self.db.delete(CONNECTIONS_COLLECTION, &id)?;
+        if self.find_connection_by_path(path).is_some() {
+            self.forget(path)?;
             self.db.sync()?;
         }
         Ok(())
     }
 
+    /// Delete a connection and its count samples, without syncing
+    fn forget(&self, path: &str) -> anyhow::Result<()> {
+        self.db.delete(CONNECTIONS_COLLECTION, path)?;
+        for (id, _) in self.count_samples(path)? {
+            self.db.delete(COUNTS_COLLECTION, &id)?;
+        }
+        Ok(())
+    }
+
+    /// Apply the [`HistoryPolicy`]: forget connections beyond the limit or
+    /// not opened recently enough, and count samples of forgotten databases
+    ///
+    /// Returns the connections removed.
+    pub fn prune(&self) -> anyhow::Result<Vec<DatabaseConnection>> {
+        let cutoff = self.policy.max_age_days.map(|days| Utc::now() - Duration::days(days.into()));
+        let max = self.policy.max_connections.unwrap_or(usize::MAX);
+        let removed: Vec<DatabaseConnection> = self
+            .list_connections()?
+            .into_iter()
+            .enumerate()
+            .filter(|(i, conn)| *i >= max || cutoff.is_some_and(|cutoff| conn.last_accessed < cutoff))
+            .map(|(_, conn)| conn)
+            .collect();
+        for conn in &removed {
+            self.forget(&conn.path)?;
+        }
+
+        // Samples can outlive their connection if a removal was interrupted
+        let known: HashSet<String> = self.list_connections()?.into_iter().map(|conn| conn.path).collect();
+        for doc in self.db.find_all(COUNTS_COLLECTION, None, None).unwrap_or_default() {
+            let path = doc.data.get("path").and_then(|p| p.as_str()).unwrap_or_default();
+            if !known.contains(path) {
+                self.db.delete(COUNTS_COLLECTION, &doc.id)?;
+            }
+        }
+        self.db.sync()?;
+        Ok(removed)
+    }
+
+    /// Forget connections whose database file no longer exists
+    ///
+    /// Returns the connections removed.
+    pub fn remove_missing(&self) -> anyhow::Result<Vec<DatabaseConnection>> {
+        let missing: Vec<DatabaseConnection> =
+            self.list_connections()?.into_iter().filter(|conn| !Path::new(&conn.path).exists()).collect();
+        for conn in &missing {
+            self.forget(&conn.path)?;
+        }
+        self.db.sync()?;
+        Ok(missing)
+    }
+
+    /// Record each collection's document count, for [`collection_history`](Self::collection_history)
+    ///
+    /// A sample taken within a minute of the previous one replaces it, and
+    /// only the latest few hundred samples of each database are kept.
+    pub fn record_collection_counts(&self, path: &str, collections: &[(String, usize)]) -> anyhow::Result<()> {
+        let now = Utc::now();
+        let sample = CountSample { path: path.to_string(), at: now, counts: collections.iter().cloned().collect() };
+        let samples = self.count_samples(path)?;
+
+        match samples.last() {
+            Some((id, last)) if (now - last.at).num_seconds() < SAMPLE_INTERVAL_SECS => {
+                self.db.update(COUNTS_COLLECTION, id, serde_json::to_value(&sample)?)?;
+            }
+            _ => {
+                self.db.insert(COUNTS_COLLECTION, serde_json::to_value(&sample)?)?;
+                let excess = (samples.len() + 1).saturating_sub(MAX_COUNT_SAMPLES);
+                for (id, _) in &samples[..excess] {
+                    self.db.delete(COUNTS_COLLECTION, id)?;
+                }
+            }
+        }
+        self.db.sync()?;
+        Ok(())
+    }
+
+    /// A collection's document count over time, oldest first
+    pub fn collection_history(&self, path: &str, collection: &str) -> anyhow::Result<Vec<(DateTime<Utc>, usize)>> {
+        Ok(self
+            .count_samples(path)?
+            .into_iter()
+            .map(|(_, sample)| (sample.at, sample.counts.get(collection).copied().unwrap_or(0)))
+            .collect())
+    }
+
+    /// A database's count samples with their document IDs, oldest first
+    fn count_samples(&self, path: &str) -> anyhow::Result<Vec<(String, CountSample)>> {
+        let mut samples: Vec<(String, CountSample)> = self
+            .db
+            .find_all(COUNTS_COLLECTION, None, None)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|doc| Some((doc.id.clone(), serde_json::from_value::<CountSample>(doc.to_value()).ok()?)))
+            .filter(|(_, sample)| sample.path == path)
+            .collect();
+        samples.sort_by_key(|(_, sample)| sample.at);
+        Ok(samples)
+    }
+
     /// Get the most recently used connection
     pub fn get_last_connection(&self) -> anyhow::Result<Option<DatabaseConnection>> {
         let connections = self.list_connections()?;
         Ok(connections.into_iter().next())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn connection(path: &str, days_ago: i64, access_count: u64) -> DatabaseConnection {
+        let at = Utc::now() - Duration::days(days_ago);
+        DatabaseConnection {
+            id: String::new(),
+            path: path.into(),
+            name: path.into(),
+            created_at: at,
+            last_accessed: at,
+            access_count,
+            collections_count: 0,
+            total_documents: 0,
+            cache_size: None,
+            durability: None,
+        }
+    }
+
+    #[test]
+    fn test_connection_history_housekeeping() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("system.db");
+        let policy = HistoryPolicy::default().with_max_connections(Some(2)).with_max_age_days(Some(30));
+        let system = SystemDatabase { db: Database::create(&path).unwrap(), path, policy };
+
+        // Older versions saved duplicates under random IDs
+        for conn in [connection("a.ndb", 3, 2), connection("a.ndb", 1, 5), connection("old.ndb", 90, 1)] {
+            system.db.insert(CONNECTIONS_COLLECTION, serde_json::to_value(&conn).unwrap()).unwrap();
+        }
+        system.rekey_connections().unwrap();
+        let a = system.find_connection_by_path("a.ndb").unwrap();
+        assert_eq!((a.id.as_str(), a.access_count), ("a.ndb", 7));
+        assert_eq!(system.list_connections().unwrap().len(), 2);
+
+        // Opening more databases drops the stale and the least recently used
+        system.record_collection_counts("old.ndb", &[("users".into(), 1)]).unwrap();
+        system.register_connection("b.ndb").unwrap();
+        let id = system.register_connection("c.ndb").unwrap();
+        assert_eq!(id, "c.ndb");
+        let paths: Vec<String> = system.list_connections().unwrap().into_iter().map(|c| c.path).collect();
+        assert_eq!(paths, ["c.ndb", "b.ndb"]);
+        assert!(system.collection_history("old.ndb", "users").unwrap().is_empty());
+
+        assert_eq!(system.remove_missing().unwrap().len(), 2);
+        assert!(system.list_connections().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_collection_history() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("system.db");
+        let system = SystemDatabase { db: Database::create(&path).unwrap(), path, policy: HistoryPolicy::default() };
+
+        let earlier = CountSample {
+            path: "app.ndb".into(),
+            at: Utc::now() - Duration::hours(1),
+            counts: HashMap::from([("users".to_string(), 3)]),
+        };
+        system.db.insert(COUNTS_COLLECTION, serde_json::to_value(&earlier).unwrap()).unwrap();
+
+        // Samples a moment apart replace each other
+        system.record_collection_counts("app.ndb", &[("users".into(), 5)]).unwrap();
+        system.record_collection_counts("app.ndb", &[("users".into(), 7), ("orders".into(), 1)]).unwrap();
+        system.record_collection_counts("other.ndb", &[("users".into(), 100)]).unwrap();
+
+        let counts: Vec<usize> = system.collection_history("app.ndb", "users").unwrap().into_iter().map(|(_, n)| n).collect();
+        assert_eq!(counts, [3, 7]);
+        assert_eq!(system.collection_history("app.ndb", "orders").unwrap()[0].1, 0);
+    }
+}
@@ -1,7 +1,14 @@
 use crate::Database;
-use crate::vector::{VectorConfig, Distance};
+use crate::advisor::IndexSuggestion;
+use crate::oplog::ChangeStream;
+use crate::stats::DatabaseStats;
+use crate::import::{self, ImportFormat, ImportOptions};
+use crate::types::{Config, Document};
+use crate::vector::{Projection, ProjectionMethod, VectorCollectionStats, VectorSearchResult};
+use crate::cli::parser::{self, Command, Filter, VectorQuery};
 use crate::cli::system_db::{SystemDatabase, DatabaseConnection};
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use crossterm::{
     event::{KeyCode, KeyEvent, KeyModifiers},
     execute,
@@ -11,17 +18,30 @@ use ratatui::{
     backend::CrosstermBackend,
     Terminal,
 };
-use std::path::Path;
-use std::time::Duration;
+use std::io::{BufReader, Stdout};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
+use super::completion::Completion;
+use super::database_form::DatabaseForm;
+use super::config::{Action, Keymap, Theme, TuiConfig};
+use super::editor::{DocumentEditor, EditTarget, Editor, InsertForm, TextArea};
+use super::export;
+use super::query_builder::QueryBuilder;
+use super::results::{LineKind, Results};
 use super::events::{AppEvent, EventHandler, is_quit_key};
 use super::ui;
+use super::worker::{CommandOutput, Outcome, Task, TaskContext};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AppMode {
     Normal,
     Insert,
     Command,
+    /// A document editor is open
+    Edit,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -36,14 +56,47 @@ pub enum FocusedPanel {
 pub enum AppScreen {
     ConnectionManager,
     DatabaseExplorer,
+    /// Vector collections and similarity search
+    VectorExplorer,
+    /// Storage and history of document collections
+    CollectionStats,
 }
 
+/// Shown in the results pane before connecting
+const WELCOME: &str = "\
+╔══════════════════════════════════════════════════════════╗
+║         Welcome to KeraDB Terminal User Interface        ║
+╚══════════════════════════════════════════════════════════╝
+
+Select a database from history or create/open a new one:
+
+  [n] Create new database
+  [o] Open existing database
+  [Enter] Connect to selected
+  [d] Remove from history
+  [?] Show help";
+
+/// Watch mode refreshes at most this often
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The last find, so watch mode can run it again
+struct LiveFind {
+    command: Command,
+    /// Where its output starts in the results
+    start: usize,
+    /// Where its output ends, once it has been shown
+    end: Option<usize>,
+}
+
+/// Results shown per vector search
+const VECTOR_RESULTS: usize = 20;
+
 pub struct TuiApp {
     // System database for connection history
     pub system_db: SystemDatabase,
     
     // Current database connection (optional)
-    pub db: Option<Database>,
+    pub db: Option<Arc<Database>>,
     pub db_path: Option<String>,
     
     // UI state
@@ -53,26 +106,76 @@ pub struct TuiApp {
     
     // Input
     pub input: String,
+    /// Byte offset into `input`, always on a character boundary
     pub cursor_position: usize,
     pub command_history: Vec<String>,
     pub history_index: Option<usize>,
+    /// The completion popup, while the word being typed has candidates
+    pub completion: Option<Completion>,
     
     // Results
-    pub results: Vec<String>,
+    pub results: Results,
     pub results_scroll: usize,
+    /// Documents behind the results, from the last find
+    pub result_documents: Vec<serde_json::Value>,
     
     // Collections
     pub collections: Vec<(String, usize)>,
     pub selected_collection: usize,
+
+    // Vector explorer
+    pub vector_collections: Vec<VectorCollectionStats>,
+    pub selected_vector_collection: usize,
+    pub vector_results: Vec<VectorSearchResult>,
+    pub selected_vector_result: usize,
+    /// The plot shown in place of the results, as (collection, projection)
+    pub vector_projection: Option<(String, Projection)>,
+
+    // Collection stats
+    pub db_stats: Option<DatabaseStats>,
+    /// Indexes that would have spared this session's queries a full scan
+    pub index_suggestions: Vec<IndexSuggestion>,
+    /// Document counts of the selected collection over time
+    pub collection_history: Vec<(DateTime<Utc>, usize)>,
+
+    // Document editing
+    pub editor: Option<Editor>,
+    /// Validation or save error shown in the editor
+    pub editor_message: Option<String>,
+    /// The last single document shown, as (collection, id), for `e`
+    pub last_document: Option<(String, String)>,
+    confirm_delete: bool,
+    external_edit: bool,
     
     // Connection history
     pub connections: Vec<DatabaseConnection>,
     pub selected_connection: usize,
+
+    // Background work
+    /// The command running on a worker thread, if any
+    pub task: Option<Task>,
+    /// Threads of cancelled tasks, waited for before exiting
+    cancelled: Vec<JoinHandle<()>>,
+
+    // Watch mode
+    /// Changes to the open database, while watch mode is on
+    watch: Option<ChangeStream>,
+    /// Whether a change arrived that the view has not caught up with
+    watch_pending: bool,
+    watch_refreshed: Instant,
+    live_find: Option<LiveFind>,
     
     // UI
     pub show_help: bool,
     pub status_message: String,
     pub should_quit: bool,
+    /// Colors from `tui.toml`
+    pub theme: Theme,
+    /// Normal-mode keys from `tui.toml`
+    pub keymap: Keymap,
+
+    /// Settings databases are opened with
+    config: Config,
 }
 
 impl TuiApp {
@@ -80,6 +183,9 @@ impl TuiApp {
     pub fn new() -> Result<Self> {
         let system_db = SystemDatabase::init()?;
         let connections = system_db.list_connections().unwrap_or_default();
+        let tui_config = TuiConfig::load()?;
+        let mut results = Results::default();
+        results.push_text(WELCOME);
 
         Ok(Self {
             system_db,
@@ -92,30 +198,48 @@ impl TuiApp {
             cursor_position: 0,
             command_history: Vec::new(),
             history_index: None,
-            results: vec![
-                "╔══════════════════════════════════════════════════════════╗".to_string(),
-                "║         Welcome to KeraDB Terminal User Interface        ║".to_string(),
-                "╚══════════════════════════════════════════════════════════╝".to_string(),
-                String::new(),
-                "Select a database from history or create/open a new one:".to_string(),
-                String::new(),
-                "  [n] Create new database".to_string(),
-                "  [o] Open existing database".to_string(),
-                "  [Enter] Connect to selected".to_string(),
-                "  [d] Remove from history".to_string(),
-                "  [?] Show help".to_string(),
-            ],
+            completion: None,
+            results,
             results_scroll: 0,
+            result_documents: Vec::new(),
             collections: Vec::new(),
             selected_collection: 0,
+            vector_collections: Vec::new(),
+            selected_vector_collection: 0,
+            vector_results: Vec::new(),
+            selected_vector_result: 0,
+            vector_projection: None,
+            db_stats: None,
+            index_suggestions: Vec::new(),
+            collection_history: Vec::new(),
+            editor: None,
+            editor_message: None,
+            last_document: None,
+            confirm_delete: false,
+            external_edit: false,
             connections,
             selected_connection: 0,
+            task: None,
+            cancelled: Vec::new(),
+            watch: None,
+            watch_pending: false,
+            watch_refreshed: Instant::now(),
+            live_find: None,
             show_help: false,
             status_message: "[n] New  [o] Open  [Enter] Connect  [d] Delete  [?] Help  [Ctrl+Q] Quit".into(),
             should_quit: false,
+            theme: tui_config.theme,
+            keymap: tui_config.keymap,
+            config: Config::default(),
         })
     }
 
+    /// Open databases with custom configuration
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
     /// Create a new TUI app and connect to a specific database
     pub fn with_database<P: AsRef<Path>>(path: P) -> Result<Self> {
         let mut app = Self::new()?;
@@ -125,35 +249,88 @@ impl TuiApp {
 
     /// Connect to a database
     pub fn connect_to_database(&mut self, path: String) -> Result<()> {
+        // Settings chosen when the database was created win over the TUI's
+        let mut config = self.config.clone();
+        if let Some(connection) = self.connections.iter().find(|c| c.path == path) {
+            connection.apply_settings(&mut config);
+        }
+
         // Open or create the database
         let db = if Path::new(&path).exists() {
-            Database::open(&path)?
+            Database::open_with_config(&path, config)?
         } else {
-            Database::create(&path)?
+            Database::create_with_config(&path, config)?
         };
 
         // Register in system database
         self.system_db.register_connection(&path)?;
+        self.attach(path, db)
+    }
 
+    /// Create a database from the new-database dialog and connect to it
+    fn create_database(&mut self) {
+        let Some(Editor::Database(form)) = self.editor.as_ref() else { return };
+        let new = match form.database(&self.config) {
+            Ok(new) if Path::new(&new.path).exists() => {
+                self.editor_message = Some(format!("{} already exists; use 'open' instead", new.path));
+                return;
+            }
+            Ok(new) => new,
+            Err(e) => {
+                self.editor_message = Some(e);
+                return;
+            }
+        };
+
+        let result = Database::create_with_config(&new.path, new.config.clone()).and_then(|db| {
+            for (name, config) in new.vectors.iter() {
+                db.create_vector_collection(name, config.clone())?;
+            }
+            Ok(db)
+        });
+        let db = match result {
+            Ok(db) => db,
+            Err(e) => {
+                self.editor_message = Some(format!("Error: {}", e));
+                return;
+            }
+        };
+        let registered = self
+            .system_db
+            .register_connection(&new.path)
+            .and_then(|_| self.system_db.set_connection_settings(&new.path, &new.config));
+        if let Err(e) = registered.and_then(|_| self.attach(new.path.clone(), db)) {
+            self.editor_message = Some(format!("Error: {}", e));
+            return;
+        }
+        match new.vectors.len() {
+            0 => self.close_editor(&format!("✓ Created {}", new.path)),
+            n => self.close_editor(&format!("✓ Created {} with {} vector collection(s)", new.path, n)),
+        }
+    }
+
+    /// Make an open database the current one
+    fn attach(&mut self, path: String, db: Database) -> Result<()> {
         // Update stats
         let collections = db.list_collections();
         let total_docs: usize = collections.iter().map(|(_, c)| c).sum();
         self.system_db.update_connection_stats(&path, collections.len(), total_docs)?;
+        let _ = self.system_db.record_collection_counts(&path, &collections);
 
         // Update app state
         self.collections = collections;
-        self.db = Some(db);
+        self.db = Some(Arc::new(db));
         self.db_path = Some(path.clone());
         self.screen = AppScreen::DatabaseExplorer;
         self.focused = FocusedPanel::Query;
         self.selected_collection = 0;
         
-        self.results.push(String::new());
-        self.results.push(format!("✓ Connected to: {}", path));
-        self.results.push(format!("  {} collections, {} total documents", 
+        self.results.push_blank();
+        self.results.push(LineKind::Success, format!("✓ Connected to: {}", path));
+        self.results.push(LineKind::Plain, format!("  {} collections, {} total documents", 
             self.collections.len(), total_docs));
-        self.results.push(String::new());
-        self.results.push("Type commands or press [i] to enter insert mode.".to_string());
+        self.results.push_blank();
+        self.results.push(LineKind::Plain, "Type commands or press [i] to enter insert mode.");
         
         self.status_message = format!("Connected: {} | [Esc] Disconnect | [?] Help", 
             Path::new(&path).file_name().unwrap_or_default().to_string_lossy());
@@ -167,12 +344,25 @@ impl TuiApp {
     /// Disconnect from current database
     pub fn disconnect(&mut self) {
         if let Some(ref path) = self.db_path {
-            self.results.push(format!("✓ Disconnected from: {}", path));
+            self.results.push(LineKind::Success, format!("✓ Disconnected from: {}", path));
+        }
+        if let Some(task) = self.task.take() {
+            self.cancelled.push(task.cancel());
         }
+        self.watch = None;
+        self.live_find = None;
         
         self.db = None;
         self.db_path = None;
         self.collections.clear();
+        self.last_document = None;
+        self.result_documents.clear();
+        self.vector_collections.clear();
+        self.vector_results.clear();
+        self.vector_projection = None;
+        self.db_stats = None;
+        self.index_suggestions.clear();
+        self.collection_history.clear();
         self.screen = AppScreen::ConnectionManager;
         self.focused = FocusedPanel::Connections;
         self.status_message = "[n] New  [o] Open  [Enter] Connect  [d] Delete  [?] Help".into();
@@ -203,6 +393,21 @@ impl TuiApp {
                 AppEvent::Key(key) => self.handle_key_event(key),
                 AppEvent::Tick => {}
             }
+
+            if std::mem::take(&mut self.external_edit) {
+                if let Err(e) = self.edit_externally(&mut terminal) {
+                    self.editor_message = Some(format!("Could not run editor: {}", e));
+                }
+            }
+
+            if let Some(mut task) = self.task.take() {
+                match task.poll() {
+                    Some(result) => self.finish_task(&task, result),
+                    None => self.task = Some(task),
+                }
+            }
+            self.cancelled.retain(|handle| !handle.is_finished());
+            self.poll_watch();
         }
 
         // Restore terminal
@@ -213,6 +418,14 @@ impl TuiApp {
         )?;
         terminal.show_cursor()?;
 
+        // Let unfinished writes complete before the database is closed
+        if let Some(task) = self.task.take() {
+            self.cancelled.push(task.cancel());
+        }
+        for handle in self.cancelled.drain(..) {
+            let _ = handle.join();
+        }
+
         Ok(())
     }
 
@@ -223,25 +436,31 @@ impl TuiApp {
             return;
         }
 
+        // Esc stops a running command before it means anything else
+        if key.code == KeyCode::Esc && matches!(self.mode, AppMode::Normal | AppMode::Insert) {
+            if let Some(task) = self.task.take() {
+                self.cancel_task(task);
+                return;
+            }
+        }
+
         match self.mode {
             AppMode::Normal => self.handle_normal_mode(key),
             AppMode::Insert => self.handle_insert_mode(key),
             AppMode::Command => self.handle_command_mode(key),
+            AppMode::Edit => self.handle_edit_mode(key),
         }
     }
 
     fn handle_normal_mode(&mut self, key: KeyEvent) {
         match key.code {
-            KeyCode::Char('?') | KeyCode::F(1) => {
-                self.show_help = !self.show_help;
-            }
             KeyCode::Tab => {
                 self.focused = match self.screen {
                     AppScreen::ConnectionManager => match self.focused {
                         FocusedPanel::Connections => FocusedPanel::Results,
                         _ => FocusedPanel::Connections,
                     },
-                    AppScreen::DatabaseExplorer => match self.focused {
+                    AppScreen::DatabaseExplorer | AppScreen::VectorExplorer | AppScreen::CollectionStats => match self.focused {
                         FocusedPanel::Collections => FocusedPanel::Query,
                         FocusedPanel::Query => FocusedPanel::Results,
                         FocusedPanel::Results => FocusedPanel::Collections,
@@ -255,7 +474,7 @@ impl TuiApp {
                         FocusedPanel::Connections => FocusedPanel::Results,
                         _ => FocusedPanel::Connections,
                     },
-                    AppScreen::DatabaseExplorer => match self.focused {
+                    AppScreen::DatabaseExplorer | AppScreen::VectorExplorer | AppScreen::CollectionStats => match self.focused {
                         FocusedPanel::Collections => FocusedPanel::Results,
                         FocusedPanel::Query => FocusedPanel::Collections,
                         FocusedPanel::Results => FocusedPanel::Query,
@@ -263,57 +482,124 @@ impl TuiApp {
                     },
                 };
             }
+            KeyCode::Enter if self.screen == AppScreen::ConnectionManager
+                && self.focused == FocusedPanel::Connections
+                && !self.connections.is_empty() => {
+                let conn = &self.connections[self.selected_connection];
+                let path = conn.path.clone();
+                if let Err(e) = self.connect_to_database(path) {
+                    self.results.push(LineKind::Error, format!("✗ Error: {}", e));
+                    self.status_message = format!("Failed to connect: {}", e);
+                }
+            }
+            KeyCode::Enter if self.focused == FocusedPanel::Query && self.screen == AppScreen::DatabaseExplorer => {
+                self.handle_action(Action::Insert);
+            }
+            KeyCode::Enter if self.screen == AppScreen::VectorExplorer => self.handle_action(Action::Search),
+            KeyCode::Esc if matches!(self.screen, AppScreen::VectorExplorer | AppScreen::CollectionStats) => {
+                self.screen = AppScreen::DatabaseExplorer;
+                self.focused = FocusedPanel::Query;
+                self.update_status_for_screen();
+            }
+            KeyCode::Esc if self.screen == AppScreen::DatabaseExplorer => {
+                self.disconnect();
+            }
+            _ => {
+                if let Some(action) = self.keymap.action(&key) {
+                    self.handle_action(action);
+                }
+            }
+        }
+    }
+
+    /// Run what a normal-mode key is bound to, where it applies
+    fn handle_action(&mut self, action: Action) {
+        match action {
+            Action::Help => {
+                self.show_help = !self.show_help;
+            }
             // Connection manager specific keys
-            KeyCode::Char('n') if self.screen == AppScreen::ConnectionManager => {
-                self.mode = AppMode::Insert;
-                self.input = "new ".to_string();
-                self.cursor_position = self.input.len();
-                self.status_message = "Enter path for new database (e.g., new mydb.ndb)".into();
+            Action::NewDatabase if self.screen == AppScreen::ConnectionManager => {
+                self.show_editor(Editor::Database(DatabaseForm::new(&self.config)));
             }
-            KeyCode::Char('o') if self.screen == AppScreen::ConnectionManager => {
+            Action::OpenDatabase if self.screen == AppScreen::ConnectionManager => {
                 self.mode = AppMode::Insert;
                 self.input = "open ".to_string();
                 self.cursor_position = self.input.len();
                 self.status_message = "Enter path to open (e.g., open mydb.ndb)".into();
             }
-            KeyCode::Enter if self.screen == AppScreen::ConnectionManager && self.focused == FocusedPanel::Connections => {
-                if !self.connections.is_empty() {
-                    let conn = &self.connections[self.selected_connection];
-                    let path = conn.path.clone();
-                    if let Err(e) = self.connect_to_database(path) {
-                        self.results.push(format!("✗ Error: {}", e));
-                        self.status_message = format!("Failed to connect: {}", e);
-                    }
-                }
-            }
-            KeyCode::Char('d') if self.screen == AppScreen::ConnectionManager && self.focused == FocusedPanel::Connections => {
-                // Delete connection from history
-                if !self.connections.is_empty() {
-                    let conn = &self.connections[self.selected_connection];
-                    let path = conn.path.clone();
-                    if self.system_db.remove_connection(&path).is_ok() {
-                        self.results.push(format!("✓ Removed from history: {}", path));
-                        self.connections = self.system_db.list_connections().unwrap_or_default();
-                        if self.selected_connection >= self.connections.len() && !self.connections.is_empty() {
-                            self.selected_connection = self.connections.len() - 1;
-                        }
+            // Delete connection from history
+            Action::RemoveConnection if self.screen == AppScreen::ConnectionManager
+                && self.focused == FocusedPanel::Connections
+                && !self.connections.is_empty() => {
+                let conn = &self.connections[self.selected_connection];
+                let path = conn.path.clone();
+                if self.system_db.remove_connection(&path).is_ok() {
+                    self.results.push(LineKind::Success, format!("✓ Removed from history: {}", path));
+                    self.connections = self.system_db.list_connections().unwrap_or_default();
+                    if self.selected_connection >= self.connections.len() && !self.connections.is_empty() {
+                        self.selected_connection = self.connections.len() - 1;
                     }
                 }
             }
             // Database explorer specific keys
-            KeyCode::Char('i') | KeyCode::Enter if self.focused == FocusedPanel::Query && self.screen == AppScreen::DatabaseExplorer => {
+            Action::Insert if self.focused == FocusedPanel::Query && self.screen == AppScreen::DatabaseExplorer => {
                 self.mode = AppMode::Insert;
                 self.status_message = "-- INSERT MODE -- (Esc to exit, Enter to execute)".into();
             }
-            KeyCode::Char(':') => {
+            // Vector explorer
+            Action::Vectors if self.screen == AppScreen::DatabaseExplorer => self.open_vector_explorer(),
+            Action::Vectors if self.screen == AppScreen::VectorExplorer => {
+                self.screen = AppScreen::DatabaseExplorer;
+                self.focused = FocusedPanel::Query;
+                self.update_status_for_screen();
+            }
+            // Collection stats
+            Action::Stats if self.screen == AppScreen::DatabaseExplorer => self.open_stats(),
+            Action::Stats if self.screen == AppScreen::CollectionStats => {
+                self.screen = AppScreen::DatabaseExplorer;
+                self.focused = FocusedPanel::Query;
+                self.update_status_for_screen();
+            }
+            Action::Insert | Action::Search if self.screen == AppScreen::VectorExplorer => {
+                self.focused = FocusedPanel::Query;
+                self.mode = AppMode::Insert;
+                self.status_message = "Search text, or a JSON vector such as [0.1, 0.2, ...] (Enter to search)".into();
+            }
+            Action::Plot if self.screen == AppScreen::VectorExplorer => self.toggle_plot(),
+            Action::Edit if self.screen == AppScreen::DatabaseExplorer => {
+                match self.last_document.clone() {
+                    Some((collection, id)) => self.open_editor(&collection, &id),
+                    None => self.status_message = "Nothing to edit: find a document by ID first".into(),
+                }
+            }
+            Action::Filter if self.screen == AppScreen::DatabaseExplorer => {
+                match self.collections.get(self.selected_collection) {
+                    Some((collection, _)) => self.open_query_builder(&collection.clone()),
+                    None => self.status_message = "No collection selected; use 'filter <collection>'".into(),
+                }
+            }
+            Action::Add if self.screen == AppScreen::DatabaseExplorer => {
+                match self.collections.get(self.selected_collection) {
+                    Some((collection, _)) => self.open_form(&collection.clone()),
+                    None => self.status_message = "No collection selected; use 'add <collection>'".into(),
+                }
+            }
+            Action::Export if matches!(self.screen, AppScreen::DatabaseExplorer | AppScreen::VectorExplorer) => {
+                self.mode = AppMode::Command;
+                self.input = "export ".to_string();
+                self.cursor_position = self.input.len();
+                self.status_message = "Export results to a .json, .ndjson or .csv file (Enter to write)".into();
+            }
+            Action::Command => {
                 self.mode = AppMode::Command;
                 self.input.clear();
                 self.cursor_position = 0;
                 self.status_message = ":".into();
             }
-            KeyCode::Char('j') | KeyCode::Down => self.handle_down(),
-            KeyCode::Char('k') | KeyCode::Up => self.handle_up(),
-            KeyCode::Char('g') => {
+            Action::Down => self.handle_down(),
+            Action::Up => self.handle_up(),
+            Action::Top => {
                 match self.focused {
                     FocusedPanel::Connections => self.selected_connection = 0,
                     FocusedPanel::Collections => self.selected_collection = 0,
@@ -321,7 +607,7 @@ impl TuiApp {
                     _ => {}
                 }
             }
-            KeyCode::Char('G') => {
+            Action::Bottom => {
                 match self.focused {
                     FocusedPanel::Connections if !self.connections.is_empty() => {
                         self.selected_connection = self.connections.len() - 1;
@@ -335,17 +621,23 @@ impl TuiApp {
                     _ => {}
                 }
             }
-            KeyCode::Char('r') => {
+            Action::Refresh => {
                 self.refresh();
             }
-            KeyCode::Esc if self.screen == AppScreen::DatabaseExplorer => {
-                self.disconnect();
+            Action::Watch if self.db.is_some() => self.toggle_watch(),
+            Action::Fold if self.focused == FocusedPanel::Results => {
+                if let Some(row) = self.results.toggle(self.results_scroll) {
+                    self.results_scroll = row;
+                }
             }
             _ => {}
         }
     }
 
     fn handle_insert_mode(&mut self, key: KeyEvent) {
+        if self.handle_completion_key(key) {
+            return;
+        }
         match key.code {
             KeyCode::Esc => {
                 self.mode = AppMode::Normal;
@@ -353,27 +645,31 @@ impl TuiApp {
                 self.cursor_position = 0;
                 self.update_status_for_screen();
             }
+            // Keep reading while a JSON object or array is open, so
+            // pretty-printed documents can be typed or pasted
+            KeyCode::Enter if parser::is_incomplete(&self.input) => {
+                self.input.insert(self.cursor_position, '\n');
+                self.cursor_position += 1;
+            }
+            KeyCode::Enter if self.screen == AppScreen::VectorExplorer => {
+                self.search_vectors();
+            }
             KeyCode::Enter => {
                 self.execute_input();
             }
-            KeyCode::Backspace => {
-                if self.cursor_position > 0 {
-                    self.cursor_position -= 1;
-                    self.input.remove(self.cursor_position);
-                }
+            KeyCode::Tab => self.complete_input(),
+            KeyCode::Backspace if self.cursor_position > 0 => {
+                self.cursor_position = previous_char(&self.input, self.cursor_position);
+                self.input.remove(self.cursor_position);
             }
-            KeyCode::Delete => {
-                if self.cursor_position < self.input.len() {
-                    self.input.remove(self.cursor_position);
-                }
+            KeyCode::Delete if self.cursor_position < self.input.len() => {
+                self.input.remove(self.cursor_position);
             }
             KeyCode::Left => {
-                self.cursor_position = self.cursor_position.saturating_sub(1);
+                self.cursor_position = previous_char(&self.input, self.cursor_position);
             }
-            KeyCode::Right => {
-                if self.cursor_position < self.input.len() {
-                    self.cursor_position += 1;
-                }
+            KeyCode::Right if self.cursor_position < self.input.len() => {
+                self.cursor_position = next_char(&self.input, self.cursor_position);
             }
             KeyCode::Home => {
                 self.cursor_position = 0;
@@ -381,16 +677,14 @@ impl TuiApp {
             KeyCode::End => {
                 self.cursor_position = self.input.len();
             }
-            KeyCode::Up => {
-                if !self.command_history.is_empty() {
-                    self.history_index = Some(match self.history_index {
-                        Some(i) => i.saturating_sub(1),
-                        None => self.command_history.len() - 1,
-                    });
-                    if let Some(idx) = self.history_index {
-                        self.input = self.command_history[idx].clone();
-                        self.cursor_position = self.input.len();
-                    }
+            KeyCode::Up if !self.command_history.is_empty() => {
+                self.history_index = Some(match self.history_index {
+                    Some(i) => i.saturating_sub(1),
+                    None => self.command_history.len() - 1,
+                });
+                if let Some(idx) = self.history_index {
+                    self.input = self.command_history[idx].clone();
+                    self.cursor_position = self.input.len();
                 }
             }
             KeyCode::Down => {
@@ -407,40 +701,306 @@ impl TuiApp {
             }
             KeyCode::Char(c) => {
                 self.input.insert(self.cursor_position, c);
-                self.cursor_position += 1;
+                self.cursor_position += c.len_utf8();
+            }
+            _ => {}
+        }
+        self.update_completion();
+    }
+
+    /// Keys the completion popup takes while it is open
+    fn handle_completion_key(&mut self, key: KeyEvent) -> bool {
+        let Some(completion) = self.completion.as_mut() else { return false };
+        match key.code {
+            KeyCode::Up => completion.select(-1),
+            KeyCode::Down => completion.select(1),
+            KeyCode::Esc => self.completion = None,
+            KeyCode::Tab => {
+                self.cursor_position = completion.apply(&mut self.input, self.cursor_position);
+                self.update_completion();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Tab with no popup open: complete a word with only one candidate
+    fn complete_input(&mut self) {
+        self.update_completion();
+        if let Some(completion) = self.completion.take().filter(|c| c.candidates.len() == 1) {
+            self.cursor_position = completion.apply(&mut self.input, self.cursor_position);
+        }
+    }
+
+    /// Open, update or close the completion popup for the input as it is now
+    fn update_completion(&mut self) {
+        let command_mode = self.mode == AppMode::Command;
+        self.completion = match (self.mode, self.screen) {
+            (AppMode::Insert, AppScreen::VectorExplorer) => None,
+            (AppMode::Insert | AppMode::Command, _) => {
+                Completion::new(self.db.as_deref(), &self.input, self.cursor_position, command_mode)
+            }
+            _ => None,
+        };
+    }
+
+    fn handle_edit_mode(&mut self, key: KeyEvent) {
+        let Some(editor) = self.editor.as_mut() else {
+            self.mode = AppMode::Normal;
+            return;
+        };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let confirm_delete = std::mem::take(&mut self.confirm_delete);
+        match (key.code, editor) {
+            (KeyCode::Esc, _) => self.close_editor("Edit cancelled"),
+            (KeyCode::Char('s') | KeyCode::Char('r'), Editor::Query(_)) if ctrl => self.run_query_builder(),
+            (KeyCode::Char('d'), Editor::Query(builder)) if ctrl => {
+                builder.remove_row();
+                self.count_query_matches();
+            }
+            (_, Editor::Query(builder)) if !ctrl => {
+                let changed = builder.handle_key(key);
+                if changed {
+                    self.count_query_matches();
+                }
+            }
+            (KeyCode::Char('s'), Editor::Database(_)) if ctrl => self.create_database(),
+            (_, Editor::Database(form)) if !ctrl => {
+                form.handle_key(key);
+                self.editor_message = None;
+            }
+            (KeyCode::Char('s'), _) if ctrl => self.save_editor(),
+            (KeyCode::Char('e'), Editor::Json(_)) if ctrl => self.external_edit = true,
+            // Continue in the JSON editor with what the form holds so far
+            (KeyCode::Char('e'), Editor::Form(form)) if ctrl => match form.document() {
+                Ok(doc) => {
+                    let collection = form.collection.clone();
+                    self.editor = Some(Editor::Json(DocumentEditor::insert(&collection, &doc)));
+                    self.editor_message = None;
+                }
+                Err(e) => self.editor_message = Some(e),
+            },
+            (KeyCode::Char('d'), Editor::Json(DocumentEditor { target: EditTarget::Update { .. }, .. })) if ctrl => {
+                if confirm_delete {
+                    self.delete_edited();
+                } else {
+                    self.confirm_delete = true;
+                    self.editor_message = Some("Press Ctrl+D again to delete this document".into());
+                }
+            }
+            (_, Editor::Json(editor)) if !ctrl => {
+                editor.text.handle_key(key);
+            }
+            (_, Editor::Form(form)) if !ctrl => {
+                form.handle_key(key);
             }
             _ => {}
         }
     }
 
+    /// Open the JSON editor on a stored document
+    fn open_editor(&mut self, collection: &str, id: &str) {
+        let Some(db) = self.db.as_ref() else {
+            self.status_message = "Not connected".into();
+            return;
+        };
+        match db.find_by_id(collection, id) {
+            Ok(doc) => self.show_editor(Editor::Json(DocumentEditor::edit(collection, &doc))),
+            Err(e) => self.status_message = format!("Error: {}", e),
+        }
+    }
+
+    /// Open the guided form for a new document
+    fn open_form(&mut self, collection: &str) {
+        self.show_editor(Editor::Form(InsertForm::new(collection)));
+    }
+
+    fn show_editor(&mut self, editor: Editor) {
+        self.status_message = match editor {
+            Editor::Json(_) => "Ctrl+S Save | Esc Cancel | Ctrl+E $EDITOR | Ctrl+D Delete".into(),
+            Editor::Form(_) => "Tab Next field | Enter New row | Ctrl+S Insert | Ctrl+E Edit as JSON | Esc Cancel".into(),
+            Editor::Query(_) => {
+                "Tab Next column | ←/→ Operator | Enter New row | Ctrl+D Remove row | Ctrl+R Run | Esc Cancel".into()
+            }
+            Editor::Database(_) => "Tab/↑/↓ Move | ←/→ Change option | Ctrl+S Create | Esc Cancel".into(),
+        };
+        self.editor = Some(editor);
+        self.editor_message = None;
+        self.confirm_delete = false;
+        self.mode = AppMode::Edit;
+    }
+
+    /// Open the filter builder on a collection
+    fn open_query_builder(&mut self, collection: &str) {
+        self.show_editor(Editor::Query(QueryBuilder::new(collection)));
+        self.count_query_matches();
+    }
+
+    /// Recount the documents the filter builder's rows match
+    fn count_query_matches(&mut self) {
+        let (Some(Editor::Query(builder)), Some(db)) = (self.editor.as_mut(), self.db.as_ref()) else { return };
+        builder.matches = builder
+            .query()
+            .and_then(|query| query.filter.count(db, &query.collection).map_err(|e| e.to_string()));
+    }
+
+    /// Run the filter builder's query as if it had been typed
+    fn run_query_builder(&mut self) {
+        let Some(Editor::Query(builder)) = self.editor.as_ref() else { return };
+        if let Err(e) = builder.query() {
+            self.editor_message = Some(e);
+            return;
+        }
+        let query = builder.query_text();
+        self.close_editor("Query run");
+        self.input = query;
+        self.execute_input();
+    }
+
+    fn close_editor(&mut self, status: &str) {
+        self.editor = None;
+        self.editor_message = None;
+        self.confirm_delete = false;
+        self.mode = AppMode::Normal;
+        self.update_status_for_screen();
+        self.status_message = format!("{} | {}", status, self.status_message);
+    }
+
+    /// Validate the editor's document and write it
+    fn save_editor(&mut self) {
+        let (Some(editor), Some(db)) = (self.editor.as_ref(), self.db.as_ref()) else { return };
+        let (target, document) = match editor {
+            Editor::Json(editor) => (editor.target.clone(), editor.document()),
+            Editor::Form(form) => (EditTarget::Insert { collection: form.collection.clone() }, form.document()),
+            Editor::Query(_) | Editor::Database(_) => return,
+        };
+        let document = match document {
+            Ok(document) => document,
+            Err(e) => {
+                self.editor_message = Some(e);
+                return;
+            }
+        };
+
+        let saved = match &target {
+            EditTarget::Update { collection, id } => db.update(collection, id, document),
+            EditTarget::Insert { collection } => db.insert(collection, document).and_then(|id| db.find_by_id(collection, &id)),
+        };
+        match saved {
+            Ok(doc) => {
+                let verb = if matches!(target, EditTarget::Update { .. }) { "Updated" } else { "Inserted" };
+                self.results.push(LineKind::Success, format!("✓ {} {}/{}", verb, target.collection(), doc.id));
+                self.push_document(&doc.to_value());
+                self.last_document = Some((target.collection().to_string(), doc.id));
+                self.refresh_collections();
+                self.update_system_db_stats();
+                self.close_editor(&format!("✓ {}", verb));
+            }
+            Err(e) => self.editor_message = Some(format!("Error: {}", e)),
+        }
+    }
+
+    fn delete_edited(&mut self) {
+        let (Some(Editor::Json(editor)), Some(db)) = (self.editor.as_ref(), self.db.as_ref()) else { return };
+        let EditTarget::Update { collection, id } = editor.target.clone() else { return };
+        match db.delete(&collection, &id) {
+            Ok(_) => {
+                self.results.push(LineKind::Success, format!("✓ Deleted {}/{}", collection, id));
+                self.results.push_blank();
+                self.last_document = None;
+                self.refresh_collections();
+                self.update_system_db_stats();
+                self.close_editor("✓ Deleted");
+            }
+            Err(e) => self.editor_message = Some(format!("Error: {}", e)),
+        }
+    }
+
+    fn push_document(&mut self, value: &serde_json::Value) {
+        self.results.push_json(value);
+        self.results.push_blank();
+        self.results_scroll = self.results.len().saturating_sub(1);
+    }
+
+    /// Hand the JSON editor's text to `$VISUAL`/`$EDITOR` and read it back
+    fn edit_externally(&mut self, terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+        let Some(Editor::Json(editor)) = self.editor.as_mut() else { return Ok(()) };
+        let path = std::env::temp_dir().join(format!("keradb-edit-{}.json", std::process::id()));
+        std::fs::write(&path, editor.text.text())?;
+
+        let default = if cfg!(target_os = "windows") { "notepad" } else { "vi" };
+        let command = std::env::var("VISUAL").or_else(|_| std::env::var("EDITOR")).unwrap_or_else(|_| default.to_string());
+        let mut words = command.split_whitespace();
+        let program = words.next().unwrap_or(default);
+
+        disable_raw_mode()?;
+        execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+        let status = std::process::Command::new(program).args(words).arg(&path).status();
+        enable_raw_mode()?;
+        execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+        terminal.clear()?;
+
+        let result = match status {
+            Ok(status) if status.success() => {
+                editor.text = TextArea::new(&std::fs::read_to_string(&path)?);
+                self.editor_message = Some(format!("Loaded changes from {}; Ctrl+S to save", program));
+                Ok(())
+            }
+            Ok(status) => {
+                self.editor_message = Some(format!("{} exited with {}; nothing changed", program, status));
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        };
+        let _ = std::fs::remove_file(&path);
+        result
+    }
+
     fn handle_command_mode(&mut self, key: KeyEvent) {
+        if self.handle_completion_key(key) {
+            return;
+        }
         match key.code {
             KeyCode::Esc => {
                 self.mode = AppMode::Normal;
                 self.input.clear();
+                self.cursor_position = 0;
                 self.update_status_for_screen();
             }
             KeyCode::Enter => {
                 let cmd = self.input.trim().to_string();
                 self.input.clear();
+                self.cursor_position = 0;
                 self.mode = AppMode::Normal;
                 self.execute_command(&cmd);
             }
-            KeyCode::Backspace => {
-                if self.cursor_position > 0 {
-                    self.cursor_position -= 1;
-                    self.input.remove(self.cursor_position);
-                }
+            KeyCode::Backspace if self.cursor_position > 0 => {
+                self.cursor_position = previous_char(&self.input, self.cursor_position);
+                self.input.remove(self.cursor_position);
             }
+            KeyCode::Tab => self.complete_input(),
             KeyCode::Char(c) => {
                 self.input.insert(self.cursor_position, c);
-                self.cursor_position += 1;
+                self.cursor_position += c.len_utf8();
             }
             _ => {}
         }
+        self.update_completion();
     }
 
     fn handle_down(&mut self) {
+        if self.screen == AppScreen::VectorExplorer {
+            match self.focused {
+                FocusedPanel::Collections if !self.vector_collections.is_empty() => {
+                    self.selected_vector_collection = (self.selected_vector_collection + 1) % self.vector_collections.len();
+                }
+                FocusedPanel::Results if self.selected_vector_result + 1 < self.vector_results.len() => {
+                    self.selected_vector_result += 1;
+                }
+                _ => {}
+            }
+            return;
+        }
         match self.focused {
             FocusedPanel::Connections if !self.connections.is_empty() => {
                 self.selected_connection = (self.selected_connection + 1) % self.connections.len();
@@ -448,16 +1008,31 @@ impl TuiApp {
             FocusedPanel::Collections if !self.collections.is_empty() => {
                 self.selected_collection = (self.selected_collection + 1) % self.collections.len();
             }
-            FocusedPanel::Results if !self.results.is_empty() => {
-                if self.results_scroll < self.results.len() - 1 {
-                    self.results_scroll += 1;
-                }
+            FocusedPanel::Results if self.results_scroll + 1 < self.results.len() => {
+                self.results_scroll += 1;
             }
             _ => {}
         }
+        if self.screen == AppScreen::CollectionStats {
+            self.load_collection_history();
+        }
     }
 
     fn handle_up(&mut self) {
+        if self.screen == AppScreen::VectorExplorer {
+            match self.focused {
+                FocusedPanel::Collections if !self.vector_collections.is_empty() => {
+                    self.selected_vector_collection = self.selected_vector_collection
+                        .checked_sub(1)
+                        .unwrap_or(self.vector_collections.len() - 1);
+                }
+                FocusedPanel::Results => {
+                    self.selected_vector_result = self.selected_vector_result.saturating_sub(1);
+                }
+                _ => {}
+            }
+            return;
+        }
         match self.focused {
             FocusedPanel::Connections if !self.connections.is_empty() => {
                 self.selected_connection = self.selected_connection
@@ -474,6 +1049,261 @@ impl TuiApp {
             }
             _ => {}
         }
+        if self.screen == AppScreen::CollectionStats {
+            self.load_collection_history();
+        }
+    }
+
+    fn open_vector_explorer(&mut self) {
+        self.refresh_vector_collections();
+        self.screen = AppScreen::VectorExplorer;
+        self.focused = FocusedPanel::Collections;
+        self.update_status_for_screen();
+    }
+
+    fn open_stats(&mut self) {
+        self.screen = AppScreen::CollectionStats;
+        self.focused = FocusedPanel::Collections;
+        self.update_status_for_screen();
+        self.load_collection_history();
+        self.load_stats();
+    }
+
+    /// Read storage statistics on a worker; they walk the whole data file
+    fn load_stats(&mut self) {
+        // Suggestions are kept in memory, so they are read right away
+        self.index_suggestions = self.db.as_ref().map(|db| db.index_suggestions()).unwrap_or_default();
+        if self.is_busy() {
+            return;
+        }
+        self.start_task("stats".into(), |db, _| Ok(Outcome::Stats(Box::new(db.stats()?))));
+    }
+
+    fn load_collection_history(&mut self) {
+        let (Some(path), Some((collection, _))) = (&self.db_path, self.collections.get(self.selected_collection)) else {
+            self.collection_history.clear();
+            return;
+        };
+        self.collection_history = self.system_db.collection_history(path, collection).unwrap_or_default();
+    }
+
+    fn refresh_vector_collections(&mut self) {
+        let Some(db) = self.db.as_ref() else { return };
+        self.vector_collections = db
+            .list_vector_collections()
+            .into_iter()
+            .filter_map(|(name, _)| db.vector_stats(&name).ok())
+            .collect();
+        self.selected_vector_collection = self.selected_vector_collection.min(self.vector_collections.len().saturating_sub(1));
+    }
+
+    /// Search the selected vector collection for the query input
+    ///
+    /// Input starting with `[` is a vector; anything else is text for the
+    /// collection's embedding provider.
+    fn search_vectors(&mut self) {
+        if self.is_busy() {
+            return;
+        }
+        let input = self.input.trim().to_string();
+        self.input.clear();
+        self.cursor_position = 0;
+        self.mode = AppMode::Normal;
+        if input.is_empty() {
+            self.update_status_for_screen();
+            return;
+        }
+        self.command_history.push(input.clone());
+        self.history_index = None;
+
+        let Some(collection) = self.vector_collections.get(self.selected_vector_collection).map(|s| s.name.clone()) else {
+            self.status_message = "No vector collection selected".into();
+            return;
+        };
+        let query = if input.starts_with('[') {
+            match serde_json::from_str(&input) {
+                Ok(vector) => VectorQuery::Vector(vector),
+                Err(e) => {
+                    self.status_message = format!("Error: invalid vector: {}", e);
+                    return;
+                }
+            }
+        } else {
+            VectorQuery::Text(input.trim_matches('"').to_string())
+        };
+
+        self.start_task(format!("vsearch {}", collection), move |db, _| {
+            let results = query.search(db, &collection, VECTOR_RESULTS, &Filter::default())?;
+            Ok(Outcome::VectorResults { collection, results })
+        });
+    }
+
+    /// Plot the selected vector collection in place of the results, or go
+    /// back to the results
+    fn toggle_plot(&mut self) {
+        if self.vector_projection.take().is_some() {
+            self.update_status_for_screen();
+            return;
+        }
+        if self.is_busy() {
+            return;
+        }
+        let Some(collection) = self.vector_collections.get(self.selected_vector_collection).map(|s| s.name.clone()) else {
+            self.status_message = "No vector collection selected".into();
+            return;
+        };
+        self.start_task(format!("plot {}", collection), move |db, _| {
+            let projection = db.project_vector_collection(&collection, ProjectionMethod::Pca)?;
+            Ok(Outcome::Projection { collection, projection: Box::new(projection) })
+        });
+    }
+
+    /// Run `job` on a worker thread; see [`finish_task`](Self::finish_task)
+    fn start_task<F>(&mut self, label: String, job: F)
+    where
+        F: FnOnce(&Database, &TaskContext) -> Result<Outcome> + Send + 'static,
+    {
+        let Some(db) = self.db.clone() else {
+            self.status_message = "Not connected".into();
+            return;
+        };
+        self.task = Some(Task::spawn(label, db, job));
+    }
+
+    /// Whether a command is still running, saying so in the status bar
+    fn is_busy(&mut self) -> bool {
+        let Some(task) = &self.task else { return false };
+        self.status_message = format!("'{}' is still running | [Esc] Cancel", task.label);
+        true
+    }
+
+    /// Show what a finished task produced
+    fn finish_task(&mut self, task: &Task, result: Result<Outcome>) {
+        match result {
+            Ok(Outcome::Output(output)) => {
+                self.results.push_text(&output.text);
+                if let Some(json) = &output.json {
+                    self.results.push_json(json);
+                }
+                if let Some(live) = self.live_find.as_mut().filter(|live| live.end.is_none()) {
+                    live.end = output.documents.is_some().then(|| self.results.mark());
+                }
+                if output.deleted.is_some() && output.deleted == self.last_document {
+                    self.last_document = None;
+                }
+                if output.document.is_some() {
+                    self.last_document = output.document;
+                }
+                if let Some(documents) = output.documents {
+                    self.result_documents = documents;
+                }
+                self.refresh_collections();
+                if output.modified {
+                    self.update_system_db_stats();
+                }
+            }
+            Ok(Outcome::Stats(stats)) => {
+                self.db_stats = Some(*stats);
+                return;
+            }
+            Ok(Outcome::Refresh(output)) => {
+                // Swap the find's output in place, leaving anything after it
+                let Some(live) = self.live_find.as_mut() else { return };
+                let Some(end) = live.end else { return };
+                let mut fresh = Results::default();
+                fresh.push_text(&output.text);
+                if let Some(json) = &output.json {
+                    fresh.push_json(json);
+                }
+                live.end = Some(self.results.replace(live.start..end, fresh));
+                self.result_documents = output.documents.unwrap_or_default();
+                self.results_scroll = self.results_scroll.min(self.results.len().saturating_sub(1));
+                return;
+            }
+            Ok(Outcome::Projection { collection, projection }) => {
+                self.status_message = format!(
+                    "{} vector(s) of '{}' by PCA | [p] Back to results | [x] Export points",
+                    projection.points.len(),
+                    collection
+                );
+                self.vector_projection = Some((collection, *projection));
+                return;
+            }
+            Ok(Outcome::VectorResults { collection, results }) => {
+                self.status_message = format!("{} result(s) from '{}' | [j/k] Browse results | [/] New search", results.len(), collection);
+                self.vector_results = results;
+                self.selected_vector_result = 0;
+                self.vector_projection = None;
+                self.focused = FocusedPanel::Results;
+                return;
+            }
+            Err(e) => {
+                self.results.push(LineKind::Error, format!("✗ Error: {}", e));
+                self.status_message = format!("Error in '{}': {}", task.label, e);
+            }
+        }
+        self.results.push_blank();
+        self.results_scroll = self.results.len().saturating_sub(1);
+    }
+
+    /// Turn watch mode on or off
+    fn toggle_watch(&mut self) {
+        if self.watch.take().is_some() {
+            self.status_message = "Watch mode off".into();
+            return;
+        }
+        let Some(db) = &self.db else { return };
+        self.watch = Some(db.watch_all());
+        self.watch_pending = false;
+        self.status_message = format!(
+            "Watching for changes: counts and the last find refresh as documents change | [{}] Stop",
+            self.keymap.keys(Action::Watch)
+        );
+    }
+
+    pub fn is_watching(&self) -> bool {
+        self.watch.is_some()
+    }
+
+    /// Follow the change stream while watch mode is on
+    fn poll_watch(&mut self) {
+        let (Some(stream), Some(db)) = (self.watch.as_mut(), self.db.as_ref()) else { return };
+        match stream.try_next() {
+            Ok(None) => {}
+            // One refresh covers everything else that arrived, so skip to the
+            // end; that also recovers a stream that fell behind the oplog
+            Ok(Some(_)) | Err(_) => {
+                *stream = db.watch_all();
+                self.watch_pending = true;
+            }
+        }
+        if !self.watch_pending || self.watch_refreshed.elapsed() < WATCH_INTERVAL {
+            return;
+        }
+        self.watch_refreshed = Instant::now();
+        self.watch_pending = false;
+        self.refresh_collections();
+
+        // Run the last find again, once it has been shown
+        let Some(live) = self.live_find.as_ref().filter(|live| live.end.is_some()) else { return };
+        if self.task.is_some() {
+            // Try again once the running command is done
+            self.watch_pending = true;
+            return;
+        }
+        let command = live.command.clone();
+        self.start_task("watch: refresh".into(), move |db, _| {
+            run_command(db, command).map(|output| Outcome::Refresh(Box::new(output)))
+        });
+    }
+
+    /// Stop waiting for a task and tell it to stop
+    fn cancel_task(&mut self, task: Task) {
+        self.results.push(LineKind::Error, format!("✗ Cancelled after {:.1}s: {}", task.elapsed().as_secs_f64(), task.label));
+        self.results.push_blank();
+        self.results_scroll = self.results.len().saturating_sub(1);
+        self.status_message = format!("Cancelled '{}'", task.label);
+        self.cancelled.push(task.cancel());
     }
 
     fn update_status_for_screen(&mut self) {
@@ -488,12 +1318,16 @@ impl TuiApp {
                         .map(|n| n.to_string_lossy().to_string())
                         .unwrap_or_else(|| "unknown".to_string()))
             }
+            AppScreen::VectorExplorer => {
+                "Vectors | [/] Search | [p] Plot | [j/k] Select | [Tab] Switch panel | [v/Esc] Back to documents".into()
+            }
+            AppScreen::CollectionStats => "Stats | [j/k] Collection | [r] Reload | [s/Esc] Back to documents".into(),
         };
     }
 
     fn execute_input(&mut self) {
         let input = self.input.trim().to_string();
-        if input.is_empty() {
+        if input.is_empty() || self.is_busy() {
             return;
         }
 
@@ -502,25 +1336,28 @@ impl TuiApp {
         self.history_index = None;
 
         // Parse and execute
-        self.results.push(format!("→ {}", input));
+        for (i, line) in input.lines().enumerate() {
+            self.results.push(LineKind::Input, format!("{} {}", if i == 0 { "→" } else { " " }, line));
+        }
         
         let result = self.process_command(&input);
         match result {
             Ok(output) => {
-                for line in output.lines() {
-                    self.results.push(line.to_string());
-                }
+                self.results.push_text(&output);
                 if !output.is_empty() {
                     self.status_message = "Command executed".into();
                 }
             }
             Err(e) => {
-                self.results.push(format!("✗ Error: {}", e));
+                self.results.push(LineKind::Error, format!("✗ Error: {}", e));
                 self.status_message = format!("Error: {}", e);
             }
         }
 
-        self.results.push(String::new());
+        // A command handed to a worker finishes its output when it is done
+        if self.task.is_none() {
+            self.results.push_blank();
+        }
         self.input.clear();
         self.cursor_position = 0;
 
@@ -528,9 +1365,12 @@ impl TuiApp {
         if !self.results.is_empty() {
             self.results_scroll = self.results.len().saturating_sub(1);
         }
-        
-        self.mode = AppMode::Normal;
-        self.update_status_for_screen();
+
+        // `edit` and `add` leave an editor open
+        if self.mode != AppMode::Edit {
+            self.mode = AppMode::Normal;
+            self.update_status_for_screen();
+        }
     }
 
     fn process_command(&mut self, input: &str) -> Result<String> {
@@ -584,129 +1424,72 @@ impl TuiApp {
                 }
             }
             
-            // Database commands (require connection)
-            "help" => Ok(self.get_help_text()),
-            "collections" | "list" => {
-                if self.db.is_none() {
-                    return Ok("Not connected. Use 'open <path>' or 'new <path>' first.".into());
-                }
-                self.refresh_collections();
-                if self.collections.is_empty() {
-                    Ok("No collections found".into())
-                } else {
-                    let mut output = String::from("Collections:\n");
-                    for (name, count) in &self.collections {
-                        output.push_str(&format!("  • {} ({} documents)\n", name, count));
-                    }
-                    Ok(output)
-                }
-            }
-            "insert" => {
-                let db = self.db.as_ref().ok_or_else(|| anyhow::anyhow!("Not connected"))?;
-                if parts.len() < 3 {
-                    return Ok("Usage: insert <collection> <json>".into());
-                }
-                let collection = parts[1];
-                let json_str = parts[2..].join(" ");
-                let doc: serde_json::Value = serde_json::from_str(&json_str)?;
-                let id = db.insert(collection, doc)?;
-                self.refresh_collections();
-                self.update_system_db_stats();
-                Ok(format!("✓ Inserted with id: {}", id))
-            }
-            "find" => {
-                let db = self.db.as_ref().ok_or_else(|| anyhow::anyhow!("Not connected"))?;
-                if parts.len() < 2 {
-                    return Ok("Usage: find <collection> [id]".into());
-                }
-                let collection = parts[1];
-                if parts.len() >= 3 {
-                    let id = parts[2];
-                    match db.find_by_id(collection, id) {
-                        Ok(doc) => Ok(serde_json::to_string_pretty(&doc.to_value())?),
-                        Err(_) => Ok(format!("Document not found: {}", id)),
-                    }
-                } else {
-                    let docs = db.find_all(collection, Some(20), None)?;
-                    if docs.is_empty() {
-                        Ok("No documents found".into())
-                    } else {
-                        let mut output = serde_json::to_string_pretty(&docs)?;
-                        if db.count(collection) > 20 {
-                            output.push_str("\n... (showing first 20)");
-                        }
-                        Ok(output)
-                    }
-                }
-            }
-            "delete" => {
-                let db = self.db.as_ref().ok_or_else(|| anyhow::anyhow!("Not connected"))?;
-                if parts.len() < 3 {
-                    return Ok("Usage: delete <collection> <id>".into());
-                }
-                let collection = parts[1];
-                let id = parts[2];
-                db.delete(collection, id)?;
-                self.refresh_collections();
-                self.update_system_db_stats();
-                Ok(format!("✓ Deleted: {}", id))
-            }
-            "count" => {
-                let db = self.db.as_ref().ok_or_else(|| anyhow::anyhow!("Not connected"))?;
-                if parts.len() < 2 {
-                    return Ok("Usage: count <collection>".into());
-                }
-                let collection = parts[1];
-                let count = db.count(collection);
-                Ok(format!("{} documents in '{}'", count, collection))
-            }
-            "sync" => {
-                let db = self.db.as_ref().ok_or_else(|| anyhow::anyhow!("Not connected"))?;
-                db.sync()?;
-                Ok("✓ Database synced to disk".into())
-            }
             "clear" => {
                 self.results.clear();
+                self.live_find = None;
                 Ok(String::new())
             }
-            "vcreate" => {
-                let db = self.db.as_ref().ok_or_else(|| anyhow::anyhow!("Not connected"))?;
-                if parts.len() < 3 {
-                    return Ok("Usage: vcreate <name> <dimensions> [distance]".into());
-                }
-                let name = parts[1];
-                let dims: usize = parts[2].parse()?;
-                let distance = if parts.len() > 3 {
-                    match parts[3].to_lowercase().as_str() {
-                        "euclidean" => Distance::Euclidean,
-                        "cosine" => Distance::Cosine,
-                        "dot" => Distance::DotProduct,
-                        _ => Distance::Cosine,
-                    }
-                } else {
-                    Distance::Cosine
-                };
-                let config = VectorConfig::new(dims).with_distance(distance);
-                db.create_vector_collection(name, config)?;
-                Ok(format!("✓ Created vector collection: {} ({} dims)", name, dims))
-            }
-            "vcollections" => {
-                let db = self.db.as_ref().ok_or_else(|| anyhow::anyhow!("Not connected"))?;
-                let collections = db.list_vector_collections();
-                if collections.is_empty() {
-                    Ok("No vector collections found".into())
-                } else {
-                    let mut output = String::from("Vector Collections:\n");
-                    for (name, count) in collections {
-                        output.push_str(&format!("  • {} ({} vectors)\n", name, count));
+
+            "edit" if self.db.is_some() => {
+                match (parts.get(1), parts.get(2)) {
+                    (Some(collection), Some(id)) => self.open_editor(collection, id),
+                    (Some(collection), None) => {
+                        self.show_editor(Editor::Json(DocumentEditor::insert(collection, &serde_json::json!({}))))
                     }
-                    Ok(output)
+                    _ => match self.last_document.clone() {
+                        Some((collection, id)) => self.open_editor(&collection, &id),
+                        None => return Ok("Usage: edit <collection> [id]".into()),
+                    },
                 }
+                Ok(String::new())
             }
-            _ => Ok(format!("Unknown command: '{}'. Type 'help' for commands.", parts[0])),
+            "filter" if self.db.is_some() => match parts.get(1) {
+                Some(collection) => {
+                    self.open_query_builder(collection);
+                    Ok(String::new())
+                }
+                None => Ok("Usage: filter <collection>".into()),
+            },
+            "add" if self.db.is_some() => match parts.get(1) {
+                Some(collection) => {
+                    self.open_form(collection);
+                    Ok(String::new())
+                }
+                None => Ok("Usage: add <collection>".into()),
+            },
+
+            "import" if self.db.is_some() => match (parts.get(1), parts.len() > 2) {
+                (Some(collection), true) => {
+                    let collection = collection.to_string();
+                    let path = PathBuf::from(parts[2..].join(" "));
+                    self.start_task(format!("import {}", collection), move |db, ctx| {
+                        import_file(db, &collection, &path, ctx).map(Outcome::Output)
+                    });
+                    Ok(String::new())
+                }
+                _ => Ok("Usage: import <collection> <file.json|ndjson|csv>".into()),
+            },
+
+            // Database commands (require connection)
+            _ => self.process_db_command(input, parser::parse(input)?),
         }
     }
 
+    fn process_db_command(&mut self, input: &str, command: Command) -> Result<String> {
+        if command == Command::Help {
+            return Ok(self.get_help_text());
+        }
+        if self.db.is_none() {
+            return Ok("Not connected. Use 'open <path>' or 'new <path>' first.".into());
+        }
+        if matches!(command, Command::Find(_)) {
+            self.live_find = Some(LiveFind { command: command.clone(), start: self.results.mark(), end: None });
+        }
+        let label = input.lines().next().unwrap_or_default().chars().take(60).collect();
+        self.start_task(label, move |db, _| run_command(db, command).map(Outcome::Output));
+        Ok(String::new())
+    }
+
     fn execute_command(&mut self, cmd: &str) {
         match cmd {
             "q" | "quit" | "exit" => {
@@ -736,6 +1519,10 @@ impl TuiApp {
                 self.disconnect();
             }
             _ => {
+                if let Some(args) = cmd.strip_prefix("export ") {
+                    self.export_results(args.trim());
+                    return;
+                }
                 // Try to parse as open command
                 if cmd.starts_with("e ") || cmd.starts_with("open ") {
                     let path = cmd.split_whitespace().skip(1).collect::<Vec<_>>().join(" ");
@@ -751,10 +1538,65 @@ impl TuiApp {
         }
     }
 
+    /// Write the documents behind the results to `<path> [json|ndjson|csv]`
+    fn export_results(&mut self, args: &str) {
+        let (path, format) = match args.rsplit_once(' ') {
+            Some((path, name)) if ImportFormat::from_name(name).is_some() => (path.trim(), ImportFormat::from_name(name)),
+            _ => (args, None),
+        };
+        let documents = self.exportable_documents();
+        if documents.is_empty() {
+            self.status_message = "Nothing to export: run a find or a vector search first".into();
+            return;
+        }
+        let path = Path::new(path);
+        let result = match format {
+            Some(format) => Ok(format),
+            None => ImportFormat::from_path(path),
+        }
+        .map_err(anyhow::Error::from)
+        .and_then(|format| export::write_documents(path, &documents, format));
+        self.status_message = match result {
+            Ok(()) => format!("✓ Exported {} document(s) to {}", documents.len(), path.display()),
+            Err(e) => format!("Error: {}", e),
+        };
+    }
+
+    /// The documents behind what the current screen shows
+    fn exportable_documents(&self) -> Vec<serde_json::Value> {
+        match self.screen {
+            AppScreen::VectorExplorer if self.vector_projection.is_some() => self
+                .vector_projection
+                .iter()
+                .flat_map(|(_, projection)| &projection.points)
+                .filter_map(|point| serde_json::to_value(point).ok())
+                .collect(),
+            AppScreen::VectorExplorer => self
+                .vector_results
+                .iter()
+                .map(|result| {
+                    serde_json::json!({
+                        "rank": result.rank + 1,
+                        "id": result.document.external_id.clone().unwrap_or_else(|| result.document.id.to_string()),
+                        "score": result.score,
+                        "text": result.document.text,
+                        "metadata": result.document.metadata,
+                    })
+                })
+                .collect(),
+            _ => self.result_documents.clone(),
+        }
+    }
+
     fn refresh(&mut self) {
         self.connections = self.system_db.list_connections().unwrap_or_default();
         if self.db.is_some() {
             self.refresh_collections();
+            self.refresh_vector_collections();
+        }
+        if self.screen == AppScreen::CollectionStats {
+            self.load_collection_history();
+            self.load_stats();
         }
         self.status_message = "✓ Refreshed".into();
     }
@@ -770,11 +1612,27 @@ impl TuiApp {
             let collections = db.list_collections();
             let total_docs: usize = collections.iter().map(|(_, c)| c).sum();
             let _ = self.system_db.update_connection_stats(path, collections.len(), total_docs);
+            let _ = self.system_db.record_collection_counts(path, &collections);
         }
     }
 
     pub fn get_help_text(&self) -> String {
-        r#"KeraDB TUI Commands
+        // One line per action, showing the keys `tui.toml` binds it to
+        let keys = |rows: &[(&[Action], &str)]| -> String {
+            rows.iter()
+                .map(|(actions, text)| {
+                    if actions.is_empty() {
+                        return format!("  {}", text);
+                    }
+                    let keys: Vec<String> = actions.iter().map(|a| self.keymap.keys(*a)).collect();
+                    format!("  {:<11} {}", keys.join(" "), text)
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        format!(
+            r#"KeraDB TUI Commands
 ═══════════════════
 
 CONNECTION
@@ -787,28 +1645,265 @@ DOCUMENTS
   collections         List all collections
   insert <coll> <json>  Insert document
   find <coll> [id]    Find documents
+    [where <field> <op> <value> [and ...]]
+    [sort <field> [asc|desc]] [limit <n>] [skip <n>]
+    [collate binary|nocase|unicode]
+  update <coll> <id> <json>  Update document
   delete <coll> <id>  Delete document
-  count <coll>        Count documents
+  edit <coll> [id]    Edit a document as JSON
+  add <coll>          Insert a document with a form
+  filter <coll>       Build a query from filter rows
+  import <coll> <file>  Import JSON, NDJSON or CSV
+  count <coll> [where ...]   Count documents
+  explain find <coll> ...    Show how a find would run
+  stats               Show storage and cache statistics
   sync                Sync to disk
 
 VECTORS
   vcreate <name> <dims> [dist]  Create vector collection
+  vinsert <coll> <vector> [json]   Insert vector
+  vsearch <coll> <vector|"text"> [k] [where ...]
   vcollections        List vector collections
+  vstats <coll>       Vector collection stats
+  voptimize <coll>    Rebalance vector index
+  vdrop <coll>        Drop vector collection
+
+  Operators: = != > >= < <= in not in contains startswith endswith
+  e.g. find users where age > 30 sort age desc limit 5
+
+  Tab completes commands, collections and IDs as you type;
+  the status bar shows the usage of the command being typed.
 
 NAVIGATION
   Tab         Switch panels
-  j/k ↑/↓     Navigate lists
-  g/G         Top/bottom
-  i/Enter     Insert mode (in query)
-  Esc         Exit mode / Disconnect
-  ?           Toggle help
+{}
   :q          Quit
   Ctrl+Q      Quit immediately
 
 HOME SCREEN
-  n           New database
-  o           Open database
-  Enter       Connect to selected
-  d           Remove from history"#.into()
+{}"#,
+            keys(&[
+                (&[Action::Down, Action::Up], "Navigate lists"),
+                (&[Action::Top, Action::Bottom], "Top/bottom"),
+                (&[Action::Insert], "Insert mode (in query, or Enter)"),
+                (&[Action::Edit], "Edit the last document shown"),
+                (&[Action::Add], "Add a document to the selected collection"),
+                (&[Action::Filter], "Filter the selected collection"),
+                (&[Action::Export], "Export results (:export <file> [format])"),
+                (&[Action::Vectors], "Vector explorer"),
+                (&[Action::Search], "Search vectors (in the explorer)"),
+                (&[Action::Plot], "Plot vectors in 2D by PCA (in the explorer)"),
+                (&[Action::Stats], "Collection stats"),
+                (&[Action::Refresh], "Refresh"),
+                (&[Action::Fold], "Fold JSON at the top of results"),
+                (&[Action::Watch], "Watch mode: refresh as documents change"),
+                (&[Action::Command], "Command line"),
+                (&[], "Esc         Cancel command / Exit mode / Disconnect"),
+                (&[Action::Help], "Toggle help"),
+            ]),
+            keys(&[
+                (&[Action::NewDatabase], "New database"),
+                (&[Action::OpenDatabase], "Open database"),
+                (&[], "Enter       Connect to selected"),
+                (&[Action::RemoveConnection], "Remove from history"),
+            ]),
+        )
+    }
+}
+
+/// Run a database command on a worker thread
+fn run_command(db: &Database, command: Command) -> Result<CommandOutput> {
+    match command {
+        // Answered by the app without starting a task
+        Command::Help => Ok(CommandOutput::default()),
+        Command::Collections => {
+            let collections = db.list_collections();
+            if collections.is_empty() {
+                Ok(CommandOutput::text("No collections found"))
+            } else {
+                let mut output = String::from("Collections:\n");
+                for (name, count) in &collections {
+                    output.push_str(&format!("  • {} ({} documents)\n", name, count));
+                }
+                Ok(CommandOutput::text(output))
+            }
+        }
+        Command::Insert { collection, document } => {
+            let id = db.insert(&collection, document)?;
+            Ok(CommandOutput {
+                text: format!("✓ Inserted with id: {}", id),
+                document: Some((collection, id)),
+                modified: true,
+                ..Default::default()
+            })
+        }
+        Command::Find(mut query) => {
+            if let Some(id) = &query.id {
+                return match query.run(db) {
+                    Ok(docs) if !docs.is_empty() => Ok(CommandOutput {
+                        json: Some(docs[0].to_value()),
+                        document: Some((query.collection.clone(), id.clone())),
+                        documents: Some(vec![docs[0].to_value()]),
+                        ..Default::default()
+                    }),
+                    _ => Ok(CommandOutput::text(format!("Document not found: {}", id))),
+                };
+            }
+            let truncated = query.limit.is_none();
+            let limit = *query.limit.get_or_insert(20);
+            let docs = query.run(db)?;
+            if docs.is_empty() {
+                return Ok(CommandOutput { documents: Some(Vec::new()), ..CommandOutput::text("No documents found") });
+            }
+            let text = if truncated && docs.len() == limit {
+                format!("Showing the first {} documents; add a limit for more", limit)
+            } else {
+                String::new()
+            };
+            let json = Some(docs.iter().map(Document::to_value).collect());
+            let document = match docs.as_slice() {
+                [doc] => Some((query.collection.clone(), doc.id.clone())),
+                _ => None,
+            };
+            let documents = Some(docs.iter().map(Document::to_value).collect());
+            Ok(CommandOutput { text, json, document, documents, ..Default::default() })
+        }
+        Command::Update { collection, id, document } => {
+            let updated = db.update(&collection, &id, document)?;
+            Ok(CommandOutput {
+                text: "✓ Updated:".to_string(),
+                json: Some(updated.to_value()),
+                document: Some((collection, id)),
+                ..Default::default()
+            })
+        }
+        Command::Delete { collection, id } => {
+            db.delete(&collection, &id)?;
+            Ok(CommandOutput {
+                text: format!("✓ Deleted: {}", id),
+                deleted: Some((collection, id)),
+                modified: true,
+                ..Default::default()
+            })
+        }
+        Command::Count { collection, filter } => {
+            let count = filter.count(db, &collection)?;
+            Ok(CommandOutput::text(format!("{} documents in '{}'", count, collection)))
+        }
+        Command::Explain(mut query) => {
+            if query.id.is_none() {
+                query.limit.get_or_insert(20);
+            }
+            Ok(CommandOutput::text(db.explain(&query).to_string().trim_end()))
+        }
+        Command::Stats => Ok(CommandOutput::text(db.stats()?.to_string())),
+        Command::Sync => {
+            db.sync()?;
+            Ok(CommandOutput::text("✓ Database synced to disk"))
+        }
+        Command::VectorCreate { name, dimensions, distance } => {
+            let mut config = db.config().vector_config(dimensions);
+            if let Some(distance) = distance {
+                config.distance = distance;
+            }
+            db.create_vector_collection(&name, config)?;
+            Ok(CommandOutput::text(format!("✓ Created vector collection: {} ({} dims)", name, dimensions)))
+        }
+        Command::VectorInsert { collection, vector, metadata } => {
+            let id = db.insert_vector(&collection, vector, metadata)?;
+            Ok(CommandOutput::text(format!("✓ Inserted vector with id: {}", id)))
+        }
+        Command::VectorSearch { collection, query, k, filter } => {
+            let results = query.search(db, &collection, k, &filter)?;
+            if results.is_empty() {
+                return Ok(CommandOutput::text("No results found"));
+            }
+            let mut output = format!("{} result(s):\n", results.len());
+            for result in results {
+                output.push_str(&format!("  • {} (score {:.6})", result.document.id, result.score));
+                if !result.document.metadata.is_null() {
+                    output.push_str(&format!(" {}", result.document.metadata));
+                }
+                output.push('\n');
+            }
+            Ok(CommandOutput::text(output))
+        }
+        Command::VectorCollections | Command::VectorStats { collection: None } => {
+            let collections = db.list_vector_collections();
+            if collections.is_empty() {
+                Ok(CommandOutput::text("No vector collections found"))
+            } else {
+                let mut output = String::from("Vector Collections:\n");
+                for (name, count) in collections {
+                    output.push_str(&format!("  • {} ({} vectors)\n", name, count));
+                }
+                Ok(CommandOutput::text(output))
+            }
+        }
+        Command::VectorStats { collection: Some(collection) } => {
+            let stats = db.vector_stats(&collection)?;
+            Ok(CommandOutput::text(format!(
+                "{}: {} vectors, {} dims, {} distance, {} KB in memory",
+                stats.name,
+                stats.vector_count,
+                stats.dimensions,
+                stats.distance.name(),
+                stats.memory_bytes / 1024
+            )))
+        }
+        Command::VectorOptimize { collection } => {
+            let report = db.optimize_vector_collection(&collection)?;
+            Ok(CommandOutput::text(format!("✓ Optimized '{}': relinked {} nodes", collection, report.relinked_nodes)))
+        }
+        Command::VectorDrop { collection } => {
+            if db.drop_vector_collection(&collection)? {
+                Ok(CommandOutput::text(format!("✓ Dropped vector collection: {}", collection)))
+            } else {
+                Ok(CommandOutput::text(format!("Vector collection not found: {}", collection)))
+            }
+        }
+    }
+}
+
+/// Import a JSON, NDJSON or CSV file, stopping at the next record if cancelled
+fn import_file(db: &Database, collection: &str, path: &Path, ctx: &TaskContext) -> Result<CommandOutput> {
+    let format = ImportFormat::from_path(path)?;
+    let options = ImportOptions::default().with_format(format);
+    let reader = BufReader::new(std::fs::File::open(path)?);
+    let records = import::records(reader, format, &options)?.take_while(|_| !ctx.is_cancelled());
+    let report = import::insert_records(db, collection, records, &options, &mut |p| {
+        ctx.progress(format!("{} of {} records imported", p.imported, p.records))
+    })?;
+    Ok(CommandOutput {
+        text: format!("✓ Imported {} documents into '{}' from {}", report.imported, collection, path.display()),
+        modified: true,
+        ..Default::default()
+    })
+}
+
+/// Byte offset of the character before `pos` in `text`, so the cursor never
+/// lands inside a multi-byte character
+fn previous_char(text: &str, pos: usize) -> usize {
+    text[..pos].char_indices().next_back().map_or(0, |(i, _)| i)
+}
+
+/// Byte offset just past the character at `pos` in `text`
+fn next_char(text: &str, pos: usize) -> usize {
+    text[pos..].chars().next().map_or(pos, |c| pos + c.len_utf8())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_moves_by_char() {
+        let text = "né 日";
+        assert_eq!(next_char(text, 1), 3);
+        assert_eq!(next_char(text, 4), 7);
+        assert_eq!(next_char(text, 7), 7);
+        assert_eq!(previous_char(text, 7), 4);
+        assert_eq!(previous_char(text, 3), 1);
+        assert_eq!(previous_char(text, 0), 0);
     }
 }
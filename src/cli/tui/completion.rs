@@ -0,0 +1,193 @@
+//! Completion popups and usage hints for the query box
+//!
+//! In Insert mode the query language completes like the shell does, through
+//! [`cli::completion::complete`](crate::cli::completion::complete), with the
+//! TUI's own commands added; in Command mode the `:` commands complete. The
+//! popup opens as soon as the word being typed has candidates, and
+//! [`usage`] gives the line the status bar shows for the command being typed.
+
+use crate::cli::completion as shell;
+use crate::Database;
+
+/// The query language, as (command, usage)
+const QUERY_COMMANDS: &[(&str, &str)] = &[
+    ("collections", "collections"),
+    ("count", "count <collection> [where <cond> [and <cond>]...] [collate <collation>]"),
+    ("delete", "delete <collection> <id>"),
+    ("explain", "explain find <collection> ...  (show how the find would run)"),
+    (
+        "find",
+        "find <collection> [<id>] [where <cond> [and <cond>]...] [sort <field> [asc|desc]] [limit <n>] [skip <n>] [collate <collation>]",
+    ),
+    ("help", "help"),
+    ("insert", "insert <collection> <json>"),
+    ("stats", "stats"),
+    ("sync", "sync"),
+    ("update", "update <collection> <id> <json>"),
+    ("vcollections", "vcollections"),
+    ("vcreate", "vcreate <name> <dimensions> [cosine|euclidean|dot|manhattan]"),
+    ("vdrop", "vdrop <collection>"),
+    ("vinsert", "vinsert <collection> <json-vector> [json-metadata]"),
+    ("voptimize", "voptimize <collection>"),
+    ("vsearch", "vsearch <collection> (<json-vector> | \"text\") [k] [where <cond>...]"),
+    ("vstats", "vstats [<collection>]"),
+];
+
+/// Commands the TUI adds to the query language
+const TUI_COMMANDS: &[(&str, &str)] = &[
+    ("add", "add <collection>  (open a form for a new document)"),
+    ("clear", "clear  (empty the results)"),
+    ("connections", "connections  (list recently opened databases)"),
+    ("disconnect", "disconnect"),
+    ("edit", "edit [<collection> [<id>]]  (open a document in the editor)"),
+    ("filter", "filter <collection>  (build a query with a form)"),
+    ("import", "import <collection> <file.json|ndjson|csv>"),
+    ("new", "new <path>  (create a database with the defaults)"),
+    ("open", "open <path>"),
+];
+
+/// TUI commands whose first argument is a document collection
+const COLLECTION_COMMANDS: &[&str] = &["add", "edit", "filter", "import"];
+
+/// Command-mode (`:`) commands
+const COLON_COMMANDS: &[(&str, &str)] = &[
+    ("close", "close  (disconnect)"),
+    ("disconnect", "disconnect"),
+    ("e", "e <path>  (open a database)"),
+    ("export", "export <file> [json|ndjson|csv]"),
+    ("help", "help"),
+    ("open", "open <path>"),
+    ("q", "q  (quit)"),
+    ("quit", "quit"),
+    ("sync", "sync  (flush to disk)"),
+    ("w", "w  (flush to disk)"),
+    ("wq", "wq  (flush and quit)"),
+    ("write", "write  (flush to disk)"),
+];
+
+const EXPORT_FORMATS: &[&str] = &["csv", "json", "ndjson"];
+
+/// Most candidates the popup shows at once
+pub const POPUP_ROWS: usize = 8;
+
+/// Candidates for the word being typed, with one of them selected
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    /// Byte offset where the word starts
+    pub start: usize,
+    pub candidates: Vec<String>,
+    pub selected: usize,
+}
+
+impl Completion {
+    /// Candidates for the word before `pos`, or `None` if there are none
+    /// worth showing: the line is empty, or the word is already complete
+    pub fn new(db: Option<&Database>, line: &str, pos: usize, command_mode: bool) -> Option<Self> {
+        let pos = char_boundary(line, pos);
+        let before = &line[..pos];
+        if before.trim().is_empty() {
+            return None;
+        }
+        let start = before.rfind([' ', '\t', '\n']).map_or(0, |i| i + 1);
+        let prefix = &before[start..];
+        let words: Vec<&str> = before[..start].split_whitespace().collect();
+
+        let names = |commands: &[(&str, &str)]| commands.iter().map(|(name, _)| name.to_string()).collect();
+        let mut candidates: Vec<String> = match (command_mode, words.as_slice()) {
+            (true, []) => names(COLON_COMMANDS),
+            (true, ["export", _]) => EXPORT_FORMATS.iter().map(|f| f.to_string()).collect(),
+            (true, _) => Vec::new(),
+            (false, []) => [QUERY_COMMANDS, TUI_COMMANDS].into_iter().flat_map(names).collect(),
+            (false, [command]) if COLLECTION_COMMANDS.contains(command) => {
+                db.map(|db| db.list_collections().into_iter().map(|(name, _)| name).collect()).unwrap_or_default()
+            }
+            (false, _) => db.map(|db| shell::complete(db, line, pos).1).unwrap_or_default(),
+        };
+        candidates.retain(|c| c.starts_with(prefix));
+        candidates.sort();
+        candidates.dedup();
+
+        match candidates.as_slice() {
+            [] => None,
+            [only] if only == prefix => None,
+            _ => Some(Self { start, candidates, selected: 0 }),
+        }
+    }
+
+    /// Move the selection by `step` rows, wrapping around
+    pub fn select(&mut self, step: isize) {
+        let n = self.candidates.len() as isize;
+        self.selected = (self.selected as isize + step).rem_euclid(n) as usize;
+    }
+
+    /// The first row the popup shows, keeping the selection in view
+    pub fn scroll(&self) -> usize {
+        self.selected.saturating_sub(POPUP_ROWS - 1)
+    }
+
+    /// Replace the word ending at `pos` with the selected candidate and a
+    /// space; returns the new cursor position
+    pub fn apply(&self, line: &mut String, pos: usize) -> usize {
+        let candidate = &self.candidates[self.selected];
+        let pos = char_boundary(line, pos).max(self.start);
+        let space = if line[pos..].starts_with(' ') { "" } else { " " };
+        line.replace_range(self.start..pos, &format!("{}{}", candidate, space));
+        self.start + candidate.len() + 1
+    }
+}
+
+/// `pos` moved back to the nearest character boundary of `line`
+fn char_boundary(line: &str, pos: usize) -> usize {
+    (0..=pos.min(line.len())).rev().find(|&i| line.is_char_boundary(i)).unwrap_or(0)
+}
+
+/// How to use the command `line` starts with, if it is one
+pub fn usage(line: &str, command_mode: bool) -> Option<&'static str> {
+    let command = line.split_whitespace().next()?.to_lowercase();
+    let commands: &[&[(&str, &str)]] = if command_mode { &[COLON_COMMANDS] } else { &[QUERY_COMMANDS, TUI_COMMANDS] };
+    commands.iter().flat_map(|c| c.iter()).find(|(name, _)| *name == command).map(|(_, usage)| *usage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_completion_and_usage() {
+        let dir = tempdir().unwrap();
+        let db = Database::create(dir.path().join("test.ndb")).unwrap();
+        db.insert("users", json!({"_id": "alice"})).unwrap();
+        db.insert("orders", json!({})).unwrap();
+
+        let complete = |line: &str, command_mode| {
+            Completion::new(Some(&db), line, line.len(), command_mode).map(|c| (c.start, c.candidates))
+        };
+        assert_eq!(complete("", false), None);
+        assert_eq!(complete("co", false), Some((0, vec!["collections".into(), "connections".into(), "count".into()])));
+        assert_eq!(complete("filter ", false), Some((7, vec!["orders".into(), "users".into()])));
+        assert_eq!(complete("find users a", false), Some((11, vec!["alice".into(), "and".into(), "asc".into()])));
+        assert_eq!(complete("find users", false), None);
+        assert_eq!(complete("w", true), Some((0, vec!["w".into(), "wq".into(), "write".into()])));
+        assert_eq!(complete("export out.csv n", true), Some((15, vec!["ndjson".into()])));
+        assert_eq!(Completion::new(None, "import ", 7, false), None);
+
+        let mut completion = Completion::new(Some(&db), "find o where", 6, false).unwrap();
+        completion.select(-1);
+        let mut line = "find o where".to_string();
+        assert_eq!(completion.apply(&mut line, 6), 12);
+        assert_eq!(line, "find orders where");
+
+        // A position inside a multi-byte character falls back to its start
+        let mut line = "find oé".to_string();
+        let completion = Completion::new(Some(&db), &line, 7, false).unwrap();
+        assert_eq!(completion.candidates, vec!["orders".to_string()]);
+        assert_eq!(completion.apply(&mut line, 7), 12);
+        assert_eq!(line, "find orders é");
+
+        assert_eq!(usage("import users", false), Some("import <collection> <file.json|ndjson|csv>"));
+        assert_eq!(usage("EXPORT", true), Some("export <file> [json|ndjson|csv]"));
+        assert_eq!(usage("nope", false), None);
+    }
+}
@@ -0,0 +1,441 @@
+//! The TUI's settings file
+//!
+//! `~/.keradb/tui.toml` picks a color theme, overrides single colors of it
+//! and rebinds the normal-mode keys. Every setting is optional:
+//!
+//! ```toml
+//! theme = "solarized"        # "dark" (default), "light" or "solarized"
+//!
+//! # Colors: a name ("cyan", "dark gray"), an ANSI index or "#rrggbb"
+//! [colors]
+//! accent = "#cb4b16"
+//!
+//! # Keys: one key or a list, e.g. "x", "G", "Ctrl+f", "F2", "Down"
+//! [keys]
+//! down = ["j", "n"]
+//! up = ["k", "e"]
+//! ```
+//!
+//! A key bound here is taken away from any action it is bound to by default,
+//! so `down = "n"` also unbinds "new database".
+
+use anyhow::{anyhow, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::style::Color;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::cli::system_db::keradb_home;
+
+/// File read from the `~/.keradb` directory
+pub const TUI_CONFIG_FILE: &str = "tui.toml";
+
+/// Colors the interface is drawn with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    /// Body text
+    pub text: Color,
+    /// Hints and placeholders
+    pub muted: Color,
+    /// Unfocused panels and the status message
+    pub border: Color,
+    /// Focused panels, selections and the running command
+    pub accent: Color,
+    /// Text on an accent background
+    pub on_accent: Color,
+    /// The header, headings and document IDs
+    pub title: Color,
+    pub success: Color,
+    pub error: Color,
+    /// List bullets and the NORMAL badge
+    pub info: Color,
+    /// The document editor
+    pub edit: Color,
+    /// Text on the mode badges
+    pub badge: Color,
+    /// Status bar background
+    pub status: Color,
+    /// Screen and popup background
+    pub background: Color,
+}
+
+/// Theme names accepted by `theme = "..."`
+const THEMES: &[&str] = &["dark", "light", "solarized"];
+
+impl Theme {
+    /// Light text on the terminal's own background
+    pub fn dark() -> Self {
+        Self {
+            text: Color::White,
+            muted: Color::DarkGray,
+            border: Color::Gray,
+            accent: Color::Yellow,
+            on_accent: Color::Black,
+            title: Color::Cyan,
+            success: Color::Green,
+            error: Color::Red,
+            info: Color::Blue,
+            edit: Color::Magenta,
+            badge: Color::White,
+            status: Color::DarkGray,
+            background: Color::Reset,
+        }
+    }
+
+    /// Dark text on white
+    pub fn light() -> Self {
+        Self {
+            text: Color::Black,
+            muted: Color::Gray,
+            border: Color::DarkGray,
+            accent: Color::Blue,
+            on_accent: Color::White,
+            title: Color::Rgb(0x00, 0x5f, 0x87),
+            success: Color::Rgb(0x00, 0x87, 0x00),
+            error: Color::Rgb(0xaf, 0x00, 0x00),
+            info: Color::Rgb(0x5f, 0x00, 0xaf),
+            edit: Color::Magenta,
+            badge: Color::White,
+            status: Color::Rgb(0xd0, 0xd0, 0xd0),
+            background: Color::White,
+        }
+    }
+
+    /// Ethan Schoonover's Solarized, dark variant
+    pub fn solarized() -> Self {
+        Self {
+            text: Color::Rgb(0x93, 0xa1, 0xa1),
+            muted: Color::Rgb(0x58, 0x6e, 0x75),
+            border: Color::Rgb(0x65, 0x7b, 0x83),
+            accent: Color::Rgb(0xb5, 0x89, 0x00),
+            on_accent: Color::Rgb(0x00, 0x2b, 0x36),
+            title: Color::Rgb(0x2a, 0xa1, 0x98),
+            success: Color::Rgb(0x85, 0x99, 0x00),
+            error: Color::Rgb(0xdc, 0x32, 0x2f),
+            info: Color::Rgb(0x26, 0x8b, 0xd2),
+            edit: Color::Rgb(0xd3, 0x36, 0x82),
+            badge: Color::Rgb(0xfd, 0xf6, 0xe3),
+            status: Color::Rgb(0x07, 0x36, 0x42),
+            background: Color::Rgb(0x00, 0x2b, 0x36),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "solarized" => Some(Self::solarized()),
+            _ => None,
+        }
+    }
+
+    /// The color named `name` in `[colors]`
+    fn color_mut(&mut self, name: &str) -> Option<&mut Color> {
+        Some(match name {
+            "text" => &mut self.text,
+            "muted" => &mut self.muted,
+            "border" => &mut self.border,
+            "accent" => &mut self.accent,
+            "on_accent" => &mut self.on_accent,
+            "title" => &mut self.title,
+            "success" => &mut self.success,
+            "error" => &mut self.error,
+            "info" => &mut self.info,
+            "edit" => &mut self.edit,
+            "badge" => &mut self.badge,
+            "status" => &mut self.status,
+            "background" => &mut self.background,
+            _ => return None,
+        })
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+/// Something a normal-mode key does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Help,
+    NewDatabase,
+    OpenDatabase,
+    RemoveConnection,
+    /// Start typing in the query box
+    Insert,
+    /// Start typing a vector search
+    Search,
+    Vectors,
+    Stats,
+    Edit,
+    Add,
+    Filter,
+    Export,
+    Command,
+    Down,
+    Up,
+    Top,
+    Bottom,
+    Refresh,
+    /// Fold or unfold the JSON at the top of the results
+    Fold,
+    /// Turn watch mode on or off
+    Watch,
+    /// Plot the selected vector collection in 2D
+    Plot,
+}
+
+/// Every action with its name in `[keys]` and its default keys
+const ACTIONS: &[(Action, &str, &[&str])] = &[
+    (Action::Help, "help", &["?", "F1"]),
+    (Action::NewDatabase, "new", &["n"]),
+    (Action::OpenDatabase, "open", &["o"]),
+    (Action::RemoveConnection, "remove", &["d"]),
+    (Action::Insert, "insert", &["i"]),
+    (Action::Search, "search", &["/"]),
+    (Action::Vectors, "vectors", &["v"]),
+    (Action::Stats, "stats", &["s"]),
+    (Action::Edit, "edit", &["e"]),
+    (Action::Add, "add", &["a"]),
+    (Action::Filter, "filter", &["f"]),
+    (Action::Export, "export", &["x"]),
+    (Action::Command, "command", &[":"]),
+    (Action::Down, "down", &["j", "Down"]),
+    (Action::Up, "up", &["k", "Up"]),
+    (Action::Top, "top", &["g"]),
+    (Action::Bottom, "bottom", &["G"]),
+    (Action::Refresh, "refresh", &["r"]),
+    (Action::Fold, "fold", &["Enter", "Space"]),
+    (Action::Watch, "watch", &["w"]),
+    (Action::Plot, "plot", &["p"]),
+];
+
+/// A key and the modifiers held with it
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+    /// As written, for the help text
+    label: String,
+}
+
+impl Key {
+    fn parse(text: &str) -> Option<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        // A lone "+" is a key, not a separator
+        while let Some((modifier, key)) = rest.split_once('+').filter(|(_, key)| !key.is_empty()) {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                _ => return None,
+            };
+            rest = key;
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_lowercase().as_str() {
+                "enter" => KeyCode::Enter,
+                "tab" => KeyCode::Tab,
+                "esc" => KeyCode::Esc,
+                "space" => KeyCode::Char(' '),
+                "backspace" => KeyCode::Backspace,
+                "delete" => KeyCode::Delete,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                name => KeyCode::F(name.strip_prefix('f')?.parse().ok().filter(|n| (1..=12).contains(n))?),
+            },
+        };
+        Some(Self { code, modifiers, label: text.to_string() })
+    }
+
+    fn matches(&self, event: &KeyEvent) -> bool {
+        // Shift is already part of the character ('G', '?')
+        let held = event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+        self.code == event.code && self.modifiers == held
+    }
+}
+
+/// Which keys run which actions in normal mode
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(Action, Key)>,
+}
+
+impl Keymap {
+    /// The action bound to `event`, if any
+    pub fn action(&self, event: &KeyEvent) -> Option<Action> {
+        self.bindings.iter().find(|(_, key)| key.matches(event)).map(|(action, _)| *action)
+    }
+
+    /// The keys bound to `action`, as "j/Down"
+    pub fn keys(&self, action: Action) -> String {
+        let keys: Vec<&str> =
+            self.bindings.iter().filter(|(a, _)| *a == action).map(|(_, key)| key.label.as_str()).collect();
+        if keys.is_empty() { "(unbound)".to_string() } else { keys.join("/") }
+    }
+
+    /// Bind `action` to `keys` instead of its defaults
+    fn rebind(&mut self, action: Action, keys: Vec<Key>) {
+        self.bindings.retain(|(a, key)| *a != action && !keys.iter().any(|k| k.code == key.code && k.modifiers == key.modifiers));
+        self.bindings.extend(keys.into_iter().map(|key| (action, key)));
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = ACTIONS
+            .iter()
+            .flat_map(|(action, _, keys)| keys.iter().map(move |key| (*action, Key::parse(key).expect("default key"))))
+            .collect();
+        Self { bindings }
+    }
+}
+
+/// Settings from `tui.toml`
+#[derive(Debug, Clone, Default)]
+pub struct TuiConfig {
+    pub theme: Theme,
+    pub keymap: Keymap,
+}
+
+impl TuiConfig {
+    /// `~/.keradb/tui.toml`
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(keradb_home()?.join(TUI_CONFIG_FILE))
+    }
+
+    /// Load `~/.keradb/tui.toml`, or the defaults if there is none
+    pub fn load() -> Result<Self> {
+        let path = Self::default_path()?;
+        if path.exists() { Self::from_file(&path) } else { Ok(Self::default()) }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|e| anyhow!("Cannot read {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| anyhow!("{}: {}", path.display(), e))
+    }
+
+    /// Settings from the contents of a `tui.toml`
+    pub fn parse(text: &str) -> std::result::Result<Self, String> {
+        let document = toml_edit::DocumentMut::from_str(text).map_err(|e| e.to_string())?;
+        let mut config = Self::default();
+
+        for (name, item) in document.iter() {
+            match name {
+                "theme" => {
+                    let theme = item.as_str().ok_or("'theme' must be a string")?;
+                    config.theme = Theme::from_name(theme)
+                        .ok_or_else(|| format!("Unknown theme '{}'; use {}", theme, THEMES.join(", ")))?;
+                }
+                "colors" | "keys" => {}
+                _ => return Err(format!("Unknown setting '{}'", name)),
+            }
+        }
+
+        // Colors apply on top of whichever theme was picked
+        if let Some(item) = document.get("colors") {
+            let table = item.as_table().ok_or("'colors' must be a table")?;
+            for (name, value) in table.iter() {
+                let text = value.as_str().ok_or_else(|| format!("'colors.{}' must be a string", name))?;
+                let color = Color::from_str(text).map_err(|_| format!("Unknown color '{}' for '{}'", text, name))?;
+                *config.theme.color_mut(name).ok_or_else(|| format!("Unknown color '{}'", name))? = color;
+            }
+        }
+
+        if let Some(item) = document.get("keys") {
+            let table = item.as_table().ok_or("'keys' must be a table")?;
+            let mut bound: Vec<(&str, Key)> = Vec::new();
+            for (name, value) in table.iter() {
+                let action = ACTIONS
+                    .iter()
+                    .find(|(_, n, _)| *n == name)
+                    .map(|(action, _, _)| *action)
+                    .ok_or_else(|| format!("Unknown action '{}'", name))?;
+                let texts: Vec<&str> = match (value.as_str(), value.as_array()) {
+                    (Some(key), _) => vec![key],
+                    (_, Some(keys)) => keys
+                        .iter()
+                        .map(|k| k.as_str().ok_or_else(|| format!("'keys.{}' must be a key or a list of keys", name)))
+                        .collect::<std::result::Result<_, _>>()?,
+                    _ => return Err(format!("'keys.{}' must be a key or a list of keys", name)),
+                };
+                let mut keys = Vec::new();
+                for text in texts {
+                    let key = Key::parse(text).ok_or_else(|| format!("Unknown key '{}' for '{}'", text, name))?;
+                    if let Some((other, _)) = bound.iter().find(|(_, k)| k.code == key.code && k.modifiers == key.modifiers) {
+                        return Err(format!("'{}' is bound to both '{}' and '{}'", text, other, name));
+                    }
+                    bound.push((name, key.clone()));
+                    keys.push(key);
+                }
+                config.keymap.rebind(action, keys);
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn test_parse_tui_config() {
+        let config = TuiConfig::parse("").unwrap();
+        assert_eq!(config.theme, Theme::dark());
+        assert_eq!(config.keymap.action(&key(KeyCode::Char('G'), KeyModifiers::SHIFT)), Some(Action::Bottom));
+        assert_eq!(config.keymap.keys(Action::Down), "j/Down");
+
+        let config = TuiConfig::parse(
+            r##"
+theme = "solarized"
+
+[colors]
+accent = "#cb4b16"
+muted = "dark gray"
+
+[keys]
+down = ["n", "Ctrl+d"]
+export = "F2"
+"##,
+        )
+        .unwrap();
+        assert_eq!(config.theme.accent, Color::Rgb(0xcb, 0x4b, 0x16));
+        assert_eq!(config.theme.muted, Color::DarkGray);
+        assert_eq!(config.theme.title, Theme::solarized().title);
+
+        let keymap = &config.keymap;
+        assert_eq!(keymap.action(&key(KeyCode::Char('n'), KeyModifiers::NONE)), Some(Action::Down));
+        assert_eq!(keymap.action(&key(KeyCode::Char('d'), KeyModifiers::CONTROL)), Some(Action::Down));
+        assert_eq!(keymap.action(&key(KeyCode::Char('d'), KeyModifiers::NONE)), Some(Action::RemoveConnection));
+        assert_eq!(keymap.action(&key(KeyCode::Char('j'), KeyModifiers::NONE)), None);
+        assert_eq!(keymap.action(&key(KeyCode::F(2), KeyModifiers::NONE)), Some(Action::Export));
+        assert_eq!(keymap.keys(Action::NewDatabase), "(unbound)");
+
+        for (text, error) in [
+            ("theme = \"neon\"", "Unknown theme 'neon'"),
+            ("[colors]\naccent = \"octarine\"", "Unknown color 'octarine'"),
+            ("[keys]\nfly = \"f\"", "Unknown action 'fly'"),
+            ("[keys]\nup = \"Hyper+k\"", "Unknown key 'Hyper+k'"),
+            ("[keys]\nup = \"u\"\ndown = \"u\"", "'u' is bound to both 'up' and 'down'"),
+        ] {
+            let e = TuiConfig::parse(text).unwrap_err();
+            assert!(e.starts_with(error), "{}", e);
+        }
+    }
+}
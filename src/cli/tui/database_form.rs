@@ -0,0 +1,196 @@
+//! Creating a database with options from the TUI
+//!
+//! [`DatabaseForm`] is the dialog `n` opens in the connection manager: the
+//! path, page size, cache size and durability, and vector collections to
+//! create up front. Typing `new <path>` still creates one with the defaults.
+
+use crate::types::{Config, Durability};
+use crate::vector::{Distance, VectorConfig};
+use crossterm::event::{KeyCode, KeyEvent};
+
+/// Page sizes offered, in the order Left/Right cycles through them
+pub const PAGE_SIZES: &[usize] = &[4096, 8192, 16384, 32768, 65536];
+
+/// Row labels, top to bottom
+pub const LABELS: &[&str] = &["Path", "Page size", "Cache size (pages)", "Durability", "Vector collections"];
+
+const PAGE_SIZE_ROW: usize = 1;
+const DURABILITY_ROW: usize = 3;
+
+/// What the form creates
+#[derive(Debug, Clone)]
+pub struct NewDatabase {
+    pub path: String,
+    pub config: Config,
+    /// Vector collections to create, by name
+    pub vectors: Vec<(String, VectorConfig)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DatabaseForm {
+    pub path: String,
+    /// Index into [`PAGE_SIZES`]
+    pub page_size: usize,
+    pub cache_size: String,
+    pub durability: Durability,
+    /// `name:dimensions[:distance]`, separated by commas
+    pub vectors: String,
+    pub row: usize,
+}
+
+impl DatabaseForm {
+    /// A form starting from the settings the TUI opens databases with
+    pub fn new(config: &Config) -> Self {
+        Self {
+            path: String::new(),
+            page_size: PAGE_SIZES.iter().position(|&size| size == config.page_size).unwrap_or(0),
+            cache_size: config.cache_size.to_string(),
+            durability: config.durability,
+            vectors: String::new(),
+            row: 0,
+        }
+    }
+
+    /// Whether a row is picked with Left/Right rather than typed
+    pub fn is_choice(row: usize) -> bool {
+        row == PAGE_SIZE_ROW || row == DURABILITY_ROW
+    }
+
+    /// Each row's value as shown
+    pub fn values(&self) -> Vec<String> {
+        let durability = match self.durability {
+            Durability::Normal => "normal (flush on sync)",
+            Durability::Full => "full (flush every write)",
+        };
+        vec![
+            self.path.clone(),
+            PAGE_SIZES[self.page_size].to_string(),
+            self.cache_size.clone(),
+            durability.to_string(),
+            self.vectors.clone(),
+        ]
+    }
+
+    fn text(&mut self) -> Option<&mut String> {
+        match self.row {
+            0 => Some(&mut self.path),
+            2 => Some(&mut self.cache_size),
+            4 => Some(&mut self.vectors),
+            _ => None,
+        }
+    }
+
+    /// Step a choice row forwards or backwards
+    fn cycle(&mut self, forward: bool) {
+        match self.row {
+            PAGE_SIZE_ROW => {
+                let n = PAGE_SIZES.len();
+                self.page_size = if forward { (self.page_size + 1) % n } else { (self.page_size + n - 1) % n };
+            }
+            DURABILITY_ROW => {
+                self.durability = match self.durability {
+                    Durability::Normal => Durability::Full,
+                    Durability::Full => Durability::Normal,
+                };
+            }
+            _ => {}
+        }
+    }
+
+    /// Apply an editing or movement key; returns false for keys it ignores
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Tab | KeyCode::Enter | KeyCode::Down => self.row = (self.row + 1) % LABELS.len(),
+            KeyCode::BackTab | KeyCode::Up => self.row = (self.row + LABELS.len() - 1) % LABELS.len(),
+            KeyCode::Left | KeyCode::Right | KeyCode::Char(' ') if Self::is_choice(self.row) => {
+                self.cycle(key.code != KeyCode::Left)
+            }
+            KeyCode::Char(c) => match self.text() {
+                Some(text) => text.push(c),
+                None => return false,
+            },
+            KeyCode::Backspace => match self.text() {
+                Some(text) => {
+                    text.pop();
+                }
+                None => return false,
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    /// The database to create, on top of `base`, or why the form is invalid
+    pub fn database(&self, base: &Config) -> Result<NewDatabase, String> {
+        let path = self.path.trim();
+        if path.is_empty() {
+            return Err("Enter a path for the new database".to_string());
+        }
+        let cache_size = match self.cache_size.trim().parse() {
+            Ok(n) if n > 0 => n,
+            _ => return Err(format!("Cache size must be a positive number of pages, got '{}'", self.cache_size)),
+        };
+        let config = Config { page_size: PAGE_SIZES[self.page_size], ..base.clone() }
+            .with_cache_size(cache_size)
+            .with_durability(self.durability);
+
+        let mut vectors: Vec<(String, VectorConfig)> = Vec::new();
+        for spec in self.vectors.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let parts: Vec<&str> = spec.split(':').map(str::trim).collect();
+            let (name, dimensions, distance) = match parts.as_slice() {
+                [name, dims] => (*name, *dims, None),
+                [name, dims, distance] => (*name, *dims, Some(*distance)),
+                _ => return Err(format!("Vector collections are name:dimensions[:distance], got '{}'", spec)),
+            };
+            let dimensions = match dimensions.parse() {
+                Ok(n) if n > 0 => n,
+                _ => return Err(format!("Dimensions for '{}' must be a positive number", name)),
+            };
+            let mut vector = config.vector_config(dimensions);
+            if let Some(distance) = distance {
+                vector.distance =
+                    Distance::from_name(distance).ok_or_else(|| format!("Unknown distance metric '{}'", distance))?;
+            }
+            if name.is_empty() || vectors.iter().any(|(n, _)| n == name) {
+                return Err(format!("Vector collection names must be unique and non-empty, got '{}'", spec));
+            }
+            vectors.push((name.to_string(), vector));
+        }
+
+        Ok(NewDatabase { path: path.to_string(), config, vectors })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+
+    fn press(form: &mut DatabaseForm, codes: impl IntoIterator<Item = KeyCode>) {
+        for code in codes {
+            form.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+        }
+    }
+
+    #[test]
+    fn test_database_form() {
+        let mut form = DatabaseForm::new(&Config::default());
+        assert_eq!(form.database(&Config::default()).unwrap_err(), "Enter a path for the new database");
+
+        press(&mut form, "app.ndb".chars().map(KeyCode::Char));
+        press(&mut form, [KeyCode::Tab, KeyCode::Right, KeyCode::Right, KeyCode::Tab, KeyCode::Backspace]);
+        press(&mut form, [KeyCode::Char('5'), KeyCode::Tab, KeyCode::Char(' '), KeyCode::Tab]);
+        press(&mut form, "docs:384, images:512:euclidean".chars().map(KeyCode::Char));
+
+        let new = form.database(&Config::default()).unwrap();
+        assert_eq!(new.path, "app.ndb");
+        assert_eq!((new.config.page_size, new.config.cache_size), (16384, 105));
+        assert_eq!(new.config.durability, Durability::Full);
+        let vectors: Vec<(&str, usize, Distance)> =
+            new.vectors.iter().map(|(name, v)| (name.as_str(), v.dimensions, v.distance)).collect();
+        assert_eq!(vectors, [("docs", 384, Distance::Cosine), ("images", 512, Distance::Euclidean)]);
+
+        form.vectors = "docs".into();
+        assert!(form.database(&Config::default()).unwrap_err().starts_with("Vector collections are"));
+    }
+}
@@ -0,0 +1,334 @@
+//! Editing documents inside the TUI
+//!
+//! [`DocumentEditor`] is a small multi-line JSON editor for changing an
+//! existing document or writing a new one; [`InsertForm`] builds a new
+//! document one field at a time. Both check what they produce before it
+//! is saved, and leave the actual writes to the app.
+
+use super::database_form::DatabaseForm;
+use super::query_builder::QueryBuilder;
+use crate::import::set_path;
+use crate::types::Document;
+use crossterm::event::{KeyCode, KeyEvent};
+use serde_json::{Map, Value};
+
+/// Spaces inserted by Tab
+const INDENT: &str = "  ";
+
+/// A plain multi-line text buffer with a cursor
+#[derive(Debug, Clone)]
+pub struct TextArea {
+    lines: Vec<String>,
+    row: usize,
+    /// Cursor column, in characters
+    col: usize,
+}
+
+impl TextArea {
+    pub fn new(text: &str) -> Self {
+        let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+        if lines.is_empty() {
+            lines.push(String::new());
+        }
+        Self { lines, row: 0, col: 0 }
+    }
+
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// Cursor as (row, column)
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.row, self.col)
+    }
+
+    /// Byte offset of the cursor in its line
+    fn offset(&self) -> usize {
+        let line = &self.lines[self.row];
+        line.char_indices().nth(self.col).map_or(line.len(), |(i, _)| i)
+    }
+
+    fn line_len(&self, row: usize) -> usize {
+        self.lines[row].chars().count()
+    }
+
+    pub fn insert_str(&mut self, text: &str) {
+        for c in text.chars() {
+            self.insert_char(c);
+        }
+    }
+
+    fn insert_char(&mut self, c: char) {
+        let offset = self.offset();
+        if c == '\n' {
+            // Keep the current line's indentation
+            let rest = self.lines[self.row].split_off(offset);
+            let indent: String = self.lines[self.row].chars().take_while(|c| *c == ' ').collect();
+            self.col = indent.chars().count();
+            self.row += 1;
+            self.lines.insert(self.row, indent + &rest);
+        } else {
+            self.lines[self.row].insert(offset, c);
+            self.col += 1;
+        }
+    }
+
+    /// Apply an editing or movement key; returns false for keys it ignores
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Char(c) => self.insert_char(c),
+            KeyCode::Enter => self.insert_char('\n'),
+            KeyCode::Tab => self.insert_str(INDENT),
+            KeyCode::Backspace if self.col > 0 => {
+                self.col -= 1;
+                let offset = self.offset();
+                self.lines[self.row].remove(offset);
+            }
+            KeyCode::Backspace if self.row > 0 => {
+                let line = self.lines.remove(self.row);
+                self.row -= 1;
+                self.col = self.line_len(self.row);
+                self.lines[self.row].push_str(&line);
+            }
+            KeyCode::Delete if self.col < self.line_len(self.row) => {
+                let offset = self.offset();
+                self.lines[self.row].remove(offset);
+            }
+            KeyCode::Delete if self.row + 1 < self.lines.len() => {
+                let line = self.lines.remove(self.row + 1);
+                self.lines[self.row].push_str(&line);
+            }
+            KeyCode::Left if self.col > 0 => self.col -= 1,
+            KeyCode::Left if self.row > 0 => {
+                self.row -= 1;
+                self.col = self.line_len(self.row);
+            }
+            KeyCode::Right if self.col < self.line_len(self.row) => self.col += 1,
+            KeyCode::Right if self.row + 1 < self.lines.len() => {
+                self.row += 1;
+                self.col = 0;
+            }
+            KeyCode::Up if self.row > 0 => {
+                self.row -= 1;
+                self.col = self.col.min(self.line_len(self.row));
+            }
+            KeyCode::Down if self.row + 1 < self.lines.len() => {
+                self.row += 1;
+                self.col = self.col.min(self.line_len(self.row));
+            }
+            KeyCode::Home => self.col = 0,
+            KeyCode::End => self.col = self.line_len(self.row),
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// What saving an editor does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditTarget {
+    /// Replace the document with this ID
+    Update { collection: String, id: String },
+    /// Insert a new document
+    Insert { collection: String },
+}
+
+impl EditTarget {
+    pub fn collection(&self) -> &str {
+        match self {
+            EditTarget::Update { collection, .. } | EditTarget::Insert { collection } => collection,
+        }
+    }
+}
+
+/// The editor open in the TUI
+#[derive(Debug, Clone)]
+pub enum Editor {
+    Json(DocumentEditor),
+    Form(InsertForm),
+    Query(QueryBuilder),
+    /// Options for a new database
+    Database(DatabaseForm),
+}
+
+impl Editor {
+    pub fn title(&self) -> String {
+        match self {
+            Editor::Json(DocumentEditor { target: EditTarget::Update { collection, id }, .. }) => {
+                format!("Edit {}/{}", collection, id)
+            }
+            Editor::Json(DocumentEditor { target: EditTarget::Insert { collection }, .. }) => {
+                format!("New document in {}", collection)
+            }
+            Editor::Form(form) => format!("New document in {}", form.collection),
+            Editor::Query(builder) => format!("Filter {}", builder.collection),
+            Editor::Database(_) => "New database".to_string(),
+        }
+    }
+}
+
+/// A JSON editor for one document
+#[derive(Debug, Clone)]
+pub struct DocumentEditor {
+    pub target: EditTarget,
+    pub text: TextArea,
+}
+
+impl DocumentEditor {
+    /// Edit an existing document
+    pub fn edit(collection: &str, doc: &Document) -> Self {
+        let mut value = doc.to_value();
+        if let Value::Object(map) = &mut value {
+            map.remove("_collection");
+        }
+        let text = serde_json::to_string_pretty(&value).unwrap_or_default();
+        Self {
+            target: EditTarget::Update { collection: collection.to_string(), id: doc.id.clone() },
+            text: TextArea::new(&text),
+        }
+    }
+
+    /// Write a new document, starting from `value`
+    pub fn insert(collection: &str, value: &Value) -> Self {
+        let text = serde_json::to_string_pretty(value).unwrap_or_default();
+        Self { target: EditTarget::Insert { collection: collection.to_string() }, text: TextArea::new(&text) }
+    }
+
+    /// The document to save, or why it cannot be saved
+    ///
+    /// For updates the `_id` may be left out but not changed, and is removed
+    /// from the returned body.
+    pub fn document(&self) -> Result<Value, String> {
+        let mut value: Value = serde_json::from_str(&self.text.text()).map_err(|e| format!("Invalid JSON: {}", e))?;
+        let Value::Object(map) = &mut value else {
+            return Err("A document must be a JSON object".to_string());
+        };
+        map.remove("_collection");
+        if let EditTarget::Update { id, .. } = &self.target {
+            match map.remove("_id") {
+                Some(Value::String(new_id)) if new_id != *id => {
+                    return Err(format!("The _id cannot be changed (was {})", id));
+                }
+                Some(Value::String(_)) | None => {}
+                Some(other) => return Err(format!("The _id cannot be changed to {}", other)),
+            }
+        }
+        Ok(value)
+    }
+}
+
+/// A form for a new document, one field per row
+#[derive(Debug, Clone)]
+pub struct InsertForm {
+    pub collection: String,
+    /// (field, value) rows; fields may be dotted to nest
+    pub fields: Vec<(String, String)>,
+    pub row: usize,
+    /// Whether the cursor is in the value column
+    pub in_value: bool,
+}
+
+impl InsertForm {
+    pub fn new(collection: &str) -> Self {
+        Self { collection: collection.to_string(), fields: vec![(String::new(), String::new())], row: 0, in_value: false }
+    }
+
+    fn cell(&mut self) -> &mut String {
+        let (field, value) = &mut self.fields[self.row];
+        if self.in_value { value } else { field }
+    }
+
+    /// Apply an editing or movement key; returns false for keys it ignores
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Char(c) => self.cell().push(c),
+            KeyCode::Backspace => {
+                self.cell().pop();
+            }
+            KeyCode::Tab if !self.in_value => self.in_value = true,
+            // Tab or Enter past the last value starts a new row
+            KeyCode::Tab | KeyCode::Enter => {
+                if self.row + 1 == self.fields.len() {
+                    self.fields.push((String::new(), String::new()));
+                }
+                self.row += 1;
+                self.in_value = false;
+            }
+            KeyCode::BackTab if self.in_value => self.in_value = false,
+            KeyCode::BackTab if self.row > 0 => {
+                self.row -= 1;
+                self.in_value = true;
+            }
+            KeyCode::Up if self.row > 0 => self.row -= 1,
+            KeyCode::Down if self.row + 1 < self.fields.len() => self.row += 1,
+            _ => return false,
+        }
+        true
+    }
+
+    /// The document the form describes
+    ///
+    /// Values are read as JSON where they parse (`42`, `true`, `["a"]`) and
+    /// as strings otherwise; rows without a field name are ignored.
+    pub fn document(&self) -> Result<Value, String> {
+        let mut doc = Value::Object(Map::new());
+        let mut seen = Vec::new();
+        for (field, value) in &self.fields {
+            let field = field.trim();
+            if field.is_empty() {
+                continue;
+            }
+            if seen.contains(&field) {
+                return Err(format!("Field '{}' is given twice", field));
+            }
+            seen.push(field);
+            let value = serde_json::from_str(value.trim()).unwrap_or_else(|_| Value::String(value.clone()));
+            set_path(&mut doc, field, value);
+        }
+        Ok(doc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+    use serde_json::json;
+
+    fn press(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_editor_and_form() {
+        let doc = Document::with_id("a1".into(), json!({"name": "Alice", "_collection": "users"}));
+        let mut editor = DocumentEditor::edit("users", &doc);
+        assert!(!editor.text.text().contains("_collection"));
+        assert_eq!(editor.document().unwrap(), json!({"name": "Alice"}));
+
+        // Type a new field on a line after the opening brace
+        editor.text.handle_key(press(KeyCode::End));
+        editor.text.handle_key(press(KeyCode::Enter));
+        editor.text.insert_str("  \"age\": 30,");
+        assert_eq!(editor.document().unwrap(), json!({"name": "Alice", "age": 30}));
+        editor.text.insert_str("}");
+        assert!(editor.document().unwrap_err().starts_with("Invalid JSON"));
+
+        let changed = DocumentEditor { text: TextArea::new(r#"{"_id": "b2"}"#), ..editor };
+        assert!(changed.document().is_err());
+
+        let mut form = InsertForm::new("users");
+        for code in "address.city".chars().map(KeyCode::Char).chain([KeyCode::Tab]) {
+            form.handle_key(press(code));
+        }
+        for code in "Oslo".chars().map(KeyCode::Char).chain([KeyCode::Enter, KeyCode::Char('n'), KeyCode::Tab]) {
+            form.handle_key(press(code));
+        }
+        form.handle_key(press(KeyCode::Char('3')));
+        assert_eq!(form.document().unwrap(), json!({"address": {"city": "Oslo"}, "n": 3}));
+    }
+}
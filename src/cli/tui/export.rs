@@ -0,0 +1,102 @@
+//! Writing TUI results to a file
+//!
+//! The documents behind the results panel are written as a JSON array,
+//! NDJSON or CSV, the same formats `keradb import` reads. CSV gets one column
+//! per field, `_id` first and the rest in alphabetical order; nested objects
+//! become dotted columns (`address.city`) and arrays are written as JSON.
+
+use crate::import::ImportFormat;
+use anyhow::Result;
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Write `documents` to `path` in `format`
+pub fn write_documents(path: &Path, documents: &[Value], format: ImportFormat) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    match format {
+        ImportFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, documents)?;
+            writeln!(writer)?;
+        }
+        ImportFormat::Ndjson => {
+            for doc in documents {
+                serde_json::to_writer(&mut writer, doc)?;
+                writeln!(writer)?;
+            }
+        }
+        ImportFormat::Csv => {
+            let rows: Vec<Vec<(String, String)>> = documents.iter().map(flatten).collect();
+            let fields: BTreeSet<&str> = rows.iter().flatten().map(|(field, _)| field.as_str()).collect();
+            let mut columns: Vec<&str> = fields.into_iter().collect();
+            if let Some(i) = columns.iter().position(|c| *c == "_id") {
+                let id = columns.remove(i);
+                columns.insert(0, id);
+            }
+
+            let mut csv = csv::Writer::from_writer(&mut writer);
+            csv.write_record(&columns)?;
+            for row in &rows {
+                csv.write_record(columns.iter().map(|column| {
+                    row.iter().find(|(field, _)| field == column).map_or("", |(_, cell)| cell.as_str())
+                }))?;
+            }
+            csv.flush()?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// A document as (dotted field, cell) pairs
+fn flatten(doc: &Value) -> Vec<(String, String)> {
+    fn walk(prefix: &str, map: &Map<String, Value>, cells: &mut Vec<(String, String)>) {
+        for (key, value) in map {
+            let field = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+            match value {
+                Value::Object(inner) => walk(&field, inner, cells),
+                Value::Null => cells.push((field, String::new())),
+                Value::String(s) => cells.push((field, s.clone())),
+                other => cells.push((field, other.to_string())),
+            }
+        }
+    }
+
+    let mut cells = Vec::new();
+    match doc {
+        Value::Object(map) => walk("", map, &mut cells),
+        other => cells.push(("value".to_string(), other.to_string())),
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_write_documents() {
+        let dir = tempdir().unwrap();
+        let docs = vec![
+            json!({"_id": "a", "name": "Alice", "address": {"city": "Oslo"}, "tags": ["x", "y"]}),
+            json!({"_id": "b", "name": "Bob, Jr.", "age": 41}),
+        ];
+
+        let path = dir.path().join("out.csv");
+        write_documents(&path, &docs, ImportFormat::Csv).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "_id,address.city,age,name,tags\na,Oslo,,Alice,\"[\"\"x\"\",\"\"y\"\"]\"\nb,,41,\"Bob, Jr.\",\n"
+        );
+
+        let path = dir.path().join("out.ndjson");
+        write_documents(&path, &docs, ImportFormat::Ndjson).unwrap();
+        let lines: Vec<Value> =
+            std::fs::read_to_string(&path).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines, docs);
+    }
+}
@@ -1,5 +1,13 @@
 pub mod app;
+pub mod completion;
+pub mod config;
+pub mod database_form;
+pub mod editor;
+pub mod export;
+pub mod query_builder;
+pub mod results;
 pub mod ui;
 pub mod events;
+pub mod worker;
 
 pub use app::TuiApp;
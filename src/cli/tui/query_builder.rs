@@ -0,0 +1,215 @@
+//! Building `find` queries from filter rows in the TUI
+//!
+//! Each row is a field, an operator and a value. The rows are written out as
+//! an ordinary query (`find users where age > 30 and ...`) and parsed with
+//! the shared [`parser`](crate::cli::parser), so the builder accepts exactly
+//! what the query box does and the command it runs can be read and reused.
+
+use crate::cli::parser::{self, Command, FindQuery};
+use crossterm::event::{KeyCode, KeyEvent};
+use serde_json::Value;
+
+/// Operators offered, in the order Left/Right cycles through them
+pub const OPERATORS: &[&str] = &["=", "!=", ">", ">=", "<", "<=", "in", "not in", "contains", "startswith", "endswith"];
+
+/// A column of the builder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Field,
+    Operator,
+    Value,
+}
+
+/// One `<field> <op> <value>` condition
+#[derive(Debug, Clone, Default)]
+pub struct FilterRow {
+    pub field: String,
+    /// Index into [`OPERATORS`]
+    pub operator: usize,
+    pub value: String,
+}
+
+impl FilterRow {
+    /// The condition as query text, or `None` while the field is empty
+    fn condition(&self) -> Option<String> {
+        let field = self.field.trim();
+        if field.is_empty() {
+            return None;
+        }
+        Some(format!("{} {} {}", field, OPERATORS[self.operator], literal(self.value.trim())))
+    }
+}
+
+/// A value as the parser should read it: JSON as is, anything else quoted
+fn literal(value: &str) -> String {
+    match serde_json::from_str::<Value>(value) {
+        Ok(_) => value.to_string(),
+        Err(_) => Value::String(value.to_string()).to_string(),
+    }
+}
+
+/// Filter rows for one collection
+#[derive(Debug, Clone)]
+pub struct QueryBuilder {
+    pub collection: String,
+    pub rows: Vec<FilterRow>,
+    pub row: usize,
+    pub column: Column,
+    /// Documents matching the current rows, or why they cannot be counted
+    pub matches: Result<usize, String>,
+}
+
+impl QueryBuilder {
+    pub fn new(collection: &str) -> Self {
+        Self {
+            collection: collection.to_string(),
+            rows: vec![FilterRow::default()],
+            row: 0,
+            column: Column::Field,
+            matches: Ok(0),
+        }
+    }
+
+    /// The rows as a `find` command
+    pub fn query_text(&self) -> String {
+        let conditions: Vec<String> = self.rows.iter().filter_map(FilterRow::condition).collect();
+        if conditions.is_empty() {
+            format!("find {}", self.collection)
+        } else {
+            format!("find {} where {}", self.collection, conditions.join(" and "))
+        }
+    }
+
+    /// Parse the rows into a query
+    pub fn query(&self) -> Result<FindQuery, String> {
+        match parser::parse(&self.query_text()) {
+            Ok(Command::Find(query)) => Ok(query),
+            Ok(_) => Err("Not a find query".to_string()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn cell(&mut self) -> Option<&mut String> {
+        let row = &mut self.rows[self.row];
+        match self.column {
+            Column::Field => Some(&mut row.field),
+            Column::Value => Some(&mut row.value),
+            Column::Operator => None,
+        }
+    }
+
+    /// Apply an editing or movement key; returns whether the rows changed
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Char(c) => match self.cell() {
+                Some(cell) => cell.push(c),
+                // Typing on the operator picks the next one starting with it
+                None => {
+                    let operator = &mut self.rows[self.row].operator;
+                    if let Some(i) = (1..=OPERATORS.len())
+                        .map(|step| (*operator + step) % OPERATORS.len())
+                        .find(|i| OPERATORS[*i].starts_with(c))
+                    {
+                        *operator = i;
+                    }
+                }
+            },
+            KeyCode::Backspace => {
+                self.cell().map(String::pop);
+            }
+            KeyCode::Right if self.column == Column::Operator => {
+                let operator = &mut self.rows[self.row].operator;
+                *operator = (*operator + 1) % OPERATORS.len();
+            }
+            KeyCode::Left if self.column == Column::Operator => {
+                let operator = &mut self.rows[self.row].operator;
+                *operator = operator.checked_sub(1).unwrap_or(OPERATORS.len() - 1);
+            }
+            KeyCode::Tab => {
+                self.column = match self.column {
+                    Column::Field => Column::Operator,
+                    Column::Operator => Column::Value,
+                    Column::Value => return self.next_row(),
+                };
+                return false;
+            }
+            KeyCode::BackTab => {
+                self.column = match self.column {
+                    Column::Value => Column::Operator,
+                    Column::Operator => Column::Field,
+                    Column::Field if self.row > 0 => {
+                        self.row -= 1;
+                        Column::Value
+                    }
+                    Column::Field => Column::Field,
+                };
+                return false;
+            }
+            KeyCode::Enter => return self.next_row(),
+            KeyCode::Up if self.row > 0 => {
+                self.row -= 1;
+                return false;
+            }
+            KeyCode::Down if self.row + 1 < self.rows.len() => {
+                self.row += 1;
+                return false;
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Move to the next row, adding one after the last
+    fn next_row(&mut self) -> bool {
+        let added = self.row + 1 == self.rows.len();
+        if added {
+            self.rows.push(FilterRow::default());
+        }
+        self.row += 1;
+        self.column = Column::Field;
+        added
+    }
+
+    /// Drop the current row
+    pub fn remove_row(&mut self) {
+        if self.rows.len() > 1 {
+            self.rows.remove(self.row);
+            self.row = self.row.min(self.rows.len() - 1);
+        } else {
+            self.rows[0] = FilterRow::default();
+        }
+        self.column = Column::Field;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+
+    fn typed(builder: &mut QueryBuilder, keys: &str) {
+        for c in keys.chars() {
+            let code = match c {
+                '\t' => KeyCode::Tab,
+                '>' if builder.column == Column::Operator => KeyCode::Right,
+                c => KeyCode::Char(c),
+            };
+            builder.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+        }
+    }
+
+    #[test]
+    fn test_rows_become_a_query() {
+        let mut builder = QueryBuilder::new("users");
+        assert_eq!(builder.query_text(), "find users");
+
+        typed(&mut builder, "age\t>>\t30\tname\tc\tAl Smith");
+        assert_eq!(builder.query_text(), r#"find users where age > 30 and name contains "Al Smith""#);
+        assert_eq!(builder.query().unwrap().filter.conditions.len(), 2);
+
+        builder.rows[0].operator = OPERATORS.iter().position(|op| *op == "in").unwrap();
+        assert!(builder.query().is_err());
+        builder.remove_row();
+        assert_eq!(builder.rows.len(), 1);
+    }
+}
@@ -0,0 +1,288 @@
+//! What the results pane shows
+//!
+//! Output is kept as entries rather than preformatted text: command output
+//! as lines tagged with what they report, and JSON values as trees that are
+//! pretty-printed as they are drawn. That lets the pane highlight JSON token
+//! by token and fold its objects and arrays, while scrolling still counts
+//! the rows on screen.
+
+use serde_json::Value;
+use std::ops::Range;
+
+/// What a line of command output reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    /// A command as it was typed
+    Input,
+    Success,
+    Error,
+    /// The welcome banner
+    Banner,
+    /// A key hint such as "[n] Create new database"
+    Hint,
+    /// A bulleted list item
+    Item,
+    Plain,
+}
+
+impl LineKind {
+    /// The kind of a line of command output, from the marker it starts with
+    pub fn of(line: &str) -> Self {
+        let marker = line.trim_start().chars().next();
+        match marker {
+            Some('→') => LineKind::Input,
+            Some('✓') => LineKind::Success,
+            Some('✗') => LineKind::Error,
+            Some('•') => LineKind::Item,
+            Some('[') => LineKind::Hint,
+            Some('╔' | '║' | '╚') => LineKind::Banner,
+            _ => LineKind::Plain,
+        }
+    }
+}
+
+/// A piece of a pretty-printed JSON line
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    /// `{` or `[` opening `len` members; `end` is the row that closes it
+    Open { bracket: char, len: usize, end: usize },
+    Close(char),
+    Scalar(Value),
+}
+
+#[derive(Debug, Clone)]
+struct JsonRow {
+    depth: usize,
+    key: Option<String>,
+    token: Token,
+    /// Whether a comma follows
+    comma: bool,
+}
+
+/// A JSON value laid out one token per row, with folded containers
+#[derive(Debug, Clone)]
+struct JsonTree {
+    rows: Vec<JsonRow>,
+    collapsed: Vec<bool>,
+}
+
+impl JsonTree {
+    fn new(value: &Value) -> Self {
+        fn walk(value: &Value, depth: usize, key: Option<String>, comma: bool, rows: &mut Vec<JsonRow>) {
+            let members: Vec<(Option<String>, &Value)> = match value {
+                Value::Object(map) => map.iter().map(|(k, v)| (Some(k.clone()), v)).collect(),
+                Value::Array(items) => items.iter().map(|v| (None, v)).collect(),
+                scalar => {
+                    rows.push(JsonRow { depth, key, token: Token::Scalar(scalar.clone()), comma });
+                    return;
+                }
+            };
+            let (open, close) = if value.is_object() { ('{', '}') } else { ('[', ']') };
+            let start = rows.len();
+            let len = members.len();
+            rows.push(JsonRow { depth, key, token: Token::Open { bracket: open, len, end: 0 }, comma: false });
+            for (i, (key, member)) in members.into_iter().enumerate() {
+                walk(member, depth + 1, key, i + 1 < len, rows);
+            }
+            let end = rows.len();
+            rows.push(JsonRow { depth, key: None, token: Token::Close(close), comma });
+            rows[start].token = Token::Open { bracket: open, len, end };
+        }
+
+        let mut rows = Vec::new();
+        walk(value, 0, None, false, &mut rows);
+        let collapsed = vec![false; rows.len()];
+        Self { rows, collapsed }
+    }
+
+    /// Rows not hidden inside a folded container
+    fn visible(&self) -> Vec<usize> {
+        let mut visible = Vec::new();
+        let mut i = 0;
+        while i < self.rows.len() {
+            visible.push(i);
+            i = match self.rows[i].token {
+                Token::Open { end, .. } if self.collapsed[i] => end + 1,
+                _ => i + 1,
+            };
+        }
+        visible
+    }
+
+    /// The container a row belongs to: the row itself if it opens one
+    fn container(&self, row: usize) -> Option<usize> {
+        (0..=row).rev().find(|&i| matches!(self.rows[i].token, Token::Open { end, .. } if end >= row))
+    }
+
+    fn line(&self, row: usize) -> JsonLine<'_> {
+        let json = &self.rows[row];
+        let collapsed = self.collapsed[row];
+        let comma = match json.token {
+            // A folded container ends where its closing bracket would
+            Token::Open { end, .. } if collapsed => self.rows[end].comma,
+            _ => json.comma,
+        };
+        JsonLine { depth: json.depth, key: json.key.as_deref(), token: &json.token, collapsed, comma }
+    }
+}
+
+/// One row of a JSON value, for drawing
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonLine<'a> {
+    pub depth: usize,
+    /// The member's name, inside an object
+    pub key: Option<&'a str>,
+    pub token: &'a Token,
+    /// Whether the container this row opens is folded
+    pub collapsed: bool,
+    pub comma: bool,
+}
+
+/// One row of the results pane
+#[derive(Debug, Clone, PartialEq)]
+pub enum Row<'a> {
+    Line(LineKind, &'a str),
+    Json(JsonLine<'a>),
+}
+
+#[derive(Debug, Clone)]
+enum Entry {
+    Line(LineKind, String),
+    Json(JsonTree),
+}
+
+/// Everything the results pane shows, oldest first
+#[derive(Debug, Clone, Default)]
+pub struct Results {
+    entries: Vec<Entry>,
+    /// Rows on screen, as (entry, row within the entry)
+    rows: Vec<(usize, usize)>,
+}
+
+impl Results {
+    pub fn push(&mut self, kind: LineKind, line: impl Into<String>) {
+        self.rows.push((self.entries.len(), 0));
+        self.entries.push(Entry::Line(kind, line.into()));
+    }
+
+    /// Add command output, one entry per line, each by its marker
+    pub fn push_text(&mut self, text: &str) {
+        for line in text.lines() {
+            self.push(LineKind::of(line), line);
+        }
+    }
+
+    pub fn push_blank(&mut self) {
+        self.push(LineKind::Plain, "");
+    }
+
+    /// Add a JSON value, fully unfolded
+    pub fn push_json(&mut self, value: &Value) {
+        let tree = JsonTree::new(value);
+        let entry = self.entries.len();
+        self.rows.extend(tree.visible().into_iter().map(|row| (entry, row)));
+        self.entries.push(Entry::Json(tree));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.rows.clear();
+    }
+
+    /// Entries added so far, to mark where the next ones start
+    pub fn mark(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Swap the entries between two marks for everything in `with`,
+    /// keeping the ones after in place; returns the mark where they now end
+    pub fn replace(&mut self, range: Range<usize>, with: Results) -> usize {
+        let end = range.start + with.entries.len();
+        let shift = |entry: usize| entry + end - range.end;
+        let first = self.rows.iter().position(|&(entry, _)| entry >= range.start).unwrap_or(self.rows.len());
+        let last = self.rows.iter().position(|&(entry, _)| entry >= range.end).unwrap_or(self.rows.len());
+
+        let after: Vec<(usize, usize)> = self.rows.drain(last..).map(|(entry, row)| (shift(entry), row)).collect();
+        self.rows.truncate(first);
+        self.rows.extend(with.rows.into_iter().map(|(entry, row)| (range.start + entry, row)));
+        self.rows.extend(after);
+        self.entries.splice(range, with.entries);
+        end
+    }
+
+    /// Rows on screen
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = Row<'_>> + '_ {
+        self.rows.iter().map(|&(entry, row)| match &self.entries[entry] {
+            Entry::Line(kind, line) => Row::Line(*kind, line.as_str()),
+            Entry::Json(tree) => Row::Json(tree.line(row)),
+        })
+    }
+
+    /// Fold or unfold the JSON object or array at, or around, row `index`
+    ///
+    /// Returns the row now showing the container's opening bracket, or
+    /// `None` if the row is not part of a JSON value.
+    pub fn toggle(&mut self, index: usize) -> Option<usize> {
+        let &(entry, row) = self.rows.get(index)?;
+        let Entry::Json(tree) = &mut self.entries[entry] else { return None };
+        let open = tree.container(row)?;
+        tree.collapsed[open] = !tree.collapsed[open];
+
+        // Swap the entry's old rows for the ones now visible
+        let first = self.rows.iter().position(|&(e, _)| e == entry)?;
+        let count = self.rows[first..].iter().take_while(|&&(e, _)| e == entry).count();
+        let visible = tree.visible();
+        let at = first + visible.iter().position(|&r| r == open)?;
+        self.rows.splice(first..first + count, visible.into_iter().map(|row| (entry, row)));
+        Some(at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_results_rows_and_folding() {
+        let mut results = Results::default();
+        results.push_text("→ find users\n✓ done");
+        results.push_json(&json!([{"_id": "a", "tags": ["x"]}, {"_id": "b"}]));
+        assert_eq!(results.len(), 2 + 11);
+
+        let rows: Vec<Row> = results.rows().collect();
+        assert_eq!(rows[0], Row::Line(LineKind::Input, "→ find users"));
+        assert_eq!(rows[1], Row::Line(LineKind::Success, "✓ done"));
+        let Row::Json(line) = &rows[5] else { panic!("expected JSON") };
+        assert_eq!((line.depth, line.key), (2, Some("tags")));
+        assert!(matches!(line.token, Token::Open { bracket: '[', len: 1, .. }));
+
+        // Folding from inside the first document hides its members
+        assert_eq!(results.toggle(4), Some(3));
+        assert_eq!(results.len(), 2 + 6);
+        let Row::Json(line) = results.rows().nth(3).unwrap() else { panic!("expected JSON") };
+        assert!(line.collapsed && line.comma);
+        assert!(matches!(line.token, Token::Open { bracket: '{', len: 2, .. }));
+
+        assert_eq!(results.toggle(3), Some(3));
+        assert_eq!(results.len(), 2 + 11);
+        assert_eq!(results.toggle(0), None);
+
+        // Swapping the first line keeps the JSON after it
+        let mut line = Results::default();
+        line.push_text("→ find users where active = true\n✓ 1 found");
+        assert_eq!(results.mark(), 3);
+        assert_eq!(results.replace(0..1, line), 2);
+        assert_eq!(results.len(), 3 + 11);
+        assert_eq!(results.rows().nth(1), Some(Row::Line(LineKind::Success, "✓ 1 found")));
+        assert!(matches!(results.rows().nth(3), Some(Row::Json(_))));
+    }
+}
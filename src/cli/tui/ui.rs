@@ -1,13 +1,31 @@
 use ratatui::{
     prelude::*,
-    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, Wrap},
-    style::{Color, Modifier, Style},
+    widgets::{
+        Axis, BarChart, Block, Borders, Chart, Clear, Dataset, GraphType, List, ListItem, Paragraph, Scrollbar,
+        ScrollbarOrientation, ScrollbarState, Wrap,
+    },
+    style::{Modifier, Style},
 };
 
 use super::app::{AppMode, AppScreen, FocusedPanel, TuiApp};
+use super::completion::{self, Completion, POPUP_ROWS};
+use super::config::{Action, Theme};
+use super::database_form::{DatabaseForm, LABELS};
+use super::editor::Editor;
+use super::query_builder::{Column, OPERATORS};
+use super::results::{LineKind, Row, Token};
+use crate::vector::{Projection, VectorSearchResult};
+use serde_json::Value;
+
+/// Most input lines shown at once; longer input scrolls
+const MAX_INPUT_LINES: usize = 10;
+
+/// Frames of the running-command indicator, one per tick
+const SPINNER: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
 
 pub fn render(app: &TuiApp, frame: &mut Frame) {
     let size = frame.area();
+    frame.render_widget(Block::default().style(Style::default().fg(app.theme.text).bg(app.theme.background)), size);
 
     // Create main layout
     let main_chunks = Layout::default()
@@ -15,7 +33,7 @@ pub fn render(app: &TuiApp, frame: &mut Frame) {
         .constraints([
             Constraint::Length(3),  // Header
             Constraint::Min(10),    // Main content
-            Constraint::Length(3),  // Query input
+            Constraint::Length(input_height(app)),  // Query input
             Constraint::Length(1),  // Status bar
         ])
         .split(size);
@@ -31,6 +49,12 @@ pub fn render(app: &TuiApp, frame: &mut Frame) {
         AppScreen::DatabaseExplorer => {
             render_database_explorer(app, frame, main_chunks[1]);
         }
+        AppScreen::VectorExplorer => {
+            render_vector_explorer(app, frame, main_chunks[1]);
+        }
+        AppScreen::CollectionStats => {
+            render_collection_stats(app, frame, main_chunks[1]);
+        }
     }
 
     // Render query input
@@ -39,12 +63,148 @@ pub fn render(app: &TuiApp, frame: &mut Frame) {
     // Render status bar
     render_status_bar(app, frame, main_chunks[3]);
 
+    if let Some(completion) = &app.completion {
+        render_completion(app, completion, frame, main_chunks[2]);
+    }
+
+    // Render the document editor over everything else
+    if let Some(editor) = &app.editor {
+        render_editor(app, editor, frame, size);
+    }
+
     // Render help popup if active
     if app.show_help {
         render_help_popup(app, frame, size);
     }
 }
 
+fn render_editor(app: &TuiApp, editor: &Editor, frame: &mut Frame, area: Rect) {
+    let popup_area = Rect {
+        x: area.width / 10,
+        y: area.height / 10,
+        width: area.width - area.width / 5,
+        height: area.height - area.height / 5,
+    };
+    frame.render_widget(Clear, popup_area);
+
+    let block = Block::default()
+        .title(format!(" {} ", editor.title()))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(app.theme.edit));
+    let inner = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(inner);
+    let height = chunks[0].height as usize;
+
+    // Lines to show, and the cursor within them as (row, column)
+    let (lines, (row, column)): (Vec<Line>, (usize, usize)) = match editor {
+        Editor::Json(editor) => {
+            let lines = editor.text.lines().iter().map(|l| Line::from(l.as_str())).collect();
+            (lines, editor.text.cursor())
+        }
+        Editor::Form(form) => {
+            let width = form.fields.iter().map(|(f, _)| f.chars().count()).max().unwrap_or(0).max(8);
+            let lines = form
+                .fields
+                .iter()
+                .enumerate()
+                .map(|(i, (field, value))| {
+                    let current = Style::default().fg(app.theme.on_accent).bg(app.theme.edit);
+                    let style = |in_value: bool| {
+                        if i == form.row && form.in_value == in_value { current } else { Style::default() }
+                    };
+                    Line::from(vec![
+                        Span::styled(format!("{:<width$}", field, width = width), style(false)),
+                        Span::raw(" : "),
+                        Span::styled(value.clone(), style(true)),
+                    ])
+                })
+                .collect();
+            let (field, value) = &form.fields[form.row];
+            let column = if form.in_value { width + 3 + value.chars().count() } else { field.chars().count() };
+            (lines, (form.row, column))
+        }
+        Editor::Query(builder) => {
+            let field_width = builder.rows.iter().map(|r| r.field.chars().count()).max().unwrap_or(0).max(8);
+            let op_width = OPERATORS.iter().map(|op| op.len()).max().unwrap_or(0);
+            let current = Style::default().fg(app.theme.on_accent).bg(app.theme.edit);
+            let mut lines: Vec<Line> = builder
+                .rows
+                .iter()
+                .enumerate()
+                .map(|(i, row)| {
+                    let style = |column: Column| {
+                        if i == builder.row && builder.column == column { current } else { Style::default() }
+                    };
+                    Line::from(vec![
+                        Span::styled(format!("{:<width$}", row.field, width = field_width), style(Column::Field)),
+                        Span::raw("  "),
+                        Span::styled(format!("{:<width$}", OPERATORS[row.operator], width = op_width), style(Column::Operator)),
+                        Span::raw("  "),
+                        Span::styled(row.value.clone(), style(Column::Value)),
+                    ])
+                })
+                .collect();
+            lines.push(Line::from(""));
+            lines.push(Line::from(Span::styled(builder.query_text(), Style::default().fg(app.theme.muted))));
+            lines.push(match &builder.matches {
+                Ok(count) => Line::from(Span::styled(
+                    format!("{} matching document(s)", count),
+                    Style::default().fg(app.theme.success).add_modifier(Modifier::BOLD),
+                )),
+                Err(e) => Line::from(Span::styled(e.clone(), Style::default().fg(app.theme.error))),
+            });
+
+            let row = &builder.rows[builder.row];
+            let column = match builder.column {
+                Column::Field => row.field.chars().count(),
+                Column::Operator => field_width + 2,
+                Column::Value => field_width + 2 + op_width + 2 + row.value.chars().count(),
+            };
+            (lines, (builder.row, column))
+        }
+        Editor::Database(form) => {
+            let width = LABELS.iter().map(|label| label.len()).max().unwrap_or(0);
+            let current = Style::default().fg(app.theme.on_accent).bg(app.theme.edit);
+            let mut lines: Vec<Line> = LABELS
+                .iter()
+                .zip(form.values())
+                .enumerate()
+                .map(|(i, (label, value))| {
+                    let value = if DatabaseForm::is_choice(i) { format!("◀ {} ▶", value) } else { value };
+                    let style = if i == form.row { current } else { Style::default() };
+                    Line::from(vec![Span::raw(format!("{:<width$}  ", label, width = width)), Span::styled(value, style)])
+                })
+                .collect();
+            lines.push(Line::from(""));
+            lines.push(Line::styled(
+                "Vector collections: name:dimensions[:distance], e.g. docs:384, images:512:euclidean",
+                Style::default().fg(app.theme.muted),
+            ));
+            let value = &form.values()[form.row];
+            let column = width + 2 + if DatabaseForm::is_choice(form.row) { 0 } else { value.chars().count() };
+            (lines, (form.row, column))
+        }
+    };
+
+    let scroll = row.saturating_sub(height.saturating_sub(1));
+    frame.render_widget(Paragraph::new(lines).scroll((scroll as u16, 0)), chunks[0]);
+    frame.set_cursor_position(Position {
+        x: chunks[0].x + column as u16,
+        y: chunks[0].y + (row - scroll) as u16,
+    });
+
+    let footer = match &app.editor_message {
+        Some(message) => Span::styled(message.as_str(), Style::default().fg(app.theme.error)),
+        None => Span::styled(app.status_message.as_str(), Style::default().fg(app.theme.muted)),
+    };
+    frame.render_widget(Paragraph::new(Line::from(footer)), chunks[1]);
+}
+
 fn render_header(app: &TuiApp, frame: &mut Frame, area: Rect) {
     let (title, info) = match app.screen {
         AppScreen::ConnectionManager => {
@@ -57,21 +217,27 @@ fn render_header(app: &TuiApp, frame: &mut Frame, area: Rect) {
                 .unwrap_or_else(|| "unknown".to_string());
             ("KeraDB", format!("{} │ {} collections", db_name, app.collections.len()))
         }
+        AppScreen::VectorExplorer => {
+            ("KeraDB", format!("Vectors │ {} collections", app.vector_collections.len()))
+        }
+        AppScreen::CollectionStats => ("KeraDB", format!("Stats │ {} collections", app.collections.len())),
     };
 
     let mode_str = match app.mode {
         AppMode::Normal => "NORMAL",
         AppMode::Insert => "INSERT",
         AppMode::Command => "COMMAND",
+        AppMode::Edit => "EDIT",
     };
 
-    let header_text = format!(" {} │ {} │ {}", title, info, mode_str);
+    let watching = if app.is_watching() { " │ WATCHING" } else { "" };
+    let header_text = format!(" {} │ {} │ {}{}", title, info, mode_str, watching);
 
     let header = Paragraph::new(header_text)
-        .style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+        .style(Style::default().fg(app.theme.title).add_modifier(Modifier::BOLD))
         .block(Block::default()
             .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::Cyan))
+            .border_style(Style::default().fg(app.theme.title))
             .title(" KeraDB TUI "));
 
     frame.render_widget(header, area);
@@ -105,16 +271,299 @@ fn render_database_explorer(app: &TuiApp, frame: &mut Frame, area: Rect) {
     render_results(app, frame, chunks[1]);
 }
 
+fn render_vector_explorer(app: &TuiApp, frame: &mut Frame, area: Rect) {
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
+        .split(area);
+    let left = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(5), Constraint::Length(10)])
+        .split(columns[0]);
+    let right = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(55), Constraint::Percentage(45)])
+        .split(columns[1]);
+
+    let panel = |title: &str, focused: bool| {
+        let border_color = if focused { app.theme.accent } else { app.theme.border };
+        let title = if focused { format!("[ {} ]", title) } else { format!(" {} ", title) };
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(border_color))
+            .title(title)
+    };
+    let selected = Style::default().fg(app.theme.on_accent).bg(app.theme.accent).add_modifier(Modifier::BOLD);
+
+    // Collections
+    let items: Vec<ListItem> = if app.vector_collections.is_empty() {
+        vec![
+            ListItem::new("  No vector collections").style(Style::default().fg(app.theme.muted)),
+            ListItem::new(""),
+            ListItem::new("  Use 'vcreate' to").style(Style::default().fg(app.theme.muted)),
+            ListItem::new("  create one").style(Style::default().fg(app.theme.muted)),
+        ]
+    } else {
+        app.vector_collections
+            .iter()
+            .enumerate()
+            .map(|(i, stats)| {
+                let is_selected = i == app.selected_vector_collection;
+                let prefix = if is_selected { "▶ " } else { "  " };
+                let item = ListItem::new(format!("{}{} ({})", prefix, stats.name, stats.vector_count));
+                if is_selected { item.style(selected) } else { item }
+            })
+            .collect()
+    };
+    let is_focused = app.focused == FocusedPanel::Collections;
+    frame.render_widget(List::new(items).block(panel("Vector Collections", is_focused)), left[0]);
+
+    // Stats for the selected collection
+    let stats_text: Vec<Line> = match app.vector_collections.get(app.selected_vector_collection) {
+        Some(stats) => vec![
+            Line::from(format!("Vectors:     {}", stats.vector_count)),
+            Line::from(format!("Dimensions:  {}", stats.dimensions)),
+            Line::from(format!("Distance:    {}", stats.distance.name())),
+            Line::from(format!(
+                "Compression: {:?} ({:.0}% saved)",
+                stats.compression_mode,
+                stats.compression_ratio * 100.0
+            )),
+            Line::from(format!("Memory:      {} KB", stats.memory_bytes / 1024)),
+            Line::from(format!("HNSW:        {} layers, M={}", stats.hnsw_layers, stats.hnsw_m)),
+        ],
+        None => Vec::new(),
+    };
+    frame.render_widget(Paragraph::new(stats_text).block(panel("Stats", false)), left[1]);
+
+    // Ranked results
+    let items: Vec<ListItem> = if app.vector_results.is_empty() {
+        vec![ListItem::new("  Press [/] to search the selected collection").style(Style::default().fg(app.theme.muted))]
+    } else {
+        app.vector_results
+            .iter()
+            .enumerate()
+            .map(|(i, result)| {
+                let item = ListItem::new(format!(
+                    "{:>3}. {:<24} {:>10.4}  {}",
+                    result.rank + 1,
+                    result_label(result),
+                    result.score,
+                    result.document.text.as_deref().unwrap_or_default().replace('\n', " ")
+                ));
+                if i == app.selected_vector_result { item.style(selected) } else { item }
+            })
+            .collect()
+    };
+    let is_focused = app.focused == FocusedPanel::Results;
+    match &app.vector_projection {
+        Some((collection, projection)) => render_projection(app, collection, projection, frame, right[0]),
+        None => frame.render_widget(List::new(items).block(panel("Results (rank, id, score)", is_focused)), right[0]),
+    }
+
+    // Preview of the selected result
+    let preview: Vec<Line> = match app.vector_results.get(app.selected_vector_result) {
+        Some(result) => {
+            let mut lines = vec![Line::from(Span::styled(
+                format!("{}  score {:.6}", result_label(result), result.score),
+                Style::default().fg(app.theme.title).add_modifier(Modifier::BOLD),
+            ))];
+            if let Some(text) = &result.document.text {
+                lines.extend(text.lines().map(|l| Line::from(l.to_string())));
+            }
+            let metadata = serde_json::to_string_pretty(&result.document.metadata).unwrap_or_default();
+            lines.extend(metadata.lines().map(|l| Line::from(Span::styled(l.to_string(), Style::default().fg(app.theme.success)))));
+            lines
+        }
+        None => Vec::new(),
+    };
+    frame.render_widget(
+        Paragraph::new(preview).wrap(Wrap { trim: false }).block(panel("Metadata", false)),
+        right[1],
+    );
+}
+
+/// A scatter plot of a collection's 2D projection
+fn render_projection(app: &TuiApp, collection: &str, projection: &Projection, frame: &mut Frame, area: Rect) {
+    let points: Vec<(f64, f64)> = projection.points.iter().map(|p| (f64::from(p.x), f64::from(p.y))).collect();
+    // Symmetric bounds keep the origin, the collection's mean, in the middle
+    let extent = |axis: fn(&(f64, f64)) -> f64| points.iter().map(|p| axis(p).abs()).fold(0.0, f64::max).max(1e-6);
+    let (x, y) = (extent(|p| p.0), extent(|p| p.1));
+    let [x_share, y_share] = projection.explained_variance;
+    let title = format!(
+        " {} of {} ({} points; x {:.0}%, y {:.0}% of variance) ",
+        projection.method.name().to_uppercase(),
+        collection,
+        points.len(),
+        x_share * 100.0,
+        y_share * 100.0
+    );
+    let dataset = Dataset::default()
+        .marker(symbols::Marker::Braille)
+        .graph_type(GraphType::Scatter)
+        .style(Style::default().fg(app.theme.accent))
+        .data(&points);
+    let label = |bound: f64| format!("{:.2}", bound);
+    let chart = Chart::new(vec![dataset])
+        .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(app.theme.border)).title(title))
+        .x_axis(Axis::default().bounds([-x, x]).labels([label(-x), label(x)]))
+        .y_axis(Axis::default().bounds([-y, y]).labels([label(-y), label(y)]));
+    frame.render_widget(chart, area);
+}
+
+fn render_collection_stats(app: &TuiApp, frame: &mut Frame, area: Rect) {
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(25), Constraint::Percentage(75)])
+        .split(area);
+    let right = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(8), Constraint::Length(5), Constraint::Min(8), Constraint::Length(10)])
+        .split(columns[1]);
+    render_collections(app, frame, columns[0]);
+
+    let panel = |title: &str| {
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(app.theme.border))
+            .title(format!(" {} ", title))
+    };
+    let dim = Style::default().fg(app.theme.muted);
+    let collection = app.collections.get(app.selected_collection).map(|(name, _)| name.as_str());
+
+    // Size of the selected collection
+    let summary: Vec<Line> = match (&app.db_stats, collection) {
+        (None, _) if app.task.is_some() => vec![Line::styled("Reading the data file...", dim)],
+        (None, _) => vec![Line::styled("Press [r] to load statistics", dim)],
+        (Some(_), None) => vec![Line::styled("No collection selected", dim)],
+        (Some(stats), Some(name)) => match stats.collections.iter().find(|c| c.name == name) {
+            Some(c) => vec![
+                Line::from(format!("Documents:  {}", c.documents)),
+                Line::from(format!(
+                    "Data:       {} ({} per document)",
+                    format_bytes(c.bytes),
+                    format_bytes(c.average_document_size())
+                )),
+                Line::from(format!("Pages:      {} of {} data pages", c.pages, stats.pages.data)),
+                Line::from(format!("Index:      {} (primary key, in memory)", format_bytes(c.index_bytes))),
+                Line::from(format!(
+                    "Database:   {} in {} pages of {}",
+                    format_bytes(stats.file_size),
+                    stats.pages.total,
+                    format_bytes(stats.page_size as u64)
+                )),
+            ],
+            None => vec![Line::styled("Not in the last statistics; press [r] to reload", dim)],
+        },
+    };
+    frame.render_widget(Paragraph::new(summary).block(panel(collection.unwrap_or("Collection"))), right[0]);
+
+    // Indexes that queries on the selected collection could have used
+    let suggestions: Vec<Line> = app
+        .index_suggestions
+        .iter()
+        .filter(|s| Some(s.collection.as_str()) == collection)
+        .map(|s| {
+            Line::from(vec![
+                Span::styled(s.index.to_string(), Style::default().fg(app.theme.accent)),
+                Span::styled(
+                    format!(
+                        "  {} full scan(s), ~{} reads saved, ~{} read per query instead of {}",
+                        s.scans,
+                        s.reads_saved(),
+                        s.estimated_reads_per_query,
+                        app.collections.get(app.selected_collection).map_or(0, |(_, count)| *count)
+                    ),
+                    dim,
+                ),
+            ])
+        })
+        .collect();
+    let suggestions = if suggestions.is_empty() {
+        vec![Line::styled("No full scans an index would have spared; press [r] after running queries", dim)]
+    } else {
+        suggestions
+    };
+    frame.render_widget(Paragraph::new(suggestions).block(panel("Index suggestions")), right[1]);
+
+    // Document count over time, from the system database
+    let history = &app.collection_history;
+    if history.len() < 2 {
+        let text = Line::styled("Counts are sampled while the TUI is connected; check back later", dim);
+        frame.render_widget(Paragraph::new(text).wrap(Wrap { trim: false }).block(panel("Documents over time")), right[2]);
+    } else {
+        let start = history[0].0;
+        let points: Vec<(f64, f64)> =
+            history.iter().map(|(at, count)| ((*at - start).num_seconds() as f64, *count as f64)).collect();
+        let end = points[points.len() - 1].0.max(1.0);
+        let max = history.iter().map(|(_, count)| *count).max().unwrap_or(0).max(1);
+        let time = |at: &chrono::DateTime<chrono::Utc>| at.with_timezone(&chrono::Local).format("%m-%d %H:%M").to_string();
+        let dataset = Dataset::default()
+            .marker(symbols::Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(app.theme.title))
+            .data(&points);
+        let chart = Chart::new(vec![dataset])
+            .block(panel("Documents over time"))
+            .x_axis(Axis::default().bounds([0.0, end]).labels([time(&start), time(&history[history.len() - 1].0)]))
+            .y_axis(Axis::default().bounds([0.0, max as f64]).labels(["0".to_string(), max.to_string()]));
+        frame.render_widget(chart, right[2]);
+    }
+
+    // How the data file's pages are used
+    match &app.db_stats {
+        Some(stats) => {
+            let pages = &stats.pages;
+            let bars = [
+                ("data", pages.data),
+                ("blob", pages.blob),
+                ("index", pages.index),
+                ("free", pages.free),
+                ("meta", pages.meta),
+                ("vector", pages.vector_data + pages.vector_index),
+                ("bad", pages.unreadable),
+            ]
+            .map(|(label, count)| (label, count as u64));
+            let chart = BarChart::default()
+                .block(panel("Page usage"))
+                .data(&bars)
+                .bar_width(7)
+                .bar_gap(2)
+                .bar_style(Style::default().fg(app.theme.accent))
+                .value_style(Style::default().fg(app.theme.on_accent).bg(app.theme.accent));
+            frame.render_widget(chart, right[3]);
+        }
+        None => frame.render_widget(panel("Page usage"), right[3]),
+    }
+}
+
+/// A size in B, KB or MB
+fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1_048_575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1024.0 / 1024.0),
+    }
+}
+
+/// A search result's external ID, or its internal one
+fn result_label(result: &VectorSearchResult) -> String {
+    match &result.document.external_id {
+        Some(id) => id.clone(),
+        None => format!("#{}", result.document.id),
+    }
+}
+
 fn render_connections_list(app: &TuiApp, frame: &mut Frame, area: Rect) {
     let is_focused = app.focused == FocusedPanel::Connections;
-    let border_color = if is_focused { Color::Yellow } else { Color::Gray };
+    let border_color = if is_focused { app.theme.accent } else { app.theme.border };
 
     let items: Vec<ListItem> = if app.connections.is_empty() {
         vec![
-            ListItem::new("  No saved connections").style(Style::default().fg(Color::DarkGray)),
+            ListItem::new("  No saved connections").style(Style::default().fg(app.theme.muted)),
             ListItem::new(""),
-            ListItem::new("  Press [n] to create new").style(Style::default().fg(Color::DarkGray)),
-            ListItem::new("  Press [o] to open existing").style(Style::default().fg(Color::DarkGray)),
+            ListItem::new("  Press [n] to create new").style(Style::default().fg(app.theme.muted)),
+            ListItem::new("  Press [o] to open existing").style(Style::default().fg(app.theme.muted)),
         ]
     } else {
         app.connections
@@ -124,11 +573,11 @@ fn render_connections_list(app: &TuiApp, frame: &mut Frame, area: Rect) {
                 let is_selected = i == app.selected_connection;
                 let style = if is_selected {
                     Style::default()
-                        .fg(Color::Black)
-                        .bg(Color::Yellow)
+                        .fg(app.theme.on_accent)
+                        .bg(app.theme.accent)
                         .add_modifier(Modifier::BOLD)
                 } else {
-                    Style::default().fg(Color::White)
+                    Style::default().fg(app.theme.text)
                 };
 
                 // Format connection info
@@ -164,14 +613,14 @@ fn render_connections_list(app: &TuiApp, frame: &mut Frame, area: Rect) {
 
 fn render_collections(app: &TuiApp, frame: &mut Frame, area: Rect) {
     let is_focused = app.focused == FocusedPanel::Collections;
-    let border_color = if is_focused { Color::Yellow } else { Color::Gray };
+    let border_color = if is_focused { app.theme.accent } else { app.theme.border };
 
     let items: Vec<ListItem> = if app.collections.is_empty() {
         vec![
-            ListItem::new("  No collections").style(Style::default().fg(Color::DarkGray)),
+            ListItem::new("  No collections").style(Style::default().fg(app.theme.muted)),
             ListItem::new(""),
-            ListItem::new("  Use 'insert' to").style(Style::default().fg(Color::DarkGray)),
-            ListItem::new("  create one").style(Style::default().fg(Color::DarkGray)),
+            ListItem::new("  Use 'insert' to").style(Style::default().fg(app.theme.muted)),
+            ListItem::new("  create one").style(Style::default().fg(app.theme.muted)),
         ]
     } else {
         app.collections
@@ -181,11 +630,11 @@ fn render_collections(app: &TuiApp, frame: &mut Frame, area: Rect) {
                 let is_selected = i == app.selected_collection;
                 let style = if is_selected {
                     Style::default()
-                        .fg(Color::Black)
-                        .bg(Color::Yellow)
+                        .fg(app.theme.on_accent)
+                        .bg(app.theme.accent)
                         .add_modifier(Modifier::BOLD)
                 } else {
-                    Style::default().fg(Color::White)
+                    Style::default().fg(app.theme.text)
                 };
 
                 let prefix = if is_selected { "▶ " } else { "  " };
@@ -208,35 +657,24 @@ fn render_collections(app: &TuiApp, frame: &mut Frame, area: Rect) {
 
 fn render_results(app: &TuiApp, frame: &mut Frame, area: Rect) {
     let is_focused = app.focused == FocusedPanel::Results;
-    let border_color = if is_focused { Color::Yellow } else { Color::Gray };
-
-    let results_text: Vec<Line> = app.results
-        .iter()
-        .map(|line| {
-            // Colorize different types of lines
-            if line.starts_with("→ ") {
-                Line::from(Span::styled(line.as_str(), Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)))
-            } else if line.starts_with("✓") {
-                Line::from(Span::styled(line.as_str(), Style::default().fg(Color::Green)))
-            } else if line.starts_with("✗") || line.contains("Error") {
-                Line::from(Span::styled(line.as_str(), Style::default().fg(Color::Red)))
-            } else if line.starts_with("╔") || line.starts_with("║") || line.starts_with("╚") {
-                Line::from(Span::styled(line.as_str(), Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)))
-            } else if line.starts_with("  [") {
-                Line::from(Span::styled(line.as_str(), Style::default().fg(Color::Yellow)))
-            } else if line.contains("\"_id\"") || line.contains("\"id\"") {
-                Line::from(Span::styled(line.as_str(), Style::default().fg(Color::Cyan)))
-            } else if line.starts_with("  •") {
-                Line::from(Span::styled(line.as_str(), Style::default().fg(Color::Blue)))
-            } else {
-                Line::from(Span::raw(line.as_str()))
-            }
+    let border_color = if is_focused { app.theme.accent } else { app.theme.border };
+
+    let results_text: Vec<Line> = app
+        .results
+        .rows()
+        .enumerate()
+        .map(|(i, row)| {
+            let line = result_line(row, &app.theme);
+            // Folding acts on the top row, so mark it while the pane has focus
+            if is_focused && i == app.results_scroll { line.patch_style(Style::default().bg(app.theme.status)) } else { line }
         })
         .collect();
 
     let title = match app.screen {
         AppScreen::ConnectionManager => if is_focused { "[ Info ]" } else { " Info " },
-        AppScreen::DatabaseExplorer => if is_focused { "[ Results ]" } else { " Results " },
+        AppScreen::DatabaseExplorer | AppScreen::VectorExplorer | AppScreen::CollectionStats => {
+            if is_focused { "[ Results ]" } else { " Results " }
+        }
     };
 
     let results = Paragraph::new(results_text)
@@ -269,19 +707,73 @@ fn render_results(app: &TuiApp, frame: &mut Frame, area: Rect) {
     }
 }
 
+/// A row of the results pane, colored by what it shows
+fn result_line<'a>(row: Row<'a>, theme: &Theme) -> Line<'a> {
+    let json = match row {
+        Row::Line(kind, line) => {
+            let style = match kind {
+                LineKind::Input => Style::default().fg(theme.success).add_modifier(Modifier::BOLD),
+                LineKind::Success => Style::default().fg(theme.success),
+                LineKind::Error => Style::default().fg(theme.error),
+                LineKind::Banner => Style::default().fg(theme.title).add_modifier(Modifier::BOLD),
+                LineKind::Hint => Style::default().fg(theme.accent),
+                LineKind::Item => Style::default().fg(theme.info),
+                LineKind::Plain => Style::default(),
+            };
+            return Line::from(Span::styled(line, style));
+        }
+        Row::Json(json) => json,
+    };
+
+    let mut spans = vec![Span::raw("  ".repeat(json.depth))];
+    let mut summary = None;
+    if let Some(key) = json.key {
+        spans.push(Span::styled(Value::from(key).to_string(), Style::default().fg(theme.title)));
+        spans.push(Span::raw(": "));
+    }
+    match json.token {
+        Token::Open { bracket, len, .. } if json.collapsed => {
+            let close = if *bracket == '{' { '}' } else { ']' };
+            spans.push(Span::raw(format!("{}…{}", bracket, close)));
+            let noun = match (*bracket, *len) {
+                ('{', 1) => "field",
+                ('{', _) => "fields",
+                (_, 1) => "item",
+                _ => "items",
+            };
+            summary = Some(Span::styled(format!("  {} {}", len, noun), Style::default().fg(theme.muted)));
+        }
+        Token::Open { bracket, .. } => spans.push(Span::raw(bracket.to_string())),
+        Token::Close(bracket) => spans.push(Span::raw(bracket.to_string())),
+        Token::Scalar(value) => {
+            let color = match value {
+                Value::String(_) => theme.success,
+                Value::Number(_) => theme.accent,
+                _ => theme.edit,
+            };
+            spans.push(Span::styled(value.to_string(), Style::default().fg(color)));
+        }
+    }
+    if json.comma {
+        spans.push(Span::raw(","));
+    }
+    spans.extend(summary);
+    Line::from(spans)
+}
+
 fn render_query_input(app: &TuiApp, frame: &mut Frame, area: Rect) {
     let is_focused = app.focused == FocusedPanel::Query;
     let is_insert = app.mode == AppMode::Insert;
     let is_command = app.mode == AppMode::Command;
     
     let border_color = if is_insert {
-        Color::Green
+        app.theme.success
     } else if is_command {
-        Color::Yellow
+        app.theme.accent
     } else if is_focused {
-        Color::Cyan
+        app.theme.title
     } else {
-        Color::Gray
+        app.theme.border
     };
 
     let title = match app.screen {
@@ -290,18 +782,29 @@ fn render_query_input(app: &TuiApp, frame: &mut Frame, area: Rect) {
             else if is_command { " Command " }
             else { " Input " }
         }
-        AppScreen::DatabaseExplorer => {
+        AppScreen::DatabaseExplorer | AppScreen::CollectionStats => {
             if is_insert { " Query (INSERT) " } 
             else if is_command { " Command " }
             else { " Query " }
         }
+        AppScreen::VectorExplorer => {
+            if is_insert { " Vector search (text or [vector]) " }
+            else if is_command { " Command " }
+            else { " Vector search " }
+        }
     };
 
     let prefix = if is_command { ":" } else { "> " };
     let display_text = format!("{}{}", prefix, app.input);
 
+    // Scroll long input so the cursor's line stays visible
+    let before_cursor = &app.input[..app.cursor_position];
+    let row = before_cursor.matches('\n').count();
+    let scroll = row.saturating_sub(MAX_INPUT_LINES - 1);
+
     let input = Paragraph::new(display_text.as_str())
-        .style(Style::default().fg(Color::White))
+        .scroll((scroll as u16, 0))
+        .style(Style::default().fg(app.theme.text))
         .block(Block::default()
             .borders(Borders::ALL)
             .border_style(Style::default().fg(border_color))
@@ -311,39 +814,119 @@ fn render_query_input(app: &TuiApp, frame: &mut Frame, area: Rect) {
 
     // Show cursor in insert/command mode
     if is_insert || is_command {
-        let cursor_x = area.x + prefix.len() as u16 + app.cursor_position as u16 + 1;
-        let cursor_y = area.y + 1;
+        let column = before_cursor[before_cursor.rfind('\n').map_or(0, |i| i + 1)..].chars().count();
+        let indent = if row == 0 { prefix.len() } else { 0 };
+        let cursor_x = area.x + (indent + column) as u16 + 1;
+        let cursor_y = area.y + (row - scroll) as u16 + 1;
         frame.set_cursor_position(Position { x: cursor_x, y: cursor_y });
     }
 }
 
+/// The completion popup, just above the query box, lined up with the word
+/// being completed
+fn render_completion(app: &TuiApp, completion: &Completion, frame: &mut Frame, input_area: Rect) {
+    let before = &app.input[..completion.start];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let indent = match (line_start, app.mode) {
+        (0, AppMode::Command) => 1,
+        (0, _) => 2,
+        _ => 0,
+    };
+    let longest = completion.candidates.iter().map(|c| c.chars().count()).max().unwrap_or(0);
+    let area = frame.area();
+    let width = (longest as u16 + 4).min(area.width);
+    let height = (completion.candidates.len().min(POPUP_ROWS) as u16 + 2).min(input_area.y);
+    // The border and padding sit left of the word, so the candidates line up with it
+    let column = (input_area.x + (indent + before[line_start..].chars().count()) as u16).saturating_sub(1);
+    let popup_area = Rect { x: column.min(area.width - width), y: input_area.y - height, width, height };
+
+    let scroll = completion.scroll();
+    let items: Vec<Line> = completion
+        .candidates
+        .iter()
+        .enumerate()
+        .skip(scroll)
+        .take(POPUP_ROWS)
+        .map(|(i, candidate)| {
+            let style = if i == completion.selected {
+                Style::default().fg(app.theme.on_accent).bg(app.theme.accent)
+            } else {
+                Style::default().fg(app.theme.text)
+            };
+            Line::from(Span::styled(format!(" {:<width$} ", candidate, width = longest), style))
+        })
+        .collect();
+
+    let mut block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(app.theme.border))
+        .style(Style::default().bg(app.theme.background));
+    if completion.candidates.len() > POPUP_ROWS {
+        block = block.title_bottom(format!(" {}/{} ", completion.selected + 1, completion.candidates.len()));
+    }
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(Paragraph::new(items).block(block), popup_area);
+}
+
+/// Height of the query box: one row per input line, up to a limit
+fn input_height(app: &TuiApp) -> u16 {
+    let lines = app.input.matches('\n').count() + 1;
+    lines.min(MAX_INPUT_LINES) as u16 + 2
+}
+
 fn render_status_bar(app: &TuiApp, frame: &mut Frame, area: Rect) {
     let mode_style = match app.mode {
-        AppMode::Normal => Style::default().fg(Color::White).bg(Color::Blue),
-        AppMode::Insert => Style::default().fg(Color::White).bg(Color::Green),
-        AppMode::Command => Style::default().fg(Color::Black).bg(Color::Yellow),
+        AppMode::Normal => Style::default().fg(app.theme.badge).bg(app.theme.info),
+        AppMode::Insert => Style::default().fg(app.theme.badge).bg(app.theme.success),
+        AppMode::Command => Style::default().fg(app.theme.on_accent).bg(app.theme.accent),
+        AppMode::Edit => Style::default().fg(app.theme.badge).bg(app.theme.edit),
     };
 
     let mode_text = match app.mode {
         AppMode::Normal => " NORMAL ",
         AppMode::Insert => " INSERT ",
         AppMode::Command => " COMMAND ",
+        AppMode::Edit => " EDIT ",
     };
 
     let screen_indicator = match app.screen {
         AppScreen::ConnectionManager => " 🏠 ",
         AppScreen::DatabaseExplorer => " 📁 ",
+        AppScreen::VectorExplorer => " 🧭 ",
+        AppScreen::CollectionStats => " 📊 ",
+    };
+
+    // While typing a command, the status bar shows how to use it
+    let usage = match app.mode {
+        AppMode::Insert if app.screen != AppScreen::VectorExplorer => completion::usage(&app.input, false),
+        AppMode::Command => completion::usage(&app.input, true),
+        _ => None,
+    };
+
+    // A running command replaces the status message until it finishes
+    let status = match (&app.task, usage) {
+        (Some(task), _) => {
+            let elapsed = task.elapsed();
+            let spinner = SPINNER[(elapsed.as_millis() / 100) as usize % SPINNER.len()];
+            let progress = task.progress.as_deref().map(|p| format!(" · {}", p)).unwrap_or_default();
+            Span::styled(
+                format!("{} {} ({:.1}s){} | [Esc] Cancel", spinner, task.label, elapsed.as_secs_f64(), progress),
+                Style::default().fg(app.theme.accent),
+            )
+        }
+        (None, Some(usage)) => Span::styled(format!("Usage: {}", usage), Style::default().fg(app.theme.title)),
+        (None, None) => Span::styled(app.status_message.as_str(), Style::default().fg(app.theme.border)),
     };
 
     let status_line = Line::from(vec![
         Span::styled(mode_text, mode_style.add_modifier(Modifier::BOLD)),
-        Span::styled(screen_indicator, Style::default().fg(Color::Cyan)),
+        Span::styled(screen_indicator, Style::default().fg(app.theme.title)),
         Span::raw("│ "),
-        Span::styled(&app.status_message, Style::default().fg(Color::Gray)),
+        status,
     ]);
 
     let status_bar = Paragraph::new(status_line)
-        .style(Style::default().bg(Color::DarkGray));
+        .style(Style::default().bg(app.theme.status));
 
     frame.render_widget(status_bar, area);
 }
@@ -369,7 +952,7 @@ fn render_help_popup(app: &TuiApp, frame: &mut Frame, area: Rect) {
             if line.contains("═") || line.starts_with("CONNECTION") || 
                line.starts_with("DOCUMENTS") || line.starts_with("VECTORS") ||
                line.starts_with("NAVIGATION") || line.starts_with("HOME") {
-                Line::from(Span::styled(line, Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)))
+                Line::from(Span::styled(line, Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD)))
             } else {
                 Line::from(Span::raw(line))
             }
@@ -379,10 +962,10 @@ fn render_help_popup(app: &TuiApp, frame: &mut Frame, area: Rect) {
     let help = Paragraph::new(help_text)
         .wrap(Wrap { trim: false })
         .block(Block::default()
-            .title(" Help (? to close) ")
+            .title(format!(" Help ({} to close) ", app.keymap.keys(Action::Help)))
             .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::Cyan))
-            .style(Style::default().bg(Color::Black)));
+            .border_style(Style::default().fg(app.theme.title))
+            .style(Style::default().bg(app.theme.background)));
 
     frame.render_widget(help, popup_area);
 }
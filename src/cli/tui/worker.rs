@@ -0,0 +1,169 @@
+//! Running database work off the TUI's event loop
+//!
+//! A [`Task`] runs one job on its own thread and reports back over a
+//! channel, so the interface keeps drawing and reading keys while a large
+//! find, a vector search or an import is under way. Jobs report progress
+//! through their [`TaskContext`] and check it for cancellation: an import
+//! stops at its next record, while a single query that has already started
+//! runs to completion in the background and its result is dropped.
+
+use crate::stats::DatabaseStats;
+use crate::vector::{Projection, VectorSearchResult};
+use crate::Database;
+use anyhow::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// What a finished job hands back to the app
+#[derive(Debug)]
+pub enum Outcome {
+    /// Text for the results pane
+    Output(CommandOutput),
+    /// Hits for the vector explorer
+    VectorResults { collection: String, results: Vec<VectorSearchResult> },
+    /// A 2D projection for the vector explorer's plot
+    Projection { collection: String, projection: Box<Projection> },
+    /// Storage statistics for the stats screen
+    Stats(Box<DatabaseStats>),
+    /// A watched find, run again after the data changed
+    Refresh(Box<CommandOutput>),
+}
+
+/// The result of a shell command
+#[derive(Debug, Default)]
+pub struct CommandOutput {
+    pub text: String,
+    /// A value shown under the text as foldable JSON
+    pub json: Option<serde_json::Value>,
+    /// Document the command found or wrote, as (collection, id)
+    pub document: Option<(String, String)>,
+    /// Document the command deleted
+    pub deleted: Option<(String, String)>,
+    /// Documents the command returned, for exporting
+    pub documents: Option<Vec<serde_json::Value>>,
+    /// Whether documents were added or removed
+    pub modified: bool,
+}
+
+impl CommandOutput {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self { text: text.into(), ..Default::default() }
+    }
+}
+
+enum TaskEvent {
+    Progress(String),
+    Done(Result<Outcome>),
+}
+
+/// Handed to a job for reporting progress and noticing cancellation
+pub struct TaskContext {
+    sender: Sender<TaskEvent>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl TaskContext {
+    /// Replace the progress shown in the status bar
+    pub fn progress(&self, message: impl Into<String>) {
+        let _ = self.sender.send(TaskEvent::Progress(message.into()));
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+}
+
+/// A job running on a worker thread
+pub struct Task {
+    /// What is running, for the status bar
+    pub label: String,
+    /// The job's latest progress report
+    pub progress: Option<String>,
+    started: Instant,
+    receiver: Receiver<TaskEvent>,
+    cancel: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl Task {
+    pub fn spawn<F>(label: impl Into<String>, db: Arc<Database>, job: F) -> Self
+    where
+        F: FnOnce(&Database, &TaskContext) -> Result<Outcome> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let context = TaskContext { sender, cancel: cancel.clone() };
+        let handle = std::thread::spawn(move || {
+            let result = job(&db, &context);
+            let _ = context.sender.send(TaskEvent::Done(result));
+        });
+        Self { label: label.into(), progress: None, started: Instant::now(), receiver, cancel, handle }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Take any events the job has sent; returns its result once it is done
+    pub fn poll(&mut self) -> Option<Result<Outcome>> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(TaskEvent::Progress(message)) => self.progress = Some(message),
+                Ok(TaskEvent::Done(result)) => return Some(result),
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => return Some(Err(anyhow::anyhow!("{} stopped unexpectedly", self.label))),
+            }
+        }
+    }
+
+    /// Ask the job to stop and stop waiting for it
+    ///
+    /// The returned handle lets the caller wait for the thread to end, e.g.
+    /// before the database is closed.
+    pub fn cancel(self) -> JoinHandle<()> {
+        self.cancel.store(true, Ordering::Relaxed);
+        self.handle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn wait(task: &mut Task) -> Result<Outcome> {
+        loop {
+            if let Some(result) = task.poll() {
+                return result;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_task_progress_and_cancel() {
+        let dir = tempdir().unwrap();
+        let db = Arc::new(Database::create(dir.path().join("test.ndb")).unwrap());
+
+        let mut task = Task::spawn("count", db.clone(), |db, ctx| {
+            ctx.progress("counting");
+            Ok(Outcome::Output(CommandOutput::text(db.list_collections().len().to_string())))
+        });
+        match wait(&mut task).unwrap() {
+            Outcome::Output(output) => assert_eq!(output.text, "0"),
+            other => panic!("unexpected outcome {:?}", other),
+        }
+        assert_eq!(task.progress.as_deref(), Some("counting"));
+
+        let task = Task::spawn("loop", db, |_, ctx| {
+            while !ctx.is_cancelled() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            Ok(Outcome::Output(CommandOutput::default()))
+        });
+        task.cancel().join().unwrap();
+    }
+}
@@ -0,0 +1,130 @@
+//! String collations
+//!
+//! A [`Collation`] decides which strings are equal and how they sort. Each
+//! one maps a string to a key, and strings compare as their keys do byte for
+//! byte, so a filter's operands are folded once and each document's values
+//! as they are read.
+//!
+//! A query can name its collation (`collate nocase` in the query language),
+//! and a [column index](crate::Database::create_columns_with_collation) can
+//! carry one, which queries that name none use for its fields.
+
+use crate::vector::FilterCondition;
+use icu_normalizer::properties::CanonicalCombiningClassMapBorrowed;
+use icu_normalizer::DecomposingNormalizerBorrowed;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::fmt;
+
+/// How strings compare
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Collation {
+    /// Byte for byte
+    #[default]
+    Binary,
+    /// ASCII letters without regard to case, like SQLite's `NOCASE`
+    NoCase,
+    /// Without regard to case, accents or compatibility forms: `"Ärger"`
+    /// equals `"ARGER"`, `"Straße"` equals `"STRASSE"` and `"ﬁle"` equals
+    /// `"file"`
+    Unicode,
+}
+
+impl Collation {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "binary" => Some(Collation::Binary),
+            "nocase" | "case_insensitive" => Some(Collation::NoCase),
+            "unicode" => Some(Collation::Unicode),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Collation::Binary => "binary",
+            Collation::NoCase => "nocase",
+            Collation::Unicode => "unicode",
+        }
+    }
+
+    /// The form of `text` compared byte for byte
+    pub fn key(self, text: &str) -> Cow<'_, str> {
+        match self {
+            Collation::Binary => Cow::Borrowed(text),
+            Collation::NoCase if !text.bytes().any(|b| b.is_ascii_uppercase()) => Cow::Borrowed(text),
+            Collation::NoCase => Cow::Owned(text.to_ascii_lowercase()),
+            Collation::Unicode => {
+                let marks = CanonicalCombiningClassMapBorrowed::new();
+                DecomposingNormalizerBorrowed::new_nfkd()
+                    .normalize_iter(text.chars())
+                    .filter(|&c| marks.get_u8(c) == 0)
+                    // Upper-casing first folds `ß` to `ss` and `ς` to `σ`
+                    .flat_map(char::to_uppercase)
+                    .flat_map(char::to_lowercase)
+                    .collect::<String>()
+                    .into()
+            }
+        }
+    }
+
+    /// `value` with every string, including those in arrays, replaced by its key
+    pub fn fold(self, value: &Value) -> Cow<'_, Value> {
+        match value {
+            _ if self == Collation::Binary => Cow::Borrowed(value),
+            Value::String(text) => Cow::Owned(Value::String(self.key(text).into_owned())),
+            Value::Array(items) => Cow::Owned(Value::Array(items.iter().map(|item| self.fold(item).into_owned()).collect())),
+            _ => Cow::Borrowed(value),
+        }
+    }
+
+    /// A condition that matches folded values as `condition` matches under
+    /// this collation
+    pub(crate) fn fold_condition(self, condition: &FilterCondition) -> FilterCondition {
+        let value = |value: &Value| self.fold(value).into_owned();
+        let text = |text: &String| self.key(text).into_owned();
+        match condition {
+            FilterCondition::Eq(v) => FilterCondition::Eq(value(v)),
+            FilterCondition::Ne(v) => FilterCondition::Ne(value(v)),
+            FilterCondition::Gt(v) => FilterCondition::Gt(value(v)),
+            FilterCondition::Gte(v) => FilterCondition::Gte(value(v)),
+            FilterCondition::Lt(v) => FilterCondition::Lt(value(v)),
+            FilterCondition::Lte(v) => FilterCondition::Lte(value(v)),
+            FilterCondition::In(values) => FilterCondition::In(values.iter().map(value).collect()),
+            FilterCondition::NotIn(values) => FilterCondition::NotIn(values.iter().map(value).collect()),
+            FilterCondition::Contains(t) => FilterCondition::Contains(text(t)),
+            FilterCondition::StartsWith(t) => FilterCondition::StartsWith(text(t)),
+            FilterCondition::EndsWith(t) => FilterCondition::EndsWith(text(t)),
+        }
+    }
+}
+
+impl fmt::Display for Collation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_collation_keys() {
+        assert_eq!(Collation::Binary.key("Ärger"), "Ärger");
+        // Only ASCII letters fold without case
+        assert_eq!(Collation::NoCase.key("ÄRGER"), "Ärger");
+        assert_eq!(Collation::Unicode.key("Ärger"), "arger");
+        assert_eq!(Collation::Unicode.key("Straße"), Collation::Unicode.key("STRASSE"));
+        assert_eq!(Collation::Unicode.key("ﬁle"), "file");
+        assert_eq!(Collation::from_name("NOCASE"), Some(Collation::NoCase));
+        assert_eq!(Collation::from_name("klingon"), None);
+
+        let condition = Collation::Unicode.fold_condition(&FilterCondition::In(vec![json!("Émile"), json!(3)]));
+        assert!(condition.matches(Some(&Collation::Unicode.fold(&json!("EMILE")))));
+        assert!(!FilterCondition::StartsWith("é".into()).matches(Some(&json!("Émile"))));
+    }
+}
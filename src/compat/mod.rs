@@ -0,0 +1,11 @@
+//! Compatibility layers for other databases' client protocols
+//!
+//! These let existing drivers and tools talk to a [`Database`](crate::Database)
+//! without a KeraDB-specific client. Currently only the MongoDB wire protocol
+//! is implemented, in [`mongo`] (feature `mongo`); [`mongo_import`] loads
+//! data exported from MongoDB.
+
+pub mod mongo;
+pub mod mongo_import;
+
+pub use mongo::MongoServer;
@@ -0,0 +1,1242 @@
+//! MongoDB wire protocol (feature `mongo`)
+//!
+//! Speaks enough of the MongoDB wire protocol for existing drivers, `mongosh`
+//! and Compass to connect and run basic CRUD against a [`Database`]. Requests
+//! arrive as `OP_MSG`; the legacy `OP_QUERY` is accepted for the initial
+//! handshake only.
+//!
+//! | Command | Notes |
+//! |---------|-------|
+//! | `hello` / `isMaster` | Handshake |
+//! | `ping`, `buildInfo`, `connectionStatus`, `endSessions` | Static replies |
+//! | `listDatabases`, `listCollections` | Every database name maps to the same collections |
+//! | `insert` | Documents without an `_id` get a new ObjectId |
+//! | `find` | `filter`, `sort`, `projection`, `skip`, `limit` |
+//! | `update` | Replacements and `$set`, `$unset`, `$inc`; `multi` and `upsert` |
+//! | `delete`, `count` | |
+//!
+//! Filters support equality on (dotted) field paths and the `$eq`, `$ne`,
+//! `$gt`, `$gte`, `$lt`, `$lte`, `$in` and `$nin` operators, plus `$near` /
+//! `$nearSphere` with a GeoJSON `$geometry` point and `$geoWithin` with a
+//! `$box` over `[lon, lat]` or GeoJSON point fields (see [`crate::geo`]).
+//! `$near` matches come back nearest first unless the command sorts them.
+//! `find` returns every match in its first batch, so no cursor is ever left
+//! open.
+//!
+//! ObjectId `_id`s are stored as their 24-character hex strings and turned back
+//! into ObjectIds on the way out. The listener does not authenticate clients,
+//! so it refuses to run with a config that requires authentication; only bind
+//! it to a trusted interface.
+
+use crate::error::{KeraDBError, Result};
+use crate::geo::{BoundingBox, GeoPoint};
+use crate::server::{error_status, ServerConfig};
+use crate::types::Document as StoredDocument;
+use crate::vector::FilterCondition;
+use crate::Database;
+
+use bson::{doc, oid::ObjectId, Bson, Document};
+use serde_json::{json, Map, Value};
+use std::cmp::Ordering as CmpOrdering;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::Arc;
+
+const OP_REPLY: i32 = 1;
+const OP_QUERY: i32 = 2004;
+const OP_MSG: i32 = 2013;
+
+const HEADER_LEN: usize = 16;
+const CHECKSUM_PRESENT: u32 = 1;
+const MORE_TO_COME: u32 = 1 << 1;
+
+const MAX_BSON_OBJECT_SIZE: i32 = 16 * 1024 * 1024;
+const MAX_MESSAGE_SIZE: i32 = 48_000_000;
+const MAX_WRITE_BATCH_SIZE: i32 = 100_000;
+
+/// Highest wire version advertised in the handshake (MongoDB 6.0)
+const MAX_WIRE_VERSION: i32 = 17;
+const SERVER_VERSION: &str = "6.0.0";
+
+/// Name the database is listed under by `listDatabases`
+const DATABASE_NAME: &str = "keradb";
+
+// Error codes, as defined by the MongoDB server
+const INTERNAL_ERROR: i32 = 1;
+const BAD_VALUE: i32 = 2;
+const CURSOR_NOT_FOUND: i32 = 43;
+const COMMAND_NOT_FOUND: i32 = 59;
+const IMMUTABLE_FIELD: i32 = 66;
+const DUPLICATE_KEY: i32 = 11000;
+
+/// A failed command, reported to the client as `{ok: 0, code, errmsg}`
+#[derive(Debug)]
+struct CommandError {
+    code: i32,
+    message: String,
+}
+
+impl CommandError {
+    fn new(code: i32, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+
+    fn bad_value(message: impl Into<String>) -> Self {
+        Self::new(BAD_VALUE, message)
+    }
+}
+
+impl From<KeraDBError> for CommandError {
+    fn from(err: KeraDBError) -> Self {
+        let code = match &err {
+            KeraDBError::DuplicateKey(_) => DUPLICATE_KEY,
+            _ if error_status(&err) == 400 => BAD_VALUE,
+            _ => INTERNAL_ERROR,
+        };
+        Self::new(code, err.to_string())
+    }
+}
+
+type CommandResult = std::result::Result<Document, CommandError>;
+
+/// MongoDB wire-protocol listener exposing a database
+pub struct MongoServer {
+    db: Arc<Database>,
+    config: ServerConfig,
+    next_request_id: AtomicI32,
+}
+
+impl MongoServer {
+    /// Create a server for `db`
+    ///
+    /// Only `host`, `port` and `sync_interval` of `config` apply. The
+    /// protocol's SCRAM authentication is not implemented, so [`run`](Self::run)
+    /// fails if `config` has tokens or access control.
+    pub fn new(db: Arc<Database>, config: ServerConfig) -> Self {
+        Self { db, config, next_request_id: AtomicI32::new(1) }
+    }
+
+    /// Bind and serve connections until the listener fails
+    ///
+    /// Drivers keep connections open and pipeline commands over them, so each
+    /// connection gets its own thread.
+    pub fn run(&self) -> Result<()> {
+        if self.config.requires_auth() {
+            return Err(KeraDBError::Config(
+                "the MongoDB listener does not authenticate clients, so it cannot require authentication".to_string(),
+            ));
+        }
+        let listener = TcpListener::bind(self.config.addr())?;
+        tracing::warn!("The MongoDB listener does not authenticate clients; bind it to a trusted interface");
+        tracing::info!("MongoDB wire protocol listening on {}", self.config.addr());
+
+        let stop = AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                while !stop.load(Ordering::Relaxed) {
+                    std::thread::sleep(self.config.sync_interval);
+                    if let Err(e) = self.db.sync() {
+                        tracing::error!("Background sync failed: {}", e);
+                    }
+                }
+            });
+
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        scope.spawn(move || self.serve_connection(stream));
+                    }
+                    Err(e) => tracing::warn!("Failed to accept connection: {}", e),
+                }
+            }
+            stop.store(true, Ordering::Relaxed);
+        });
+
+        self.db.sync()
+    }
+
+    fn serve_connection(&self, mut stream: TcpStream) {
+        loop {
+            let message = match read_message(&mut stream) {
+                Ok(Some(message)) => message,
+                Ok(None) => return,
+                Err(e) => {
+                    tracing::debug!("Closing MongoDB connection: {}", e);
+                    return;
+                }
+            };
+            match self.handle_message(&message) {
+                Ok(Some(reply)) => {
+                    if let Err(e) = stream.write_all(&reply) {
+                        tracing::debug!("Failed to send reply: {}", e);
+                        return;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::warn!("Dropping MongoDB connection: {}", e);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Handle one complete wire message, returning the encoded reply
+    ///
+    /// Returns `None` when the client asked for no reply (`moreToCome`).
+    /// Errors are protocol violations after which the connection should be
+    /// closed; command failures are reported inside the reply instead.
+    pub fn handle_message(&self, message: &[u8]) -> Result<Option<Vec<u8>>> {
+        if message.len() < HEADER_LEN {
+            return Err(KeraDBError::InvalidFormat("Truncated message header".to_string()));
+        }
+        let request_id = read_i32(message, 4)?;
+        let op_code = read_i32(message, 12)?;
+        let body = &message[HEADER_LEN..];
+
+        match op_code {
+            OP_MSG => {
+                let (flags, command) = parse_op_msg(body)?;
+                let reply = self.run_command(command);
+                if flags & MORE_TO_COME != 0 {
+                    return Ok(None);
+                }
+                // flagBits, then a single kind-0 section
+                self.encode_reply(request_id, OP_MSG, &[0; 5], &reply).map(Some)
+            }
+            OP_QUERY => {
+                let (namespace, query) = parse_op_query(body)?;
+                let reply = if namespace.ends_with(".$cmd") {
+                    // Legacy drivers may wrap the command as {$query: {...}}
+                    match query.get_document("$query") {
+                        Ok(command) => self.run_command(command.clone()),
+                        Err(_) => self.run_command(query),
+                    }
+                } else {
+                    error_reply(CommandError::bad_value("OP_QUERY is only supported for commands; use OP_MSG"))
+                };
+                // responseFlags, cursorID, startingFrom, numberReturned
+                let mut prefix = [0u8; 20];
+                prefix[16..].copy_from_slice(&1i32.to_le_bytes());
+                self.encode_reply(request_id, OP_REPLY, &prefix, &reply).map(Some)
+            }
+            op => Err(KeraDBError::InvalidFormat(format!("Unsupported opcode {}", op))),
+        }
+    }
+
+    fn encode_reply(&self, response_to: i32, op_code: i32, prefix: &[u8], reply: &Document) -> Result<Vec<u8>> {
+        let mut body = Vec::new();
+        reply.to_writer(&mut body).map_err(|e| KeraDBError::Serialization(e.to_string()))?;
+
+        let len = HEADER_LEN + prefix.len() + body.len();
+        let mut out = Vec::with_capacity(len);
+        out.extend_from_slice(&(len as i32).to_le_bytes());
+        out.extend_from_slice(&self.next_request_id.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+        out.extend_from_slice(&response_to.to_le_bytes());
+        out.extend_from_slice(&op_code.to_le_bytes());
+        out.extend_from_slice(prefix);
+        out.extend_from_slice(&body);
+        Ok(out)
+    }
+
+    /// Run a command document and build its reply
+    fn run_command(&self, command: Document) -> Document {
+        let Some(name) = command.keys().next().cloned() else {
+            return error_reply(CommandError::bad_value("Empty command"));
+        };
+
+        let result = match name.as_str() {
+            "hello" | "isMaster" | "ismaster" => Ok(hello(&name)),
+            "ping" | "endSessions" => Ok(Document::new()),
+            "buildInfo" | "buildinfo" => Ok(doc! {
+                "version": SERVER_VERSION,
+                "versionArray": [6, 0, 0, 0],
+                "gitVersion": "",
+                "maxBsonObjectSize": MAX_BSON_OBJECT_SIZE,
+            }),
+            "connectionStatus" => Ok(doc! {
+                "authInfo": { "authenticatedUsers": [], "authenticatedUserRoles": [] },
+            }),
+            "listDatabases" => Ok(self.list_databases()),
+            "listCollections" => self.list_collections(&command),
+            "insert" => self.insert(&command),
+            "find" => self.find(&command),
+            "getMore" => Err(CommandError::new(CURSOR_NOT_FOUND, "Cursor not found")),
+            "killCursors" => Ok(doc! {
+                "cursorsKilled": [],
+                "cursorsNotFound": command.get_array("cursors").cloned().unwrap_or_default(),
+                "cursorsAlive": [],
+                "cursorsUnknown": [],
+            }),
+            "update" => self.update(&command),
+            "delete" => self.delete(&command),
+            "count" => self.count(&command),
+            _ => Err(CommandError::new(COMMAND_NOT_FOUND, format!("no such command: '{}'", name))),
+        };
+
+        match result {
+            Ok(mut reply) => {
+                reply.insert("ok", 1.0);
+                reply
+            }
+            Err(e) => error_reply(e),
+        }
+    }
+
+    fn list_databases(&self) -> Document {
+        let empty = self.db.list_collections().is_empty();
+        doc! {
+            "databases": [{ "name": DATABASE_NAME, "sizeOnDisk": 0i64, "empty": empty }],
+            "totalSize": 0i64,
+        }
+    }
+
+    fn list_collections(&self, command: &Document) -> CommandResult {
+        let filter = Filter::parse(optional_document(command, "filter")?)?;
+        let batch = self
+            .db
+            .list_collections()
+            .into_iter()
+            .filter(|(name, _)| filter.matches(&json!({ "name": name, "type": "collection" })))
+            .map(|(name, _)| {
+                Bson::Document(doc! {
+                    "name": name,
+                    "type": "collection",
+                    "options": {},
+                    "info": { "readOnly": false },
+                })
+            })
+            .collect();
+        Ok(cursor_reply(format!("{}.$cmd.listCollections", database_name(command)), batch))
+    }
+
+    fn insert(&self, command: &Document) -> CommandResult {
+        let collection = collection_name(command, "insert")?;
+        let documents = required_array(command, "documents")?;
+
+        let mut n = 0;
+        let mut write_errors = Vec::new();
+        for (index, document) in documents.iter().enumerate() {
+            let result = match document {
+                Bson::Document(document) => {
+                    let mut document = document.clone();
+                    if !document.contains_key("_id") {
+                        document.insert("_id", ObjectId::new());
+                    }
+                    self.db.insert(collection, to_json(Bson::Document(document))).map_err(CommandError::from)
+                }
+                _ => Err(CommandError::bad_value("Documents to insert must be objects")),
+            };
+            match result {
+                Ok(_) => n += 1,
+                Err(e) => {
+                    write_errors.push(write_error(index, e));
+                    if ordered(command) {
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(write_reply(doc! { "n": n }, write_errors))
+    }
+
+    fn find(&self, command: &Document) -> CommandResult {
+        let collection = collection_name(command, "find")?;
+        let filter = Filter::parse(optional_document(command, "filter")?)?;
+        let skip = optional_int(command, "skip")?.unwrap_or(0).max(0) as usize;
+        // A negative limit is the legacy "single batch" spelling of the same limit
+        let limit = match optional_int(command, "limit")?.unwrap_or(0).unsigned_abs() {
+            0 => usize::MAX,
+            n => n as usize,
+        };
+
+        let mut docs: Vec<Value> = self.matching(collection, &filter)?.iter().map(|d| d.to_value()).collect();
+        if let Some(sort) = optional_document(command, "sort")? {
+            sort_documents(&mut docs, sort);
+        } else if let Some(near) = &filter.near {
+            docs.sort_by(|a, b| near.distance(a).total_cmp(&near.distance(b)));
+        }
+
+        let projection = optional_document(command, "projection")?;
+        let batch = docs
+            .into_iter()
+            .skip(skip)
+            .take(limit)
+            .map(|d| {
+                let document = to_document(d);
+                Bson::Document(match projection {
+                    Some(projection) => project(document, projection),
+                    None => document,
+                })
+            })
+            .collect();
+        Ok(cursor_reply(format!("{}.{}", database_name(command), collection), batch))
+    }
+
+    fn update(&self, command: &Document) -> CommandResult {
+        let collection = collection_name(command, "update")?;
+        let updates = required_array(command, "updates")?;
+
+        let (mut matched, mut modified) = (0, 0);
+        let mut upserted = Vec::new();
+        let mut write_errors = Vec::new();
+        for (index, statement) in updates.iter().enumerate() {
+            match self.apply_update(collection, statement) {
+                Ok(outcome) => {
+                    matched += outcome.matched;
+                    modified += outcome.modified;
+                    if let Some(id) = outcome.upserted {
+                        upserted.push(Bson::Document(doc! { "index": index as i32, "_id": id }));
+                    }
+                }
+                Err(e) => {
+                    write_errors.push(write_error(index, e));
+                    if ordered(command) {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut reply = doc! { "n": matched + upserted.len() as i32, "nModified": modified };
+        if !upserted.is_empty() {
+            reply.insert("upserted", upserted);
+        }
+        Ok(write_reply(reply, write_errors))
+    }
+
+    fn apply_update(&self, collection: &str, statement: &Bson) -> std::result::Result<UpdateOutcome, CommandError> {
+        let statement = statement
+            .as_document()
+            .ok_or_else(|| CommandError::bad_value("Update statements must be objects"))?;
+        let filter = Filter::parse(optional_document(statement, "q")?)?;
+        let update = match statement.get("u") {
+            Some(Bson::Document(u)) => Update::parse(u)?,
+            Some(Bson::Array(_)) => return Err(CommandError::bad_value("Pipeline-style updates are not supported")),
+            _ => return Err(CommandError::bad_value("Update statements require a 'u' object")),
+        };
+        let multi = statement.get_bool("multi").unwrap_or(false);
+        let upsert = statement.get_bool("upsert").unwrap_or(false);
+        if multi && matches!(update, Update::Replace(_)) {
+            return Err(CommandError::bad_value("multi update is not supported for replacement-style update"));
+        }
+
+        let mut targets = self.matching(collection, &filter)?;
+        if !multi {
+            targets.truncate(1);
+        }
+
+        let mut outcome = UpdateOutcome { matched: targets.len() as i32, ..Default::default() };
+        for target in targets {
+            let current = match target.data {
+                Value::Object(map) => map,
+                _ => Map::new(),
+            };
+            let next = update.apply(current.clone())?;
+            let next = without_id(next, &target.id)?;
+            if next != current {
+                self.db.update(collection, &target.id, Value::Object(next))?;
+                outcome.modified += 1;
+            }
+        }
+
+        if outcome.matched == 0 && upsert {
+            // Seed the new document with the filter's top-level equality conditions
+            let mut seed = Map::new();
+            for (field, condition) in &filter.conditions {
+                if let FilterCondition::Eq(value) = condition {
+                    if !field.contains('.') {
+                        seed.insert(field.clone(), value.clone());
+                    }
+                }
+            }
+            let mut document = update.apply(seed)?;
+            let id = match document.get("_id") {
+                Some(Value::String(id)) => id.clone(),
+                Some(_) => return Err(CommandError::bad_value("_id must be a string or ObjectId")),
+                None => {
+                    let id = ObjectId::new().to_hex();
+                    document.insert("_id".to_string(), Value::String(id.clone()));
+                    id
+                }
+            };
+            self.db.insert(collection, Value::Object(document))?;
+            outcome.upserted = Some(stored_id(&id));
+        }
+
+        Ok(outcome)
+    }
+
+    fn delete(&self, command: &Document) -> CommandResult {
+        let collection = collection_name(command, "delete")?;
+        let deletes = required_array(command, "deletes")?;
+
+        let mut n = 0;
+        let mut write_errors = Vec::new();
+        for (index, statement) in deletes.iter().enumerate() {
+            match self.apply_delete(collection, statement) {
+                Ok(deleted) => n += deleted,
+                Err(e) => {
+                    write_errors.push(write_error(index, e));
+                    if ordered(command) {
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(write_reply(doc! { "n": n }, write_errors))
+    }
+
+    fn apply_delete(&self, collection: &str, statement: &Bson) -> std::result::Result<i32, CommandError> {
+        let statement = statement
+            .as_document()
+            .ok_or_else(|| CommandError::bad_value("Delete statements must be objects"))?;
+        let filter = Filter::parse(optional_document(statement, "q")?)?;
+        let mut targets = self.matching(collection, &filter)?;
+        // `limit` is 0 (delete all matches) or 1 (delete one)
+        if optional_int(statement, "limit")?.unwrap_or(0) == 1 {
+            targets.truncate(1);
+        }
+        for target in &targets {
+            self.db.delete(collection, &target.id)?;
+        }
+        Ok(targets.len() as i32)
+    }
+
+    fn count(&self, command: &Document) -> CommandResult {
+        let collection = collection_name(command, "count")?;
+        let filter = Filter::parse(optional_document(command, "query")?)?;
+        let total = if filter.conditions.is_empty() {
+            self.db.count(collection)
+        } else {
+            self.matching(collection, &filter)?.len()
+        };
+        let skip = optional_int(command, "skip")?.unwrap_or(0).max(0) as usize;
+        let mut n = total.saturating_sub(skip);
+        if let Some(limit) = optional_int(command, "limit")?.filter(|&l| l != 0) {
+            n = n.min(limit.unsigned_abs() as usize);
+        }
+        Ok(doc! { "n": n as i64 })
+    }
+
+    /// Every document in `collection` matching `filter`
+    fn matching(&self, collection: &str, filter: &Filter) -> std::result::Result<Vec<StoredDocument>, CommandError> {
+        Ok(self
+            .db
+            .find_all(collection, None, None)?
+            .into_iter()
+            .filter(|d| filter.matches(&d.to_value()))
+            .collect())
+    }
+}
+
+#[derive(Default)]
+struct UpdateOutcome {
+    matched: i32,
+    modified: i32,
+    upserted: Option<Bson>,
+}
+
+fn hello(name: &str) -> Document {
+    let mut reply = doc! {
+        "helloOk": true,
+        "maxBsonObjectSize": MAX_BSON_OBJECT_SIZE,
+        "maxMessageSizeBytes": MAX_MESSAGE_SIZE,
+        "maxWriteBatchSize": MAX_WRITE_BATCH_SIZE,
+        "localTime": bson::DateTime::now(),
+        "logicalSessionTimeoutMinutes": 30,
+        "minWireVersion": 0,
+        "maxWireVersion": MAX_WIRE_VERSION,
+        "readOnly": false,
+    };
+    reply.insert(if name == "hello" { "isWritablePrimary" } else { "ismaster" }, true);
+    reply
+}
+
+fn error_reply(err: CommandError) -> Document {
+    doc! { "ok": 0.0, "errmsg": err.message, "code": err.code }
+}
+
+fn write_error(index: usize, err: CommandError) -> Bson {
+    Bson::Document(doc! { "index": index as i32, "code": err.code, "errmsg": err.message })
+}
+
+fn write_reply(mut reply: Document, write_errors: Vec<Bson>) -> Document {
+    if !write_errors.is_empty() {
+        reply.insert("writeErrors", write_errors);
+    }
+    reply
+}
+
+fn cursor_reply(namespace: String, batch: Vec<Bson>) -> Document {
+    doc! { "cursor": { "id": 0i64, "ns": namespace, "firstBatch": batch } }
+}
+
+fn database_name(command: &Document) -> &str {
+    command.get_str("$db").unwrap_or(DATABASE_NAME)
+}
+
+fn collection_name<'a>(command: &'a Document, key: &str) -> std::result::Result<&'a str, CommandError> {
+    command
+        .get_str(key)
+        .map_err(|_| CommandError::bad_value(format!("'{}' must name a collection", key)))
+}
+
+fn ordered(command: &Document) -> bool {
+    command.get_bool("ordered").unwrap_or(true)
+}
+
+fn required_array<'a>(command: &'a Document, key: &str) -> std::result::Result<&'a Vec<Bson>, CommandError> {
+    command
+        .get_array(key)
+        .map_err(|_| CommandError::bad_value(format!("'{}' must be an array", key)))
+}
+
+fn optional_document<'a>(command: &'a Document, key: &str) -> std::result::Result<Option<&'a Document>, CommandError> {
+    match command.get(key) {
+        None | Some(Bson::Null) => Ok(None),
+        Some(Bson::Document(d)) => Ok(Some(d)),
+        Some(_) => Err(CommandError::bad_value(format!("'{}' must be an object", key))),
+    }
+}
+
+/// Read an integer option, which drivers may send as any numeric type
+fn optional_int(command: &Document, key: &str) -> std::result::Result<Option<i64>, CommandError> {
+    match command.get(key) {
+        None | Some(Bson::Null) => Ok(None),
+        Some(Bson::Int32(n)) => Ok(Some(i64::from(*n))),
+        Some(Bson::Int64(n)) => Ok(Some(*n)),
+        Some(Bson::Double(n)) if n.fract() == 0.0 => Ok(Some(*n as i64)),
+        Some(_) => Err(CommandError::bad_value(format!("'{}' must be an integer", key))),
+    }
+}
+
+/// Strip `_id` from an updated document, rejecting attempts to change it
+fn without_id(mut document: Map<String, Value>, id: &str) -> std::result::Result<Map<String, Value>, CommandError> {
+    match document.remove("_id") {
+        Some(Value::String(new_id)) if new_id != id => {
+            Err(CommandError::new(IMMUTABLE_FIELD, "Performing an update would modify the immutable field '_id'"))
+        }
+        Some(Value::String(_)) | None => Ok(document),
+        Some(_) => Err(CommandError::new(IMMUTABLE_FIELD, "Performing an update would modify the immutable field '_id'")),
+    }
+}
+
+/// A query filter compiled to per-field conditions
+struct Filter {
+    conditions: Vec<(String, FilterCondition)>,
+    near: Option<Near>,
+    /// `$geoWithin` boxes
+    within: Vec<(String, BoundingBox)>,
+}
+
+/// A `$near` condition: documents between `min` and `max` meters of `point`
+struct Near {
+    field: String,
+    point: GeoPoint,
+    min: f64,
+    max: f64,
+}
+
+impl Near {
+    fn parse(field: &str, operand: &Value) -> std::result::Result<Self, CommandError> {
+        let point = operand
+            .get("$geometry")
+            .and_then(GeoPoint::from_value)
+            .ok_or_else(|| CommandError::bad_value("$near needs a $geometry GeoJSON point; legacy coordinates are not supported"))?;
+        let distance = |key: &str, default: f64| match operand.get(key) {
+            None => Ok(default),
+            Some(value) => value
+                .as_f64()
+                .filter(|d| *d >= 0.0)
+                .ok_or_else(|| CommandError::bad_value(format!("{} must be a non-negative number", key))),
+        };
+        Ok(Self {
+            field: field.to_string(),
+            point,
+            min: distance("$minDistance", 0.0)?,
+            max: distance("$maxDistance", f64::INFINITY)?,
+        })
+    }
+
+    /// Meters from the query point to the document's location, if it has one
+    fn distance(&self, document: &Value) -> f64 {
+        lookup(document, &self.field)
+            .and_then(GeoPoint::from_value)
+            .map_or(f64::INFINITY, |location| self.point.distance(&location))
+    }
+}
+
+fn parse_box(operand: &Value) -> std::result::Result<BoundingBox, CommandError> {
+    let corners = operand.get("$box").and_then(Value::as_array).map(|corners| corners.as_slice());
+    match corners {
+        Some([min, max]) => match (GeoPoint::from_value(min), GeoPoint::from_value(max)) {
+            (Some(min), Some(max)) => Ok(BoundingBox::new(min, max)),
+            _ => Err(CommandError::bad_value("$box corners must be [lon, lat] pairs")),
+        },
+        _ => Err(CommandError::bad_value("$geoWithin supports $box: [[lon, lat], [lon, lat]] only")),
+    }
+}
+
+impl Filter {
+    fn parse(filter: Option<&Document>) -> std::result::Result<Self, CommandError> {
+        let mut conditions = Vec::new();
+        let mut near = None;
+        let mut within = Vec::new();
+        for (field, value) in filter.into_iter().flatten() {
+            if field.starts_with('$') {
+                return Err(CommandError::bad_value(format!("Unsupported query operator '{}'", field)));
+            }
+            match value {
+                Bson::Document(operators) if operators.keys().next().is_some_and(|k| k.starts_with('$')) => {
+                    for (operator, operand) in operators {
+                        let operand = to_json(operand.clone());
+                        let condition = match operator.as_str() {
+                            "$near" | "$nearSphere" if near.is_some() => {
+                                return Err(CommandError::bad_value("Too many geoNear expressions"))
+                            }
+                            "$near" | "$nearSphere" => {
+                                near = Some(Near::parse(field, &operand)?);
+                                continue;
+                            }
+                            "$geoWithin" => {
+                                within.push((field.clone(), parse_box(&operand)?));
+                                continue;
+                            }
+                            "$eq" => FilterCondition::Eq(operand),
+                            "$ne" => FilterCondition::Ne(operand),
+                            "$gt" => FilterCondition::Gt(operand),
+                            "$gte" => FilterCondition::Gte(operand),
+                            "$lt" => FilterCondition::Lt(operand),
+                            "$lte" => FilterCondition::Lte(operand),
+                            "$in" => FilterCondition::In(array_operand(operator, operand)?),
+                            "$nin" => FilterCondition::NotIn(array_operand(operator, operand)?),
+                            _ => {
+                                return Err(CommandError::bad_value(format!("Unsupported query operator '{}'", operator)))
+                            }
+                        };
+                        conditions.push((field.clone(), condition));
+                    }
+                }
+                _ => conditions.push((field.clone(), FilterCondition::Eq(to_json(value.clone())))),
+            }
+        }
+        Ok(Self { conditions, near, within })
+    }
+
+    fn matches(&self, document: &Value) -> bool {
+        let located = |field: &str| lookup(document, field).and_then(GeoPoint::from_value);
+        if let Some(near) = &self.near {
+            if !(near.min..=near.max).contains(&near.distance(document)) {
+                return false;
+            }
+        }
+        if !self.within.iter().all(|(field, bbox)| located(field).is_some_and(|point| bbox.contains(&point))) {
+            return false;
+        }
+        self.conditions.iter().all(|(field, condition)| {
+            let value = lookup(document, field);
+            // Missing fields compare as null, unlike vector metadata filters
+            match (condition, value) {
+                (FilterCondition::Eq(Value::Null), None) => true,
+                (FilterCondition::Ne(expected), None) => !expected.is_null(),
+                (FilterCondition::NotIn(values), None) => !values.contains(&Value::Null),
+                _ => condition.matches(value),
+            }
+        })
+    }
+}
+
+fn array_operand(operator: &str, operand: Value) -> std::result::Result<Vec<Value>, CommandError> {
+    match operand {
+        Value::Array(values) => Ok(values),
+        _ => Err(CommandError::bad_value(format!("{} needs an array", operator))),
+    }
+}
+
+/// Follow a dotted field path
+fn lookup<'a>(document: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(document, |value, key| value.get(key))
+}
+
+/// An `update` statement's modification
+enum Update {
+    Replace(Map<String, Value>),
+    /// `(operator, field, operand)` in statement order
+    Operators(Vec<(String, String, Value)>),
+}
+
+impl Update {
+    fn parse(update: &Document) -> std::result::Result<Self, CommandError> {
+        if !update.keys().any(|k| k.starts_with('$')) {
+            return match to_json(Bson::Document(update.clone())) {
+                Value::Object(map) => Ok(Update::Replace(map)),
+                _ => unreachable!("documents convert to JSON objects"),
+            };
+        }
+
+        let mut operations = Vec::new();
+        for (operator, fields) in update {
+            if !matches!(operator.as_str(), "$set" | "$unset" | "$inc") {
+                return Err(CommandError::bad_value(format!("Unsupported update operator '{}'", operator)));
+            }
+            let fields = fields
+                .as_document()
+                .ok_or_else(|| CommandError::bad_value(format!("Modifier {} requires an object", operator)))?;
+            for (field, operand) in fields {
+                operations.push((operator.clone(), field.clone(), to_json(operand.clone())));
+            }
+        }
+        Ok(Update::Operators(operations))
+    }
+
+    fn apply(&self, mut document: Map<String, Value>) -> std::result::Result<Map<String, Value>, CommandError> {
+        let operations = match self {
+            Update::Replace(replacement) => return Ok(replacement.clone()),
+            Update::Operators(operations) => operations,
+        };
+
+        for (operator, field, operand) in operations {
+            match operator.as_str() {
+                "$set" => {
+                    let (parent, key) = parent_mut(&mut document, field)?;
+                    parent.insert(key.to_string(), operand.clone());
+                }
+                "$unset" => {
+                    if lookup(&Value::Object(document.clone()), field).is_some() {
+                        let (parent, key) = parent_mut(&mut document, field)?;
+                        parent.remove(key);
+                    }
+                }
+                _ => {
+                    let (parent, key) = parent_mut(&mut document, field)?;
+                    let current = parent.get(key).cloned().unwrap_or(json!(0));
+                    let sum = add_numbers(&current, operand)
+                        .ok_or_else(|| CommandError::bad_value(format!("Cannot apply $inc to '{}'", field)))?;
+                    parent.insert(key.to_string(), sum);
+                }
+            }
+        }
+        Ok(document)
+    }
+}
+
+/// Find the object holding the last segment of a dotted path, creating
+/// intermediate objects as needed
+fn parent_mut<'a, 'p>(
+    document: &'a mut Map<String, Value>,
+    path: &'p str,
+) -> std::result::Result<(&'a mut Map<String, Value>, &'p str), CommandError> {
+    match path.split_once('.') {
+        None => Ok((document, path)),
+        Some((head, rest)) => match document.entry(head).or_insert_with(|| Value::Object(Map::new())) {
+            Value::Object(child) => parent_mut(child, rest),
+            _ => Err(CommandError::bad_value(format!("Cannot create field '{}' in non-object '{}'", rest, head))),
+        },
+    }
+}
+
+fn add_numbers(a: &Value, b: &Value) -> Option<Value> {
+    if let (Some(a), Some(b)) = (a.as_i64(), b.as_i64()) {
+        return a.checked_add(b).map(Value::from);
+    }
+    serde_json::Number::from_f64(a.as_f64()? + b.as_f64()?).map(Value::Number)
+}
+
+/// Sort documents by a `{field: 1 | -1}` specification
+fn sort_documents(documents: &mut [Value], sort: &Document) {
+    let keys: Vec<(&str, bool)> = sort
+        .iter()
+        .map(|(field, direction)| {
+            let descending = match direction {
+                Bson::Int32(n) => *n < 0,
+                Bson::Int64(n) => *n < 0,
+                Bson::Double(n) => *n < 0.0,
+                _ => false,
+            };
+            (field.as_str(), descending)
+        })
+        .collect();
+
+    documents.sort_by(|a, b| {
+        for (field, descending) in &keys {
+            let ordering = compare_json(lookup(a, field), lookup(b, field));
+            if ordering != CmpOrdering::Equal {
+                return if *descending { ordering.reverse() } else { ordering };
+            }
+        }
+        CmpOrdering::Equal
+    });
+}
+
+/// Order values the way MongoDB does across types: null, numbers, strings,
+/// objects, arrays, booleans
+fn compare_json(a: Option<&Value>, b: Option<&Value>) -> CmpOrdering {
+    fn rank(value: Option<&Value>) -> u8 {
+        match value {
+            None | Some(Value::Null) => 0,
+            Some(Value::Number(_)) => 1,
+            Some(Value::String(_)) => 2,
+            Some(Value::Object(_)) => 3,
+            Some(Value::Array(_)) => 4,
+            Some(Value::Bool(_)) => 5,
+        }
+    }
+
+    match (a, b) {
+        (Some(Value::Number(x)), Some(Value::Number(y))) => {
+            x.as_f64().partial_cmp(&y.as_f64()).unwrap_or(CmpOrdering::Equal)
+        }
+        (Some(Value::String(x)), Some(Value::String(y))) => x.cmp(y),
+        (Some(Value::Bool(x)), Some(Value::Bool(y))) => x.cmp(y),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+/// Apply an inclusion or exclusion projection to top-level fields
+fn project(document: Document, projection: &Document) -> Document {
+    fn truthy(value: &Bson) -> bool {
+        match value {
+            Bson::Boolean(b) => *b,
+            Bson::Int32(n) => *n != 0,
+            Bson::Int64(n) => *n != 0,
+            Bson::Double(n) => *n != 0.0,
+            _ => true,
+        }
+    }
+
+    let inclusive = projection.iter().any(|(field, value)| field != "_id" && truthy(value));
+    document
+        .into_iter()
+        .filter(|(field, _)| match projection.get(field) {
+            Some(value) => truthy(value),
+            None => field == "_id" || !inclusive,
+        })
+        .collect()
+}
+
+/// Convert BSON to the JSON stored by KeraDB
+///
+/// ObjectIds become hex strings; other non-JSON types use relaxed extended
+/// JSON (e.g. `{"$date": ...}`).
+pub(crate) fn to_json(value: Bson) -> Value {
+    match value {
+        Bson::ObjectId(oid) => Value::String(oid.to_hex()),
+        Bson::Document(document) => Value::Object(document.into_iter().map(|(k, v)| (k, to_json(v))).collect()),
+        Bson::Array(items) => Value::Array(items.into_iter().map(to_json).collect()),
+        other => other.into_relaxed_extjson(),
+    }
+}
+
+/// Convert stored JSON back to BSON
+fn to_bson(value: Value) -> Bson {
+    match value {
+        Value::Null => Bson::Null,
+        Value::Bool(b) => Bson::Boolean(b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => i32::try_from(i).map(Bson::Int32).unwrap_or(Bson::Int64(i)),
+            None => Bson::Double(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        Value::String(s) => Bson::String(s),
+        Value::Array(items) => Bson::Array(items.into_iter().map(to_bson).collect()),
+        Value::Object(map) => {
+            // Extended JSON wrappers such as {"$date": ...} restore their BSON type
+            if map.keys().next().is_some_and(|k| k.starts_with('$')) {
+                if let Ok(bson) = Bson::try_from(map.clone()) {
+                    return bson;
+                }
+            }
+            Bson::Document(map.into_iter().map(|(k, v)| (k, to_bson(v))).collect())
+        }
+    }
+}
+
+/// The BSON form of a stored `_id`: hex strings that came from ObjectIds
+/// become ObjectIds again
+fn stored_id(id: &str) -> Bson {
+    match ObjectId::parse_str(id) {
+        Ok(oid) => Bson::ObjectId(oid),
+        Err(_) => Bson::String(id.to_string()),
+    }
+}
+
+/// Convert a stored document (as returned by `to_value`) to BSON
+fn to_document(value: Value) -> Document {
+    let Value::Object(mut map) = value else {
+        return Document::new();
+    };
+
+    let mut document = Document::new();
+    if let Some(Value::String(id)) = map.remove("_id") {
+        document.insert("_id", stored_id(&id));
+    }
+    for (key, value) in map {
+        document.insert(key, to_bson(value));
+    }
+    document
+}
+
+/// Read one length-prefixed message, or `None` if the peer closed cleanly
+fn read_message(stream: &mut TcpStream) -> Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    match stream.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    let len = i32::from_le_bytes(len);
+    if !(HEADER_LEN as i32..=MAX_MESSAGE_SIZE).contains(&len) {
+        return Err(KeraDBError::InvalidFormat(format!("Invalid message length {}", len)));
+    }
+    let mut message = vec![0u8; len as usize];
+    message[..4].copy_from_slice(&len.to_le_bytes());
+    stream.read_exact(&mut message[4..])?;
+    Ok(Some(message))
+}
+
+fn read_i32(bytes: &[u8], pos: usize) -> Result<i32> {
+    bytes
+        .get(pos..pos + 4)
+        .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| KeraDBError::InvalidFormat("Truncated message".to_string()))
+}
+
+/// Read a BSON document from the start of `bytes`, returning it and its length
+fn read_document(bytes: &[u8]) -> Result<(Document, usize)> {
+    let len = read_i32(bytes, 0)?;
+    if len < 5 || len as usize > bytes.len() {
+        return Err(KeraDBError::InvalidFormat(format!("Invalid BSON document length {}", len)));
+    }
+    let len = len as usize;
+    let document = Document::from_reader(&bytes[..len]).map_err(|e| KeraDBError::InvalidFormat(e.to_string()))?;
+    Ok((document, len))
+}
+
+fn read_cstring(bytes: &[u8]) -> Result<(String, usize)> {
+    let nul = bytes
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or_else(|| KeraDBError::InvalidFormat("Unterminated string".to_string()))?;
+    Ok((String::from_utf8_lossy(&bytes[..nul]).into_owned(), nul + 1))
+}
+
+/// Parse an `OP_MSG` body into its flags and command
+///
+/// Kind-1 document sequences (used by drivers for bulk `documents`,
+/// `updates` and `deletes`) are folded into the command as arrays.
+fn parse_op_msg(body: &[u8]) -> Result<(u32, Document)> {
+    let flags = read_i32(body, 0)? as u32;
+    let end = if flags & CHECKSUM_PRESENT != 0 { body.len().saturating_sub(4) } else { body.len() };
+
+    let mut command = None;
+    let mut sequences = Vec::new();
+    let mut pos = 4;
+    while pos < end {
+        let kind = body[pos];
+        pos += 1;
+        match kind {
+            0 => {
+                let (document, len) = read_document(&body[pos..end])?;
+                command = Some(document);
+                pos += len;
+            }
+            1 => {
+                let size = read_i32(&body[..end], pos)?;
+                if size < 4 || pos + size as usize > end {
+                    return Err(KeraDBError::InvalidFormat(format!("Invalid document sequence size {}", size)));
+                }
+                let section = &body[pos + 4..pos + size as usize];
+                let (identifier, mut offset) = read_cstring(section)?;
+                let mut documents = Vec::new();
+                while offset < section.len() {
+                    let (document, len) = read_document(&section[offset..])?;
+                    documents.push(Bson::Document(document));
+                    offset += len;
+                }
+                sequences.push((identifier, documents));
+                pos += size as usize;
+            }
+            kind => return Err(KeraDBError::InvalidFormat(format!("Unknown OP_MSG section kind {}", kind))),
+        }
+    }
+
+    let mut command = command.ok_or_else(|| KeraDBError::InvalidFormat("OP_MSG has no body section".to_string()))?;
+    for (identifier, documents) in sequences {
+        command.insert(identifier, documents);
+    }
+    Ok((flags, command))
+}
+
+/// Parse an `OP_QUERY` body into its namespace and query document
+fn parse_op_query(body: &[u8]) -> Result<(String, Document)> {
+    let rest = body
+        .get(4..)
+        .ok_or_else(|| KeraDBError::InvalidFormat("Truncated OP_QUERY".to_string()))?;
+    let (namespace, len) = read_cstring(rest)?;
+    // Skip numberToSkip and numberToReturn
+    let query = rest
+        .get(len + 8..)
+        .ok_or_else(|| KeraDBError::InvalidFormat("Truncated OP_QUERY".to_string()))?;
+    let (query, _) = read_document(query)?;
+    Ok((namespace, query))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn server() -> (tempfile::TempDir, MongoServer) {
+        let dir = tempdir().unwrap();
+        let db = Database::create(dir.path().join("test.ndb")).unwrap();
+        (dir, MongoServer::new(Arc::new(db), ServerConfig::new(27017)))
+    }
+
+    fn message(op_code: i32, body: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&((HEADER_LEN + body.len()) as i32).to_le_bytes());
+        out.extend_from_slice(&7i32.to_le_bytes());
+        out.extend_from_slice(&0i32.to_le_bytes());
+        out.extend_from_slice(&op_code.to_le_bytes());
+        out.extend_from_slice(body);
+        out
+    }
+
+    /// Send an OP_MSG with an optional kind-1 sequence and decode the reply body
+    fn command(server: &MongoServer, command: Document, sequence: Option<(&str, Vec<Document>)>) -> Document {
+        let mut body = vec![0, 0, 0, 0, 0];
+        command.to_writer(&mut body).unwrap();
+        if let Some((identifier, documents)) = sequence {
+            let mut section = Vec::new();
+            section.extend_from_slice(identifier.as_bytes());
+            section.push(0);
+            for document in documents {
+                document.to_writer(&mut section).unwrap();
+            }
+            body.push(1);
+            body.extend_from_slice(&((section.len() + 4) as i32).to_le_bytes());
+            body.extend_from_slice(&section);
+        }
+
+        let reply = server.handle_message(&message(OP_MSG, &body)).unwrap().unwrap();
+        assert_eq!(read_i32(&reply, 8).unwrap(), 7);
+        assert_eq!(read_i32(&reply, 12).unwrap(), OP_MSG);
+        read_document(&reply[HEADER_LEN + 5..]).unwrap().0
+    }
+
+    #[test]
+    fn test_op_msg_crud() {
+        let (_dir, server) = server();
+        let oid = ObjectId::new();
+
+        let reply = command(
+            &server,
+            doc! { "insert": "users", "$db": "app" },
+            Some(("documents", vec![
+                doc! { "_id": oid, "name": "Alice", "age": 30 },
+                doc! { "name": "Bob", "age": 25, "address": { "city": "Oslo" } },
+                doc! { "name": "Carol", "age": 35 },
+            ])),
+        );
+        assert_eq!(reply.get_i32("n").unwrap(), 3);
+
+        let reply = command(
+            &server,
+            doc! { "find": "users", "filter": { "age": { "$gte": 30 } }, "sort": { "age": -1 }, "$db": "app" },
+            None,
+        );
+        let cursor = reply.get_document("cursor").unwrap();
+        assert_eq!(cursor.get_str("ns").unwrap(), "app.users");
+        let batch = cursor.get_array("firstBatch").unwrap();
+        let names: Vec<_> = batch.iter().map(|d| d.as_document().unwrap().get_str("name").unwrap()).collect();
+        assert_eq!(names, ["Carol", "Alice"]);
+        assert_eq!(batch[1].as_document().unwrap().get_object_id("_id").unwrap(), oid);
+        assert!(!batch[1].as_document().unwrap().contains_key("_collection"));
+
+        let reply = command(
+            &server,
+            doc! {
+                "update": "users",
+                "updates": [{ "q": { "_id": oid }, "u": { "$set": { "address.city": "Bergen" }, "$inc": { "age": 1 } } }],
+            },
+            None,
+        );
+        assert_eq!(reply.get_i32("n").unwrap(), 1);
+        assert_eq!(reply.get_i32("nModified").unwrap(), 1);
+        let reply = command(&server, doc! { "find": "users", "filter": { "address.city": "Bergen" } }, None);
+        let alice = reply.get_document("cursor").unwrap().get_array("firstBatch").unwrap()[0].as_document().unwrap().clone();
+        assert_eq!(alice.get_i32("age").unwrap(), 31);
+
+        let reply = command(&server, doc! { "delete": "users", "deletes": [{ "q": { "age": { "$lt": 30 } }, "limit": 0 }] }, None);
+        assert_eq!(reply.get_i32("n").unwrap(), 1);
+        let reply = command(&server, doc! { "count": "users" }, None);
+        assert_eq!(reply.get_i64("n").unwrap(), 2);
+
+        let reply = command(&server, doc! { "insert": "users", "documents": [{ "_id": oid }] }, None);
+        let errors = reply.get_array("writeErrors").unwrap();
+        assert_eq!(errors[0].as_document().unwrap().get_i32("code").unwrap(), DUPLICATE_KEY);
+    }
+
+    #[test]
+    fn test_geo_queries() {
+        let (_dir, server) = server();
+        command(
+            &server,
+            doc! { "insert": "places", "documents": [
+                { "name": "Louvre", "at": { "type": "Point", "coordinates": [2.3376, 48.8606] } },
+                { "name": "Notre-Dame", "at": [2.3499, 48.8530] },
+                { "name": "Big Ben", "at": [-0.1246, 51.5007] },
+            ] },
+            None,
+        );
+        let names = |reply: Document| -> Vec<String> {
+            let batch = reply.get_document("cursor").unwrap().get_array("firstBatch").unwrap().clone();
+            batch.iter().map(|d| d.as_document().unwrap().get_str("name").unwrap().to_string()).collect()
+        };
+
+        let near = doc! { "$near": { "$geometry": { "type": "Point", "coordinates": [2.3488, 48.8534] }, "$maxDistance": 5000 } };
+        let reply = command(&server, doc! { "find": "places", "filter": { "at": near } }, None);
+        assert_eq!(names(reply), ["Notre-Dame", "Louvre"]);
+
+        let within = doc! { "$geoWithin": { "$box": [[-1.0, 51.0], [0.0, 52.0]] } };
+        let reply = command(&server, doc! { "find": "places", "filter": { "at": within } }, None);
+        assert_eq!(names(reply), ["Big Ben"]);
+
+        let reply = command(&server, doc! { "find": "places", "filter": { "at": { "$near": [2.3, 48.8] } } }, None);
+        assert_eq!(reply.get_i32("code").unwrap(), BAD_VALUE);
+    }
+
+    #[test]
+    fn test_refuses_to_run_with_auth() {
+        let (_dir, server) = server();
+        let config = ServerConfig::new(0).with_token("secret");
+        let server = MongoServer::new(server.db.clone(), config);
+        assert!(matches!(server.run(), Err(KeraDBError::Config(_))));
+    }
+
+    #[test]
+    fn test_legacy_handshake_and_unknown_command() {
+        let (_dir, server) = server();
+
+        let mut body = vec![0, 0, 0, 0];
+        body.extend_from_slice(b"admin.$cmd\0");
+        body.extend_from_slice(&0i32.to_le_bytes());
+        body.extend_from_slice(&(-1i32).to_le_bytes());
+        doc! { "isMaster": 1 }.to_writer(&mut body).unwrap();
+
+        let reply = server.handle_message(&message(OP_QUERY, &body)).unwrap().unwrap();
+        assert_eq!(read_i32(&reply, 12).unwrap(), OP_REPLY);
+        assert_eq!(read_i32(&reply, HEADER_LEN + 16).unwrap(), 1);
+        let hello = read_document(&reply[HEADER_LEN + 20..]).unwrap().0;
+        assert!(hello.get_bool("ismaster").unwrap());
+        assert_eq!(hello.get_i32("maxWireVersion").unwrap(), MAX_WIRE_VERSION);
+
+        let reply = command(&server, doc! { "frobnicate": 1 }, None);
+        assert_eq!(reply.get_f64("ok").unwrap(), 0.0);
+        assert_eq!(reply.get_i32("code").unwrap(), COMMAND_NOT_FOUND);
+    }
+}
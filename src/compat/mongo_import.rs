@@ -0,0 +1,214 @@
+//! Importing `mongoexport` and `mongodump` output (feature `mongo`)
+//!
+//! [`Database::import_mongo`] loads data exported from MongoDB, for moving a
+//! small deployment onto an embedded store:
+//!
+//! - **A `mongoexport` file**: Extended JSON (canonical or relaxed), one
+//!   document per line or a `--jsonArray` array. The collection is named
+//!   after the file unless one is given.
+//! - **A `.bson` file** written by `mongodump`, named after its collection.
+//! - **A `mongodump` directory**: every `.bson` file below it, each into the
+//!   collection named by its file stem. `.metadata.json` files and `system.*`
+//!   collections are skipped, and collections of the same name in different
+//!   databases end up in one collection. Gzipped dumps must be unpacked first.
+//!
+//! `_id`s are kept: ObjectIds are stored as their hex strings, just as the
+//! [wire protocol](super::mongo) stores them, so drivers see the same
+//! ObjectIds after the move. Numeric IDs become strings. Other BSON types are
+//! stored as relaxed Extended JSON, e.g. dates as `{"$date": "..."}`.
+
+use super::mongo::to_json;
+use crate::error::{KeraDBError, Result};
+use crate::import::{insert_records, ImportOptions, ImportReport};
+use crate::Database;
+use bson::{Bson, Document};
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// What was loaded into one collection
+#[derive(Debug, Clone, Serialize)]
+pub struct MongoImport {
+    pub collection: String,
+    pub source: PathBuf,
+    #[serde(flatten)]
+    pub report: ImportReport,
+}
+
+/// Import `source` into `db`; see [`Database::import_mongo`]
+pub(crate) fn import(
+    db: &Database,
+    source: &Path,
+    collection: Option<&str>,
+    options: &ImportOptions,
+) -> Result<Vec<MongoImport>> {
+    let files = if source.is_dir() {
+        if collection.is_some() {
+            return Err(KeraDBError::InvalidQuery(
+                "A collection name can only be given when importing a single file".to_string(),
+            ));
+        }
+        let mut files = Vec::new();
+        find_bson_files(source, &mut files)?;
+        if files.is_empty() {
+            return Err(KeraDBError::InvalidFormat(format!("No .bson files found in '{}'", source.display())));
+        }
+        files.sort();
+        files
+    } else {
+        vec![source.to_path_buf()]
+    };
+
+    let mut imports = Vec::new();
+    for path in files {
+        let name = match collection {
+            Some(name) => name.to_string(),
+            None => collection_name(&path)?,
+        };
+        let reader = BufReader::new(fs::File::open(&path)?);
+        let records: Box<dyn Iterator<Item = Result<Value>>> = match extension(&path) {
+            "bson" => Box::new(bson_documents(reader).map(|doc| doc.and_then(to_record))),
+            "gz" => {
+                return Err(KeraDBError::InvalidFormat(format!(
+                    "'{}' is compressed; unpack it or run mongodump without --gzip",
+                    path.display()
+                )))
+            }
+            _ => extended_json(reader)?,
+        };
+        let report = insert_records(db, &name, records, options, &mut |_| {})?;
+        imports.push(MongoImport { collection: name, source: path, report });
+    }
+    Ok(imports)
+}
+
+fn extension(path: &Path) -> &str {
+    path.extension().and_then(|e| e.to_str()).unwrap_or("")
+}
+
+/// The collection a dump file belongs to: `users.bson` → `users`
+fn collection_name(path: &Path) -> Result<String> {
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| KeraDBError::InvalidQuery(format!("Cannot name a collection after '{}'", path.display())))
+}
+
+fn find_bson_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_bson_files(&path, files)?;
+        } else if extension(&path) == "bson"
+            && !path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("system."))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Read the length-prefixed documents of a `.bson` file
+///
+/// A malformed document leaves the reader mid-stream, so nothing after it
+/// is read.
+fn bson_documents<R: BufRead>(mut reader: R) -> impl Iterator<Item = Result<Document>> {
+    let mut failed = false;
+    std::iter::from_fn(move || {
+        if failed {
+            return None;
+        }
+        let result = match reader.fill_buf() {
+            Ok([]) => return None,
+            Ok(_) => Document::from_reader(&mut reader).map_err(|e| KeraDBError::ParseError(e.to_string())),
+            Err(e) => Err(e.into()),
+        };
+        failed = result.is_err();
+        Some(result)
+    })
+}
+
+/// Read `mongoexport` output: one document per line, or a JSON array
+fn extended_json<'a, R: BufRead + 'a>(mut reader: R) -> Result<Box<dyn Iterator<Item = Result<Value>> + 'a>> {
+    let parse = |value: Value| match Bson::try_from(value) {
+        Ok(Bson::Document(doc)) => to_record(doc),
+        Ok(other) => Err(KeraDBError::InvalidDocument(format!("Expected a document, got {}", other))),
+        Err(e) => Err(KeraDBError::ParseError(e.to_string())),
+    };
+
+    let is_array = reader.fill_buf()?.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'[');
+    if is_array {
+        let values: Vec<Value> =
+            serde_json::from_reader(reader).map_err(|e| KeraDBError::ParseError(e.to_string()))?;
+        return Ok(Box::new(values.into_iter().map(parse)));
+    }
+    Ok(Box::new(
+        reader
+            .lines()
+            .filter(|line| !matches!(line, Ok(l) if l.trim().is_empty()))
+            .map(move |line| parse(serde_json::from_str(&line?).map_err(|e| KeraDBError::ParseError(e.to_string()))?)),
+    ))
+}
+
+/// Convert a Mongo document for storage, keeping its `_id`
+fn to_record(doc: Document) -> Result<Value> {
+    let mut value = to_json(Bson::Document(doc));
+    if let Value::Object(map) = &mut value {
+        match map.get("_id") {
+            None | Some(Value::String(_)) => {}
+            Some(Value::Number(n)) => {
+                let id = n.to_string();
+                map.insert("_id".to_string(), Value::String(id));
+            }
+            Some(other) => {
+                return Err(KeraDBError::InvalidDocument(format!("Unsupported _id {}", other)));
+            }
+        }
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::{doc, oid::ObjectId};
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_import_dump_and_export() {
+        let dir = tempdir().unwrap();
+        let db = Database::create(dir.path().join("test.ndb")).unwrap();
+
+        let dump = dir.path().join("dump").join("shop");
+        fs::create_dir_all(&dump).unwrap();
+        let oid = ObjectId::new();
+        let mut bytes = Vec::new();
+        for doc in [doc! {"_id": oid, "qty": 5_i64}, doc! {"_id": 7, "qty": 1}] {
+            doc.to_writer(&mut bytes).unwrap();
+        }
+        fs::write(dump.join("orders.bson"), bytes).unwrap();
+        fs::write(dump.join("orders.metadata.json"), "{}").unwrap();
+
+        let imports = db.import_mongo(dir.path().join("dump"), None, &ImportOptions::default()).unwrap();
+        assert_eq!(imports.len(), 1);
+        assert_eq!((imports[0].collection.as_str(), imports[0].report.imported), ("orders", 2));
+        assert_eq!(db.find_by_id("orders", &oid.to_hex()).unwrap().get("qty"), Some(json!(5)));
+        assert!(db.find_by_id("orders", "7").is_ok());
+
+        let export = dir.path().join("users.json");
+        fs::write(
+            &export,
+            "{\"_id\":{\"$oid\":\"5f1d7f3e9b1e8a3c4d2b1a00\"},\"age\":{\"$numberInt\":\"30\"}}\n\n\
+             {\"_id\":\"bob\",\"joined\":{\"$date\":\"2020-01-02T00:00:00Z\"}}\n",
+        )
+        .unwrap();
+        let imports = db.import_mongo(&export, Some("people"), &ImportOptions::default()).unwrap();
+        assert_eq!((imports[0].collection.as_str(), imports[0].report.imported), ("people", 2));
+        assert_eq!(db.find_by_id("people", "5f1d7f3e9b1e8a3c4d2b1a00").unwrap().get("age"), Some(json!(30)));
+        assert!(db.find_by_id("people", "bob").unwrap().get("joined").unwrap().get("$date").is_some());
+    }
+}
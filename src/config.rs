@@ -0,0 +1,257 @@
+//! Loading [`Config`] from a file and the environment
+//!
+//! Settings are read from a TOML file and then from `KERADB_*` environment
+//! variables, which take precedence. Every setting is optional:
+//!
+//! ```toml
+//! cache_size = 1000          # KERADB_CACHE_SIZE
+//! page_size = 4096           # KERADB_PAGE_SIZE (new databases only)
+//! durability = "full"        # KERADB_DURABILITY: "normal" or "full"
+//! oplog_capacity = 10000     # KERADB_OPLOG_CAPACITY
+//! query_threads = 4          # KERADB_QUERY_THREADS
+//! flush_interval_ms = 1000   # KERADB_FLUSH_INTERVAL_MS: 0 for no background flusher
+//! max_dirty_pages = 1024     # KERADB_MAX_DIRTY_PAGES
+//! max_document_size = 65536  # KERADB_MAX_DOCUMENT_SIZE: bytes, 0 for no limit
+//! direct_io = false          # KERADB_DIRECT_IO: bypass the page cache (Linux)
+//! preallocate = 67108864     # KERADB_PREALLOCATE: bytes reserved at a time, 0 for none (Linux)
+//!
+//! # Defaults for new vector collections
+//! [vector]
+//! distance = "cosine"        # KERADB_VECTOR_DISTANCE
+//! m = 16                     # KERADB_VECTOR_M
+//! ef_construction = 200      # KERADB_VECTOR_EF_CONSTRUCTION
+//! ef_search = 50             # KERADB_VECTOR_EF_SEARCH
+//! compression = "delta"      # KERADB_VECTOR_COMPRESSION: "none", "delta" or "quantized"
+//!
+//! # Calls to the embedding provider
+//! [embedding]
+//! max_concurrency = 8        # KERADB_EMBEDDING_MAX_CONCURRENCY: 0 for no limit
+//! max_retries = 3            # KERADB_EMBEDDING_MAX_RETRIES
+//! backoff_ms = 250           # KERADB_EMBEDDING_BACKOFF_MS: first retry; doubles after
+//! max_backoff_ms = 10000     # KERADB_EMBEDDING_MAX_BACKOFF_MS
+//! failure_threshold = 5      # KERADB_EMBEDDING_FAILURE_THRESHOLD: 0 never opens the circuit
+//! cooldown_ms = 30000        # KERADB_EMBEDDING_COOLDOWN_MS
+//! overflow = "truncate"      # KERADB_EMBEDDING_OVERFLOW: texts over the model's limit; "error", "truncate" or "split"
+//! ```
+//!
+//! The `keradb` CLI loads the file given with `--config`, or `keradb.toml`
+//! in the working directory if there is one.
+
+use crate::error::{KeraDBError, Result};
+use crate::types::{Config, Durability};
+use crate::vector::{CompressionConfig, Distance, TextOverflow};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// File looked for in the working directory when no path is given
+pub const DEFAULT_CONFIG_FILE: &str = "keradb.toml";
+
+/// Every setting, as `table.key`
+const KEYS: &[&str] = &[
+    "page_size",
+    "cache_size",
+    "auto_checkpoint",
+    "durability",
+    "oplog_capacity",
+    "query_threads",
+    "flush_interval_ms",
+    "max_dirty_pages",
+    "max_document_size",
+    "direct_io",
+    "preallocate",
+    "vector.distance",
+    "vector.m",
+    "vector.ef_construction",
+    "vector.ef_search",
+    "vector.compression",
+    "embedding.max_concurrency",
+    "embedding.max_retries",
+    "embedding.backoff_ms",
+    "embedding.max_backoff_ms",
+    "embedding.failure_threshold",
+    "embedding.cooldown_ms",
+    "embedding.overflow",
+];
+
+impl Config {
+    /// Load settings from `path` (or `keradb.toml` if it exists), then from
+    /// the environment, on top of the defaults
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let mut config = match path {
+            Some(path) => Self::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => Self::from_file(DEFAULT_CONFIG_FILE)?,
+            None => Self::default(),
+        };
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    /// Load settings from a TOML file on top of the defaults
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| KeraDBError::Config(format!("Cannot read {}: {}", path.display(), e)))?;
+        let mut config = Self::default();
+        config
+            .apply_toml_text(&text)
+            .map_err(|e| KeraDBError::Config(format!("{}: {}", path.display(), e)))?;
+        Ok(config)
+    }
+
+    /// Override settings from `KERADB_*` environment variables
+    pub fn apply_env(&mut self) -> Result<()> {
+        for key in KEYS {
+            let var = format!("KERADB_{}", key.replace('.', "_").to_uppercase());
+            if let Ok(value) = std::env::var(&var) {
+                self.set(key, &value).map_err(|e| KeraDBError::Config(format!("{}: {}", var, e)))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Override settings from the contents of a TOML file
+    pub fn apply_toml(&mut self, text: &str) -> Result<()> {
+        self.apply_toml_text(text).map_err(KeraDBError::Config)
+    }
+
+    fn apply_toml_text(&mut self, text: &str) -> std::result::Result<(), String> {
+        let document = toml_edit::DocumentMut::from_str(text).map_err(|e| e.to_string())?;
+        self.apply_table(document.as_table(), "")
+    }
+
+    fn apply_table(&mut self, table: &toml_edit::Table, prefix: &str) -> std::result::Result<(), String> {
+        for (name, item) in table.iter() {
+            let key = format!("{}{}", prefix, name);
+            if let Some(table) = item.as_table() {
+                self.apply_table(table, &format!("{}.", key))?;
+                continue;
+            }
+            let value = match item.as_value() {
+                Some(toml_edit::Value::String(s)) => s.value().clone(),
+                Some(toml_edit::Value::Integer(i)) => i.value().to_string(),
+                Some(toml_edit::Value::Boolean(b)) => b.value().to_string(),
+                _ => return Err(format!("Unsupported value for '{}'", key)),
+            };
+            self.set(&key, &value)?;
+        }
+        Ok(())
+    }
+
+    /// Set one setting from its text form
+    fn set(&mut self, key: &str, value: &str) -> std::result::Result<(), String> {
+        fn number(key: &str, value: &str) -> std::result::Result<usize, String> {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| format!("'{}' must be a non-negative integer, got '{}'", key, value))
+        }
+        fn boolean(key: &str, value: &str) -> std::result::Result<bool, String> {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| format!("'{}' must be true or false, got '{}'", key, value))
+        }
+
+        match key {
+            "page_size" => self.page_size = number(key, value)?,
+            "cache_size" => self.cache_size = number(key, value)?,
+            "oplog_capacity" => self.oplog_capacity = number(key, value)?,
+            "query_threads" => self.query_threads = number(key, value)?,
+            "flush_interval_ms" => {
+                self.flush_interval = match number(key, value)? {
+                    0 => None,
+                    ms => Some(Duration::from_millis(ms as u64)),
+                }
+            }
+            "max_dirty_pages" => self.max_dirty_pages = number(key, value)? as u64,
+            "max_document_size" => {
+                self.max_document_size = Some(number(key, value)?).filter(|&size| size > 0)
+            }
+            "direct_io" => self.direct_io = boolean(key, value)?,
+            "preallocate" => self.preallocate = number(key, value)? as u64,
+            "auto_checkpoint" => self.auto_checkpoint = boolean(key, value)?,
+            "durability" => {
+                self.durability = match value.trim().to_lowercase().as_str() {
+                    "normal" => Durability::Normal,
+                    "full" => Durability::Full,
+                    _ => return Err(format!("Unknown durability '{}'; use normal or full", value)),
+                }
+            }
+            "vector.distance" => {
+                self.vector.distance = Distance::from_name(value.trim())
+                    .ok_or_else(|| format!("Unknown distance metric '{}'", value))?
+            }
+            "vector.m" => self.vector.m = number(key, value)?,
+            "vector.ef_construction" => self.vector.ef_construction = number(key, value)?,
+            "vector.ef_search" => self.vector.ef_search = number(key, value)?,
+            "vector.compression" => {
+                self.vector.compression = match value.trim().to_lowercase().as_str() {
+                    "none" => CompressionConfig::none(),
+                    "delta" => CompressionConfig::delta(),
+                    "quantized" => CompressionConfig::quantized(),
+                    _ => return Err(format!("Unknown compression '{}'; use none, delta or quantized", value)),
+                }
+            }
+            "embedding.max_concurrency" => self.embedding.max_concurrency = number(key, value)?,
+            "embedding.max_retries" => self.embedding.max_retries = number(key, value)? as u32,
+            "embedding.backoff_ms" => self.embedding.initial_backoff = Duration::from_millis(number(key, value)? as u64),
+            "embedding.max_backoff_ms" => self.embedding.max_backoff = Duration::from_millis(number(key, value)? as u64),
+            "embedding.failure_threshold" => self.embedding.failure_threshold = number(key, value)? as u32,
+            "embedding.cooldown_ms" => self.embedding.cooldown = Duration::from_millis(number(key, value)? as u64),
+            "embedding.overflow" => {
+                self.embedding.overflow = TextOverflow::from_name(value.trim())
+                    .ok_or_else(|| format!("Unknown overflow '{}'; use error, truncate or split", value))?
+            }
+            _ => return Err(format!("Unknown setting '{}'", key)),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::CompressionMode;
+
+    #[test]
+    fn test_toml_settings() {
+        let mut config = Config::default();
+        config
+            .apply_toml(
+                r#"
+                cache_size = 500
+                query_threads = 2
+                durability = "full"
+                flush_interval_ms = 250
+                direct_io = true
+                preallocate = 1048576
+
+                [vector]
+                distance = "l2"
+                ef_search = 80
+                compression = "quantized"
+
+                [embedding]
+                max_concurrency = 2
+                backoff_ms = 100
+                "#,
+            )
+            .unwrap();
+        assert_eq!((config.cache_size, config.durability), (500, Durability::Full));
+        assert_eq!((config.page_size, config.query_threads), (4096, 2));
+        assert_eq!(config.flush_interval, Some(std::time::Duration::from_millis(250)));
+        assert!(config.direct_io);
+        assert_eq!(config.preallocate, 1 << 20);
+
+        let vector = config.vector_config(3);
+        assert_eq!((vector.dimensions, vector.distance, vector.ef_search), (3, Distance::Euclidean, 80));
+        assert_eq!(vector.compression.mode, CompressionMode::QuantizedDelta);
+        assert_eq!(config.embedding.max_concurrency, 2);
+        assert_eq!(config.embedding.initial_backoff, Duration::from_millis(100));
+
+        let err = config.apply_toml("cache_sise = 1").unwrap_err();
+        assert!(err.to_string().contains("Unknown setting 'cache_sise'"));
+        assert!(config.apply_toml("[vector]\nm = -1").is_err());
+    }
+}
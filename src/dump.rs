@@ -0,0 +1,211 @@
+//! Portable logical dumps
+//!
+//! A dump is an NDJSON file that holds documents and vector collections as
+//! plain JSON rather than pages, so it can be restored by any later KeraDB
+//! version, kept offsite, or inspected with ordinary tools. Each line is an
+//! object tagged with a `type`:
+//!
+//! ```text
+//! {"type":"header","format":"keradb-dump","version":1,"keradb_version":"0.1.0","created_at":"..."}
+//! {"type":"document","collection":"users","document":{"_id":"...","name":"Alice"}}
+//! {"type":"vector_collection","name":"embeddings","config":{"dimensions":3,...}}
+//! {"type":"vector","collection":"embeddings","record":{"vector":[1.0,0.0,0.0],...}}
+//! ```
+//!
+//! Document IDs are preserved. Vectors keep their external IDs and metadata
+//! but get new internal IDs, so restore into an empty database.
+
+use crate::error::{KeraDBError, Result};
+use crate::oplog::upsert_document;
+use crate::vector::{VectorConfig, VectorRecord};
+use crate::Database;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{BufRead, Write};
+
+const DUMP_FORMAT: &str = "keradb-dump";
+const DUMP_VERSION: u32 = 1;
+
+/// Vectors inserted at a time on restore
+const VECTOR_BATCH: usize = 1000;
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum DumpLine {
+    Header {
+        format: String,
+        version: u32,
+        keradb_version: String,
+        created_at: DateTime<Utc>,
+    },
+    Document {
+        collection: String,
+        document: Value,
+    },
+    VectorCollection {
+        name: String,
+        config: VectorConfig,
+    },
+    Vector {
+        collection: String,
+        record: VectorRecord,
+    },
+}
+
+/// What a dump or restore covered
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DumpSummary {
+    pub collections: usize,
+    pub documents: usize,
+    pub vector_collections: usize,
+    pub vectors: usize,
+}
+
+/// Write `db` to `writer`; see [`Database::dump`]
+pub(crate) fn dump<W: Write>(db: &Database, mut writer: W, only: &[String]) -> Result<DumpSummary> {
+    let collections: Vec<String> = db.list_collections().into_iter().map(|(name, _)| name).collect();
+    let vector_collections: Vec<String> = db.list_vector_collections().into_iter().map(|(name, _)| name).collect();
+    if let Some(missing) = only.iter().find(|c| !collections.contains(c) && !vector_collections.contains(c)) {
+        return Err(KeraDBError::CollectionNotFound(missing.clone()));
+    }
+    let selected = |name: &String| only.is_empty() || only.contains(name);
+
+    let mut write = |line: &DumpLine| -> Result<()> {
+        serde_json::to_writer(&mut writer, line)?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    };
+    write(&DumpLine::Header {
+        format: DUMP_FORMAT.to_string(),
+        version: DUMP_VERSION,
+        keradb_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: Utc::now(),
+    })?;
+
+    let mut summary = DumpSummary::default();
+    for collection in collections.iter().filter(|c| selected(c)) {
+        summary.collections += 1;
+        for doc in db.find_all(collection, None, None)? {
+            write(&DumpLine::Document { collection: collection.clone(), document: doc.to_value() })?;
+            summary.documents += 1;
+        }
+    }
+    for name in vector_collections.iter().filter(|c| selected(c)) {
+        let collection = db.vector_collection(name)?;
+        write(&DumpLine::VectorCollection { name: name.clone(), config: collection.config.clone() })?;
+        summary.vector_collections += 1;
+        for record in collection.to_records() {
+            // Internal IDs are reassigned on restore
+            let record = VectorRecord { id: None, ..record };
+            write(&DumpLine::Vector { collection: name.clone(), record })?;
+            summary.vectors += 1;
+        }
+    }
+    writer.flush()?;
+    Ok(summary)
+}
+
+/// Load a dump into `db`; see [`Database::restore_dump`]
+pub(crate) fn restore<R: BufRead>(db: &Database, reader: R) -> Result<DumpSummary> {
+    let mut summary = DumpSummary::default();
+    let mut collections = std::collections::HashSet::new();
+    let mut pending: Option<(String, Vec<VectorRecord>)> = None;
+    let mut seen_header = false;
+
+    let flush = |pending: &mut Option<(String, Vec<VectorRecord>)>| -> Result<usize> {
+        match pending.take() {
+            Some((collection, records)) => db.import_vector_records(&collection, records, None),
+            None => Ok(0),
+        }
+    };
+
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let parsed: DumpLine = serde_json::from_str(&line)
+            .map_err(|e| KeraDBError::ParseError(format!("Dump line {}: {}", i + 1, e)))?;
+
+        match parsed {
+            DumpLine::Header { format, version, .. } => {
+                if format != DUMP_FORMAT || version > DUMP_VERSION {
+                    return Err(KeraDBError::InvalidFormat(format!(
+                        "Unsupported dump {} version {} (this build reads {} up to version {})",
+                        format, version, DUMP_FORMAT, DUMP_VERSION
+                    )));
+                }
+                seen_header = true;
+                continue;
+            }
+            _ if !seen_header => {
+                return Err(KeraDBError::InvalidFormat("Not a KeraDB dump: missing header line".to_string()));
+            }
+            DumpLine::Document { collection, document } => {
+                upsert_document(db, &collection, document)?;
+                summary.documents += 1;
+                if collections.insert(collection) {
+                    summary.collections += 1;
+                }
+            }
+            DumpLine::VectorCollection { name, config } => {
+                summary.vectors += flush(&mut pending)?;
+                if !db.list_vector_collections().iter().any(|(existing, _)| *existing == name) {
+                    db.create_vector_collection(&name, config)?;
+                }
+                summary.vector_collections += 1;
+            }
+            DumpLine::Vector { collection, record } => {
+                if pending.as_ref().is_some_and(|(c, records)| *c != collection || records.len() >= VECTOR_BATCH) {
+                    summary.vectors += flush(&mut pending)?;
+                }
+                pending.get_or_insert_with(|| (collection, Vec::new())).1.push(record);
+            }
+        }
+    }
+    summary.vectors += flush(&mut pending)?;
+
+    if !seen_header {
+        return Err(KeraDBError::InvalidFormat("Not a KeraDB dump: missing header line".to_string()));
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_dump_and_restore() {
+        let dir = tempdir().unwrap();
+        let db = Database::create(dir.path().join("source.ndb")).unwrap();
+        let alice = db.insert("users", json!({"name": "Alice", "tags": ["a"]})).unwrap();
+        db.insert("orders", json!({"total": 12})).unwrap();
+        db.create_vector_collection("embeddings", VectorConfig::new(3)).unwrap();
+        db.insert_vector_with_id("embeddings", "doc-1", vec![1.0, 0.0, 0.0], Some(json!({"lang": "en"}))).unwrap();
+        db.insert_vector("embeddings", vec![0.0, 1.0, 0.0], None).unwrap();
+
+        let mut out = Vec::new();
+        let summary = db.dump(&mut out, &[]).unwrap();
+        assert_eq!((summary.documents, summary.vectors), (2, 2));
+
+        let restored = Database::create(dir.path().join("restored.ndb")).unwrap();
+        let summary = restored.restore_dump(out.as_slice()).unwrap();
+        assert_eq!((summary.collections, summary.documents, summary.vector_collections, summary.vectors), (2, 2, 1, 2));
+        assert_eq!(restored.find_by_id("users", &alice).unwrap().get("tags"), Some(json!(["a"])));
+        let vector = restored.get_vector_by_external_id("embeddings", "doc-1").unwrap().unwrap();
+        assert_eq!(vector.metadata, json!({"lang": "en"}));
+
+        // Only the named collections are dumped
+        let mut out = Vec::new();
+        let summary = db.dump(&mut out, &["orders".to_string()]).unwrap();
+        assert_eq!((summary.collections, summary.documents, summary.vector_collections), (1, 1, 0));
+        assert!(db.dump(Vec::new(), &["nope".to_string()]).is_err());
+
+        let err = restored.restore_dump("{\"type\":\"document\",\"collection\":\"x\",\"document\":{}}".as_bytes());
+        assert!(matches!(err, Err(KeraDBError::InvalidFormat(_))));
+    }
+}
@@ -26,8 +26,14 @@ pub enum KeraDBError {
     #[error("Checksum mismatch: data may be corrupted")]
     ChecksumMismatch,
 
-    #[error("Database is locked by another process")]
-    DatabaseLocked,
+    #[error("Database {0} is locked by another process")]
+    Locked(String),
+
+    #[error("Database is opened read-only")]
+    ReadOnly,
+
+    #[error("Configuration error: {0}")]
+    Config(String),
 
     #[error("Invalid query: {0}")]
     InvalidQuery(String),
@@ -64,6 +70,23 @@ pub enum KeraDBError {
 
     #[error("Embedding error: {0}")]
     EmbeddingError(String),
+
+    /// A rate limit or server, network or circuit breaker failure that may
+    /// pass if the call is retried later
+    #[error("Embedding provider unavailable: {0}")]
+    EmbeddingUnavailable(String),
+
+    #[error("Oplog no longer holds changes after sequence {0}")]
+    OplogTruncated(u64),
+
+    #[error("Document is {size} bytes; the limit is {limit}")]
+    DocumentTooLarge { size: usize, limit: usize },
+
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+
+    #[error("Reference violation: {0}")]
+    ReferenceViolation(String),
 }
 
 pub type Result<T> = std::result::Result<T, KeraDBError>;
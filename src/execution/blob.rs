@@ -0,0 +1,487 @@
+//! Binary attachments stored alongside documents
+//!
+//! A blob is a named byte stream attached to a document, such as an avatar
+//! or a PDF, kept in a chain of [`PageType::Blob`] pages rather than inside
+//! the document's JSON. Blobs are written and read as streams, so they may
+//! be much larger than a page, or than memory.
+//!
+//! Every page of a chain starts with a small header:
+//!
+//! ```text
+//! kind (u8): 1 = head, 2 = continuation
+//! next page (u32, u32::MAX at the end)
+//! chunk length (u32)
+//! head only: generation (u64), total size (u64), key length (u16), key
+//! chunk
+//! ```
+//!
+//! The key is the JSON array `[collection, doc_id, name]`, so the index can
+//! be rebuilt from head pages when the database is opened. A blob is written
+//! head last: until then its pages are unreachable, and a crash leaves the
+//! previous version in place. Replacing a blob bumps its generation, so if
+//! two heads share a key on open, the newer one wins.
+
+use crate::error::{KeraDBError, Result};
+use crate::execution::Executor;
+use crate::storage::pager::Page;
+use crate::types::{DocumentId, PageType};
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const HEAD: u8 = 1;
+const CONTINUATION: u8 = 2;
+const NO_PAGE: u32 = u32::MAX;
+/// Bytes before the chunk on a continuation page
+const CHAIN_HEADER: usize = 9;
+/// Bytes before the key on a head page
+const HEAD_HEADER: usize = CHAIN_HEADER + 18;
+
+/// A blob attached to a document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BlobInfo {
+    pub name: String,
+    /// Length in bytes
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BlobEntry {
+    head: u32,
+    size: u64,
+    generation: u64,
+}
+
+/// Where each blob's chain starts, by document and name
+#[derive(Default)]
+pub(crate) struct BlobIndex {
+    blobs: RwLock<HashMap<(String, DocumentId), BTreeMap<String, BlobEntry>>>,
+    next_generation: AtomicU64,
+}
+
+impl BlobIndex {
+    fn get(&self, collection: &str, doc_id: &str, name: &str) -> Option<BlobEntry> {
+        let key = (collection.to_string(), doc_id.to_string());
+        self.blobs.read().get(&key).and_then(|blobs| blobs.get(name)).copied()
+    }
+
+    /// Record a blob, returning the entry it replaces
+    fn insert(&self, collection: &str, doc_id: &str, name: &str, entry: BlobEntry) -> Option<BlobEntry> {
+        let key = (collection.to_string(), doc_id.to_string());
+        let mut blobs = self.blobs.write();
+        let named = blobs.entry(key).or_default();
+        match named.get(name) {
+            // The blob begun last wins, whichever finishes or is loaded last
+            Some(current) if current.generation > entry.generation => Some(entry),
+            _ => named.insert(name.to_string(), entry),
+        }
+    }
+
+    fn remove(&self, collection: &str, doc_id: &str, name: Option<&str>) -> Vec<BlobEntry> {
+        let key = (collection.to_string(), doc_id.to_string());
+        let mut blobs = self.blobs.write();
+        let Some(named) = blobs.get_mut(&key) else {
+            return Vec::new();
+        };
+        let removed = match name {
+            Some(name) => named.remove(name).into_iter().collect(),
+            None => std::mem::take(named).into_values().collect(),
+        };
+        if named.is_empty() {
+            blobs.remove(&key);
+        }
+        removed
+    }
+
+    fn list(&self, collection: &str, doc_id: &str) -> Vec<BlobInfo> {
+        let key = (collection.to_string(), doc_id.to_string());
+        self.blobs
+            .read()
+            .get(&key)
+            .into_iter()
+            .flatten()
+            .map(|(name, entry)| BlobInfo { name: name.clone(), size: entry.size })
+            .collect()
+    }
+}
+
+fn read_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(data[at..at + 8].try_into().unwrap())
+}
+
+/// Length of the key on a head page
+fn key_len(head: &[u8]) -> usize {
+    u16::from_le_bytes([head[HEAD_HEADER - 2], head[HEAD_HEADER - 1]]) as usize
+}
+
+fn corrupt(page: u32, what: &str) -> KeraDBError {
+    KeraDBError::StorageError(format!("Blob page {} {}", page, what))
+}
+
+fn replaced() -> KeraDBError {
+    KeraDBError::StorageError("Blob was replaced while being read".to_string())
+}
+
+/// The kind, next page and chunk of a blob page
+fn chunk_of(page: &Page, offset: usize) -> Result<(u8, u32, &[u8])> {
+    if page.page_type != PageType::Blob || page.data.len() < offset {
+        return Err(corrupt(page.page_num, "is not part of a blob"));
+    }
+    let len = read_u32(&page.data, 5) as usize;
+    let chunk = page
+        .data
+        .get(offset..offset + len)
+        .ok_or_else(|| corrupt(page.page_num, "has an invalid chunk length"))?;
+    Ok((page.data[0], read_u32(&page.data, 1), chunk))
+}
+
+/// Streams a new blob into pages; see [`Executor::blob_writer`]
+///
+/// Nothing is visible until [`finish`](Self::finish). Dropping the writer
+/// without finishing discards what was written.
+pub struct BlobWriter<'a> {
+    executor: &'a Executor,
+    collection: String,
+    doc_id: DocumentId,
+    name: String,
+    generation: u64,
+    head: u32,
+    /// The head, once later pages are being filled
+    head_data: Option<Vec<u8>>,
+    /// The page being filled, and where its chunk starts
+    page: u32,
+    data: Vec<u8>,
+    offset: usize,
+    /// Every page taken so far, to give back if the blob is discarded
+    pages: Vec<u32>,
+    size: u64,
+    finished: bool,
+}
+
+impl<'a> BlobWriter<'a> {
+    fn new(executor: &'a Executor, collection: &str, doc_id: &str, name: &str) -> Result<Self> {
+        let key = serde_json::to_vec(&(collection, doc_id, name))?;
+        let page_data = executor.page_size() - 5;
+        if key.len() > u16::MAX as usize || HEAD_HEADER + key.len() >= page_data {
+            return Err(KeraDBError::InvalidQuery(format!("Blob name '{}' is too long", name)));
+        }
+
+        // Taken as free pages, so a crash before `finish` leaves no blob
+        let head = executor.allocate_page(PageType::Free)?;
+        let generation = executor.blobs().next_generation.fetch_add(1, Ordering::Relaxed);
+        let mut data = vec![HEAD];
+        data.extend_from_slice(&NO_PAGE.to_le_bytes());
+        data.extend_from_slice(&[0u8; 4]);
+        data.extend_from_slice(&generation.to_le_bytes());
+        data.extend_from_slice(&[0u8; 8]);
+        data.extend_from_slice(&(key.len() as u16).to_le_bytes());
+        data.extend_from_slice(&key);
+        Ok(Self {
+            executor,
+            collection: collection.to_string(),
+            doc_id: doc_id.to_string(),
+            name: name.to_string(),
+            generation,
+            head,
+            head_data: None,
+            page: head,
+            offset: data.len(),
+            data,
+            pages: vec![head],
+            size: 0,
+            finished: false,
+        })
+    }
+
+    /// Move on to a new page once the current one is full
+    fn next_page(&mut self) -> Result<()> {
+        let next = self.executor.allocate_page(PageType::Free)?;
+        self.pages.push(next);
+        self.data[1..5].copy_from_slice(&next.to_le_bytes());
+        let mut data = vec![CONTINUATION];
+        data.extend_from_slice(&NO_PAGE.to_le_bytes());
+        data.extend_from_slice(&[0u8; 4]);
+        let full = std::mem::replace(&mut self.data, data);
+        let offset = std::mem::replace(&mut self.offset, CHAIN_HEADER);
+        let page = std::mem::replace(&mut self.page, next);
+        if page == self.head {
+            self.head_data = Some(full);
+            Ok(())
+        } else {
+            self.write(page, full, offset)
+        }
+    }
+
+    /// Write a filled page, recording its chunk length
+    fn write(&self, page_num: u32, mut data: Vec<u8>, offset: usize) -> Result<()> {
+        let len = (data.len() - offset) as u32;
+        data[5..9].copy_from_slice(&len.to_le_bytes());
+        data.resize(self.executor.page_size() - 5, 0);
+        self.executor.write_page(&Page::new(page_num, PageType::Blob, data))
+    }
+
+    /// Store the blob, replacing any of the same name, and return its size
+    ///
+    /// Fails if the document was deleted while the blob was being written.
+    pub fn finish(mut self) -> Result<u64> {
+        let data = std::mem::take(&mut self.data);
+        let mut head = match self.head_data.take() {
+            Some(head) => {
+                self.write(self.page, data, self.offset)?;
+                head
+            }
+            None => data,
+        };
+        head[CHAIN_HEADER + 8..CHAIN_HEADER + 16].copy_from_slice(&self.size.to_le_bytes());
+        let head_offset = HEAD_HEADER + key_len(&head);
+
+        // Writing the head makes the blob reachable
+        let lock = self.executor.write_lock(&self.collection);
+        let _write = lock.lock();
+        self.check_document()?;
+        self.write(self.head, head, head_offset)?;
+        self.finished = true;
+        self.replace()
+    }
+
+    fn check_document(&self) -> Result<()> {
+        if self.executor.contains(&self.collection, &self.doc_id) {
+            Ok(())
+        } else {
+            Err(KeraDBError::DocumentNotFound(self.doc_id.clone()))
+        }
+    }
+
+    /// Index the written blob and free the one it replaces
+    fn replace(&self) -> Result<u64> {
+        let entry = BlobEntry { head: self.head, size: self.size, generation: self.generation };
+        if let Some(old) = self.executor.blobs().insert(&self.collection, &self.doc_id, &self.name, entry) {
+            self.executor.free_blob(old.head)?;
+        }
+        Ok(self.size)
+    }
+}
+
+impl io::Write for BlobWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let capacity = self.executor.page_size() - 5;
+        if self.data.len() == capacity {
+            self.next_page().map_err(io::Error::other)?;
+        }
+        let n = buf.len().min(capacity - self.data.len());
+        self.data.extend_from_slice(&buf[..n]);
+        self.size += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for BlobWriter<'_> {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        for &page_num in &self.pages {
+            let page = Page::new(page_num, PageType::Free, vec![0u8; self.executor.page_size() - 5]);
+            if let Err(e) = self.executor.write_page(&page) {
+                tracing::warn!("could not free page {} of a discarded blob: {}", page_num, e);
+            }
+        }
+    }
+}
+
+/// Streams a blob out of its pages; see [`Executor::blob_reader`]
+pub struct BlobReader<'a> {
+    executor: &'a Executor,
+    size: u64,
+    next: u32,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl BlobReader<'_> {
+    /// Length of the blob in bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Load the next page's chunk
+    fn next_chunk(&mut self) -> Result<()> {
+        let page = self.executor.read_page(self.next)?;
+        let (kind, next, chunk) = chunk_of(&page, CHAIN_HEADER)
+            .map_err(|_| replaced())?;
+        if kind != CONTINUATION {
+            return Err(corrupt(page.page_num, "is not a continuation"));
+        }
+        self.chunk = chunk.to_vec();
+        self.position = 0;
+        self.next = next;
+        Ok(())
+    }
+}
+
+impl io::Read for BlobReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.chunk.len() {
+            if self.next == NO_PAGE {
+                return Ok(0);
+            }
+            self.next_chunk().map_err(io::Error::other)?;
+        }
+        let n = buf.len().min(self.chunk.len() - self.position);
+        buf[..n].copy_from_slice(&self.chunk[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+impl Executor {
+    /// Start writing a blob attached to a document
+    pub fn blob_writer(&self, collection: &str, doc_id: &str, name: &str) -> Result<BlobWriter<'_>> {
+        if !self.contains(collection, doc_id) {
+            return Err(KeraDBError::DocumentNotFound(doc_id.to_string()));
+        }
+        BlobWriter::new(self, collection, doc_id, name)
+    }
+
+    /// Start reading a blob attached to a document
+    pub fn blob_reader(&self, collection: &str, doc_id: &str, name: &str) -> Result<BlobReader<'_>> {
+        let entry = self
+            .blobs()
+            .get(collection, doc_id, name)
+            .ok_or_else(|| KeraDBError::NotFound(format!("Blob '{}' of {}/{}", name, collection, doc_id)))?;
+        let page = self.read_page(entry.head)?;
+        if page.page_type != PageType::Blob || page.data[0] != HEAD || read_u64(&page.data, CHAIN_HEADER) != entry.generation {
+            return Err(replaced());
+        }
+        let (_, next, chunk) = chunk_of(&page, HEAD_HEADER + key_len(&page.data))?;
+        Ok(BlobReader { executor: self, size: entry.size, next, chunk: chunk.to_vec(), position: 0 })
+    }
+
+    /// Remove a blob, returning whether there was one
+    pub fn delete_blob(&self, collection: &str, doc_id: &str, name: &str) -> Result<bool> {
+        let lock = self.write_lock(collection);
+        let _write = lock.lock();
+        let removed = self.blobs().remove(collection, doc_id, Some(name));
+        for entry in &removed {
+            self.free_blob(entry.head)?;
+        }
+        Ok(!removed.is_empty())
+    }
+
+    /// Remove every blob of a document; the caller holds the collection's
+    /// write lock
+    pub(crate) fn delete_blobs(&self, collection: &str, doc_id: &str) -> Result<()> {
+        for entry in self.blobs().remove(collection, doc_id, None) {
+            self.free_blob(entry.head)?;
+        }
+        Ok(())
+    }
+
+    /// The blobs attached to a document, by name
+    pub fn list_blobs(&self, collection: &str, doc_id: &str) -> Vec<BlobInfo> {
+        self.blobs().list(collection, doc_id)
+    }
+
+    /// Index a blob page found while opening the database
+    pub(crate) fn load_blob_page(&self, page: &Page) -> Result<()> {
+        if page.data.first() != Some(&HEAD) {
+            // Continuation pages are reached from their head
+            return Ok(());
+        }
+        if page.data.len() < HEAD_HEADER {
+            return Err(corrupt(page.page_num, "is too short"));
+        }
+        let key = page
+            .data
+            .get(HEAD_HEADER..HEAD_HEADER + key_len(&page.data))
+            .ok_or_else(|| corrupt(page.page_num, "has an invalid key"))?;
+        let (collection, doc_id, name): (String, String, String) = serde_json::from_slice(key)?;
+        let generation = read_u64(&page.data, CHAIN_HEADER);
+        let size = read_u64(&page.data, CHAIN_HEADER + 8);
+        self.blobs().next_generation.fetch_max(generation + 1, Ordering::Relaxed);
+        self.blobs().insert(&collection, &doc_id, &name, BlobEntry { head: page.page_num, size, generation });
+        Ok(())
+    }
+
+    /// Free the pages of a blob that is no longer indexed, head first so
+    /// the blob is gone even if this is interrupted
+    fn free_blob(&self, head: u32) -> Result<()> {
+        let mut next = head;
+        while next != NO_PAGE {
+            let page = self.read_page(next)?;
+            if page.page_type != PageType::Blob {
+                break;
+            }
+            self.write_page(&Page::new(next, PageType::Free, vec![0u8; self.page_size() - 5]))?;
+            next = read_u32(&page.data, 1);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Database;
+    use serde_json::json;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn test_blob_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blobs.ndb");
+        let db = Database::create(&path).unwrap();
+        let id = db.insert("files", json!({"name": "report"})).unwrap();
+        let large: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+
+        let mut writer = db.blob_writer("files", &id, "report.pdf").unwrap();
+        for chunk in large.chunks(777) {
+            writer.write_all(chunk).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), large.len() as u64);
+        db.put_blob("files", &id, "empty", b"").unwrap();
+        assert!(db.put_blob("files", "nobody", "x", b"x").is_err());
+
+        // Dropped unfinished, so never stored
+        let mut writer = db.blob_writer("files", &id, "partial").unwrap();
+        writer.write_all(&large).unwrap();
+        drop(writer);
+
+        let mut streamed = Vec::new();
+        db.blob_reader("files", &id, "report.pdf").unwrap().read_to_end(&mut streamed).unwrap();
+        assert_eq!(streamed, large);
+        let names: Vec<_> = db.list_blobs("files", &id).into_iter().map(|blob| blob.name).collect();
+        assert_eq!(names, ["empty", "report.pdf"]);
+        db.sync().unwrap();
+        drop(db);
+
+        let db = Database::open(&path).unwrap();
+        assert_eq!(db.get_blob("files", &id, "report.pdf").unwrap(), large);
+        assert_eq!(db.get_blob("files", &id, "empty").unwrap(), b"");
+        assert!(db.get_blob("files", &id, "partial").is_err());
+
+        // Replacing frees the old pages
+        db.put_blob("files", &id, "report.pdf", b"v2").unwrap();
+        assert_eq!(db.get_blob("files", &id, "report.pdf").unwrap(), b"v2");
+        assert_eq!(db.stats().unwrap().pages.blob, 2);
+        db.sync().unwrap();
+        drop(db);
+
+        let db = Database::open(&path).unwrap();
+        assert_eq!(db.get_blob("files", &id, "report.pdf").unwrap(), b"v2");
+        assert!(db.delete_blob("files", &id, "empty").unwrap());
+        assert!(!db.delete_blob("files", &id, "empty").unwrap());
+        db.delete("files", &id).unwrap();
+        assert!(db.list_blobs("files", &id).is_empty());
+        assert_eq!(db.stats().unwrap().pages.blob, 0);
+    }
+}
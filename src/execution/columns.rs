@@ -0,0 +1,414 @@
+//! Column projections of document fields
+//!
+//! A collection's column projection keeps chosen fields of every document,
+//! typically numbers that are often summed or averaged, in one array per
+//! field. The executor updates it on every insert, update and delete, so an
+//! [aggregation pipeline](crate::aggregate) that only reads projected fields
+//! runs over the arrays without reading or deserializing any document.
+//!
+//! Which fields are projected is kept in a catalog page (see
+//! [`compression`](crate::storage::compression)); the arrays themselves live
+//! in memory and are rebuilt from the documents when the database is opened.
+//!
+//! A projection can carry a [`Collation`], which `find` queries naming none
+//! use for string comparisons on its fields; see [`crate::query`].
+
+use crate::aggregate::{Pipeline, Row, PARALLEL_THRESHOLD};
+use crate::collation::Collation;
+use crate::error::{KeraDBError, Result};
+use crate::execution::Executor;
+use crate::execution::index::IndexEntry;
+use crate::storage::pager::Page;
+use crate::types::{DocumentId, PageType};
+use parking_lot::RwLock;
+use serde_json::{Map, Value};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Kind byte of a catalog page listing a collection's projected fields
+const COLUMNS_PAGE: u8 = 2;
+
+/// Each collection's projection, with the catalog page listing its fields
+pub(crate) type Projections = HashMap<String, (u32, Arc<ColumnStore>)>;
+
+/// Projected values, one column per field, one row per document
+#[derive(Default)]
+struct Columns {
+    ids: Vec<Value>,
+    rows: HashMap<DocumentId, usize>,
+    values: Vec<Vec<Option<Value>>>,
+}
+
+/// The column projection of one collection
+pub(crate) struct ColumnStore {
+    fields: Vec<String>,
+    collation: Collation,
+    columns: RwLock<Columns>,
+}
+
+impl ColumnStore {
+    fn new(fields: Vec<String>, collation: Collation) -> Self {
+        let columns = Columns { values: vec![Vec::new(); fields.len()], ..Columns::default() };
+        Self { fields, collation, columns: RwLock::new(columns) }
+    }
+
+    /// Whether every field is projected; `_id` always is
+    fn covers(&self, fields: &[&str]) -> bool {
+        fields.iter().all(|field| *field == "_id" || self.fields.iter().any(|f| f == field))
+    }
+
+    /// Project a document written to the collection
+    pub(crate) fn upsert(&self, doc_id: &str, data: &Value) {
+        let mut columns = self.columns.write();
+        let columns = &mut *columns;
+        let row = match columns.rows.get(doc_id) {
+            Some(&row) => row,
+            None => {
+                columns.ids.push(Value::String(doc_id.to_string()));
+                columns.values.iter_mut().for_each(|column| column.push(None));
+                columns.rows.insert(doc_id.to_string(), columns.ids.len() - 1);
+                columns.ids.len() - 1
+            }
+        };
+        for (field, column) in self.fields.iter().zip(&mut columns.values) {
+            column[row] = field.split('.').try_fold(data, |value, key| value.get(key)).cloned();
+        }
+    }
+
+    /// Drop a deleted document's row
+    pub(crate) fn remove(&self, doc_id: &str) {
+        let mut columns = self.columns.write();
+        let Some(row) = columns.rows.remove(doc_id) else {
+            return;
+        };
+        // The last row takes the removed one's place
+        columns.ids.swap_remove(row);
+        columns.values.iter_mut().for_each(|column| {
+            column.swap_remove(row);
+        });
+        if let Some(Value::String(moved)) = columns.ids.get(row).cloned() {
+            columns.rows.insert(moved, row);
+        }
+    }
+
+    /// Run a pipeline over the projected rows, split into up to `threads`
+    /// ranges run at once
+    fn aggregate(&self, pipeline: &Pipeline, threads: usize) -> Vec<Value> {
+        let columns = self.columns.read();
+        let len = columns.ids.len();
+        let run = |rows: Range<usize>| {
+            pipeline.run_part(rows.map(|row| ColumnRow { fields: &self.fields, columns: &columns, row }))
+        };
+        if threads <= 1 || len < PARALLEL_THRESHOLD {
+            return pipeline.merge(vec![run(0..len)]);
+        }
+        let step = len.div_ceil(threads);
+        let parts = (0..threads).into_par_iter().map(|i| run(i * step..len.min((i + 1) * step))).collect();
+        pipeline.merge(parts)
+    }
+
+    /// Catalog page data: `[kind][collection length][collection][fields as JSON][collation]`
+    fn to_page_data(&self, collection: &str) -> Result<Vec<u8>> {
+        let mut data = vec![COLUMNS_PAGE];
+        data.extend_from_slice(&(collection.len() as u16).to_le_bytes());
+        data.extend_from_slice(collection.as_bytes());
+        data.extend_from_slice(&serde_json::to_vec(&self.fields)?);
+        data.push(match self.collation {
+            Collation::Binary => 0,
+            Collation::NoCase => 1,
+            Collation::Unicode => 2,
+        });
+        Ok(data)
+    }
+
+    /// Read a catalog page as `(collection, fields, collation)`; `None` if it
+    /// holds something else
+    pub(crate) fn from_page_data(data: &[u8]) -> Result<Option<(String, Vec<String>, Collation)>> {
+        if data.first() != Some(&COLUMNS_PAGE) {
+            return Ok(None);
+        }
+        let invalid = || KeraDBError::StorageError("Invalid column projection page".to_string());
+        let name_len = u16::from_le_bytes([*data.get(1).ok_or_else(invalid)?, *data.get(2).ok_or_else(invalid)?]) as usize;
+        let collection = data.get(3..3 + name_len).ok_or_else(invalid)?;
+        let collection = String::from_utf8(collection.to_vec()).map_err(|_| invalid())?;
+        // Pages are zero-padded after the fields
+        let mut stream = serde_json::Deserializer::from_slice(&data[3 + name_len..]).into_iter::<Vec<String>>();
+        let fields = stream.next().ok_or_else(invalid)??;
+        let collation = match data.get(3 + name_len + stream.byte_offset()) {
+            Some(1) => Collation::NoCase,
+            Some(2) => Collation::Unicode,
+            // Pages written before collations have zero padding here
+            _ => Collation::Binary,
+        };
+        Ok(Some((collection, fields, collation)))
+    }
+}
+
+/// One document's projected values, as a pipeline row
+pub(crate) struct ColumnRow<'a> {
+    fields: &'a [String],
+    columns: &'a Columns,
+    row: usize,
+}
+
+impl Row for ColumnRow<'_> {
+    fn get(&self, field: &str) -> Option<&Value> {
+        if field == "_id" {
+            return Some(&self.columns.ids[self.row]);
+        }
+        let column = self.fields.iter().position(|f| f == field)?;
+        self.columns.values[column][self.row].as_ref()
+    }
+
+    /// The projected fields, with dotted ones nested as in the document
+    fn into_value(self) -> Value {
+        let mut row = Map::new();
+        row.insert("_id".to_string(), self.columns.ids[self.row].clone());
+        for (field, column) in self.fields.iter().zip(&self.columns.values) {
+            let Some(value) = &column[self.row] else { continue };
+            let mut parts = field.split('.').peekable();
+            let mut object = &mut row;
+            while let Some(part) = parts.next() {
+                if parts.peek().is_none() {
+                    object.insert(part.to_string(), value.clone());
+                    break;
+                }
+                let child = object.entry(part).or_insert_with(|| Value::Object(Map::new()));
+                match child {
+                    Value::Object(child) => object = child,
+                    _ => break,
+                }
+            }
+        }
+        Value::Object(row)
+    }
+}
+
+impl Executor {
+    /// Project `fields` of a collection's documents into columns, adding to
+    /// any already projected
+    ///
+    /// Without a collation, the projection keeps the one it had, if any.
+    pub fn create_columns(&self, collection: &str, fields: &[&str], collation: Option<Collation>) -> Result<()> {
+        if fields.is_empty() {
+            return Err(KeraDBError::InvalidQuery("No fields to project".to_string()));
+        }
+        let lock = self.write_lock(collection);
+        let _write = lock.lock();
+        let previous = self.column_projections().read().get(collection).cloned();
+        let mut all: Vec<String> = previous.as_ref().map(|(_, store)| store.fields.clone()).unwrap_or_default();
+        for field in fields {
+            if !all.iter().any(|f| f == field) {
+                all.push(field.to_string());
+            }
+        }
+
+        let collation = collation.or(previous.as_ref().map(|(_, store)| store.collation)).unwrap_or_default();
+        let store = Arc::new(ColumnStore::new(all, collation));
+        self.fill_columns(collection, &store)?;
+        let page_num = self.allocate_page(PageType::Meta)?;
+        self.write_page(&Page::new(page_num, PageType::Meta, store.to_page_data(collection)?))?;
+        self.column_projections().write().insert(collection.to_string(), (page_num, store));
+        if let Some((old_page, _)) = previous {
+            self.free_catalog_page(old_page)?;
+        }
+        Ok(())
+    }
+
+    /// Stop projecting a collection's fields; returns whether any were
+    pub fn drop_columns(&self, collection: &str) -> Result<bool> {
+        let lock = self.write_lock(collection);
+        let _write = lock.lock();
+        let Some((page_num, _)) = self.column_projections().write().remove(collection) else {
+            return Ok(false);
+        };
+        self.free_catalog_page(page_num)?;
+        Ok(true)
+    }
+
+    /// The fields of a collection projected into columns
+    pub fn columns(&self, collection: &str) -> Vec<String> {
+        self.column_projections().read().get(collection).map(|(_, store)| store.fields.clone()).unwrap_or_default()
+    }
+
+    /// The collation of a collection's projection, if it holds `field`
+    pub(crate) fn field_collation(&self, collection: &str, field: &str) -> Option<Collation> {
+        let projections = self.column_projections().read();
+        let (_, store) = projections.get(collection)?;
+        store.covers(&[field]).then_some(store.collation)
+    }
+
+    /// The collation of a collection's projection
+    pub fn column_collation(&self, collection: &str) -> Option<Collation> {
+        self.column_projections().read().get(collection).map(|(_, store)| store.collation)
+    }
+
+    /// Whether a collection's projection holds every one of `fields`
+    pub(crate) fn columns_cover(&self, collection: &str, fields: &[&str]) -> bool {
+        self.column_projections().read().get(collection).is_some_and(|(_, store)| store.covers(fields))
+    }
+
+    /// IDs of the documents whose projected values `keep` accepts, or `None`
+    /// unless the collection's projection holds every one of `fields`
+    pub(crate) fn select_columns(
+        &self,
+        collection: &str,
+        fields: &[&str],
+        keep: impl Fn(&ColumnRow) -> bool,
+    ) -> Option<Vec<DocumentId>> {
+        let store = self.column_projections().read().get(collection).map(|(_, store)| store.clone())?;
+        if !store.covers(fields) {
+            return None;
+        }
+        let columns = store.columns.read();
+        let ids = (0..columns.ids.len())
+            .map(|row| ColumnRow { fields: &store.fields, columns: &columns, row })
+            .filter(|row| keep(row))
+            .filter_map(|row| row.columns.ids[row.row].as_str().map(str::to_string))
+            .collect();
+        Some(ids)
+    }
+
+    /// Run an aggregation pipeline over a collection, from its columns if
+    /// they hold every field it reads
+    ///
+    /// The leading match and group stages run on up to `threads` ranges of
+    /// pages (or of projected rows) at once, and their results are merged
+    /// before the remaining stages.
+    pub fn aggregate(&self, collection: &str, pipeline: &Pipeline, threads: usize) -> Result<Vec<Value>> {
+        let store = self.column_projections().read().get(collection).map(|(_, store)| store.clone());
+        if let (Some(store), Some(fields)) = (store, pipeline.input_fields()) {
+            if store.covers(&fields) {
+                return Ok(store.aggregate(pipeline, threads));
+            }
+        }
+        let entries = self.entries_by_page(collection);
+        // Documents that fail to load are skipped, as with `find_all`
+        let run = |range: &[IndexEntry]| {
+            pipeline.run_part(range.iter().filter_map(|entry| self.read_entry(entry).ok()).map(|doc| doc.to_value()))
+        };
+        if threads <= 1 || entries.len() < PARALLEL_THRESHOLD {
+            return Ok(pipeline.merge(vec![run(&entries)]));
+        }
+        let parts = entries.par_chunks(entries.len().div_ceil(threads)).map(run).collect();
+        Ok(pipeline.merge(parts))
+    }
+
+    /// Register a projection found in the catalog while opening; it is
+    /// filled once every document is indexed
+    pub(crate) fn load_column_page(&self, page_num: u32, collection: String, fields: Vec<String>, collation: Collation) {
+        let mut projections = self.column_projections().write();
+        // A newer page is left behind if a change was interrupted
+        if projections.get(&collection).is_none_or(|(page, _)| *page < page_num) {
+            projections.insert(collection, (page_num, Arc::new(ColumnStore::new(fields, collation))));
+        }
+    }
+
+    /// Fill every projection loaded from the catalog
+    pub(crate) fn fill_loaded_columns(&self) -> Result<()> {
+        let projections: Vec<_> = self
+            .column_projections()
+            .read()
+            .iter()
+            .map(|(collection, (_, store))| (collection.clone(), store.clone()))
+            .collect();
+        for (collection, store) in projections {
+            self.fill_columns(&collection, &store)?;
+        }
+        Ok(())
+    }
+
+    fn fill_columns(&self, collection: &str, store: &ColumnStore) -> Result<()> {
+        for doc in self.find_all(collection, None, None)? {
+            store.upsert(&doc.id, &doc.data);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn free_catalog_page(&self, page_num: u32) -> Result<()> {
+        self.write_page(&Page::new(page_num, PageType::Free, vec![0u8; self.page_size() - 5]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aggregate::{Group, Pipeline};
+    use crate::{Database, MetadataFilter};
+    use serde_json::json;
+
+    #[test]
+    fn test_aggregate_with_columns() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("columns.ndb");
+        let db = Database::create(&path).unwrap();
+        let mut ids = Vec::new();
+        for (customer, total, status) in [("ann", json!(10), "paid"), ("bob", json!(5), "paid"), ("ann", json!(7), "open"), ("cat", json!(2.5), "paid")] {
+            let order = json!({"customer": customer, "total": total, "status": status, "items": [{"sku": "a"}]});
+            ids.push(db.insert("orders", order).unwrap());
+        }
+        let pipeline = Pipeline::new()
+            .matching(MetadataFilter::new().eq("status", json!("paid")))
+            .group(Group::by("customer").count("orders").sum("spent", "total"))
+            .sort("spent", true);
+        let from_documents = db.aggregate("orders", &pipeline).unwrap();
+        assert_eq!(from_documents, vec![
+            json!({"_id": "ann", "orders": 1, "spent": 10}),
+            json!({"_id": "bob", "orders": 1, "spent": 5}),
+            json!({"_id": "cat", "orders": 1, "spent": 2.5}),
+        ]);
+
+        db.create_columns("orders", &["customer", "total"]).unwrap();
+        db.create_columns("orders", &["status"]).unwrap();
+        assert_eq!(db.columns("orders"), ["customer", "total", "status"]);
+        assert_eq!(db.aggregate("orders", &pipeline).unwrap(), from_documents);
+
+        // Columns follow writes
+        db.update("orders", &ids[2], json!({"customer": "ann", "total": 1, "status": "paid"})).unwrap();
+        db.delete("orders", &ids[1]).unwrap();
+        db.insert("orders", json!({"customer": "bob", "total": 4, "status": "paid"})).unwrap();
+        let totals = Pipeline::new().group(Group::all().avg("average", "total").min("least", "total").max("most", "total"));
+        assert_eq!(db.aggregate("orders", &totals).unwrap(), vec![json!({"_id": null, "average": 4.375, "least": 1, "most": 10})]);
+        let expected = db.aggregate("orders", &pipeline).unwrap();
+        assert_eq!(expected[0], json!({"_id": "ann", "orders": 2, "spent": 11}));
+        db.sync().unwrap();
+        drop(db);
+
+        // Projections are reloaded from the catalog
+        let db = Database::open(&path).unwrap();
+        assert_eq!(db.columns("orders"), ["customer", "total", "status"]);
+        assert_eq!(db.aggregate("orders", &pipeline).unwrap(), expected);
+        // Reads fields outside the projection, so uses the documents
+        let skus = Pipeline::new().group(Group::by("items").count("n"));
+        assert_eq!(db.aggregate("orders", &skus).unwrap(), vec![json!({"_id": [{"sku": "a"}], "n": 2}), json!({"_id": null, "n": 2})]);
+        assert!(db.drop_columns("orders").unwrap());
+        assert!(!db.drop_columns("orders").unwrap());
+        assert_eq!(db.stats().unwrap().pages.meta, 0);
+    }
+
+    #[test]
+    fn test_parallel_aggregate() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::create(dir.path().join("parallel.ndb")).unwrap();
+        for i in 0..3000 {
+            let reading = json!({"sensor": {"id": i % 7}, "value": i % 101, "ok": i % 3 != 0});
+            db.insert("readings", reading).unwrap();
+        }
+        let grouped = Pipeline::new()
+            .matching(MetadataFilter::new().eq("ok", json!(true)))
+            .group(Group::by("sensor.id").count("n").avg("mean", "value").min("low", "value").max("high", "value"))
+            .sort("n", true)
+            .limit(5);
+        let ungrouped = Pipeline::new().matching(MetadataFilter::new().gt("value", json!(98))).sort("_id", false);
+
+        for pipeline in [&grouped, &ungrouped] {
+            let sequential = db.executor.aggregate("readings", pipeline, 1).unwrap();
+            assert_eq!(db.executor.aggregate("readings", pipeline, 4).unwrap(), sequential);
+            db.create_columns("readings", &["sensor.id", "value", "ok"]).unwrap();
+            assert_eq!(db.executor.aggregate("readings", pipeline, 4).unwrap(), sequential);
+            assert!(db.drop_columns("readings").unwrap());
+        }
+        assert_eq!(db.executor.aggregate("readings", &grouped, 4).unwrap().len(), 5);
+    }
+}
@@ -1,77 +1,258 @@
 use crate::error::{KeraDBError, Result};
+use crate::execution::blob::BlobIndex;
+use crate::execution::columns::{ColumnStore, Projections};
+use crate::execution::multikey::{MultikeyIndex, MultikeyIndexes};
+use crate::execution::mvcc::{Snapshot, VersionStore};
+use crate::execution::quota::{self, Quotas};
+use crate::execution::references::{Cascaded, References, Referencing};
+use crate::execution::settings::{self, Settings};
+use crate::execution::index::IndexEntry;
 use crate::execution::Index;
-use crate::storage::{BufferPool, Pager, Serializer};
-use crate::types::{CollectionMetadata, Document, DocumentId, PageType};
-use parking_lot::RwLock;
+use crate::storage::compression::{self, Dictionaries, Dictionary, DictionaryStats, COMPRESSED};
+use crate::storage::pager::{DatabaseInfo, Page};
+use crate::storage::{BufferPool, CacheStats, Pager, Serializer};
+use crate::stats::{CollectionStats, PageCounts};
+use crate::types::{CollectionMetadata, Document, DocumentId, OpenWarning, PageType, ScanOptions};
+use dashmap::DashMap;
+use parking_lot::{Mutex, RwLock};
+use rayon::prelude::*;
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
+/// Number of striped page latches
+const PAGE_LATCHES: usize = 64;
+
+/// Fewest documents a compression dictionary is trained on
+const MIN_TRAINING_DOCUMENTS: usize = 8;
+
+/// Most documents sampled to train a compression dictionary
+const MAX_TRAINING_SAMPLES: usize = 1000;
+
+/// DEFLATE cannot look further back than this
+const MAX_DICTIONARY_SIZE: usize = 32 * 1024;
+
 /// Executor handles CRUD operations
+///
+/// Writers to the same collection are serialized by a per-collection lock;
+/// writers to different collections run concurrently. Reads take no
+/// collection lock, and only wait while the page they need is being written.
 pub struct Executor {
-    pager: Arc<RwLock<Pager>>,
+    pager: Arc<Pager>,
     buffer_pool: BufferPool,
     index: Index,
     collections: Arc<RwLock<HashMap<String, CollectionMetadata>>>,
+    write_locks: DashMap<String, Arc<Mutex<()>>>,
+    /// Page `n` is guarded by latch `n % PAGE_LATCHES`, held across disk I/O
+    /// and the matching buffer pool update
+    page_latches: Vec<RwLock<()>>,
+    versions: VersionStore,
+    blobs: BlobIndex,
+    dictionaries: RwLock<Dictionaries>,
+    columns: RwLock<Projections>,
+    multikey: RwLock<MultikeyIndexes>,
+    quotas: RwLock<Quotas>,
+    references: RwLock<References>,
+    settings: RwLock<Settings>,
+    /// Largest serialized document any collection takes
+    max_document_size: Option<usize>,
 }
 
 impl Executor {
     pub fn new(pager: Pager, cache_size: usize) -> Self {
+        Self::open(pager, cache_size).0
+    }
+
+    /// Create an executor over `pager`, also returning what had to be
+    /// skipped while rebuilding the index
+    pub fn open(pager: Pager, cache_size: usize) -> (Self, Vec<OpenWarning>) {
         let executor = Self {
-            pager: Arc::new(RwLock::new(pager)),
+            pager: Arc::new(pager),
             buffer_pool: BufferPool::new(cache_size),
             index: Index::new(),
             collections: Arc::new(RwLock::new(HashMap::new())),
+            write_locks: DashMap::new(),
+            page_latches: (0..PAGE_LATCHES).map(|_| RwLock::new(())).collect(),
+            versions: VersionStore::new(),
+            blobs: BlobIndex::default(),
+            dictionaries: RwLock::new(Dictionaries::default()),
+            columns: RwLock::new(HashMap::new()),
+            multikey: RwLock::new(HashMap::new()),
+            quotas: RwLock::new(HashMap::new()),
+            references: RwLock::new(Vec::new()),
+            settings: RwLock::new(HashMap::new()),
+            max_document_size: None,
         };
         
         // Rebuild index from existing pages
-        if let Err(e) = executor.rebuild_index() {
-            eprintln!("Warning: Failed to rebuild index: {}", e);
-        }
-        
-        executor
+        let warnings = executor.rebuild_index();
+        (executor, warnings)
     }
     
     /// Rebuild the index by scanning all pages in the database
-    fn rebuild_index(&self) -> Result<()> {
-        let pager = self.pager.read();
-        let page_count = pager.page_count();
-        drop(pager);
+    ///
+    /// Pages that cannot be indexed are skipped and reported.
+    #[tracing::instrument(level = "debug", skip_all, fields(pages = tracing::field::Empty, documents = tracing::field::Empty))]
+    fn rebuild_index(&self) -> Vec<OpenWarning> {
+        let page_count = self.pager.page_count();
+        let mut documents = 0;
+        let mut warnings = Vec::new();
         
+        // Documents compressed with a dictionary catalogued later in the file
+        let mut deferred = Vec::new();
+        // Pages capped collections freed, by collection
+        let mut freed: HashMap<String, Vec<u32>> = HashMap::new();
+
         for page_num in 0..page_count {
-            let mut pager = self.pager.write();
-            let page = match pager.read_page(page_num) {
+            let page = match self.pager.read_page(page_num) {
                 Ok(p) => p,
-                Err(_) => continue, // Skip invalid pages
+                Err(e) => {
+                    warnings.push(OpenWarning::UnreadablePage { page: page_num, error: e.to_string() });
+                    continue;
+                }
             };
-            drop(pager);
-            
-            if page.page_type != PageType::Data {
+
+            match page.page_type {
+                PageType::Blob => {
+                    if let Err(e) = self.load_blob_page(&page) {
+                        warnings.push(OpenWarning::InvalidBlob { page: page_num, error: e.to_string() });
+                    }
+                    continue;
+                }
+                PageType::Meta => {
+                    if let Err(e) = self.load_catalog_page(&page) {
+                        warnings.push(OpenWarning::InvalidDictionary { page: page_num, error: e.to_string() });
+                    }
+                    continue;
+                }
+                PageType::Free => {
+                    if let Some(collection) = quota::freed_by(&page) {
+                        freed.entry(collection).or_default().push(page_num);
+                    }
+                    continue;
+                }
+                PageType::Data => {}
+                _ => continue,
+            }
+
+            if self.dictionary_missing(&page) {
+                deferred.push(page_num);
                 continue;
             }
-            
-            // Try to extract document from page
-            if let Ok(doc) = self.extract_document_from_page(&page) {
-                // Extract collection name from document metadata
-                // For now, we'll use a simple approach: store collection in a special field
-                if let Some(collection_name) = doc.data.get("_collection").and_then(|v| v.as_str()) {
-                    self.index.insert(collection_name, doc.id.clone(), page_num, 0)?;
-                    self.update_collection_metadata(collection_name, 1);
+            if self.index_document_page(&page, &mut warnings) {
+                documents += 1;
+            }
+        }
+
+        for page_num in deferred {
+            match self.pager.read_page(page_num) {
+                Ok(page) => {
+                    if self.index_document_page(&page, &mut warnings) {
+                        documents += 1;
+                    }
                 }
+                Err(e) => warnings.push(OpenWarning::UnreadablePage { page: page_num, error: e.to_string() }),
             }
         }
-        
+
+        if let Err(e) = self.fill_loaded_columns() {
+            tracing::warn!("Could not rebuild column projections: {}", e);
+        }
+        if let Err(e) = self.fill_loaded_multikey_indexes() {
+            tracing::warn!("Could not rebuild multikey indexes: {}", e);
+        }
+        if let Err(e) = self.fill_loaded_quotas(freed) {
+            tracing::warn!("Could not rebuild quota usage: {}", e);
+        }
+        if let Err(e) = self.fill_loaded_references() {
+            tracing::warn!("Could not rebuild references: {}", e);
+        }
+
+        for warning in &warnings {
+            tracing::warn!("{}", warning);
+        }
+        tracing::Span::current().record("pages", page_count).record("documents", documents);
+        warnings
+    }
+
+    /// Add the document on a data page to the index, or report why not
+    fn index_document_page(&self, page: &Page, warnings: &mut Vec<OpenWarning>) -> bool {
+        let doc = match self.extract_document_from_page(page) {
+            Ok(doc) => doc,
+            Err(e) => {
+                warnings.push(OpenWarning::InvalidDocument { page: page.page_num, error: e.to_string() });
+                return false;
+            }
+        };
+
+        // The collection is stored in the document itself
+        let Some(collection_name) = doc.data.get("_collection").and_then(|v| v.as_str()) else {
+            warnings.push(OpenWarning::InvalidDocument {
+                page: page.page_num,
+                error: format!("Document {} has no collection", doc.id),
+            });
+            return false;
+        };
+        match self.index.insert(collection_name, doc.id.clone(), page.page_num, 0) {
+            Ok(()) => {
+                self.update_collection_metadata(collection_name, 1);
+                true
+            }
+            Err(e) => {
+                warnings.push(OpenWarning::UnindexedDocument {
+                    page: page.page_num,
+                    doc_id: doc.id.clone(),
+                    error: e.to_string(),
+                });
+                false
+            }
+        }
+    }
+
+    /// Load what a catalog page holds
+    fn load_catalog_page(&self, page: &Page) -> Result<()> {
+        if let Some(dictionary) = Dictionary::from_page_data(&page.data)? {
+            self.dictionaries.write().add(dictionary, page.page_num);
+        } else if let Some((collection, fields, collation)) = ColumnStore::from_page_data(&page.data)? {
+            self.load_column_page(page.page_num, collection, fields, collation);
+        } else if let Some((collection, field)) = MultikeyIndex::from_page_data(&page.data)? {
+            self.load_multikey_page(page.page_num, collection, field);
+        } else if let Some((collection, quota)) = quota::from_page_data(&page.data)? {
+            self.load_quota_page(page.page_num, collection, quota);
+        } else if let Some(reference) = Referencing::from_page_data(&page.data)? {
+            self.load_reference_page(page.page_num, reference);
+        } else if let Some((name, value)) = settings::from_page_data(&page.data)? {
+            self.load_setting_page(page.page_num, name, value);
+        }
         Ok(())
     }
 
+    /// Whether a data page is compressed with a dictionary not loaded yet
+    fn dictionary_missing(&self, page: &Page) -> bool {
+        let Some(len) = page.data.get(..4) else {
+            return false;
+        };
+        let stored = u32::from_le_bytes([len[0], len[1], len[2], len[3]]);
+        stored & COMPRESSED != 0
+            && compression::dictionary_id(&page.data[4..]).is_some_and(|id| self.dictionaries.read().get(id).is_none())
+    }
+
     /// Insert a document into a collection
-    pub fn insert(&self, collection: &str, mut data: Value) -> Result<DocumentId> {
+    pub fn insert(&self, collection: &str, data: Value) -> Result<DocumentId> {
+        self.insert_evicting(collection, data).map(|(id, _)| id)
+    }
+
+    /// Insert a document, also returning the documents its collection's
+    /// quota evicted to make room for it and the writes they cascaded to
+    #[tracing::instrument(name = "insert", level = "debug", skip_all, fields(collection = collection, doc_id = tracing::field::Empty, page = tracing::field::Empty), err(level = "debug"))]
+    pub(crate) fn insert_evicting(&self, collection: &str, mut data: Value) -> Result<(DocumentId, Vec<Cascaded>)> {
         // Ensure data is an object
         if !data.is_object() {
             return Err(KeraDBError::InvalidDocument(
                 "Document must be a JSON object".to_string(),
             ));
         }
+        self.check_references(collection, &data)?;
 
         // Add collection name to data for persistence
         if let Value::Object(ref mut map) = data {
@@ -95,133 +276,217 @@ impl Executor {
         };
 
         // Serialize document
-        let doc_bytes = Serializer::serialize(&doc)?;
+        let raw = Serializer::serialize(&doc)?;
+        let size = raw.len();
+        self.check_document_size(collection, size)?;
+        let (doc_bytes, compressed) = self.encode(collection, raw)?;
 
-        // Allocate page and write document
-        let mut pager = self.pager.write();
-        let page_num = pager.allocate_page(PageType::Data)?;
-        
-        let mut page = pager.read_page(page_num)?;
-        
-        // Simple storage: write length + data
-        let len_bytes = (doc_bytes.len() as u32).to_le_bytes();
-        page.data[0..4].copy_from_slice(&len_bytes);
-        
-        if doc_bytes.len() + 4 > page.data.len() {
-            return Err(KeraDBError::StorageError(
-                "Document too large for page".to_string(),
-            ));
+        if doc_bytes.len() + 4 + self.sequence_len(collection) > self.pager.page_size() - 5 {
+            return Err(KeraDBError::StorageError(format!(
+                "Document too large for a {}-byte page",
+                self.pager.page_size()
+            )));
         }
-        
-        page.data[4..4 + doc_bytes.len()].copy_from_slice(&doc_bytes);
-        page.checksum = crc32fast::hash(&page.data);
-        
-        pager.write_page(&page)?;
-        drop(pager);
+
+        let lock = self.write_lock(collection);
+        let _write = lock.lock();
+        if self.index.find(collection, &doc.id).is_some() {
+            return Err(KeraDBError::DuplicateKey(doc.id));
+        }
+        let evicted = self.make_room(collection, &doc.id, size as u64)?;
+        let _pending = self.versions.begin_write(collection, &doc.id, None);
+
+        // Allocate page, or take one a capped collection freed, and write
+        // document; no reader can reach a new page before it is indexed, but
+        // one holding an old index entry can reach a reused page, so readers
+        // are held off until it is cached
+        let (page_num, latch) = match self.reusable_page(collection) {
+            Some(page_num) => (page_num, Some(self.page_latch(page_num).write())),
+            None => (self.pager.allocate_page(PageType::Data)?, None),
+        };
+        tracing::Span::current().record("doc_id", doc.id.as_str()).record("page", page_num);
+        let sequence = self.sequence(collection, &doc.id);
+        let page = self.document_page(page_num, &doc_bytes, compressed, sequence);
+        self.pager.write_page(&page)?;
 
         // Update index
         self.index.insert(collection, doc.id.clone(), page_num, 0)?;
+        self.project(collection, &doc.id, Some(&doc.data));
+        self.account(collection, &doc.id, page_num, sequence, Some(size));
 
         // Update collection metadata
         self.update_collection_metadata(collection, 1);
 
         // Cache the page
         self.buffer_pool.put(page);
+        drop(latch);
+        drop(_pending);
+        drop(_write);
 
-        Ok(doc.id)
+        Ok((doc.id, self.cascade_evicted(collection, evicted)?))
     }
 
     /// Find a document by ID
+    ///
+    /// The primary index holds every ID in memory, so an ID that is not in
+    /// the collection fails without reading a page or touching the cache.
+    #[tracing::instrument(level = "debug", skip(self), fields(page = tracing::field::Empty), err(level = "debug"))]
     pub fn find_by_id(&self, collection: &str, doc_id: &str) -> Result<Document> {
         // Look up in index
         let entry = self.index.find(collection, doc_id)
             .ok_or_else(|| KeraDBError::DocumentNotFound(doc_id.to_string()))?;
+        tracing::Span::current().record("page", entry.page_num);
+
+        // A capped collection may have reused the page since the lookup
+        let doc = self.read_entry(&entry)?;
+        if doc.id != doc_id {
+            return Err(KeraDBError::DocumentNotFound(doc_id.to_string()));
+        }
+        Ok(doc)
+    }
 
+    /// Read the document an index entry points to
+    pub(crate) fn read_entry(&self, entry: &IndexEntry) -> Result<Document> {
+        let mut doc = self.read_stored(entry)?;
+        if let Value::Object(ref mut map) = doc.data {
+            map.remove("_collection");
+        }
+        Ok(doc)
+    }
+
+    /// Read a document as stored, with the `_collection` field the index is
+    /// rebuilt from
+    pub(crate) fn read_stored(&self, entry: &IndexEntry) -> Result<Document> {
         // Check cache first
         if let Some(page) = self.buffer_pool.get(entry.page_num) {
             return self.extract_document_from_page(&page);
         }
 
-        // Read from disk
-        let mut pager = self.pager.write();
-        let page = pager.read_page(entry.page_num)?;
-        drop(pager);
-
-        // Cache the page
+        // Read from disk, caching the page before a writer can change it
+        let latch = self.page_latch(entry.page_num).read();
+        let page = self.pager.read_page(entry.page_num)?;
         self.buffer_pool.put(page.clone());
+        drop(latch);
 
         self.extract_document_from_page(&page)
     }
 
     /// Update a document
-    pub fn update(&self, collection: &str, doc_id: &str, mut data: Value) -> Result<Document> {
+    pub fn update(&self, collection: &str, doc_id: &str, data: Value) -> Result<Document> {
+        self.update_evicting(collection, doc_id, data).map(|(doc, _)| doc)
+    }
+
+    /// Update a document, also returning the documents its collection's
+    /// quota evicted to make room for it and the writes they cascaded to
+    #[tracing::instrument(name = "update", level = "debug", skip(self, data), fields(page = tracing::field::Empty), err(level = "debug"))]
+    pub(crate) fn update_evicting(&self, collection: &str, doc_id: &str, mut data: Value) -> Result<(Document, Vec<Cascaded>)> {
         // Ensure data is an object
         if !data.is_object() {
             return Err(KeraDBError::InvalidDocument(
                 "Document must be a JSON object".to_string(),
             ));
         }
+        self.check_references(collection, &data)?;
+
+        let lock = self.write_lock(collection);
+        let _write = lock.lock();
 
         // Check if document exists
         let entry = self.index.find(collection, doc_id)
             .ok_or_else(|| KeraDBError::DocumentNotFound(doc_id.to_string()))?;
 
-        // Add collection name to data for persistence (needed for index rebuild on reopen)
+        // The ID is the document's, not a field; add collection name to data
+        // for persistence (needed for index rebuild on reopen)
         if let Value::Object(ref mut map) = data {
+            map.remove("_id");
+        }
+        let mut stored = data.clone();
+        if let Value::Object(ref mut map) = stored {
             map.insert("_collection".to_string(), Value::String(collection.to_string()));
         }
 
         // Create updated document
-        let doc = Document::with_id(doc_id.to_string(), data);
+        let doc = Document::with_id(doc_id.to_string(), stored);
 
         // Serialize document
-        let doc_bytes = Serializer::serialize(&doc)?;
+        let raw = Serializer::serialize(&doc)?;
+        let size = raw.len();
+        self.check_document_size(collection, size)?;
+        let (doc_bytes, compressed) = self.encode(collection, raw)?;
 
-        // Write to same page (simple approach - no overflow handling yet)
-        let mut pager = self.pager.write();
-        let mut page = pager.read_page(entry.page_num)?;
-        
-        let len_bytes = (doc_bytes.len() as u32).to_le_bytes();
-        page.data[0..4].copy_from_slice(&len_bytes);
-        
-        if doc_bytes.len() + 4 > page.data.len() {
-            return Err(KeraDBError::StorageError(
-                "Updated document too large for page".to_string(),
-            ));
+        if doc_bytes.len() + 4 + self.sequence_len(collection) > self.pager.page_size() - 5 {
+            return Err(KeraDBError::StorageError(format!(
+                "Updated document too large for a {}-byte page",
+                self.pager.page_size()
+            )));
         }
-        
-        page.data[4..4 + doc_bytes.len()].copy_from_slice(&doc_bytes);
-        page.checksum = crc32fast::hash(&page.data);
-        
-        pager.write_page(&page)?;
-        drop(pager);
+        let evicted = self.make_room(collection, doc_id, size as u64)?;
+
+        let previous = self.find_by_id(collection, doc_id)?;
+        let _pending = self.versions.begin_write(collection, doc_id, Some(previous));
+
+        // Write to same page (simple approach - no overflow handling yet)
+        tracing::Span::current().record("page", entry.page_num);
+        let sequence = self.sequence(collection, doc_id);
+        let page = self.document_page(entry.page_num, &doc_bytes, compressed, sequence);
+        let latch = self.page_latch(entry.page_num).write();
+        self.pager.write_page(&page)?;
 
-        // Invalidate cache
+        // Invalidate cache while readers are still held off
         self.buffer_pool.remove(entry.page_num);
+        drop(latch);
+        self.project(collection, doc_id, Some(&doc.data));
+        self.account(collection, doc_id, entry.page_num, sequence, Some(size));
+        drop(_pending);
+        drop(_write);
 
-        Ok(doc)
+        Ok((Document::with_id(doc.id, data), self.cascade_evicted(collection, evicted)?))
     }
 
     /// Delete a document
     pub fn delete(&self, collection: &str, doc_id: &str) -> Result<Document> {
+        self.delete_cascading(collection, doc_id).map(|(doc, _)| doc)
+    }
+
+    /// Delete a document, also returning the writes the references to it
+    /// cascaded to
+    pub(crate) fn delete_cascading(&self, collection: &str, doc_id: &str) -> Result<(Document, Vec<Cascaded>)> {
+        let doc = {
+            let lock = self.write_lock(collection);
+            let _write = lock.lock();
+            self.check_restrict(collection, doc_id)?;
+            self.delete_locked(collection, doc_id)?
+        };
+        let cascaded = self.cascade(collection, doc_id)?;
+        Ok((doc, cascaded))
+    }
+
+    /// Delete a document while holding its collection's write lock
+    #[tracing::instrument(name = "delete", level = "debug", skip(self), fields(page = tracing::field::Empty), err(level = "debug"))]
+    pub(crate) fn delete_locked(&self, collection: &str, doc_id: &str) -> Result<Document> {
         // Get the document first
         let doc = self.find_by_id(collection, doc_id)?;
+        let _pending = self.versions.begin_write(collection, doc_id, Some(doc.clone()));
+
+        // Attachments go first, so none outlive the document
+        self.delete_blobs(collection, doc_id)?;
 
         // Remove from index
         let entry = self.index.remove(collection, doc_id)
             .ok_or_else(|| KeraDBError::DocumentNotFound(doc_id.to_string()))?;
 
         // Mark page as free (simple approach)
-        let mut pager = self.pager.write();
-        let mut page = pager.read_page(entry.page_num)?;
-        page.page_type = PageType::Free;
-        page.data = vec![0u8; page.data.len()];
-        page.checksum = crc32fast::hash(&page.data);
-        pager.write_page(&page)?;
-        drop(pager);
-
-        // Invalidate cache
+        tracing::Span::current().record("page", entry.page_num);
+        let page = Page::new(entry.page_num, PageType::Free, self.freed_page_data(collection));
+        let latch = self.page_latch(entry.page_num).write();
+        self.pager.write_page(&page)?;
+
+        // Invalidate cache while readers are still held off
         self.buffer_pool.remove(entry.page_num);
+        drop(latch);
+
+        self.project(collection, doc_id, None);
+        self.account(collection, doc_id, entry.page_num, None, None);
 
         // Update collection metadata
         self.update_collection_metadata(collection, -1);
@@ -231,20 +496,278 @@ impl Executor {
 
     /// Find all documents in a collection
     pub fn find_all(&self, collection: &str, limit: Option<usize>, skip: Option<usize>) -> Result<Vec<Document>> {
-        let doc_ids = self.index.list_ids(collection);
-        
-        let skip = skip.unwrap_or(0);
-        let limit = limit.unwrap_or(usize::MAX);
-        
-        let mut documents = Vec::new();
-        
-        for doc_id in doc_ids.iter().skip(skip).take(limit) {
-            if let Ok(doc) = self.find_by_id(collection, doc_id) {
-                documents.push(doc);
+        self.scan(collection, &ScanOptions { limit, skip, ..ScanOptions::default() })
+    }
+
+    /// Read the documents in a collection, in parallel for large collections
+    ///
+    /// Documents that fail to load are skipped, as with `find_all`.
+    #[tracing::instrument(level = "debug", skip_all, fields(collection = collection, ordered = options.ordered, entries = tracing::field::Empty, parallel = tracing::field::Empty))]
+    pub fn scan(&self, collection: &str, options: &ScanOptions) -> Result<Vec<Document>> {
+        let mut entries = self.index.entries(collection);
+        if options.ordered {
+            entries.sort_unstable_by_key(|e| e.page_num);
+        }
+        let entries: Vec<IndexEntry> = entries
+            .into_iter()
+            .skip(options.skip.unwrap_or(0))
+            .take(options.limit.unwrap_or(usize::MAX))
+            .collect();
+
+        let read_batch = |batch: &[IndexEntry]| -> Vec<Document> {
+            batch.iter().filter_map(|entry| self.read_entry(entry).ok()).collect()
+        };
+
+        let parallel = entries.len() >= options.parallel_threshold;
+        tracing::Span::current().record("entries", entries.len()).record("parallel", parallel);
+
+        if !parallel {
+            return Ok(read_batch(&entries));
+        }
+
+        // Each task reads its pages in file order; batches are reassembled
+        // in their original order
+        let batches: Vec<Vec<Document>> = entries
+            .par_chunks(options.batch_size.max(1))
+            .map(|batch| {
+                let mut batch = batch.to_vec();
+                if !options.ordered {
+                    batch.sort_unstable_by_key(|e| e.page_num);
+                }
+                read_batch(&batch)
+            })
+            .collect();
+        Ok(batches.concat())
+    }
+
+    /// Count pages by type and measure each collection's documents
+    ///
+    /// Reads every page of the file, under its latch so writes in progress
+    /// are never seen half-done.
+    pub fn storage_stats(&self) -> (PageCounts, Vec<CollectionStats>) {
+        let mut owners = HashMap::new();
+        let mut collections: Vec<CollectionStats> = self
+            .index
+            .list_collections()
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let entries = self.index.entries(&name);
+                owners.extend(entries.iter().map(|e| (e.page_num, i)));
+                let pages: HashSet<u32> = entries.iter().map(|e| e.page_num).collect();
+                // Each ID is held twice: as the map key and in its entry
+                let index_bytes = entries
+                    .iter()
+                    .map(|e| (e.doc_id.len() * 2 + std::mem::size_of::<IndexEntry>()) as u64)
+                    .sum();
+                CollectionStats { name, documents: entries.len(), bytes: 0, pages: pages.len() as u32, index_bytes }
+            })
+            .collect();
+
+        let mut pages = PageCounts { total: self.pager.page_count(), ..PageCounts::default() };
+        for page_num in 0..pages.total {
+            let page = {
+                let _latch = self.page_latch(page_num).read();
+                self.pager.read_page(page_num)
+            };
+            let Ok(page) = page else {
+                pages.unreadable += 1;
+                continue;
+            };
+            match page.page_type {
+                PageType::Meta => pages.meta += 1,
+                PageType::Data => pages.data += 1,
+                PageType::Index => pages.index += 1,
+                PageType::Free => pages.free += 1,
+                PageType::VectorData => pages.vector_data += 1,
+                PageType::VectorIndex => pages.vector_index += 1,
+                PageType::Blob => pages.blob += 1,
+            }
+            if let (Some(&i), Some(len)) = (owners.get(&page_num), page.data.get(..4)) {
+                collections[i].bytes += (u32::from_le_bytes([len[0], len[1], len[2], len[3]]) & !COMPRESSED) as u64;
+            }
+        }
+
+        collections.sort_by(|a, b| a.name.cmp(&b.name));
+        (pages, collections)
+    }
+
+    /// Train a compression dictionary on a collection's documents and
+    /// rewrite them with it; later writes use it too
+    ///
+    /// See [`compression`]. Writes to the collection wait until every
+    /// document is rewritten. Retraining replaces the collection's dictionary.
+    #[tracing::instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub fn train_dictionary(&self, collection: &str) -> Result<DictionaryStats> {
+        let lock = self.write_lock(collection);
+        let _write = lock.lock();
+        let entries = self.index.entries(collection);
+        if entries.len() < MIN_TRAINING_DOCUMENTS {
+            return Err(KeraDBError::InvalidQuery(format!(
+                "Need at least {} documents in '{}' to train a dictionary",
+                MIN_TRAINING_DOCUMENTS, collection
+            )));
+        }
+
+        let step = entries.len().div_ceil(MAX_TRAINING_SAMPLES);
+        let samples = entries
+            .iter()
+            .step_by(step)
+            .map(|entry| Serializer::serialize(&self.read_stored(entry)?))
+            .collect::<Result<Vec<_>>>()?;
+        let max_size = (self.pager.page_size() - 5)
+            .saturating_sub(Dictionary::page_overhead(collection))
+            .min(MAX_DICTIONARY_SIZE);
+        let bytes = compression::train(&samples, max_size);
+        if bytes.is_empty() {
+            return Err(KeraDBError::InvalidQuery(format!(
+                "Documents in '{}' have too little in common to train a dictionary",
+                collection
+            )));
+        }
+
+        // Catalogued before any document needs it
+        let id = {
+            let mut dictionaries = self.dictionaries.write();
+            let dictionary = Dictionary::new(dictionaries.next_id(), collection, bytes);
+            let page_num = self.pager.allocate_page(PageType::Meta)?;
+            self.pager.write_page(&Page::new(page_num, PageType::Meta, dictionary.to_page_data()))?;
+            let id = dictionary.id;
+            dictionaries.add(dictionary, page_num);
+            id
+        };
+
+        let mut stats = DictionaryStats {
+            collection: collection.to_string(),
+            dictionary_bytes: self.dictionaries.read().get(id).map_or(0, |d| d.len()),
+            documents: entries.len(),
+            raw_bytes: 0,
+            stored_bytes: 0,
+        };
+        for entry in &entries {
+            let raw = Serializer::serialize(&self.read_stored(entry)?)?;
+            stats.raw_bytes += raw.len() as u64;
+            let (doc_bytes, compressed) = self.encode(collection, raw)?;
+            stats.stored_bytes += doc_bytes.len() as u64;
+            // The document is unchanged, so snapshots need no old version
+            let sequence = self.sequence(collection, &entry.doc_id);
+            let page = self.document_page(entry.page_num, &doc_bytes, compressed, sequence);
+            let latch = self.page_latch(entry.page_num).write();
+            self.pager.write_page(&page)?;
+            self.buffer_pool.remove(entry.page_num);
+            drop(latch);
+        }
+
+        // No document uses the collection's older dictionaries any more
+        for page_num in self.dictionaries.write().retire(collection, id) {
+            self.write_page(&Page::new(page_num, PageType::Free, vec![0u8; self.pager.page_size() - 5]))?;
+        }
+        Ok(stats)
+    }
+
+    /// Size of each page in the data file
+    pub fn page_size(&self) -> usize {
+        self.pager.page_size()
+    }
+
+    /// The database's UUID, creation time and last writer
+    pub fn database_info(&self) -> &DatabaseInfo {
+        self.pager.info()
+    }
+
+    /// Size of the database storage in bytes
+    pub fn storage_size(&self) -> Result<u64> {
+        self.pager.size()
+    }
+
+    /// Page cache counters
+    pub fn cache_stats(&self) -> CacheStats {
+        self.buffer_pool.stats()
+    }
+
+    /// Take a snapshot that keeps seeing the documents as they are now
+    pub fn snapshot(&self) -> Snapshot<'_> {
+        Snapshot::new(self)
+    }
+
+    pub(crate) fn versions(&self) -> &VersionStore {
+        &self.versions
+    }
+
+    pub(crate) fn blobs(&self) -> &BlobIndex {
+        &self.blobs
+    }
+
+    pub(crate) fn column_projections(&self) -> &RwLock<Projections> {
+        &self.columns
+    }
+
+    pub(crate) fn multikey_indexes(&self) -> &RwLock<MultikeyIndexes> {
+        &self.multikey
+    }
+
+    pub(crate) fn quotas(&self) -> &RwLock<Quotas> {
+        &self.quotas
+    }
+
+    pub(crate) fn reference_table(&self) -> &RwLock<References> {
+        &self.references
+    }
+
+    pub(crate) fn settings(&self) -> &RwLock<Settings> {
+        &self.settings
+    }
+
+    pub(crate) fn max_document_size(&self) -> Option<usize> {
+        self.max_document_size
+    }
+
+    /// Refuse documents that serialize to more than `max_document_size` bytes
+    pub fn with_max_document_size(mut self, max_document_size: Option<usize>) -> Self {
+        self.max_document_size = max_document_size;
+        self
+    }
+
+    /// Bring a collection's column projection, multikey indexes and
+    /// references up to date with a write; `data` is `None` for a delete
+    fn project(&self, collection: &str, doc_id: &str, data: Option<&Value>) {
+        if let Some(store) = self.columns.read().get(collection).map(|(_, store)| store.clone()) {
+            match data {
+                Some(data) => store.upsert(doc_id, data),
+                None => store.remove(doc_id),
             }
         }
+        let indexes = self.multikey.read().get(collection).cloned().unwrap_or_default();
+        for (_, index) in indexes {
+            match data {
+                Some(data) => index.upsert(doc_id, data),
+                None => index.remove(doc_id),
+            }
+        }
+        self.follow_references(collection, doc_id, data);
+    }
+
+    /// Whether a document exists
+    pub(crate) fn contains(&self, collection: &str, doc_id: &str) -> bool {
+        self.index.find(collection, doc_id).is_some()
+    }
+
+    /// Index entries of a collection's documents, in page order
+    pub(crate) fn entries_by_page(&self, collection: &str) -> Vec<IndexEntry> {
+        let mut entries = self.index.entries(collection);
+        entries.sort_unstable_by_key(|e| e.page_num);
+        entries
+    }
+
+    /// Read documents by ID in page order, skipping any not found
+    pub(crate) fn find_many(&self, collection: &str, ids: &[DocumentId]) -> Vec<Document> {
+        let mut entries: Vec<IndexEntry> = ids.iter().filter_map(|id| self.index.find(collection, id)).collect();
+        entries.sort_unstable_by_key(|e| e.page_num);
+        entries.iter().filter_map(|entry| self.read_entry(entry).ok()).collect()
+    }
 
-        Ok(documents)
+    pub(crate) fn list_ids(&self, collection: &str) -> Vec<DocumentId> {
+        self.index.list_ids(collection)
     }
 
     /// Count documents in a collection
@@ -265,27 +788,97 @@ impl Executor {
     }
 
     /// Sync data to disk
+    #[tracing::instrument(level = "debug", skip_all, err)]
     pub fn sync(&self) -> Result<()> {
-        let mut pager = self.pager.write();
-        pager.sync()?;
-        Ok(())
+        self.pager.sync()
+    }
+
+    /// Pages written since the last sync
+    pub fn unsynced_pages(&self) -> u64 {
+        self.pager.unsynced_pages()
     }
 
     // Helper methods
 
-    fn extract_document_from_page(&self, page: &crate::storage::pager::Page) -> Result<Document> {
+    pub(crate) fn write_lock(&self, collection: &str) -> Arc<Mutex<()>> {
+        if let Some(lock) = self.write_locks.get(collection) {
+            return lock.clone();
+        }
+        self.write_locks.entry(collection.to_string()).or_default().clone()
+    }
+
+    fn page_latch(&self, page_num: u32) -> &RwLock<()> {
+        &self.page_latches[page_num as usize % PAGE_LATCHES]
+    }
+
+    /// Read a page that is not cached, under its latch
+    pub(crate) fn read_page(&self, page_num: u32) -> Result<Page> {
+        let _latch = self.page_latch(page_num).read();
+        self.pager.read_page(page_num)
+    }
+
+    /// Write a page that is not cached, under its latch
+    pub(crate) fn write_page(&self, page: &Page) -> Result<()> {
+        let _latch = self.page_latch(page.page_num).write();
+        self.pager.write_page(page)
+    }
+
+    pub(crate) fn allocate_page(&self, page_type: PageType) -> Result<u32> {
+        self.pager.allocate_page(page_type)
+    }
+
+    /// Compress a serialized document with its collection's dictionary, if
+    /// it has one and that makes it smaller
+    fn encode(&self, collection: &str, doc_bytes: Vec<u8>) -> Result<(Vec<u8>, bool)> {
+        let Some(dictionary) = self.dictionaries.read().active(collection) else {
+            return Ok((doc_bytes, false));
+        };
+        let compressed = dictionary.compress(&doc_bytes)?;
+        if compressed.len() < doc_bytes.len() {
+            Ok((compressed, true))
+        } else {
+            Ok((doc_bytes, false))
+        }
+    }
+
+    /// A full-size data page holding a serialized document
+    ///
+    /// Documents of capped collections are followed by their insertion
+    /// sequence, since reused pages no longer say which came first.
+    fn document_page(&self, page_num: u32, doc_bytes: &[u8], compressed: bool, sequence: Option<u64>) -> Page {
+        let mut data = vec![0u8; self.pager.page_size() - 5];
+        let len = doc_bytes.len() as u32 | if compressed { COMPRESSED } else { 0 };
+        data[0..4].copy_from_slice(&len.to_le_bytes());
+        data[4..4 + doc_bytes.len()].copy_from_slice(doc_bytes);
+        if let Some(sequence) = sequence {
+            let end = 4 + doc_bytes.len();
+            data[end..end + 8].copy_from_slice(&sequence.to_le_bytes());
+        }
+        Page::new(page_num, PageType::Data, data)
+    }
+
+    /// The insertion sequence stored after a document, if any
+    pub(crate) fn page_sequence(page: &Page) -> Option<u64> {
+        let len = page.data.get(0..4)?;
+        let end = 4 + (u32::from_le_bytes([len[0], len[1], len[2], len[3]]) & !COMPRESSED) as usize;
+        let sequence = u64::from_le_bytes(page.data.get(end..end + 8)?.try_into().ok()?);
+        (sequence > 0).then_some(sequence)
+    }
+
+    fn extract_document_from_page(&self, page: &Page) -> Result<Document> {
         if page.data.len() < 4 {
             return Err(KeraDBError::StorageError(
                 "Invalid page data".to_string(),
             ));
         }
 
-        let len = u32::from_le_bytes([
+        let stored = u32::from_le_bytes([
             page.data[0],
             page.data[1],
             page.data[2],
             page.data[3],
-        ]) as usize;
+        ]);
+        let len = (stored & !COMPRESSED) as usize;
 
         if len == 0 || len + 4 > page.data.len() {
             return Err(KeraDBError::StorageError(
@@ -294,6 +887,12 @@ impl Executor {
         }
 
         let doc_bytes = &page.data[4..4 + len];
+        if stored & COMPRESSED != 0 {
+            let dictionary = compression::dictionary_id(doc_bytes)
+                .and_then(|id| self.dictionaries.read().get(id))
+                .ok_or_else(|| KeraDBError::StorageError("Compression dictionary not found".to_string()))?;
+            return Serializer::deserialize(&dictionary.decompress(doc_bytes)?);
+        }
         Serializer::deserialize(doc_bytes)
     }
 
@@ -334,6 +933,22 @@ mod tests {
         assert_eq!(found.data.get("name").unwrap(), "Alice");
     }
 
+    #[test]
+    fn test_missing_id_reads_no_pages() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.ndb");
+        let executor = Executor::new(Pager::create(&path, 4096).unwrap(), 10);
+        executor.insert("users", json!({"name": "Alice"})).unwrap();
+
+        let before = executor.cache_stats();
+        for id in ["missing", "also-missing"] {
+            assert!(matches!(executor.find_by_id("users", id), Err(KeraDBError::DocumentNotFound(_))));
+        }
+        assert!(executor.find_by_id("nobody", "missing").is_err());
+        let after = executor.cache_stats();
+        assert_eq!((after.hits, after.misses), (before.hits, before.misses));
+    }
+
     #[test]
     fn test_update() {
         let dir = tempdir().unwrap();
@@ -348,6 +963,32 @@ mod tests {
         
         let found = executor.find_by_id("users", &doc_id).unwrap();
         assert_eq!(found.data.get("age").unwrap(), 31);
+
+        // An `_id` in the new data is the document's ID, not a field
+        executor.update("users", &doc_id, json!({"_id": doc_id, "name": "Alice"})).unwrap();
+        let found = executor.find_by_id("users", &doc_id).unwrap();
+        assert_eq!(found.data, json!({"name": "Alice"}));
+        assert_eq!(found.to_value()["_id"], json!(doc_id));
+    }
+
+    #[test]
+    fn test_reads_omit_collection_field() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.ndb");
+
+        let executor = Executor::new(Pager::create(&path, 4096).unwrap(), 10);
+        let doc_id = executor.insert("users", json!({"name": "Alice"})).unwrap();
+        let updated = executor.update("users", &doc_id, json!({"name": "Alicia"})).unwrap();
+        assert_eq!(updated.data, json!({"name": "Alicia"}));
+        assert_eq!(executor.find_by_id("users", &doc_id).unwrap().data, json!({"name": "Alicia"}));
+        executor.sync().unwrap();
+        drop(executor);
+
+        // The field is still stored, since reopening rebuilds the index from it
+        let executor = Executor::new(Pager::open(&path).unwrap(), 10);
+        let docs = executor.find_all("users", None, None).unwrap();
+        assert_eq!(docs.len(), 1);
+        assert_eq!(docs[0].data, json!({"name": "Alicia"}));
     }
 
     #[test]
@@ -364,4 +1005,102 @@ mod tests {
         
         assert!(executor.find_by_id("users", &doc_id).is_err());
     }
+
+    #[test]
+    fn test_concurrent_writers_on_different_collections() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.ndb");
+
+        let executor = Executor::new(Pager::create(&path, 4096).unwrap(), 10);
+        std::thread::scope(|scope| {
+            for collection in ["users", "logs", "events"] {
+                let executor = &executor;
+                scope.spawn(move || {
+                    for i in 0..50 {
+                        let id = executor.insert(collection, json!({"n": i})).unwrap();
+                        executor.update(collection, &id, json!({"n": i + 1})).unwrap();
+                    }
+                });
+            }
+        });
+        executor.sync().unwrap();
+        drop(executor);
+
+        // Every page was allocated exactly once and the header counts them all
+        let executor = Executor::new(Pager::open(&path).unwrap(), 10);
+        for collection in ["users", "logs", "events"] {
+            assert_eq!(executor.count(collection), 50);
+        }
+    }
+
+    #[test]
+    fn test_parallel_scan_matches_sequential() {
+        let dir = tempdir().unwrap();
+        let executor = Executor::new(Pager::create(dir.path().join("test.ndb"), 4096).unwrap(), 10);
+        let ids: Vec<DocumentId> = (0..300).map(|i| executor.insert("items", json!({"n": i})).unwrap()).collect();
+
+        let parallel = ScanOptions::default().with_batch_size(16).with_parallel_threshold(0);
+        let ordered = executor.scan("items", &parallel.clone().with_ordered(true)).unwrap();
+        let order: Vec<&DocumentId> = ordered.iter().map(|d| &d.id).collect();
+        assert_eq!(order, ids.iter().collect::<Vec<_>>());
+
+        let page = executor.scan("items", &parallel.with_ordered(true).with_skip(100).with_limit(5)).unwrap();
+        assert_eq!(page[0].get("n"), Some(json!(100)));
+        assert_eq!(page.len(), 5);
+
+        let unordered = executor.find_all("items", None, None).unwrap();
+        assert_eq!(unordered.len(), 300);
+    }
+
+    #[test]
+    fn test_operations_emit_spans() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing_subscriber::layer::{Context, SubscriberExt};
+        use tracing_subscriber::Layer;
+
+        /// Collects `name field=value` for every span field that is set
+        #[derive(Clone, Default)]
+        struct Fields(Arc<Mutex<Vec<String>>>);
+
+        struct Visitor<'a>(&'a Fields, &'static str);
+
+        impl Visit for Visitor<'_> {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                self.0 .0.lock().unwrap().push(format!("{} {}={:?}", self.1, field.name(), value));
+            }
+        }
+
+        impl<S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>> Layer<S> for Fields {
+            fn on_new_span(&self, attrs: &tracing::span::Attributes<'_>, _: &tracing::span::Id, _: Context<'_, S>) {
+                attrs.record(&mut Visitor(self, attrs.metadata().name()));
+            }
+
+            fn on_record(&self, id: &tracing::span::Id, values: &tracing::span::Record<'_>, ctx: Context<'_, S>) {
+                let name = ctx.span(id).unwrap().name();
+                values.record(&mut Visitor(self, name));
+            }
+        }
+
+        let fields = Fields::default();
+        let subscriber = tracing_subscriber::registry().with(fields.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let dir = tempdir().unwrap();
+            let executor = Executor::new(Pager::create(dir.path().join("test.ndb"), 4096).unwrap(), 10);
+            let id = executor.insert("users", json!({"name": "Alice"})).unwrap();
+            executor.find_by_id("users", &id).unwrap();
+
+            let fields = fields.0.lock().unwrap();
+            for expected in [
+                "insert collection=\"users\"".to_string(),
+                format!("insert doc_id=\"{}\"", id),
+                "insert page=0".to_string(),
+                format!("find_by_id doc_id=\"{}\"", id),
+                "find_by_id page=0".to_string(),
+                "allocate_page page_num=0".to_string(),
+            ] {
+                assert!(fields.contains(&expected), "missing `{}` in {:?}", expected, fields);
+            }
+        });
+    }
 }
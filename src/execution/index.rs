@@ -50,11 +50,18 @@ impl Index {
             .and_then(|idx| idx.get(doc_id).cloned())
     }
 
-    /// Remove an entry from the index
+    /// Remove an entry from the index, and the collection with its last
+    /// entry, as a reopen would not find it either
     pub fn remove(&self, collection: &str, doc_id: &str) -> Option<IndexEntry> {
-        self.indexes
-            .get_mut(collection)
-            .and_then(|mut idx| idx.remove(doc_id))
+        let (entry, empty) = {
+            let mut idx = self.indexes.get_mut(collection)?;
+            let entry = idx.remove(doc_id);
+            (entry, idx.is_empty())
+        };
+        if empty {
+            self.indexes.remove_if(collection, |_, idx| idx.is_empty());
+        }
+        entry
     }
 
     /// Get all document IDs in a collection
@@ -65,6 +72,14 @@ impl Index {
             .unwrap_or_default()
     }
 
+    /// Get all entries in a collection
+    pub fn entries(&self, collection: &str) -> Vec<IndexEntry> {
+        self.indexes
+            .get(collection)
+            .map(|idx| idx.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
     /// Get count of documents in a collection
     pub fn count(&self, collection: &str) -> usize {
         self.indexes
@@ -103,5 +118,8 @@ mod tests {
         
         index.remove("users", "id1");
         assert_eq!(index.count("users"), 1);
+
+        index.remove("users", "id2");
+        assert!(index.list_collections().is_empty());
     }
 }
@@ -1,5 +1,16 @@
+pub mod blob;
+pub mod columns;
 pub mod executor;
 pub mod index;
+pub mod multikey;
+pub mod mvcc;
+pub mod quota;
+pub mod references;
+pub(crate) mod settings;
 
+pub use blob::{BlobInfo, BlobReader, BlobWriter};
 pub use executor::Executor;
 pub use index::Index;
+pub use mvcc::Snapshot;
+pub use quota::Quota;
+pub use references::{OnDelete, Reference};
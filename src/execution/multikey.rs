@@ -0,0 +1,297 @@
+//! Multikey indexes over array fields
+//!
+//! A multikey index maps the values of one field to the documents holding
+//! them. An array is indexed under each of its elements as well as under the
+//! whole array, so `find posts where tags contains rust` or `tags in [rust,
+//! go]` reads only the documents that can match instead of scanning the
+//! collection. The executor keeps each index up to date on every insert,
+//! update and delete.
+//!
+//! As with [column projections](super::columns), the indexed field is kept
+//! in a catalog page and the index itself is rebuilt in memory from the
+//! documents when the database is opened.
+
+use crate::error::{KeraDBError, Result};
+use crate::execution::Executor;
+use crate::storage::pager::Page;
+use crate::types::{DocumentId, PageType};
+use crate::vector::FilterCondition;
+use parking_lot::RwLock;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Kind byte of a catalog page naming an indexed field
+const MULTIKEY_PAGE: u8 = 3;
+
+/// Each collection's multikey indexes, with the catalog page naming each field
+pub(crate) type MultikeyIndexes = HashMap<String, Vec<(u32, Arc<MultikeyIndex>)>>;
+
+#[derive(Default)]
+struct Entries {
+    /// Documents by the JSON text of each value they hold
+    documents: HashMap<String, HashSet<DocumentId>>,
+    /// Keys of each indexed document, to remove them when it changes
+    keys: HashMap<DocumentId, Vec<String>>,
+    /// Documents whose field is a string, which `contains` matches by substring
+    strings: HashSet<DocumentId>,
+}
+
+/// The multikey index of one field
+pub(crate) struct MultikeyIndex {
+    field: String,
+    entries: RwLock<Entries>,
+}
+
+impl MultikeyIndex {
+    fn new(field: &str) -> Self {
+        Self { field: field.to_string(), entries: RwLock::new(Entries::default()) }
+    }
+
+    /// Index a document written to the collection
+    pub(crate) fn upsert(&self, doc_id: &str, data: &Value) {
+        let value = self.field.split('.').try_fold(data, |value, key| value.get(key));
+        let mut keys = Vec::new();
+        if let Some(value) = value {
+            keys.push(value.to_string());
+            if let Value::Array(items) = value {
+                keys.extend(items.iter().map(Value::to_string));
+            }
+        }
+        keys.sort_unstable();
+        keys.dedup();
+
+        let mut entries = self.entries.write();
+        Self::unindex(&mut entries, doc_id);
+        for key in &keys {
+            entries.documents.entry(key.clone()).or_default().insert(doc_id.to_string());
+        }
+        if matches!(value, Some(Value::String(_))) {
+            entries.strings.insert(doc_id.to_string());
+        }
+        if !keys.is_empty() {
+            entries.keys.insert(doc_id.to_string(), keys);
+        }
+    }
+
+    /// Drop a deleted document
+    pub(crate) fn remove(&self, doc_id: &str) {
+        Self::unindex(&mut self.entries.write(), doc_id);
+    }
+
+    fn unindex(entries: &mut Entries, doc_id: &str) {
+        entries.strings.remove(doc_id);
+        for key in entries.keys.remove(doc_id).unwrap_or_default() {
+            if let Some(documents) = entries.documents.get_mut(&key) {
+                documents.remove(doc_id);
+                if documents.is_empty() {
+                    entries.documents.remove(&key);
+                }
+            }
+        }
+    }
+
+    /// Every document that can match `condition` on the field, or `None` if
+    /// the index cannot narrow it down
+    fn candidates(&self, condition: &FilterCondition) -> Option<HashSet<DocumentId>> {
+        let entries = self.entries.read();
+        let holding = |value: &Value| entries.documents.get(&value.to_string()).into_iter().flatten().cloned();
+        match condition {
+            FilterCondition::Eq(value) => Some(holding(value).collect()),
+            FilterCondition::In(values) => Some(values.iter().flat_map(holding).collect()),
+            FilterCondition::Contains(text) => {
+                Some(holding(&Value::String(text.clone())).chain(entries.strings.iter().cloned()).collect())
+            }
+            _ => None,
+        }
+    }
+
+    /// Catalog page data: `[kind][collection length][collection][field]`
+    fn to_page_data(&self, collection: &str) -> Vec<u8> {
+        let mut data = vec![MULTIKEY_PAGE];
+        data.extend_from_slice(&(collection.len() as u16).to_le_bytes());
+        data.extend_from_slice(collection.as_bytes());
+        data.extend_from_slice(&(self.field.len() as u16).to_le_bytes());
+        data.extend_from_slice(self.field.as_bytes());
+        data
+    }
+
+    /// Read a catalog page as `(collection, field)`; `None` if it holds
+    /// something else
+    pub(crate) fn from_page_data(data: &[u8]) -> Result<Option<(String, String)>> {
+        if data.first() != Some(&MULTIKEY_PAGE) {
+            return Ok(None);
+        }
+        let invalid = || KeraDBError::StorageError("Invalid multikey index page".to_string());
+        let text = |at: usize| -> Result<(String, usize)> {
+            let len = data.get(at..at + 2).ok_or_else(invalid)?;
+            let len = u16::from_le_bytes([len[0], len[1]]) as usize;
+            let bytes = data.get(at + 2..at + 2 + len).ok_or_else(invalid)?;
+            Ok((String::from_utf8(bytes.to_vec()).map_err(|_| invalid())?, at + 2 + len))
+        };
+        let (collection, next) = text(1)?;
+        let (field, _) = text(next)?;
+        Ok(Some((collection, field)))
+    }
+}
+
+impl Executor {
+    /// Index each element of a collection's array-valued `field`; returns
+    /// false if it already was
+    pub fn create_multikey_index(&self, collection: &str, field: &str) -> Result<bool> {
+        let lock = self.write_lock(collection);
+        let _write = lock.lock();
+        if self.multikey_index(collection, field).is_some() {
+            return Ok(false);
+        }
+        let index = Arc::new(MultikeyIndex::new(field));
+        self.fill_multikey_index(collection, &index)?;
+        let page_num = self.allocate_page(PageType::Meta)?;
+        self.write_page(&Page::new(page_num, PageType::Meta, index.to_page_data(collection)))?;
+        self.multikey_indexes().write().entry(collection.to_string()).or_default().push((page_num, index));
+        Ok(true)
+    }
+
+    /// Drop the multikey index of a field; returns whether there was one
+    pub fn drop_multikey_index(&self, collection: &str, field: &str) -> Result<bool> {
+        let lock = self.write_lock(collection);
+        let _write = lock.lock();
+        let page_num = {
+            let mut indexes = self.multikey_indexes().write();
+            let Some(fields) = indexes.get_mut(collection) else {
+                return Ok(false);
+            };
+            let Some(position) = fields.iter().position(|(_, index)| index.field == field) else {
+                return Ok(false);
+            };
+            let (page_num, _) = fields.remove(position);
+            if fields.is_empty() {
+                indexes.remove(collection);
+            }
+            page_num
+        };
+        self.free_catalog_page(page_num)?;
+        Ok(true)
+    }
+
+    /// The fields of a collection with multikey indexes
+    pub fn multikey_fields(&self, collection: &str) -> Vec<String> {
+        self.multikey_indexes()
+            .read()
+            .get(collection)
+            .map(|fields| fields.iter().map(|(_, index)| index.field.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    fn multikey_index(&self, collection: &str, field: &str) -> Option<Arc<MultikeyIndex>> {
+        let indexes = self.multikey_indexes().read();
+        indexes.get(collection)?.iter().find(|(_, index)| index.field == field).map(|(_, index)| index.clone())
+    }
+
+    /// Whether the multikey index of a field can narrow down `condition`
+    pub(crate) fn multikey_narrows(&self, collection: &str, field: &str, condition: &FilterCondition) -> bool {
+        matches!(condition, FilterCondition::Eq(_) | FilterCondition::In(_) | FilterCondition::Contains(_))
+            && self.multikey_index(collection, field).is_some()
+    }
+
+    /// IDs of every document that can match `condition` on a field, or
+    /// `None` if the field has no multikey index to tell
+    pub(crate) fn multikey_candidates(
+        &self,
+        collection: &str,
+        field: &str,
+        condition: &FilterCondition,
+    ) -> Option<Vec<DocumentId>> {
+        self.multikey_index(collection, field)?.candidates(condition).map(|ids| ids.into_iter().collect())
+    }
+
+    /// Register an index found in the catalog while opening; it is filled
+    /// once every document is indexed
+    pub(crate) fn load_multikey_page(&self, page_num: u32, collection: String, field: String) {
+        let mut indexes = self.multikey_indexes().write();
+        let fields = indexes.entry(collection).or_default();
+        // A duplicate is left behind if a change was interrupted
+        if !fields.iter().any(|(_, index)| index.field == field) {
+            fields.push((page_num, Arc::new(MultikeyIndex::new(&field))));
+        }
+    }
+
+    /// Fill every multikey index loaded from the catalog
+    pub(crate) fn fill_loaded_multikey_indexes(&self) -> Result<()> {
+        let indexes: Vec<_> = self
+            .multikey_indexes()
+            .read()
+            .iter()
+            .flat_map(|(collection, fields)| fields.iter().map(|(_, index)| (collection.clone(), index.clone())))
+            .collect();
+        for (collection, index) in indexes {
+            self.fill_multikey_index(&collection, &index)?;
+        }
+        Ok(())
+    }
+
+    fn fill_multikey_index(&self, collection: &str, index: &MultikeyIndex) -> Result<()> {
+        for doc in self.find_all(collection, None, None)? {
+            index.upsert(&doc.id, &doc.data);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::query::{AccessPath, Filter, FindQuery};
+    use crate::vector::FilterCondition;
+    use crate::Database;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_multikey_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("multikey.ndb");
+        let db = Database::create(&path).unwrap();
+        let mut ids = Vec::new();
+        for (title, tags) in [("intro", json!(["rust", "db"])), ("deep dive", json!(["rust"])), ("notes", json!("rusty")), ("misc", json!([]))] {
+            ids.push(db.insert("posts", json!({"title": title, "tags": tags})).unwrap());
+        }
+        let find = |condition: FilterCondition| FindQuery {
+            collection: "posts".to_string(),
+            id: None,
+            filter: Filter { conditions: vec![("tags".to_string(), condition)], collation: None },
+            sort: None,
+            limit: None,
+            skip: 0,
+        };
+        let titles = |query: &FindQuery, db: &Database| -> Vec<Value> {
+            query.run(db).unwrap().iter().map(|doc| doc.get("title").unwrap()).collect()
+        };
+        let rust = find(FilterCondition::Contains("rust".to_string()));
+        let scanned = titles(&rust, &db);
+        assert_eq!(scanned, [json!("intro"), json!("deep dive"), json!("notes")]);
+
+        assert!(db.create_multikey_index("posts", "tags").unwrap());
+        assert!(!db.create_multikey_index("posts", "tags").unwrap());
+        let plan = db.explain(&rust);
+        assert_eq!((plan.access, plan.documents_examined), (AccessPath::MultikeyIndex { field: "tags".to_string() }, 3));
+        assert_eq!(titles(&rust, &db), scanned);
+
+        // The index follows writes, and is rebuilt when the database is opened
+        db.update("posts", &ids[1], json!({"title": "deep dive", "tags": ["go"]})).unwrap();
+        db.delete("posts", &ids[0]).unwrap();
+        db.insert("posts", json!({"title": "late", "tags": ["db", "go"]})).unwrap();
+        db.sync().unwrap();
+        drop(db);
+        let db = Database::open(&path).unwrap();
+        assert_eq!(db.multikey_indexes("posts"), ["tags"]);
+        let either = find(FilterCondition::In(vec![json!("go"), json!("db")]));
+        assert_eq!(db.explain(&either).documents_examined, 2);
+        assert_eq!(titles(&either, &db), [json!("deep dive"), json!("late")]);
+        assert_eq!(titles(&find(FilterCondition::Eq(json!([]))), &db), [json!("misc")]);
+        assert_eq!(titles(&rust, &db), [json!("notes")]);
+
+        assert!(db.drop_multikey_index("posts", "tags").unwrap());
+        assert!(!db.drop_multikey_index("posts", "tags").unwrap());
+        assert_eq!(db.explain(&rust).access, AccessPath::FullScan);
+        assert_eq!(db.stats().unwrap().pages.meta, 0);
+    }
+}
@@ -0,0 +1,245 @@
+//! Snapshot reads
+//!
+//! Documents are updated in place on disk, so before every write the
+//! executor records the document's previous state here, tagged with the
+//! version of the write that replaced it. A [`Snapshot`] taken at version `v`
+//! reads the current state of a document unless it changed after `v`, in
+//! which case the recorded state is used instead.
+//!
+//! Recorded states are dropped as soon as no open snapshot can need them, so
+//! without snapshots the store stays empty.
+
+use crate::error::{KeraDBError, Result};
+use crate::execution::Executor;
+use crate::types::{Document, DocumentId};
+use parking_lot::{Mutex, RwLock};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// State of a document before the write at `superseded_at`
+struct OldVersion {
+    superseded_at: u64,
+    /// `None` if the document did not exist yet
+    document: Option<Document>,
+}
+
+struct VersionState {
+    /// Version handed to the most recent writer
+    last: u64,
+    /// Versions of writes that have started but not finished
+    in_flight: BTreeSet<u64>,
+    /// Versions of open snapshots, with how many snapshots share each
+    snapshots: BTreeMap<u64, usize>,
+}
+
+impl VersionState {
+    /// Latest version up to which every write has finished
+    fn visible(&self) -> u64 {
+        self.in_flight.first().map_or(self.last, |v| v - 1)
+    }
+}
+
+pub(crate) struct VersionStore {
+    state: Mutex<VersionState>,
+    /// Earlier states of documents, oldest first
+    history: RwLock<HashMap<(String, DocumentId), Vec<OldVersion>>>,
+}
+
+/// A write in progress; it becomes visible to new snapshots when dropped
+pub(crate) struct PendingWrite<'a> {
+    store: &'a VersionStore,
+    version: u64,
+}
+
+impl Drop for PendingWrite<'_> {
+    fn drop(&mut self) {
+        let mut state = self.store.state.lock();
+        state.in_flight.remove(&self.version);
+        if state.snapshots.is_empty() {
+            self.store.prune(state.visible());
+        }
+    }
+}
+
+impl VersionStore {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Mutex::new(VersionState { last: 0, in_flight: BTreeSet::new(), snapshots: BTreeMap::new() }),
+            history: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Record a document's state before a write
+    ///
+    /// Must be called with the collection's write lock held and before the
+    /// write touches the index or any page. Writes to different collections
+    /// may be in progress at the same time.
+    pub(crate) fn begin_write(&self, collection: &str, doc_id: &str, previous: Option<Document>) -> PendingWrite<'_> {
+        let version = {
+            let mut state = self.state.lock();
+            state.last += 1;
+            let version = state.last;
+            state.in_flight.insert(version);
+            version
+        };
+        self.history
+            .write()
+            .entry((collection.to_string(), doc_id.to_string()))
+            .or_default()
+            .push(OldVersion { superseded_at: version, document: previous });
+        PendingWrite { store: self, version }
+    }
+
+    fn register(&self) -> u64 {
+        let mut state = self.state.lock();
+        let version = state.visible();
+        *state.snapshots.entry(version).or_default() += 1;
+        version
+    }
+
+    fn release(&self, version: u64) {
+        let mut state = self.state.lock();
+        if let Some(count) = state.snapshots.get_mut(&version) {
+            *count -= 1;
+            if *count == 0 {
+                state.snapshots.remove(&version);
+            }
+        }
+
+        // Keep only states some remaining snapshot, or one taken now, could
+        // still read. This includes those recorded by writes in progress.
+        let oldest = state.snapshots.keys().next().copied().unwrap_or_else(|| state.visible());
+        self.prune(oldest);
+    }
+
+    /// Drop states no snapshot at `oldest` or later can read
+    fn prune(&self, oldest: u64) {
+        let mut history = self.history.write();
+        if history.is_empty() {
+            return;
+        }
+        history.retain(|_, versions| {
+            versions.retain(|v| v.superseded_at > oldest);
+            !versions.is_empty()
+        });
+    }
+
+    /// The state of a document at `version`, if it changed since then
+    fn lookup(&self, collection: &str, doc_id: &str, version: u64) -> Option<Option<Document>> {
+        let history = self.history.read();
+        let versions = history.get(&(collection.to_string(), doc_id.to_string()))?;
+        versions
+            .iter()
+            .find(|v| v.superseded_at > version)
+            .map(|v| v.document.clone())
+    }
+
+    /// Documents in `collection` that changed since `version`
+    fn changed_ids(&self, collection: &str, version: u64) -> Vec<DocumentId> {
+        self.history
+            .read()
+            .iter()
+            .filter(|((c, _), versions)| c == collection && versions.iter().any(|v| v.superseded_at > version))
+            .map(|((_, id), _)| id.clone())
+            .collect()
+    }
+}
+
+/// A consistent, read-only view of the documents as of when it was taken
+///
+/// Writes made after the snapshot was taken are invisible to it, and reading
+/// from it never waits for writers. Old document states are kept in memory
+/// while a snapshot is open, so drop snapshots when done with them.
+pub struct Snapshot<'a> {
+    executor: &'a Executor,
+    version: u64,
+}
+
+impl<'a> Snapshot<'a> {
+    pub(crate) fn new(executor: &'a Executor) -> Self {
+        let version = executor.versions().register();
+        Self { executor, version }
+    }
+
+    /// Version up to which every write is visible to this snapshot
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Find a document by ID as it was when the snapshot was taken
+    pub fn find_by_id(&self, collection: &str, doc_id: &str) -> Result<Document> {
+        // Read the current state first: a write recorded after this read
+        // started is then always found in the history below
+        let current = self.executor.find_by_id(collection, doc_id);
+        match self.executor.versions().lookup(collection, doc_id, self.version) {
+            Some(Some(document)) => Ok(document),
+            Some(None) => Err(KeraDBError::DocumentNotFound(doc_id.to_string())),
+            None => current,
+        }
+    }
+
+    /// Find all documents in a collection as they were when the snapshot was taken
+    pub fn find_all(&self, collection: &str, limit: Option<usize>, skip: Option<usize>) -> Result<Vec<Document>> {
+        let mut ids = self.executor.list_ids(collection);
+        let mut seen: HashSet<DocumentId> = ids.iter().cloned().collect();
+        ids.extend(
+            self.executor
+                .versions()
+                .changed_ids(collection, self.version)
+                .into_iter()
+                .filter(|id| seen.insert(id.clone())),
+        );
+
+        let documents = ids
+            .iter()
+            .filter_map(|id| self.find_by_id(collection, id).ok())
+            .skip(skip.unwrap_or(0))
+            .take(limit.unwrap_or(usize::MAX))
+            .collect();
+        Ok(documents)
+    }
+
+    /// Count documents in a collection as of when the snapshot was taken
+    pub fn count(&self, collection: &str) -> Result<usize> {
+        Ok(self.find_all(collection, None, None)?.len())
+    }
+}
+
+impl Drop for Snapshot<'_> {
+    fn drop(&mut self) {
+        self.executor.versions().release(self.version);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Pager;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_snapshot_ignores_later_writes() {
+        let dir = tempdir().unwrap();
+        let executor = Executor::new(Pager::create(dir.path().join("test.ndb"), 4096).unwrap(), 10);
+        let alice = executor.insert("users", json!({"name": "Alice"})).unwrap();
+        let bob = executor.insert("users", json!({"name": "Bob"})).unwrap();
+
+        let snapshot = executor.snapshot();
+        executor.update("users", &alice, json!({"name": "Alicia"})).unwrap();
+        executor.delete("users", &bob).unwrap();
+        let carol = executor.insert("users", json!({"name": "Carol"})).unwrap();
+
+        assert_eq!(snapshot.find_by_id("users", &alice).unwrap().get("name"), Some(json!("Alice")));
+        assert_eq!(snapshot.find_by_id("users", &bob).unwrap().get("name"), Some(json!("Bob")));
+        assert!(snapshot.find_by_id("users", &carol).is_err());
+        assert_eq!(snapshot.count("users").unwrap(), 2);
+
+        // A newer snapshot sees the writes; history is freed once both are dropped
+        let later = executor.snapshot();
+        assert_eq!(later.count("users").unwrap(), 2);
+        assert!(later.find_by_id("users", &bob).is_err());
+        drop(snapshot);
+        drop(later);
+        assert!(executor.versions().history.read().is_empty());
+    }
+}
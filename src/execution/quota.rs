@@ -0,0 +1,565 @@
+//! Collection quotas
+//!
+//! A [`Quota`] caps how many documents a collection holds, how many bytes
+//! they take and how large each may be, measured as serialized documents.
+//! A write that would go over fails with
+//! [`KeraDBError::QuotaExceeded`] or [`KeraDBError::DocumentTooLarge`],
+//! unless the quota evicts: then the oldest documents, in insertion order,
+//! are deleted to make room, as in a cache. Evicting a document applies the
+//! [references](super::references) to it as deleting it would: one a
+//! restricting reference keeps is passed over for the next oldest, and
+//! cascades run once the write that evicted it is made.
+//!
+//! A capped collection, made with
+//! [`Database::create_capped_collection`](crate::Database::create_capped_collection),
+//! has an evicting quota that also hands the pages of evicted and deleted
+//! documents to the documents inserted next, so once full it stops growing
+//! the data file. As pages are reused out of order, each of its documents
+//! carries its insertion sequence in its page, and each page it frees
+//! carries its name, so pages still unused when the database is closed are
+//! handed back to it when the database is opened again.
+//!
+//! Quotas are kept in catalog pages like [column projections](super::columns);
+//! the usage they are checked against is rebuilt from the documents when the
+//! database is opened.
+
+use crate::error::{KeraDBError, Result};
+use crate::execution::references::Cascaded;
+use crate::execution::Executor;
+use crate::storage::pager::Page;
+use crate::storage::Serializer;
+use crate::types::{Document, DocumentId, PageType};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Kind byte of a catalog page holding a collection's quota
+const QUOTA_PAGE: u8 = 4;
+
+/// Limits on a collection's documents; see [`Database::set_quota`](crate::Database::set_quota)
+///
+/// # Example
+/// ```ignore
+/// // Keep the last 10,000 events, dropping the oldest
+/// db.set_quota("events", Quota::new().with_max_documents(10_000).evicting())?;
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quota {
+    pub max_documents: Option<usize>,
+    /// Most bytes the collection's serialized documents take together
+    pub max_bytes: Option<u64>,
+    /// Largest serialized document
+    pub max_document_bytes: Option<usize>,
+    /// Delete the oldest documents to make room rather than fail writes
+    pub evict: bool,
+    /// Whether this is a capped collection's quota, which reuses the pages
+    /// of the documents it evicts
+    #[serde(default)]
+    pub capped: bool,
+}
+
+impl Quota {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_documents(mut self, max_documents: usize) -> Self {
+        self.max_documents = Some(max_documents);
+        self
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    pub fn with_max_document_bytes(mut self, max_document_bytes: usize) -> Self {
+        self.max_document_bytes = Some(max_document_bytes);
+        self
+    }
+
+    /// Evict the oldest documents instead of failing writes over the quota
+    pub fn evicting(mut self) -> Self {
+        self.evict = true;
+        self
+    }
+}
+
+/// What a collection's documents take, in insertion order
+#[derive(Default)]
+struct Usage {
+    bytes: u64,
+    /// Document and size at each position in insertion order: the page
+    /// number, as pages are allocated in insertion order, or in capped
+    /// collections the insertion sequence
+    order: BTreeMap<u64, (DocumentId, u64)>,
+    positions: HashMap<DocumentId, u64>,
+    /// Pages of a capped collection's deleted documents, to reuse
+    free_pages: Vec<u32>,
+}
+
+impl Usage {
+    fn set(&mut self, doc_id: &str, position: u64, size: Option<u64>) {
+        if let Some(old_position) = self.positions.remove(doc_id) {
+            if let Some((_, old)) = self.order.remove(&old_position) {
+                self.bytes -= old;
+            }
+        }
+        if let Some(size) = size {
+            self.positions.insert(doc_id.to_string(), position);
+            self.order.insert(position, (doc_id.to_string(), size));
+            self.bytes += size;
+        }
+    }
+
+    fn size(&self, doc_id: &str) -> Option<u64> {
+        self.positions.get(doc_id).and_then(|position| self.order.get(position)).map(|(_, size)| *size)
+    }
+
+    /// The oldest document other than `except` that `evictable` accepts
+    fn oldest(&self, except: &str, mut evictable: impl FnMut(&str) -> bool) -> Option<DocumentId> {
+        self.order.values().map(|(id, _)| id).find(|id| *id != except && evictable(id)).cloned()
+    }
+
+    /// A document's insertion sequence, or the next one for a new document
+    fn sequence(&self, doc_id: &str) -> u64 {
+        match self.positions.get(doc_id) {
+            Some(&sequence) => sequence,
+            None => self.order.last_key_value().map_or(1, |(&last, _)| last + 1),
+        }
+    }
+}
+
+/// A collection's quota with its catalog page and current usage
+pub(crate) struct Limited {
+    page: u32,
+    quota: Quota,
+    usage: Mutex<Usage>,
+}
+
+/// Quotas by collection
+pub(crate) type Quotas = HashMap<String, Arc<Limited>>;
+
+fn to_page_data(collection: &str, quota: &Quota) -> Result<Vec<u8>> {
+    let mut data = vec![QUOTA_PAGE];
+    data.extend_from_slice(&(collection.len() as u16).to_le_bytes());
+    data.extend_from_slice(collection.as_bytes());
+    data.extend_from_slice(&serde_json::to_vec(quota)?);
+    Ok(data)
+}
+
+/// Read a catalog page as `(collection, quota)`; `None` if it holds something else
+pub(crate) fn from_page_data(data: &[u8]) -> Result<Option<(String, Quota)>> {
+    if data.first() != Some(&QUOTA_PAGE) {
+        return Ok(None);
+    }
+    let invalid = || KeraDBError::StorageError("Invalid quota page".to_string());
+    let len = data.get(1..3).ok_or_else(invalid)?;
+    let end = 3 + u16::from_le_bytes([len[0], len[1]]) as usize;
+    let collection = String::from_utf8(data.get(3..end).ok_or_else(invalid)?.to_vec()).map_err(|_| invalid())?;
+    // The quota is followed by the page's zero padding
+    let quota = serde_json::Deserializer::from_slice(&data[end..]).into_iter().next().ok_or_else(invalid)??;
+    Ok(Some((collection, quota)))
+}
+
+impl Executor {
+    /// Limit a collection's documents, replacing any quota it had, and
+    /// return how many documents were evicted to fit it
+    ///
+    /// Fails with [`KeraDBError::QuotaExceeded`] if the collection is over a
+    /// quota that does not evict.
+    pub fn set_quota(&self, collection: &str, quota: Quota) -> Result<usize> {
+        self.set_quota_cascading(collection, quota).map(|writes| {
+            writes.iter().filter(|write| matches!(write, Cascaded::Evicted(..))).count()
+        })
+    }
+
+    /// Set a quota, also returning the documents evicted to fit it and the
+    /// writes they cascaded to
+    pub(crate) fn set_quota_cascading(&self, collection: &str, quota: Quota) -> Result<Vec<Cascaded>> {
+        let evicted = {
+            let lock = self.write_lock(collection);
+            let _write = lock.lock();
+            self.set_quota_locked(collection, quota)?
+        };
+        self.cascade_evicted(collection, evicted)
+    }
+
+    fn set_quota_locked(&self, collection: &str, quota: Quota) -> Result<Vec<Document>> {
+        if quota.capped {
+            return Err(KeraDBError::InvalidQuery(
+                "capped quotas are set by creating a capped collection".to_string(),
+            ));
+        }
+        self.check_not_capped(collection)?;
+        let mut usage = Usage::default();
+        for entry in self.entries_by_page(collection) {
+            let doc = self.read_stored(&entry)?;
+            usage.set(&doc.id, entry.page_num as u64, Some(Serializer::serialize(&doc)?.len() as u64));
+        }
+        if !quota.evict {
+            if let Some(message) = over(collection, &quota, usage.positions.len(), usage.bytes) {
+                return Err(KeraDBError::QuotaExceeded(message));
+            }
+        }
+        self.install_quota(collection, quota, usage)?;
+        self.make_room(collection, "", 0)
+    }
+
+    /// Create an empty collection holding at most `max_documents` documents
+    /// of `max_bytes` bytes together, evicting the oldest to make room
+    pub fn create_capped_collection(&self, collection: &str, max_documents: usize, max_bytes: u64) -> Result<()> {
+        let lock = self.write_lock(collection);
+        let _write = lock.lock();
+        if self.count(collection) > 0 || self.quotas().read().contains_key(collection) {
+            return Err(KeraDBError::CollectionExists(collection.to_string()));
+        }
+        let quota = Quota { capped: true, ..Quota::new().with_max_documents(max_documents).with_max_bytes(max_bytes).evicting() };
+        self.install_quota(collection, quota, Usage::default())
+    }
+
+    /// Catalog a collection's quota, replacing any it had; the caller holds
+    /// the collection's write lock
+    fn install_quota(&self, collection: &str, quota: Quota, usage: Usage) -> Result<()> {
+        let page = self.allocate_page(PageType::Meta)?;
+        self.write_page(&Page::new(page, PageType::Meta, to_page_data(collection, &quota)?))?;
+        let limited = Arc::new(Limited { page, quota, usage: Mutex::new(usage) });
+        if let Some(old) = self.quotas().write().insert(collection.to_string(), limited) {
+            self.free_catalog_page(old.page)?;
+        }
+        Ok(())
+    }
+
+    /// Lift a collection's quota; returns whether it had one
+    pub fn remove_quota(&self, collection: &str) -> Result<bool> {
+        let lock = self.write_lock(collection);
+        let _write = lock.lock();
+        self.check_not_capped(collection)?;
+        let Some(old) = self.quotas().write().remove(collection) else {
+            return Ok(false);
+        };
+        self.free_catalog_page(old.page)?;
+        Ok(true)
+    }
+
+    /// A collection's quota, if it has one
+    pub fn quota(&self, collection: &str) -> Option<Quota> {
+        self.quotas().read().get(collection).map(|limited| limited.quota)
+    }
+
+    fn check_not_capped(&self, collection: &str) -> Result<()> {
+        if self.quota(collection).is_some_and(|quota| quota.capped) {
+            return Err(KeraDBError::InvalidQuery(format!("collection '{}' is capped", collection)));
+        }
+        Ok(())
+    }
+
+    /// Fail if a serialized document is over the database's or the collection's size limit
+    pub(crate) fn check_document_size(&self, collection: &str, size: usize) -> Result<()> {
+        let quota_limit = self.quotas().read().get(collection).and_then(|limited| limited.quota.max_document_bytes);
+        match self.max_document_size().into_iter().chain(quota_limit).min() {
+            Some(limit) if size > limit => Err(KeraDBError::DocumentTooLarge { size, limit }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Make room under the collection's quota for `doc_id` at `size` bytes,
+    /// evicting the oldest other documents if the quota allows; the caller
+    /// holds the collection's write lock
+    pub(crate) fn make_room(&self, collection: &str, doc_id: &str, size: u64) -> Result<Vec<Document>> {
+        let Some(limited) = self.quotas().read().get(collection).cloned() else {
+            return Ok(Vec::new());
+        };
+        let mut evicted = Vec::new();
+        loop {
+            let oldest = {
+                let usage = limited.usage.lock();
+                let documents = usage.positions.len();
+                let (documents, bytes) = match usage.size(doc_id) {
+                    Some(old) => (documents, usage.bytes - old + size),
+                    None if doc_id.is_empty() => (documents, usage.bytes),
+                    None => (documents + 1, usage.bytes + size),
+                };
+                let Some(message) = over(collection, &limited.quota, documents, bytes) else {
+                    return Ok(evicted);
+                };
+                if !limited.quota.evict {
+                    return Err(KeraDBError::QuotaExceeded(message));
+                }
+                // A document a restricting reference keeps is passed over; if
+                // every one is kept, the write fails as a delete would
+                let mut restricted = None;
+                let oldest = usage.oldest(doc_id, |id| match self.check_restrict(collection, id) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        restricted.get_or_insert(e);
+                        false
+                    }
+                });
+                match oldest {
+                    Some(oldest) => oldest,
+                    None => return Err(restricted.unwrap_or(KeraDBError::QuotaExceeded(message))),
+                }
+            };
+            evicted.push(self.delete_locked(collection, &oldest)?);
+        }
+    }
+
+    /// Apply the cascading references to documents a quota evicted, once the
+    /// collection's write lock is released; returns the evictions followed
+    /// by the writes each cascaded to
+    pub(crate) fn cascade_evicted(&self, collection: &str, evicted: Vec<Document>) -> Result<Vec<Cascaded>> {
+        let mut writes = Vec::new();
+        for doc in evicted {
+            let cascaded = self.cascade(collection, &doc.id)?;
+            writes.push(Cascaded::Evicted(collection.to_string(), doc));
+            writes.extend(cascaded);
+        }
+        Ok(writes)
+    }
+
+    /// Record a write to a collection with a quota, at the insertion
+    /// `sequence` of a capped collection's document; `size` is `None` for a
+    /// delete
+    pub(crate) fn account(&self, collection: &str, doc_id: &str, page_num: u32, sequence: Option<u64>, size: Option<usize>) {
+        if let Some(limited) = self.quotas().read().get(collection) {
+            let mut usage = limited.usage.lock();
+            usage.set(doc_id, sequence.unwrap_or(page_num as u64), size.map(|size| size as u64));
+            if size.is_none() && limited.quota.capped {
+                usage.free_pages.push(page_num);
+            }
+        }
+    }
+
+    /// The insertion sequence to store with a document of a capped collection
+    pub(crate) fn sequence(&self, collection: &str, doc_id: &str) -> Option<u64> {
+        let quotas = self.quotas().read();
+        let limited = quotas.get(collection).filter(|limited| limited.quota.capped)?;
+        let sequence = limited.usage.lock().sequence(doc_id);
+        Some(sequence)
+    }
+
+    /// Bytes stored after each document of the collection for its sequence
+    pub(crate) fn sequence_len(&self, collection: &str) -> usize {
+        if self.quota(collection).is_some_and(|quota| quota.capped) {
+            8
+        } else {
+            0
+        }
+    }
+
+    /// A page a capped collection freed, for its next document
+    pub(crate) fn reusable_page(&self, collection: &str) -> Option<u32> {
+        self.quotas().read().get(collection)?.usage.lock().free_pages.pop()
+    }
+
+    /// Contents of the free page left by deleting a document: zeros, after
+    /// the collection's name if it is capped, so the page finds its way back
+    /// to it after reopening
+    pub(crate) fn freed_page_data(&self, collection: &str) -> Vec<u8> {
+        let mut data = vec![0u8; self.page_size() - 5];
+        if self.quota(collection).is_some_and(|quota| quota.capped) && 2 + collection.len() <= data.len() {
+            data[0..2].copy_from_slice(&(collection.len() as u16).to_le_bytes());
+            data[2..2 + collection.len()].copy_from_slice(collection.as_bytes());
+        }
+        data
+    }
+
+    /// Register a quota found in the catalog while opening; its usage is
+    /// filled once every document is indexed
+    pub(crate) fn load_quota_page(&self, page: u32, collection: String, quota: Quota) {
+        // A duplicate is left behind if a change was interrupted; the newest page wins
+        let mut quotas = self.quotas().write();
+        if quotas.get(&collection).is_none_or(|limited| limited.page < page) {
+            quotas.insert(collection, Arc::new(Limited { page, quota, usage: Mutex::default() }));
+        }
+    }
+
+    /// Fill the usage of every quota loaded from the catalog, handing capped
+    /// collections the pages they `freed`
+    pub(crate) fn fill_loaded_quotas(&self, mut freed: HashMap<String, Vec<u32>>) -> Result<()> {
+        let quotas: Vec<_> = self.quotas().read().iter().map(|(c, limited)| (c.clone(), limited.clone())).collect();
+        for (collection, limited) in quotas {
+            let mut usage = limited.usage.lock();
+            if limited.quota.capped {
+                usage.free_pages = freed.remove(&collection).unwrap_or_default();
+            }
+            for entry in self.entries_by_page(&collection) {
+                let doc = self.read_stored(&entry)?;
+                let mut position = entry.page_num as u64;
+                if limited.quota.capped {
+                    let page = self.read_page(entry.page_num)?;
+                    position = Executor::page_sequence(&page).unwrap_or(position);
+                }
+                usage.set(&doc.id, position, Some(Serializer::serialize(&doc)?.len() as u64));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The capped collection whose delete left a free page, if one did
+pub(crate) fn freed_by(page: &Page) -> Option<String> {
+    let len = page.data.get(0..2).map(|len| u16::from_le_bytes([len[0], len[1]]) as usize)?;
+    if len == 0 {
+        return None;
+    }
+    String::from_utf8(page.data.get(2..2 + len)?.to_vec()).ok()
+}
+
+/// Why a collection would be over its quota, if it would
+fn over(collection: &str, quota: &Quota, documents: usize, bytes: u64) -> Option<String> {
+    match (quota.max_documents, quota.max_bytes) {
+        (Some(max), _) if documents > max => Some(format!("collection '{}' holds at most {} documents", collection, max)),
+        (_, Some(max)) if bytes > max => Some(format!("collection '{}' holds at most {} bytes", collection, max)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Quota;
+    use crate::{Config, Database, KeraDBError, OnDelete, Reference};
+    use serde_json::json;
+
+    #[test]
+    fn test_quotas() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("quota.ndb");
+        let config = Config::default().with_max_document_size(1000);
+        let db = Database::create_with_config(&path, config.clone()).unwrap();
+        let err = db.insert("notes", json!({"text": "x".repeat(1500)})).unwrap_err();
+        assert!(matches!(err, KeraDBError::DocumentTooLarge { limit: 1000, .. }), "{}", err);
+
+        // A quota that fails writes
+        let ids: Vec<String> = (0..3).map(|n| db.insert("notes", json!({"n": n})).unwrap()).collect();
+        assert!(matches!(db.set_quota("notes", Quota::new().with_max_documents(2)), Err(KeraDBError::QuotaExceeded(_))));
+        assert_eq!(db.set_quota("notes", Quota::new().with_max_documents(4).with_max_document_bytes(100)).unwrap(), 0);
+        db.insert("notes", json!({"n": 3})).unwrap();
+        let err = db.insert("notes", json!({"n": 4})).unwrap_err();
+        assert_eq!(err.to_string(), "Quota exceeded: collection 'notes' holds at most 4 documents");
+        assert!(matches!(db.update("notes", &ids[0], json!({"text": "x".repeat(150)})), Err(KeraDBError::DocumentTooLarge { limit: 100, .. })));
+        db.update("notes", &ids[0], json!({"n": 10})).unwrap();
+
+        // An evicting quota drops the oldest documents, reported as deletes
+        let mut changes = db.watch("notes");
+        assert_eq!(db.set_quota("notes", Quota::new().with_max_documents(3).evicting()).unwrap(), 1);
+        db.insert("notes", json!({"n": 5})).unwrap();
+        let deleted: Vec<String> = std::iter::from_fn(|| changes.try_next().unwrap())
+            .filter(|event| event.operation == crate::oplog::OperationType::Delete)
+            .map(|event| event.doc_id)
+            .collect();
+        assert_eq!(deleted, ids[..2]);
+        assert!(db.find_by_id("notes", &ids[2]).is_ok());
+
+        // The quota survives reopening, with its usage
+        drop(changes);
+        db.sync().unwrap();
+        drop(db);
+        let db = Database::open_with_config(&path, config).unwrap();
+        assert_eq!(db.quota("notes").map(|q| (q.max_documents, q.evict)), Some((Some(3), true)));
+        db.insert("notes", json!({"n": 6})).unwrap();
+        let mut left: Vec<i64> = db.find_all("notes", None, None).unwrap().iter().map(|d| d.data["n"].as_i64().unwrap()).collect();
+        left.sort_unstable();
+        assert_eq!(left, [3, 5, 6]);
+
+        // A bytes quota
+        let size = |n: usize| json!({"pad": "x".repeat(n)});
+        db.set_quota("blobs", Quota::new().with_max_bytes(700)).unwrap();
+        db.insert("blobs", size(200)).unwrap();
+        db.insert("blobs", size(200)).unwrap();
+        assert!(matches!(db.insert("blobs", size(200)), Err(KeraDBError::QuotaExceeded(_))));
+        assert!(db.remove_quota("blobs").unwrap());
+        db.insert("blobs", size(200)).unwrap();
+        assert_eq!(db.quota("blobs"), None);
+    }
+
+    #[test]
+    fn test_eviction_follows_references() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::create(dir.path().join("refs.ndb")).unwrap();
+        db.set_quota("customers", Quota::new().with_max_documents(2).evicting()).unwrap();
+        db.add_reference(Reference::new("orders", "customer", "customers")).unwrap();
+        db.add_reference(Reference::new("notes", "customer", "customers").with_on_delete(OnDelete::Cascade)).unwrap();
+        for id in ["ann", "bob"] {
+            db.insert("customers", json!({"_id": id})).unwrap();
+        }
+        db.insert("orders", json!({"_id": "o1", "customer": "ann"})).unwrap();
+        db.insert("notes", json!({"_id": "n1", "customer": "bob"})).unwrap();
+
+        // The order keeps ann, so bob goes instead, and his note with him
+        let mut changes = db.watch_all();
+        db.insert("customers", json!({"_id": "cat"})).unwrap();
+        let events: Vec<(String, String)> = std::iter::from_fn(|| changes.try_next().unwrap())
+            .map(|event| (event.collection, event.doc_id))
+            .collect();
+        let expected = [("customers", "bob"), ("notes", "n1"), ("customers", "cat")];
+        assert_eq!(events, expected.map(|(c, id)| (c.to_string(), id.to_string())));
+        assert!(db.find_by_id("customers", "ann").is_ok());
+
+        // With every document kept, the insert fails
+        db.insert("orders", json!({"_id": "o2", "customer": "cat"})).unwrap();
+        let err = db.insert("customers", json!({"_id": "dan"})).unwrap_err();
+        assert!(matches!(err, KeraDBError::ReferenceViolation(_)), "{}", err);
+        assert_eq!(db.count("customers"), 2);
+    }
+
+    #[test]
+    fn test_capped_collection() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("capped.ndb");
+        let db = Database::create(&path).unwrap();
+        db.insert("events", json!({"n": 0})).unwrap();
+        assert!(matches!(db.create_capped_collection("events", 5, 1 << 20), Err(KeraDBError::CollectionExists(_))));
+        db.create_capped_collection("logs", 5, 1 << 20).unwrap();
+        assert!(matches!(db.remove_quota("logs"), Err(KeraDBError::InvalidQuery(_))));
+
+        let ids: Vec<String> = (0..5).map(|n| db.insert("logs", json!({"n": n})).unwrap()).collect();
+        let pages = db.stats().unwrap().pages.total;
+        for n in 5..20 {
+            db.insert("logs", json!({"n": n})).unwrap();
+        }
+        let numbers = |db: &Database| -> Vec<i64> {
+            let mut numbers: Vec<i64> = db.find_all("logs", None, None).unwrap().iter().map(|d| d.data["n"].as_i64().unwrap()).collect();
+            numbers.sort_unstable();
+            numbers
+        };
+        assert_eq!(numbers(&db), [15, 16, 17, 18, 19]);
+        assert_eq!(db.stats().unwrap().pages.total, pages);
+        assert!(matches!(db.find_by_id("logs", &ids[0]), Err(KeraDBError::DocumentNotFound(_))));
+
+        // A deleted document's page goes to the next one, so page order no
+        // longer follows insertion order; that survives reopening, and
+        // updates keep a document's place
+        let find = |db: &Database, n: i64| db.find_all("logs", None, None).unwrap().into_iter().find(|d| d.data["n"] == n).unwrap();
+        db.delete("logs", &find(&db, 17).id).unwrap();
+        db.insert("logs", json!({"n": 20})).unwrap();
+        db.update("logs", &find(&db, 15).id, json!({"n": 15, "seen": true})).unwrap();
+        db.sync().unwrap();
+        drop(db);
+        let db = Database::open(&path).unwrap();
+        for n in 21..24 {
+            db.insert("logs", json!({"n": n})).unwrap();
+        }
+        assert_eq!(numbers(&db), [19, 20, 21, 22, 23]);
+        assert_eq!(db.stats().unwrap().pages.total, pages);
+
+        // Pages left free when the database is closed are reused once it is
+        // opened again
+        for n in 19..22 {
+            db.delete("logs", &find(&db, n).id).unwrap();
+        }
+        db.sync().unwrap();
+        drop(db);
+        let size = std::fs::metadata(&path).unwrap().len();
+        let db = Database::open(&path).unwrap();
+        for n in 24..27 {
+            db.insert("logs", json!({"n": n})).unwrap();
+        }
+        assert_eq!(numbers(&db), [22, 23, 24, 25, 26]);
+        db.sync().unwrap();
+        drop(db);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), size);
+    }
+}
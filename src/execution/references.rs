@@ -0,0 +1,416 @@
+//! References between collections
+//!
+//! A [`Reference`] declares that a field of one collection holds the ID of a
+//! document in another, like a foreign key: `orders.customer_id ->
+//! customers._id`. The executor then refuses inserts and updates whose field
+//! names a document that does not exist (a missing or `null` field is fine),
+//! and when a referenced document is deleted it does what the reference's
+//! [`OnDelete`] says to the documents pointing at it.
+//!
+//! Each reference keeps an in-memory map from referenced IDs to the
+//! documents holding them, so deletes find those documents without a scan.
+//! As with [multikey indexes](super::multikey), the reference is kept in a
+//! catalog page and the map is rebuilt from the documents when the database
+//! is opened.
+//!
+//! The checks are made without locking the referenced collection, so a
+//! document inserted while the one it references is being deleted can
+//! escape them; cascades run after the delete that caused them, each
+//! under its own collection's lock.
+
+use crate::error::{KeraDBError, Result};
+use crate::execution::Executor;
+use crate::storage::pager::Page;
+use crate::types::{Document, DocumentId, PageType};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+
+/// Kind byte of a catalog page holding a reference
+const REFERENCE_PAGE: u8 = 5;
+
+/// What deleting a referenced document does to the documents referencing it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnDelete {
+    /// Fail the delete while any document references it
+    #[default]
+    Restrict,
+    /// Delete the referencing documents too
+    Cascade,
+    /// Set the referencing field to `null`
+    SetNull,
+}
+
+/// A field of `collection` holding IDs of documents in `target`; see
+/// [`Database::add_reference`](crate::Database::add_reference)
+///
+/// # Example
+/// ```ignore
+/// db.add_reference(Reference::new("orders", "customer_id", "customers").with_on_delete(OnDelete::Cascade))?;
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reference {
+    pub collection: String,
+    /// Field holding the referenced ID; dots reach into nested objects
+    pub field: String,
+    pub target: String,
+    pub on_delete: OnDelete,
+}
+
+impl Reference {
+    pub fn new(collection: &str, field: &str, target: &str) -> Self {
+        Self {
+            collection: collection.to_string(),
+            field: field.to_string(),
+            target: target.to_string(),
+            on_delete: OnDelete::default(),
+        }
+    }
+
+    pub fn with_on_delete(mut self, on_delete: OnDelete) -> Self {
+        self.on_delete = on_delete;
+        self
+    }
+
+    /// The ID a document's field references, if it references one; fails if
+    /// the field holds something other than a string or `null`
+    fn referenced<'a>(&self, data: &'a Value) -> Result<Option<&'a str>> {
+        match self.field.split('.').try_fold(data, |value, key| value.get(key)) {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::String(id)) => Ok(Some(id)),
+            Some(other) => Err(KeraDBError::ReferenceViolation(format!("{} holds {}, not a document ID", self, other))),
+        }
+    }
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{} -> {}._id", self.collection, self.field, self.target)
+    }
+}
+
+#[derive(Default)]
+struct Referrers {
+    /// Referencing documents by the ID they reference
+    by_target: HashMap<DocumentId, HashSet<DocumentId>>,
+    /// The ID each referencing document references
+    targets: HashMap<DocumentId, DocumentId>,
+}
+
+/// A reference with the documents following it
+pub(crate) struct Referencing {
+    reference: Reference,
+    referrers: RwLock<Referrers>,
+}
+
+impl Referencing {
+    fn new(reference: Reference) -> Self {
+        Self { reference, referrers: RwLock::new(Referrers::default()) }
+    }
+
+    /// Follow a document written to the referencing collection; `data` is
+    /// `None` for a delete
+    pub(crate) fn upsert(&self, doc_id: &str, data: Option<&Value>) {
+        let target = data.and_then(|data| self.reference.referenced(data).ok().flatten());
+        let mut referrers = self.referrers.write();
+        if let Some(old) = referrers.targets.remove(doc_id) {
+            if let Some(ids) = referrers.by_target.get_mut(&old) {
+                ids.remove(doc_id);
+                if ids.is_empty() {
+                    referrers.by_target.remove(&old);
+                }
+            }
+        }
+        if let Some(target) = target {
+            referrers.by_target.entry(target.to_string()).or_default().insert(doc_id.to_string());
+            referrers.targets.insert(doc_id.to_string(), target.to_string());
+        }
+    }
+
+    /// Documents referencing `target_id`, other than itself
+    fn referrers(&self, target_id: &str) -> Vec<DocumentId> {
+        let referrers = self.referrers.read();
+        let mut ids: Vec<DocumentId> = referrers.by_target.get(target_id).into_iter().flatten().cloned().collect();
+        if self.reference.collection == self.reference.target {
+            ids.retain(|id| id != target_id);
+        }
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Catalog page data: `[kind][JSON reference]`
+    fn to_page_data(&self) -> Result<Vec<u8>> {
+        let mut data = vec![REFERENCE_PAGE];
+        data.extend_from_slice(&serde_json::to_vec(&self.reference)?);
+        Ok(data)
+    }
+
+    /// Read a catalog page; `None` if it holds something else
+    pub(crate) fn from_page_data(data: &[u8]) -> Result<Option<Reference>> {
+        if data.first() != Some(&REFERENCE_PAGE) {
+            return Ok(None);
+        }
+        let invalid = || KeraDBError::StorageError("Invalid reference page".to_string());
+        // The reference is followed by the page's zero padding
+        let reference = serde_json::Deserializer::from_slice(&data[1..]).into_iter().next().ok_or_else(invalid)??;
+        Ok(Some(reference))
+    }
+}
+
+/// Every reference, with the catalog page holding it
+pub(crate) type References = Vec<(u32, Arc<Referencing>)>;
+
+/// A write made by a delete's [`OnDelete`] rules, or a quota's eviction
+pub(crate) enum Cascaded {
+    Deleted(String, Document),
+    Updated(String, Document),
+    /// A document deleted to make room under its collection's quota
+    Evicted(String, Document),
+}
+
+impl Executor {
+    /// Declare a reference, checking the documents already in its
+    /// collection; returns false if the field already references a collection
+    pub fn add_reference(&self, reference: Reference) -> Result<bool> {
+        let lock = self.write_lock(&reference.collection);
+        let _write = lock.lock();
+        if self.referencing(&reference.collection, &reference.field).is_some() {
+            return Ok(false);
+        }
+        let referencing = Arc::new(Referencing::new(reference));
+        for doc in self.find_all(&referencing.reference.collection, None, None)? {
+            self.check_reference(&referencing.reference, &doc.data)?;
+            referencing.upsert(&doc.id, Some(&doc.data));
+        }
+        let page_num = self.allocate_page(PageType::Meta)?;
+        self.write_page(&Page::new(page_num, PageType::Meta, referencing.to_page_data()?))?;
+        self.reference_table().write().push((page_num, referencing));
+        Ok(true)
+    }
+
+    /// Drop the reference a collection's field makes; returns whether it made one
+    pub fn drop_reference(&self, collection: &str, field: &str) -> Result<bool> {
+        let lock = self.write_lock(collection);
+        let _write = lock.lock();
+        let page_num = {
+            let mut references = self.reference_table().write();
+            let Some(position) = references
+                .iter()
+                .position(|(_, r)| r.reference.collection == collection && r.reference.field == field)
+            else {
+                return Ok(false);
+            };
+            references.remove(position).0
+        };
+        self.free_catalog_page(page_num)?;
+        Ok(true)
+    }
+
+    /// Every declared reference
+    pub fn references(&self) -> Vec<Reference> {
+        self.reference_table().read().iter().map(|(_, r)| r.reference.clone()).collect()
+    }
+
+    fn referencing(&self, collection: &str, field: &str) -> Option<Arc<Referencing>> {
+        let references = self.reference_table().read();
+        references
+            .iter()
+            .find(|(_, r)| r.reference.collection == collection && r.reference.field == field)
+            .map(|(_, r)| r.clone())
+    }
+
+    /// References made by or to a collection
+    fn references_where(&self, matches: impl Fn(&Reference) -> bool) -> Vec<Arc<Referencing>> {
+        self.reference_table().read().iter().filter(|(_, r)| matches(&r.reference)).map(|(_, r)| r.clone()).collect()
+    }
+
+    fn check_reference(&self, reference: &Reference, data: &Value) -> Result<()> {
+        match reference.referenced(data)? {
+            Some(id) if !self.contains(&reference.target, id) => Err(KeraDBError::ReferenceViolation(format!(
+                "{} names '{}', which is not in {}",
+                reference, id, reference.target
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fail if a document about to be written to a collection references
+    /// documents that do not exist
+    pub(crate) fn check_references(&self, collection: &str, data: &Value) -> Result<()> {
+        for referencing in self.references_where(|r| r.collection == collection) {
+            self.check_reference(&referencing.reference, data)?;
+        }
+        Ok(())
+    }
+
+    /// Bring the references a collection makes up to date with a write;
+    /// `data` is `None` for a delete
+    pub(crate) fn follow_references(&self, collection: &str, doc_id: &str, data: Option<&Value>) {
+        for referencing in self.references_where(|r| r.collection == collection) {
+            referencing.upsert(doc_id, data);
+        }
+    }
+
+    /// Fail if a restricting reference keeps a document from being deleted
+    pub(crate) fn check_restrict(&self, collection: &str, doc_id: &str) -> Result<()> {
+        for referencing in self.references_where(|r| r.target == collection && r.on_delete == OnDelete::Restrict) {
+            if let Some(referrer) = referencing.referrers(doc_id).first() {
+                return Err(KeraDBError::ReferenceViolation(format!(
+                    "'{}' is referenced by {} in {}",
+                    doc_id, referencing.reference, referrer
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply the cascading references to a deleted document, returning the
+    /// writes they made
+    pub(crate) fn cascade(&self, collection: &str, doc_id: &str) -> Result<Vec<Cascaded>> {
+        let mut writes = Vec::new();
+        for referencing in self.references_where(|r| r.target == collection && r.on_delete != OnDelete::Restrict) {
+            let reference = &referencing.reference;
+            for referrer in referencing.referrers(doc_id) {
+                match reference.on_delete {
+                    OnDelete::Cascade => {
+                        let (doc, cascaded) = match self.delete_cascading(&reference.collection, &referrer) {
+                            Err(KeraDBError::DocumentNotFound(_)) => continue,
+                            result => result?,
+                        };
+                        writes.push(Cascaded::Deleted(reference.collection.clone(), doc));
+                        writes.extend(cascaded);
+                    }
+                    OnDelete::SetNull => {
+                        let mut data = match self.find_by_id(&reference.collection, &referrer) {
+                            Err(KeraDBError::DocumentNotFound(_)) => continue,
+                            result => result?.data,
+                        };
+                        set_null(&mut data, &reference.field);
+                        let (doc, evicted) = self.update_evicting(&reference.collection, &referrer, data)?;
+                        writes.extend(evicted);
+                        writes.push(Cascaded::Updated(reference.collection.clone(), doc));
+                    }
+                    OnDelete::Restrict => {}
+                }
+            }
+        }
+        Ok(writes)
+    }
+
+    /// Register a reference found in the catalog while opening; it is
+    /// filled once every document is indexed
+    pub(crate) fn load_reference_page(&self, page_num: u32, reference: Reference) {
+        // A duplicate is left behind if a change was interrupted; the newest page wins
+        let mut references = self.reference_table().write();
+        let existing = references
+            .iter()
+            .position(|(_, r)| r.reference.collection == reference.collection && r.reference.field == reference.field);
+        match existing {
+            Some(i) if references[i].0 < page_num => references[i] = (page_num, Arc::new(Referencing::new(reference))),
+            Some(_) => {}
+            None => references.push((page_num, Arc::new(Referencing::new(reference)))),
+        }
+    }
+
+    /// Fill every reference loaded from the catalog
+    pub(crate) fn fill_loaded_references(&self) -> Result<()> {
+        for referencing in self.references_where(|_| true) {
+            for doc in self.find_all(&referencing.reference.collection, None, None)? {
+                referencing.upsert(&doc.id, Some(&doc.data));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Set a field, dots reaching into nested objects, to `null`
+fn set_null(data: &mut Value, field: &str) {
+    let mut value = data;
+    for key in field.split('.') {
+        match value.get_mut(key) {
+            Some(next) => value = next,
+            None => return,
+        }
+    }
+    *value = Value::Null;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OnDelete, Reference};
+    use crate::execution::Executor;
+    use crate::oplog::OperationType;
+    use crate::storage::Pager;
+    use crate::{Database, KeraDBError};
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_references() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("references.ndb");
+        let db = Database::create(&path).unwrap();
+        let alice = db.insert("customers", json!({"name": "Alice"})).unwrap();
+        let bob = db.insert("customers", json!({"name": "Bob"})).unwrap();
+        let order = db.insert("orders", json!({"customer_id": alice, "total": 10})).unwrap();
+        let dangling = db.insert("orders", json!({"customer_id": "nobody"})).unwrap();
+        assert!(matches!(
+            db.add_reference(Reference::new("orders", "customer_id", "customers")),
+            Err(KeraDBError::ReferenceViolation(_))
+        ));
+        db.delete("orders", &dangling).unwrap();
+
+        // Restrict: writes must name existing documents, which cannot be deleted
+        assert!(db.add_reference(Reference::new("orders", "customer_id", "customers")).unwrap());
+        let err = db.insert("orders", json!({"customer_id": "nobody"})).unwrap_err();
+        assert_eq!(err.to_string(), "Reference violation: orders.customer_id -> customers._id names 'nobody', which is not in customers");
+        assert!(matches!(db.update("orders", &order, json!({"customer_id": 7})), Err(KeraDBError::ReferenceViolation(_))));
+        db.insert("orders", json!({"total": 5})).unwrap();
+        assert!(matches!(db.delete("customers", &alice), Err(KeraDBError::ReferenceViolation(_))));
+        db.delete("customers", &bob).unwrap();
+
+        // Cascade, through an order's lines, and set null, surviving reopening
+        assert!(db.drop_reference("orders", "customer_id").unwrap());
+        db.add_reference(Reference::new("orders", "customer_id", "customers").with_on_delete(OnDelete::Cascade)).unwrap();
+        db.add_reference(Reference::new("lines", "order.id", "orders").with_on_delete(OnDelete::Cascade)).unwrap();
+        db.add_reference(Reference::new("reviews", "customer_id", "customers").with_on_delete(OnDelete::SetNull)).unwrap();
+        db.insert("lines", json!({"order": {"id": order}, "sku": "a1"})).unwrap();
+        let review = db.insert("reviews", json!({"customer_id": alice, "stars": 5})).unwrap();
+        db.sync().unwrap();
+        drop(db);
+        let db = Database::open(&path).unwrap();
+        assert_eq!(db.references().len(), 3);
+
+        let mut changes = db.watch_all();
+        db.delete("customers", &alice).unwrap();
+        let events: Vec<(OperationType, String)> = std::iter::from_fn(|| changes.try_next().unwrap())
+            .map(|event| (event.operation, event.collection))
+            .collect();
+        assert_eq!(
+            events,
+            [
+                (OperationType::Delete, "customers".to_string()),
+                (OperationType::Delete, "orders".to_string()),
+                (OperationType::Delete, "lines".to_string()),
+                (OperationType::Update, "reviews".to_string()),
+            ]
+        );
+        assert_eq!((db.count("orders"), db.count("lines")), (1, 0));
+        assert_eq!(db.find_by_id("reviews", &review).unwrap().data["customer_id"], Value::Null);
+    }
+
+    #[test]
+    fn test_duplicate_reference_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let executor = Executor::new(Pager::create(dir.path().join("test.ndb"), 4096).unwrap(), 10);
+        let reference = Reference::new("orders", "customer_id", "customers");
+        executor.load_reference_page(7, reference.clone());
+        executor.load_reference_page(9, reference.clone().with_on_delete(OnDelete::Cascade));
+        executor.load_reference_page(8, reference.clone().with_on_delete(OnDelete::SetNull));
+        assert_eq!(executor.references(), [reference.with_on_delete(OnDelete::Cascade)]);
+        assert_eq!(executor.reference_table().read()[0].0, 9);
+    }
+}
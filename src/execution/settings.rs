@@ -0,0 +1,74 @@
+//! Database-wide settings
+//!
+//! A setting is a JSON value under a name, kept in a catalog page like
+//! [quotas](super::quota) and loaded when the database is opened. The
+//! database stores its embedding provider's configuration here, so text
+//! search keeps working after a restart.
+
+use crate::error::{KeraDBError, Result};
+use crate::execution::Executor;
+use crate::storage::pager::Page;
+use crate::types::PageType;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Kind byte of a catalog page holding a setting
+const SETTING_PAGE: u8 = 6;
+
+/// Settings by name, with the catalog page holding each
+pub(crate) type Settings = HashMap<String, (u32, Value)>;
+
+fn to_page_data(name: &str, value: &Value) -> Result<Vec<u8>> {
+    let mut data = vec![SETTING_PAGE];
+    data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    data.extend_from_slice(name.as_bytes());
+    data.extend_from_slice(&serde_json::to_vec(value)?);
+    Ok(data)
+}
+
+/// Read a catalog page as `(name, value)`; `None` if it holds something else
+pub(crate) fn from_page_data(data: &[u8]) -> Result<Option<(String, Value)>> {
+    if data.first() != Some(&SETTING_PAGE) {
+        return Ok(None);
+    }
+    let invalid = || KeraDBError::StorageError("Invalid setting page".to_string());
+    let len = data.get(1..3).ok_or_else(invalid)?;
+    let end = 3 + u16::from_le_bytes([len[0], len[1]]) as usize;
+    let name = String::from_utf8(data.get(3..end).ok_or_else(invalid)?.to_vec()).map_err(|_| invalid())?;
+    // The value is followed by the page's zero padding
+    let value = serde_json::Deserializer::from_slice(&data[end..]).into_iter().next().ok_or_else(invalid)??;
+    Ok(Some((name, value)))
+}
+
+impl Executor {
+    /// A setting's value, if it is set
+    pub(crate) fn setting(&self, name: &str) -> Option<Value> {
+        self.settings().read().get(name).map(|(_, value)| value.clone())
+    }
+
+    /// Catalog a setting, replacing its value, or remove it with `None`
+    pub(crate) fn set_setting(&self, name: &str, value: Option<Value>) -> Result<()> {
+        let mut settings = self.settings().write();
+        let old = match value {
+            Some(value) => {
+                let page = self.allocate_page(PageType::Meta)?;
+                self.write_page(&Page::new(page, PageType::Meta, to_page_data(name, &value)?))?;
+                settings.insert(name.to_string(), (page, value))
+            }
+            None => settings.remove(name),
+        };
+        if let Some((page, _)) = old {
+            self.free_catalog_page(page)?;
+        }
+        Ok(())
+    }
+
+    /// Register a setting found in the catalog while opening
+    pub(crate) fn load_setting_page(&self, page: u32, name: String, value: Value) {
+        // A duplicate is left behind if a change was interrupted; the newest page wins
+        let mut settings = self.settings().write();
+        if settings.get(&name).is_none_or(|(old, _)| *old < page) {
+            settings.insert(name, (page, value));
+        }
+    }
+}
@@ -2,14 +2,32 @@
 //! 
 //! These functions are designed to be called from C/C++ code.
 //! All pointer arguments are checked for null before use.
+//!
+//! # Errors
+//!
+//! A function that fails returns `NULL`, `0` or `-1`, as documented on it,
+//! and records why for the calling thread: [`keradb_last_error_code`]
+//! returns a [`KeraDBErrorCode`] and [`keradb_last_error`] a message. Every
+//! function clears the record when it is called, so after a call that
+//! succeeded the code is `Ok` and the message `NULL`.
+//!
+//! # Strings
+//!
+//! Every `char *` a function returns, including [`keradb_last_error`]'s
+//! message, is a new allocation owned by the caller, who must release it
+//! with [`keradb_free_string`] exactly once, and never with `free()`.
 #![allow(clippy::not_unsafe_ptr_arg_deref)]
 
 use std::ffi::{CStr, CString};
-use std::os::raw::{c_char, c_int};
+use std::os::raw::{c_char, c_int, c_void};
 use std::ptr;
-use std::panic;
+use std::panic::{self, UnwindSafe};
+use serde::Serialize;
 use serde_json::Value;
 
+use crate::error::KeraDBError;
+use crate::vector::embedding::{EmbedFn, EmbeddingConfig};
+use crate::vector::Distance;
 use crate::Database;
 
 // Opaque pointer types
@@ -18,29 +36,137 @@ pub struct KeraDB {
     _private: [u8; 0],
 }
 
+/// Why the last call on this thread failed
+///
+/// The values are part of the C API: they never change, and new codes are
+/// only ever added.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeraDBErrorCode {
+    Ok = 0,
+    /// A required pointer argument was `NULL`
+    NullArgument = 1,
+    /// A string argument was not valid UTF-8
+    InvalidUtf8 = 2,
+    /// A JSON argument did not parse
+    InvalidJson = 3,
+    /// KeraDB panicked; the database may need to be reopened
+    Panic = 4,
+    /// Anything not covered by another code
+    Other = 5,
+
+    Io = 10,
+    Serialization = 11,
+    DatabaseNotFound = 12,
+    CollectionNotFound = 13,
+    DocumentNotFound = 14,
+    InvalidFormat = 15,
+    VersionMismatch = 16,
+    ChecksumMismatch = 17,
+    Locked = 18,
+    ReadOnly = 19,
+    Config = 20,
+    InvalidQuery = 21,
+    InvalidDocument = 22,
+    DuplicateKey = 23,
+    Index = 24,
+    Transaction = 25,
+    Storage = 26,
+    Parse = 27,
+    CollectionExists = 28,
+    NotFound = 29,
+    NotImplemented = 30,
+    Vector = 31,
+    Embedding = 32,
+    OplogTruncated = 33,
+    DocumentTooLarge = 34,
+    QuotaExceeded = 35,
+    ReferenceViolation = 36,
+    EmbeddingUnavailable = 37,
+}
+
+impl From<&KeraDBError> for KeraDBErrorCode {
+    fn from(err: &KeraDBError) -> Self {
+        match err {
+            KeraDBError::Io(_) => Self::Io,
+            KeraDBError::Serialization(_) => Self::Serialization,
+            KeraDBError::DatabaseNotFound(_) => Self::DatabaseNotFound,
+            KeraDBError::CollectionNotFound(_) => Self::CollectionNotFound,
+            KeraDBError::DocumentNotFound(_) => Self::DocumentNotFound,
+            KeraDBError::InvalidFormat(_) => Self::InvalidFormat,
+            KeraDBError::VersionMismatch { .. } => Self::VersionMismatch,
+            KeraDBError::ChecksumMismatch => Self::ChecksumMismatch,
+            KeraDBError::Locked(_) => Self::Locked,
+            KeraDBError::ReadOnly => Self::ReadOnly,
+            KeraDBError::Config(_) => Self::Config,
+            KeraDBError::InvalidQuery(_) => Self::InvalidQuery,
+            KeraDBError::InvalidDocument(_) => Self::InvalidDocument,
+            KeraDBError::DuplicateKey(_) => Self::DuplicateKey,
+            KeraDBError::IndexError(_) => Self::Index,
+            KeraDBError::TransactionError(_) => Self::Transaction,
+            KeraDBError::StorageError(_) => Self::Storage,
+            KeraDBError::ParseError(_) => Self::Parse,
+            KeraDBError::CollectionExists(_) => Self::CollectionExists,
+            KeraDBError::NotFound(_) => Self::NotFound,
+            KeraDBError::NotImplemented(_) => Self::NotImplemented,
+            KeraDBError::VectorError(_) => Self::Vector,
+            KeraDBError::EmbeddingError(_) => Self::Embedding,
+            KeraDBError::OplogTruncated(_) => Self::OplogTruncated,
+            KeraDBError::DocumentTooLarge { .. } => Self::DocumentTooLarge,
+            KeraDBError::QuotaExceeded(_) => Self::QuotaExceeded,
+            KeraDBError::ReferenceViolation(_) => Self::ReferenceViolation,
+            KeraDBError::EmbeddingUnavailable(_) => Self::EmbeddingUnavailable,
+        }
+    }
+}
+
 // Error handling
 thread_local! {
-    static LAST_ERROR: std::cell::RefCell<Option<String>> = const { std::cell::RefCell::new(None) };
+    static LAST_ERROR: std::cell::RefCell<Option<(KeraDBErrorCode, String)>> = const { std::cell::RefCell::new(None) };
 }
 
-fn set_last_error(err: String) {
+fn set_last_error(code: KeraDBErrorCode, err: String) {
     LAST_ERROR.with(|e| {
-        *e.borrow_mut() = Some(err);
+        *e.borrow_mut() = Some((code, err));
     });
 }
 
+/// Record a failed database operation, after what was being attempted
+fn set_db_error(context: &str, err: KeraDBError) {
+    set_last_error(KeraDBErrorCode::from(&err), format!("{}: {}", context, err));
+}
+
+/// Run the body of an FFI function: clear the last error, and turn a panic
+/// into a `Panic` error and `fallback` rather than unwinding into C
+fn guard<T>(fallback: T, f: impl FnOnce() -> T + UnwindSafe) -> T {
+    LAST_ERROR.with(|e| e.borrow_mut().take());
+    panic::catch_unwind(f).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        set_last_error(KeraDBErrorCode::Panic, format!("KeraDB panicked: {}", message));
+        fallback
+    })
+}
+
+/// Why the last call on this thread failed, or `Ok` if it succeeded
 #[no_mangle]
-pub extern "C" fn keradb_last_error() -> *const c_char {
-    LAST_ERROR.with(|e| {
-        match e.borrow().as_ref() {
-            Some(err) => {
-                match CString::new(err.as_str()) {
-                    Ok(s) => s.into_raw(),
-                    Err(_) => ptr::null(),
-                }
-            }
-            None => ptr::null(),
-        }
+pub extern "C" fn keradb_last_error_code() -> KeraDBErrorCode {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(KeraDBErrorCode::Ok, |(code, _)| *code))
+}
+
+/// Why the last call on this thread failed, or `NULL` if it succeeded
+///
+/// Each call returns a new copy of the message, which the caller owns and
+/// must release with [`keradb_free_string`].
+#[no_mangle]
+pub extern "C" fn keradb_last_error() -> *mut c_char {
+    LAST_ERROR.with(|e| match e.borrow().as_ref() {
+        // A NUL inside the message would cut it short in C, so drop any
+        Some((_, err)) => CString::new(err.replace('\0', "")).map_or(ptr::null_mut(), CString::into_raw),
+        None => ptr::null_mut(),
     })
 }
 
@@ -56,191 +182,175 @@ pub unsafe extern "C" fn keradb_free_string(s: *mut c_char) {
     }
 }
 
+/// Borrow a string argument, recording `InvalidUtf8` if it is not UTF-8
+///
+/// The pointer must be non-null; callers check that first.
+fn str_arg<'a>(ptr: *const c_char, what: &str) -> Option<&'a str> {
+    match unsafe { CStr::from_ptr(ptr) }.to_str() {
+        Ok(s) => Some(s),
+        Err(e) => {
+            set_last_error(KeraDBErrorCode::InvalidUtf8, format!("Invalid UTF-8 in {}: {}", what, e));
+            None
+        }
+    }
+}
+
+/// Hand a string to the caller, or `NULL` if it cannot be a C string
+fn c_string(s: String, what: &str) -> *mut c_char {
+    match CString::new(s) {
+        Ok(s) => s.into_raw(),
+        Err(_) => {
+            set_last_error(KeraDBErrorCode::Other, format!("{} contains a NUL byte", what));
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Hand `value` to the caller as JSON
+fn json_string(value: &impl Serialize) -> *mut c_char {
+    match serde_json::to_string(value) {
+        Ok(json) => c_string(json, "JSON"),
+        Err(e) => {
+            set_last_error(KeraDBErrorCode::Serialization, format!("Failed to serialize JSON: {}", e));
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Parse a JSON argument, recording `InvalidJson` if it does not parse
+fn json_arg(json: &str) -> Option<Value> {
+    match serde_json::from_str(json) {
+        Ok(v) => Some(v),
+        Err(e) => {
+            set_last_error(KeraDBErrorCode::InvalidJson, format!("Invalid JSON: {}", e));
+            None
+        }
+    }
+}
+
+/// Record that a required pointer argument was `NULL`
+fn null_argument(message: &str) {
+    set_last_error(KeraDBErrorCode::NullArgument, message.to_string());
+}
+
 // Database operations
+/// Create a database file at `path`
+///
+/// Returns a handle to release with [`keradb_close`], or `NULL` on error.
 #[no_mangle]
 pub extern "C" fn keradb_create(path: *const c_char) -> *mut KeraDB {
-    let result = panic::catch_unwind(|| {
+    guard(ptr::null_mut(), || {
         if path.is_null() {
-            set_last_error("Path cannot be null".to_string());
+            null_argument("Path cannot be null");
             return ptr::null_mut();
         }
+        let Some(path) = str_arg(path, "path") else { return ptr::null_mut() };
 
-        let path_str = unsafe {
-            match CStr::from_ptr(path).to_str() {
-                Ok(s) => s,
-                Err(e) => {
-                    set_last_error(format!("Invalid UTF-8 in path: {}", e));
-                    return ptr::null_mut();
-                }
-            }
-        };
-
-        match Database::create(path_str) {
+        match Database::create(path) {
             Ok(db) => Box::into_raw(Box::new(db)) as *mut KeraDB,
             Err(e) => {
-                set_last_error(format!("Failed to create database: {}", e));
+                set_db_error("Failed to create database", e);
                 ptr::null_mut()
             }
         }
-    });
-
-    result.unwrap_or(ptr::null_mut())
+    })
 }
 
+/// Open the existing database file at `path`
+///
+/// Returns a handle to release with [`keradb_close`], or `NULL` on error.
 #[no_mangle]
 pub extern "C" fn keradb_open(path: *const c_char) -> *mut KeraDB {
-    let result = panic::catch_unwind(|| {
+    guard(ptr::null_mut(), || {
         if path.is_null() {
-            set_last_error("Path cannot be null".to_string());
+            null_argument("Path cannot be null");
             return ptr::null_mut();
         }
+        let Some(path) = str_arg(path, "path") else { return ptr::null_mut() };
 
-        let path_str = unsafe {
-            match CStr::from_ptr(path).to_str() {
-                Ok(s) => s,
-                Err(e) => {
-                    set_last_error(format!("Invalid UTF-8 in path: {}", e));
-                    return ptr::null_mut();
-                }
-            }
-        };
-
-        match Database::open(path_str) {
+        match Database::open(path) {
             Ok(db) => Box::into_raw(Box::new(db)) as *mut KeraDB,
             Err(e) => {
-                set_last_error(format!("Failed to open database: {}", e));
+                set_db_error("Failed to open database", e);
                 ptr::null_mut()
             }
         }
-    });
-
-    result.unwrap_or(ptr::null_mut())
+    })
 }
 
+/// Close a database and free its handle; `NULL` is ignored
 #[no_mangle]
 pub extern "C" fn keradb_close(db: *mut KeraDB) {
     if !db.is_null() {
-        let _ = panic::catch_unwind(|| unsafe {
+        guard((), || unsafe {
             let _ = Box::from_raw(db as *mut Database);
         });
     }
 }
 
+/// Insert a JSON object into `collection`
+///
+/// Returns the new document's ID, or `NULL` on error.
 #[no_mangle]
 pub extern "C" fn keradb_insert(
     db: *mut KeraDB,
     collection: *const c_char,
     json_data: *const c_char,
 ) -> *mut c_char {
-    let result = panic::catch_unwind(|| {
+    guard(ptr::null_mut(), || {
         if db.is_null() || collection.is_null() || json_data.is_null() {
-            set_last_error("Arguments cannot be null".to_string());
+            null_argument("Arguments cannot be null");
             return ptr::null_mut();
         }
 
         let db = unsafe { &*(db as *const Database) };
-        
-        let collection_str = unsafe {
-            match CStr::from_ptr(collection).to_str() {
-                Ok(s) => s,
-                Err(e) => {
-                    set_last_error(format!("Invalid UTF-8 in collection: {}", e));
-                    return ptr::null_mut();
-                }
-            }
-        };
+        let Some(collection) = str_arg(collection, "collection") else { return ptr::null_mut() };
+        let Some(json) = str_arg(json_data, "JSON") else { return ptr::null_mut() };
+        let Some(data) = json_arg(json) else { return ptr::null_mut() };
 
-        let json_str = unsafe {
-            match CStr::from_ptr(json_data).to_str() {
-                Ok(s) => s,
-                Err(e) => {
-                    set_last_error(format!("Invalid UTF-8 in JSON: {}", e));
-                    return ptr::null_mut();
-                }
-            }
-        };
-
-        let data: Value = match serde_json::from_str(json_str) {
-            Ok(v) => v,
+        match db.insert(collection, data) {
+            Ok(id) => c_string(id, "Document ID"),
             Err(e) => {
-                set_last_error(format!("Invalid JSON: {}", e));
-                return ptr::null_mut();
-            }
-        };
-
-        match db.insert(collection_str, data) {
-            Ok(id) => match CString::new(id) {
-                Ok(s) => s.into_raw(),
-                Err(_) => {
-                    set_last_error("Failed to create ID string".to_string());
-                    ptr::null_mut()
-                }
-            },
-            Err(e) => {
-                set_last_error(format!("Insert failed: {}", e));
+                set_db_error("Insert failed", e);
                 ptr::null_mut()
             }
         }
-    });
-
-    result.unwrap_or(ptr::null_mut())
+    })
 }
 
+/// Find a document by ID
+///
+/// Returns the document as JSON, or `NULL` on error, including when there
+/// is no such document (`DocumentNotFound`).
 #[no_mangle]
 pub extern "C" fn keradb_find_by_id(
     db: *mut KeraDB,
     collection: *const c_char,
     doc_id: *const c_char,
 ) -> *mut c_char {
-    let result = panic::catch_unwind(|| {
+    guard(ptr::null_mut(), || {
         if db.is_null() || collection.is_null() || doc_id.is_null() {
-            set_last_error("Arguments cannot be null".to_string());
+            null_argument("Arguments cannot be null");
             return ptr::null_mut();
         }
 
         let db = unsafe { &*(db as *const Database) };
-        
-        let collection_str = unsafe {
-            match CStr::from_ptr(collection).to_str() {
-                Ok(s) => s,
-                Err(e) => {
-                    set_last_error(format!("Invalid UTF-8 in collection: {}", e));
-                    return ptr::null_mut();
-                }
-            }
-        };
-
-        let id_str = unsafe {
-            match CStr::from_ptr(doc_id).to_str() {
-                Ok(s) => s,
-                Err(e) => {
-                    set_last_error(format!("Invalid UTF-8 in ID: {}", e));
-                    return ptr::null_mut();
-                }
-            }
-        };
+        let Some(collection) = str_arg(collection, "collection") else { return ptr::null_mut() };
+        let Some(id) = str_arg(doc_id, "ID") else { return ptr::null_mut() };
 
-        match db.find_by_id(collection_str, id_str) {
-            Ok(doc) => {
-                let json = serde_json::to_string(&doc).unwrap();
-                match CString::new(json) {
-                    Ok(s) => s.into_raw(),
-                    Err(_) => {
-                        set_last_error("Failed to create JSON string".to_string());
-                        ptr::null_mut()
-                    }
-                }
-            }
+        match db.find_by_id(collection, id) {
+            Ok(doc) => json_string(&doc),
             Err(e) => {
-                set_last_error(format!("Find failed: {}", e));
+                set_db_error("Find failed", e);
                 ptr::null_mut()
             }
         }
-    });
-
-    result.unwrap_or(ptr::null_mut())
+    })
 }
 
+/// Replace a document's data with a JSON object
+///
+/// Returns the updated document as JSON, or `NULL` on error.
 #[no_mangle]
 pub extern "C" fn keradb_update(
     db: *mut KeraDB,
@@ -248,73 +358,61 @@ pub extern "C" fn keradb_update(
     doc_id: *const c_char,
     json_data: *const c_char,
 ) -> *mut c_char {
-    let result = panic::catch_unwind(|| {
+    guard(ptr::null_mut(), || {
         if db.is_null() || collection.is_null() || doc_id.is_null() || json_data.is_null() {
-            set_last_error("Arguments cannot be null".to_string());
+            null_argument("Arguments cannot be null");
             return ptr::null_mut();
         }
 
         let db = unsafe { &*(db as *const Database) };
-        
-        let collection_str = unsafe { CStr::from_ptr(collection).to_str().unwrap() };
-        let id_str = unsafe { CStr::from_ptr(doc_id).to_str().unwrap() };
-        let json_str = unsafe { CStr::from_ptr(json_data).to_str().unwrap() };
+        let Some(collection) = str_arg(collection, "collection") else { return ptr::null_mut() };
+        let Some(id) = str_arg(doc_id, "ID") else { return ptr::null_mut() };
+        let Some(json) = str_arg(json_data, "JSON") else { return ptr::null_mut() };
+        let Some(data) = json_arg(json) else { return ptr::null_mut() };
 
-        let data: Value = match serde_json::from_str(json_str) {
-            Ok(v) => v,
+        match db.update(collection, id, data) {
+            Ok(doc) => json_string(&doc),
             Err(e) => {
-                set_last_error(format!("Invalid JSON: {}", e));
-                return ptr::null_mut();
-            }
-        };
-
-        match db.update(collection_str, id_str, data) {
-            Ok(doc) => {
-                let json = serde_json::to_string(&doc).unwrap();
-                match CString::new(json) {
-                    Ok(s) => s.into_raw(),
-                    Err(_) => ptr::null_mut()
-                }
-            }
-            Err(e) => {
-                set_last_error(format!("Update failed: {}", e));
+                set_db_error("Update failed", e);
                 ptr::null_mut()
             }
         }
-    });
-
-    result.unwrap_or(ptr::null_mut())
+    })
 }
 
+/// Delete a document by ID
+///
+/// Returns 1 if it was deleted, or 0 on error.
 #[no_mangle]
 pub extern "C" fn keradb_delete(
     db: *mut KeraDB,
     collection: *const c_char,
     doc_id: *const c_char,
 ) -> c_int {
-    let result = panic::catch_unwind(|| {
+    guard(0, || {
         if db.is_null() || collection.is_null() || doc_id.is_null() {
-            set_last_error("Arguments cannot be null".to_string());
+            null_argument("Arguments cannot be null");
             return 0;
         }
 
         let db = unsafe { &*(db as *const Database) };
-        
-        let collection_str = unsafe { CStr::from_ptr(collection).to_str().unwrap() };
-        let id_str = unsafe { CStr::from_ptr(doc_id).to_str().unwrap() };
+        let Some(collection) = str_arg(collection, "collection") else { return 0 };
+        let Some(id) = str_arg(doc_id, "ID") else { return 0 };
 
-        match db.delete(collection_str, id_str) {
+        match db.delete(collection, id) {
             Ok(_) => 1,
             Err(e) => {
-                set_last_error(format!("Delete failed: {}", e));
+                set_db_error("Delete failed", e);
                 0
             }
         }
-    });
-
-    result.unwrap_or(0)
+    })
 }
 
+/// Documents in `collection`, skipping `skip` and returning at most
+/// `limit`; pass -1 for either to not skip or not limit
+///
+/// Returns a JSON array, or `NULL` on error.
 #[no_mangle]
 pub extern "C" fn keradb_find_all(
     db: *mut KeraDB,
@@ -322,80 +420,71 @@ pub extern "C" fn keradb_find_all(
     limit: c_int,
     skip: c_int,
 ) -> *mut c_char {
-    let result = panic::catch_unwind(|| {
+    guard(ptr::null_mut(), || {
         if db.is_null() || collection.is_null() {
-            set_last_error("Arguments cannot be null".to_string());
+            null_argument("Arguments cannot be null");
             return ptr::null_mut();
         }
 
         let db = unsafe { &*(db as *const Database) };
-        let collection_str = unsafe { CStr::from_ptr(collection).to_str().unwrap() };
+        let Some(collection) = str_arg(collection, "collection") else { return ptr::null_mut() };
 
         let limit_opt = if limit < 0 { None } else { Some(limit as usize) };
         let skip_opt = if skip < 0 { None } else { Some(skip as usize) };
 
-        match db.find_all(collection_str, limit_opt, skip_opt) {
-            Ok(docs) => {
-                let json = serde_json::to_string(&docs).unwrap();
-                match CString::new(json) {
-                    Ok(s) => s.into_raw(),
-                    Err(_) => ptr::null_mut()
-                }
-            }
+        match db.find_all(collection, limit_opt, skip_opt) {
+            Ok(docs) => json_string(&docs),
             Err(e) => {
-                set_last_error(format!("Find all failed: {}", e));
+                set_db_error("Find all failed", e);
                 ptr::null_mut()
             }
         }
-    });
-
-    result.unwrap_or(ptr::null_mut())
+    })
 }
 
+/// Number of documents in `collection`, or -1 on error
 #[no_mangle]
 pub extern "C" fn keradb_count(
     db: *mut KeraDB,
     collection: *const c_char,
 ) -> c_int {
-    let result = panic::catch_unwind(|| {
+    guard(-1, || {
         if db.is_null() || collection.is_null() {
+            null_argument("Arguments cannot be null");
             return -1;
         }
 
         let db = unsafe { &*(db as *const Database) };
-        let collection_str = unsafe { CStr::from_ptr(collection).to_str().unwrap() };
-
-        db.count(collection_str) as c_int
-    });
+        let Some(collection) = str_arg(collection, "collection") else { return -1 };
 
-    result.unwrap_or(-1)
+        c_int::try_from(db.count(collection)).unwrap_or(c_int::MAX)
+    })
 }
 
+/// Collections and their document counts
+///
+/// Returns a JSON array of `[name, count]` pairs, or `NULL` on error.
 #[no_mangle]
 pub extern "C" fn keradb_list_collections(db: *mut KeraDB) -> *mut c_char {
-    let result = panic::catch_unwind(|| {
+    guard(ptr::null_mut(), || {
         if db.is_null() {
-            set_last_error("Database pointer cannot be null".to_string());
+            null_argument("Database pointer cannot be null");
             return ptr::null_mut();
         }
 
         let db = unsafe { &*(db as *const Database) };
-        let collections = db.list_collections();
-        
-        let json = serde_json::to_string(&collections).unwrap();
-        match CString::new(json) {
-            Ok(s) => s.into_raw(),
-            Err(_) => ptr::null_mut()
-        }
-    });
-
-    result.unwrap_or(ptr::null_mut())
+        json_string(&db.list_collections())
+    })
 }
 
+/// Flush the database to disk
+///
+/// Returns 1 on success, or 0 on error.
 #[no_mangle]
 pub extern "C" fn keradb_sync(db: *mut KeraDB) -> c_int {
-    let result = panic::catch_unwind(|| {
+    guard(0, || {
         if db.is_null() {
+            null_argument("Database pointer cannot be null");
             return 0;
         }
 
@@ -403,11 +492,554 @@ pub extern "C" fn keradb_sync(db: *mut KeraDB) -> c_int {
         match db.sync() {
             Ok(_) => 1,
             Err(e) => {
-                set_last_error(format!("Sync failed: {}", e));
+                set_db_error("Sync failed", e);
                 0
             }
         }
-    });
+    })
+}
+
+// Vector operations
+/// Create a vector collection for `dimensions`-long embeddings
+///
+/// `distance` is `"cosine"`, `"euclidean"`, `"dot"` or `"manhattan"`, or
+/// `NULL` for cosine. Returns 1 on success, or 0 on error.
+#[no_mangle]
+pub extern "C" fn keradb_create_vector_collection(
+    db: *mut KeraDB,
+    name: *const c_char,
+    dimensions: usize,
+    distance: *const c_char,
+) -> c_int {
+    guard(0, || {
+        if db.is_null() || name.is_null() {
+            null_argument("Arguments cannot be null");
+            return 0;
+        }
+
+        let db = unsafe { &*(db as *const Database) };
+        let Some(name) = str_arg(name, "name") else { return 0 };
+        let mut config = db.config().vector_config(dimensions);
+        if !distance.is_null() {
+            let Some(distance) = str_arg(distance, "distance") else { return 0 };
+            match Distance::from_name(distance) {
+                Some(distance) => config = config.with_distance(distance),
+                None => {
+                    set_db_error("Create vector collection failed", KeraDBError::InvalidQuery(format!("Unknown distance metric: {}", distance)));
+                    return 0;
+                }
+            }
+        }
+
+        match db.create_vector_collection(name, config) {
+            Ok(()) => 1,
+            Err(e) => {
+                set_db_error("Create vector collection failed", e);
+                0
+            }
+        }
+    })
+}
+
+/// Insert the `dimensions` floats at `embedding` into `collection`, with
+/// optional JSON `metadata` (`NULL` for none)
+///
+/// Stores the new vector's ID in `*id` and returns 1, or returns 0 on error.
+#[no_mangle]
+pub extern "C" fn keradb_insert_vector(
+    db: *mut KeraDB,
+    collection: *const c_char,
+    embedding: *const f32,
+    dimensions: usize,
+    metadata: *const c_char,
+    id: *mut u64,
+) -> c_int {
+    guard(0, || {
+        if db.is_null() || collection.is_null() || embedding.is_null() || id.is_null() {
+            null_argument("Arguments cannot be null");
+            return 0;
+        }
+
+        let db = unsafe { &*(db as *const Database) };
+        let Some(collection) = str_arg(collection, "collection") else { return 0 };
+        let metadata = if metadata.is_null() {
+            None
+        } else {
+            let Some(json) = str_arg(metadata, "metadata") else { return 0 };
+            let Some(metadata) = json_arg(json) else { return 0 };
+            Some(metadata)
+        };
+        let embedding = unsafe { std::slice::from_raw_parts(embedding, dimensions) }.to_vec();
+
+        match db.insert_vector(collection, embedding, metadata) {
+            Ok(new_id) => {
+                unsafe { *id = new_id };
+                1
+            }
+            Err(e) => {
+                set_db_error("Insert vector failed", e);
+                0
+            }
+        }
+    })
+}
+
+/// The `k` vectors in `collection` nearest the `dimensions` floats at `query`
+///
+/// Returns a JSON array of `{"document", "score", "rank"}` objects, nearest
+/// first, or `NULL` on error.
+#[no_mangle]
+pub extern "C" fn keradb_vector_search(
+    db: *mut KeraDB,
+    collection: *const c_char,
+    query: *const f32,
+    dimensions: usize,
+    k: usize,
+) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        if db.is_null() || collection.is_null() || query.is_null() {
+            null_argument("Arguments cannot be null");
+            return ptr::null_mut();
+        }
+
+        let db = unsafe { &*(db as *const Database) };
+        let Some(collection) = str_arg(collection, "collection") else { return ptr::null_mut() };
+        let query = unsafe { std::slice::from_raw_parts(query, dimensions) }.to_vec();
+
+        match db.vector_search(collection, &query, k) {
+            Ok(results) => json_string(&results),
+            Err(e) => {
+                set_db_error("Vector search failed", e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Find a vector by ID
+///
+/// Returns it as JSON, or `NULL` on error, including when there is no such
+/// vector (`NotFound`).
+#[no_mangle]
+pub extern "C" fn keradb_get_vector(db: *mut KeraDB, collection: *const c_char, id: u64) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        if db.is_null() || collection.is_null() {
+            null_argument("Arguments cannot be null");
+            return ptr::null_mut();
+        }
+
+        let db = unsafe { &*(db as *const Database) };
+        let Some(collection) = str_arg(collection, "collection") else { return ptr::null_mut() };
+
+        match db.get_vector(collection, id) {
+            Ok(Some(vector)) => json_string(&vector),
+            Ok(None) => {
+                set_db_error("Get vector failed", KeraDBError::NotFound(format!("Vector {}", id)));
+                ptr::null_mut()
+            }
+            Err(e) => {
+                set_db_error("Get vector failed", e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Delete a vector by ID
+///
+/// Returns 1 if it was deleted, 0 if there was no such vector, or -1 on error.
+#[no_mangle]
+pub extern "C" fn keradb_delete_vector(db: *mut KeraDB, collection: *const c_char, id: u64) -> c_int {
+    guard(-1, || {
+        if db.is_null() || collection.is_null() {
+            null_argument("Arguments cannot be null");
+            return -1;
+        }
+
+        let db = unsafe { &*(db as *const Database) };
+        let Some(collection) = str_arg(collection, "collection") else { return -1 };
+
+        match db.delete_vector(collection, id) {
+            Ok(deleted) => deleted as c_int,
+            Err(e) => {
+                set_db_error("Delete vector failed", e);
+                -1
+            }
+        }
+    })
+}
+
+/// Vector collections and their vector counts
+///
+/// Returns a JSON array of `[name, count]` pairs, or `NULL` on error.
+#[no_mangle]
+pub extern "C" fn keradb_list_vector_collections(db: *mut KeraDB) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        if db.is_null() {
+            null_argument("Database pointer cannot be null");
+            return ptr::null_mut();
+        }
+
+        let db = unsafe { &*(db as *const Database) };
+        json_string(&db.list_vector_collections())
+    })
+}
+
+/// Embeds `text` by writing `dimensions` floats to `out`, returning 0 on
+/// success or anything else on failure; `user_data` is the pointer given to
+/// [`keradb_set_embedding_callback`]
+pub type KeraDBEmbedFn =
+    Option<unsafe extern "C" fn(text: *const c_char, out: *mut f32, dimensions: usize, user_data: *mut c_void) -> c_int>;
+
+/// An embedding callback's `user_data`, which the caller vouches may be used
+/// from any thread
+struct UserData(*mut c_void);
+
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+impl UserData {
+    fn get(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+/// Embed text with `embed`, a model the application runs itself, for vector
+/// collections created after this call and those already open
+///
+/// `model` names the model in the collections it builds. `embed` may be
+/// called from any thread, until the database is closed or another provider
+/// is set. Returns 1 on success, or 0 on error.
+#[no_mangle]
+pub extern "C" fn keradb_set_embedding_callback(
+    db: *mut KeraDB,
+    model: *const c_char,
+    dimensions: usize,
+    embed: KeraDBEmbedFn,
+    user_data: *mut c_void,
+) -> c_int {
+    guard(0, || {
+        let Some(callback) = embed.filter(|_| !db.is_null() && !model.is_null()) else {
+            null_argument("Arguments cannot be null");
+            return 0;
+        };
+
+        let db = unsafe { &*(db as *const Database) };
+        let Some(model) = str_arg(model, "model") else { return 0 };
+        let name = model.to_string();
+        let user_data = UserData(user_data);
+        let embed = EmbedFn::new(move |text| {
+            let text = CString::new(text).map_err(|_| KeraDBError::EmbeddingError("Text contains a NUL byte".into()))?;
+            let mut out = vec![0.0f32; dimensions];
+            match unsafe { callback(text.as_ptr(), out.as_mut_ptr(), dimensions, user_data.get()) } {
+                0 => Ok(out),
+                code => Err(KeraDBError::EmbeddingError(format!("Embedding callback for '{}' returned {}", name, code))),
+            }
+        });
+
+        match db.set_embedding_provider(EmbeddingConfig::Custom { model: model.to_string(), dimensions, embed }) {
+            Ok(()) => 1,
+            Err(e) => {
+                set_db_error("Set embedding callback failed", e);
+                0
+            }
+        }
+    })
+}
+
+/// Embed `text` and insert it into `collection`, with optional JSON
+/// `metadata` (`NULL` for none)
+///
+/// Stores the new vector's ID in `*id` and returns 1, or returns 0 on error.
+#[no_mangle]
+pub extern "C" fn keradb_insert_text(
+    db: *mut KeraDB,
+    collection: *const c_char,
+    text: *const c_char,
+    metadata: *const c_char,
+    id: *mut u64,
+) -> c_int {
+    guard(0, || {
+        if db.is_null() || collection.is_null() || text.is_null() || id.is_null() {
+            null_argument("Arguments cannot be null");
+            return 0;
+        }
+
+        let db = unsafe { &*(db as *const Database) };
+        let Some(collection) = str_arg(collection, "collection") else { return 0 };
+        let Some(text) = str_arg(text, "text") else { return 0 };
+        let metadata = if metadata.is_null() {
+            None
+        } else {
+            let Some(json) = str_arg(metadata, "metadata") else { return 0 };
+            let Some(metadata) = json_arg(json) else { return 0 };
+            Some(metadata)
+        };
+
+        match db.insert_text(collection, text, metadata) {
+            Ok(new_id) => {
+                unsafe { *id = new_id };
+                1
+            }
+            Err(e) => {
+                set_db_error("Insert text failed", e);
+                0
+            }
+        }
+    })
+}
+
+/// The `k` vectors in `collection` nearest the embedding of `query`
+///
+/// Returns a JSON array like [`keradb_vector_search`]'s, or `NULL` on error.
+#[no_mangle]
+pub extern "C" fn keradb_vector_search_text(
+    db: *mut KeraDB,
+    collection: *const c_char,
+    query: *const c_char,
+    k: usize,
+) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        if db.is_null() || collection.is_null() || query.is_null() {
+            null_argument("Arguments cannot be null");
+            return ptr::null_mut();
+        }
+
+        let db = unsafe { &*(db as *const Database) };
+        let Some(collection) = str_arg(collection, "collection") else { return ptr::null_mut() };
+        let Some(query) = str_arg(query, "query") else { return ptr::null_mut() };
+
+        match db.vector_search_text(collection, query, k) {
+            Ok(results) => json_string(&results),
+            Err(e) => {
+                set_db_error("Vector search failed", e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use tempfile::tempdir;
+
+    /// Take the last error's message, freeing it as C callers must
+    fn last_error() -> Option<String> {
+        let message = keradb_last_error();
+        if message.is_null() {
+            return None;
+        }
+        let text = unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned();
+        unsafe { keradb_free_string(message) };
+        Some(text)
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let dir = tempdir().unwrap();
+        let path = CString::new(dir.path().join("missing.ndb").to_str().unwrap()).unwrap();
+
+        assert!(keradb_open(path.as_ptr()).is_null());
+        assert_ne!(keradb_last_error_code(), KeraDBErrorCode::Ok);
+        assert!(last_error().unwrap().starts_with("Failed to open database"));
+        // Each call returns its own copy
+        assert_eq!(last_error(), last_error());
 
-    result.unwrap_or(0)
+        assert!(keradb_open(ptr::null()).is_null());
+        assert_eq!(keradb_last_error_code(), KeraDBErrorCode::NullArgument);
+
+        let db = keradb_create(path.as_ptr());
+        assert!(!db.is_null());
+        assert_eq!((keradb_last_error_code(), last_error()), (KeraDBErrorCode::Ok, None));
+
+        let users = CString::new("users").unwrap();
+        let nobody = CString::new("nobody").unwrap();
+        assert!(keradb_find_by_id(db, users.as_ptr(), nobody.as_ptr()).is_null());
+        assert_eq!(keradb_last_error_code(), KeraDBErrorCode::DocumentNotFound);
+
+        let bad_json = CString::new("{").unwrap();
+        assert!(keradb_insert(db, users.as_ptr(), bad_json.as_ptr()).is_null());
+        assert_eq!(keradb_last_error_code(), KeraDBErrorCode::InvalidJson);
+
+        keradb_close(db);
+    }
+
+    #[test]
+    fn test_vectors() {
+        let dir = tempdir().unwrap();
+        let path = CString::new(dir.path().join("test.ndb").to_str().unwrap()).unwrap();
+        let db = keradb_create(path.as_ptr());
+        let owned = |s: *mut c_char| {
+            assert!(!s.is_null(), "{:?}", last_error());
+            let text = unsafe { CStr::from_ptr(s) }.to_string_lossy().into_owned();
+            unsafe { keradb_free_string(s) };
+            text
+        };
+        let name = CString::new("embeddings").unwrap();
+        let euclidean = CString::new("euclidean").unwrap();
+        assert_eq!(keradb_create_vector_collection(db, name.as_ptr(), 2, euclidean.as_ptr()), 1);
+
+        let metadata = CString::new(r#"{"n":1}"#).unwrap();
+        let (mut near, mut far) = (0, 0);
+        assert_eq!(keradb_insert_vector(db, name.as_ptr(), [1.0f32, 0.0].as_ptr(), 2, metadata.as_ptr(), &mut near), 1);
+        assert_eq!(keradb_insert_vector(db, name.as_ptr(), [0.0f32, 5.0].as_ptr(), 2, ptr::null(), &mut far), 1);
+        assert_eq!(keradb_insert_vector(db, name.as_ptr(), [1.0f32].as_ptr(), 1, ptr::null(), &mut far), 0);
+        assert_eq!(keradb_last_error_code(), KeraDBErrorCode::InvalidFormat);
+
+        let hits: Value = serde_json::from_str(&owned(keradb_vector_search(db, name.as_ptr(), [0.9f32, 0.1].as_ptr(), 2, 1))).unwrap();
+        assert_eq!(hits[0]["document"]["id"], near);
+        assert_eq!(hits[0]["document"]["metadata"]["n"], 1);
+
+        assert_eq!(keradb_delete_vector(db, name.as_ptr(), far), 1);
+        assert_eq!(keradb_delete_vector(db, name.as_ptr(), far), 0);
+        assert!(keradb_get_vector(db, name.as_ptr(), far).is_null());
+        assert_eq!(keradb_last_error_code(), KeraDBErrorCode::NotFound);
+        assert_eq!(owned(keradb_list_vector_collections(db)), r#"[["embeddings",1]]"#);
+        keradb_close(db);
+    }
+
+    #[test]
+    fn test_embedding_callback() {
+        /// Embeds a text as (its length, the number in `user_data`); fails on "fail"
+        unsafe extern "C" fn embed(text: *const c_char, out: *mut f32, dimensions: usize, user_data: *mut c_void) -> c_int {
+            let text = CStr::from_ptr(text).to_bytes();
+            if text == b"fail" {
+                return 7;
+            }
+            let out = std::slice::from_raw_parts_mut(out, dimensions);
+            out[0] = text.len() as f32;
+            out[1] = *(user_data as *const f32);
+            0
+        }
+
+        let dir = tempdir().unwrap();
+        let path = CString::new(dir.path().join("test.ndb").to_str().unwrap()).unwrap();
+        let db = keradb_create(path.as_ptr());
+        let model = CString::new("lengths").unwrap();
+        let mut scale = 1.0f32;
+        assert_eq!(keradb_set_embedding_callback(db, model.as_ptr(), 2, Some(embed), &mut scale as *mut f32 as *mut c_void), 1);
+        assert_eq!(keradb_set_embedding_callback(db, model.as_ptr(), 2, None, ptr::null_mut()), 0);
+        assert_eq!(keradb_last_error_code(), KeraDBErrorCode::NullArgument);
+
+        let name = CString::new("texts").unwrap();
+        let euclidean = CString::new("euclidean").unwrap();
+        assert_eq!(keradb_create_vector_collection(db, name.as_ptr(), 2, euclidean.as_ptr()), 1);
+        let mut id = 0;
+        for text in ["a", "abcdef"] {
+            let text = CString::new(text).unwrap();
+            assert_eq!(keradb_insert_text(db, name.as_ptr(), text.as_ptr(), ptr::null(), &mut id), 1);
+        }
+        let fail = CString::new("fail").unwrap();
+        assert_eq!(keradb_insert_text(db, name.as_ptr(), fail.as_ptr(), ptr::null(), &mut id), 0);
+        assert_eq!(keradb_last_error_code(), KeraDBErrorCode::Embedding);
+
+        let query = CString::new("abcde").unwrap();
+        let hits = keradb_vector_search_text(db, name.as_ptr(), query.as_ptr(), 1);
+        let json: Value = serde_json::from_str(&unsafe { CStr::from_ptr(hits) }.to_string_lossy()).unwrap();
+        unsafe { keradb_free_string(hits) };
+        assert_eq!(json[0]["document"]["text"], "abcdef");
+        keradb_close(db);
+    }
+
+    /// include/keradb.h is checked in, so make sure it still declares what
+    /// this module exports; regenerate it with `make header` if not
+    #[test]
+    fn test_header_in_sync() {
+        let header = include_str!("../include/keradb.h");
+        let source = include_str!("ffi.rs");
+
+        let declared: Vec<&str> = header
+            .lines()
+            .filter(|l| !l.starts_with([' ', '#', '/']))
+            .filter_map(|l| l.split('(').next()?.rsplit([' ', '*']).next())
+            .filter(|name| name.starts_with("keradb_"))
+            .collect();
+        let exported: Vec<&str> = source
+            .lines()
+            .filter_map(|l| l.strip_prefix("pub extern \"C\" fn ").or_else(|| l.strip_prefix("pub unsafe extern \"C\" fn ")))
+            .filter_map(|l| l.split('(').next())
+            .collect();
+        assert_eq!(declared, exported);
+
+        let body = source.split("pub enum KeraDBErrorCode {").nth(1).unwrap();
+        let codes: Vec<String> = body[..body.find("\n}").unwrap()]
+            .lines()
+            .map(str::trim)
+            .filter(|l| l.contains(" = "))
+            .map(|l| format!("  KeraDBErrorCode_{}", l))
+            .collect();
+        let header_codes: Vec<String> = header.lines().filter(|l| l.starts_with("  KeraDBErrorCode_")).map(String::from).collect();
+        assert_eq!(header_codes, codes);
+
+        assert!(header.contains(&format!("#define KERADB_VERSION \"{}\"", env!("CARGO_PKG_VERSION"))));
+    }
+
+    /// Check a call's result against the last-error record: a failure value
+    /// must come with a code and a message, a success with neither, and
+    /// nothing may have panicked
+    fn check_outcome(failed: bool) -> std::result::Result<(), TestCaseError> {
+        let code = keradb_last_error_code();
+        let message = last_error();
+        prop_assert_ne!(code, KeraDBErrorCode::Panic, "{:?}", message);
+        prop_assert_eq!(failed, code != KeraDBErrorCode::Ok, "code {:?}, message {:?}", code, message);
+        prop_assert_eq!(failed, message.is_some());
+        Ok(())
+    }
+
+    /// Take a string returned to C, checking the outcome and freeing it
+    fn take(s: *mut c_char) -> std::result::Result<Option<String>, TestCaseError> {
+        check_outcome(s.is_null())?;
+        if s.is_null() {
+            return Ok(None);
+        }
+        let text = unsafe { CStr::from_ptr(s) }.to_string_lossy().into_owned();
+        unsafe { keradb_free_string(s) };
+        Ok(Some(text))
+    }
+
+    /// A C string argument: arbitrary bytes, text or JSON, never with a NUL
+    fn c_arg() -> impl Strategy<Value = CString> {
+        prop_oneof![
+            prop::collection::vec(1..=u8::MAX, 0..24),
+            "[^\\x00]{0,24}".prop_map(String::into_bytes),
+            "\\{(\"[a-z_]{1,4}\": ?(-?[0-9]{1,3}|\"[a-z]{0,4}\"|null|\\[\\]),? ?){0,3}\\}".prop_map(String::into_bytes),
+        ]
+        .prop_map(|bytes| CString::new(bytes).unwrap())
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(128))]
+
+        /// Every function taking strings, fed whatever a C caller could
+        /// pass: none may panic or unwind, and each reports its outcome
+        #[test]
+        fn fuzz_string_arguments(
+            collection in c_arg(),
+            id in c_arg(),
+            json in c_arg(),
+            limit in any::<c_int>(),
+            skip in any::<c_int>(),
+        ) {
+            let dir = tempdir().unwrap();
+            let path = CString::new(dir.path().join("fuzz.ndb").to_str().unwrap()).unwrap();
+            let db = keradb_create(path.as_ptr());
+            prop_assert!(!db.is_null());
+
+            let inserted = take(keradb_insert(db, collection.as_ptr(), json.as_ptr()))?;
+            if let Some(new_id) = inserted {
+                let new_id = CString::new(new_id).unwrap();
+                prop_assert!(take(keradb_find_by_id(db, collection.as_ptr(), new_id.as_ptr()))?.is_some());
+            }
+            take(keradb_find_by_id(db, collection.as_ptr(), id.as_ptr()))?;
+            take(keradb_update(db, collection.as_ptr(), id.as_ptr(), json.as_ptr()))?;
+            take(keradb_find_all(db, collection.as_ptr(), limit, skip))?;
+            take(keradb_list_collections(db))?;
+            check_outcome(keradb_count(db, collection.as_ptr()) < 0)?;
+            check_outcome(keradb_delete(db, collection.as_ptr(), id.as_ptr()) == 0)?;
+            check_outcome(keradb_sync(db) == 0)?;
+            keradb_close(db);
+        }
+    }
 }
@@ -0,0 +1,181 @@
+//! Background flushing of writes
+//!
+//! Under [`Durability::Normal`](crate::Durability::Normal) writes reach the
+//! disk only when [`Database::sync`](crate::Database::sync) is called, or
+//! when the database is dropped for vector collections. A [`Flusher`] does
+//! this on a thread of its own instead, so writers never wait for an
+//! `fsync`, while a crash loses at most about one interval of writes.
+//!
+//! The flusher wakes every interval, and earlier when writers have left more
+//! than [`Config::max_dirty_pages`](crate::Config::max_dirty_pages) pages
+//! unsynced. Each time it syncs the data file if anything was written to it
+//! and saves vector collections that changed. Under
+//! [`Durability::Full`](crate::Durability::Full) documents are synced by
+//! every write already, so it only saves vector collections.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let db = Arc::new(Database::open("app.ndb")?);
+//! let flusher = Flusher::start(&db, Duration::from_secs(1));
+//! // ... writes ...
+//! flusher.stop(); // flushes once more
+//! ```
+
+use crate::Database;
+use parking_lot::{Condvar, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+#[derive(Default)]
+struct State {
+    stop: bool,
+    /// Set by writers past the dirty page limit
+    wake: bool,
+}
+
+/// How writers and [`Flusher::stop`] reach the flusher thread
+#[derive(Default)]
+pub(crate) struct Signal {
+    state: Mutex<State>,
+    condvar: Condvar,
+    flushes: AtomicU64,
+}
+
+impl Signal {
+    /// Have the flusher run now rather than at the end of its interval
+    pub(crate) fn wake(&self) {
+        let mut state = self.state.lock();
+        if !state.wake {
+            state.wake = true;
+            self.condvar.notify_one();
+        }
+    }
+}
+
+/// A thread flushing a database's writes in the background; stops, after
+/// one last flush, when stopped or dropped, or once the database is dropped
+pub struct Flusher {
+    signal: Arc<Signal>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Flusher {
+    /// Start flushing `db` every `interval`, replacing any flusher it had
+    pub fn start(db: &Arc<Database>, interval: Duration) -> Self {
+        let signal = Arc::new(Signal::default());
+        db.set_flush_signal(Some(signal.clone()));
+        let weak = Arc::downgrade(db);
+        let thread_signal = signal.clone();
+        let handle = std::thread::Builder::new()
+            .name("keradb-flusher".to_string())
+            .spawn(move || run(weak, &thread_signal, interval))
+            .expect("failed to spawn the flusher thread");
+        Self { signal, handle: Some(handle) }
+    }
+
+    /// Flushes done so far
+    pub fn flushes(&self) -> u64 {
+        self.signal.flushes.load(Ordering::Relaxed)
+    }
+
+    /// Flush once more and stop the thread
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.signal.state.lock().stop = true;
+        self.signal.condvar.notify_one();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for Flusher {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn run(db: Weak<Database>, signal: &Arc<Signal>, interval: Duration) {
+    loop {
+        let stop = {
+            let mut state = signal.state.lock();
+            if !state.stop && !state.wake {
+                signal.condvar.wait_for(&mut state, interval);
+            }
+            state.wake = false;
+            state.stop
+        };
+        // Holding the database only while flushing lets it be dropped in between
+        let Some(db) = db.upgrade() else { return };
+        match db.flush() {
+            Ok(()) => {
+                signal.flushes.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(e) => tracing::warn!("Background flush failed: {}", e),
+        }
+        if stop {
+            db.clear_flush_signal(signal);
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Flusher;
+    use crate::{Config, Database, VectorConfig};
+    use serde_json::json;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    fn wait_for(what: &str, done: impl Fn() -> bool) {
+        let start = Instant::now();
+        while !done() {
+            assert!(start.elapsed() < Duration::from_secs(10), "timed out waiting for {}", what);
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_background_flusher() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("flushed.ndb");
+        let config = Config::default().with_max_dirty_pages(20);
+        let db = Arc::new(Database::create_with_config(&path, config).unwrap());
+
+        // A long interval: only the dirty page limit wakes the flusher
+        let flusher = Flusher::start(&db, Duration::from_secs(3600));
+        db.insert("logs", json!({"n": 0})).unwrap();
+        assert!(db.unsynced_pages() > 0);
+        for n in 1..40 {
+            db.insert("logs", json!({"n": n})).unwrap();
+        }
+        wait_for("the dirty page limit", || flusher.flushes() > 0 && db.unsynced_pages() < 20);
+
+        // Stopping flushes what is left, vectors included
+        db.create_vector_collection("embeddings", VectorConfig::new(2)).unwrap();
+        db.insert_vector("embeddings", vec![1.0, 0.0], None).unwrap();
+        db.insert("logs", json!({"n": 40})).unwrap();
+        flusher.stop();
+        assert_eq!(db.unsynced_pages(), 0);
+        assert!(!db.has_unsynced_vectors());
+
+        // A short interval flushes without being woken
+        let flusher = Flusher::start(&db, Duration::from_millis(10));
+        db.insert("logs", json!({"n": 41})).unwrap();
+        wait_for("the interval", || db.unsynced_pages() == 0);
+        drop(flusher);
+
+        // Dropping the database ends the thread
+        let flusher = Flusher::start(&db, Duration::from_millis(10));
+        drop(db);
+        drop(flusher);
+        assert_eq!(Database::open(&path).unwrap().count("logs"), 42);
+    }
+}
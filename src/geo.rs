@@ -0,0 +1,363 @@
+//! Geospatial indexes and queries
+//!
+//! Documents carry a location as a `[lon, lat]` array or a GeoJSON point
+//! (`{"type": "Point", "coordinates": [lon, lat]}`) in one field.
+//! [`Database::create_geo_index`] indexes that field by geohash, and keeps the
+//! index up to date with [hooks](crate::hooks), so that
+//! [`Database::find_near`] and [`Database::find_within`] only read the
+//! documents in the cells around the query instead of scanning the
+//! collection.
+//!
+//! Distances are great-circle distances in meters on a spherical Earth,
+//! which is within 0.5% of the true distance anywhere. Geo indexes live in
+//! memory and are rebuilt from the documents by `create_geo_index`, so call
+//! it after each open.
+//!
+//! # Example
+//!
+//! ```ignore
+//! db.create_geo_index("places", "location")?;
+//! db.insert("places", json!({"name": "Café", "location": [2.3522, 48.8566]}))?;
+//!
+//! for hit in db.find_near("places", GeoPoint::new(2.35, 48.85), 1_000.0)? {
+//!     println!("{} is {:.0} m away", hit.document.id, hit.distance);
+//! }
+//! let in_view = db.find_within("places", &BoundingBox::new(GeoPoint::new(2.2, 48.8), GeoPoint::new(2.5, 48.9)))?;
+//! ```
+
+use crate::hooks::HookId;
+use crate::types::{Document, DocumentId};
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Mean radius of the Earth in meters
+pub const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+/// Geohash length stored in the index, about 1 m by 0.6 m cells
+const INDEX_PRECISION: usize = 10;
+
+/// Most cells a query reads before falling back to coarser ones
+const MAX_QUERY_CELLS: usize = 32;
+
+const BASE32: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// A position in degrees
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GeoPoint {
+    pub lon: f64,
+    pub lat: f64,
+}
+
+impl GeoPoint {
+    pub fn new(lon: f64, lat: f64) -> Self {
+        Self { lon, lat }
+    }
+
+    /// Read a `[lon, lat]` array or a GeoJSON point, rejecting coordinates
+    /// outside [-180, 180] and [-90, 90]
+    pub fn from_value(value: &Value) -> Option<Self> {
+        let coordinates = match value {
+            Value::Array(_) => value,
+            Value::Object(map) if map.get("type").and_then(Value::as_str) == Some("Point") => map.get("coordinates")?,
+            _ => return None,
+        };
+        match coordinates.as_array()?.as_slice() {
+            [lon, lat] => {
+                let point = Self::new(lon.as_f64()?, lat.as_f64()?);
+                ((-180.0..=180.0).contains(&point.lon) && (-90.0..=90.0).contains(&point.lat)).then_some(point)
+            }
+            _ => None,
+        }
+    }
+
+    /// Great-circle distance to `other` in meters
+    pub fn distance(&self, other: &GeoPoint) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.lon - self.lon).to_radians();
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_M * a.sqrt().min(1.0).asin()
+    }
+
+    /// The point's geohash with `precision` characters
+    pub fn geohash(&self, precision: usize) -> String {
+        let (mut lon_range, mut lat_range) = ((-180.0, 180.0), (-90.0, 90.0));
+        let mut hash = String::with_capacity(precision);
+        let (mut bits, mut count, mut even) = (0usize, 0, true);
+        while hash.len() < precision {
+            let (range, value) = if even { (&mut lon_range, self.lon) } else { (&mut lat_range, self.lat) };
+            let mid = (range.0 + range.1) / 2.0;
+            bits <<= 1;
+            if value >= mid {
+                bits |= 1;
+                range.0 = mid;
+            } else {
+                range.1 = mid;
+            }
+            even = !even;
+            count += 1;
+            if count == 5 {
+                hash.push(BASE32[bits] as char);
+                bits = 0;
+                count = 0;
+            }
+        }
+        hash
+    }
+}
+
+/// A latitude/longitude rectangle
+///
+/// A box whose `min.lon` is greater than its `max.lon` crosses the
+/// antimeridian.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoundingBox {
+    /// South-west corner
+    pub min: GeoPoint,
+    /// North-east corner
+    pub max: GeoPoint,
+}
+
+impl BoundingBox {
+    pub fn new(min: GeoPoint, max: GeoPoint) -> Self {
+        Self { min, max }
+    }
+
+    /// The smallest box holding every point within `radius` meters of `center`
+    pub fn around(center: GeoPoint, radius: f64) -> Self {
+        let angle = radius / EARTH_RADIUS_M;
+        let dlat = angle.to_degrees();
+        let (min_lat, max_lat) = (center.lat - dlat, center.lat + dlat);
+        let spread = angle.sin() / center.lat.to_radians().cos();
+        // Circles reaching over a pole, or half way round, span every longitude
+        if min_lat <= -90.0 || max_lat >= 90.0 || angle >= std::f64::consts::FRAC_PI_2 || spread >= 1.0 {
+            return Self::new(GeoPoint::new(-180.0, min_lat.max(-90.0)), GeoPoint::new(180.0, max_lat.min(90.0)));
+        }
+        let dlon = spread.asin().to_degrees();
+        Self::new(
+            GeoPoint::new(wrap_lon(center.lon - dlon), min_lat),
+            GeoPoint::new(wrap_lon(center.lon + dlon), max_lat),
+        )
+    }
+
+    pub fn contains(&self, point: &GeoPoint) -> bool {
+        let lat = (self.min.lat..=self.max.lat).contains(&point.lat);
+        let lon = if self.min.lon <= self.max.lon {
+            (self.min.lon..=self.max.lon).contains(&point.lon)
+        } else {
+            point.lon >= self.min.lon || point.lon <= self.max.lon
+        };
+        lat && lon
+    }
+
+    /// Longitude ranges covered, split at the antimeridian
+    fn lon_ranges(&self) -> Vec<(f64, f64)> {
+        if self.min.lon <= self.max.lon {
+            vec![(self.min.lon, self.max.lon)]
+        } else {
+            vec![(self.min.lon, 180.0), (-180.0, self.max.lon)]
+        }
+    }
+
+    /// Geohash prefixes whose cells together cover the box; empty means
+    /// the box needs every cell
+    fn cover(&self) -> Vec<String> {
+        for precision in (1..=INDEX_PRECISION).rev() {
+            let lon_bits = (5 * precision).div_ceil(2);
+            let lat_bits = 5 * precision / 2;
+            let (width, height) = (360.0 / (1u64 << lon_bits) as f64, 180.0 / (1u64 << lat_bits) as f64);
+            let cell = |value: f64, origin: f64, size: f64, bits: usize| {
+                (((value - origin) / size).floor() as u64).min((1u64 << bits) - 1)
+            };
+
+            let rows = cell(self.max.lat, -90.0, height, lat_bits).saturating_sub(cell(self.min.lat, -90.0, height, lat_bits)) + 1;
+            let columns: u64 = self
+                .lon_ranges()
+                .iter()
+                .map(|&(min, max)| cell(max, -180.0, width, lon_bits).saturating_sub(cell(min, -180.0, width, lon_bits)) + 1)
+                .sum();
+            if (rows * columns) as usize > MAX_QUERY_CELLS {
+                continue;
+            }
+
+            let mut cells = HashSet::new();
+            for row in cell(self.min.lat, -90.0, height, lat_bits)..=cell(self.max.lat, -90.0, height, lat_bits) {
+                for (min, max) in self.lon_ranges() {
+                    for column in cell(min, -180.0, width, lon_bits)..=cell(max, -180.0, width, lon_bits) {
+                        let center = GeoPoint::new(
+                            -180.0 + (column as f64 + 0.5) * width,
+                            -90.0 + (row as f64 + 0.5) * height,
+                        );
+                        cells.insert(center.geohash(precision));
+                    }
+                }
+            }
+            return cells.into_iter().collect();
+        }
+        Vec::new()
+    }
+}
+
+fn wrap_lon(lon: f64) -> f64 {
+    if lon > 180.0 {
+        lon - 360.0
+    } else if lon < -180.0 {
+        lon + 360.0
+    } else {
+        lon
+    }
+}
+
+/// A document found by [`Database::find_near`](crate::Database::find_near)
+#[derive(Debug, Clone, Serialize)]
+pub struct GeoResult {
+    pub document: Document,
+    /// Meters from the query point
+    pub distance: f64,
+}
+
+#[derive(Default)]
+struct GeoIndexState {
+    /// `(geohash, document ID)`, so cells are contiguous ranges
+    cells: BTreeSet<(String, DocumentId)>,
+    points: HashMap<DocumentId, (GeoPoint, String)>,
+}
+
+/// Geohash index over one field of a collection
+pub(crate) struct GeoIndex {
+    /// Field holding the location; dots reach into nested objects
+    field: String,
+    state: RwLock<GeoIndexState>,
+    /// Hooks keeping the index up to date
+    pub(crate) hooks: Mutex<Vec<HookId>>,
+}
+
+impl GeoIndex {
+    pub(crate) fn new(field: &str) -> Self {
+        Self {
+            field: field.to_string(),
+            state: RwLock::new(GeoIndexState::default()),
+            hooks: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// Index a document's location, or drop it if it no longer has one
+    pub(crate) fn update(&self, doc_id: &str, document: &Value) {
+        let point = self
+            .field
+            .split('.')
+            .try_fold(document, |value, key| value.get(key))
+            .and_then(GeoPoint::from_value);
+        let mut state = self.state.write();
+        if let Some((_, hash)) = state.points.remove(doc_id) {
+            state.cells.remove(&(hash, doc_id.to_string()));
+        }
+        if let Some(point) = point {
+            let hash = point.geohash(INDEX_PRECISION);
+            state.cells.insert((hash.clone(), doc_id.to_string()));
+            state.points.insert(doc_id.to_string(), (point, hash));
+        }
+    }
+
+    pub(crate) fn remove(&self, doc_id: &str) {
+        let mut state = self.state.write();
+        if let Some((_, hash)) = state.points.remove(doc_id) {
+            state.cells.remove(&(hash, doc_id.to_string()));
+        }
+    }
+
+    /// Documents located inside `bbox`
+    pub(crate) fn within(&self, bbox: &BoundingBox) -> Vec<(DocumentId, GeoPoint)> {
+        let state = self.state.read();
+        let cells = bbox.cover();
+        let candidates: Box<dyn Iterator<Item = &DocumentId>> = if cells.is_empty() {
+            Box::new(state.points.keys())
+        } else {
+            Box::new(cells.into_iter().flat_map(|prefix| {
+                state
+                    .cells
+                    .range((prefix.clone(), String::new())..)
+                    .take_while(move |(hash, _)| hash.starts_with(&prefix))
+                    .map(|(_, id)| id)
+                    .collect::<Vec<_>>()
+            }))
+        };
+        let mut seen = HashSet::new();
+        candidates
+            .filter_map(|id| {
+                let (point, _) = state.points.get(id)?;
+                (bbox.contains(point) && seen.insert(id)).then(|| (id.clone(), *point))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Database;
+    use serde_json::json;
+
+    #[test]
+    fn test_points_and_boxes() {
+        assert_eq!(GeoPoint::new(-5.6, 42.6).geohash(5), "ezs42");
+        assert_eq!(GeoPoint::from_value(&json!([2.35, 48.85])), Some(GeoPoint::new(2.35, 48.85)));
+        assert_eq!(
+            GeoPoint::from_value(&json!({"type": "Point", "coordinates": [2.35, 48.85]})),
+            Some(GeoPoint::new(2.35, 48.85))
+        );
+        assert_eq!(GeoPoint::from_value(&json!([48.85, 200.0])), None);
+
+        let (paris, london) = (GeoPoint::new(2.3522, 48.8566), GeoPoint::new(-0.1276, 51.5072));
+        assert!((paris.distance(&london) - 343_500.0).abs() < 1_000.0);
+
+        // Around Fiji, across the antimeridian
+        let bbox = BoundingBox::around(GeoPoint::new(179.9, -17.0), 50_000.0);
+        assert!(bbox.min.lon > bbox.max.lon);
+        assert!(bbox.contains(&GeoPoint::new(-179.9, -17.0)));
+        assert!(!bbox.contains(&GeoPoint::new(0.0, -17.0)));
+    }
+
+    #[test]
+    fn test_find_near_and_within() {
+        let db = Database::in_memory().unwrap();
+        let place = |name: &str, lon: f64, lat: f64| db.insert("places", json!({"name": name, "at": [lon, lat]})).unwrap();
+        let louvre = place("Louvre", 2.3376, 48.8606);
+        let eiffel = place("Eiffel Tower", 2.2945, 48.8584);
+        place("Big Ben", -0.1246, 51.5007);
+        db.insert("places", json!({"name": "Nowhere"})).unwrap();
+
+        assert!(db.find_near("places", GeoPoint::new(2.35, 48.85), 1_000.0).is_err());
+        db.create_geo_index("places", "at").unwrap();
+        let notre_dame = db.insert("places", json!({"name": "Notre-Dame", "at": {"type": "Point", "coordinates": [2.3499, 48.8530]}})).unwrap();
+
+        let near = db.find_near("places", GeoPoint::new(2.3488, 48.8534), 5_000.0).unwrap();
+        let ids: Vec<_> = near.iter().map(|hit| hit.document.id.clone()).collect();
+        assert_eq!(ids, vec![notre_dame.clone(), louvre.clone(), eiffel.clone()]);
+        assert!(near[0].distance < 100.0);
+
+        let bbox = BoundingBox::new(GeoPoint::new(2.33, 48.85), GeoPoint::new(2.36, 48.87));
+        let mut within: Vec<_> = db.find_within("places", &bbox).unwrap().into_iter().map(|doc| doc.id).collect();
+        within.sort();
+        let mut expected = vec![louvre.clone(), notre_dame.clone()];
+        expected.sort();
+        assert_eq!(within, expected);
+
+        db.update("places", &louvre, json!({"name": "Louvre", "at": [-0.1246, 51.5]})).unwrap();
+        db.delete("places", &notre_dame).unwrap();
+        let near = db.find_near("places", GeoPoint::new(2.3488, 48.8534), 5_000.0).unwrap();
+        assert_eq!(near.len(), 1);
+        assert_eq!(near[0].document.id, eiffel);
+        assert_eq!(db.find_near("places", GeoPoint::new(-0.12, 51.5), 1_000.0).unwrap().len(), 2);
+        assert_eq!(db.find_near("places", GeoPoint::new(-0.12, 51.5), 2.0e7).unwrap().len(), 3);
+
+        assert!(db.drop_geo_index("places"));
+        assert!(db.find_within("places", &bbox).is_err());
+    }
+}
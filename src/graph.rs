@@ -0,0 +1,288 @@
+//! Relationships between documents
+//!
+//! [`Database::link`] records a labelled, directed edge from one document to
+//! another, such as `users/alice -follows-> users/bob`. Edges are documents
+//! in the [`EDGES`] collection, so they are stored, synced, backed up and
+//! replicated like any other. An in-memory adjacency index, built from that
+//! collection on first use and kept up to date with [hooks](crate::hooks),
+//! answers [`neighbors`](Database::neighbors) and
+//! [`traverse`](Database::traverse) queries without reading edge documents.
+//!
+//! Deleting a document leaves its edges in place; remove them with
+//! [`Database::unlink_all`].
+//!
+//! # Example
+//!
+//! ```ignore
+//! db.link("users", &alice, "follows", "users", &bob)?;
+//! db.link("users", &bob, "follows", "users", &carol)?;
+//!
+//! let following = db.neighbors("users", &alice, Some("follows"), Direction::Outgoing)?;
+//! let followers = db.neighbors("users", &bob, Some("follows"), Direction::Incoming)?;
+//!
+//! // Friends of friends
+//! let reach = db.traverse("users", &alice, &Traversal::new(2).with_label("follows"))?;
+//! ```
+
+use crate::error::{KeraDBError, Result};
+use crate::types::DocumentId;
+use crate::Database;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+
+/// Collection holding the edges
+pub const EDGES: &str = "_edges";
+
+/// A document, by collection and ID
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct NodeRef {
+    pub collection: String,
+    pub id: DocumentId,
+}
+
+impl NodeRef {
+    pub fn new(collection: &str, id: &str) -> Self {
+        Self {
+            collection: collection.to_string(),
+            id: id.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for NodeRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.collection, self.id)
+    }
+}
+
+/// A directed, labelled relationship between two documents
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Edge {
+    pub from: NodeRef,
+    pub label: String,
+    pub to: NodeRef,
+    /// Data stored on the edge, such as a weight or when it was made
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub properties: Value,
+}
+
+impl Edge {
+    /// `_id` of the edge's document; one edge per `(from, label, to)`
+    pub fn key(from: &NodeRef, label: &str, to: &NodeRef) -> String {
+        // A JSON array, so no ID or label can make two keys collide
+        serde_json::json!([from.collection, from.id, label, to.collection, to.id]).to_string()
+    }
+
+    pub(crate) fn from_document(document: &Value) -> Option<Self> {
+        serde_json::from_value(document.clone()).ok()
+    }
+}
+
+/// Which edges of a node to follow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Edges from the node
+    Outgoing,
+    /// Edges to the node
+    Incoming,
+    Both,
+}
+
+/// Options for [`Database::traverse`]
+#[derive(Debug, Clone)]
+pub struct Traversal {
+    /// Edges further than this from the start are not followed
+    pub max_depth: usize,
+    /// Only follow edges with this label
+    pub label: Option<String>,
+    pub direction: Direction,
+    /// Stop after reaching this many nodes
+    pub limit: Option<usize>,
+}
+
+impl Traversal {
+    /// Follow outgoing edges of any label, up to `max_depth` hops
+    pub fn new(max_depth: usize) -> Self {
+        Self {
+            max_depth,
+            label: None,
+            direction: Direction::Outgoing,
+            limit: None,
+        }
+    }
+
+    pub fn with_label(mut self, label: &str) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    pub fn with_direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+/// A node reached by [`Database::traverse`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TraversalHit {
+    pub node: NodeRef,
+    /// Hops from the start node
+    pub depth: usize,
+}
+
+/// Edges by node, in both directions
+#[derive(Default)]
+struct AdjacencyState {
+    outgoing: HashMap<NodeRef, BTreeSet<(String, NodeRef)>>,
+    incoming: HashMap<NodeRef, BTreeSet<(String, NodeRef)>>,
+}
+
+/// In-memory index over the edge collection
+#[derive(Default)]
+pub(crate) struct Adjacency {
+    state: RwLock<AdjacencyState>,
+}
+
+impl Adjacency {
+    pub(crate) fn add(&self, edge: &Edge) {
+        let mut state = self.state.write();
+        state.outgoing.entry(edge.from.clone()).or_default().insert((edge.label.clone(), edge.to.clone()));
+        state.incoming.entry(edge.to.clone()).or_default().insert((edge.label.clone(), edge.from.clone()));
+    }
+
+    pub(crate) fn remove(&self, edge: &Edge) {
+        let mut state = self.state.write();
+        let unlink = |map: &mut HashMap<NodeRef, BTreeSet<(String, NodeRef)>>, node: &NodeRef, other: &NodeRef| {
+            if let Some(set) = map.get_mut(node) {
+                set.remove(&(edge.label.clone(), other.clone()));
+                if set.is_empty() {
+                    map.remove(node);
+                }
+            }
+        };
+        unlink(&mut state.outgoing, &edge.from, &edge.to);
+        unlink(&mut state.incoming, &edge.to, &edge.from);
+    }
+
+    /// Edges of `node` as `(from, label, to)`
+    pub(crate) fn edges(&self, node: &NodeRef, label: Option<&str>, direction: Direction) -> Vec<(NodeRef, String, NodeRef)> {
+        let state = self.state.read();
+        let mut edges = Vec::new();
+        let wanted = |edge_label: &str| label.is_none_or(|label| label == edge_label);
+        if matches!(direction, Direction::Outgoing | Direction::Both) {
+            for (edge_label, to) in state.outgoing.get(node).into_iter().flatten().filter(|(l, _)| wanted(l)) {
+                edges.push((node.clone(), edge_label.clone(), to.clone()));
+            }
+        }
+        if matches!(direction, Direction::Incoming | Direction::Both) {
+            for (edge_label, from) in state.incoming.get(node).into_iter().flatten().filter(|(l, _)| wanted(l)) {
+                edges.push((from.clone(), edge_label.clone(), node.clone()));
+            }
+        }
+        edges
+    }
+
+    /// Distinct nodes one edge away from `node`
+    pub(crate) fn neighbors(&self, node: &NodeRef, label: Option<&str>, direction: Direction) -> Vec<NodeRef> {
+        let mut seen = HashSet::new();
+        self.edges(node, label, direction)
+            .into_iter()
+            .map(|(from, _, to)| if from == *node { to } else { from })
+            .filter(|other| seen.insert(other.clone()))
+            .collect()
+    }
+
+    /// Breadth-first search from `start`, which is not itself reported
+    pub(crate) fn traverse(&self, start: &NodeRef, options: &Traversal) -> Vec<TraversalHit> {
+        let limit = options.limit.unwrap_or(usize::MAX);
+        let mut visited = HashSet::from([start.clone()]);
+        let mut queue = VecDeque::from([(start.clone(), 0)]);
+        let mut hits = Vec::new();
+        while let Some((node, depth)) = queue.pop_front() {
+            if depth == options.max_depth {
+                continue;
+            }
+            for next in self.neighbors(&node, options.label.as_deref(), options.direction) {
+                if hits.len() == limit {
+                    return hits;
+                }
+                if visited.insert(next.clone()) {
+                    hits.push(TraversalHit { node: next.clone(), depth: depth + 1 });
+                    queue.push_back((next, depth + 1));
+                }
+            }
+        }
+        hits
+    }
+}
+
+/// Fail unless the document an edge points at exists
+pub(crate) fn check_node(db: &Database, node: &NodeRef) -> Result<()> {
+    match db.find_by_id(&node.collection, &node.id) {
+        Ok(_) => Ok(()),
+        Err(KeraDBError::DocumentNotFound(_)) => Err(KeraDBError::DocumentNotFound(node.to_string())),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_link_and_traverse() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.ndb");
+        let db = Database::create(&path).unwrap();
+        let user = |name: &str| db.insert("users", json!({"_id": name})).unwrap();
+        let (alice, bob, carol, dave) = (user("alice"), user("bob"), user("carol"), user("dave"));
+        let post = db.insert("posts", json!({"_id": "hello", "title": "Hello"})).unwrap();
+
+        db.link("users", &alice, "follows", "users", &bob).unwrap();
+        db.link("users", &bob, "follows", "users", &carol).unwrap();
+        db.link("users", &carol, "follows", "users", &dave).unwrap();
+        db.link("users", &carol, "follows", "users", &alice).unwrap();
+        db.link_with("users", &bob, "likes", "posts", &post, json!({"at": 1700000000})).unwrap();
+        // Linking again replaces the edge rather than adding another
+        db.link("users", &alice, "follows", "users", &bob).unwrap();
+        assert_eq!(db.count(EDGES), 5);
+        assert!(db.link("users", &alice, "follows", "users", "nobody").is_err());
+
+        let node = |collection: &str, id: &str| NodeRef::new(collection, id);
+        assert_eq!(db.neighbors("users", &bob, None, Direction::Outgoing).unwrap(), vec![node("users", "carol"), node("posts", "hello")]);
+        assert_eq!(db.neighbors("users", &alice, Some("follows"), Direction::Incoming).unwrap(), vec![node("users", "carol")]);
+        let edges = db.edges("users", &bob, Direction::Outgoing).unwrap();
+        let like = edges.iter().find(|edge| edge.label == "likes").unwrap();
+        assert_eq!(like.properties, json!({"at": 1700000000}));
+
+        let reach = db.traverse("users", &alice, &Traversal::new(2).with_label("follows")).unwrap();
+        assert_eq!(reach, vec![
+            TraversalHit { node: node("users", "bob"), depth: 1 },
+            TraversalHit { node: node("users", "carol"), depth: 2 },
+        ]);
+        let all = db.traverse("users", &alice, &Traversal::new(10).with_label("follows")).unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(db.traverse("users", &alice, &Traversal::new(10).with_limit(2)).unwrap().len(), 2);
+
+        assert!(db.unlink("users", &carol, "follows", "users", &dave).unwrap());
+        assert!(!db.unlink("users", &carol, "follows", "users", &dave).unwrap());
+        db.sync().unwrap();
+        drop(db);
+
+        // The index is rebuilt from the edge collection
+        let db = Database::open(&path).unwrap();
+        let all = db.traverse("users", &alice, &Traversal::new(10).with_direction(Direction::Both)).unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(db.unlink_all("users", &carol).unwrap(), 2);
+        assert!(db.neighbors("users", &alice, None, Direction::Both).unwrap() == vec![node("users", "bob")]);
+    }
+}
@@ -0,0 +1,245 @@
+//! Hooks on document mutations
+//!
+//! [`Database::register_hook`] runs a callback after each insert, update or
+//! delete in a collection, so applications can keep denormalized copies up
+//! to date, bust caches or embed new documents as they arrive.
+//!
+//! Hooks run on the writing thread, after the write has succeeded and been
+//! recorded in the oplog, and before the write call returns. They receive the
+//! same [`ChangeEvent`] a change stream would, and the database itself, so a
+//! hook may write to other collections. A hook that writes to its own
+//! collection triggers itself again; anything slow belongs on another thread
+//! or in a [change stream](crate::oplog) instead.
+//!
+//! [`Database::auto_embed`] uses hooks to keep a vector collection in step
+//! with a text field, embedding documents as they are written.
+//!
+//! # Example
+//!
+//! ```ignore
+//! // Embed each new article into a vector collection for semantic search
+//! db.register_hook("articles", HookKind::AfterInsert, |db, event| {
+//!     let body = event.document["body"].as_str().unwrap_or_default();
+//!     let metadata = json!({"article": event.doc_id});
+//!     if let Err(e) = db.insert_text("article_embeddings", body, Some(metadata)) {
+//!         tracing::warn!("could not embed article {}: {}", event.doc_id, e);
+//!     }
+//! });
+//! ```
+
+use crate::error::Result;
+use crate::oplog::{ChangeEvent, OperationType};
+use crate::Database;
+use parking_lot::RwLock;
+use serde_json::Value;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// When a hook runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookKind {
+    AfterInsert,
+    AfterUpdate,
+    AfterDelete,
+}
+
+impl HookKind {
+    /// Every kind, for hooks on all writes
+    pub const ALL: [HookKind; 3] = [HookKind::AfterInsert, HookKind::AfterUpdate, HookKind::AfterDelete];
+
+    fn after(operation: OperationType) -> Self {
+        match operation {
+            OperationType::Insert => HookKind::AfterInsert,
+            OperationType::Update => HookKind::AfterUpdate,
+            OperationType::Delete => HookKind::AfterDelete,
+        }
+    }
+}
+
+/// Identifies a registered hook, for [`Database::remove_hook`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HookId(u64);
+
+/// A hook callback
+pub type HookFn = dyn Fn(&Database, &ChangeEvent) + Send + Sync;
+
+struct Hook {
+    id: HookId,
+    /// Collection name, or `*` for every collection
+    collection: String,
+    kind: HookKind,
+    callback: Arc<HookFn>,
+}
+
+/// The hooks registered on a database
+#[derive(Default)]
+pub(crate) struct Hooks {
+    hooks: RwLock<Vec<Hook>>,
+    next_id: AtomicU64,
+}
+
+impl Hooks {
+    pub(crate) fn register(&self, collection: &str, kind: HookKind, callback: Arc<HookFn>) -> HookId {
+        let id = HookId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        self.hooks.write().push(Hook {
+            id,
+            collection: collection.to_string(),
+            kind,
+            callback,
+        });
+        id
+    }
+
+    pub(crate) fn remove(&self, id: HookId) -> bool {
+        let mut hooks = self.hooks.write();
+        let before = hooks.len();
+        hooks.retain(|hook| hook.id != id);
+        hooks.len() != before
+    }
+
+    /// Run the hooks matching a write; `event` is only built if one does
+    pub(crate) fn fire(
+        &self,
+        db: &Database,
+        operation: OperationType,
+        collection: &str,
+        event: impl FnOnce() -> ChangeEvent,
+    ) {
+        let kind = HookKind::after(operation);
+        // Cloned out so hooks can register and remove hooks
+        let callbacks: Vec<Arc<HookFn>> = self
+            .hooks
+            .read()
+            .iter()
+            .filter(|hook| hook.kind == kind && (hook.collection == "*" || hook.collection == collection))
+            .map(|hook| hook.callback.clone())
+            .collect();
+        if callbacks.is_empty() {
+            return;
+        }
+        let event = event();
+        for callback in callbacks {
+            callback(db, &event);
+        }
+    }
+}
+
+/// Embeds a text field of documents into a vector collection, for
+/// [`Database::auto_embed`]
+pub(crate) struct AutoEmbed {
+    /// Field holding the text; dots reach into nested objects
+    field: String,
+    vector_collection: String,
+}
+
+impl AutoEmbed {
+    pub(crate) fn new(field: &str, vector_collection: &str) -> Self {
+        Self {
+            field: field.to_string(),
+            vector_collection: vector_collection.to_string(),
+        }
+    }
+
+    pub(crate) fn vector_collection(&self) -> &str {
+        &self.vector_collection
+    }
+
+    /// Bring the vector for a changed document up to date
+    pub(crate) fn apply(&self, db: &Database, event: &ChangeEvent) -> Result<()> {
+        match event.operation {
+            OperationType::Delete => {
+                db.delete_vector_by_external_id(&self.vector_collection, &event.doc_id)?;
+                Ok(())
+            }
+            OperationType::Insert | OperationType::Update => self.upsert(db, &event.doc_id, &event.document),
+        }
+    }
+
+    /// Embed a document's text, unless its vector already holds that text,
+    /// or remove its vector if it has no text
+    pub(crate) fn upsert(&self, db: &Database, doc_id: &str, document: &Value) -> Result<()> {
+        let text = self
+            .field
+            .split('.')
+            .try_fold(document, |value, key| value.get(key))
+            .and_then(Value::as_str)
+            .filter(|text| !text.is_empty());
+        let Some(text) = text else {
+            db.delete_vector_by_external_id(&self.vector_collection, doc_id)?;
+            return Ok(());
+        };
+
+        let current = db.get_vector_by_external_id(&self.vector_collection, doc_id)?;
+        if current.and_then(|vector| vector.text).as_deref() != Some(text) {
+            db.insert_text_with_id(&self.vector_collection, doc_id, text, None)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+    use serde_json::json;
+
+    #[test]
+    fn test_hooks_run_after_writes() {
+        let db = Database::in_memory().unwrap();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+
+        let log = seen.clone();
+        let id = db.register_hook("users", HookKind::AfterInsert, move |_, event| {
+            log.lock().push((event.operation, event.doc_id.clone()));
+        });
+        // Keep deleted users in another collection
+        db.register_hook("*", HookKind::AfterDelete, |db, event| {
+            if event.collection == "users" {
+                db.insert("deleted", json!({"_id": event.doc_id, "name": event.document["name"]})).unwrap();
+            }
+        });
+
+        let alice = db.insert("users", json!({"name": "Alice"})).unwrap();
+        db.insert("orders", json!({"total": 5})).unwrap();
+        db.update("users", &alice, json!({"name": "Alice B"})).unwrap();
+        assert_eq!(*seen.lock(), vec![(OperationType::Insert, alice.clone())]);
+
+        db.delete("users", &alice).unwrap();
+        assert_eq!(db.find_by_id("deleted", &alice).unwrap().get("name"), Some(json!("Alice B")));
+
+        assert!(db.remove_hook(id));
+        assert!(!db.remove_hook(id));
+        db.insert("users", json!({"name": "Bob"})).unwrap();
+        assert_eq!(seen.lock().len(), 1);
+    }
+
+    #[test]
+    fn test_auto_embed() {
+        use crate::vector::embedding::EmbeddingConfig;
+        use crate::VectorConfig;
+
+        let db = Database::in_memory().unwrap();
+        db.set_embedding_provider(EmbeddingConfig::TfIdf { dimensions: 64 }).unwrap();
+        assert!(db.auto_embed("articles", "content.body", "article_vectors").is_err());
+        db.create_vector_collection("article_vectors", VectorConfig::new(64)).unwrap();
+        let text = |id: &str| db.get_vector_by_external_id("article_vectors", id).unwrap().and_then(|v| v.text);
+
+        let old = db.insert("articles", json!({"content": {"body": "written before"}})).unwrap();
+        db.auto_embed("articles", "content.body", "article_vectors").unwrap();
+        assert_eq!(text(&old).as_deref(), Some("written before"));
+
+        let id = db.insert("articles", json!({"content": {"body": "rust database engine"}})).unwrap();
+        assert_eq!(text(&id).as_deref(), Some("rust database engine"));
+        let hits = db.vector_search_text("article_vectors", "rust database engine", 1).unwrap();
+        assert_eq!(hits[0].document.external_id.as_deref(), Some(id.as_str()));
+
+        db.update("articles", &id, json!({"content": {"body": "edited"}})).unwrap();
+        assert_eq!(text(&id).as_deref(), Some("edited"));
+        db.update("articles", &id, json!({"title": "no body"})).unwrap();
+        assert_eq!(text(&id), None);
+
+        db.delete("articles", &old).unwrap();
+        assert_eq!(text(&old), None);
+        assert_eq!(db.vector_stats("article_vectors").unwrap().vector_count, 0);
+    }
+}
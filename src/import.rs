@@ -0,0 +1,329 @@
+//! Loading documents from JSON, NDJSON and CSV files
+//!
+//! [`Database::import_file`](crate::Database::import_file) and friends read
+//! records one at a time and insert each into a collection:
+//!
+//! - **JSON**: an array of objects, or a single object
+//! - **NDJSON / JSONL**: one object per line; blank lines are skipped
+//! - **CSV**: a header row, then one document per row. Cells that look like
+//!   numbers or `true`/`false` are stored as such, empty cells are left out,
+//!   and [`ImportOptions::with_field`] renames a column, possibly into a
+//!   nested field (`city` → `address.city`)
+//!
+//! Under [`Durability::Full`](crate::Durability::Full) the data file is
+//! synced once per batch rather than after every document.
+
+use crate::error::{KeraDBError, Result};
+use crate::Database;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::Path;
+
+/// Errors kept in an [`ImportReport`]; later ones are only counted
+const MAX_REPORTED_ERRORS: usize = 100;
+
+/// Input file format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    Json,
+    Ndjson,
+    Csv,
+}
+
+impl ImportFormat {
+    /// Parse a format name: `json`, `ndjson`/`jsonl` or `csv`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "json" => Some(ImportFormat::Json),
+            "ndjson" | "jsonl" => Some(ImportFormat::Ndjson),
+            "csv" => Some(ImportFormat::Csv),
+            _ => None,
+        }
+    }
+
+    /// Detect the format from a file extension
+    pub fn from_path(path: &Path) -> Result<Self> {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .and_then(Self::from_name)
+            .ok_or_else(|| {
+                KeraDBError::InvalidFormat(format!(
+                    "Cannot infer import format from '{}' (expected .json, .ndjson, .jsonl or .csv)",
+                    path.display()
+                ))
+            })
+    }
+}
+
+/// How to read and insert records
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    /// Format to read; detected from the file extension when `None`
+    pub format: Option<ImportFormat>,
+    /// Documents inserted between syncs and progress reports
+    pub batch_size: usize,
+    /// Field whose value becomes each document's `_id`
+    pub id_field: Option<String>,
+    /// CSV header → document field; an empty field drops the column
+    pub field_map: HashMap<String, String>,
+    /// CSV field delimiter
+    pub delimiter: u8,
+    /// Count and skip records that cannot be inserted instead of stopping
+    pub skip_errors: bool,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            format: None,
+            batch_size: 1000,
+            id_field: None,
+            field_map: HashMap::new(),
+            delimiter: b',',
+            skip_errors: false,
+        }
+    }
+}
+
+impl ImportOptions {
+    pub fn with_format(mut self, format: ImportFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn with_id_field(mut self, field: impl Into<String>) -> Self {
+        self.id_field = Some(field.into());
+        self
+    }
+
+    /// Store the CSV column `header` under `field` (dotted for nesting)
+    pub fn with_field(mut self, header: impl Into<String>, field: impl Into<String>) -> Self {
+        self.field_map.insert(header.into(), field.into());
+        self
+    }
+
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn with_skip_errors(mut self, skip_errors: bool) -> Self {
+        self.skip_errors = skip_errors;
+        self
+    }
+}
+
+/// Counts reported after every batch
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ImportProgress {
+    /// Records read so far
+    pub records: usize,
+    pub imported: usize,
+    pub failed: usize,
+}
+
+/// Outcome of an import
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+    pub records: usize,
+    pub imported: usize,
+    /// Records skipped with [`ImportOptions::skip_errors`]
+    pub failed: usize,
+    /// The first failures, as `record N: error`
+    pub errors: Vec<String>,
+}
+
+pub(crate) fn import<R: BufRead>(
+    db: &Database,
+    collection: &str,
+    reader: R,
+    format: ImportFormat,
+    options: &ImportOptions,
+    progress: &mut dyn FnMut(&ImportProgress),
+) -> Result<ImportReport> {
+    insert_records(db, collection, records(reader, format, options)?, options, progress)
+}
+
+/// Insert parsed records, syncing and reporting progress once per batch
+pub(crate) fn insert_records(
+    db: &Database,
+    collection: &str,
+    records: impl Iterator<Item = Result<Value>>,
+    options: &ImportOptions,
+    progress: &mut dyn FnMut(&ImportProgress),
+) -> Result<ImportReport> {
+    let mut report = ImportReport::default();
+    let batch_size = options.batch_size.max(1);
+
+    for (i, record) in records.enumerate() {
+        let number = i + 1;
+        report.records = number;
+        let result = record
+            .and_then(|value| with_id(value, options.id_field.as_deref()))
+            .and_then(|value| db.insert_unsynced(collection, value));
+        match result {
+            Ok(_) => report.imported += 1,
+            Err(e) if options.skip_errors => {
+                report.failed += 1;
+                if report.errors.len() < MAX_REPORTED_ERRORS {
+                    report.errors.push(format!("record {}: {}", number, e));
+                }
+            }
+            Err(e) => {
+                db.sync_if_durable()?;
+                return Err(at_record(e, number));
+            }
+        }
+
+        if number % batch_size == 0 {
+            db.sync_if_durable()?;
+            progress(&ImportProgress { records: number, imported: report.imported, failed: report.failed });
+        }
+    }
+
+    db.sync_if_durable()?;
+    progress(&ImportProgress { records: report.records, imported: report.imported, failed: report.failed });
+    Ok(report)
+}
+
+/// Say which record an error came from
+fn at_record(err: KeraDBError, record: usize) -> KeraDBError {
+    let at = |message: String| format!("{} (record {})", message, record);
+    match err {
+        KeraDBError::InvalidDocument(m) => KeraDBError::InvalidDocument(at(m)),
+        KeraDBError::ParseError(m) => KeraDBError::ParseError(at(m)),
+        KeraDBError::DuplicateKey(m) => KeraDBError::DuplicateKey(at(m)),
+        KeraDBError::StorageError(m) => KeraDBError::StorageError(at(m)),
+        other => other,
+    }
+}
+
+pub(crate) type Records<'a> = Box<dyn Iterator<Item = Result<Value>> + 'a>;
+
+pub(crate) fn records<'a, R: BufRead + 'a>(reader: R, format: ImportFormat, options: &ImportOptions) -> Result<Records<'a>> {
+    match format {
+        ImportFormat::Ndjson => Ok(Box::new(
+            reader
+                .lines()
+                .filter(|line| !matches!(line, Ok(l) if l.trim().is_empty()))
+                .map(|line| serde_json::from_str(&line?).map_err(|e| KeraDBError::ParseError(e.to_string()))),
+        )),
+        ImportFormat::Json => match serde_json::from_reader(reader).map_err(|e| KeraDBError::ParseError(e.to_string()))? {
+            Value::Array(values) => Ok(Box::new(values.into_iter().map(Ok))),
+            value => Ok(Box::new(std::iter::once(Ok(value)))),
+        },
+        ImportFormat::Csv => {
+            let mut csv = csv::ReaderBuilder::new().delimiter(options.delimiter).from_reader(reader);
+            let headers = csv.headers().map_err(|e| KeraDBError::ParseError(e.to_string()))?;
+            let fields: Vec<String> = headers
+                .iter()
+                .map(|h| options.field_map.get(h).cloned().unwrap_or_else(|| h.to_string()))
+                .collect();
+            Ok(Box::new(csv.into_records().map(move |row| {
+                let row = row.map_err(|e| KeraDBError::ParseError(e.to_string()))?;
+                let mut doc = Value::Object(Map::new());
+                for (field, cell) in fields.iter().zip(row.iter()) {
+                    if !field.is_empty() && !cell.is_empty() {
+                        set_path(&mut doc, field, csv_value(cell));
+                    }
+                }
+                Ok(doc)
+            })))
+        }
+    }
+}
+
+/// A CSV cell as a number or boolean if it looks like one, else a string
+fn csv_value(cell: &str) -> Value {
+    if let Ok(i) = cell.parse::<i64>() {
+        return Value::from(i);
+    }
+    if let Some(f) = cell.parse::<f64>().ok().filter(|f| f.is_finite()) {
+        return Value::from(f);
+    }
+    match cell {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => Value::String(cell.to_string()),
+    }
+}
+
+/// Set a dotted field, creating intermediate objects
+pub(crate) fn set_path(doc: &mut Value, path: &str, value: Value) {
+    let mut current = doc;
+    let mut parts = path.split('.').peekable();
+    while let Some(part) = parts.next() {
+        let Value::Object(map) = current else { return };
+        if parts.peek().is_none() {
+            map.insert(part.to_string(), value);
+            return;
+        }
+        current = map.entry(part).or_insert_with(|| Value::Object(Map::new()));
+    }
+}
+
+/// Copy `id_field` into `_id`
+fn with_id(mut value: Value, id_field: Option<&str>) -> Result<Value> {
+    let Some(field) = id_field else { return Ok(value) };
+    let id = match field.split('.').try_fold(&value, |v, key| v.get(key)) {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Number(n)) => n.to_string(),
+        Some(other) => {
+            return Err(KeraDBError::InvalidDocument(format!("'{}' must be a string or number, got {}", field, other)))
+        }
+        None => return Err(KeraDBError::InvalidDocument(format!("Missing ID field '{}'", field))),
+    };
+    if let Value::Object(map) = &mut value {
+        map.insert("_id".to_string(), Value::String(id));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_import_ndjson_and_csv() {
+        let dir = tempdir().unwrap();
+        let db = Database::create(dir.path().join("test.ndb")).unwrap();
+
+        let ndjson = "{\"sku\": 1, \"name\": \"pen\"}\n\n{\"sku\": 2, \"name\": \"ink\"}\n";
+        let options = ImportOptions::default().with_id_field("sku");
+        let report = db.import_ndjson("items", ndjson.as_bytes(), &options).unwrap();
+        assert_eq!((report.records, report.imported), (2, 2));
+        assert_eq!(db.find_by_id("items", "2").unwrap().get("name"), Some(json!("ink")));
+
+        // A duplicate ID stops the import, or is skipped and reported
+        let err = db.import_ndjson("items", "{\"sku\": 3}\n{\"sku\": 1}\n".as_bytes(), &options).unwrap_err();
+        assert!(matches!(err, KeraDBError::DuplicateKey(ref m) if m == "1 (record 2)"));
+        let report = db.import_ndjson("items", "{\"sku\": 1}\n{\"sku\": 4}".as_bytes(), &options.with_skip_errors(true)).unwrap();
+        assert_eq!((report.imported, report.failed), (1, 1));
+        assert_eq!(db.count("items"), 4);
+
+        let csv = "id,name,city,age,active\n7,Alice,Oslo,30,true\n8,Bob,,41.5,false\n";
+        let options = ImportOptions::default()
+            .with_format(ImportFormat::Csv)
+            .with_id_field("id")
+            .with_field("city", "address.city")
+            .with_batch_size(1);
+        let mut batches = 0;
+        let report = db.import_reader("users", csv.as_bytes(), &options, |_| batches += 1).unwrap();
+        assert_eq!((report.imported, batches), (2, 3));
+
+        let alice = db.find_by_id("users", "7").unwrap();
+        assert_eq!(alice.get("address"), Some(json!({"city": "Oslo"})));
+        assert_eq!((alice.get("age"), alice.get("active")), (Some(json!(30)), Some(json!(true))));
+        let bob = db.find_by_id("users", "8").unwrap();
+        assert_eq!((bob.get("address"), bob.get("age")), (None, Some(json!(41.5))));
+    }
+}
@@ -1,43 +1,97 @@
 pub mod error;
 pub mod types;
+pub mod config;
 pub mod storage;
 pub mod execution;
+#[cfg(feature = "cli")]
 pub mod cli;
 pub mod ffi;
 pub mod vector;
+pub mod aggregate;
+pub mod query;
+pub mod advisor;
+pub mod flusher;
+pub mod collation;
+pub mod oplog;
+pub mod hooks;
+pub mod geo;
+pub mod graph;
+pub mod replication;
+pub mod backup;
+pub mod bench;
+pub mod dump;
+pub mod import;
+pub mod metrics;
+pub mod stats;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "mongo")]
+pub mod compat;
+#[cfg(feature = "async")]
+pub mod async_db;
 
 use error::Result;
+use execution::references::Cascaded;
 use execution::Executor;
-use storage::Pager;
-use types::{Config, DocumentId};
+use hooks::{HookFn, Hooks};
+use oplog::{ChangeEvent, ChangeStream, OperationType, Oplog};
+use storage::{Pager, StorageBackend};
+use vector::store::VectorStore;
+use types::DocumentId;
 use serde_json::Value;
 use std::path::{Path, PathBuf};
 
 // Vector database imports (internal use)
 use vector::embedding::{EmbeddingProvider, EmbeddingConfig, create_provider};
-use parking_lot::RwLock;
+use vector::policy::GuardedProvider;
+use parking_lot::{Mutex, RwLock};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::fs;
-use std::io::{Read, Write};
-use serde::{Serialize, Deserialize};
-
-/// Serialized vector data format for persistence
-#[derive(Serialize, Deserialize)]
-struct SerializedVectorData {
-    version: u32,
-    collections: Vec<Vec<u8>>,
-}
+use std::io::Write;
+
+/// Setting holding the stored config of the embedding provider
+const EMBEDDING_PROVIDER_SETTING: &str = "embedding_provider";
+
+/// Vector collections by name
+type VectorCollections = HashMap<String, Arc<vector::search::VectorCollection>>;
 
 /// Main database interface
 pub struct Database {
     executor: Executor,
+    /// Recent document changes, tailed by change streams
+    oplog: Arc<Oplog>,
+    /// Callbacks run after document writes
+    hooks: Hooks,
+    /// Geohash indexes, by collection
+    geo_indexes: RwLock<HashMap<String, Arc<geo::GeoIndex>>>,
+    /// Index over the graph edge collection, loaded on first use
+    adjacency: RwLock<Option<Arc<graph::Adjacency>>>,
+    /// Vector collections kept in step by `auto_embed`, by document collection
+    embedded_collections: RwLock<HashMap<String, String>>,
     /// Vector collections for similarity search
-    vector_collections: RwLock<HashMap<String, vector::search::VectorCollection>>,
+    vector_collections: RwLock<VectorCollections>,
+    /// Set when vector collections change; cleared when they are saved
+    vector_dirty: AtomicBool,
+    /// Serializes writers of the vector sidecar file
+    vector_save_lock: Mutex<()>,
     /// Default embedding provider
-    embedding_provider: Option<Arc<dyn EmbeddingProvider>>,
-    /// Path to the database file (for vector persistence)
+    embedding_provider: RwLock<Option<Arc<dyn EmbeddingProvider>>>,
+    /// Path to the database file, or the name given with its backends
     db_path: PathBuf,
+    /// Where vector collections are saved
+    vectors: VectorStore,
+    /// Opened with `open_read_only`; every write fails with `KeraDBError::ReadOnly`
+    read_only: bool,
+    /// Operation counters and latencies
+    metrics: metrics::Metrics,
+    /// Full scans that an index could have spared
+    advisor: advisor::Advisor,
+    /// Wakes the background flusher, if one is running
+    flush_signal: RwLock<Option<Arc<flusher::Signal>>>,
+    /// Settings the database was created or opened with
+    config: Config,
 }
 
 impl Database {
@@ -50,94 +104,71 @@ impl Database {
         path
     }
 
-    /// Load vector collections from disk
-    fn load_vector_collections(db_path: &Path) -> HashMap<String, vector::search::VectorCollection> {
-        let vector_path = Self::vector_data_path(db_path);
-        if !vector_path.exists() {
-            return HashMap::new();
-        }
+    /// Load vector collections from storage
+    ///
+    /// When the newest save cannot be read or decoded, the one before it is
+    /// loaded instead. Collections that cannot be decoded are skipped and
+    /// reported. Fails for a save in a format a newer KeraDB wrote and this
+    /// one cannot read, rather than skip collections the next save would drop.
+    fn load_vector_collections(
+        store: &VectorStore,
+    ) -> Result<(VectorCollections, Vec<types::OpenWarning>)> {
+        let mut collections = HashMap::new();
+        let mut warnings = Vec::new();
 
-        match fs::File::open(&vector_path) {
-            Ok(mut file) => {
-                let mut data = Vec::new();
-                if let Err(e) = file.read_to_end(&mut data) {
-                    eprintln!("Failed to read vector data file: {}", e);
-                    return HashMap::new();
+        // Collections stream in as they are read; the save's checksum is
+        // checked once all of it is
+        let decode = |reader: std::io::Result<Option<vector::store::SaveReader>>| {
+            let Some(mut reader) = reader? else { return Ok(None) };
+            let decoded = vector::format::decode(&mut reader)?;
+            reader.finish()?;
+            Ok::<_, error::KeraDBError>(Some(decoded))
+        };
+        let decoded = match decode(store.reader()) {
+            Ok(Some(decoded)) => decoded,
+            Ok(None) => return Ok((collections, warnings)),
+            Err(e @ error::KeraDBError::VersionMismatch { .. }) => return Err(e),
+            Err(e) => match decode(store.previous_reader()) {
+                Ok(Some(decoded)) => {
+                    warnings.push(types::OpenWarning::PreviousVectorFile { error: e.to_string() });
+                    decoded
                 }
-                
-                // Deserialize the collections
-                match bincode::deserialize::<SerializedVectorData>(&data) {
-                    Ok(serialized) => {
-                        let mut collections = HashMap::new();
-                        for coll_data in serialized.collections {
-                            match vector::search::VectorCollection::from_bytes(&coll_data) {
-                                Ok(coll) => {
-                                    collections.insert(coll.name.clone(), coll);
-                                }
-                                Err(e) => {
-                                    eprintln!("Failed to deserialize vector collection: {}", e);
-                                }
-                            }
-                        }
-                        collections
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to deserialize vector data: {}", e);
-                        HashMap::new()
-                    }
+                _ => {
+                    warnings.push(types::OpenWarning::VectorFile { error: e.to_string() });
+                    tracing::warn!("{}", warnings[0]);
+                    return Ok((collections, warnings));
                 }
+            },
+        };
+
+        for (position, collection) in decoded.into_iter().enumerate() {
+            match collection {
+                Ok(coll) => {
+                    collections.insert(coll.name.clone(), Arc::new(coll));
+                }
+                Err(error) => warnings.push(types::OpenWarning::VectorCollection { position, error }),
             }
-            Err(e) => {
-                eprintln!("Failed to open vector data file: {}", e);
-                HashMap::new()
-            }
         }
+
+        for warning in &warnings {
+            tracing::warn!("{}", warning);
+        }
+        Ok((collections, warnings))
     }
 
-    /// Save vector collections to disk
+    /// Save vector collections to storage
     fn save_vector_collections(&self) -> Result<()> {
-        let vector_path = Self::vector_data_path(&self.db_path);
-        let collections = self.vector_collections.read();
-        
+        let _guard = self.vector_save_lock.lock();
+        // Snapshot the collection handles so writers are not blocked while serializing
+        let collections: Vec<Arc<vector::search::VectorCollection>> =
+            self.vector_collections.read().values().cloned().collect();
         if collections.is_empty() {
-            // Remove vector file if no collections
-            let _ = fs::remove_file(&vector_path);
+            // Remove vector data if no collections
+            self.vectors.clear();
             return Ok(());
         }
-        
-        let mut coll_bytes = Vec::new();
-        for coll in collections.values() {
-            match coll.to_bytes() {
-                Ok(bytes) => coll_bytes.push(bytes),
-                Err(e) => {
-                    return Err(error::KeraDBError::StorageError(
-                        format!("Failed to serialize vector collection: {}", e)
-                    ));
-                }
-            }
-        }
-        
-        let serialized = SerializedVectorData {
-            version: 1,
-            collections: coll_bytes,
-        };
-        
-        let data = bincode::serialize(&serialized).map_err(|e| {
-            error::KeraDBError::StorageError(format!("Failed to serialize vector data: {}", e))
-        })?;
-        
-        let mut file = fs::File::create(&vector_path).map_err(|e| {
-            error::KeraDBError::StorageError(format!("Failed to create vector file: {}", e))
-        })?;
-        
-        file.write_all(&data).map_err(|e| {
-            error::KeraDBError::StorageError(format!("Failed to write vector data: {}", e))
-        })?;
-        
-        file.sync_all().map_err(|e| {
-            error::KeraDBError::StorageError(format!("Failed to sync vector file: {}", e))
-        })?;
-        
+        self.vectors.write_with(&|writer| vector::format::encode(&collections, writer))?;
+        self.metrics.record_fsync();
         Ok(())
     }
 
@@ -150,18 +181,93 @@ impl Database {
     /// Create a new database with custom configuration
     pub fn create_with_config<P: AsRef<Path>>(path: P, config: Config) -> Result<Self> {
         let path = path.as_ref();
-        let pager = Pager::create(path, config.page_size)?;
-        let executor = Executor::new(pager, config.cache_size);
+        let pager = Pager::create_with_options(path, config.page_size, config.file_options())?;
+        // Vectors of a database that was at this path before are not this one's
+        let vectors = VectorStore::File(Self::vector_data_path(path));
+        vectors.clear();
+        Ok(Self::create_with_pager(path, pager, vectors, config))
+    }
+
+    /// Create a database that lives only in memory
+    ///
+    /// Nothing is written to disk: [`sync`](Self::sync) does nothing, and the
+    /// data is gone when the database is dropped unless it is first copied to
+    /// a file with [`persist_to`](Self::persist_to). Useful for tests, caches
+    /// and other throwaway data.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let db = Database::in_memory()?;
+    /// db.insert("sessions", json!({"user": "alice"}))?;
+    /// ```
+    pub fn in_memory() -> Result<Self> {
+        Self::in_memory_with_config(Config::default())
+    }
+
+    /// Create an in-memory database with custom configuration
+    pub fn in_memory_with_config(config: Config) -> Result<Self> {
+        let name = Path::new(":memory:");
+        let pager = Pager::create_with_backend(Box::new(storage::MemoryBackend::new()), name, config.page_size)?;
+        Ok(Self::create_with_pager(name, pager, VectorStore::Memory, config))
+    }
+
+    /// Create a new database in storage backends rather than files
+    ///
+    /// Documents go to `data` and vector collections to `vectors`; whatever
+    /// either held is overwritten. `name` only identifies the database in
+    /// logs and [`stats`](Self::stats). This is how KeraDB runs in a browser,
+    /// over [`OpfsBackend`](storage::OpfsBackend) files or a
+    /// [`MemoryBackend`](storage::MemoryBackend) the page saves to IndexedDB.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let data = MemoryBackend::new();
+    /// let db = Database::create_with_backends("app", Box::new(data.clone()), Box::new(MemoryBackend::new()), Config::default())?;
+    /// db.insert("users", json!({"name": "Alice"}))?;
+    /// db.sync()?;
+    /// save_somewhere(data.to_bytes());
+    /// ```
+    pub fn create_with_backends<P: AsRef<Path>>(
+        name: P,
+        data: Box<dyn StorageBackend>,
+        vectors: Box<dyn StorageBackend>,
+        config: Config,
+    ) -> Result<Self> {
+        let name = name.as_ref();
+        let pager = Pager::create_with_backend(data, name, config.page_size)?;
+        vectors.set_size(0)?;
+        Ok(Self::create_with_pager(name, pager, VectorStore::Backend(vectors), config))
+    }
+
+    fn create_with_pager(path: &Path, pager: Pager, vectors: VectorStore, config: Config) -> Self {
+        let executor = Executor::new(pager, config.cache_size).with_max_document_size(config.max_document_size);
         
-        Ok(Self { 
+        Self { 
             executor,
+            oplog: Arc::new(Oplog::new(config.oplog_capacity)),
+            hooks: Hooks::default(),
+            geo_indexes: RwLock::new(HashMap::new()),
+            adjacency: RwLock::new(None),
+            embedded_collections: RwLock::new(HashMap::new()),
             vector_collections: RwLock::new(HashMap::new()),
-            embedding_provider: None,
+            vector_dirty: AtomicBool::new(false),
+            vector_save_lock: Mutex::new(()),
+            embedding_provider: RwLock::new(None),
             db_path: path.to_path_buf(),
-        })
+            vectors,
+            read_only: false,
+            metrics: metrics::Metrics::new(),
+            advisor: advisor::Advisor::default(),
+            flush_signal: RwLock::new(None),
+            config,
+        }
     }
 
     /// Open an existing database file
+    ///
+    /// The file is locked exclusively until the database is dropped; opening
+    /// it again, from this process or another, fails with
+    /// [`KeraDBError::Locked`].
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
         let config = Config::default();
         Self::open_with_config(path, config)
@@ -169,19 +275,143 @@ impl Database {
 
     /// Open an existing database with custom configuration
     pub fn open_with_config<P: AsRef<Path>>(path: P, config: Config) -> Result<Self> {
+        Ok(Self::open_with_report(path, config)?.0)
+    }
+
+    /// Open an existing database and report anything that could not be loaded
+    ///
+    /// Unreadable pages, invalid documents and undecodable vector collections
+    /// are skipped (and logged with `tracing`) so the rest of the data stays
+    /// available; the report lists them.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let (db, report) = Database::open_with_report("mydata.ndb", Config::default())?;
+    /// for warning in &report.warnings {
+    ///     println!("{}", warning);
+    /// }
+    /// ```
+    pub fn open_with_report<P: AsRef<Path>>(path: P, config: Config) -> Result<(Self, types::OpenReport)> {
+        let path = path.as_ref();
+        let pager = Pager::open_with_options(path, false, config.file_options())?;
+        Self::open_with_pager(path, pager, VectorStore::File(Self::vector_data_path(path)), config)
+    }
+
+    /// Open an existing database file for reading only
+    ///
+    /// Any number of read-only handles can be open at once, but none while a
+    /// writer holds the file (and vice versa). Writes fail with
+    /// [`KeraDBError::ReadOnly`].
+    ///
+    /// # Example
+    /// ```ignore
+    /// let db = Database::open_read_only("mydata.ndb")?;
+    /// ```
+    pub fn open_read_only<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_read_only_with_config(path, Config::default())
+    }
+
+    /// Open an existing database file for reading only, with custom configuration
+    pub fn open_read_only_with_config<P: AsRef<Path>>(path: P, config: Config) -> Result<Self> {
         let path = path.as_ref();
-        let pager = Pager::open(path)?;
-        let executor = Executor::new(pager, config.cache_size);
+        let vectors = VectorStore::File(Self::vector_data_path(path));
+        let pager = Pager::open_with_options(path, true, config.file_options())?;
+        Ok(Self::open_with_pager(path, pager, vectors, config)?.0)
+    }
+
+    /// Open a database saved in storage backends by
+    /// [`create_with_backends`](Self::create_with_backends)
+    ///
+    /// Backends are not locked, so the caller must not open the same storage
+    /// twice. Like [`open_with_report`](Self::open_with_report), the report
+    /// lists anything that could not be loaded.
+    pub fn open_with_backends<P: AsRef<Path>>(
+        name: P,
+        data: Box<dyn StorageBackend>,
+        vectors: Box<dyn StorageBackend>,
+        read_only: bool,
+        config: Config,
+    ) -> Result<(Self, types::OpenReport)> {
+        let name = name.as_ref();
+        let pager = Pager::open_with_backend(data, name, read_only)?;
+        Self::open_with_pager(name, pager, VectorStore::Backend(vectors), config)
+    }
+
+    fn open_with_pager(
+        path: &Path,
+        pager: Pager,
+        vectors: VectorStore,
+        config: Config,
+    ) -> Result<(Self, types::OpenReport)> {
+        let read_only = pager.is_read_only();
+        let (executor, mut warnings) = Executor::open(pager, config.cache_size);
+        let executor = executor.with_max_document_size(config.max_document_size);
         
-        // Load vector collections from disk
-        let vector_collections = Self::load_vector_collections(path);
+        // Load vector collections from storage
+        let (vector_collections, vector_warnings) = Self::load_vector_collections(&vectors)?;
+        warnings.extend(vector_warnings);
+
+        let mut vector_names: Vec<String> = vector_collections.keys().cloned().collect();
+        vector_names.sort();
+        let mut report = types::OpenReport {
+            documents: executor.list_collections().iter().map(|(_, count)| count).sum(),
+            vector_collections: vector_names,
+            warnings,
+        };
         
-        Ok(Self { 
+        let db = Self { 
             executor,
+            oplog: Arc::new(Oplog::new(config.oplog_capacity)),
+            hooks: Hooks::default(),
+            geo_indexes: RwLock::new(HashMap::new()),
+            adjacency: RwLock::new(None),
+            embedded_collections: RwLock::new(HashMap::new()),
             vector_collections: RwLock::new(vector_collections),
-            embedding_provider: None,
+            vector_dirty: AtomicBool::new(false),
+            vector_save_lock: Mutex::new(()),
+            embedding_provider: RwLock::new(None),
             db_path: path.to_path_buf(),
-        })
+            vectors,
+            read_only,
+            metrics: metrics::Metrics::new(),
+            advisor: advisor::Advisor::default(),
+            flush_signal: RwLock::new(None),
+            config,
+        };
+
+        // Text search needs the provider the database was last given
+        if let Some(stored) = db.executor.setting(EMBEDDING_PROVIDER_SETTING) {
+            match EmbeddingConfig::from_stored(stored).and_then(create_provider) {
+                Ok(provider) => db.attach_embedding_provider(provider),
+                Err(e) => {
+                    tracing::warn!("Could not restore the embedding provider: {}", e);
+                    report.warnings.push(types::OpenWarning::EmbeddingProvider { error: e.to_string() });
+                }
+            }
+        }
+        Ok((db, report))
+    }
+
+    /// Whether the database was created with [`in_memory`](Self::in_memory)
+    pub fn is_in_memory(&self) -> bool {
+        matches!(self.vectors, VectorStore::Memory)
+    }
+
+    /// Whether the database was opened with [`open_read_only`](Self::open_read_only)
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Settings the database was created or opened with
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        if self.read_only {
+            return Err(error::KeraDBError::ReadOnly);
+        }
+        Ok(())
     }
 
     /// Insert a document into a collection
@@ -192,7 +422,44 @@ impl Database {
     /// let id = db.insert("users", doc)?;
     /// ```
     pub fn insert(&self, collection: &str, data: Value) -> Result<DocumentId> {
-        self.executor.insert(collection, data)
+        self.metrics.inserts.time(|| {
+            let id = self.insert_unsynced(collection, data)?;
+            self.sync_if_durable()?;
+            Ok(id)
+        })
+    }
+
+    /// Insert without the [`Durability::Full`](types::Durability::Full)
+    /// fsync; the caller syncs, e.g. once per import batch
+    pub(crate) fn insert_unsynced(&self, collection: &str, data: Value) -> Result<DocumentId> {
+        self.check_writable()?;
+        let mut doc = types::Document::with_id(String::new(), data.clone());
+        let (id, evicted) = self.executor.insert_evicting(collection, data)?;
+        doc.id = id;
+        if let Value::Object(ref mut map) = doc.data {
+            map.remove("_id");
+        }
+        self.record_cascaded(&evicted);
+        self.record(OperationType::Insert, collection, &doc);
+        Ok(doc.id)
+    }
+
+    /// Append a write to the oplog and run its hooks
+    fn record(&self, operation: OperationType, collection: &str, doc: &types::Document) {
+        let seq = self.oplog.append(operation, collection, doc);
+        self.hooks.fire(self, operation, collection, || ChangeEvent::new(seq, operation, collection, doc));
+    }
+
+    /// Record the writes a delete's references or a quota's evictions made
+    fn record_cascaded(&self, writes: &[Cascaded]) {
+        for write in writes {
+            match write {
+                Cascaded::Deleted(collection, doc) | Cascaded::Evicted(collection, doc) => {
+                    self.record(OperationType::Delete, collection, doc)
+                }
+                Cascaded::Updated(collection, doc) => self.record(OperationType::Update, collection, doc),
+            }
+        }
     }
 
     /// Find a document by ID
@@ -202,7 +469,7 @@ impl Database {
     /// let doc = db.find_by_id("users", "abc123")?;
     /// ```
     pub fn find_by_id(&self, collection: &str, doc_id: &str) -> Result<types::Document> {
-        self.executor.find_by_id(collection, doc_id)
+        self.metrics.finds.time(|| self.executor.find_by_id(collection, doc_id))
     }
 
     /// Update a document
@@ -212,7 +479,14 @@ impl Database {
     /// db.update("users", "abc123", json!({"age": 31}))?;
     /// ```
     pub fn update(&self, collection: &str, doc_id: &str, data: Value) -> Result<types::Document> {
-        self.executor.update(collection, doc_id, data)
+        self.metrics.updates.time(|| {
+            self.check_writable()?;
+            let (doc, evicted) = self.executor.update_evicting(collection, doc_id, data)?;
+            self.record_cascaded(&evicted);
+            self.record(OperationType::Update, collection, &doc);
+            self.sync_if_durable()?;
+            Ok(doc)
+        })
     }
 
     /// Delete a document
@@ -222,7 +496,14 @@ impl Database {
     /// db.delete("users", "abc123")?;
     /// ```
     pub fn delete(&self, collection: &str, doc_id: &str) -> Result<types::Document> {
-        self.executor.delete(collection, doc_id)
+        self.metrics.deletes.time(|| {
+            self.check_writable()?;
+            let (doc, cascaded) = self.executor.delete_cascading(collection, doc_id)?;
+            self.record(OperationType::Delete, collection, &doc);
+            self.record_cascaded(&cascaded);
+            self.sync_if_durable()?;
+            Ok(doc)
+        })
     }
 
     /// Find all documents in a collection
@@ -233,7 +514,22 @@ impl Database {
     /// let page = db.find_all("users", Some(10), Some(20))?; // limit 10, skip 20
     /// ```
     pub fn find_all(&self, collection: &str, limit: Option<usize>, skip: Option<usize>) -> Result<Vec<types::Document>> {
-        self.executor.find_all(collection, limit, skip)
+        self.metrics.scans.time(|| self.executor.find_all(collection, limit, skip))
+    }
+
+    /// Read the documents in a collection with explicit scan options
+    ///
+    /// Large collections are read in parallel batches; set
+    /// [`ScanOptions::ordered`](types::ScanOptions) to get documents back in
+    /// insertion order.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let options = ScanOptions::default().with_ordered(true).with_limit(1000);
+    /// let docs = db.scan("events", &options)?;
+    /// ```
+    pub fn scan(&self, collection: &str, options: &types::ScanOptions) -> Result<Vec<types::Document>> {
+        self.metrics.scans.time(|| self.executor.scan(collection, options))
     }
 
     /// Count documents in a collection
@@ -259,258 +555,1694 @@ impl Database {
         self.executor.list_collections()
     }
 
-    /// Sync all changes to disk (including vector data)
-    pub fn sync(&self) -> Result<()> {
-        // Sync document data
-        self.executor.sync()?;
-        
-        // Sync vector collections
-        self.save_vector_collections()?;
-        
-        Ok(())
+    /// Operation counters, latency histograms and cache statistics
+    ///
+    /// # Example
+    /// ```ignore
+    /// let metrics = db.metrics();
+    /// println!("{} inserts", metrics.operation("insert").unwrap().count);
+    /// std::fs::write("metrics.prom", metrics.to_prometheus())?;
+    /// ```
+    pub fn metrics(&self) -> metrics::MetricsSnapshot {
+        let collections = self.list_collections();
+        let vector_collections = self.list_vector_collections();
+        metrics::MetricsSnapshot {
+            operations: self.metrics.operations(),
+            cache: self.executor.cache_stats(),
+            fsyncs: self.metrics.fsyncs(),
+            embedding_retries: self.metrics.embeddings.retries(),
+            embedding_rejections: self.metrics.embeddings.rejections(),
+            documents: collections.iter().map(|(_, count)| count).sum(),
+            collections: collections.len(),
+            vectors: vector_collections.iter().map(|(_, count)| count).sum(),
+            vector_collections: vector_collections.len(),
+        }
     }
 
-    // ============================================================
-    // Vector Database API
-    // ============================================================
+    /// The database's UUID, creation time and the KeraDB version that last
+    /// opened it for writing, from its file header
+    ///
+    /// The UUID stays the same for the life of the database, including in
+    /// copies and backups of its file, so it can tell replicas of one
+    /// database from unrelated ones.
+    ///
+    /// # Example
+    /// ```ignore
+    /// println!("{} created {:?}", db.info().uuid, db.info().created_at);
+    /// ```
+    pub fn info(&self) -> DatabaseInfo {
+        self.executor.database_info().clone()
+    }
 
-    /// Create a vector collection for similarity search
-    /// 
+    /// Storage, cache and vector statistics for the whole database
+    ///
+    /// Reads every page of the data file, so it takes longer the larger the
+    /// database is; use [`metrics`](Self::metrics) for cheap, frequent polling.
+    ///
     /// # Example
     /// ```ignore
-    /// use keradb::vector::VectorConfig;
-    /// 
-    /// let db = Database::create("mydata.ndb")?;
-    /// db.create_vector_collection("embeddings", VectorConfig::new(384))?;
+    /// let stats = db.stats()?;
+    /// println!("{} free pages of {}", stats.pages.free, stats.pages.total);
     /// ```
-    pub fn create_vector_collection(&self, name: &str, config: vector::VectorConfig) -> Result<()> {
-        let mut collections = self.vector_collections.write();
-        
-        if collections.contains_key(name) {
-            return Err(error::KeraDBError::CollectionExists(name.to_string()));
-        }
-        
-        let collection = if let Some(ref provider) = self.embedding_provider {
-            vector::search::VectorCollection::with_embedding_provider(
-                name.to_string(),
-                config,
-                provider.clone(),
-            )
-        } else {
-            vector::search::VectorCollection::new(name.to_string(), config)
-        };
-        
-        collections.insert(name.to_string(), collection);
-        drop(collections); // Release the lock before saving
-        
-        // Auto-save vector collections
-        self.save_vector_collections()?;
-        
-        Ok(())
+    pub fn stats(&self) -> Result<stats::DatabaseStats> {
+        let (pages, collections) = self.executor.storage_stats();
+        let mut vector_collections: Vec<_> = self.vector_collections.read().values().map(|c| c.stats()).collect();
+        vector_collections.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(stats::DatabaseStats {
+            path: self.db_path.clone(),
+            info: self.info(),
+            file_size: self.executor.storage_size()?,
+            vector_file_size: self.vectors.size()?,
+            page_size: self.executor.page_size(),
+            pages,
+            collections,
+            cache: self.executor.cache_stats(),
+            vector_collections,
+        })
     }
 
-    /// Insert a vector into a collection
-    /// 
+    /// How a `find` query would run: the access path it uses, the documents
+    /// it would read and how it sorts; see [`query`]
+    ///
     /// # Example
     /// ```ignore
-    /// let vector = vec![0.1, 0.2, 0.3, ...]; // 384 dimensions
-    /// let id = db.insert_vector("embeddings", vector, Some(json!({"source": "doc1"})))?;
+    /// println!("{}", db.explain(&query));
     /// ```
-    pub fn insert_vector(
-        &self,
-        collection: &str,
-        vector: Embedding,
-        metadata: Option<Value>,
-    ) -> Result<VectorId> {
-        let id = {
-            let collections = self.vector_collections.read();
-            let coll = collections.get(collection).ok_or_else(|| {
-                error::KeraDBError::CollectionNotFound(collection.to_string())
-            })?;
-            coll.insert(vector, metadata)?
-        };
-        
-        // Auto-save vector collections after insert
-        self.save_vector_collections()?;
-        
-        Ok(id)
+    pub fn explain(&self, query: &query::FindQuery) -> query::QueryPlan {
+        query::PreparedQuery::new(query.clone(), &self.executor).explain(&self.executor)
     }
 
-    /// Insert text into a vector collection (requires embedding provider)
-    /// 
+    /// Indexes that would have spared the filtered `find` and `count`
+    /// queries run so far a full scan, most reads saved first; see [`advisor`]
+    ///
     /// # Example
     /// ```ignore
-    /// db.insert_text("documents", "Machine learning is fascinating", 
-    ///     Some(json!({"category": "tech"})))?;
+    /// for suggestion in db.index_suggestions() {
+    ///     println!("{}", suggestion);
+    /// }
     /// ```
-    pub fn insert_text(
-        &self,
-        collection: &str,
-        text: &str,
-        metadata: Option<Value>,
-    ) -> Result<VectorId> {
-        let id = {
-            let collections = self.vector_collections.read();
-            let coll = collections.get(collection).ok_or_else(|| {
-                error::KeraDBError::CollectionNotFound(collection.to_string())
-            })?;
-            coll.insert_text(text, metadata)?
-        };
-        
-        // Auto-save vector collections after insert
-        self.save_vector_collections()?;
-        
-        Ok(id)
+    pub fn index_suggestions(&self) -> Vec<advisor::IndexSuggestion> {
+        self.advisor.suggestions(&self.executor)
     }
 
-    /// Search for similar vectors
-    /// 
+    /// Compile a `find` query and choose how it runs once, for running it
+    /// many times, like a prepared statement
+    ///
     /// # Example
     /// ```ignore
-    /// let query = vec![0.1, 0.2, 0.3, ...];
-    /// let results = db.vector_search("embeddings", &query, 10)?;
-    /// for result in results {
-    ///     println!("ID: {}, Score: {}", result.document.id, result.score);
+    /// let adults = db.prepare(query);
+    /// for _ in 0..1000 {
+    ///     let docs = adults.run(&db)?;
     /// }
     /// ```
-    pub fn vector_search(
-        &self,
-        collection: &str,
-        query: &Embedding,
-        k: usize,
-    ) -> Result<Vec<VectorSearchResult>> {
-        let collections = self.vector_collections.read();
-        let coll = collections.get(collection).ok_or_else(|| {
-            error::KeraDBError::CollectionNotFound(collection.to_string())
-        })?;
-        coll.search(query, k)
+    pub fn prepare(&self, query: query::FindQuery) -> query::PreparedQuery {
+        query::PreparedQuery::new(query, &self.executor)
     }
 
-    /// Search for similar vectors by text query
-    /// 
+    /// Run an aggregation pipeline over a collection; see [`aggregate`]
+    ///
+    /// Over large collections, the leading match and group stages run on
+    /// [`Config::query_threads`] ranges of the collection at once.
+    ///
     /// # Example
     /// ```ignore
-    /// let results = db.vector_search_text("documents", "artificial intelligence", 10)?;
+    /// let pipeline = Pipeline::new().group(Group::by("customer").sum("spent", "total"));
+    /// let spending = db.aggregate("orders", &pipeline)?;
     /// ```
-    pub fn vector_search_text(
-        &self,
-        collection: &str,
-        query: &str,
-        k: usize,
-    ) -> Result<Vec<VectorSearchResult>> {
-        let collections = self.vector_collections.read();
-        let coll = collections.get(collection).ok_or_else(|| {
-            error::KeraDBError::CollectionNotFound(collection.to_string())
-        })?;
-        coll.search_text(query, k)
+    pub fn aggregate(&self, collection: &str, pipeline: &aggregate::Pipeline) -> Result<Vec<Value>> {
+        self.metrics.scans.time(|| self.executor.aggregate(collection, pipeline, self.config.query_threads))
     }
 
-    /// Search with metadata filtering
-    /// 
+    /// Keep `fields` of a collection's documents in memory as columns, so
+    /// pipelines grouping over them need not read the documents
+    ///
+    /// Adds to the fields already projected. The fields are remembered in
+    /// the database file and the columns rebuilt when it is opened; see
+    /// [`execution::columns`].
+    ///
     /// # Example
     /// ```ignore
-    /// let filter = MetadataFilter::new().eq("category", json!("tech"));
-    /// let results = db.vector_search_filtered("documents", &query, 10, &filter)?;
+    /// db.create_columns("orders", &["customer", "total"])?;
     /// ```
-    pub fn vector_search_filtered(
-        &self,
-        collection: &str,
-        query: &Embedding,
-        k: usize,
-        filter: &MetadataFilter,
-    ) -> Result<Vec<VectorSearchResult>> {
-        let collections = self.vector_collections.read();
-        let coll = collections.get(collection).ok_or_else(|| {
-            error::KeraDBError::CollectionNotFound(collection.to_string())
-        })?;
-        coll.search_filtered(query, k, filter)
+    pub fn create_columns(&self, collection: &str, fields: &[&str]) -> Result<()> {
+        self.check_writable()?;
+        self.executor.create_columns(collection, fields, None)?;
+        self.sync_if_durable()
     }
 
-    /// Get a vector document by ID
-    pub fn get_vector(&self, collection: &str, id: VectorId) -> Result<Option<vector::VectorDocument>> {
-        let collections = self.vector_collections.read();
-        let coll = collections.get(collection).ok_or_else(|| {
-            error::KeraDBError::CollectionNotFound(collection.to_string())
-        })?;
-        Ok(coll.get(id))
+    /// Like [`create_columns`](Self::create_columns), with a collation that
+    /// `find` queries naming none use to compare the projected fields
+    ///
+    /// # Example
+    /// ```ignore
+    /// // `find users where email = ALICE@EXAMPLE.COM` now matches alice@example.com
+    /// db.create_columns_with_collation("users", &["email"], Collation::NoCase)?;
+    /// ```
+    pub fn create_columns_with_collation(&self, collection: &str, fields: &[&str], collation: Collation) -> Result<()> {
+        self.check_writable()?;
+        self.executor.create_columns(collection, fields, Some(collation))?;
+        self.sync_if_durable()
     }
 
-    /// Delete a vector by ID
-    pub fn delete_vector(&self, collection: &str, id: VectorId) -> Result<bool> {
-        let result = {
-            let collections = self.vector_collections.read();
-            let coll = collections.get(collection).ok_or_else(|| {
-                error::KeraDBError::CollectionNotFound(collection.to_string())
-            })?;
-            coll.delete(id)?
-        };
-        
-        // Auto-save vector collections after delete
-        self.save_vector_collections()?;
-        
-        Ok(result)
+    /// Stop projecting a collection's fields into columns; returns whether
+    /// any were
+    pub fn drop_columns(&self, collection: &str) -> Result<bool> {
+        self.check_writable()?;
+        let dropped = self.executor.drop_columns(collection)?;
+        self.sync_if_durable()?;
+        Ok(dropped)
     }
 
-    /// List all vector collections
-    pub fn list_vector_collections(&self) -> Vec<(String, usize)> {
-        self.vector_collections
-            .read()
-            .iter()
-            .map(|(name, coll)| (name.clone(), coll.len()))
-            .collect()
+    /// The fields of a collection projected into columns
+    pub fn columns(&self, collection: &str) -> Vec<String> {
+        self.executor.columns(collection)
     }
 
-    /// Drop a vector collection
-    pub fn drop_vector_collection(&self, name: &str) -> Result<bool> {
-        let removed = self.vector_collections.write().remove(name).is_some();
-        
-        // Auto-save vector collections after drop
-        self.save_vector_collections()?;
-        
-        Ok(removed)
+    /// The collation of a collection's projected fields, if it has any
+    pub fn column_collation(&self, collection: &str) -> Option<Collation> {
+        self.executor.column_collation(collection)
     }
 
-    /// Set the default embedding provider for text-to-vector conversion
-    pub fn set_embedding_provider(&mut self, config: EmbeddingConfig) -> Result<()> {
-        self.embedding_provider = Some(create_provider(config)?);
-        Ok(())
+    /// Index each element of a collection's array-valued `field`, so `find`
+    /// queries with `=`, `in` or `contains` on it read only the documents
+    /// holding the values; returns false if the field was already indexed
+    ///
+    /// # Example
+    /// ```ignore
+    /// db.create_multikey_index("posts", "tags")?;
+    /// // `find posts where tags contains rust` now reads only the posts tagged rust
+    /// ```
+    pub fn create_multikey_index(&self, collection: &str, field: &str) -> Result<bool> {
+        self.check_writable()?;
+        let created = self.executor.create_multikey_index(collection, field)?;
+        self.sync_if_durable()?;
+        Ok(created)
     }
 
-    /// Get vector collection statistics
-    pub fn vector_stats(&self, collection: &str) -> Result<vector::VectorCollectionStats> {
-        let collections = self.vector_collections.read();
-        let coll = collections.get(collection).ok_or_else(|| {
-            error::KeraDBError::CollectionNotFound(collection.to_string())
-        })?;
-        
-        Ok(vector::VectorCollectionStats {
-            name: coll.name.clone(),
-            vector_count: coll.len(),
-            dimensions: coll.config.dimensions,
-            distance: coll.config.distance,
-            memory_bytes: coll.len() * coll.config.dimensions * 4, // Approximate
-            hnsw_layers: coll.config.m,
-            lazy_embedding: coll.config.lazy_embedding,
-            compression_mode: coll.config.compression.mode,
-            compression_ratio: 0.0, // TODO: Get actual compression ratio from store
-            anchor_count: 0,
-            delta_count: 0,
-        })
+    /// Drop the multikey index of a field; returns whether there was one
+    pub fn drop_multikey_index(&self, collection: &str, field: &str) -> Result<bool> {
+        self.check_writable()?;
+        let dropped = self.executor.drop_multikey_index(collection, field)?;
+        self.sync_if_durable()?;
+        Ok(dropped)
     }
-}
 
-// Re-export commonly used types
+    /// The fields of a collection with multikey indexes
+    pub fn multikey_indexes(&self, collection: &str) -> Vec<String> {
+        self.executor.multikey_fields(collection)
+    }
+
+    /// Limit a collection's documents, replacing any quota it had; returns
+    /// how many of the oldest documents an evicting quota deleted to fit
+    ///
+    /// Writes over the quota fail with [`KeraDBError::QuotaExceeded`] or
+    /// [`KeraDBError::DocumentTooLarge`], or evict the oldest documents if
+    /// the quota says so; see [`execution::quota`].
+    ///
+    /// # Example
+    /// ```ignore
+    /// db.set_quota("events", Quota::new().with_max_bytes(10 << 20).evicting())?;
+    /// ```
+    pub fn set_quota(&self, collection: &str, quota: execution::Quota) -> Result<usize> {
+        self.check_writable()?;
+        let writes = self.executor.set_quota_cascading(collection, quota)?;
+        self.record_cascaded(&writes);
+        self.sync_if_durable()?;
+        Ok(writes.iter().filter(|write| matches!(write, Cascaded::Evicted(..))).count())
+    }
+
+    /// Create a capped collection: one holding at most `max_documents`
+    /// documents of `max_bytes` bytes together, which deletes the oldest,
+    /// in insertion order, to make room for new ones and reuses their pages
+    ///
+    /// Fails with [`KeraDBError::CollectionExists`] if the collection has
+    /// documents or a quota already. Evicted documents are recorded as
+    /// deletes, as with an evicting [`Quota`](execution::Quota).
+    ///
+    /// # Example
+    /// ```ignore
+    /// db.create_capped_collection("logs", 100_000, 64 << 20)?;
+    /// ```
+    pub fn create_capped_collection(&self, collection: &str, max_documents: usize, max_bytes: u64) -> Result<()> {
+        self.check_writable()?;
+        self.executor.create_capped_collection(collection, max_documents, max_bytes)?;
+        self.sync_if_durable()
+    }
+
+    /// Lift a collection's quota; returns whether it had one
+    pub fn remove_quota(&self, collection: &str) -> Result<bool> {
+        self.check_writable()?;
+        let removed = self.executor.remove_quota(collection)?;
+        self.sync_if_durable()?;
+        Ok(removed)
+    }
+
+    /// A collection's quota, if it has one
+    pub fn quota(&self, collection: &str) -> Option<execution::Quota> {
+        self.executor.quota(collection)
+    }
+
+    /// Declare that a field of one collection holds IDs of documents in
+    /// another; returns false if the field already references a collection
+    ///
+    /// From then on inserts and updates naming a missing document fail with
+    /// [`KeraDBError::ReferenceViolation`], as does this call if a document
+    /// already does, and deleting a referenced document does what the
+    /// reference's [`OnDelete`] says; see [`execution::references`].
+    ///
+    /// # Example
+    /// ```ignore
+    /// // orders.customer_id -> customers._id, deleting a customer's orders with them
+    /// db.add_reference(Reference::new("orders", "customer_id", "customers").with_on_delete(OnDelete::Cascade))?;
+    /// ```
+    pub fn add_reference(&self, reference: Reference) -> Result<bool> {
+        self.check_writable()?;
+        let added = self.executor.add_reference(reference)?;
+        self.sync_if_durable()?;
+        Ok(added)
+    }
+
+    /// Drop the reference a collection's field makes; returns whether it made one
+    pub fn drop_reference(&self, collection: &str, field: &str) -> Result<bool> {
+        self.check_writable()?;
+        let dropped = self.executor.drop_reference(collection, field)?;
+        self.sync_if_durable()?;
+        Ok(dropped)
+    }
+
+    /// Every declared reference
+    pub fn references(&self) -> Vec<Reference> {
+        self.executor.references()
+    }
+
+    /// Train a compression dictionary on a collection's documents, then
+    /// compress them and later writes with it
+    ///
+    /// Worth it for collections of many small documents with the same shape,
+    /// such as events or logs. Writes to the collection wait while its
+    /// documents are rewritten. Train again once the documents have changed
+    /// shape; see [`storage::compression`].
+    ///
+    /// # Example
+    /// ```ignore
+    /// let stats = db.train_dictionary("events")?;
+    /// println!("events now take {:.0}% of the space", stats.ratio() * 100.0);
+    /// ```
+    pub fn train_dictionary(&self, collection: &str) -> Result<storage::compression::DictionaryStats> {
+        self.check_writable()?;
+        let stats = self.executor.train_dictionary(collection)?;
+        self.sync_if_durable()?;
+        Ok(stats)
+    }
+
+    /// Take a consistent read-only view of the documents
+    ///
+    /// Reads through the snapshot ignore every write made after it was taken,
+    /// and never wait for writers.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let snapshot = db.snapshot();
+    /// db.insert("users", json!({"name": "Bob"}))?;
+    /// assert_eq!(snapshot.count("users")?, db.count("users") - 1);
+    /// ```
+    pub fn snapshot(&self) -> execution::Snapshot<'_> {
+        self.executor.snapshot()
+    }
+
+    /// Watch a collection for inserts, updates and deletes
+    ///
+    /// The stream starts at the current end of the oplog, so only changes made
+    /// after this call are reported.
+    ///
+    /// # Example
+    /// ```ignore
+    /// for event in db.watch("users") {
+    ///     let event = event?;
+    ///     cache.invalidate(&event.doc_id);
+    /// }
+    /// ```
+    pub fn watch(&self, collection: &str) -> ChangeStream {
+        self.oplog.watch(Some(collection), self.oplog.latest_seq())
+    }
+
+    /// Watch every collection for changes
+    pub fn watch_all(&self) -> ChangeStream {
+        self.oplog.watch(None, self.oplog.latest_seq())
+    }
+
+    /// Run `callback` after each write of `kind` to `collection` (`*` for
+    /// every collection); see [`hooks`]
+    ///
+    /// # Example
+    /// ```ignore
+    /// db.register_hook("products", HookKind::AfterUpdate, move |_, event| {
+    ///     cache.invalidate(&event.doc_id);
+    /// });
+    /// ```
+    pub fn register_hook<F>(&self, collection: &str, kind: HookKind, callback: F) -> HookId
+    where
+        F: Fn(&Database, &ChangeEvent) + Send + Sync + 'static,
+    {
+        self.hooks.register(collection, kind, Arc::new(callback) as Arc<HookFn>)
+    }
+
+    /// Unregister a hook; returns whether it was registered
+    pub fn remove_hook(&self, id: HookId) -> bool {
+        self.hooks.remove(id)
+    }
+
+    /// Keep `vector_collection` in step with the `field` text of the
+    /// documents in `collection`
+    ///
+    /// Each document's text is embedded with the vector collection's provider
+    /// and stored under the document's ID: inserts and updates upsert it,
+    /// deletes remove it. Existing documents are embedded now. Remove the
+    /// returned hooks to stop.
+    ///
+    /// # Example
+    /// ```ignore
+    /// db.create_vector_collection("article_embeddings", VectorConfig::new(384))?;
+    /// db.auto_embed("articles", "body", "article_embeddings")?;
+    ///
+    /// let id = db.insert("articles", json!({"title": "HNSW", "body": "Graphs of graphs"}))?;
+    /// let hits = db.vector_search_text("article_embeddings", "layered graph search", 5)?;
+    /// assert_eq!(hits[0].document.external_id.as_deref(), Some(id.as_str()));
+    /// ```
+    pub fn auto_embed(&self, collection: &str, field: &str, vector_collection: &str) -> Result<Vec<HookId>> {
+        self.check_writable()?;
+        if !self.vector_collection(vector_collection)?.has_embedding_provider() {
+            return Err(error::KeraDBError::EmbeddingError(format!(
+                "Vector collection '{}' has no embedding provider",
+                vector_collection
+            )));
+        }
+
+        let sync = Arc::new(hooks::AutoEmbed::new(field, vector_collection));
+        let ids = HookKind::ALL
+            .into_iter()
+            .map(|kind| {
+                let sync = sync.clone();
+                self.register_hook(collection, kind, move |db, event| {
+                    if let Err(e) = sync.apply(db, event) {
+                        tracing::warn!("could not embed {} {} into {}: {}", event.collection, event.doc_id, sync.vector_collection(), e);
+                    }
+                })
+            })
+            .collect();
+
+        // After registering, so documents written meanwhile are not missed
+        for doc in self.find_all(collection, None, None)? {
+            sync.upsert(self, &doc.id, &doc.data)?;
+        }
+        self.embedded_collections.write().insert(collection.to_string(), vector_collection.to_string());
+        Ok(ids)
+    }
+
+    /// Find the `k` documents of `collection` most similar to one of them,
+    /// by the embeddings [`auto_embed`](Self::auto_embed) keeps of them
+    ///
+    /// The document itself is left out, as are documents without text.
+    ///
+    /// # Example
+    /// ```ignore
+    /// db.auto_embed("articles", "body", "article_embeddings")?;
+    /// for related in db.find_similar_documents("articles", &article_id, 5)? {
+    ///     println!("{} ({})", related.document.id, related.score);
+    /// }
+    /// ```
+    pub fn find_similar_documents(&self, collection: &str, doc_id: &str, k: usize) -> Result<Vec<vector::SimilarDocument>> {
+        let vector_collection = self.embedded_collections.read().get(collection).cloned().ok_or_else(|| {
+            error::KeraDBError::InvalidQuery(format!(
+                "Collection '{}' is not embedded; call auto_embed first",
+                collection
+            ))
+        })?;
+        self.find_by_id(collection, doc_id)?;
+        let embedding = self
+            .get_vector_by_external_id(&vector_collection, doc_id)?
+            .and_then(|vector| vector.embedding)
+            .ok_or_else(|| {
+                error::KeraDBError::NotFound(format!("Document {} has no embedding in '{}'", doc_id, vector_collection))
+            })?;
+
+        let mut similar = Vec::with_capacity(k);
+        for result in self.vector_search(&vector_collection, &embedding, k + 1)? {
+            let Some(id) = result.document.external_id.filter(|id| id != doc_id) else { continue };
+            match self.find_by_id(collection, &id) {
+                Ok(document) => similar.push(vector::SimilarDocument { document, score: result.score }),
+                // Deleted since it was embedded
+                Err(error::KeraDBError::DocumentNotFound(_)) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        similar.truncate(k);
+        Ok(similar)
+    }
+
+    /// Index the `[lon, lat]` or GeoJSON point locations in `field` of
+    /// `collection`, for [`find_near`](Self::find_near) and
+    /// [`find_within`](Self::find_within); see [`geo`]
+    ///
+    /// A collection has at most one geo index. It is kept in memory, so
+    /// create it again after opening the database.
+    ///
+    /// # Example
+    /// ```ignore
+    /// db.create_geo_index("places", "location")?;
+    /// ```
+    pub fn create_geo_index(&self, collection: &str, field: &str) -> Result<()> {
+        let index = Arc::new(geo::GeoIndex::new(field));
+        {
+            let mut indexes = self.geo_indexes.write();
+            if let Some(existing) = indexes.get(collection) {
+                return Err(error::KeraDBError::IndexError(format!(
+                    "Collection '{}' already has a geo index on '{}'",
+                    collection,
+                    existing.field()
+                )));
+            }
+            indexes.insert(collection.to_string(), index.clone());
+        }
+
+        let hooks = HookKind::ALL
+            .into_iter()
+            .map(|kind| {
+                let index = index.clone();
+                self.register_hook(collection, kind, move |_, event| match event.operation {
+                    OperationType::Delete => index.remove(&event.doc_id),
+                    OperationType::Insert | OperationType::Update => index.update(&event.doc_id, &event.document),
+                })
+            })
+            .collect();
+        *index.hooks.lock() = hooks;
+
+        // After registering, so documents written meanwhile are not missed
+        for doc in self.find_all(collection, None, None)? {
+            index.update(&doc.id, &doc.data);
+        }
+        Ok(())
+    }
+
+    /// Remove a collection's geo index; returns whether it had one
+    pub fn drop_geo_index(&self, collection: &str) -> bool {
+        let Some(index) = self.geo_indexes.write().remove(collection) else {
+            return false;
+        };
+        for id in index.hooks.lock().drain(..) {
+            self.remove_hook(id);
+        }
+        true
+    }
+
+    fn geo_index(&self, collection: &str) -> Result<Arc<geo::GeoIndex>> {
+        self.geo_indexes.read().get(collection).cloned().ok_or_else(|| {
+            error::KeraDBError::IndexError(format!(
+                "Collection '{}' has no geo index; create one with create_geo_index",
+                collection
+            ))
+        })
+    }
+
+    /// Documents within `radius` meters of `point`, nearest first
+    ///
+    /// Needs a geo index on the collection.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let nearby = db.find_near("places", GeoPoint::new(2.35, 48.85), 500.0)?;
+    /// ```
+    pub fn find_near(&self, collection: &str, point: geo::GeoPoint, radius: f64) -> Result<Vec<geo::GeoResult>> {
+        self.metrics.finds.time(|| {
+            let index = self.geo_index(collection)?;
+            let mut hits: Vec<(DocumentId, f64)> = index
+                .within(&geo::BoundingBox::around(point, radius))
+                .into_iter()
+                .map(|(id, location)| (id, point.distance(&location)))
+                .filter(|&(_, distance)| distance <= radius)
+                .collect();
+            hits.sort_by(|a, b| a.1.total_cmp(&b.1));
+            self.load_geo_hits(collection, hits.into_iter())
+                .map(|docs| docs.into_iter().map(|(document, distance)| geo::GeoResult { document, distance }).collect())
+        })
+    }
+
+    /// Documents located inside `bbox`
+    ///
+    /// Needs a geo index on the collection.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let bbox = BoundingBox::new(GeoPoint::new(2.2, 48.8), GeoPoint::new(2.5, 48.9));
+    /// let in_view = db.find_within("places", &bbox)?;
+    /// ```
+    pub fn find_within(&self, collection: &str, bbox: &geo::BoundingBox) -> Result<Vec<types::Document>> {
+        self.metrics.finds.time(|| {
+            let index = self.geo_index(collection)?;
+            let hits = index.within(bbox).into_iter().map(|(id, _)| (id, ()));
+            Ok(self.load_geo_hits(collection, hits)?.into_iter().map(|(doc, _)| doc).collect())
+        })
+    }
+
+    /// Read the documents for geo index hits, skipping any deleted meanwhile
+    fn load_geo_hits<T>(
+        &self,
+        collection: &str,
+        hits: impl Iterator<Item = (DocumentId, T)>,
+    ) -> Result<Vec<(types::Document, T)>> {
+        let mut docs = Vec::new();
+        for (id, extra) in hits {
+            match self.executor.find_by_id(collection, &id) {
+                Ok(doc) => docs.push((doc, extra)),
+                Err(error::KeraDBError::DocumentNotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(docs)
+    }
+
+    /// Record a `label` edge from one document to another; see [`graph`]
+    ///
+    /// Both documents must exist. There is at most one edge with a given
+    /// label between two documents, so linking again does nothing.
+    ///
+    /// # Example
+    /// ```ignore
+    /// db.link("users", &alice, "follows", "users", &bob)?;
+    /// ```
+    pub fn link(&self, from_collection: &str, from_id: &str, label: &str, to_collection: &str, to_id: &str) -> Result<()> {
+        self.link_with(from_collection, from_id, label, to_collection, to_id, Value::Null)
+    }
+
+    /// Like [`link`](Self::link), storing `properties` on the edge and
+    /// replacing those of an existing edge
+    pub fn link_with(
+        &self,
+        from_collection: &str,
+        from_id: &str,
+        label: &str,
+        to_collection: &str,
+        to_id: &str,
+        properties: Value,
+    ) -> Result<()> {
+        let edge = graph::Edge {
+            from: graph::NodeRef::new(from_collection, from_id),
+            label: label.to_string(),
+            to: graph::NodeRef::new(to_collection, to_id),
+            properties,
+        };
+        graph::check_node(self, &edge.from)?;
+        graph::check_node(self, &edge.to)?;
+
+        let mut document = serde_json::to_value(&edge)?;
+        document["_id"] = Value::String(graph::Edge::key(&edge.from, label, &edge.to));
+        oplog::upsert_document(self, graph::EDGES, document)
+    }
+
+    /// Remove an edge; returns whether it existed
+    pub fn unlink(&self, from_collection: &str, from_id: &str, label: &str, to_collection: &str, to_id: &str) -> Result<bool> {
+        let from = graph::NodeRef::new(from_collection, from_id);
+        let to = graph::NodeRef::new(to_collection, to_id);
+        match self.delete(graph::EDGES, &graph::Edge::key(&from, label, &to)) {
+            Ok(_) => Ok(true),
+            Err(error::KeraDBError::DocumentNotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Remove every edge from or to a document; returns how many there were
+    pub fn unlink_all(&self, collection: &str, id: &str) -> Result<usize> {
+        let node = graph::NodeRef::new(collection, id);
+        let edges = self.adjacency()?.edges(&node, None, graph::Direction::Both);
+        for (from, label, to) in &edges {
+            self.unlink(&from.collection, &from.id, label, &to.collection, &to.id)?;
+        }
+        Ok(edges.len())
+    }
+
+    /// The edges from and/or to a document, with their properties
+    pub fn edges(&self, collection: &str, id: &str, direction: graph::Direction) -> Result<Vec<graph::Edge>> {
+        let node = graph::NodeRef::new(collection, id);
+        let mut edges = Vec::new();
+        for (from, label, to) in self.adjacency()?.edges(&node, None, direction) {
+            match self.find_by_id(graph::EDGES, &graph::Edge::key(&from, &label, &to)) {
+                Ok(doc) => edges.extend(graph::Edge::from_document(&doc.data)),
+                Err(error::KeraDBError::DocumentNotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(edges)
+    }
+
+    /// The documents one edge away, optionally only along `label` edges
+    ///
+    /// # Example
+    /// ```ignore
+    /// let followers = db.neighbors("users", &bob, Some("follows"), Direction::Incoming)?;
+    /// ```
+    pub fn neighbors(
+        &self,
+        collection: &str,
+        id: &str,
+        label: Option<&str>,
+        direction: graph::Direction,
+    ) -> Result<Vec<graph::NodeRef>> {
+        Ok(self.adjacency()?.neighbors(&graph::NodeRef::new(collection, id), label, direction))
+    }
+
+    /// The documents reachable from a document, breadth first, each with its
+    /// distance in edges
+    ///
+    /// # Example
+    /// ```ignore
+    /// let friends_of_friends = db.traverse("users", &alice, &Traversal::new(2).with_label("follows"))?;
+    /// ```
+    pub fn traverse(&self, collection: &str, id: &str, options: &graph::Traversal) -> Result<Vec<graph::TraversalHit>> {
+        Ok(self.adjacency()?.traverse(&graph::NodeRef::new(collection, id), options))
+    }
+
+    fn adjacency(&self) -> Result<Arc<graph::Adjacency>> {
+        if let Some(adjacency) = self.adjacency.read().as_ref() {
+            return Ok(adjacency.clone());
+        }
+        let mut slot = self.adjacency.write();
+        if let Some(adjacency) = slot.as_ref() {
+            return Ok(adjacency.clone());
+        }
+
+        let adjacency = Arc::new(graph::Adjacency::default());
+        for kind in HookKind::ALL {
+            let adjacency = adjacency.clone();
+            self.register_hook(graph::EDGES, kind, move |_, event| {
+                if let Some(edge) = graph::Edge::from_document(&event.document) {
+                    match event.operation {
+                        OperationType::Delete => adjacency.remove(&edge),
+                        OperationType::Insert | OperationType::Update => adjacency.add(&edge),
+                    }
+                }
+            });
+        }
+        // After registering, so edges written meanwhile are not missed
+        for doc in self.find_all(graph::EDGES, None, None)? {
+            if let Some(edge) = graph::Edge::from_document(&doc.data) {
+                adjacency.add(&edge);
+            }
+        }
+        *slot = Some(adjacency.clone());
+        Ok(adjacency)
+    }
+
+    /// Attach `bytes` to a document as the blob `name`, replacing any blob of
+    /// that name; see [`execution::blob`]
+    ///
+    /// Blobs are not part of change streams, replication, dumps or backups,
+    /// and are deleted with their document.
+    ///
+    /// # Example
+    /// ```ignore
+    /// db.put_blob("users", &alice, "avatar.png", &std::fs::read("avatar.png")?)?;
+    /// let avatar = db.get_blob("users", &alice, "avatar.png")?;
+    /// ```
+    pub fn put_blob(&self, collection: &str, doc_id: &str, name: &str, bytes: &[u8]) -> Result<u64> {
+        let mut writer = self.blob_writer(collection, doc_id, name)?;
+        writer.write_all(bytes)?;
+        let size = writer.finish()?;
+        self.sync_if_durable()?;
+        Ok(size)
+    }
+
+    /// Read a whole blob into memory
+    pub fn get_blob(&self, collection: &str, doc_id: &str, name: &str) -> Result<Vec<u8>> {
+        let mut reader = self.blob_reader(collection, doc_id, name)?;
+        let mut bytes = Vec::with_capacity(reader.size() as usize);
+        std::io::Read::read_to_end(&mut reader, &mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Stream a blob in; it replaces any blob of the same name once
+    /// [`finish`](execution::BlobWriter::finish) is called
+    ///
+    /// `finish` does not sync, whatever the durability; call
+    /// [`sync`](Self::sync) afterwards if the blob must survive a crash.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut writer = db.blob_writer("files", &id, "report.pdf")?;
+    /// std::io::copy(&mut std::fs::File::open("report.pdf")?, &mut writer)?;
+    /// writer.finish()?;
+    /// ```
+    pub fn blob_writer(&self, collection: &str, doc_id: &str, name: &str) -> Result<execution::BlobWriter<'_>> {
+        self.check_writable()?;
+        self.executor.blob_writer(collection, doc_id, name)
+    }
+
+    /// Stream a blob out
+    pub fn blob_reader(&self, collection: &str, doc_id: &str, name: &str) -> Result<execution::BlobReader<'_>> {
+        self.executor.blob_reader(collection, doc_id, name)
+    }
+
+    /// Remove a blob; returns whether it existed
+    pub fn delete_blob(&self, collection: &str, doc_id: &str, name: &str) -> Result<bool> {
+        self.check_writable()?;
+        let deleted = self.executor.delete_blob(collection, doc_id, name)?;
+        self.sync_if_durable()?;
+        Ok(deleted)
+    }
+
+    /// The blobs attached to a document, by name
+    pub fn list_blobs(&self, collection: &str, doc_id: &str) -> Vec<execution::BlobInfo> {
+        self.executor.list_blobs(collection, doc_id)
+    }
+
+    /// The operation log backing change streams
+    pub fn oplog(&self) -> &Arc<Oplog> {
+        &self.oplog
+    }
+
+    /// Back up the database to `store`
+    ///
+    /// The first backup to a store is a full dump; later ones only upload the
+    /// changes made since, while the oplog still holds them. See [`backup`].
+    ///
+    /// # Example
+    /// ```ignore
+    /// let store = backup::DirectoryStore::new("/mnt/backups/app");
+    /// let info = db.backup_to(&store)?;
+    /// ```
+    pub fn backup_to(&self, store: &dyn backup::BackupStore) -> Result<backup::BackupInfo> {
+        backup::backup(self, store)
+    }
+
+    /// Back up the database to an S3-compatible bucket under `prefix`
+    ///
+    /// Credentials, region and endpoint are read from the standard `AWS_*`
+    /// environment variables; see [`backup::S3Store::from_env`].
+    #[cfg(feature = "s3")]
+    pub fn backup_to_s3(&self, bucket: &str, prefix: &str) -> Result<backup::BackupInfo> {
+        self.backup_to(&backup::S3Store::from_env(bucket, prefix)?)
+    }
+
+    /// Restore the latest backup in `store` into a new database at `path`
+    pub fn restore_from<P: AsRef<Path>>(store: &dyn backup::BackupStore, path: P) -> Result<Self> {
+        backup::restore(store, path.as_ref())
+    }
+
+    /// Restore the latest backup in an S3-compatible bucket into a new database
+    #[cfg(feature = "s3")]
+    pub fn restore_from_s3<P: AsRef<Path>>(bucket: &str, prefix: &str, path: P) -> Result<Self> {
+        Self::restore_from(&backup::S3Store::from_env(bucket, prefix)?, path)
+    }
+
+    /// Write a portable NDJSON dump of the database to `writer`
+    ///
+    /// Only the named document and vector collections are dumped, or all of
+    /// them when `collections` is empty. See [`dump`].
+    ///
+    /// # Example
+    /// ```ignore
+    /// let file = std::io::BufWriter::new(std::fs::File::create("app.dump.ndjson")?);
+    /// db.dump(file, &[])?;
+    /// ```
+    pub fn dump<W: Write>(&self, writer: W, collections: &[String]) -> Result<dump::DumpSummary> {
+        dump::dump(self, writer, collections)
+    }
+
+    /// Load a dump written by [`Database::dump`]
+    ///
+    /// Documents replace any with the same ID; vectors are added, so restore
+    /// into an empty database.
+    pub fn restore_dump<R: std::io::BufRead>(&self, reader: R) -> Result<dump::DumpSummary> {
+        self.check_writable()?;
+        let summary = dump::restore(self, reader)?;
+        self.sync()?;
+        Ok(summary)
+    }
+
+    /// Import documents from a JSON, NDJSON or CSV file into `collection`
+    ///
+    /// The format comes from `options` or the file extension. See [`import`].
+    ///
+    /// # Example
+    /// ```ignore
+    /// let options = ImportOptions::default().with_id_field("sku");
+    /// let report = db.import_file("products", "products.csv", &options)?;
+    /// ```
+    pub fn import_file<P: AsRef<Path>>(
+        &self,
+        collection: &str,
+        path: P,
+        options: &import::ImportOptions,
+    ) -> Result<import::ImportReport> {
+        let path = path.as_ref();
+        let format = match options.format {
+            Some(format) => format,
+            None => import::ImportFormat::from_path(path)?,
+        };
+        let reader = std::io::BufReader::new(fs::File::open(path)?);
+        import::import(self, collection, reader, format, options, &mut |_| {})
+    }
+
+    /// Import newline-delimited JSON documents into `collection`
+    pub fn import_ndjson<R: std::io::BufRead>(
+        &self,
+        collection: &str,
+        reader: R,
+        options: &import::ImportOptions,
+    ) -> Result<import::ImportReport> {
+        import::import(self, collection, reader, import::ImportFormat::Ndjson, options, &mut |_| {})
+    }
+
+    /// Import documents, calling `progress` after every batch
+    ///
+    /// `options.format` must be set.
+    pub fn import_reader<R: std::io::BufRead>(
+        &self,
+        collection: &str,
+        reader: R,
+        options: &import::ImportOptions,
+        mut progress: impl FnMut(&import::ImportProgress),
+    ) -> Result<import::ImportReport> {
+        let format = options
+            .format
+            .ok_or_else(|| error::KeraDBError::InvalidQuery("No import format given".to_string()))?;
+        import::import(self, collection, reader, format, options, &mut progress)
+    }
+
+    /// Import `mongoexport` or `mongodump` output
+    ///
+    /// `source` is an Extended JSON export, a `.bson` file or a dump
+    /// directory. A single file goes into `collection`, or the collection
+    /// named after it; a directory fills one collection per `.bson` file.
+    /// `_id`s are preserved. See [`compat::mongo_import`].
+    ///
+    /// # Example
+    /// ```ignore
+    /// let imports = db.import_mongo("dump/shop", None, &ImportOptions::default())?;
+    /// ```
+    #[cfg(feature = "mongo")]
+    pub fn import_mongo<P: AsRef<Path>>(
+        &self,
+        source: P,
+        collection: Option<&str>,
+        options: &import::ImportOptions,
+    ) -> Result<Vec<compat::mongo_import::MongoImport>> {
+        compat::mongo_import::import(self, source.as_ref(), collection, options)
+    }
+
+    /// Sync all changes to disk (including vector data)
+    /// 
+    /// Vector mutations only mark the collections dirty; they are written to the
+    /// sidecar file here, or when the database is dropped. Does nothing for an
+    /// [`in_memory`](Self::in_memory) database.
+    pub fn sync(&self) -> Result<()> {
+        if self.is_in_memory() {
+            return Ok(());
+        }
+        self.metrics.syncs.time(|| {
+            // Sync document data
+            self.executor.sync()?;
+            self.metrics.record_fsync();
+        
+            // Sync vector collections
+            if self.vector_dirty.swap(false, Ordering::AcqRel) {
+                if let Err(e) = self.save_vector_collections() {
+                    self.vector_dirty.store(true, Ordering::Release);
+                    return Err(e);
+                }
+            }
+        
+            Ok(())
+        })
+    }
+
+    /// Flush a document write under [`Durability::Full`](types::Durability::Full),
+    /// or wake the background flusher once too many pages are unsynced
+    pub(crate) fn sync_if_durable(&self) -> Result<()> {
+        if self.config.durability == types::Durability::Full && !self.is_in_memory() {
+            self.executor.sync()?;
+            self.metrics.record_fsync();
+        } else if self.executor.unsynced_pages() >= self.config.max_dirty_pages {
+            if let Some(signal) = self.flush_signal.read().as_ref() {
+                signal.wake();
+            }
+        }
+        Ok(())
+    }
+
+    /// What the background flusher does: sync the data file if anything
+    /// was written since the last sync, and save changed vector collections
+    pub(crate) fn flush(&self) -> Result<()> {
+        if self.is_in_memory() || self.read_only {
+            return Ok(());
+        }
+        if self.executor.unsynced_pages() > 0 {
+            self.executor.sync()?;
+            self.metrics.record_fsync();
+        }
+        if self.vector_dirty.swap(false, Ordering::AcqRel) {
+            if let Err(e) = self.save_vector_collections() {
+                self.vector_dirty.store(true, Ordering::Release);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn set_flush_signal(&self, signal: Option<Arc<flusher::Signal>>) {
+        *self.flush_signal.write() = signal;
+    }
+
+    /// Forget a stopped flusher, unless another has replaced it
+    pub(crate) fn clear_flush_signal(&self, signal: &Arc<flusher::Signal>) {
+        let mut current = self.flush_signal.write();
+        if current.as_ref().is_some_and(|current| Arc::ptr_eq(current, signal)) {
+            *current = None;
+        }
+    }
+
+    /// Pages written to the data file since the last sync, which a crash
+    /// could lose
+    pub fn unsynced_pages(&self) -> u64 {
+        self.executor.unsynced_pages()
+    }
+
+    /// Whether vector collections have changes not yet written by `sync`
+    pub fn has_unsynced_vectors(&self) -> bool {
+        self.vector_dirty.load(Ordering::Acquire)
+    }
+
+    fn mark_vectors_dirty(&self) {
+        if !self.is_in_memory() {
+            self.vector_dirty.store(true, Ordering::Release);
+        }
+    }
+
+    /// Copy the database to a new file at `path`
+    ///
+    /// Documents are copied from a [`snapshot`](Self::snapshot), so writes made
+    /// meanwhile are left out rather than torn, and vector collections go to
+    /// the usual sidecar file. The result opens with [`open`](Self::open).
+    /// This is how an [`in_memory`](Self::in_memory) database is saved, but
+    /// it works for any database. Blobs are not copied. Fails if `path`
+    /// already exists.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let db = Database::in_memory()?;
+    /// db.insert("users", json!({"name": "Alice"}))?;
+    /// db.persist_to("users.ndb")?;
+    /// ```
+    pub fn persist_to<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let target = Database::create_with_config(path, self.config.clone())?;
+        let snapshot = self.snapshot();
+        for (collection, _) in self.list_collections() {
+            for doc in snapshot.find_all(&collection, None, None)? {
+                target.insert_unsynced(&collection, doc.to_value())?;
+            }
+        }
+        let collections: Vec<Arc<vector::search::VectorCollection>> =
+            self.vector_collections.read().values().cloned().collect();
+        if !collections.is_empty() {
+            VectorStore::File(Self::vector_data_path(path)).write_with(&|writer| vector::format::encode(&collections, writer))?;
+        }
+        target.sync()
+    }
+
+    /// Look up a vector collection, releasing the map lock before returning
+    fn vector_collection(&self, name: &str) -> Result<Arc<vector::search::VectorCollection>> {
+        self.vector_collections
+            .read()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| error::KeraDBError::CollectionNotFound(name.to_string()))
+    }
+
+    // ============================================================
+    // Vector Database API
+    // ============================================================
+
+    /// Create a vector collection for similarity search
+    /// 
+    /// # Example
+    /// ```ignore
+    /// use keradb::vector::VectorConfig;
+    /// 
+    /// let db = Database::create("mydata.ndb")?;
+    /// db.create_vector_collection("embeddings", VectorConfig::new(384))?;
+    /// ```
+    pub fn create_vector_collection(&self, name: &str, config: vector::VectorConfig) -> Result<()> {
+        self.check_writable()?;
+        let mut collections = self.vector_collections.write();
+        
+        if collections.contains_key(name) {
+            return Err(error::KeraDBError::CollectionExists(name.to_string()));
+        }
+        
+        let collection = if let Some(provider) = self.embedding_provider.read().as_ref() {
+            vector::search::VectorCollection::with_embedding_provider(
+                name.to_string(),
+                config,
+                provider.clone(),
+            )
+        } else {
+            vector::search::VectorCollection::new(name.to_string(), config)
+        };
+        
+        collections.insert(name.to_string(), Arc::new(collection));
+        drop(collections);
+        
+        self.mark_vectors_dirty();
+        
+        Ok(())
+    }
+
+    /// Insert a vector into a collection
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let vector = vec![0.1, 0.2, 0.3, ...]; // 384 dimensions
+    /// let id = db.insert_vector("embeddings", vector, Some(json!({"source": "doc1"})))?;
+    /// ```
+    pub fn insert_vector(
+        &self,
+        collection: &str,
+        vector: Embedding,
+        metadata: Option<Value>,
+    ) -> Result<VectorId> {
+        self.metrics.vector_inserts.time(|| {
+            self.check_writable()?;
+            let id = self.vector_collection(collection)?.insert(vector, metadata)?;
+            self.mark_vectors_dirty();
+        
+            Ok(id)
+        })
+    }
+
+    /// Insert a vector under a user-supplied ID, replacing any vector already
+    /// stored under that ID
+    /// 
+    /// Useful for keeping vectors keyed by the ID of the document they embed.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let doc_id = db.insert("articles", json!({"title": "Intro to HNSW"}))?;
+    /// db.insert_vector_with_id("embeddings", &doc_id, vector, None)?;
+    /// ```
+    pub fn insert_vector_with_id(
+        &self,
+        collection: &str,
+        external_id: &str,
+        vector: Embedding,
+        metadata: Option<Value>,
+    ) -> Result<VectorId> {
+        self.metrics.vector_inserts.time(|| {
+            self.check_writable()?;
+            let id = self.vector_collection(collection)?.insert_with_id(external_id, vector, metadata)?;
+            self.mark_vectors_dirty();
+        
+            Ok(id)
+        })
+    }
+
+    /// Insert text into a vector collection (requires embedding provider)
+    /// 
+    /// # Example
+    /// ```ignore
+    /// db.insert_text("documents", "Machine learning is fascinating", 
+    ///     Some(json!({"category": "tech"})))?;
+    /// ```
+    pub fn insert_text(
+        &self,
+        collection: &str,
+        text: &str,
+        metadata: Option<Value>,
+    ) -> Result<VectorId> {
+        self.metrics.vector_inserts.time(|| {
+            self.check_writable()?;
+            let id = self.vector_collection(collection)?.insert_text(text, metadata)?;
+            self.mark_vectors_dirty();
+        
+            Ok(id)
+        })
+    }
+
+    /// Insert several texts, with optional metadata each, embedding them in
+    /// one batch (requires embedding provider)
+    /// 
+    /// Returns the new IDs in the order of `texts`.
+    pub fn insert_texts(&self, collection: &str, texts: Vec<(String, Option<Value>)>) -> Result<Vec<VectorId>> {
+        self.metrics.vector_inserts.time(|| {
+            self.check_writable()?;
+            let ids = self.vector_collection(collection)?.insert_texts(texts)?;
+            self.mark_vectors_dirty();
+
+            Ok(ids)
+        })
+    }
+
+    /// Insert text under a user-supplied ID, replacing any vector already
+    /// stored under that ID (requires embedding provider)
+    pub fn insert_text_with_id(
+        &self,
+        collection: &str,
+        external_id: &str,
+        text: &str,
+        metadata: Option<Value>,
+    ) -> Result<VectorId> {
+        self.metrics.vector_inserts.time(|| {
+            self.check_writable()?;
+            let id = self.vector_collection(collection)?.insert_text_with_id(external_id, text, metadata)?;
+            self.mark_vectors_dirty();
+
+            Ok(id)
+        })
+    }
+
+    /// Insert a vector into a namespace of a collection
+    /// 
+    /// Namespaces partition one collection, e.g. by tenant:
+    /// [`vector_search_in_namespace`](Self::vector_search_in_namespace) only
+    /// returns vectors from the namespace it is given, while the other searches
+    /// cover the whole collection.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// db.insert_vector_in_namespace("embeddings", "tenant-42", vector, None)?;
+    /// let results = db.vector_search_in_namespace("embeddings", "tenant-42", &query, 10)?;
+    /// ```
+    pub fn insert_vector_in_namespace(
+        &self,
+        collection: &str,
+        namespace: &str,
+        vector: Embedding,
+        metadata: Option<Value>,
+    ) -> Result<VectorId> {
+        self.metrics.vector_inserts.time(|| {
+            self.check_writable()?;
+            let id = self.vector_collection(collection)?.insert_in_namespace(namespace, vector, metadata)?;
+            self.mark_vectors_dirty();
+
+            Ok(id)
+        })
+    }
+
+    /// Insert text into a namespace of a collection (requires embedding provider)
+    pub fn insert_text_in_namespace(
+        &self,
+        collection: &str,
+        namespace: &str,
+        text: &str,
+        metadata: Option<Value>,
+    ) -> Result<VectorId> {
+        self.metrics.vector_inserts.time(|| {
+            self.check_writable()?;
+            let id = self.vector_collection(collection)?.insert_text_in_namespace(namespace, text, metadata)?;
+            self.mark_vectors_dirty();
+
+            Ok(id)
+        })
+    }
+
+    /// Search for similar vectors
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let query = vec![0.1, 0.2, 0.3, ...];
+    /// let results = db.vector_search("embeddings", &query, 10)?;
+    /// for result in results {
+    ///     println!("ID: {}, Score: {}", result.document.id, result.score);
+    /// }
+    /// ```
+    pub fn vector_search(
+        &self,
+        collection: &str,
+        query: &Embedding,
+        k: usize,
+    ) -> Result<Vec<VectorSearchResult>> {
+        self.metrics.vector_searches.time(|| {
+            let coll = self.vector_collection(collection)?;
+            coll.search(query, k)
+        })
+    }
+
+    /// Search for similar vectors with maximal marginal relevance re-ranking
+    /// 
+    /// `lambda` trades relevance (1.0) against diversity (0.0); 0.5 is a common default.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let results = db.vector_search_mmr("embeddings", &query, 5, 0.5)?;
+    /// ```
+    pub fn vector_search_mmr(
+        &self,
+        collection: &str,
+        query: &Embedding,
+        k: usize,
+        lambda: f32,
+    ) -> Result<Vec<VectorSearchResult>> {
+        self.metrics.vector_searches.time(|| {
+            let coll = self.vector_collection(collection)?;
+            coll.search_mmr(query, k, lambda)
+        })
+    }
+
+    /// Exact (brute-force) search with perfect recall
+    /// 
+    /// Scans every vector instead of traversing the HNSW graph. With the `gpu`
+    /// feature, large collections are scored on the GPU when one is available.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let results = db.vector_search_exact("documents", &query_vector, 10)?;
+    /// ```
+    pub fn vector_search_exact(
+        &self,
+        collection: &str,
+        query: &Embedding,
+        k: usize,
+    ) -> Result<Vec<VectorSearchResult>> {
+        self.metrics.vector_searches.time(|| {
+            let coll = self.vector_collection(collection)?;
+            coll.search_exact(query, k)
+        })
+    }
+
+    /// Search for similar vectors by text query
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let results = db.vector_search_text("documents", "artificial intelligence", 10)?;
+    /// ```
+    pub fn vector_search_text(
+        &self,
+        collection: &str,
+        query: &str,
+        k: usize,
+    ) -> Result<Vec<VectorSearchResult>> {
+        self.metrics.vector_searches.time(|| {
+            let coll = self.vector_collection(collection)?;
+            coll.search_text(query, k)
+        })
+    }
+
+    /// Search for similar vectors within one namespace of a collection
+    pub fn vector_search_in_namespace(
+        &self,
+        collection: &str,
+        namespace: &str,
+        query: &Embedding,
+        k: usize,
+    ) -> Result<Vec<VectorSearchResult>> {
+        self.metrics.vector_searches.time(|| {
+            let coll = self.vector_collection(collection)?;
+            coll.search_in_namespace(namespace, query, k)
+        })
+    }
+
+    /// Search by text query within one namespace of a collection
+    pub fn vector_search_text_in_namespace(
+        &self,
+        collection: &str,
+        namespace: &str,
+        query: &str,
+        k: usize,
+    ) -> Result<Vec<VectorSearchResult>> {
+        self.metrics.vector_searches.time(|| {
+            let coll = self.vector_collection(collection)?;
+            coll.search_text_in_namespace(namespace, query, k)
+        })
+    }
+
+    /// List the namespaces of a vector collection with their vector counts
+    pub fn list_vector_namespaces(&self, collection: &str) -> Result<Vec<(String, usize)>> {
+        Ok(self.vector_collection(collection)?.namespaces())
+    }
+
+    /// Find clusters of near-identical vectors, within `threshold` of each
+    /// other in the collection's distance metric; each cluster is sorted by ID
+    /// 
+    /// # Example
+    /// ```ignore
+    /// for cluster in db.find_vector_duplicates("embeddings", 0.01)? {
+    ///     println!("{} copies of vector {}", cluster.len(), cluster[0]);
+    /// }
+    /// ```
+    pub fn find_vector_duplicates(&self, collection: &str, threshold: f32) -> Result<Vec<Vec<VectorId>>> {
+        self.vector_collection(collection)?.find_duplicates(threshold)
+    }
+
+    /// Delete near-identical vectors, keeping the oldest of each cluster
+    /// [`find_vector_duplicates`](Self::find_vector_duplicates) reports;
+    /// returns how many were deleted
+    pub fn dedup_vector_collection(&self, collection: &str, threshold: f32) -> Result<usize> {
+        self.check_writable()?;
+        let deleted = self.vector_collection(collection)?.dedup(threshold)?;
+        self.mark_vectors_dirty();
+        
+        Ok(deleted)
+    }
+
+    /// Group a collection's vectors into `k` clusters with k-means, storing
+    /// each vector's cluster number in its metadata under
+    /// [`CLUSTER_KEY`](vector::CLUSTER_KEY)
+    ///
+    /// # Example
+    /// ```ignore
+    /// let clustering = db.cluster_vector_collection("embeddings", 8)?;
+    /// let filter = MetadataFilter::new().eq("_cluster", json!(3));
+    /// let in_cluster = db.vector_search_filtered("embeddings", &clustering.centroids[3], 10, &filter)?;
+    /// ```
+    pub fn cluster_vector_collection(&self, collection: &str, k: usize) -> Result<vector::Clustering> {
+        self.check_writable()?;
+        let clustering = self.vector_collection(collection)?.cluster(k)?;
+        self.mark_vectors_dirty();
+
+        Ok(clustering)
+    }
+
+    /// Delete every vector in a namespace, returning how many were deleted
+    pub fn delete_vector_namespace(&self, collection: &str, namespace: &str) -> Result<usize> {
+        self.check_writable()?;
+        let deleted = self.vector_collection(collection)?.delete_namespace(namespace)?;
+        self.mark_vectors_dirty();
+        
+        Ok(deleted)
+    }
+
+    /// Search with metadata filtering
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let filter = MetadataFilter::new().eq("category", json!("tech"));
+    /// let results = db.vector_search_filtered("documents", &query, 10, &filter)?;
+    /// ```
+    pub fn vector_search_filtered(
+        &self,
+        collection: &str,
+        query: &Embedding,
+        k: usize,
+        filter: &MetadataFilter,
+    ) -> Result<Vec<VectorSearchResult>> {
+        self.metrics.vector_searches.time(|| {
+            let coll = self.vector_collection(collection)?;
+            coll.search_filtered(query, k, filter)
+        })
+    }
+
+    /// Get a vector document by ID
+    pub fn get_vector(&self, collection: &str, id: VectorId) -> Result<Option<vector::VectorDocument>> {
+        let coll = self.vector_collection(collection)?;
+        Ok(coll.get(id))
+    }
+
+    /// Get a vector document by its user-supplied ID
+    pub fn get_vector_by_external_id(
+        &self,
+        collection: &str,
+        external_id: &str,
+    ) -> Result<Option<vector::VectorDocument>> {
+        let coll = self.vector_collection(collection)?;
+        Ok(coll.resolve_id(external_id).and_then(|id| coll.get(id)))
+    }
+
+    /// Delete a vector by its user-supplied ID
+    pub fn delete_vector_by_external_id(&self, collection: &str, external_id: &str) -> Result<bool> {
+        self.check_writable()?;
+        let coll = self.vector_collection(collection)?;
+        let Some(id) = coll.resolve_id(external_id) else {
+            return Ok(false);
+        };
+        let result = coll.delete(id)?;
+        self.mark_vectors_dirty();
+        
+        Ok(result)
+    }
+
+    /// Replace a vector's metadata without re-inserting its embedding; `Null`
+    /// clears it. Returns `false` if the collection has no vector with this ID
+    /// 
+    /// # Example
+    /// ```ignore
+    /// db.update_vector_metadata("embeddings", id, json!({"label": "cat"}))?;
+    /// ```
+    pub fn update_vector_metadata(&self, collection: &str, id: VectorId, metadata: Value) -> Result<bool> {
+        self.check_writable()?;
+        let updated = self.vector_collection(collection)?.update_metadata(id, metadata);
+        if updated {
+            self.mark_vectors_dirty();
+        }
+        
+        Ok(updated)
+    }
+
+    /// Merge `patch` into a vector's metadata as a JSON merge patch: keys are
+    /// merged into nested objects and `null` removes a key
+    /// 
+    /// # Example
+    /// ```ignore
+    /// // Adds a tag and drops "draft", keeping the rest of the metadata
+    /// db.merge_vector_metadata("embeddings", id, json!({"tags": {"reviewed": true}, "draft": null}))?;
+    /// ```
+    pub fn merge_vector_metadata(&self, collection: &str, id: VectorId, patch: Value) -> Result<bool> {
+        self.check_writable()?;
+        let updated = self.vector_collection(collection)?.merge_metadata(id, patch);
+        if updated {
+            self.mark_vectors_dirty();
+        }
+        
+        Ok(updated)
+    }
+
+    /// Delete a vector by ID
+    pub fn delete_vector(&self, collection: &str, id: VectorId) -> Result<bool> {
+        self.check_writable()?;
+        let result = self.vector_collection(collection)?.delete(id)?;
+        self.mark_vectors_dirty();
+        
+        Ok(result)
+    }
+
+    /// Optimize a vector collection
+    /// 
+    /// Repairs HNSW connectivity after deletions, resets the entry point and
+    /// drops orphaned metadata. Returns index statistics from before and after.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let report = db.optimize_vector_collection("documents")?;
+    /// println!("relinked {} nodes", report.relinked_nodes);
+    /// ```
+    pub fn optimize_vector_collection(&self, collection: &str) -> Result<vector::OptimizeReport> {
+        self.check_writable()?;
+        let report = self.vector_collection(collection)?.optimize()?;
+        self.mark_vectors_dirty();
+        
+        Ok(report)
+    }
+
+    /// Optimize a vector collection automatically after every `mutations`
+    /// inserts/deletes; `None` disables auto-optimization
+    pub fn set_vector_auto_optimize(&self, collection: &str, mutations: Option<usize>) -> Result<()> {
+        let coll = self.vector_collection(collection)?;
+        coll.set_auto_optimize(mutations);
+        Ok(())
+    }
+
+    /// List all vector collections
+    pub fn list_vector_collections(&self) -> Vec<(String, usize)> {
+        self.vector_collections
+            .read()
+            .iter()
+            .map(|(name, coll)| (name.clone(), coll.len()))
+            .collect()
+    }
+
+    /// Drop a vector collection
+    pub fn drop_vector_collection(&self, name: &str) -> Result<bool> {
+        self.check_writable()?;
+        let removed = self.vector_collections.write().remove(name).is_some();
+        
+        if removed {
+            self.mark_vectors_dirty();
+        }
+        
+        Ok(removed)
+    }
+
+    /// Export a vector collection to a file (`.jsonl`/`.ndjson` or `.parquet`)
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let count = db.export_vector_collection("embeddings", "embeddings.jsonl")?;
+    /// ```
+    pub fn export_vector_collection<P: AsRef<Path>>(&self, collection: &str, path: P) -> Result<usize> {
+        let path = path.as_ref();
+        let format = vector::VectorFileFormat::from_path(path)?;
+        let records = self.vector_collection(collection)?.to_records();
+        format.write(path, &records)?;
+        Ok(records.len())
+    }
+
+    /// Project a vector collection to two dimensions for plotting
+    pub fn project_vector_collection(&self, collection: &str, method: vector::ProjectionMethod) -> Result<vector::Projection> {
+        self.vector_collection(collection)?.project_2d(method)
+    }
+
+    /// Write a 2D projection of a vector collection to a `.json` or `.csv`
+    /// file, returning the number of points
+    ///
+    /// # Example
+    /// ```ignore
+    /// let count = db.export_vector_projection("embeddings", ProjectionMethod::Pca, "embeddings.csv")?;
+    /// ```
+    pub fn export_vector_projection<P: AsRef<Path>>(&self, collection: &str, method: vector::ProjectionMethod, path: P) -> Result<usize> {
+        let projection = self.project_vector_collection(collection, method)?;
+        projection.write(path.as_ref())?;
+        Ok(projection.points.len())
+    }
+
+    /// Import vectors from a file (`.jsonl`/`.ndjson` or `.parquet`) into a collection
+    ///
+    /// If the collection does not exist it is created, using `config` when given or
+    /// default settings with dimensions taken from the first record.
+    /// 
+    /// # Example
+    /// ```ignore
+    /// let count = db.import_vector_collection("embeddings", "faiss_dump.parquet", None)?;
+    /// ```
+    pub fn import_vector_collection<P: AsRef<Path>>(
+        &self,
+        collection: &str,
+        path: P,
+        config: Option<vector::VectorConfig>,
+    ) -> Result<usize> {
+        let path = path.as_ref();
+        let records = vector::VectorFileFormat::from_path(path)?.read(path)?;
+        self.import_vector_records(collection, records, config)
+    }
+
+    /// Insert exported vector records into a collection, creating it if needed
+    pub fn import_vector_records(
+        &self,
+        collection: &str,
+        records: Vec<vector::VectorRecord>,
+        config: Option<vector::VectorConfig>,
+    ) -> Result<usize> {
+        self.check_writable()?;
+        if !self.vector_collections.read().contains_key(collection) {
+            let config = match config {
+                Some(config) => config,
+                None => {
+                    let dimensions = records.first().map(|r| r.vector.len()).ok_or_else(|| {
+                        error::KeraDBError::InvalidFormat(
+                            "Cannot infer dimensions from an empty vector file".to_string(),
+                        )
+                    })?;
+                    self.config.vector_config(dimensions)
+                }
+            };
+            self.create_vector_collection(collection, config)?;
+        }
+
+        let count = self.vector_collection(collection)?.import_records(records)?.len();
+        self.mark_vectors_dirty();
+
+        Ok(count)
+    }
+
+    /// Set the embedding provider for text-to-vector conversion, for new vector
+    /// collections and those already open
+    /// 
+    /// Collections record the model they were created with, and inserting or
+    /// searching text in one fails with [`KeraDBError::EmbeddingError`] while
+    /// the provider's model differs, rather than returning unrelated results.
+    /// 
+    /// The config is stored in the database and the provider restored when
+    /// it is opened again, with API tokens read from the environment
+    /// (`HF_TOKEN`, `OPENAI_API_KEY`) rather than stored. A
+    /// [`Custom`](EmbeddingConfig::Custom) function cannot be stored and must
+    /// be set again after each open.
+    pub fn set_embedding_provider(&self, config: EmbeddingConfig) -> Result<()> {
+        let stored = config.to_stored();
+        self.attach_embedding_provider(create_provider(config)?);
+        self.store_embedding_provider(stored)
+    }
+
+    /// Remember the provider's config for the next open, or forget the
+    /// previous one for a provider that cannot be stored
+    pub(crate) fn store_embedding_provider(&self, stored: Option<Value>) -> Result<()> {
+        if self.read_only || self.executor.setting(EMBEDDING_PROVIDER_SETTING) == stored {
+            return Ok(());
+        }
+        self.executor.set_setting(EMBEDDING_PROVIDER_SETTING, stored)
+    }
+
+    /// Embed text with `provider` in every vector collection
+    /// 
+    /// Calls to it follow the config's [`ProviderPolicy`](vector::ProviderPolicy).
+    pub(crate) fn attach_embedding_provider(&self, provider: Arc<dyn EmbeddingProvider>) {
+        let policy = self.config.embedding.clone();
+        let provider: Arc<dyn EmbeddingProvider> =
+            Arc::new(GuardedProvider::new(provider, policy, self.metrics.embeddings.clone()));
+        // Holding the collections keeps new ones from missing the provider
+        let collections = self.vector_collections.read();
+        for collection in collections.values() {
+            collection.set_embedding_provider(provider.clone());
+        }
+        *self.embedding_provider.write() = Some(provider);
+    }
+
+    /// Get vector collection statistics
+    pub fn vector_stats(&self, collection: &str) -> Result<vector::VectorCollectionStats> {
+        let coll = self.vector_collection(collection)?;
+        
+        Ok(coll.stats())
+    }
+}
+
+impl Drop for Database {
+    fn drop(&mut self) {
+        // Let change streams finish instead of waiting forever
+        self.oplog.close();
+
+        // Vector mutations are only persisted on sync; flush anything outstanding
+        if self.vector_dirty.load(Ordering::Acquire) {
+            if let Err(e) = self.save_vector_collections() {
+                tracing::error!("Failed to save vector collections: {}", e);
+            }
+        }
+    }
+}
+
+// Re-export commonly used types
 pub use error::KeraDBError;
-pub use types::Document;
+pub use types::{Config, Document, Durability, OpenReport, OpenWarning, ScanOptions};
+pub use stats::DatabaseStats;
+pub use storage::pager::DatabaseInfo;
+pub use storage::compression::DictionaryStats;
+pub use execution::{BlobInfo, BlobReader, BlobWriter, OnDelete, Quota, Reference, Snapshot};
+pub use flusher::Flusher;
+pub use hooks::{HookId, HookKind};
+pub use aggregate::{Accumulator, Group, Pipeline, Stage};
+pub use collation::Collation;
+pub use advisor::{IndexSuggestion, SuggestedIndex};
+pub use query::{AccessPath, FindQuery, PreparedQuery, QueryPlan, SortStrategy};
+pub use geo::{BoundingBox, GeoPoint, GeoResult};
+pub use graph::{Direction, Edge, NodeRef, Traversal, TraversalHit};
 
 // Re-export vector types for public API
 pub use vector::{
     VectorConfig, VectorDocument, VectorSearchResult, 
     Embedding, VectorId, Distance, MetadataFilter, VectorCollectionStats,
-    CompressionConfig, CompressionMode, CompressionStats,
+    CompressionConfig, CompressionMode, CompressionStats, SimilarDocument,
 };
 pub use vector::search::VectorCollection;
 
+#[cfg(feature = "async")]
+pub use async_db::AsyncDatabase;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -570,4 +2302,378 @@ mod tests {
         assert_eq!(collections[0].0, "users");
         assert_eq!(collections[0].1, 1);
     }
+
+    #[test]
+    fn test_concurrent_vector_inserts_persist_on_sync() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.ndb");
+        let db = Database::create(&path).unwrap();
+        db.create_vector_collection("a", vector::VectorConfig::new(4)).unwrap();
+        db.create_vector_collection("b", vector::VectorConfig::new(4)).unwrap();
+        db.sync().unwrap();
+
+        std::thread::scope(|scope| {
+            for name in ["a", "b"] {
+                let db = &db;
+                scope.spawn(move || {
+                    for i in 0..50 {
+                        db.insert_vector(name, vec![i as f32, 1.0, 2.0, 3.0], None).unwrap();
+                    }
+                });
+            }
+        });
+
+        assert!(db.has_unsynced_vectors());
+        db.sync().unwrap();
+        assert!(!db.has_unsynced_vectors());
+        drop(db);
+
+        let db = Database::open(&path).unwrap();
+        let mut collections = db.list_vector_collections();
+        collections.sort();
+        assert_eq!(collections, vec![("a".to_string(), 50), ("b".to_string(), 50)]);
+    }
+
+    #[test]
+    fn test_optimize_reselects_compression_anchors() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.ndb");
+        let db = Database::create(&path).unwrap();
+        let config = vector::VectorConfig::new(32).with_distance(vector::Distance::Euclidean).with_delta_compression();
+        db.create_vector_collection("vectors", config).unwrap();
+
+        // Two clusters, each member differing from its center in one component
+        let mut ids = Vec::new();
+        for i in 0..40 {
+            let mut vector = vec![if i % 2 == 0 { 1.0 } else { -1.0 }; 32];
+            vector[i % 32] = 0.0;
+            ids.push(db.insert_vector("vectors", vector, None).unwrap());
+        }
+        assert_eq!(db.vector_stats("vectors").unwrap().anchor_count, 0);
+
+        let report = db.optimize_vector_collection("vectors").unwrap();
+        let compression = report.compression.unwrap();
+        assert_eq!((compression.anchor_count, compression.delta_count), (2, 38));
+        assert!(compression.compression_ratio > 0.5);
+
+        // Deleting both anchors leaves the rest to be re-encoded against new ones
+        db.delete_vector("vectors", ids[0]).unwrap();
+        db.delete_vector("vectors", ids[1]).unwrap();
+        let compression = db.optimize_vector_collection("vectors").unwrap().compression.unwrap();
+        assert_eq!((compression.anchor_count, compression.delta_count), (2, 36));
+        db.sync().unwrap();
+        drop(db);
+
+        // The compressed vectors are saved with the collection
+        let db = Database::open(&path).unwrap();
+        let stats = db.vector_stats("vectors").unwrap();
+        assert_eq!((stats.anchor_count, stats.delta_count), (2, 36));
+        assert!((stats.compression_ratio - compression.compression_ratio).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_embedding_model_mismatch() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.ndb");
+        let db = Database::create(&path).unwrap();
+        db.set_embedding_provider(EmbeddingConfig::TfIdf { dimensions: 32 }).unwrap();
+        db.create_vector_collection("docs", vector::VectorConfig::new(32)).unwrap();
+        db.insert_text("docs", "the quick brown fox", None).unwrap();
+        drop(db);
+
+        // Another model of the same size would give meaningless results
+        let db = Database::open(&path).unwrap();
+        db.set_embedding_provider(EmbeddingConfig::Mock { dimensions: 32 }).unwrap();
+        let err = db.vector_search_text("docs", "quick fox", 1).unwrap_err();
+        assert!(matches!(err, KeraDBError::EmbeddingError(_)), "{}", err);
+        assert!(db.insert_text("docs", "lazy dog", None).is_err());
+
+        db.set_embedding_provider(EmbeddingConfig::TfIdf { dimensions: 32 }).unwrap();
+        assert_eq!(db.vector_search_text("docs", "quick fox", 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_find_similar_documents() {
+        let db = Database::in_memory().unwrap();
+        db.set_embedding_provider(EmbeddingConfig::TfIdf { dimensions: 64 }).unwrap();
+        db.create_vector_collection("article_vectors", vector::VectorConfig::new(64)).unwrap();
+        let rust = db.insert("articles", json!({"body": "rust database engine internals"})).unwrap();
+        assert!(db.find_similar_documents("articles", &rust, 2).is_err());
+
+        db.auto_embed("articles", "body", "article_vectors").unwrap();
+        let storage = db.insert("articles", json!({"body": "rust storage engine design"})).unwrap();
+        db.insert("articles", json!({"body": "sourdough bread at home"})).unwrap();
+        db.insert("articles", json!({"title": "no body"})).unwrap();
+
+        let similar = db.find_similar_documents("articles", &rust, 2).unwrap();
+        assert_eq!(similar.len(), 2);
+        assert_eq!(similar[0].document.id, storage);
+        assert!(similar.iter().all(|s| s.document.id != rust));
+        assert!(db.find_similar_documents("articles", "missing", 2).is_err());
+    }
+
+    #[test]
+    fn test_embedding_provider_restored() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.ndb");
+        let db = Database::create(&path).unwrap();
+        db.set_embedding_provider(EmbeddingConfig::TfIdf { dimensions: 32 }).unwrap();
+        db.create_vector_collection("docs", vector::VectorConfig::new(32)).unwrap();
+        db.insert_text("docs", "the quick brown fox", None).unwrap();
+        db.sync().unwrap();
+        drop(db);
+
+        let db = Database::open(&path).unwrap();
+        assert_eq!(db.vector_search_text("docs", "quick fox", 1).unwrap().len(), 1);
+
+        // A function cannot be stored, so it replaces the stored provider
+        let embed = vector::embedding::EmbedFn::new(|_| Ok(vec![0.0; 32]));
+        db.set_embedding_provider(EmbeddingConfig::Custom { model: "zeros".into(), dimensions: 32, embed }).unwrap();
+        drop(db);
+        let db = Database::open(&path).unwrap();
+        assert!(db.vector_search_text("docs", "quick fox", 1).is_err());
+    }
+
+    #[test]
+    fn test_memory_backends() {
+        use storage::MemoryBackend;
+
+        let data = MemoryBackend::new();
+        let vectors = MemoryBackend::new();
+        let db = Database::create_with_backends("app", Box::new(data.clone()), Box::new(vectors.clone()), Config::default()).unwrap();
+        let id = db.insert("users", json!({"name": "Alice"})).unwrap();
+        db.create_vector_collection("embeddings", vector::VectorConfig::new(2)).unwrap();
+        db.insert_vector("embeddings", vec![1.0, 0.0], None).unwrap();
+        db.sync().unwrap();
+        let stats = db.stats().unwrap();
+        assert_eq!(stats.file_size, data.to_bytes().len() as u64);
+        assert_eq!(stats.vector_file_size, vectors.to_bytes().len() as u64);
+        drop(db);
+
+        // Reopen from saved copies, as a browser would after reloading them
+        let (db, report) = Database::open_with_backends(
+            "app",
+            Box::new(MemoryBackend::from_bytes(data.to_bytes())),
+            Box::new(MemoryBackend::from_bytes(vectors.to_bytes())),
+            true,
+            Config::default(),
+        )
+        .unwrap();
+        assert!(report.warnings.is_empty());
+        assert_eq!(db.find_by_id("users", &id).unwrap().data["name"], "Alice");
+        assert_eq!(db.list_vector_collections(), vec![("embeddings".to_string(), 1)]);
+        assert!(matches!(db.insert("users", json!({})), Err(KeraDBError::ReadOnly)));
+    }
+
+    #[test]
+    fn test_in_memory_persist_to() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("saved.ndb");
+        let db = Database::in_memory().unwrap();
+        assert!(db.is_in_memory());
+        let id = db.insert("users", json!({"name": "Alice"})).unwrap();
+        db.create_vector_collection("embeddings", vector::VectorConfig::new(2)).unwrap();
+        db.insert_vector("embeddings", vec![1.0, 0.0], None).unwrap();
+        assert!(!db.has_unsynced_vectors());
+        db.sync().unwrap();
+        assert_eq!(db.stats().unwrap().vector_file_size, 0);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        db.persist_to(&path).unwrap();
+        assert!(matches!(db.persist_to(&path), Err(KeraDBError::InvalidFormat(_))));
+        drop(db);
+
+        let db = Database::open(&path).unwrap();
+        assert!(!db.is_in_memory());
+        assert_eq!(db.find_by_id("users", &id).unwrap().data["name"], "Alice");
+        assert_eq!(db.list_vector_collections(), vec![("embeddings".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_read_only_open() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.ndb");
+        let db = Database::create(&path).unwrap();
+        let id = db.insert("users", json!({"name": "Alice"})).unwrap();
+        assert!(matches!(Database::open_read_only(&path), Err(KeraDBError::Locked(_))));
+        db.sync().unwrap();
+        drop(db);
+
+        let reader = Database::open_read_only(&path).unwrap();
+        let other = Database::open_read_only(&path).unwrap();
+        assert!(reader.is_read_only());
+        assert_eq!(other.find_by_id("users", &id).unwrap().get("name"), Some(json!("Alice")));
+        assert!(matches!(reader.insert("users", json!({})), Err(KeraDBError::ReadOnly)));
+        assert!(matches!(reader.create_vector_collection("v", vector::VectorConfig::new(2)), Err(KeraDBError::ReadOnly)));
+        assert!(matches!(Database::open(&path), Err(KeraDBError::Locked(_))));
+    }
+
+    #[test]
+    fn test_open_with_report() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.ndb");
+        let db = Database::create(&path).unwrap();
+        let alice = db.insert("users", json!({"name": "Alice"})).unwrap();
+        db.insert("users", json!({"name": "Bob"})).unwrap();
+        db.create_vector_collection("embeddings", vector::VectorConfig::new(3)).unwrap();
+        db.sync().unwrap();
+        drop(db);
+
+        let (db, report) = Database::open_with_report(&path, Config::default()).unwrap();
+        assert!(report.is_clean());
+        assert_eq!((report.documents, report.vector_collections.clone()), (2, vec!["embeddings".to_string()]));
+        drop(db);
+
+        // Corrupt Bob's page (page 1, after the 64-byte header) and the vector file
+        let mut data = fs::read(&path).unwrap();
+        data[64 + 4096 + 10] ^= 0xff;
+        fs::write(&path, data).unwrap();
+        fs::write(Database::vector_data_path(&path), b"garbage").unwrap();
+
+        let (db, report) = Database::open_with_report(&path, Config::default()).unwrap();
+        assert_eq!(report.documents, 1);
+        assert!(report.vector_collections.is_empty());
+        assert!(matches!(report.warnings[0], types::OpenWarning::UnreadablePage { page: 1, .. }));
+        assert!(matches!(report.warnings[1], types::OpenWarning::VectorFile { .. }));
+        assert_eq!(report.warnings.len(), 2);
+        assert!(db.find_by_id("users", &alice).is_ok());
+    }
+
+    mod model {
+        //! Random CRUD sequences run against a database and a map of what it
+        //! should hold, which must agree after every step and every reopen
+
+        use crate::storage::MemoryBackend;
+        use crate::{Config, Database, KeraDBError};
+        use proptest::prelude::*;
+        use proptest::test_runner::RngSeed;
+        use serde_json::{json, Value};
+        use std::collections::BTreeMap;
+
+        const COLLECTIONS: [&str; 2] = ["users", "orders"];
+
+        #[derive(Debug, Clone)]
+        enum Op {
+            /// Insert into a collection, with an `_id` from a small set or a generated one
+            Insert(usize, Option<u8>, Value),
+            Update(usize, u8, Value),
+            Delete(usize, u8),
+            Sync,
+            Reopen,
+        }
+
+        /// Expected documents by collection and ID
+        type Model = BTreeMap<&'static str, BTreeMap<String, Value>>;
+
+        fn value() -> impl Strategy<Value = Value> {
+            let scalar = prop_oneof![any::<i32>().prop_map(|n| json!(n)), "[a-z]{0,6}".prop_map(|s| json!(s)), Just(Value::Null)];
+            let field = prop_oneof![
+                scalar.clone(),
+                prop::collection::vec(scalar.clone(), 0..3).prop_map(Value::from),
+                scalar.prop_map(|v| json!({"inner": v})),
+            ];
+            prop::collection::btree_map("[a-z]{1,3}", field, 0..4).prop_map(|fields| Value::Object(fields.into_iter().collect()))
+        }
+
+        fn op() -> impl Strategy<Value = Op> {
+            let collection = 0..COLLECTIONS.len();
+            prop_oneof![
+                4 => (collection.clone(), prop::option::weighted(0.8, 0..6u8), value()).prop_map(|(c, id, v)| Op::Insert(c, id, v)),
+                3 => (collection.clone(), 0..6u8, value()).prop_map(|(c, id, v)| Op::Update(c, id, v)),
+                2 => (collection, 0..6u8).prop_map(|(c, id)| Op::Delete(c, id)),
+                1 => Just(Op::Sync),
+                1 => Just(Op::Reopen),
+            ]
+        }
+
+        fn id(n: u8) -> String {
+            format!("doc{}", n)
+        }
+
+        fn open(data: &MemoryBackend) -> Database {
+            let backend = Box::new(data.clone());
+            let (db, report) = Database::open_with_backends("model", backend, Box::new(MemoryBackend::new()), false, Config::default()).unwrap();
+            assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+            db
+        }
+
+        fn check(db: &Database, model: &Model) -> std::result::Result<(), TestCaseError> {
+            let mut listed: Vec<(String, usize)> = db.list_collections();
+            listed.sort();
+            let expected: Vec<(String, usize)> = model
+                .iter()
+                .filter(|(_, docs)| !docs.is_empty())
+                .map(|(name, docs)| (name.to_string(), docs.len()))
+                .collect();
+            prop_assert_eq!(listed, expected);
+            for name in COLLECTIONS {
+                let empty = BTreeMap::new();
+                let docs = model.get(name).unwrap_or(&empty);
+                prop_assert_eq!(db.count(name), docs.len());
+                let found: BTreeMap<String, Value> = db.find_all(name, None, None).unwrap().into_iter().map(|d| (d.id, d.data)).collect();
+                prop_assert_eq!(&found, docs);
+                for (id, data) in docs {
+                    prop_assert_eq!(&db.find_by_id(name, id).unwrap().data, data);
+                }
+            }
+            Ok(())
+        }
+
+        proptest! {
+            #![proptest_config(ProptestConfig { cases: 64, rng_seed: RngSeed::Fixed(0x6b657261), ..ProptestConfig::default() })]
+
+            #[test]
+            fn crud_matches_model(ops in prop::collection::vec(op(), 1..40)) {
+                let data = MemoryBackend::new();
+                let mut db = Database::create_with_backends("model", Box::new(data.clone()), Box::new(MemoryBackend::new()), Config::default()).unwrap();
+                let mut model = Model::new();
+                for op in ops {
+                    match op {
+                        Op::Insert(c, n, value) => {
+                            let docs = model.entry(COLLECTIONS[c]).or_default();
+                            let mut doc = value.clone();
+                            if let Some(n) = n {
+                                doc["_id"] = json!(id(n));
+                            }
+                            match db.insert(COLLECTIONS[c], doc) {
+                                Ok(new_id) => {
+                                    prop_assert!(n.is_none_or(|n| new_id == id(n)));
+                                    prop_assert!(docs.insert(new_id, value).is_none());
+                                }
+                                Err(KeraDBError::DuplicateKey(dup)) => prop_assert!(docs.contains_key(&dup)),
+                                Err(e) => prop_assert!(false, "insert failed: {}", e),
+                            }
+                        }
+                        Op::Update(c, n, value) => {
+                            let docs = model.entry(COLLECTIONS[c]).or_default();
+                            match db.update(COLLECTIONS[c], &id(n), value.clone()) {
+                                Ok(doc) => {
+                                    prop_assert_eq!(&doc.data, &value);
+                                    prop_assert!(docs.insert(id(n), value).is_some());
+                                }
+                                Err(e) => prop_assert!(matches!(e, KeraDBError::DocumentNotFound(_)) && !docs.contains_key(&id(n)), "{}", e),
+                            }
+                        }
+                        Op::Delete(c, n) => {
+                            let docs = model.entry(COLLECTIONS[c]).or_default();
+                            match db.delete(COLLECTIONS[c], &id(n)) {
+                                Ok(doc) => prop_assert_eq!(Some(doc.data), docs.remove(&id(n))),
+                                Err(e) => prop_assert!(matches!(e, KeraDBError::DocumentNotFound(_)) && !docs.contains_key(&id(n)), "{}", e),
+                            }
+                        }
+                        Op::Sync => db.sync().unwrap(),
+                        Op::Reopen => {
+                            db.sync().unwrap();
+                            drop(db);
+                            db = open(&data);
+                        }
+                    }
+                    check(&db, &model)?;
+                }
+                db.sync().unwrap();
+                drop(db);
+                check(&open(&data), &model)?;
+            }
+        }
+    }
 }
@@ -1,6 +1,13 @@
 use clap::{Parser, Subcommand};
-use keradb::{Database, cli::{Repl, TuiApp}};
-use std::path::PathBuf;
+use keradb::{Config, Database, Distance, KeraDBError, cli::{OutputFormat, Repl, SystemDatabase, TuiApp, output::render_table, parser::{self, Command}}};
+use keradb::cli::system_db::HistoryPolicy;
+use keradb::import::{ImportFormat, ImportOptions};
+use keradb::vector::{ProjectionMethod, VectorFileFormat};
+use keradb::backup::{BackupStore, DirectoryStore};
+use keradb::bench::BenchOptions;
+use serde_json::json;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(name = "keradb")]
@@ -8,6 +15,14 @@ use std::path::PathBuf;
 #[command(author = "KeraDB Contributors")]
 #[command(about = "KeraDB - A lightweight embedded NoSQL database", long_about = None)]
 struct Cli {
+    /// Settings file (defaults to ./keradb.toml if present; KERADB_* variables override it)
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// How to print results and errors: table for people, json or ndjson for scripts
+    #[arg(long, short, global = true, value_enum, default_value_t = OutputFormat::Table)]
+    output: OutputFormat,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -18,12 +33,25 @@ enum Commands {
     Create {
         /// Path to the database file
         path: PathBuf,
+
+        /// Page size in bytes, a power of two from 512 to 65536; larger
+        /// pages hold larger documents
+        #[arg(long)]
+        page_size: Option<usize>,
     },
     
-    /// Open interactive shell (basic REPL)
+    /// Open interactive shell (basic REPL), or run a script of shell commands
     Shell {
         /// Path to the database file
         path: PathBuf,
+
+        /// Run the commands in this file ("-" for stdin) instead of prompting
+        #[arg(long, value_name = "FILE")]
+        script: Option<PathBuf>,
+
+        /// Keep running a script after a command fails
+        #[arg(long)]
+        continue_on_error: bool,
     },
 
     /// Open interactive TUI (Terminal User Interface)
@@ -38,120 +66,1050 @@ enum Commands {
         path: PathBuf,
     },
     
+    /// Export a vector collection to a .jsonl/.ndjson or .parquet file
+    Vexport {
+        /// Path to the database file
+        path: PathBuf,
+
+        /// Vector collection to export
+        collection: String,
+
+        /// Output file (format is inferred from the extension)
+        output: PathBuf,
+    },
+
+    /// Project a vector collection to 2D for plotting, written to a .json or .csv file
+    Vproject {
+        /// Path to the database file
+        path: PathBuf,
+
+        /// Vector collection to project
+        collection: String,
+
+        /// Output file (format is inferred from the extension)
+        output: PathBuf,
+
+        /// Projection method
+        #[arg(long, default_value = "pca")]
+        method: String,
+    },
+
+    /// Import vectors from a .jsonl/.ndjson or .parquet file
+    Vimport {
+        /// Path to the database file
+        path: PathBuf,
+
+        /// Target vector collection (created if it does not exist)
+        collection: String,
+
+        /// Input file (format is inferred from the extension)
+        input: PathBuf,
+
+        /// Distance metric for a newly created collection (defaults to the configured one)
+        #[arg(long)]
+        distance: Option<String>,
+    },
+    
+    /// Import documents from a .json, .ndjson/.jsonl or .csv file
+    Import {
+        /// Path to the database file (created if it does not exist)
+        path: PathBuf,
+
+        /// Target collection
+        collection: String,
+
+        /// Input file
+        input: PathBuf,
+
+        /// Input format: json, ndjson or csv (defaults to the file extension)
+        #[arg(long)]
+        format: Option<String>,
+
+        /// Use this field's value as each document's _id
+        #[arg(long, value_name = "FIELD")]
+        id_field: Option<String>,
+
+        /// Store a CSV column under another field, e.g. city=address.city (repeatable; an empty FIELD drops the column)
+        #[arg(long = "map", value_name = "HEADER=FIELD")]
+        map: Vec<String>,
+
+        /// CSV field delimiter
+        #[arg(long, default_value_t = ',')]
+        delimiter: char,
+
+        /// Documents inserted between progress reports
+        #[arg(long, default_value_t = 1000)]
+        batch_size: usize,
+
+        /// Skip records that cannot be inserted instead of stopping at the first one
+        #[arg(long)]
+        skip_errors: bool,
+    },
+
+    /// Import mongoexport Extended JSON, a .bson file or a mongodump directory
+    #[cfg(feature = "mongo")]
+    ImportMongo {
+        /// Path to the database file (created if it does not exist)
+        path: PathBuf,
+
+        /// Export file, .bson file or mongodump output directory
+        source: PathBuf,
+
+        /// Target collection for a single file (defaults to the file name)
+        #[arg(long)]
+        collection: Option<String>,
+
+        /// Documents inserted between syncs
+        #[arg(long, default_value_t = 1000)]
+        batch_size: usize,
+
+        /// Skip documents that cannot be inserted instead of stopping at the first one
+        #[arg(long)]
+        skip_errors: bool,
+    },
+
+    /// Write a portable NDJSON dump of documents and vector collections
+    Dump {
+        /// Path to the database file
+        path: PathBuf,
+
+        /// Only dump this document or vector collection (repeatable)
+        #[arg(long = "collection", value_name = "NAME")]
+        collections: Vec<String>,
+
+        /// File to write (defaults to stdout)
+        #[arg(long, value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+
+    /// Back up a database to a directory or an S3-compatible bucket
+    Backup {
+        /// Path to the database file
+        path: PathBuf,
+
+        /// Directory to write the backup to
+        #[arg(long, value_name = "DIR")]
+        dir: Option<PathBuf>,
+
+        /// Bucket and prefix to write the backup to (s3://bucket/prefix)
+        #[cfg(feature = "s3")]
+        #[arg(long, value_name = "URL")]
+        s3: Option<String>,
+    },
+
+    /// Restore the latest backup into a new database file
+    Restore {
+        /// Path of the database file to create (or to load a dump into)
+        path: PathBuf,
+
+        /// Directory holding the backup
+        #[arg(long, value_name = "DIR")]
+        dir: Option<PathBuf>,
+
+        /// Load a dump written by `keradb dump` instead ("-" reads stdin)
+        #[arg(long, value_name = "FILE", conflicts_with = "dir")]
+        dump: Option<PathBuf>,
+
+        /// Bucket and prefix holding the backup (s3://bucket/prefix)
+        #[cfg(feature = "s3")]
+        #[arg(long, value_name = "URL", conflicts_with = "dump")]
+        s3: Option<String>,
+    },
+
+    /// Serve the database as a JSON REST API
+    #[cfg(feature = "server")]
+    Serve {
+        /// Path to the database file (created if it does not exist)
+        path: PathBuf,
+
+        /// Port to listen on
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+
+        /// Address to bind
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+
+        /// Bearer token clients must present (repeatable; also read from KERADB_TOKEN)
+        #[arg(long = "token", env = "KERADB_TOKEN")]
+        tokens: Vec<String>,
+
+        /// Authenticate the users in the system database and enforce their grants (see `keradb users`)
+        #[arg(long)]
+        auth: bool,
+
+        /// Keep users and the audit log in this database instead of the system database (implies --auth)
+        #[arg(long, value_name = "PATH")]
+        auth_db: Option<PathBuf>,
+
+        /// POST each write as JSON to this URL, optionally only for one collection (repeatable)
+        #[arg(long = "webhook", value_name = "[COLLECTION=]URL")]
+        webhooks: Vec<String>,
+
+        /// Also serve the gRPC API on this port
+        #[cfg(feature = "grpc")]
+        #[arg(long)]
+        grpc_port: Option<u16>,
+
+        /// Stream changes to followers on this port
+        #[arg(long)]
+        replication_port: Option<u16>,
+
+        /// Run as a read replica of the leader at this address (host:port)
+        #[arg(long, value_name = "ADDR")]
+        follow: Option<String>,
+
+        /// Secret followers must present to the replication port, and --follow
+        /// presents to its leader; required with --auth or --token (also read
+        /// from KERADB_REPLICATION_SECRET)
+        #[arg(long, value_name = "SECRET", env = "KERADB_REPLICATION_SECRET")]
+        replication_secret: Option<String>,
+
+        /// Periodically back up to this bucket and prefix (s3://bucket/prefix)
+        #[cfg(feature = "s3")]
+        #[arg(long, value_name = "URL")]
+        backup_s3: Option<String>,
+
+        /// Seconds between backups; all but the first are incremental
+        #[cfg(feature = "s3")]
+        #[arg(long, default_value_t = 300)]
+        backup_interval: u64,
+
+        /// Also speak the MongoDB wire protocol, without authentication, so not
+        /// with --auth or --token (port defaults to 27017)
+        #[cfg(feature = "mongo")]
+        #[arg(long, value_name = "PORT", num_args = 0..=1, default_missing_value = "27017")]
+        mongo: Option<u16>,
+    },
+    
+    /// Time a built-in workload on a scratch database and report throughput and latency
+    Bench {
+        /// Path of the database file to create (deleted afterwards unless --keep)
+        path: PathBuf,
+
+        /// Documents to insert
+        #[arg(long, default_value_t = 100_000)]
+        docs: usize,
+
+        /// Documents to read back by ID
+        #[arg(long, default_value_t = 10_000)]
+        reads: usize,
+
+        /// Full scans of the documents
+        #[arg(long, default_value_t = 10)]
+        scans: usize,
+
+        /// Vectors to insert
+        #[arg(long, default_value_t = 10_000)]
+        vectors: usize,
+
+        /// Dimensions of each vector
+        #[arg(long, default_value_t = 384)]
+        dims: usize,
+
+        /// Vector searches to run
+        #[arg(long, default_value_t = 1_000)]
+        searches: usize,
+
+        /// Results per vector search
+        #[arg(short, default_value_t = 10)]
+        k: usize,
+
+        /// Keep the database file afterwards
+        #[arg(long)]
+        keep: bool,
+    },
+
+    /// List the databases the TUI remembers, and clean up that history
+    Connections {
+        /// Forget connections beyond the history limits now
+        /// (KERADB_HISTORY_MAX_CONNECTIONS and KERADB_HISTORY_MAX_AGE_DAYS)
+        #[arg(long)]
+        prune: bool,
+
+        /// Keep at most this many connections when pruning (0 for no limit)
+        #[arg(long, requires = "prune")]
+        max: Option<usize>,
+
+        /// Forget connections not opened for this many days when pruning (0 for no limit)
+        #[arg(long, requires = "prune")]
+        max_age_days: Option<u32>,
+
+        /// Forget connections whose database file no longer exists
+        #[arg(long)]
+        missing: bool,
+
+        /// Forget the connection to this database (repeatable)
+        #[arg(long, value_name = "PATH")]
+        remove: Vec<String>,
+    },
+
+    /// Manage the users `keradb serve --auth` accepts and read its audit log
+    #[cfg(feature = "server")]
+    Users {
+        /// Database holding the users (defaults to the system database)
+        #[arg(long, global = true, value_name = "PATH")]
+        auth_db: Option<PathBuf>,
+
+        #[command(subcommand)]
+        action: Option<UsersAction>,
+    },
+
     /// Execute a single query
     Query {
         /// Path to the database file
         path: PathBuf,
         
-        /// Query to execute
+        /// Query to execute, e.g. "find users where age > 30 sort age desc limit 10"
         query: String,
     },
 }
 
-fn main() -> anyhow::Result<()> {
+/// `keradb users` subcommands; without one, users are listed
+#[cfg(feature = "server")]
+#[derive(Subcommand)]
+enum UsersAction {
+    /// Add a user, with a password from KERADB_PASSWORD or a prompt
+    Add {
+        name: String,
+
+        /// Allow the user everything
+        #[arg(long)]
+        admin: bool,
+
+        /// Create the user without a password, to use API tokens only
+        #[arg(long)]
+        no_password: bool,
+    },
+
+    /// Change a user's password (from KERADB_PASSWORD or a prompt)
+    Passwd { name: String },
+
+    /// Remove a user
+    Remove { name: String },
+
+    /// Let a user read or write a database's collections
+    Grant {
+        name: String,
+
+        /// read or write (which includes read)
+        access: String,
+
+        /// DATABASE/COLLECTION, or DATABASE for all its collections; either may be *
+        target: String,
+    },
+
+    /// Take back a grant made with `grant`
+    Revoke { name: String, target: String },
+
+    /// Issue an API token for a user; it is only shown once
+    Token {
+        name: String,
+
+        /// Revoke all of the user's tokens instead
+        #[arg(long)]
+        revoke_all: bool,
+    },
+
+    /// Show the most recent mutations made through the server
+    Audit {
+        /// Entries to show
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+    },
+}
+
+fn main() {
     tracing_subscriber::fmt::init();
 
     let cli = Cli::parse();
+    let output = cli.output;
+    if let Err(e) = run(cli) {
+        output.print_error(&e);
+        std::process::exit(keradb::cli::output::exit_code(&e));
+    }
+}
+
+fn run(cli: Cli) -> anyhow::Result<()> {
+    let config = Config::load(cli.config.as_deref())?;
+    let output = cli.output;
 
     // If no command provided, launch TUI directly
     let command = cli.command.unwrap_or(Commands::Tui { path: None });
 
     match command {
-        Commands::Create { path } => {
+        Commands::Create { path, page_size } => {
             if path.exists() {
-                eprintln!("Error: Database file already exists: {}", path.display());
-                std::process::exit(1);
+                anyhow::bail!("Database file already exists: {}", path.display());
             }
 
-            Database::create(&path)?;
-            println!("Created database: {}", path.display());
+            let config = match page_size {
+                Some(page_size) => {
+                    keradb::storage::pager::check_page_size(page_size)?;
+                    config.with_page_size(page_size)
+                }
+                None => config,
+            };
+            Database::create_with_config(&path, config)?;
+            output.print(&json!({ "path": path }), || println!("Created database: {}", path.display()))?;
         }
 
-        Commands::Shell { path } => {
-            let mut repl = Repl::new(&path)?;
-            repl.run()?;
+        Commands::Shell { path, script, continue_on_error } => {
+            let mut repl = Repl::with_config(&path, config)?.with_output(output);
+            match script {
+                Some(file) if file.as_os_str() != "-" => {
+                    let reader = std::io::BufReader::new(std::fs::File::open(&file)?);
+                    repl.run_script(reader, continue_on_error)?
+                }
+                // Commands piped in are run as a script too
+                Some(_) => repl.run_script(std::io::stdin().lock(), continue_on_error)?,
+                None if !std::io::stdin().is_terminal() => {
+                    repl.run_script(std::io::stdin().lock(), continue_on_error)?
+                }
+                None => repl.run()?,
+            }
         }
 
         Commands::Tui { path } => {
-            let mut tui = match path {
-                Some(p) => TuiApp::with_database(&p)?,
-                None => TuiApp::new()?,
-            };
+            let mut tui = TuiApp::new()?.with_config(config);
+            if let Some(p) = path {
+                tui.connect_to_database(p.to_string_lossy().to_string())?;
+            }
             tui.run()?;
         }
 
         Commands::Stats { path } => {
-            let db = Database::open(&path)?;
-            
-            let collections = db.list_collections();
-            let total_docs: usize = collections.iter().map(|(_, count)| count).sum();
-            
-            let file_size = std::fs::metadata(&path)?.len();
-            let file_size_mb = file_size as f64 / 1024.0 / 1024.0;
-            
-            println!("Database: {}", path.display());
-            println!("Size: {:.2} MB", file_size_mb);
-            println!("Collections: {}", collections.len());
-            println!("Total Documents: {}", total_docs);
-            println!();
-            
-            if !collections.is_empty() {
-                println!("Collections:");
-                for (name, count) in collections {
-                    println!("  {} - {} documents", name, count);
+            let db = Database::open_read_only_with_config(&path, config)?;
+            let stats = db.stats()?;
+            output.print(&stats, || print!("{}", stats))?;
+        }
+
+        Commands::Vexport { path, collection, output: file } => {
+            let db = Database::open_read_only_with_config(&path, config)?;
+            let count = db.export_vector_collection(&collection, &file)?;
+            let exported = json!({ "collection": collection, "path": file, "vectors": count });
+            output.print(&exported, || {
+                println!("Exported {} vectors from '{}' to {}", count, collection, file.display())
+            })?;
+        }
+
+        Commands::Vproject { path, collection, output: file, method } => {
+            let Some(method) = ProjectionMethod::from_name(&method) else {
+                return Err(KeraDBError::InvalidQuery(format!("Unknown projection method: {}", method)).into());
+            };
+            let db = Database::open_read_only_with_config(&path, config)?;
+            let count = db.export_vector_projection(&collection, method, &file)?;
+            let projected = json!({ "collection": collection, "path": file, "method": method.name(), "vectors": count });
+            output.print(&projected, || {
+                println!("Projected {} vectors from '{}' to {} ({})", count, collection, file.display(), method.name())
+            })?;
+        }
+
+        Commands::Vimport { path, collection, input, distance } => {
+            let db = open_or_create(&path, config)?;
+
+            let distance = match distance {
+                Some(name) => match Distance::from_name(&name) {
+                    Some(distance) => distance,
+                    None => return Err(KeraDBError::InvalidQuery(format!("Unknown distance metric: {}", name)).into()),
+                },
+                None => db.config().vector.distance,
+            };
+
+            // Dimensions for a new collection are taken from the first record
+            let records = VectorFileFormat::from_path(&input)?.read(&input)?;
+            let config = records.first().map(|r| db.config().vector_config(r.vector.len()).with_distance(distance));
+
+            let count = db.import_vector_records(&collection, records, config)?;
+            db.sync()?;
+            let imported = json!({ "collection": collection, "path": input, "vectors": count });
+            output.print(&imported, || {
+                println!("Imported {} vectors into '{}' from {}", count, collection, input.display())
+            })?;
+        }
+
+        Commands::Import { path, collection, input, format, id_field, map, delimiter, batch_size, skip_errors } => {
+            let format = match format {
+                Some(name) => ImportFormat::from_name(&name)
+                    .ok_or_else(|| KeraDBError::InvalidQuery(format!("Unknown import format: {}", name)))?,
+                None => ImportFormat::from_path(&input)?,
+            };
+            if !delimiter.is_ascii() {
+                anyhow::bail!("The CSV delimiter must be an ASCII character");
+            }
+            let mut options = ImportOptions::default()
+                .with_format(format)
+                .with_batch_size(batch_size)
+                .with_delimiter(delimiter as u8)
+                .with_skip_errors(skip_errors);
+            if let Some(field) = id_field {
+                options = options.with_id_field(field);
+            }
+            for mapping in map {
+                let (header, field) = mapping
+                    .split_once('=')
+                    .ok_or_else(|| KeraDBError::InvalidQuery(format!("Expected HEADER=FIELD, got '{}'", mapping)))?;
+                options = options.with_field(header, field);
+            }
+
+            let db = open_or_create(&path, config)?;
+            let reader = std::io::BufReader::new(std::fs::File::open(&input)?);
+            let show_progress = output == OutputFormat::Table && std::io::stderr().is_terminal();
+            let result = db.import_reader(&collection, reader, &options, |p| {
+                if show_progress {
+                    eprint!("\rImported {} of {} records ({} failed)", p.imported, p.records, p.failed);
                 }
+            });
+            if show_progress {
+                eprintln!();
             }
+            db.sync()?;
+            let report = result?;
+
+            output.print(&report, || {
+                println!("Imported {} documents into '{}' from {}", report.imported, collection, input.display());
+                if report.failed > 0 {
+                    println!("Skipped {} records:", report.failed);
+                    for error in &report.errors {
+                        println!("  {}", error);
+                    }
+                }
+            })?;
         }
 
-        Commands::Query { path, query } => {
-            let db = Database::open(&path)?;
-            
-            // Simple query parser: "find <collection> [id]"
-            let parts: Vec<&str> = query.split_whitespace().collect();
-            
-            if parts.is_empty() {
-                eprintln!("Error: Empty query");
-                std::process::exit(1);
-            }
-
-            match parts[0] {
-                "find" => {
-                    if parts.len() < 2 {
-                        eprintln!("Usage: find <collection> [id]");
-                        std::process::exit(1);
+        #[cfg(feature = "mongo")]
+        Commands::ImportMongo { path, source, collection, batch_size, skip_errors } => {
+            let options = ImportOptions::default().with_batch_size(batch_size).with_skip_errors(skip_errors);
+            let db = open_or_create(&path, config)?;
+            let result = db.import_mongo(&source, collection.as_deref(), &options);
+            db.sync()?;
+            let imports = result?;
+
+            output.print(&imports, || {
+                for import in &imports {
+                    let report = &import.report;
+                    println!(
+                        "Imported {} documents into '{}' from {}",
+                        report.imported,
+                        import.collection,
+                        import.source.display()
+                    );
+                    if report.failed > 0 {
+                        println!("Skipped {} records:", report.failed);
+                        for error in &report.errors {
+                            println!("  {}", error);
+                        }
                     }
-                    
-                    let collection = parts[1];
-                    
-                    if parts.len() == 2 {
-                        // Find all
-                        let docs = db.find_all(collection, Some(10), None)?;
-                        let json = serde_json::to_string_pretty(&docs)?;
-                        println!("{}", json);
-                    } else {
-                        // Find by ID
-                        let doc = db.find_by_id(collection, parts[2])?;
-                        let json = serde_json::to_string_pretty(&doc.to_value())?;
-                        println!("{}", json);
+                }
+            })?;
+        }
+
+        Commands::Backup { path, dir, #[cfg(feature = "s3")] s3 } => {
+            #[cfg(not(feature = "s3"))]
+            let s3: Option<String> = None;
+            let store = backup_store(dir, s3)?;
+            let db = Database::open_with_config(&path, config)?;
+            let info = db.backup_to(store.as_ref())?;
+            output.print(&info, || println!("Wrote {:?} backup {} ({} records)", info.kind, info.key, info.records))?;
+        }
+
+        Commands::Dump { path, collections, out } => {
+            let db = Database::open_read_only_with_config(&path, config)?;
+            match out {
+                Some(file) => {
+                    let writer = std::io::BufWriter::new(std::fs::File::create(&file)?);
+                    let summary = db.dump(writer, &collections)?;
+                    output.print(&summary, || {
+                        println!(
+                            "Dumped {} documents and {} vectors to {}",
+                            summary.documents, summary.vectors, file.display()
+                        )
+                    })?;
+                }
+                None => {
+                    db.dump(std::io::stdout().lock(), &collections)?;
+                }
+            }
+        }
+
+        Commands::Restore { path, dump: Some(file), .. } => {
+            let db = open_or_create(&path, config)?;
+            let summary = if file == Path::new("-") {
+                db.restore_dump(std::io::stdin().lock())?
+            } else {
+                db.restore_dump(std::io::BufReader::new(std::fs::File::open(&file)?))?
+            };
+            output.print(&summary, || {
+                println!("Restored {} documents and {} vectors into {}", summary.documents, summary.vectors, path.display())
+            })?;
+        }
+
+        Commands::Restore { path, dir, dump: None, #[cfg(feature = "s3")] s3 } => {
+            #[cfg(not(feature = "s3"))]
+            let s3: Option<String> = None;
+            let store = backup_store(dir, s3)?;
+            let db = Database::restore_from(store.as_ref(), &path)?;
+            let documents: usize = db.list_collections().iter().map(|(_, count)| count).sum();
+            output.print(&json!({ "path": path, "documents": documents }), || {
+                println!("Restored {} documents into {}", documents, path.display())
+            })?;
+        }
+
+        #[cfg(feature = "server")]
+        Commands::Serve {
+            path,
+            port,
+            host,
+            tokens,
+            auth,
+            auth_db,
+            webhooks,
+            replication_port,
+            follow,
+            replication_secret,
+            #[cfg(feature = "s3")]
+            backup_s3,
+            #[cfg(feature = "s3")]
+            backup_interval,
+            #[cfg(feature = "grpc")]
+            grpc_port,
+            #[cfg(feature = "mongo")]
+            mongo,
+        } => {
+            use keradb::server::{HttpServer, ServerConfig};
+
+            let db = open_or_create(&path, config)?;
+
+            let mut config = ServerConfig::new(port).with_host(host);
+            config.tokens = tokens;
+
+            if auth || auth_db.is_some() {
+                let name = path.file_stem().map_or_else(|| "default".into(), |s| s.to_string_lossy().into_owned());
+                let access = open_access_control(auth_db)?.with_database(name);
+                if access.users()?.is_empty() && config.tokens.is_empty() {
+                    eprintln!("Warning: no users yet; add one with `keradb users add`");
+                }
+                config = config.with_access_control(std::sync::Arc::new(access));
+            }
+            if replication_port.is_some() && replication_secret.is_none() && config.requires_auth() {
+                anyhow::bail!("--replication-port streams every document, so with --auth or --token it needs --replication-secret");
+            }
+            #[cfg(feature = "mongo")]
+            if mongo.is_some() && config.requires_auth() {
+                anyhow::bail!("--mongo does not authenticate clients, so it cannot be combined with --auth or --token");
+            }
+
+            for spec in &webhooks {
+                let webhook = keradb::server::Webhook::parse(spec)?;
+                webhook.register(&db);
+                match webhook.collection() {
+                    "*" => println!("Sending writes to {}", webhook.url()),
+                    collection => println!("Sending writes to {} to {}", collection, webhook.url()),
+                }
+            }
+
+            let db = std::sync::Arc::new(db);
+
+            // Keeps flushing until the server stops
+            let _flusher = db.config().flush_interval.map(|interval| {
+                println!("Flushing writes every {}ms", interval.as_millis());
+                keradb::Flusher::start(&db, interval)
+            });
+
+            if let Some(replication_port) = replication_port {
+                let mut leader = keradb::replication::ReplicationLeader::bind(db.clone(), &format!("{}:{}", config.host, replication_port))?;
+                if let Some(secret) = &replication_secret {
+                    leader = leader.with_secret(secret.clone());
+                }
+                println!("Serving replication on {}", leader.local_addr()?);
+                std::thread::spawn(move || {
+                    if let Err(e) = leader.run() {
+                        eprintln!("Error: replication leader stopped: {}", e);
                     }
+                });
+            }
+
+            if let Some(leader) = follow {
+                let mut follower = keradb::replication::ReplicationFollower::new(db.clone(), leader.clone());
+                if let Some(secret) = replication_secret {
+                    follower = follower.with_secret(secret);
                 }
-                "count" => {
-                    if parts.len() < 2 {
-                        eprintln!("Usage: count <collection>");
-                        std::process::exit(1);
+                println!("Following {}", leader);
+                std::thread::spawn(move || {
+                    if let Err(e) = follower.run() {
+                        eprintln!("Error: replication stopped: {}", e);
                     }
-                    
-                    let collection = parts[1];
-                    let count = db.count(collection);
-                    println!("{}", count);
+                });
+            }
+
+            #[cfg(feature = "s3")]
+            if let Some(url) = backup_s3 {
+                let store = keradb::backup::S3Store::from_url(&url)?;
+                let db = db.clone();
+                println!("Backing up to {} every {}s", url, backup_interval);
+                std::thread::spawn(move || loop {
+                    if let Err(e) = db.backup_to(&store) {
+                        eprintln!("Error: backup to {} failed: {}", url, e);
+                    }
+                    std::thread::sleep(std::time::Duration::from_secs(backup_interval));
+                });
+            }
+
+            #[cfg(feature = "grpc")]
+            if let Some(grpc_port) = grpc_port {
+                let grpc = keradb::server::GrpcServer::new(db.clone(), ServerConfig { port: grpc_port, ..config.clone() });
+                println!("Serving gRPC on {}:{}", config.host, grpc_port);
+                std::thread::spawn(move || {
+                    if let Err(e) = grpc.run() {
+                        eprintln!("Error: gRPC server stopped: {}", e);
+                    }
+                });
+            }
+
+            #[cfg(feature = "mongo")]
+            if let Some(mongo_port) = mongo {
+                let mongo = keradb::compat::MongoServer::new(db.clone(), ServerConfig { port: mongo_port, ..config.clone() });
+                println!("Serving MongoDB wire protocol on mongodb://{}:{}", config.host, mongo_port);
+                std::thread::spawn(move || {
+                    if let Err(e) = mongo.run() {
+                        eprintln!("Error: MongoDB listener stopped: {}", e);
+                    }
+                });
+            }
+
+            println!("Serving {} on http://{}", path.display(), config.addr());
+            HttpServer::new(db, config).run()?;
+        }
+
+        Commands::Bench { path, docs, reads, scans, vectors, dims, searches, k, keep } => {
+            if path.exists() {
+                anyhow::bail!("{} already exists; bench needs a path for a new database", path.display());
+            }
+            let options = BenchOptions { docs, reads, scans, vectors, dimensions: dims, searches, k, keep };
+            let interactive = std::io::stderr().is_terminal();
+            let report = keradb::bench::run(&path, config, &options, |operation| {
+                if interactive {
+                    eprintln!("Running {}...", operation);
                 }
-                _ => {
-                    eprintln!("Unknown query command: {}", parts[0]);
-                    std::process::exit(1);
+            })?;
+            output.print(&report, || print!("{}", report))?;
+        }
+
+        Commands::Connections { prune, max, max_age_days, missing, remove } => {
+            let mut policy = HistoryPolicy::from_env()?;
+            if let Some(max) = max {
+                policy = policy.with_max_connections((max > 0).then_some(max));
+            }
+            if let Some(days) = max_age_days {
+                policy = policy.with_max_age_days((days > 0).then_some(days));
+            }
+            let system = SystemDatabase::init()?.with_policy(policy);
+
+            let mut removed = Vec::new();
+            for path in &remove {
+                match system.list_connections()?.into_iter().find(|c| c.path == *path) {
+                    Some(connection) => removed.push(connection),
+                    None => return Err(KeraDBError::NotFound(format!("{} (not in the connection history)", path)).into()),
                 }
+                system.remove_connection(path)?;
+            }
+            if missing {
+                removed.extend(system.remove_missing()?);
             }
+            if prune {
+                removed.extend(system.prune()?);
+            }
+
+            let connections = system.list_connections()?;
+            let rows: Vec<_> = connections
+                .iter()
+                .map(|c| {
+                    json!({
+                        "name": c.name,
+                        "path": c.path,
+                        "last opened": c.format_last_accessed(),
+                        "opened": c.access_count,
+                        "collections": c.collections_count,
+                        "documents": c.total_documents,
+                    })
+                })
+                .collect();
+            output.print(&json!({ "connections": connections, "removed": removed }), || {
+                if !removed.is_empty() {
+                    println!("Removed {} connection(s)", removed.len());
+                }
+                if connections.is_empty() {
+                    println!("No connection history");
+                } else {
+                    println!("{}", render_table(&json!(rows)));
+                }
+            })?;
+        }
+
+        #[cfg(feature = "server")]
+        Commands::Users { auth_db, action } => run_users(open_access_control(auth_db)?, action, output)?,
+
+        Commands::Query { path, query } => {
+            let db = Database::open_with_config(&path, config)?;
+            run_query(&db, &query, output)?;
         }
     }
 
     Ok(())
 }
+
+/// Run one `keradb query` command and print its result
+///
+/// Writes are synced before returning. Interactive-only commands such as
+/// `vcreate` are left to `keradb shell`; see `keradb::cli::parser` for the
+/// query language.
+fn run_query(db: &Database, query: &str, output: OutputFormat) -> anyhow::Result<()> {
+    match parser::parse(query)? {
+        Command::Find(mut query) => {
+            if let Some(id) = &query.id {
+                match query.run(db)?.first() {
+                    Some(doc) => output.print_value(&doc.to_value())?,
+                    None => return Err(KeraDBError::DocumentNotFound(format!("{} does not match the filter", id)).into()),
+                }
+            } else {
+                query.limit.get_or_insert(10);
+                let docs: Vec<_> = query.run(db)?.iter().map(|doc| doc.to_value()).collect();
+                output.print_value(&docs)?;
+            }
+        }
+        Command::Count { collection, filter } => {
+            let count = filter.count(db, &collection)?;
+            output.print(&json!({ "collection": collection, "count": count }), || println!("{}", count))?;
+        }
+        Command::Explain(mut query) => {
+            if query.id.is_none() {
+                query.limit.get_or_insert(10);
+            }
+            let plan = db.explain(&query);
+            output.print(&plan, || print!("{}", plan))?;
+        }
+        Command::Insert { collection, document } => {
+            let id = db.insert(&collection, document)?;
+            db.sync()?;
+            output.print(&json!({ "_id": id }), || println!("{}", id))?;
+        }
+        Command::Update { collection, id, document } => {
+            let doc = db.update(&collection, &id, document)?;
+            db.sync()?;
+            output.print_value(&doc.to_value())?;
+        }
+        Command::Delete { collection, id } => {
+            let doc = db.delete(&collection, &id)?;
+            db.sync()?;
+            output.print_value(&doc.to_value())?;
+        }
+        Command::VectorSearch { collection, query, k, filter } => {
+            let results = query.search(db, &collection, k, &filter)?;
+            output.print(&results, || {
+                let rows: Vec<_> = results
+                    .iter()
+                    .map(|r| json!({ "id": r.document.id, "score": r.score, "metadata": r.document.metadata }))
+                    .collect();
+                println!("{}", render_table(&json!(rows)));
+            })?;
+        }
+        Command::VectorStats { collection: Some(collection) } => output.print_value(&db.vector_stats(&collection)?)?,
+        Command::VectorStats { collection: None } | Command::VectorCollections => {
+            let rows: Vec<_> =
+                db.list_vector_collections().into_iter().map(|(name, count)| json!({ "name": name, "vectors": count })).collect();
+            output.print_value(&rows)?;
+        }
+        Command::Collections => {
+            let rows: Vec<_> =
+                db.list_collections().into_iter().map(|(name, count)| json!({ "name": name, "documents": count })).collect();
+            output.print_value(&rows)?;
+        }
+        Command::Stats => {
+            let stats = db.stats()?;
+            output.print(&stats, || print!("{}", stats))?;
+        }
+        _ => anyhow::bail!("Only reads, explains, inserts, updates, deletes and vector searches are supported here; use `keradb shell`"),
+    }
+    Ok(())
+}
+
+/// Server users from `path`, or from the system database
+#[cfg(feature = "server")]
+fn open_access_control(path: Option<PathBuf>) -> anyhow::Result<keradb::server::AccessControl> {
+    let path = match path {
+        Some(path) => path,
+        None => SystemDatabase::get_system_db_path()?,
+    };
+    let system = open_or_create(&path, Config::default())?;
+    Ok(keradb::server::AccessControl::new(std::sync::Arc::new(system)))
+}
+
+/// Run one `keradb users` command
+#[cfg(feature = "server")]
+fn run_users(access: keradb::server::AccessControl, action: Option<UsersAction>, output: OutputFormat) -> anyhow::Result<()> {
+    use keradb::server::Access;
+
+    // DATABASE/COLLECTION, or DATABASE alone for all of its collections
+    let target = |target: &str| match target.split_once('/') {
+        Some((database, collection)) => (database.to_string(), collection.to_string()),
+        None => (target.to_string(), "*".to_string()),
+    };
+
+    match action {
+        None => {
+            let users = access.users()?;
+            let rows: Vec<_> = users
+                .iter()
+                .map(|user| {
+                    let grants: Vec<_> =
+                        user.grants.iter().map(|g| format!("{}/{} ({})", g.database, g.collection, g.access)).collect();
+                    json!({
+                        "name": user.name,
+                        "admin": user.admin,
+                        "password": user.has_password(),
+                        "tokens": user.token_count(),
+                        "grants": grants.join(", "),
+                    })
+                })
+                .collect();
+            output.print(&json!(rows), || {
+                if rows.is_empty() {
+                    println!("No users");
+                } else {
+                    println!("{}", render_table(&json!(rows)));
+                }
+            })?;
+        }
+        Some(UsersAction::Add { name, admin, no_password }) => {
+            let password = if no_password { None } else { Some(read_password(&format!("Password for {}: ", name))?) };
+            access.add_user(&name, password.as_deref(), admin)?;
+            output.print(&json!({ "added": name }), || println!("Added user {}", name))?;
+        }
+        Some(UsersAction::Passwd { name }) => {
+            let password = read_password(&format!("New password for {}: ", name))?;
+            access.set_password(&name, Some(&password))?;
+            output.print(&json!({ "updated": name }), || println!("Changed the password of {}", name))?;
+        }
+        Some(UsersAction::Remove { name }) => {
+            if !access.remove_user(&name)? {
+                return Err(KeraDBError::NotFound(format!("User '{}'", name)).into());
+            }
+            output.print(&json!({ "removed": name }), || println!("Removed user {}", name))?;
+        }
+        Some(UsersAction::Grant { name, access: level, target: spec }) => {
+            let level = Access::from_name(&level)
+                .ok_or_else(|| KeraDBError::InvalidQuery(format!("Access must be read or write, not '{}'", level)))?;
+            let (database, collection) = target(&spec);
+            access.grant(&name, &database, &collection, level)?;
+            output.print(&json!({ "user": name, "database": database, "collection": collection, "access": level }), || {
+                println!("{} may now {} {}/{}", name, level, database, collection)
+            })?;
+        }
+        Some(UsersAction::Revoke { name, target: spec }) => {
+            let (database, collection) = target(&spec);
+            if !access.revoke(&name, &database, &collection)? {
+                return Err(KeraDBError::NotFound(format!("Grant of {}/{} to '{}'", database, collection, name)).into());
+            }
+            output.print(&json!({ "user": name, "database": database, "collection": collection }), || {
+                println!("Revoked {}/{} from {}", database, collection, name)
+            })?;
+        }
+        Some(UsersAction::Token { name, revoke_all: true }) => {
+            let count = access.revoke_tokens(&name)?;
+            output.print(&json!({ "user": name, "revoked": count }), || println!("Revoked {} token(s) of {}", count, name))?;
+        }
+        Some(UsersAction::Token { name, revoke_all: false }) => {
+            let token = access.create_token(&name)?;
+            output.print(&json!({ "user": name, "token": token }), || println!("{}", token))?;
+        }
+        Some(UsersAction::Audit { limit }) => {
+            let entries = access.audit_log(limit)?;
+            output.print(&entries, || {
+                let rows: Vec<_> = entries
+                    .iter()
+                    .map(|e| {
+                        json!({
+                            "at": e.at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                            "user": e.user,
+                            "database": e.database,
+                            "collection": e.collection,
+                            "operation": e.operation,
+                            "id": e.id,
+                        })
+                    })
+                    .collect();
+                if rows.is_empty() {
+                    println!("No audited changes");
+                } else {
+                    println!("{}", render_table(&json!(rows)));
+                }
+            })?;
+        }
+    }
+    access.sync()?;
+    Ok(())
+}
+
+/// A password from KERADB_PASSWORD, typed without echo, or piped in
+#[cfg(feature = "server")]
+fn read_password(prompt: &str) -> anyhow::Result<String> {
+    use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+
+    if let Ok(password) = std::env::var("KERADB_PASSWORD") {
+        return Ok(password);
+    }
+    if !std::io::stdin().is_terminal() {
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line)?;
+        return Ok(line.trim_end_matches(['\r', '\n']).to_string());
+    }
+
+    eprint!("{}", prompt);
+    crossterm::terminal::enable_raw_mode()?;
+    let mut password = String::new();
+    let result = loop {
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => match key.code {
+                KeyCode::Enter => break Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    break Err(anyhow::anyhow!("Cancelled"))
+                }
+                KeyCode::Char(c) => password.push(c),
+                KeyCode::Backspace => {
+                    password.pop();
+                }
+                _ => {}
+            },
+            Ok(_) => {}
+            Err(e) => break Err(e.into()),
+        }
+    };
+    crossterm::terminal::disable_raw_mode()?;
+    eprintln!();
+    result?;
+    if password.is_empty() {
+        anyhow::bail!("The password must not be empty");
+    }
+    Ok(password)
+}
+
+/// Open the database at `path`, creating it if it does not exist
+fn open_or_create(path: &Path, config: Config) -> keradb::error::Result<Database> {
+    if path.exists() {
+        Database::open_with_config(path, config)
+    } else {
+        Database::create_with_config(path, config)
+    }
+}
+
+/// Pick the backup target given on the command line
+fn backup_store(dir: Option<PathBuf>, s3: Option<String>) -> anyhow::Result<Box<dyn BackupStore>> {
+    match (dir, s3) {
+        (Some(dir), None) => Ok(Box::new(DirectoryStore::new(dir))),
+        #[cfg(feature = "s3")]
+        (None, Some(url)) => Ok(Box::new(keradb::backup::S3Store::from_url(&url)?)),
+        #[cfg(not(feature = "s3"))]
+        (None, Some(_)) => anyhow::bail!("S3 backups require the `s3` feature"),
+        _ => anyhow::bail!("Specify exactly one backup target (--dir or --s3)"),
+    }
+}
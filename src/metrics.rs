@@ -0,0 +1,289 @@
+//! Operation counters and latency histograms
+//!
+//! Every [`Database`](crate::Database) records how many inserts, finds,
+//! vector searches and so on it has served, how many failed, and how long
+//! they took. [`Database::metrics`](crate::Database::metrics) returns a
+//! [`MetricsSnapshot`] that can be inspected directly or rendered in the
+//! Prometheus text format, which is what `keradb serve` exposes at `/metrics`.
+
+use crate::error::Result;
+use crate::storage::CacheStats;
+use serde::Serialize;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::Instant;
+
+/// `std::time::Instant` panics in browsers, so time operations with the
+/// JavaScript clock instead
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+struct Instant(f64);
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+impl Instant {
+    fn now() -> Self {
+        Self(js_sys::Date::now())
+    }
+
+    fn elapsed(&self) -> std::time::Duration {
+        std::time::Duration::from_secs_f64((js_sys::Date::now() - self.0).max(0.0) / 1000.0)
+    }
+}
+
+/// Upper bounds, in seconds, of the latency histogram buckets
+const BUCKETS: [f64; 14] = [
+    0.00005, 0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0, 5.0,
+];
+
+/// Latency histogram and error count for one kind of operation
+pub struct Histogram {
+    /// Non-cumulative count per bucket, plus one for slower operations
+    buckets: [AtomicU64; BUCKETS.len() + 1],
+    count: AtomicU64,
+    errors: AtomicU64,
+    sum_nanos: AtomicU64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+            count: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            sum_nanos: AtomicU64::new(0),
+        }
+    }
+
+    /// Run `f`, recording its duration and whether it failed
+    pub(crate) fn time<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let start = Instant::now();
+        let result = f();
+        self.record(start.elapsed().as_secs_f64(), result.is_err());
+        result
+    }
+
+    fn record(&self, seconds: f64, failed: bool) {
+        let bucket = BUCKETS.iter().position(|&b| seconds <= b).unwrap_or(BUCKETS.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_nanos.fetch_add((seconds * 1e9) as u64, Ordering::Relaxed);
+        if failed {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn snapshot(&self, operation: &'static str) -> OperationMetrics {
+        let mut cumulative = 0;
+        let buckets = BUCKETS
+            .iter()
+            .zip(&self.buckets)
+            .map(|(&le, count)| {
+                cumulative += count.load(Ordering::Relaxed);
+                (le, cumulative)
+            })
+            .collect();
+        OperationMetrics {
+            operation,
+            count: self.count.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            total_seconds: self.sum_nanos.load(Ordering::Relaxed) as f64 / 1e9,
+            buckets,
+        }
+    }
+}
+
+/// Calls to embedding providers, shared by every
+/// [`GuardedProvider`](crate::vector::policy::GuardedProvider) a database creates
+pub struct EmbeddingMetrics {
+    pub(crate) calls: Histogram,
+    retries: AtomicU64,
+    rejections: AtomicU64,
+}
+
+impl EmbeddingMetrics {
+    pub(crate) fn new() -> Self {
+        Self {
+            calls: Histogram::new(),
+            retries: AtomicU64::new(0),
+            rejections: AtomicU64::new(0),
+        }
+    }
+
+    /// Count a call retried after a transient failure
+    pub(crate) fn record_retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a call refused while the circuit breaker was open
+    pub(crate) fn record_rejection(&self) {
+        self.rejections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn retries(&self) -> u64 {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn rejections(&self) -> u64 {
+        self.rejections.load(Ordering::Relaxed)
+    }
+}
+
+/// Live metrics for a database
+pub struct Metrics {
+    pub(crate) inserts: Histogram,
+    pub(crate) updates: Histogram,
+    pub(crate) deletes: Histogram,
+    pub(crate) finds: Histogram,
+    pub(crate) scans: Histogram,
+    pub(crate) vector_inserts: Histogram,
+    pub(crate) vector_searches: Histogram,
+    pub(crate) syncs: Histogram,
+    pub(crate) embeddings: Arc<EmbeddingMetrics>,
+    fsyncs: AtomicU64,
+}
+
+impl Metrics {
+    pub(crate) fn new() -> Self {
+        Self {
+            inserts: Histogram::new(),
+            updates: Histogram::new(),
+            deletes: Histogram::new(),
+            finds: Histogram::new(),
+            scans: Histogram::new(),
+            vector_inserts: Histogram::new(),
+            vector_searches: Histogram::new(),
+            syncs: Histogram::new(),
+            embeddings: Arc::new(EmbeddingMetrics::new()),
+            fsyncs: AtomicU64::new(0),
+        }
+    }
+
+    /// Count a completed `fsync` of the database or vector file
+    pub(crate) fn record_fsync(&self) {
+        self.fsyncs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn operations(&self) -> Vec<OperationMetrics> {
+        vec![
+            self.inserts.snapshot("insert"),
+            self.updates.snapshot("update"),
+            self.deletes.snapshot("delete"),
+            self.finds.snapshot("find"),
+            self.scans.snapshot("scan"),
+            self.vector_inserts.snapshot("vector_insert"),
+            self.vector_searches.snapshot("vector_search"),
+            self.syncs.snapshot("sync"),
+            self.embeddings.calls.snapshot("embed"),
+        ]
+    }
+
+    pub(crate) fn fsyncs(&self) -> u64 {
+        self.fsyncs.load(Ordering::Relaxed)
+    }
+}
+
+/// Counters and latency distribution for one kind of operation
+#[derive(Debug, Clone, Serialize)]
+pub struct OperationMetrics {
+    pub operation: &'static str,
+    pub count: u64,
+    pub errors: u64,
+    pub total_seconds: f64,
+    /// Cumulative counts: `(upper bound in seconds, operations at most that slow)`
+    pub buckets: Vec<(f64, u64)>,
+}
+
+/// Point-in-time copy of a database's metrics
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsSnapshot {
+    pub operations: Vec<OperationMetrics>,
+    pub cache: CacheStats,
+    pub fsyncs: u64,
+    /// Embedding provider calls retried after a rate limit or other
+    /// transient failure
+    pub embedding_retries: u64,
+    /// Embedding provider calls refused while the circuit breaker was open
+    pub embedding_rejections: u64,
+    pub collections: usize,
+    pub documents: usize,
+    pub vector_collections: usize,
+    pub vectors: usize,
+}
+
+impl MetricsSnapshot {
+    /// Metrics for one operation (`"insert"`, `"vector_search"`, ...)
+    pub fn operation(&self, name: &str) -> Option<&OperationMetrics> {
+        self.operations.iter().find(|o| o.operation == name)
+    }
+
+    /// Render in the Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP keradb_operation_duration_seconds Latency of database operations\n");
+        out.push_str("# TYPE keradb_operation_duration_seconds histogram\n");
+        for op in &self.operations {
+            for (le, count) in &op.buckets {
+                let _ = writeln!(out, "keradb_operation_duration_seconds_bucket{{op=\"{}\",le=\"{}\"}} {}", op.operation, le, count);
+            }
+            let _ = writeln!(out, "keradb_operation_duration_seconds_bucket{{op=\"{}\",le=\"+Inf\"}} {}", op.operation, op.count);
+            let _ = writeln!(out, "keradb_operation_duration_seconds_sum{{op=\"{}\"}} {}", op.operation, op.total_seconds);
+            let _ = writeln!(out, "keradb_operation_duration_seconds_count{{op=\"{}\"}} {}", op.operation, op.count);
+        }
+
+        out.push_str("# HELP keradb_operation_errors_total Database operations that returned an error\n");
+        out.push_str("# TYPE keradb_operation_errors_total counter\n");
+        for op in &self.operations {
+            let _ = writeln!(out, "keradb_operation_errors_total{{op=\"{}\"}} {}", op.operation, op.errors);
+        }
+
+        let scalars: [(&str, &str, &str, f64); 10] = [
+            ("keradb_cache_hits_total", "counter", "Page cache hits", self.cache.hits as f64),
+            ("keradb_cache_misses_total", "counter", "Page cache misses", self.cache.misses as f64),
+            ("keradb_cache_pages", "gauge", "Pages held in the page cache", self.cache.pages as f64),
+            ("keradb_fsyncs_total", "counter", "fsync calls on the database and vector files", self.fsyncs as f64),
+            ("keradb_embedding_retries_total", "counter", "Embedding provider calls retried", self.embedding_retries as f64),
+            ("keradb_embedding_rejections_total", "counter", "Embedding provider calls refused by the circuit breaker", self.embedding_rejections as f64),
+            ("keradb_collections", "gauge", "Document collections", self.collections as f64),
+            ("keradb_documents", "gauge", "Documents across all collections", self.documents as f64),
+            ("keradb_vector_collections", "gauge", "Vector collections", self.vector_collections as f64),
+            ("keradb_vectors", "gauge", "Vectors across all vector collections", self.vectors as f64),
+        ];
+        for (name, kind, help, value) in scalars {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}\n{} {}", name, help, name, kind, name, value);
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Database;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_operations_are_counted() {
+        let dir = tempdir().unwrap();
+        let db = Database::create(dir.path().join("test.ndb")).unwrap();
+        let id = db.insert("users", json!({"name": "Alice"})).unwrap();
+        db.find_by_id("users", &id).unwrap();
+        assert!(db.find_by_id("users", "missing").is_err());
+        db.sync().unwrap();
+
+        let metrics = db.metrics();
+        assert_eq!(metrics.operation("insert").unwrap().count, 1);
+        let finds = metrics.operation("find").unwrap();
+        assert_eq!((finds.count, finds.errors), (2, 1));
+        assert_eq!(finds.buckets.last().unwrap().1, 2);
+        assert_eq!(metrics.documents, 1);
+        assert!(metrics.fsyncs >= 1);
+
+        let text = metrics.to_prometheus();
+        assert!(text.contains("keradb_operation_duration_seconds_count{op=\"insert\"} 1"));
+        assert!(text.contains("keradb_operation_errors_total{op=\"find\"} 1"));
+        assert!(text.contains("# TYPE keradb_documents gauge\nkeradb_documents 1"));
+    }
+}
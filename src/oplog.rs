@@ -0,0 +1,325 @@
+//! Operation log and change streams
+//!
+//! Every document write is appended to an in-memory [`Oplog`] with a
+//! monotonically increasing sequence number. [`ChangeStream`]s tail the log,
+//! so applications can react to inserts, updates and deletes without polling.
+//!
+//! The log is bounded by [`Config::oplog_capacity`](crate::types::Config);
+//! a stream that falls further behind than that fails with
+//! [`KeraDBError::OplogTruncated`] rather than silently missing changes.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let mut changes = db.watch("users");
+//! std::thread::spawn(move || {
+//!     for event in changes {
+//!         let event = event?;
+//!         println!("{:?} {} in {}", event.operation, event.doc_id, event.collection);
+//!     }
+//! });
+//! ```
+
+use crate::error::{KeraDBError, Result};
+use crate::types::{Document, DocumentId};
+use crate::Database;
+use parking_lot::{Condvar, Mutex};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Kind of write recorded in the oplog
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationType {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// A single change to a document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChangeEvent {
+    /// Position in the oplog; pass to [`Oplog::watch`] to resume after this event
+    pub seq: u64,
+    pub operation: OperationType,
+    pub collection: String,
+    pub doc_id: DocumentId,
+    /// The document after the change, or the removed document for deletes
+    pub document: Value,
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+impl ChangeEvent {
+    /// The event for a write of `document`, timestamped now
+    pub(crate) fn new(seq: u64, operation: OperationType, collection: &str, document: &Document) -> Self {
+        let payload = document.to_value();
+        Self {
+            seq,
+            operation,
+            collection: collection.to_string(),
+            doc_id: document.id.clone(),
+            document: payload,
+            // chrono rather than SystemTime, which panics in browsers
+            timestamp: chrono::Utc::now().timestamp_millis().max(0) as u64,
+        }
+    }
+}
+
+struct OplogState {
+    entries: VecDeque<ChangeEvent>,
+    /// Sequence number the next appended event will get
+    next_seq: u64,
+    closed: bool,
+}
+
+impl OplogState {
+    /// Sequence number of the oldest event still held
+    fn first_seq(&self) -> u64 {
+        self.entries.front().map_or(self.next_seq, |e| e.seq)
+    }
+}
+
+/// Bounded in-memory log of document changes
+pub struct Oplog {
+    /// Identifies this log instance; sequence numbers restart with a new epoch
+    epoch: String,
+    state: Mutex<OplogState>,
+    appended: Condvar,
+    capacity: usize,
+}
+
+impl Oplog {
+    /// Create an empty log holding at most `capacity` events
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            epoch: uuid::Uuid::new_v4().to_string(),
+            state: Mutex::new(OplogState { entries: VecDeque::new(), next_seq: 1, closed: false }),
+            appended: Condvar::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Record a change and wake any waiting streams
+    pub(crate) fn append(&self, operation: OperationType, collection: &str, document: &Document) -> u64 {
+        let mut event = ChangeEvent::new(0, operation, collection, document);
+
+        let mut state = self.state.lock();
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        event.seq = seq;
+        state.entries.push_back(event);
+        while state.entries.len() > self.capacity {
+            state.entries.pop_front();
+        }
+        drop(state);
+
+        self.appended.notify_all();
+        seq
+    }
+
+    /// Identifier of this log instance
+    ///
+    /// Sequence numbers are only meaningful within one epoch: reopening the
+    /// database starts a new log from 1.
+    pub fn epoch(&self) -> &str {
+        &self.epoch
+    }
+
+    /// Whether every event after `seq` is still retained
+    pub fn can_resume_from(&self, seq: u64) -> bool {
+        let state = self.state.lock();
+        seq + 1 >= state.first_seq() && seq < state.next_seq
+    }
+
+    /// Sequence number of the most recent event, or 0 if nothing was logged
+    pub fn latest_seq(&self) -> u64 {
+        self.state.lock().next_seq - 1
+    }
+
+    /// All retained events after `seq`
+    ///
+    /// Fails with [`KeraDBError::OplogTruncated`] if events after `seq` have
+    /// already been discarded.
+    pub fn since(&self, seq: u64) -> Result<Vec<ChangeEvent>> {
+        let state = self.state.lock();
+        if seq + 1 < state.first_seq() {
+            return Err(KeraDBError::OplogTruncated(seq));
+        }
+        Ok(state.entries.iter().filter(|e| e.seq > seq).cloned().collect())
+    }
+
+    /// Stream events after `seq`, optionally only those for one collection
+    pub fn watch(self: &Arc<Self>, collection: Option<&str>, seq: u64) -> ChangeStream {
+        ChangeStream { oplog: self.clone(), collection: collection.map(str::to_string), position: seq }
+    }
+
+    /// End all streams once they have drained the log
+    pub(crate) fn close(&self) {
+        self.state.lock().closed = true;
+        self.appended.notify_all();
+    }
+
+    /// Next event after `*position` matching `collection`, waiting as `wait` says
+    fn next_after(&self, position: &mut u64, collection: Option<&str>, wait: Wait) -> Result<Option<ChangeEvent>> {
+        let mut state = self.state.lock();
+        loop {
+            let first_seq = state.first_seq();
+            if *position + 1 < first_seq {
+                return Err(KeraDBError::OplogTruncated(*position));
+            }
+
+            let start = (*position + 1 - first_seq) as usize;
+            for event in state.entries.iter().skip(start) {
+                *position = event.seq;
+                if collection.is_none_or(|c| c == event.collection) {
+                    return Ok(Some(event.clone()));
+                }
+            }
+
+            if state.closed {
+                return Ok(None);
+            }
+            match wait {
+                Wait::Never => return Ok(None),
+                Wait::Until(deadline) => {
+                    if self.appended.wait_until(&mut state, deadline).timed_out() {
+                        return Ok(None);
+                    }
+                }
+                Wait::Forever => self.appended.wait(&mut state),
+            }
+        }
+    }
+}
+
+/// How long [`Oplog::next_after`] waits for a matching change
+///
+/// `Never` is separate from a deadline of now so that polling does not read
+/// the clock, which is unavailable in browsers.
+enum Wait {
+    Never,
+    Until(Instant),
+    Forever,
+}
+
+/// Insert or replace a document by its `_id`
+pub(crate) fn upsert_document(db: &Database, collection: &str, mut document: Value) -> Result<()> {
+    let id = match document.get("_id") {
+        Some(Value::String(id)) => id.clone(),
+        _ => return Err(KeraDBError::InvalidDocument("Document has no string _id".to_string())),
+    };
+    if db.find_by_id(collection, &id).is_ok() {
+        if let Value::Object(ref mut map) = document {
+            map.remove("_id");
+        }
+        db.update(collection, &id, document)?;
+    } else {
+        db.insert(collection, document)?;
+    }
+    Ok(())
+}
+
+/// Replay a change on another database
+///
+/// Replaying is idempotent, so a change may safely be applied more than once.
+pub(crate) fn apply_change(db: &Database, event: &ChangeEvent) -> Result<()> {
+    match event.operation {
+        OperationType::Insert | OperationType::Update => upsert_document(db, &event.collection, event.document.clone()),
+        OperationType::Delete => match db.delete(&event.collection, &event.doc_id) {
+            Ok(_) | Err(KeraDBError::DocumentNotFound(_)) => Ok(()),
+            Err(e) => Err(e),
+        },
+    }
+}
+
+/// Iterator over changes, blocking until the next one arrives
+///
+/// Iteration ends when the database is dropped. Use [`try_next`](Self::try_next)
+/// or [`next_timeout`](Self::next_timeout) to avoid blocking indefinitely.
+pub struct ChangeStream {
+    oplog: Arc<Oplog>,
+    collection: Option<String>,
+    position: u64,
+}
+
+impl ChangeStream {
+    /// Sequence number of the last event consumed, usable to resume later
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Return the next change if one is already available
+    pub fn try_next(&mut self) -> Result<Option<ChangeEvent>> {
+        self.oplog.next_after(&mut self.position, self.collection.as_deref(), Wait::Never)
+    }
+
+    /// Wait up to `timeout` for the next change
+    pub fn next_timeout(&mut self, timeout: Duration) -> Result<Option<ChangeEvent>> {
+        self.oplog.next_after(&mut self.position, self.collection.as_deref(), Wait::Until(Instant::now() + timeout))
+    }
+}
+
+impl Iterator for ChangeStream {
+    type Item = Result<ChangeEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.oplog.next_after(&mut self.position, self.collection.as_deref(), Wait::Forever).transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Config;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_watch_collection_changes() {
+        let dir = tempdir().unwrap();
+        let db = Database::create(dir.path().join("test.ndb")).unwrap();
+        db.insert("users", json!({"name": "before"})).unwrap();
+
+        let mut changes = db.watch("users");
+        let id = db.insert("users", json!({"name": "Alice"})).unwrap();
+        db.insert("orders", json!({"total": 5})).unwrap();
+        db.update("users", &id, json!({"name": "Alicia"})).unwrap();
+        db.delete("users", &id).unwrap();
+
+        let events: Vec<ChangeEvent> = (0..3).map(|_| changes.try_next().unwrap().unwrap()).collect();
+        let ops: Vec<OperationType> = events.iter().map(|e| e.operation).collect();
+        assert_eq!(ops, [OperationType::Insert, OperationType::Update, OperationType::Delete]);
+        assert!(events.iter().all(|e| e.doc_id == id && e.collection == "users"));
+        assert_eq!(events[1].document, json!({"_id": id, "name": "Alicia"}));
+        assert!(changes.try_next().unwrap().is_none());
+        assert_eq!(changes.position(), db.oplog().latest_seq());
+
+        // Blocking iteration picks up writes from another thread
+        let db = Arc::new(db);
+        let writer = db.clone();
+        let handle = std::thread::spawn(move || writer.insert("users", json!({"name": "Bob"})).unwrap());
+        let event = changes.next().unwrap().unwrap();
+        assert_eq!(event.doc_id, handle.join().unwrap());
+    }
+
+    #[test]
+    fn test_lagging_stream_reports_truncation() {
+        let dir = tempdir().unwrap();
+        let config = Config { oplog_capacity: 4, ..Config::default() };
+        let db = Database::create_with_config(dir.path().join("test.ndb"), config).unwrap();
+
+        let mut changes = db.watch_all();
+        for i in 0..10 {
+            db.insert("items", json!({"n": i})).unwrap();
+        }
+        assert!(matches!(changes.try_next(), Err(KeraDBError::OplogTruncated(0))));
+
+        let mut resumed = db.oplog().watch(None, 6);
+        assert_eq!(resumed.try_next().unwrap().unwrap().seq, 7);
+        assert_eq!(db.oplog().since(8).unwrap().len(), 2);
+    }
+}
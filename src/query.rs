@@ -0,0 +1,599 @@
+//! `find` queries and how they are planned
+//!
+//! A [`FindQuery`] reaches its documents one of four ways, chosen when it
+//! is [prepared](crate::Database::prepare) and shown by
+//! [`Database::explain`](crate::Database::explain):
+//!
+//! - the **id index**, when the query names a document or filters on
+//!   `_id = <id>`;
+//! - a **column index**, when the collection's
+//!   [column projection](crate::Database::create_columns) holds every
+//!   filtered field, so the filter runs on the projected values and only the
+//!   matching documents are read;
+//! - a **multikey index**, when an `=`, `in` or `contains` condition is on a
+//!   field with a [multikey index](crate::Database::create_multikey_index),
+//!   so only the documents holding one of the values are read;
+//! - a **full scan** of the collection otherwise.
+//!
+//! Sorted queries with a limit keep only the first `skip + limit` documents
+//! instead of sorting everything that matched.
+//!
+//! Strings compare under the filter's [`Collation`] if it names one, and
+//! otherwise under the collation of the column index holding the field, if
+//! any, so `where email = Alice@Example.com collate nocase` matches
+//! `alice@example.com`.
+
+use crate::advisor::SuggestedIndex;
+use crate::aggregate::Row;
+use crate::collation::Collation;
+use crate::error::{KeraDBError, Result};
+use crate::execution::Executor;
+use crate::types::{Document, DocumentId, ScanOptions};
+use crate::vector::{compare_values, FilterCondition, MetadataFilter};
+use crate::Database;
+use serde::Serialize;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::fmt;
+
+/// A `find` query
+#[derive(Debug, Clone, PartialEq)]
+pub struct FindQuery {
+    pub collection: String,
+    pub id: Option<String>,
+    pub filter: Filter,
+    /// Field to sort by, and whether to sort descending
+    pub sort: Option<(String, bool)>,
+    pub limit: Option<usize>,
+    pub skip: usize,
+}
+
+impl FindQuery {
+    /// Run the query
+    ///
+    /// Without a sort, documents come back in insertion order. To run the
+    /// same query many times, [prepare](crate::Database::prepare) it once.
+    pub fn run(&self, db: &Database) -> Result<Vec<Document>> {
+        PreparedQuery::new(self.clone(), &db.executor).run(db)
+    }
+}
+
+/// Where a prepared query reads its documents from
+#[derive(Debug, Clone, PartialEq)]
+enum Access {
+    /// The document with this ID, named by the query or by an `_id = <id>` condition
+    Id(String),
+    /// The collection's column projection, which held every filtered field
+    /// when the query was prepared
+    Columns,
+    /// The multikey index of the field of this condition
+    Multikey(usize),
+    Scan,
+}
+
+/// A field path, split once
+type Path = Vec<String>;
+
+/// A filter condition with its path split and its operand folded under the
+/// collation it compares with
+#[derive(Debug, Clone)]
+struct Condition {
+    field: String,
+    path: Path,
+    condition: FilterCondition,
+    collation: Collation,
+}
+
+impl Condition {
+    fn matches(&self, value: Option<&Value>) -> bool {
+        self.condition.matches(value.map(|value| self.collation.fold(value)).as_deref())
+    }
+}
+
+/// Compile a filter's conditions, given the collation of the column index
+/// holding each field
+fn compile(filter: &Filter, index_collation: impl Fn(&str) -> Option<Collation>) -> Vec<Condition> {
+    filter
+        .conditions
+        .iter()
+        .map(|(field, condition)| {
+            let collation = filter.collation.or_else(|| index_collation(field)).unwrap_or_default();
+            Condition {
+                field: field.clone(),
+                path: split(field),
+                condition: collation.fold_condition(condition),
+                collation,
+            }
+        })
+        .collect()
+}
+
+fn split(field: &str) -> Path {
+    field.split('.').map(str::to_string).collect()
+}
+
+/// A [`FindQuery`] with its filter compiled and its access path chosen, to
+/// be run many times; see [`Database::prepare`](crate::Database::prepare)
+///
+/// The access path and collations are chosen when the query is prepared: a
+/// query prepared before its collection's columns are projected goes on
+/// scanning, and one whose columns are dropped falls back to scanning.
+#[derive(Debug, Clone)]
+pub struct PreparedQuery {
+    query: FindQuery,
+    conditions: Vec<Condition>,
+    sort: Option<(Path, bool, Collation)>,
+    access: Access,
+}
+
+impl PreparedQuery {
+    pub(crate) fn new(query: FindQuery, executor: &Executor) -> Self {
+        let index_collation = |field: &str| executor.field_collation(&query.collection, field);
+        let id = query.id.clone().or_else(|| {
+            query.filter.conditions.iter().find_map(|(field, condition)| match condition {
+                FilterCondition::Eq(Value::String(id)) if field == "_id" => Some(id.clone()),
+                _ => None,
+            })
+        });
+        let fields: Vec<&str> = query.filter.conditions.iter().map(|(field, _)| field.as_str()).collect();
+        let conditions = compile(&query.filter, index_collation);
+        // The index holds values as written, so only byte-for-byte conditions can use it
+        let multikey = conditions.iter().position(|condition| {
+            condition.collation == Collation::Binary
+                && executor.multikey_narrows(&query.collection, &condition.field, &condition.condition)
+        });
+        let access = match (id, multikey) {
+            (Some(id), _) => Access::Id(id),
+            _ if !fields.is_empty() && executor.columns_cover(&query.collection, &fields) => Access::Columns,
+            (None, Some(condition)) => Access::Multikey(condition),
+            (None, None) => Access::Scan,
+        };
+        let sort = query.sort.as_ref().map(|(field, descending)| {
+            let collation = query.filter.collation.or_else(|| index_collation(field)).unwrap_or_default();
+            (split(field), *descending, collation)
+        });
+        Self { conditions, sort, access, query }
+    }
+
+    /// The query as written
+    pub fn query(&self) -> &FindQuery {
+        &self.query
+    }
+
+    /// Run the query; see [`FindQuery::run`]
+    pub fn run(&self, db: &Database) -> Result<Vec<Document>> {
+        let query = &self.query;
+        let limit = query.limit.unwrap_or(usize::MAX);
+        let scan_all = || db.scan(&query.collection, &ScanOptions::default().with_ordered(true));
+        let docs = match &self.access {
+            Access::Id(id) => match db.find_by_id(&query.collection, id) {
+                Ok(doc) if query.id.is_some() => {
+                    return Ok(if self.matches(&doc.to_value()) { vec![doc] } else { Vec::new() });
+                }
+                Ok(doc) => vec![doc],
+                Err(KeraDBError::DocumentNotFound(_)) if query.id.is_none() => Vec::new(),
+                Err(e) => return Err(e),
+            },
+            Access::Columns => {
+                let selected = db.executor.select_columns(&query.collection, &self.fields(), |row| self.matches_row(row));
+                match selected {
+                    Some(ids) => db.executor.find_many(&query.collection, &ids),
+                    None => scan_all()?,
+                }
+            }
+            Access::Multikey(condition) => match self.multikey_candidates(&db.executor, *condition) {
+                Some(ids) => db.executor.find_many(&query.collection, &ids),
+                None => scan_all()?,
+            },
+            Access::Scan if self.conditions.is_empty() && self.sort.is_none() => {
+                let options = ScanOptions::default().with_ordered(true).with_skip(query.skip).with_limit(limit);
+                return db.scan(&query.collection, &options);
+            }
+            Access::Scan => scan_all()?,
+        };
+
+        // Indexed values can change before their documents are read, so the
+        // filter is checked again
+        let examined = docs.len();
+        let mut docs: Vec<(Value, Document)> = docs
+            .into_iter()
+            .map(|doc| (doc.to_value(), doc))
+            .filter(|(value, _)| self.matches(value))
+            .collect();
+        if self.access == Access::Scan && !self.conditions.is_empty() {
+            let index = suggested_index(&self.conditions, docs.iter().map(|(value, _)| value));
+            db.advisor.record(&query.collection, index, examined, docs.len());
+        }
+        if let Some((path, descending, collation)) = &self.sort {
+            let compare = |a: &Value, b: &Value| {
+                let (a, b) = (follow(a, path).map(|a| collation.fold(a)), follow(b, path).map(|b| collation.fold(b)));
+                let order = compare_fields(a.as_deref(), b.as_deref());
+                if *descending { order.reverse() } else { order }
+            };
+            match query.limit {
+                None => docs.sort_by(|(a, _), (b, _)| compare(a, b)),
+                Some(_) => {
+                    // Only the first `skip + limit` are sorted; ties keep
+                    // their order, as with a full stable sort
+                    let k = query.skip.saturating_add(limit);
+                    let mut ranked: Vec<(usize, (Value, Document))> = docs.into_iter().enumerate().collect();
+                    let order = |(i, (a, _)): &(usize, (Value, Document)), (j, (b, _)): &(usize, (Value, Document))| {
+                        compare(a, b).then(i.cmp(j))
+                    };
+                    if k < ranked.len() {
+                        if k > 0 {
+                            ranked.select_nth_unstable_by(k - 1, order);
+                        }
+                        ranked.truncate(k);
+                    }
+                    ranked.sort_by(order);
+                    docs = ranked.into_iter().map(|(_, doc)| doc).collect();
+                }
+            }
+        }
+        Ok(docs.into_iter().map(|(_, doc)| doc).skip(query.skip).take(limit).collect())
+    }
+
+    /// The filtered fields
+    fn fields(&self) -> Vec<&str> {
+        self.query.filter.conditions.iter().map(|(field, _)| field.as_str()).collect()
+    }
+
+    /// IDs the multikey index of a condition's field gives, or `None` if
+    /// the index was dropped
+    fn multikey_candidates(&self, executor: &Executor, condition: usize) -> Option<Vec<DocumentId>> {
+        let condition = &self.conditions[condition];
+        executor.multikey_candidates(&self.query.collection, &condition.field, &condition.condition)
+    }
+
+    fn matches(&self, document: &Value) -> bool {
+        self.conditions.iter().all(|condition| condition.matches(follow(document, &condition.path)))
+    }
+
+    fn matches_row<R: Row>(&self, row: &R) -> bool {
+        self.conditions.iter().all(|condition| condition.matches(row.get(&condition.field)))
+    }
+
+    /// How the query runs, with how many documents it would read now
+    pub(crate) fn explain(&self, executor: &Executor) -> QueryPlan {
+        let query = &self.query;
+        let selected = match self.access {
+            Access::Columns => executor.select_columns(&query.collection, &self.fields(), |row| self.matches_row(row)),
+            Access::Multikey(condition) => self.multikey_candidates(executor, condition),
+            _ => None,
+        };
+        let (access, documents_examined) = match (&self.access, selected) {
+            (Access::Id(id), _) => {
+                let found = executor.contains(&query.collection, id);
+                (AccessPath::IdIndex { id: id.clone() }, usize::from(found))
+            }
+            (Access::Columns, Some(ids)) => {
+                let fields = executor.columns(&query.collection);
+                let collation = executor.column_collation(&query.collection).unwrap_or_default();
+                (AccessPath::ColumnIndex { fields, collation }, ids.len())
+            }
+            (Access::Multikey(condition), Some(ids)) => {
+                (AccessPath::MultikeyIndex { field: self.conditions[*condition].field.clone() }, ids.len())
+            }
+            _ => {
+                let count = executor.count(&query.collection);
+                let examined = match query.limit {
+                    Some(limit) if self.conditions.is_empty() && self.sort.is_none() => {
+                        count.min(query.skip.saturating_add(limit))
+                    }
+                    _ => count,
+                };
+                (AccessPath::FullScan, examined)
+            }
+        };
+
+        let sort = match (&query.sort, query.limit) {
+            (None, _) => SortStrategy::None,
+            (Some((field, descending)), None) => SortStrategy::Full { field: field.clone(), descending: *descending },
+            (Some((field, descending)), Some(limit)) => SortStrategy::TopK {
+                field: field.clone(),
+                descending: *descending,
+                k: query.skip.saturating_add(limit),
+            },
+        };
+        QueryPlan { collection: query.collection.clone(), access, documents_examined, sort }
+    }
+}
+
+/// The index that would spare a full scan for these conditions, judged by
+/// the documents that matched them: a multikey index if one can narrow
+/// down a condition on a field holding arrays, otherwise a column projection
+/// of every filtered field
+fn suggested_index<'a>(conditions: &[Condition], matched: impl Iterator<Item = &'a Value> + Clone) -> SuggestedIndex {
+    let multikey = conditions.iter().find(|condition| {
+        condition.collation == Collation::Binary
+            && matches!(condition.condition, FilterCondition::Eq(_) | FilterCondition::In(_) | FilterCondition::Contains(_))
+            && matched.clone().any(|doc| matches!(follow(doc, &condition.path), Some(Value::Array(_))))
+    });
+    match multikey {
+        Some(condition) => SuggestedIndex::Multikey { field: condition.field.clone() },
+        None => {
+            let mut fields: Vec<String> = conditions.iter().map(|condition| condition.field.clone()).collect();
+            fields.sort_unstable();
+            fields.dedup();
+            SuggestedIndex::Columns { fields }
+        }
+    }
+}
+
+/// Follow a field path
+fn follow<'a>(document: &'a Value, path: &[String]) -> Option<&'a Value> {
+    path.iter().try_fold(document, |value, key| value.get(key))
+}
+
+/// Documents with the field sort before documents without it; values that
+/// cannot be compared (e.g. a number and a string) keep their order
+fn compare_fields(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => compare_values(a, b).unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Conditions a document must all meet
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filter {
+    pub conditions: Vec<(String, FilterCondition)>,
+    /// How strings compare, here and when sorting a `find`; without one,
+    /// each field uses the collation of the column index holding it, if any
+    pub collation: Option<Collation>,
+}
+
+impl Filter {
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+    }
+
+    /// Whether a document meets every condition, comparing strings under
+    /// the filter's collation or else byte for byte
+    pub fn matches(&self, document: &Value) -> bool {
+        compile(self, |_| None).iter().all(|condition| condition.matches(follow(document, &condition.path)))
+    }
+
+    /// Count the documents in `collection` that match
+    pub fn count(&self, db: &Database, collection: &str) -> Result<usize> {
+        if self.is_empty() {
+            return Ok(db.count(collection));
+        }
+        let conditions = compile(self, |field| db.executor.field_collation(collection, field));
+        let docs = db.scan(collection, &ScanOptions::default())?;
+        let matched: Vec<Value> = docs
+            .iter()
+            .map(Document::to_value)
+            .filter(|doc| conditions.iter().all(|condition| condition.matches(follow(doc, &condition.path))))
+            .collect();
+        db.advisor.record(collection, suggested_index(&conditions, matched.iter()), docs.len(), matched.len());
+        Ok(matched.len())
+    }
+
+    /// The equivalent vector metadata filter, which allows one condition per
+    /// field and compares strings byte for byte
+    pub fn to_metadata_filter(&self) -> Result<MetadataFilter> {
+        if self.collation.is_some_and(|collation| collation != Collation::Binary) {
+            return Err(KeraDBError::InvalidQuery("Vector searches compare strings byte for byte".to_string()));
+        }
+        let mut filter = MetadataFilter::new();
+        for (field, condition) in &self.conditions {
+            if filter.filters.insert(field.clone(), condition.clone()).is_some() {
+                return Err(KeraDBError::InvalidQuery(format!(
+                    "Vector searches allow one condition per field; '{}' has several",
+                    field
+                )));
+            }
+        }
+        Ok(filter)
+    }
+}
+
+/// How a [`FindQuery`] would run; see [`Database::explain`](crate::Database::explain)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QueryPlan {
+    pub collection: String,
+    pub access: AccessPath,
+    /// Documents expected to be read from storage
+    pub documents_examined: usize,
+    pub sort: SortStrategy,
+}
+
+/// Where a query's documents come from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AccessPath {
+    /// One document, looked up by ID
+    IdIndex { id: String },
+    /// The documents whose projected fields match the filter
+    ColumnIndex { fields: Vec<String>, collation: Collation },
+    /// The documents holding a value the filter looks for in a field, or in
+    /// an array in it
+    MultikeyIndex { field: String },
+    /// Every document of the collection, in insertion order
+    FullScan,
+}
+
+/// How a query's results are ordered
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SortStrategy {
+    /// Left in insertion order
+    None,
+    /// Every matching document sorted
+    Full { field: String, descending: bool },
+    /// Only the first `k` (skip plus limit) documents kept and sorted
+    TopK { field: String, descending: bool, k: usize },
+}
+
+impl fmt::Display for QueryPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let direction = |descending: bool| if descending { "desc" } else { "asc" };
+        writeln!(f, "Collection: {}", self.collection)?;
+        match &self.access {
+            AccessPath::IdIndex { id } => writeln!(f, "Access:     id index ({})", id)?,
+            AccessPath::ColumnIndex { fields, collation: Collation::Binary } => {
+                writeln!(f, "Access:     column index on {}", fields.join(", "))?
+            }
+            AccessPath::ColumnIndex { fields, collation } => {
+                writeln!(f, "Access:     column index on {} ({})", fields.join(", "), collation)?
+            }
+            AccessPath::MultikeyIndex { field } => writeln!(f, "Access:     multikey index on {}", field)?,
+            AccessPath::FullScan => writeln!(f, "Access:     full scan")?,
+        }
+        writeln!(f, "Examines:   ~{} document(s)", self.documents_examined)?;
+        match &self.sort {
+            SortStrategy::None => writeln!(f, "Sort:       none (insertion order)"),
+            SortStrategy::Full { field, descending } => {
+                writeln!(f, "Sort:       in memory by {} {}", field, direction(*descending))
+            }
+            SortStrategy::TopK { field, descending, k } => {
+                writeln!(f, "Sort:       top {} by {} {}", k, field, direction(*descending))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_explain_access_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::create(dir.path().join("explain.ndb")).unwrap();
+        let mut ids = Vec::new();
+        for i in 0..50 {
+            ids.push(db.insert("users", json!({"name": format!("user{}", i), "age": 20 + i % 30, "team": i % 5})).unwrap());
+        }
+        let query = |id: Option<&str>, conditions: Vec<(&str, FilterCondition)>, sort: Option<(&str, bool)>, limit| {
+            FindQuery {
+                collection: "users".to_string(),
+                id: id.map(str::to_string),
+                filter: Filter {
+                    conditions: conditions.into_iter().map(|(f, c)| (f.to_string(), c)).collect(),
+                    collation: None,
+                },
+                sort: sort.map(|(f, d)| (f.to_string(), d)),
+                limit,
+                skip: 0,
+            }
+        };
+
+        let by_id = query(None, vec![("_id", FilterCondition::Eq(json!(ids[3])))], None, None);
+        let plan = db.explain(&by_id);
+        assert_eq!((plan.access, plan.documents_examined), (AccessPath::IdIndex { id: ids[3].clone() }, 1));
+        assert_eq!(by_id.run(&db).unwrap()[0].id, ids[3]);
+
+        let teams = query(None, vec![("team", FilterCondition::Eq(json!(2)))], Some(("age", true)), Some(3));
+        let plan = db.explain(&teams);
+        assert_eq!((plan.access.clone(), plan.documents_examined), (AccessPath::FullScan, 50));
+        assert_eq!(plan.sort, SortStrategy::TopK { field: "age".to_string(), descending: true, k: 3 });
+        let scanned = teams.run(&db).unwrap();
+
+        db.create_columns("users", &["team"]).unwrap();
+        let plan = db.explain(&teams);
+        assert_eq!(plan.access, AccessPath::ColumnIndex { fields: vec!["team".to_string()], collation: Collation::Binary });
+        assert_eq!(plan.documents_examined, 10);
+        assert!(plan.to_string().contains("column index on team"));
+        assert_eq!(teams.run(&db).unwrap(), scanned);
+        let ages: Vec<Value> = scanned.iter().map(|doc| doc.get("age").unwrap()).collect();
+        assert_eq!(ages, [json!(47), json!(42), json!(37)]);
+
+        let first = query(None, vec![], None, Some(5));
+        assert_eq!(db.explain(&first).documents_examined, 5);
+        let everyone = query(None, vec![], Some(("name", false)), None);
+        let plan = db.explain(&everyone);
+        assert_eq!((plan.access, plan.documents_examined), (AccessPath::FullScan, 50));
+        assert!(matches!(plan.sort, SortStrategy::Full { .. }));
+    }
+
+    #[test]
+    fn test_prepared_query() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::create(dir.path().join("prepared.ndb")).unwrap();
+        for i in 0..20 {
+            db.insert("events", json!({"kind": if i % 2 == 0 { "click" } else { "view" }, "at": {"ms": i}})).unwrap();
+        }
+        let clicks = FindQuery {
+            collection: "events".to_string(),
+            id: None,
+            filter: Filter { conditions: vec![("kind".to_string(), FilterCondition::Eq(json!("click")))], collation: None },
+            sort: Some(("at.ms".to_string(), true)),
+            limit: Some(3),
+            skip: 1,
+        };
+        let times = |docs: Vec<Document>| -> Vec<Value> { docs.iter().map(|doc| doc.get("at").unwrap()["ms"].clone()).collect() };
+
+        let scanning = db.prepare(clicks.clone());
+        db.create_columns("events", &["kind"]).unwrap();
+        let indexed = db.prepare(clicks.clone());
+        assert_eq!(indexed.query(), &clicks);
+        assert_eq!(indexed.access, Access::Columns);
+        assert_eq!(scanning.access, Access::Scan);
+
+        // Prepared queries see later writes
+        db.insert("events", json!({"kind": "click", "at": {"ms": 100}})).unwrap();
+        let expected = vec![json!(18), json!(16), json!(14)];
+        assert_eq!(times(indexed.run(&db).unwrap()), expected);
+        assert_eq!(times(scanning.run(&db).unwrap()), expected);
+        assert_eq!(times(clicks.run(&db).unwrap()), expected);
+
+        // Without its columns, a prepared query scans instead
+        db.drop_columns("events").unwrap();
+        db.create_columns("events", &["at.ms"]).unwrap();
+        assert_eq!(times(indexed.run(&db).unwrap()), expected);
+    }
+
+    #[test]
+    fn test_collations() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("collations.ndb");
+        let db = Database::create(&path).unwrap();
+        for (name, city) in [("émile", "Zürich"), ("Bob", "zurich"), ("alice", "ZURICH"), ("Émile", "Basel")] {
+            db.insert("people", json!({"name": name, "city": city})).unwrap();
+        }
+        let find = |condition: Option<(&str, FilterCondition)>, collation: Option<Collation>| FindQuery {
+            collection: "people".to_string(),
+            id: None,
+            filter: Filter {
+                conditions: condition.into_iter().map(|(f, c)| (f.to_string(), c)).collect(),
+                collation,
+            },
+            sort: Some(("name".to_string(), false)),
+            limit: None,
+            skip: 0,
+        };
+        let names = |query: &FindQuery, db: &Database| -> Vec<Value> {
+            query.run(db).unwrap().iter().map(|doc| doc.get("name").unwrap()).collect()
+        };
+
+        let zurich = |collation| find(Some(("city", FilterCondition::Eq(json!("zurich")))), collation);
+        assert_eq!(names(&zurich(None), &db), [json!("Bob")]);
+        assert_eq!(names(&zurich(Some(Collation::NoCase)), &db), [json!("alice"), json!("Bob")]);
+        assert_eq!(names(&zurich(Some(Collation::Unicode)), &db), [json!("alice"), json!("Bob"), json!("émile")]);
+        // Sorting follows the collation too
+        assert_eq!(names(&find(None, None), &db), [json!("Bob"), json!("alice"), json!("Émile"), json!("émile")]);
+        assert_eq!(names(&find(None, Some(Collation::Unicode)), &db), [json!("alice"), json!("Bob"), json!("émile"), json!("Émile")]);
+
+        // A collated column index sets the default for its fields, and is
+        // kept in the catalog
+        db.create_columns_with_collation("people", &["city"], Collation::Unicode).unwrap();
+        db.create_columns("people", &["name"]).unwrap();
+        db.sync().unwrap();
+        drop(db);
+        let db = Database::open(&path).unwrap();
+        assert_eq!(db.column_collation("people"), Some(Collation::Unicode));
+        assert_eq!(names(&zurich(None), &db), [json!("alice"), json!("Bob"), json!("émile")]);
+        assert_eq!(names(&zurich(Some(Collation::Binary)), &db), [json!("Bob")]);
+        assert!(db.explain(&zurich(None)).to_string().contains("column index on city, name (unicode)"));
+        let count = Filter { conditions: vec![("city".to_string(), FilterCondition::StartsWith("zu".to_string()))], collation: None };
+        assert_eq!(count.count(&db, "people").unwrap(), 3);
+        assert!(count.to_metadata_filter().is_ok());
+        assert!(Filter { collation: Some(Collation::NoCase), ..count }.to_metadata_filter().is_err());
+    }
+}
@@ -0,0 +1,421 @@
+//! Asynchronous leader-follower replication
+//!
+//! A [`ReplicationLeader`] streams its [oplog](crate::oplog) to followers over
+//! TCP as newline-delimited JSON. A [`ReplicationFollower`] connects, applies
+//! each change to its own database, and reconnects after failures.
+//!
+//! A follower that is new, was restarted, or has fallen further behind than
+//! the leader's oplog capacity is bootstrapped with a full snapshot of the
+//! leader's documents before tailing resumes. Applying changes is idempotent,
+//! so writes that race with the snapshot are safe to replay.
+//!
+//! Only documents are replicated; vector collections are not. Replication is
+//! asynchronous: a follower may lag the leader, and writes made directly on a
+//! follower are overwritten when the leader changes the same document. To fail
+//! over, [`stop`](ReplicationFollower::stop) the follower and start accepting
+//! writes on it.
+//!
+//! A leader given a secret refuses followers that do not present the same
+//! one in their hello, before sending them anything. The secret travels in
+//! the clear, so the connection should still stay on a trusted network.
+//!
+//! # Example
+//!
+//! ```ignore
+//! // Leader
+//! let leader = ReplicationLeader::bind(db.clone(), "0.0.0.0:7700")?.with_secret("s3cret");
+//! std::thread::spawn(move || leader.run());
+//!
+//! // Follower
+//! let follower = ReplicationFollower::new(replica.clone(), "leader:7700").with_secret("s3cret");
+//! follower.run()?;
+//! ```
+
+use crate::error::{KeraDBError, Result};
+use crate::oplog::{apply_change, upsert_document, ChangeEvent, ChangeStream};
+use crate::Database;
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A line of the replication protocol
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message {
+    /// Follower to leader: the last change applied, if any, and the
+    /// replication secret
+    Hello {
+        epoch: Option<String>,
+        since: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        secret: Option<String>,
+    },
+    /// Leader to follower: why it will not be served
+    Refused { reason: String },
+    SnapshotBegin { epoch: String, seq: u64 },
+    Document { collection: String, document: Value },
+    SnapshotEnd { seq: u64 },
+    Change(ChangeEvent),
+    /// Sent when idle so followers can detect dead connections and track lag
+    Heartbeat { seq: u64 },
+}
+
+fn send(writer: &mut impl Write, message: &Message) -> Result<()> {
+    serde_json::to_writer(&mut *writer, message)?;
+    writer.write_all(b"\n")?;
+    Ok(())
+}
+
+/// Read the next message, or `None` at end of stream
+fn receive(reader: &mut impl BufRead) -> Result<Option<Message>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(&line)?))
+}
+
+/// Compare two byte strings without short-circuiting on the first difference
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Serves the oplog to followers
+pub struct ReplicationLeader {
+    db: Arc<Database>,
+    listener: TcpListener,
+    heartbeat: Duration,
+    secret: Option<String>,
+}
+
+impl ReplicationLeader {
+    /// Listen for followers on `addr`
+    pub fn bind(db: Arc<Database>, addr: &str) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        Ok(Self { db, listener, heartbeat: Duration::from_secs(1), secret: None })
+    }
+
+    /// Serve only followers presenting `secret`
+    pub fn with_secret(mut self, secret: impl Into<String>) -> Self {
+        self.secret = Some(secret.into());
+        self
+    }
+
+    /// Set how often idle connections are sent a heartbeat
+    pub fn with_heartbeat(mut self, heartbeat: Duration) -> Self {
+        self.heartbeat = heartbeat;
+        self
+    }
+
+    /// The address the leader is listening on
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Accept followers until the listener fails, one thread per follower
+    pub fn run(&self) -> Result<()> {
+        tracing::info!("Replication leader listening on {}", self.local_addr()?);
+        std::thread::scope(|scope| {
+            for stream in self.listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        scope.spawn(move || {
+                            let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+                            match self.serve_follower(stream) {
+                                Ok(()) => tracing::info!("Follower {} disconnected", peer),
+                                Err(e) => tracing::warn!("Follower {} dropped: {}", peer, e),
+                            }
+                        });
+                    }
+                    Err(e) => tracing::warn!("Failed to accept follower: {}", e),
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn serve_follower(&self, stream: TcpStream) -> Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+        let oplog = self.db.oplog();
+
+        let (epoch, since, secret) = match receive(&mut reader)? {
+            Some(Message::Hello { epoch, since, secret }) => (epoch, since, secret),
+            Some(other) => return Err(KeraDBError::InvalidFormat(format!("Expected hello, got {:?}", other))),
+            None => return Ok(()),
+        };
+        if let Some(expected) = &self.secret {
+            if !secret.is_some_and(|secret| constant_time_eq(secret.as_bytes(), expected.as_bytes())) {
+                send(&mut writer, &Message::Refused { reason: "wrong or missing replication secret".to_string() })?;
+                writer.flush()?;
+                return Err(KeraDBError::InvalidQuery("Follower presented a wrong or missing secret".to_string()));
+            }
+        }
+
+        let resumable = epoch.as_deref() == Some(oplog.epoch()) && oplog.can_resume_from(since);
+        let mut changes = if resumable {
+            tracing::info!("Follower resuming after change {}", since);
+            oplog.watch(None, since)
+        } else {
+            self.send_snapshot(&mut writer)?
+        };
+        writer.flush()?;
+
+        loop {
+            match changes.next_timeout(self.heartbeat)? {
+                Some(event) => send(&mut writer, &Message::Change(event))?,
+                None => send(&mut writer, &Message::Heartbeat { seq: oplog.latest_seq() })?,
+            }
+            writer.flush()?;
+        }
+    }
+
+    /// Send every document, returning a stream positioned at the snapshot
+    fn send_snapshot(&self, writer: &mut impl Write) -> Result<ChangeStream> {
+        let oplog = self.db.oplog();
+        // Changes made while the snapshot is read are replayed afterwards
+        let seq = oplog.latest_seq();
+        let changes = oplog.watch(None, seq);
+        tracing::info!("Sending snapshot at change {} to follower", seq);
+
+        send(writer, &Message::SnapshotBegin { epoch: oplog.epoch().to_string(), seq })?;
+        for (collection, _) in self.db.list_collections() {
+            for doc in self.db.find_all(&collection, None, None)? {
+                send(writer, &Message::Document { collection: collection.clone(), document: doc.to_value() })?;
+            }
+        }
+        send(writer, &Message::SnapshotEnd { seq })?;
+        Ok(changes)
+    }
+}
+
+/// Replication position: the leader's oplog epoch and last applied change
+#[derive(Debug, Clone, Default)]
+struct Position {
+    epoch: Option<String>,
+    seq: u64,
+}
+
+/// Applies a leader's changes to a local database
+pub struct ReplicationFollower {
+    db: Arc<Database>,
+    leader: String,
+    secret: Option<String>,
+    position: Mutex<Position>,
+    leader_seq: AtomicU64,
+    stopped: AtomicBool,
+    retry_interval: Duration,
+    read_timeout: Duration,
+}
+
+impl ReplicationFollower {
+    /// Create a follower of the leader at `leader` (`host:port`)
+    pub fn new(db: Arc<Database>, leader: impl Into<String>) -> Self {
+        Self {
+            db,
+            leader: leader.into(),
+            secret: None,
+            position: Mutex::new(Position::default()),
+            leader_seq: AtomicU64::new(0),
+            stopped: AtomicBool::new(false),
+            retry_interval: Duration::from_secs(1),
+            read_timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Present `secret` to a leader that requires one
+    pub fn with_secret(mut self, secret: impl Into<String>) -> Self {
+        self.secret = Some(secret.into());
+        self
+    }
+
+    /// Set the delay before reconnecting after a failure
+    pub fn with_retry_interval(mut self, interval: Duration) -> Self {
+        self.retry_interval = interval;
+        self
+    }
+
+    /// Set how long to wait for any message (including heartbeats) before
+    /// treating the connection as dead
+    pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+
+    /// Replicate until [`stop`](Self::stop) is called, reconnecting on failure
+    pub fn run(&self) -> Result<()> {
+        while !self.stopped.load(Ordering::Acquire) {
+            if let Err(e) = self.follow_once() {
+                if self.stopped.load(Ordering::Acquire) {
+                    break;
+                }
+                tracing::warn!("Replication from {} interrupted: {}", self.leader, e);
+                std::thread::sleep(self.retry_interval);
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop replicating, e.g. to promote this database to a leader
+    ///
+    /// [`run`](Self::run) returns once the current message has been applied.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Release);
+    }
+
+    /// Sequence number of the last leader change applied
+    pub fn position(&self) -> u64 {
+        self.position.lock().seq
+    }
+
+    /// Number of leader changes not yet applied, as of the last heartbeat
+    pub fn lag(&self) -> u64 {
+        self.leader_seq.load(Ordering::Relaxed).saturating_sub(self.position())
+    }
+
+    /// Connect once and apply changes until the connection ends or we are stopped
+    fn follow_once(&self) -> Result<()> {
+        let stream = TcpStream::connect(&self.leader)?;
+        stream.set_read_timeout(Some(self.read_timeout))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+
+        let Position { epoch, seq } = self.position.lock().clone();
+        send(&mut writer, &Message::Hello { epoch, since: seq, secret: self.secret.clone() })?;
+        writer.flush()?;
+        tracing::info!("Following {} from change {}", self.leader, seq);
+
+        while !self.stopped.load(Ordering::Acquire) {
+            match receive(&mut reader)? {
+                Some(message) => self.apply(message)?,
+                None => return Err(KeraDBError::StorageError("Leader closed the connection".to_string())),
+            }
+        }
+        Ok(())
+    }
+
+    fn apply(&self, message: Message) -> Result<()> {
+        match message {
+            Message::SnapshotBegin { epoch, seq } => {
+                // Forget the old position so an interrupted snapshot starts over
+                *self.position.lock() = Position::default();
+                self.clear_documents()?;
+                tracing::info!("Receiving snapshot {} at change {}", epoch, seq);
+                self.position.lock().epoch = Some(epoch);
+            }
+            Message::Document { collection, document } => upsert_document(&self.db, &collection, document)?,
+            Message::SnapshotEnd { seq } => {
+                self.position.lock().seq = seq;
+                self.leader_seq.fetch_max(seq, Ordering::Relaxed);
+            }
+            Message::Change(event) => {
+                apply_change(&self.db, &event)?;
+                self.position.lock().seq = event.seq;
+                self.leader_seq.fetch_max(event.seq, Ordering::Relaxed);
+            }
+            Message::Heartbeat { seq } => self.leader_seq.store(seq, Ordering::Relaxed),
+            Message::Hello { .. } => {
+                return Err(KeraDBError::InvalidFormat("Unexpected hello from leader".to_string()));
+            }
+            Message::Refused { reason } => {
+                return Err(KeraDBError::InvalidQuery(format!("Leader refused to replicate: {}", reason)));
+            }
+        }
+        Ok(())
+    }
+
+    fn clear_documents(&self) -> Result<()> {
+        for (collection, _) in self.db.list_collections() {
+            for doc in self.db.find_all(&collection, None, None)? {
+                self.db.delete(&collection, &doc.id)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::time::Instant;
+    use tempfile::tempdir;
+
+    fn wait_for(mut condition: impl FnMut() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !condition() {
+            assert!(Instant::now() < deadline, "timed out waiting for replication");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_follower_bootstraps_and_tails_leader() {
+        let dir = tempdir().unwrap();
+        let leader_db = Arc::new(Database::create(dir.path().join("leader.ndb")).unwrap());
+        let follower_db = Arc::new(Database::create(dir.path().join("follower.ndb")).unwrap());
+
+        let alice = leader_db.insert("users", json!({"name": "Alice"})).unwrap();
+        leader_db.insert("users", json!({"name": "Bob"})).unwrap();
+        follower_db.insert("stale", json!({"gone": true})).unwrap();
+
+        let leader = ReplicationLeader::bind(leader_db.clone(), "127.0.0.1:0")
+            .unwrap()
+            .with_heartbeat(Duration::from_millis(20));
+        let addr = leader.local_addr().unwrap().to_string();
+        std::thread::spawn(move || leader.run());
+
+        let follower = Arc::new(ReplicationFollower::new(follower_db.clone(), addr));
+        let runner = follower.clone();
+        let handle = std::thread::spawn(move || runner.run());
+
+        wait_for(|| follower_db.count("users") == 2);
+        assert_eq!(follower_db.count("stale"), 0);
+
+        leader_db.update("users", &alice, json!({"name": "Alicia"})).unwrap();
+        let carol = leader_db.insert("users", json!({"name": "Carol"})).unwrap();
+        leader_db.delete("users", &carol).unwrap();
+
+        wait_for(|| follower.position() == leader_db.oplog().latest_seq());
+        assert_eq!(follower_db.find_by_id("users", &alice).unwrap().get("name"), Some(json!("Alicia")));
+        assert!(follower_db.find_by_id("users", &carol).is_err());
+        wait_for(|| follower.lag() == 0);
+
+        follower.stop();
+        handle.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_leader_refuses_followers_without_its_secret() {
+        let dir = tempdir().unwrap();
+        let leader_db = Arc::new(Database::create(dir.path().join("leader.ndb")).unwrap());
+        let follower_db = Arc::new(Database::create(dir.path().join("follower.ndb")).unwrap());
+        leader_db.insert("users", json!({"name": "Alice"})).unwrap();
+
+        let leader = ReplicationLeader::bind(leader_db.clone(), "127.0.0.1:0").unwrap().with_secret("s3cret");
+        let addr = leader.local_addr().unwrap().to_string();
+        std::thread::spawn(move || leader.run());
+
+        for follower in [
+            ReplicationFollower::new(follower_db.clone(), addr.clone()),
+            ReplicationFollower::new(follower_db.clone(), addr.clone()).with_secret("guess"),
+        ] {
+            let err = follower.follow_once().unwrap_err();
+            assert!(err.to_string().contains("refused"), "{}", err);
+        }
+        assert_eq!(follower_db.count("users"), 0);
+
+        let follower = Arc::new(ReplicationFollower::new(follower_db.clone(), addr).with_secret("s3cret"));
+        let runner = follower.clone();
+        let handle = std::thread::spawn(move || runner.run());
+        wait_for(|| follower_db.count("users") == 1);
+        follower.stop();
+        handle.join().unwrap().unwrap();
+    }
+}
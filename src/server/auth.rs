@@ -0,0 +1,495 @@
+//! Users, permissions and audit logging for the servers
+//!
+//! An [`AccessControl`] keeps server users in a system database (by default
+//! the per-user `~/.keradb/.keradb_system.db`, see `keradb users`), separate
+//! from the databases being served. Clients authenticate with a password
+//! (`Authorization: Basic ...`) or with an API token issued to their user
+//! (`Authorization: Bearer ...`); passwords are stored as PBKDF2-SHA256
+//! hashes and tokens as SHA-256 hashes.
+//!
+//! A user may read or write the collections its [`Grant`]s cover, named by
+//! database and collection, either of which can be `*`. Admins may do
+//! anything. Every successful mutation is appended to the system database's
+//! `audit_log` collection and logged at info level under the
+//! `keradb::audit` target.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let access = AccessControl::new(Arc::new(Database::open("system.ndb")?));
+//! access.add_user("alice", Some("s3cret"), false)?;
+//! access.grant("alice", "shop", "orders", Access::Write)?;
+//!
+//! let config = ServerConfig::new(8080).with_access_control(Arc::new(access.with_database("shop")));
+//! ```
+
+use crate::error::{KeraDBError, Result};
+use crate::Database;
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+const USERS_COLLECTION: &str = "server_users";
+const AUDIT_COLLECTION: &str = "audit_log";
+
+const PBKDF2_ROUNDS: u32 = 100_000;
+const TOKEN_PREFIX: &str = "kdb_";
+
+/// What a grant allows; `Write` includes `Read`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Access {
+    Read,
+    Write,
+}
+
+impl Access {
+    /// Parse `read` or `write`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "read" | "r" => Some(Access::Read),
+            "write" | "rw" | "w" => Some(Access::Write),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Access::Read => "read",
+            Access::Write => "write",
+        })
+    }
+}
+
+/// Access to a collection of a database; either name may be `*`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Grant {
+    pub database: String,
+    pub collection: String,
+    pub access: Access,
+}
+
+impl Grant {
+    fn covers(&self, database: &str, collection: &str) -> bool {
+        (self.database == "*" || self.database == database) && (self.collection == "*" || self.collection == collection)
+    }
+}
+
+/// A server user, as stored in the system database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct User {
+    #[serde(rename = "_id")]
+    pub name: String,
+
+    /// Admins may read and write everything
+    #[serde(default)]
+    pub admin: bool,
+
+    #[serde(default)]
+    pub grants: Vec<Grant>,
+
+    /// `pbkdf2-sha256$<rounds>$<salt>$<hash>`, if the user has a password
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    password: Option<String>,
+
+    /// SHA-256 hashes of the user's API tokens
+    #[serde(default)]
+    tokens: Vec<String>,
+}
+
+impl User {
+    /// Whether the user can log in with a password
+    pub fn has_password(&self) -> bool {
+        self.password.is_some()
+    }
+
+    /// Number of API tokens issued to the user
+    pub fn token_count(&self) -> usize {
+        self.tokens.len()
+    }
+}
+
+/// Who made a request
+#[derive(Debug, Clone)]
+pub enum Principal {
+    /// Anyone, on a server with no authentication configured
+    Anonymous,
+    /// A holder of one of the server's configured tokens, which grant
+    /// full access
+    Token,
+    /// A user from the system database
+    User(User),
+}
+
+impl Principal {
+    /// The name recorded in the audit log
+    pub fn name(&self) -> &str {
+        match self {
+            Principal::Anonymous => "anonymous",
+            Principal::Token => "token",
+            Principal::User(user) => &user.name,
+        }
+    }
+
+    /// Whether this principal may `access` `collection` of `database`
+    pub fn can(&self, database: &str, collection: &str, access: Access) -> bool {
+        match self {
+            Principal::Anonymous | Principal::Token => true,
+            Principal::User(user) => {
+                user.admin || user.grants.iter().any(|g| g.access >= access && g.covers(database, collection))
+            }
+        }
+    }
+}
+
+/// Credentials from an `Authorization` header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Credentials {
+    Bearer(String),
+    Basic { user: String, password: String },
+}
+
+impl Credentials {
+    /// Parse a `Bearer <token>` or `Basic <base64 user:password>` header
+    pub fn from_header(value: &str) -> Option<Self> {
+        let (scheme, rest) = value.trim().split_once(' ')?;
+        let rest = rest.trim();
+        if scheme.eq_ignore_ascii_case("bearer") {
+            return Some(Credentials::Bearer(rest.to_string()));
+        }
+        if scheme.eq_ignore_ascii_case("basic") {
+            let decoded = String::from_utf8(base64_decode(rest)?).ok()?;
+            let (user, password) = decoded.split_once(':')?;
+            return Some(Credentials::Basic { user: user.to_string(), password: password.to_string() });
+        }
+        None
+    }
+}
+
+/// One audited mutation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub at: DateTime<Utc>,
+    pub user: String,
+    pub database: String,
+    pub collection: String,
+    pub operation: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+}
+
+/// Server users, their permissions, and the audit log, kept in a system
+/// database
+pub struct AccessControl {
+    system: Arc<Database>,
+    database: String,
+    /// Password records already checked, mapped to the SHA-256 of the
+    /// password that matched, so PBKDF2 runs once per login rather than
+    /// once per request
+    verified: Mutex<HashMap<String, [u8; 32]>>,
+}
+
+impl fmt::Debug for AccessControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessControl").field("database", &self.database).finish_non_exhaustive()
+    }
+}
+
+impl AccessControl {
+    /// Keep users in `system`
+    pub fn new(system: Arc<Database>) -> Self {
+        Self { system, database: "default".to_string(), verified: Mutex::new(HashMap::new()) }
+    }
+
+    /// Name the served database, as grants and the audit log refer to it
+    pub fn with_database(mut self, name: impl Into<String>) -> Self {
+        self.database = name.into();
+        self
+    }
+
+    /// The served database's name
+    pub fn database(&self) -> &str {
+        &self.database
+    }
+
+    /// Flush users and audit entries to disk
+    pub fn sync(&self) -> Result<()> {
+        self.system.sync()
+    }
+
+    /// Add a user, optionally with a password
+    pub fn add_user(&self, name: &str, password: Option<&str>, admin: bool) -> Result<User> {
+        if name.is_empty() || name.contains(':') {
+            return Err(KeraDBError::InvalidQuery(format!("Invalid user name '{}'", name)));
+        }
+        let user = User {
+            name: name.to_string(),
+            admin,
+            grants: Vec::new(),
+            password: password.map(hash_password),
+            tokens: Vec::new(),
+        };
+        match self.system.insert(USERS_COLLECTION, serde_json::to_value(&user)?) {
+            Err(KeraDBError::DuplicateKey(_)) => Err(KeraDBError::DuplicateKey(format!("User '{}' already exists", name))),
+            result => result.map(|_| user),
+        }
+    }
+
+    /// The user called `name`, if there is one
+    pub fn user(&self, name: &str) -> Result<Option<User>> {
+        match self.system.find_by_id(USERS_COLLECTION, name) {
+            Ok(doc) => Ok(Some(serde_json::from_value(doc.to_value())?)),
+            Err(KeraDBError::DocumentNotFound(_) | KeraDBError::CollectionNotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// All users, by name
+    pub fn users(&self) -> Result<Vec<User>> {
+        let docs = match self.system.find_all(USERS_COLLECTION, None, None) {
+            Err(KeraDBError::CollectionNotFound(_)) => return Ok(Vec::new()),
+            docs => docs?,
+        };
+        let mut users = docs
+            .iter()
+            .map(|doc| serde_json::from_value(doc.to_value()).map_err(Into::into))
+            .collect::<Result<Vec<User>>>()?;
+        users.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(users)
+    }
+
+    /// Remove a user; returns whether it existed
+    pub fn remove_user(&self, name: &str) -> Result<bool> {
+        match self.system.delete(USERS_COLLECTION, name) {
+            Ok(_) => Ok(true),
+            Err(KeraDBError::DocumentNotFound(_) | KeraDBError::CollectionNotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Set or clear a user's password
+    pub fn set_password(&self, name: &str, password: Option<&str>) -> Result<()> {
+        self.modify(name, |user| user.password = password.map(hash_password))
+    }
+
+    /// Make a user an admin, or not
+    pub fn set_admin(&self, name: &str, admin: bool) -> Result<()> {
+        self.modify(name, |user| user.admin = admin)
+    }
+
+    /// Allow a user `access` to `collection` of `database`, replacing any
+    /// grant for the same pair
+    pub fn grant(&self, name: &str, database: &str, collection: &str, access: Access) -> Result<()> {
+        self.modify(name, |user| {
+            user.grants.retain(|g| g.database != database || g.collection != collection);
+            user.grants.push(Grant { database: database.to_string(), collection: collection.to_string(), access });
+        })
+    }
+
+    /// Remove a user's grant for `collection` of `database`; returns
+    /// whether there was one
+    pub fn revoke(&self, name: &str, database: &str, collection: &str) -> Result<bool> {
+        let mut revoked = false;
+        self.modify(name, |user| {
+            let before = user.grants.len();
+            user.grants.retain(|g| g.database != database || g.collection != collection);
+            revoked = user.grants.len() != before;
+        })?;
+        Ok(revoked)
+    }
+
+    /// Issue a new API token to a user; only its hash is stored, so this is
+    /// the one chance to see it
+    pub fn create_token(&self, name: &str) -> Result<String> {
+        let token = format!("{}{}", TOKEN_PREFIX, hex::encode(rand::random::<[u8; 24]>()));
+        let hash = hex::encode(Sha256::digest(token.as_bytes()));
+        self.modify(name, |user| user.tokens.push(hash))?;
+        Ok(token)
+    }
+
+    /// Revoke all of a user's API tokens; returns how many there were
+    pub fn revoke_tokens(&self, name: &str) -> Result<usize> {
+        let mut count = 0;
+        self.modify(name, |user| count = std::mem::take(&mut user.tokens).len())?;
+        Ok(count)
+    }
+
+    fn modify(&self, name: &str, change: impl FnOnce(&mut User)) -> Result<()> {
+        let mut user = self.user(name)?.ok_or_else(|| KeraDBError::NotFound(format!("User '{}'", name)))?;
+        change(&mut user);
+        let mut data = serde_json::to_value(&user)?;
+        if let Value::Object(ref mut map) = data {
+            map.remove("_id");
+        }
+        self.system.update(USERS_COLLECTION, name, data)?;
+        Ok(())
+    }
+
+    /// The user these credentials belong to, if they are valid
+    pub fn authenticate(&self, credentials: &Credentials) -> Option<User> {
+        match credentials {
+            Credentials::Basic { user, password } => {
+                let user = self.user(user).ok()??;
+                let record = user.password.as_deref()?;
+                let digest: [u8; 32] = Sha256::digest(password.as_bytes()).into();
+                if self.verified.lock().get(record) == Some(&digest) {
+                    return Some(user);
+                }
+                if !verify_password(record, password) {
+                    return None;
+                }
+                self.verified.lock().insert(record.to_string(), digest);
+                Some(user)
+            }
+            Credentials::Bearer(token) if token.starts_with(TOKEN_PREFIX) => {
+                let hash = hex::encode(Sha256::digest(token.as_bytes()));
+                self.users().ok()?.into_iter().find(|user| {
+                    user.tokens.iter().any(|t| super::constant_time_eq(t.as_bytes(), hash.as_bytes()))
+                })
+            }
+            Credentials::Bearer(_) => None,
+        }
+    }
+
+    /// Record a mutation by `principal` to `collection` of the served
+    /// database
+    ///
+    /// Failing to write the entry is logged rather than failing the request,
+    /// which has already been carried out.
+    pub fn audit(&self, principal: &Principal, operation: &str, collection: &str, id: Option<&str>) {
+        tracing::info!(
+            target: "keradb::audit",
+            user = principal.name(),
+            database = %self.database,
+            collection,
+            operation,
+            id,
+        );
+        let entry = AuditEntry {
+            at: Utc::now(),
+            user: principal.name().to_string(),
+            database: self.database.clone(),
+            collection: collection.to_string(),
+            operation: operation.to_string(),
+            id: id.map(str::to_string),
+        };
+        let result = serde_json::to_value(&entry)
+            .map_err(KeraDBError::from)
+            .and_then(|data| self.system.insert(AUDIT_COLLECTION, data));
+        if let Err(e) = result {
+            tracing::error!("Failed to write audit entry: {}", e);
+        }
+    }
+
+    /// The most recent `limit` audit entries, oldest first
+    pub fn audit_log(&self, limit: usize) -> Result<Vec<AuditEntry>> {
+        let docs = match self.system.find_all(AUDIT_COLLECTION, None, None) {
+            Err(KeraDBError::CollectionNotFound(_)) => return Ok(Vec::new()),
+            docs => docs?,
+        };
+        let mut entries = docs
+            .iter()
+            .map(|doc| serde_json::from_value(doc.data.clone()).map_err(Into::into))
+            .collect::<Result<Vec<AuditEntry>>>()?;
+        // Collections are not kept in insertion order
+        entries.sort_by_key(|entry| entry.at);
+        Ok(entries.split_off(entries.len().saturating_sub(limit)))
+    }
+}
+
+fn pbkdf2(password: &str, salt: &[u8], rounds: u32) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, rounds, &mut hash);
+    hash
+}
+
+fn hash_password(password: &str) -> String {
+    let salt: [u8; 16] = rand::random();
+    let hash = pbkdf2(password, &salt, PBKDF2_ROUNDS);
+    format!("pbkdf2-sha256${}${}${}", PBKDF2_ROUNDS, hex::encode(salt), hex::encode(hash))
+}
+
+fn verify_password(record: &str, password: &str) -> bool {
+    let parts: Vec<&str> = record.split('$').collect();
+    let ["pbkdf2-sha256", rounds, salt, hash] = parts[..] else { return false };
+    let (Ok(rounds), Ok(salt), Ok(hash)) = (rounds.parse(), hex::decode(salt), hex::decode(hash)) else {
+        return false;
+    };
+    super::constant_time_eq(&pbkdf2(password, &salt, rounds), &hash)
+}
+
+/// Decode standard base64, with or without padding
+fn base64_decode(input: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len() * 3 / 4);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for byte in input.trim_end_matches('=').bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_users_grants_and_tokens() {
+        let dir = tempdir().unwrap();
+        let system = Arc::new(Database::create(dir.path().join("system.ndb")).unwrap());
+        let access = AccessControl::new(system).with_database("shop");
+
+        access.add_user("alice", Some("s3cret"), false).unwrap();
+        assert!(access.add_user("alice", None, false).is_err());
+        access.grant("alice", "shop", "orders", Access::Write).unwrap();
+        access.grant("alice", "*", "products", Access::Read).unwrap();
+
+        let basic = |password: &str| Credentials::Basic { user: "alice".into(), password: password.into() };
+        assert!(access.authenticate(&basic("wrong")).is_none());
+        let alice = Principal::User(access.authenticate(&basic("s3cret")).unwrap());
+        // Served from the cache the second time
+        assert!(access.authenticate(&basic("s3cret")).is_some());
+        assert!(alice.can("shop", "orders", Access::Write));
+        assert!(alice.can("other", "products", Access::Read));
+        assert!(!alice.can("other", "products", Access::Write));
+        assert!(!alice.can("shop", "users", Access::Read));
+
+        let token = access.create_token("alice").unwrap();
+        let header = format!("Bearer {}", token);
+        let user = access.authenticate(&Credentials::from_header(&header).unwrap()).unwrap();
+        assert_eq!(user.name, "alice");
+        assert!(access.revoke("alice", "shop", "orders").unwrap());
+        assert_eq!(access.revoke_tokens("alice").unwrap(), 1);
+        assert!(access.authenticate(&Credentials::Bearer(token)).is_none());
+
+        access.audit(&alice, "insert", "orders", Some("o1"));
+        let log = access.audit_log(10).unwrap();
+        assert_eq!((log[0].user.as_str(), log[0].database.as_str(), log[0].id.as_deref()), ("alice", "shop", Some("o1")));
+
+        // "alice:s3cret"
+        assert_eq!(Credentials::from_header("Basic YWxpY2U6czNjcmV0"), Some(basic("s3cret")));
+        assert!(access.remove_user("alice").unwrap());
+        assert!(access.authenticate(&basic("s3cret")).is_none());
+    }
+}
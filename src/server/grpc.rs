@@ -0,0 +1,395 @@
+//! gRPC service (feature `grpc`)
+//!
+//! Implements the `keradb.v1.KeraDb` service from `proto/keradb.proto`:
+//! document CRUD, cursored collection scans as server-streaming RPCs, and
+//! vector insert/search. Documents travel as JSON strings.
+//!
+//! Clients authenticate with `authorization` metadata, as for the REST API;
+//! with an [`AccessControl`](super::AccessControl), each call is checked
+//! against the user's grants and mutations are audited.
+
+// Handlers must return `tonic::Status`, which is large by design
+#![allow(clippy::result_large_err)]
+
+use super::{error_status, forbidden, Access, Credentials, Principal, ServerConfig};
+use crate::error::{KeraDBError, Result};
+use crate::vector::{MetadataFilter, VectorSearchResult};
+use crate::Database;
+
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+/// Generated protobuf types and service stubs
+pub mod proto {
+    tonic::include_proto!("keradb.v1");
+}
+
+use proto::kera_db_server::{KeraDb, KeraDbServer};
+
+const DEFAULT_SCAN_BATCH: usize = 100;
+
+/// Convert a database error into a gRPC status
+fn to_status(err: KeraDBError) -> Status {
+    let message = err.to_string();
+    match error_status(&err) {
+        404 => Status::not_found(message),
+        409 => Status::already_exists(message),
+        400 => Status::invalid_argument(message),
+        501 => Status::unimplemented(message),
+        423 => Status::unavailable(message),
+        _ => Status::internal(message),
+    }
+}
+
+fn parse_json(field: &str, json: &str) -> std::result::Result<Value, Status> {
+    serde_json::from_str(json).map_err(|e| Status::invalid_argument(format!("Invalid JSON in '{}': {}", field, e)))
+}
+
+fn to_proto_document(doc: &crate::types::Document) -> proto::Document {
+    proto::Document { id: doc.id.clone(), json: doc.data.to_string() }
+}
+
+fn to_proto_result(result: VectorSearchResult) -> proto::VectorSearchResult {
+    let doc = result.document;
+    proto::VectorSearchResult {
+        id: doc.id,
+        external_id: doc.external_id.unwrap_or_default(),
+        score: result.score,
+        rank: result.rank as u32,
+        metadata_json: if doc.metadata.is_null() { String::new() } else { doc.metadata.to_string() },
+        text: doc.text.unwrap_or_default(),
+    }
+}
+
+/// Who made a request, as the server's interceptor found; anonymous when
+/// the service is used without one
+fn principal<T>(request: &Request<T>) -> Principal {
+    request.extensions().get::<Principal>().cloned().unwrap_or(Principal::Anonymous)
+}
+
+/// gRPC service implementation backed by a [`Database`]
+pub struct KeraDbService {
+    db: Arc<Database>,
+    config: ServerConfig,
+}
+
+impl KeraDbService {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db, config: ServerConfig::default() }
+    }
+
+    /// Check permissions and audit mutations with `config`'s access control
+    pub fn with_config(mut self, config: ServerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    fn check(&self, principal: &Principal, collection: &str, access: Access) -> std::result::Result<(), Status> {
+        if self.config.permits(principal, collection, access) {
+            Ok(())
+        } else {
+            Err(Status::permission_denied(forbidden(principal, collection, access)))
+        }
+    }
+
+    fn search(&self, request: Request<proto::VectorSearchRequest>) -> std::result::Result<Vec<VectorSearchResult>, Status> {
+        let (principal, request) = (principal(&request), request.into_inner());
+        self.check(&principal, &request.collection, Access::Read)?;
+        let k = if request.k == 0 { 10 } else { request.k as usize };
+        let results = if request.filter_json.is_empty() {
+            self.db.vector_search(&request.collection, &request.vector, k)
+        } else {
+            let filters = serde_json::from_str(&request.filter_json)
+                .map_err(|e| Status::invalid_argument(format!("Invalid JSON in 'filter_json': {}", e)))?;
+            self.db.vector_search_filtered(&request.collection, &request.vector, k, &MetadataFilter { filters })
+        };
+        results.map_err(to_status)
+    }
+}
+
+#[tonic::async_trait]
+impl KeraDb for KeraDbService {
+    async fn insert(&self, request: Request<proto::InsertRequest>) -> std::result::Result<Response<proto::InsertResponse>, Status> {
+        let (principal, request) = (principal(&request), request.into_inner());
+        self.check(&principal, &request.collection, Access::Write)?;
+        let data = parse_json("json", &request.json)?;
+        let id = self.db.insert(&request.collection, data).map_err(to_status)?;
+        self.config.audit(&principal, "insert", &request.collection, Some(&id));
+        Ok(Response::new(proto::InsertResponse { id }))
+    }
+
+    async fn get(&self, request: Request<proto::DocumentRequest>) -> std::result::Result<Response<proto::Document>, Status> {
+        let (principal, request) = (principal(&request), request.into_inner());
+        self.check(&principal, &request.collection, Access::Read)?;
+        let doc = self.db.find_by_id(&request.collection, &request.id).map_err(to_status)?;
+        Ok(Response::new(to_proto_document(&doc)))
+    }
+
+    async fn update(&self, request: Request<proto::UpdateRequest>) -> std::result::Result<Response<proto::Document>, Status> {
+        let (principal, request) = (principal(&request), request.into_inner());
+        self.check(&principal, &request.collection, Access::Write)?;
+        let data = parse_json("json", &request.json)?;
+        let doc = self.db.update(&request.collection, &request.id, data).map_err(to_status)?;
+        self.config.audit(&principal, "update", &request.collection, Some(&request.id));
+        Ok(Response::new(to_proto_document(&doc)))
+    }
+
+    async fn delete(&self, request: Request<proto::DocumentRequest>) -> std::result::Result<Response<proto::Document>, Status> {
+        let (principal, request) = (principal(&request), request.into_inner());
+        self.check(&principal, &request.collection, Access::Write)?;
+        let doc = self.db.delete(&request.collection, &request.id).map_err(to_status)?;
+        self.config.audit(&principal, "delete", &request.collection, Some(&request.id));
+        Ok(Response::new(to_proto_document(&doc)))
+    }
+
+    async fn count(&self, request: Request<proto::CollectionRequest>) -> std::result::Result<Response<proto::CountResponse>, Status> {
+        let (principal, request) = (principal(&request), request.into_inner());
+        self.check(&principal, &request.collection, Access::Read)?;
+        let count = self.db.count(&request.collection) as u64;
+        Ok(Response::new(proto::CountResponse { count }))
+    }
+
+    async fn list_collections(
+        &self,
+        request: Request<proto::ListCollectionsRequest>,
+    ) -> std::result::Result<Response<proto::ListCollectionsResponse>, Status> {
+        let principal = principal(&request);
+        let collections = self
+            .db
+            .list_collections()
+            .into_iter()
+            .filter(|(name, _)| self.config.permits(&principal, name, Access::Read))
+            .map(|(name, count)| proto::CollectionInfo { name, count: count as u64 })
+            .collect();
+        Ok(Response::new(proto::ListCollectionsResponse { collections }))
+    }
+
+    type ScanStream = ReceiverStream<std::result::Result<proto::ScanResult, Status>>;
+
+    async fn scan(&self, request: Request<proto::ScanRequest>) -> std::result::Result<Response<Self::ScanStream>, Status> {
+        let (principal, request) = (principal(&request), request.into_inner());
+        self.check(&principal, &request.collection, Access::Read)?;
+        let batch_size = match request.batch_size {
+            0 => DEFAULT_SCAN_BATCH,
+            n => n as usize,
+        };
+        let limit = match request.limit {
+            0 => usize::MAX,
+            n => n as usize,
+        };
+
+        let db = self.db.clone();
+        let (tx, rx) = mpsc::channel(batch_size);
+
+        // Page through the collection on a blocking thread; the bounded channel
+        // applies backpressure when the client reads slowly
+        tokio::task::spawn_blocking(move || {
+            let mut position = request.skip as usize;
+            let mut sent = 0;
+            while sent < limit {
+                let batch = match db.find_all(&request.collection, Some(batch_size.min(limit - sent)), Some(position)) {
+                    Ok(batch) => batch,
+                    Err(e) => {
+                        let _ = tx.blocking_send(Err(to_status(e)));
+                        return;
+                    }
+                };
+                if batch.is_empty() {
+                    return;
+                }
+                for doc in &batch {
+                    let result = proto::ScanResult { position: position as u64, document: Some(to_proto_document(doc)) };
+                    if tx.blocking_send(Ok(result)).is_err() {
+                        return; // Client went away
+                    }
+                    position += 1;
+                    sent += 1;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn insert_vector(
+        &self,
+        request: Request<proto::InsertVectorRequest>,
+    ) -> std::result::Result<Response<proto::InsertVectorResponse>, Status> {
+        let (principal, request) = (principal(&request), request.into_inner());
+        self.check(&principal, &request.collection, Access::Write)?;
+        let metadata = match request.metadata_json.as_str() {
+            "" => None,
+            json => Some(parse_json("metadata_json", json)?),
+        };
+        let id = if request.external_id.is_empty() {
+            self.db.insert_vector(&request.collection, request.vector, metadata)
+        } else {
+            self.db.insert_vector_with_id(&request.collection, &request.external_id, request.vector, metadata)
+        }
+        .map_err(to_status)?;
+        self.config.audit(&principal, "insert_vector", &request.collection, Some(&id.to_string()));
+        Ok(Response::new(proto::InsertVectorResponse { id }))
+    }
+
+    async fn vector_search(
+        &self,
+        request: Request<proto::VectorSearchRequest>,
+    ) -> std::result::Result<Response<proto::VectorSearchResponse>, Status> {
+        let results = self.search(request)?;
+        Ok(Response::new(proto::VectorSearchResponse {
+            results: results.into_iter().map(to_proto_result).collect(),
+        }))
+    }
+
+    type VectorSearchStreamStream = ReceiverStream<std::result::Result<proto::VectorSearchResult, Status>>;
+
+    async fn vector_search_stream(
+        &self,
+        request: Request<proto::VectorSearchRequest>,
+    ) -> std::result::Result<Response<Self::VectorSearchStreamStream>, Status> {
+        let results = self.search(request)?;
+        let (tx, rx) = mpsc::channel(results.len().max(1));
+        for result in results {
+            // Capacity covers every result, so this never waits
+            let _ = tx.try_send(Ok(to_proto_result(result)));
+        }
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+/// gRPC server exposing a database
+pub struct GrpcServer {
+    db: Arc<Database>,
+    config: ServerConfig,
+}
+
+impl GrpcServer {
+    /// Create a server for `db`
+    pub fn new(db: Arc<Database>, config: ServerConfig) -> Self {
+        Self { db, config }
+    }
+
+    /// Bind and serve requests, blocking the current thread
+    pub fn run(&self) -> Result<()> {
+        let addr = self
+            .config
+            .addr()
+            .parse()
+            .map_err(|e| KeraDBError::InvalidQuery(format!("Invalid listen address '{}': {}", self.config.addr(), e)))?;
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(self.config.workers)
+            .enable_all()
+            .build()?;
+
+        let config = self.config.clone();
+        let service = KeraDbService::new(self.db.clone()).with_config(self.config.clone());
+        let service = KeraDbServer::with_interceptor(service, move |mut request: Request<()>| {
+            let credentials = request
+                .metadata()
+                .get("authorization")
+                .and_then(|v| v.to_str().ok())
+                .and_then(Credentials::from_header);
+            match config.authenticate(credentials.as_ref()) {
+                Some(principal) => {
+                    request.extensions_mut().insert(principal);
+                    Ok(request)
+                }
+                None => Err(Status::unauthenticated("Missing or invalid credentials")),
+            }
+        });
+
+        if !self.config.requires_auth() {
+            tracing::warn!("No authentication tokens configured; the API is open to anyone who can connect");
+        }
+        tracing::info!("gRPC listening on {}", addr);
+
+        let db = self.db.clone();
+        let access = self.config.access.clone();
+        let sync_interval = self.config.sync_interval;
+        runtime.block_on(async move {
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(sync_interval);
+                loop {
+                    interval.tick().await;
+                    if let Err(e) = db.sync() {
+                        tracing::error!("Background sync failed: {}", e);
+                    }
+                    if let Some(Err(e)) = access.as_ref().map(|a| a.sync()) {
+                        tracing::error!("Background sync of the system database failed: {}", e);
+                    }
+                }
+            });
+
+            tonic::transport::Server::builder()
+                .add_service(service)
+                .serve(addr)
+                .await
+                .map_err(|e| KeraDBError::StorageError(format!("gRPC server failed: {}", e)))
+        })?;
+
+        self.db.sync()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    use tokio_stream::StreamExt;
+
+    #[tokio::test]
+    async fn test_crud_and_streaming_scan() {
+        let dir = tempdir().unwrap();
+        let db = Arc::new(Database::create(dir.path().join("test.ndb")).unwrap());
+        let service = KeraDbService::new(db);
+
+        for i in 0..25 {
+            let request = proto::InsertRequest { collection: "items".into(), json: format!(r#"{{"n":{}}}"#, i) };
+            service.insert(Request::new(request)).await.unwrap();
+        }
+
+        let count = service.count(Request::new(proto::CollectionRequest { collection: "items".into() })).await.unwrap();
+        assert_eq!(count.into_inner().count, 25);
+
+        let request = proto::ScanRequest { collection: "items".into(), batch_size: 10, skip: 5, limit: 0 };
+        let stream = service.scan(Request::new(request)).await.unwrap().into_inner();
+        let results: Vec<_> = stream.collect().await;
+        assert_eq!(results.len(), 20);
+        assert_eq!(results[0].as_ref().unwrap().position, 5);
+        assert_eq!(results[19].as_ref().unwrap().position, 24);
+
+        let missing = proto::DocumentRequest { collection: "items".into(), id: "nope".into() };
+        let err = service.get(Request::new(missing)).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_permissions() {
+        let dir = tempdir().unwrap();
+        let db = Arc::new(Database::create(dir.path().join("test.ndb")).unwrap());
+        let system = Arc::new(Database::create(dir.path().join("system.ndb")).unwrap());
+        let access = Arc::new(super::super::AccessControl::new(system).with_database("test"));
+        access.add_user("reader", None, false).unwrap();
+        access.grant("reader", "test", "items", Access::Read).unwrap();
+        let service = KeraDbService::new(db).with_config(ServerConfig::default().with_access_control(access.clone()));
+
+        let reader = Principal::User(access.user("reader").unwrap().unwrap());
+        fn as_user<T>(principal: &Principal, message: T) -> Request<T> {
+            let mut request = Request::new(message);
+            request.extensions_mut().insert(principal.clone());
+            request
+        }
+        let insert = || proto::InsertRequest { collection: "items".into(), json: "{}".into() };
+        let err = service.insert(as_user(&reader, insert())).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+
+        service.insert(Request::new(insert())).await.unwrap();
+        let count = service.count(as_user(&reader, proto::CollectionRequest { collection: "items".into() })).await.unwrap();
+        assert_eq!(count.into_inner().count, 1);
+        assert_eq!(access.audit_log(10).unwrap()[0].user, "anonymous");
+    }
+}
@@ -0,0 +1,185 @@
+//! Network server mode (feature `server`)
+//!
+//! Exposes a [`Database`](crate::Database) over the network so that non-Rust
+//! clients can use KeraDB as a small standalone service: JSON over HTTP in
+//! [`rest`], and gRPC in `grpc` (feature `grpc`). Clients authenticate with
+//! one of the configured bearer tokens or, given an [`AccessControl`], as a
+//! user with per-collection permissions (see [`auth`]). [`Webhook`]s POST
+//! each write to HTTP endpoints.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use keradb::server::{HttpServer, ServerConfig};
+//!
+//! let db = Arc::new(Database::open("mydata.ndb")?);
+//! let config = ServerConfig::new(8080).with_token("s3cret");
+//! HttpServer::new(db, config).run()?;
+//! ```
+
+pub mod auth;
+pub mod rest;
+pub mod webhook;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+
+pub use auth::{Access, AccessControl, Credentials, Principal};
+pub use rest::HttpServer;
+pub use webhook::Webhook;
+#[cfg(feature = "grpc")]
+pub use grpc::GrpcServer;
+
+use crate::error::KeraDBError;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Configuration shared by the network servers
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Address to bind (default `127.0.0.1`)
+    pub host: String,
+
+    /// Port to listen on
+    pub port: u16,
+
+    /// Accepted bearer tokens, each granting full access
+    pub tokens: Vec<String>,
+
+    /// Users and permissions; with no tokens either, authentication is
+    /// disabled
+    pub access: Option<Arc<AccessControl>>,
+
+    /// Number of request-handling threads
+    pub workers: usize,
+
+    /// How often pending changes are synced to disk
+    pub sync_interval: Duration,
+}
+
+impl ServerConfig {
+    /// Create a config listening on `127.0.0.1:<port>`
+    pub fn new(port: u16) -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port,
+            tokens: Vec::new(),
+            access: None,
+            workers: 4,
+            sync_interval: Duration::from_secs(1),
+        }
+    }
+
+    /// Set the bind address
+    pub fn with_host(mut self, host: impl Into<String>) -> Self {
+        self.host = host.into();
+        self
+    }
+
+    /// Accept an authentication token
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.tokens.push(token.into());
+        self
+    }
+
+    /// Authenticate users and check their permissions against `access`
+    pub fn with_access_control(mut self, access: Arc<AccessControl>) -> Self {
+        self.access = Some(access);
+        self
+    }
+
+    /// Set the number of worker threads
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Set the background sync interval
+    pub fn with_sync_interval(mut self, interval: Duration) -> Self {
+        self.sync_interval = interval;
+        self
+    }
+
+    /// The `host:port` address to bind
+    pub fn addr(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    /// Whether clients must authenticate
+    pub fn requires_auth(&self) -> bool {
+        !self.tokens.is_empty() || self.access.is_some()
+    }
+
+    /// Check a presented token against the configured tokens
+    pub fn authorize(&self, token: Option<&str>) -> bool {
+        self.authenticate(token.map(|t| Credentials::Bearer(t.to_string())).as_ref()).is_some()
+    }
+
+    /// Who presented `credentials`, or `None` if they are missing or invalid
+    pub fn authenticate(&self, credentials: Option<&Credentials>) -> Option<Principal> {
+        if !self.requires_auth() {
+            return Some(Principal::Anonymous);
+        }
+        match credentials? {
+            Credentials::Bearer(token)
+                if self.tokens.iter().any(|t| constant_time_eq(t.as_bytes(), token.as_bytes())) =>
+            {
+                Some(Principal::Token)
+            }
+            credentials => self.access.as_ref()?.authenticate(credentials).map(Principal::User),
+        }
+    }
+
+    /// Whether `principal` may `access` `collection` of the served database
+    pub fn permits(&self, principal: &Principal, collection: &str, access: Access) -> bool {
+        let database = self.access.as_ref().map_or("", |a| a.database());
+        principal.can(database, collection, access)
+    }
+
+    /// Record a mutation in the audit log, if there is one
+    pub fn audit(&self, principal: &Principal, operation: &str, collection: &str, id: Option<&str>) {
+        if let Some(access) = &self.access {
+            access.audit(principal, operation, collection, id);
+        }
+    }
+
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self::new(8080)
+    }
+}
+
+/// The error message for a request `principal` is not permitted to make
+pub(crate) fn forbidden(principal: &Principal, collection: &str, access: Access) -> String {
+    format!("User '{}' may not {} collection '{}'", principal.name(), access, collection)
+}
+
+/// Compare two byte strings without short-circuiting on the first difference
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Map a database error to an HTTP-style status code
+pub(crate) fn error_status(err: &KeraDBError) -> u16 {
+    match err {
+        KeraDBError::CollectionNotFound(_)
+        | KeraDBError::DocumentNotFound(_)
+        | KeraDBError::DatabaseNotFound(_)
+        | KeraDBError::NotFound(_) => 404,
+        KeraDBError::CollectionExists(_) | KeraDBError::DuplicateKey(_) | KeraDBError::ReferenceViolation(_) => 409,
+        KeraDBError::DocumentTooLarge { .. } => 413,
+        KeraDBError::QuotaExceeded(_) => 507,
+        KeraDBError::InvalidQuery(_)
+        | KeraDBError::InvalidDocument(_)
+        | KeraDBError::InvalidFormat(_)
+        | KeraDBError::ParseError(_)
+        | KeraDBError::Serialization(_)
+        | KeraDBError::VectorError(_) => 400,
+        KeraDBError::NotImplemented(_) => 501,
+        KeraDBError::EmbeddingUnavailable(_) => 503,
+        KeraDBError::Locked(_) => 423,
+        KeraDBError::ReadOnly => 403,
+        _ => 500,
+    }
+}
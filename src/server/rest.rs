@@ -0,0 +1,532 @@
+//! JSON-over-HTTP server
+//!
+//! # Endpoints
+//!
+//! | Method | Path | Description |
+//! |--------|------|-------------|
+//! | GET | `/health` | Liveness check (no authentication) |
+//! | GET | `/metrics` | Metrics in the Prometheus text format |
+//! | GET | `/collections` | List document collections |
+//! | GET | `/collections/{c}/documents?limit=&skip=` | List documents |
+//! | POST | `/collections/{c}/documents` | Insert a document |
+//! | GET/PUT/DELETE | `/collections/{c}/documents/{id}` | Read, update or delete a document |
+//! | GET | `/collections/{c}/count` | Count documents |
+//! | POST | `/collections/{c}/query` | Filter documents: `{"filter": {...}, "limit", "skip"}` |
+//! | GET | `/vectors` | List vector collections |
+//! | POST/DELETE | `/vectors/{c}` | Create (body is a `VectorConfig`) or drop a vector collection |
+//! | GET | `/vectors/{c}/stats` | Vector collection statistics |
+//! | POST | `/vectors/{c}/vectors` | Insert `{"vector", "metadata", "id"}` (`id` is optional) |
+//! | GET/DELETE | `/vectors/{c}/vectors/{id}` | Read or delete a vector by numeric or external ID |
+//! | POST | `/vectors/{c}/search` | Search `{"vector", "k", "filter"}` |
+//!
+//! Filters use the same conditions as vector metadata filters, e.g.
+//! `{"age": {"gte": 21}, "tags": {"contains": "rust"}}`.
+//!
+//! Requests carry `Authorization: Bearer <token>` or, for users with a
+//! password, `Authorization: Basic ...`. With an
+//! [`AccessControl`](super::AccessControl), users only see and touch the
+//! collections their grants cover (`403` otherwise), and mutations are
+//! audited.
+
+use super::{error_status, forbidden, Access, Credentials, Principal, ServerConfig};
+use crate::error::{KeraDBError, Result};
+use crate::vector::{FilterCondition, MetadataFilter, VectorConfig, VectorId};
+use crate::Database;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+const JSON: &str = "application/json";
+
+/// A response with an HTTP status code
+///
+/// The body is JSON unless `content_type` says otherwise, in which case it
+/// is a string sent as-is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Value,
+    pub content_type: &'static str,
+}
+
+impl Response {
+    fn ok(body: Value) -> Self {
+        Self { status: 200, body, content_type: JSON }
+    }
+
+    fn created(body: Value) -> Self {
+        Self { status: 201, body, content_type: JSON }
+    }
+
+    fn text(content_type: &'static str, body: String) -> Self {
+        Self { status: 200, body: Value::String(body), content_type }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Self { status, body: json!({ "error": message.into() }), content_type: JSON }
+    }
+}
+
+#[derive(Deserialize)]
+struct QueryRequest {
+    #[serde(default)]
+    filter: HashMap<String, FilterCondition>,
+    limit: Option<usize>,
+    #[serde(default)]
+    skip: usize,
+}
+
+#[derive(Deserialize)]
+struct VectorInsertRequest {
+    vector: Vec<f32>,
+    #[serde(default)]
+    metadata: Option<Value>,
+    #[serde(default)]
+    id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct VectorSearchRequest {
+    vector: Vec<f32>,
+    #[serde(default = "default_k")]
+    k: usize,
+    #[serde(default)]
+    filter: Option<HashMap<String, FilterCondition>>,
+}
+
+fn default_k() -> usize {
+    10
+}
+
+/// HTTP server exposing a database as a JSON API
+pub struct HttpServer {
+    db: Arc<Database>,
+    config: ServerConfig,
+}
+
+impl HttpServer {
+    /// Create a server for `db`
+    pub fn new(db: Arc<Database>, config: ServerConfig) -> Self {
+        Self { db, config }
+    }
+
+    /// Bind and serve requests until the listener fails
+    ///
+    /// Writes are synced to disk in the background every
+    /// [`sync_interval`](ServerConfig::sync_interval).
+    pub fn run(&self) -> Result<()> {
+        let server = tiny_http::Server::http(self.config.addr()).map_err(|e| {
+            KeraDBError::StorageError(format!("Failed to bind {}: {}", self.config.addr(), e))
+        })?;
+        if !self.config.requires_auth() {
+            tracing::warn!("No authentication tokens configured; the API is open to anyone who can connect");
+        }
+        tracing::info!("Listening on http://{}", self.config.addr());
+
+        let stop = AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                while !stop.load(Ordering::Relaxed) {
+                    std::thread::sleep(self.config.sync_interval);
+                    if let Err(e) = self.db.sync() {
+                        tracing::error!("Background sync failed: {}", e);
+                    }
+                    if let Some(Err(e)) = self.config.access.as_ref().map(|a| a.sync()) {
+                        tracing::error!("Background sync of the system database failed: {}", e);
+                    }
+                }
+            });
+
+            let workers: Vec<_> = (0..self.config.workers)
+                .map(|_| {
+                    scope.spawn(|| loop {
+                        match server.recv() {
+                            Ok(request) => self.respond(request),
+                            Err(e) => {
+                                tracing::error!("Failed to receive request: {}", e);
+                                break;
+                            }
+                        }
+                    })
+                })
+                .collect();
+            for worker in workers {
+                let _ = worker.join();
+            }
+            stop.store(true, Ordering::Relaxed);
+        });
+
+        self.db.sync()
+    }
+
+    fn respond(&self, mut request: tiny_http::Request) {
+        let mut body = Vec::new();
+        let response = match request.as_reader().read_to_end(&mut body) {
+            Ok(_) => {
+                let credentials = request
+                    .headers()
+                    .iter()
+                    .find(|h| h.field.equiv("Authorization"))
+                    .and_then(|h| Credentials::from_header(h.value.as_str()));
+                self.handle_with_credentials(request.method().as_str(), request.url(), credentials.as_ref(), &body)
+            }
+            Err(e) => Response::error(400, format!("Failed to read request body: {}", e)),
+        };
+
+        let data = match &response.body {
+            Value::String(text) if response.content_type != JSON => text.clone().into_bytes(),
+            body => serde_json::to_vec(body).unwrap_or_default(),
+        };
+        let content_type = tiny_http::Header::from_bytes(&b"Content-Type"[..], response.content_type.as_bytes())
+            .expect("static header is valid");
+        let http_response = tiny_http::Response::from_data(data)
+            .with_status_code(response.status)
+            .with_header(content_type);
+        if let Err(e) = request.respond(http_response) {
+            tracing::warn!("Failed to send response: {}", e);
+        }
+    }
+
+    /// Handle a single request
+    ///
+    /// `token` is the bearer token presented by the client, if any.
+    pub fn handle(&self, method: &str, url: &str, token: Option<&str>, body: &[u8]) -> Response {
+        let credentials = token.map(|t| Credentials::Bearer(t.to_string()));
+        self.handle_with_credentials(method, url, credentials.as_ref(), body)
+    }
+
+    /// Handle a single request from a client presenting `credentials`
+    pub fn handle_with_credentials(
+        &self,
+        method: &str,
+        url: &str,
+        credentials: Option<&Credentials>,
+        body: &[u8],
+    ) -> Response {
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let segments: Vec<String> = path
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(percent_decode)
+            .collect();
+        let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+        if method == "GET" && segments == ["health"] {
+            return Response::ok(json!({ "status": "ok" }));
+        }
+        let Some(principal) = self.config.authenticate(credentials) else {
+            return Response::error(401, "Missing or invalid credentials");
+        };
+        let operation = operation(method, &segments);
+        if let Some((collection, access, _)) = operation {
+            if !self.config.permits(&principal, collection, access) {
+                return Response::error(403, forbidden(&principal, collection, access));
+            }
+        }
+
+        let query = parse_query(query);
+        let response = match self.route(&principal, method, &segments, &query, body) {
+            Ok(response) => response,
+            Err(e) => return Response::error(error_status(&e), e.to_string()),
+        };
+        if let Some((collection, Access::Write, name)) = operation {
+            let id = segments.get(3).map(|id| id.to_string()).or_else(|| {
+                let id = response.body.get("_id").or_else(|| response.body.get("id"))?;
+                Some(id.as_str().map_or_else(|| id.to_string(), str::to_string))
+            });
+            self.config.audit(&principal, name, collection, id.as_deref());
+        }
+        response
+    }
+
+    fn route(
+        &self,
+        principal: &Principal,
+        method: &str,
+        segments: &[&str],
+        query: &HashMap<String, String>,
+        body: &[u8],
+    ) -> Result<Response> {
+        let db = &self.db;
+        let readable = |name: &String| self.config.permits(principal, name, Access::Read);
+
+        let response = match (method, segments) {
+            ("GET", ["metrics"]) => Response::text("text/plain; version=0.0.4", db.metrics().to_prometheus()),
+            ("GET", ["collections"]) => {
+                let collections: Vec<Value> = db
+                    .list_collections()
+                    .into_iter()
+                    .filter(|(name, _)| readable(name))
+                    .map(|(name, count)| json!({ "name": name, "count": count }))
+                    .collect();
+                Response::ok(json!(collections))
+            }
+            ("GET", ["collections", c, "documents"]) => {
+                let limit = query_param(query, "limit")?;
+                let skip = query_param(query, "skip")?;
+                let docs: Vec<Value> = db.find_all(c, limit, skip)?.iter().map(|d| d.to_value()).collect();
+                Response::ok(json!(docs))
+            }
+            ("POST", ["collections", c, "documents"]) => {
+                let id = db.insert(c, json_body(body)?)?;
+                Response::created(json!({ "_id": id }))
+            }
+            ("GET", ["collections", c, "documents", id]) => Response::ok(db.find_by_id(c, id)?.to_value()),
+            ("PUT", ["collections", c, "documents", id]) => {
+                Response::ok(db.update(c, id, json_body(body)?)?.to_value())
+            }
+            ("DELETE", ["collections", c, "documents", id]) => Response::ok(db.delete(c, id)?.to_value()),
+            ("GET", ["collections", c, "count"]) => Response::ok(json!({ "count": db.count(c) })),
+            ("POST", ["collections", c, "query"]) => {
+                let request: QueryRequest = json_body(body)?;
+                let filter = MetadataFilter { filters: request.filter };
+                let docs: Vec<Value> = db
+                    .find_all(c, None, None)?
+                    .iter()
+                    .map(|d| d.to_value())
+                    .filter(|d| filter.matches(d))
+                    .skip(request.skip)
+                    .take(request.limit.unwrap_or(usize::MAX))
+                    .collect();
+                Response::ok(json!(docs))
+            }
+
+            ("GET", ["vectors"]) => {
+                let collections: Vec<Value> = db
+                    .list_vector_collections()
+                    .into_iter()
+                    .filter(|(name, _)| readable(name))
+                    .map(|(name, count)| json!({ "name": name, "count": count }))
+                    .collect();
+                Response::ok(json!(collections))
+            }
+            ("POST", ["vectors", c]) => {
+                let config: VectorConfig = json_body(body)?;
+                db.create_vector_collection(c, config)?;
+                Response::created(json!({ "name": c }))
+            }
+            ("DELETE", ["vectors", c]) => {
+                if !db.drop_vector_collection(c)? {
+                    return Err(KeraDBError::CollectionNotFound(c.to_string()));
+                }
+                Response::ok(json!({ "dropped": c }))
+            }
+            ("GET", ["vectors", c, "stats"]) => Response::ok(serde_json::to_value(db.vector_stats(c)?)?),
+            ("POST", ["vectors", c, "vectors"]) => {
+                let request: VectorInsertRequest = json_body(body)?;
+                let id = match request.id {
+                    Some(external_id) => db.insert_vector_with_id(c, &external_id, request.vector, request.metadata)?,
+                    None => db.insert_vector(c, request.vector, request.metadata)?,
+                };
+                Response::created(json!({ "id": id }))
+            }
+            ("GET", ["vectors", c, "vectors", id]) => {
+                let doc = match id.parse::<VectorId>() {
+                    Ok(id) => db.get_vector(c, id)?,
+                    Err(_) => db.get_vector_by_external_id(c, id)?,
+                };
+                let doc = doc.ok_or_else(|| KeraDBError::NotFound(format!("Vector {}", id)))?;
+                Response::ok(serde_json::to_value(doc)?)
+            }
+            ("DELETE", ["vectors", c, "vectors", id]) => {
+                let deleted = match id.parse::<VectorId>() {
+                    Ok(id) => db.delete_vector(c, id)?,
+                    Err(_) => db.delete_vector_by_external_id(c, id)?,
+                };
+                if !deleted {
+                    return Err(KeraDBError::NotFound(format!("Vector {}", id)));
+                }
+                Response::ok(json!({ "deleted": true }))
+            }
+            ("POST", ["vectors", c, "search"]) => {
+                let request: VectorSearchRequest = json_body(body)?;
+                let results = match request.filter {
+                    Some(filters) => {
+                        db.vector_search_filtered(c, &request.vector, request.k, &MetadataFilter { filters })?
+                    }
+                    None => db.vector_search(c, &request.vector, request.k)?,
+                };
+                Response::ok(serde_json::to_value(results)?)
+            }
+
+            _ => Response::error(404, format!("No route for {} /{}", method, segments.join("/"))),
+        };
+
+        Ok(response)
+    }
+}
+
+/// The collection a request touches, the access it needs, and the
+/// operation the audit log records for it
+fn operation<'a>(method: &str, segments: &[&'a str]) -> Option<(&'a str, Access, &'static str)> {
+    let (collection, access, name) = match (method, segments) {
+        ("POST", ["collections", c, "documents"]) => (c, Access::Write, "insert"),
+        ("PUT", ["collections", c, "documents", _]) => (c, Access::Write, "update"),
+        ("DELETE", ["collections", c, "documents", _]) => (c, Access::Write, "delete"),
+        ("GET", ["collections", c, ..]) | ("POST", ["collections", c, "query"]) => (c, Access::Read, "read"),
+        ("POST", ["vectors", c]) => (c, Access::Write, "create_vector_collection"),
+        ("DELETE", ["vectors", c]) => (c, Access::Write, "drop_vector_collection"),
+        ("POST", ["vectors", c, "vectors"]) => (c, Access::Write, "insert_vector"),
+        ("DELETE", ["vectors", c, "vectors", _]) => (c, Access::Write, "delete_vector"),
+        ("GET", ["vectors", c, ..]) | ("POST", ["vectors", c, "search"]) => (c, Access::Read, "read"),
+        _ => return None,
+    };
+    Some((collection, access, name))
+}
+
+fn json_body<T: DeserializeOwned>(body: &[u8]) -> Result<T> {
+    serde_json::from_slice(body).map_err(|e| KeraDBError::InvalidQuery(format!("Invalid JSON body: {}", e)))
+}
+
+fn query_param(query: &HashMap<String, String>, name: &str) -> Result<Option<usize>> {
+    query
+        .get(name)
+        .map(|v| {
+            v.parse()
+                .map_err(|_| KeraDBError::InvalidQuery(format!("'{}' must be a non-negative integer", name)))
+        })
+        .transpose()
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+/// Decode `%XX` escapes (and `+` as space) in a URL component
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(b) => {
+                        out.push(b);
+                        i += 3;
+                        continue;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            b'+' => out.push(b' '),
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn server(config: ServerConfig) -> (tempfile::TempDir, HttpServer) {
+        let dir = tempdir().unwrap();
+        let db = Database::create(dir.path().join("test.ndb")).unwrap();
+        (dir, HttpServer::new(Arc::new(db), config))
+    }
+
+    #[test]
+    fn test_document_crud_and_query() {
+        let (_dir, server) = server(ServerConfig::default());
+
+        let created = server.handle("POST", "/collections/users/documents", None, br#"{"name":"Alice","age":30}"#);
+        assert_eq!(created.status, 201);
+        let id = created.body["_id"].as_str().unwrap().to_string();
+        server.handle("POST", "/collections/users/documents", None, br#"{"name":"Bob","age":20}"#);
+
+        let doc = server.handle("GET", &format!("/collections/users/documents/{}", id), None, b"");
+        assert_eq!(doc.body["name"], "Alice");
+
+        let found = server.handle("POST", "/collections/users/query", None, br#"{"filter":{"age":{"gt":25}}}"#);
+        assert_eq!(found.status, 200);
+        assert_eq!(found.body.as_array().unwrap().len(), 1);
+
+        let missing = server.handle("GET", "/collections/users/documents/nope", None, b"");
+        assert_eq!(missing.status, 404);
+        let bad = server.handle("POST", "/collections/users/documents", None, b"{not json");
+        assert_eq!(bad.status, 400);
+    }
+
+    #[test]
+    fn test_vector_endpoints() {
+        let (_dir, server) = server(ServerConfig::default());
+
+        let created = server.handle("POST", "/vectors/emb", None, br#"{"dimensions":3}"#);
+        assert_eq!(created.status, 201);
+        server.handle("POST", "/vectors/emb/vectors", None, br#"{"vector":[1,0,0],"id":"doc 1"}"#);
+        server.handle("POST", "/vectors/emb/vectors", None, br#"{"vector":[0,1,0]}"#);
+
+        let results = server.handle("POST", "/vectors/emb/search", None, br#"{"vector":[1,0.1,0],"k":1}"#);
+        assert_eq!(results.status, 200);
+        assert_eq!(results.body[0]["document"]["external_id"], "doc 1");
+
+        let deleted = server.handle("DELETE", "/vectors/emb/vectors/doc%201", None, b"");
+        assert_eq!(deleted.status, 200);
+    }
+
+    #[test]
+    fn test_authentication() {
+        let (_dir, server) = server(ServerConfig::default().with_token("secret"));
+
+        assert_eq!(server.handle("GET", "/health", None, b"").status, 200);
+        assert_eq!(server.handle("GET", "/collections", None, b"").status, 401);
+        assert_eq!(server.handle("GET", "/collections", Some("wrong"), b"").status, 401);
+        assert_eq!(server.handle("GET", "/collections", Some("secret"), b"").status, 200);
+
+        let metrics = server.handle("GET", "/metrics", Some("secret"), b"");
+        assert_eq!(metrics.content_type, "text/plain; version=0.0.4");
+        assert!(metrics.body.as_str().unwrap().contains("keradb_operation_duration_seconds_bucket"));
+    }
+
+    #[test]
+    fn test_user_permissions_and_audit() {
+        let dir = tempdir().unwrap();
+        let system = Arc::new(Database::create(dir.path().join("system.ndb")).unwrap());
+        let access = Arc::new(super::super::AccessControl::new(system).with_database("shop"));
+        access.add_user("alice", Some("s3cret"), false).unwrap();
+        access.grant("alice", "shop", "orders", Access::Write).unwrap();
+        access.grant("alice", "shop", "products", Access::Read).unwrap();
+        let (_dir, server) = server(ServerConfig::default().with_token("admin").with_access_control(access.clone()));
+
+        let alice = Credentials::Basic { user: "alice".into(), password: "s3cret".into() };
+        let handle = |method, url, body: &[u8]| server.handle_with_credentials(method, url, Some(&alice), body);
+
+        assert_eq!(server.handle("POST", "/collections/products/documents", Some("admin"), br#"{"_id":"p1"}"#).status, 201);
+        server.handle("POST", "/collections/users/documents", Some("admin"), br#"{"_id":"u1"}"#);
+
+        assert_eq!(handle("POST", "/collections/orders/documents", br#"{"_id":"o1"}"#).status, 201);
+        assert_eq!(handle("GET", "/collections/products/documents/p1", b"").status, 200);
+        assert_eq!(handle("DELETE", "/collections/products/documents/p1", b"").status, 403);
+        assert_eq!(handle("GET", "/collections/users/count", b"").status, 403);
+
+        let listed = handle("GET", "/collections", b"");
+        let mut names: Vec<_> = listed.body.as_array().unwrap().iter().map(|c| c["name"].to_string()).collect();
+        names.sort();
+        assert_eq!(names, [r#""orders""#, r#""products""#]);
+
+        let wrong = Credentials::Basic { user: "alice".into(), password: "nope".into() };
+        assert_eq!(server.handle_with_credentials("GET", "/collections", Some(&wrong), b"").status, 401);
+
+        let log = access.audit_log(10).unwrap();
+        let entries: Vec<_> = log.iter().map(|e| (e.user.as_str(), e.operation.as_str(), e.id.as_deref())).collect();
+        assert_eq!(
+            entries,
+            [("token", "insert", Some("p1")), ("token", "insert", Some("u1")), ("alice", "insert", Some("o1"))]
+        );
+    }
+}
@@ -0,0 +1,155 @@
+//! Webhooks: document changes POSTed to HTTP endpoints
+//!
+//! A [`Webhook`] is a [hook](crate::hooks) on every insert, update and delete
+//! in a collection that POSTs the [`ChangeEvent`] as JSON to a URL, so
+//! services outside the process can react to changes to a served database:
+//!
+//! ```text
+//! POST /keradb-hook HTTP/1.1
+//! Content-Type: application/json
+//!
+//! {"seq": 12, "operation": "insert", "collection": "users", "doc_id": "...", "document": {...}, "timestamp": 1700000000000}
+//! ```
+//!
+//! Events are delivered in order from a background thread, so a slow
+//! endpoint never holds up writes. A failed delivery is retried a few times
+//! before the event is dropped with a warning, as are new events while
+//! [`QUEUE_CAPACITY`] are already waiting.
+
+use crate::error::{KeraDBError, Result};
+use crate::hooks::{HookId, HookKind};
+use crate::oplog::ChangeEvent;
+use crate::Database;
+use std::sync::mpsc::{self, Receiver, TrySendError};
+use std::time::Duration;
+
+/// Events that may wait for delivery to one webhook
+pub const QUEUE_CAPACITY: usize = 1024;
+
+/// Attempts per event, doubling the delay after each failure
+const ATTEMPTS: u32 = 3;
+
+/// An HTTP endpoint notified of writes to a collection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Webhook {
+    /// Collection name, or `*` for every collection
+    collection: String,
+    url: String,
+}
+
+impl Webhook {
+    /// POST writes to `collection` (`*` for all) to `url`
+    pub fn new(collection: &str, url: &str) -> Result<Self> {
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err(KeraDBError::InvalidQuery(format!("Expected an http:// or https:// URL, got '{}'", url)));
+        }
+        Ok(Self {
+            collection: collection.to_string(),
+            url: url.to_string(),
+        })
+    }
+
+    /// Parse `[COLLECTION=]URL`, as `keradb serve --webhook` takes
+    pub fn parse(spec: &str) -> Result<Self> {
+        match spec.split_once('=') {
+            // `=` may also appear in the URL's query string
+            Some((collection, url)) if !collection.contains("://") => Self::new(collection, url),
+            _ => Self::new("*", spec),
+        }
+    }
+
+    /// The collection whose writes are sent, or `*`
+    pub fn collection(&self) -> &str {
+        &self.collection
+    }
+
+    /// Where events are POSTed
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Start sending `db`'s writes; remove the returned hooks to stop
+    pub fn register(&self, db: &Database) -> Vec<HookId> {
+        let (sender, receiver) = mpsc::sync_channel::<ChangeEvent>(QUEUE_CAPACITY);
+        let url = self.url.clone();
+        // Exits once the hooks, and with them the sender, are dropped
+        std::thread::spawn(move || deliver(&url, receiver));
+
+        HookKind::ALL
+            .into_iter()
+            .map(|kind| {
+                let sender = sender.clone();
+                let url = self.url.clone();
+                db.register_hook(&self.collection, kind, move |_, event| {
+                    if let Err(TrySendError::Full(event)) = sender.try_send(event.clone()) {
+                        tracing::warn!("webhook {} is behind; dropped event {}", url, event.seq);
+                    }
+                })
+            })
+            .collect()
+    }
+}
+
+fn deliver(url: &str, events: Receiver<ChangeEvent>) {
+    let agent = ureq::AgentBuilder::new().timeout(Duration::from_secs(10)).build();
+    for event in events {
+        let body = match serde_json::to_string(&event) {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::warn!("webhook {} cannot send event {}: {}", url, event.seq, e);
+                continue;
+            }
+        };
+        let mut delay = Duration::from_millis(500);
+        for attempt in 1..=ATTEMPTS {
+            match agent.post(url).set("Content-Type", "application/json").send_string(&body) {
+                Ok(_) => break,
+                Err(e) if attempt == ATTEMPTS => {
+                    tracing::warn!("webhook {} failed; dropped event {}: {}", url, event.seq, e);
+                }
+                Err(_) => {
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Webhook::parse("http://localhost:9000/hook").unwrap().collection(), "*");
+        let hook = Webhook::parse("users=https://example.com/hook?a=b").unwrap();
+        assert_eq!((hook.collection(), hook.url()), ("users", "https://example.com/hook?a=b"));
+        assert_eq!(Webhook::parse("http://example.com/?a=b").unwrap().url(), "http://example.com/?a=b");
+        assert!(Webhook::parse("users=ftp://example.com").is_err());
+    }
+
+    #[test]
+    fn test_posts_changes() {
+        let endpoint = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", endpoint.server_addr().to_ip().unwrap());
+        let db = Database::in_memory().unwrap();
+        Webhook::new("users", &url).unwrap().register(&db);
+
+        db.insert("orders", json!({"total": 5})).unwrap();
+        let id = db.insert("users", json!({"name": "Alice"})).unwrap();
+        db.delete("users", &id).unwrap();
+
+        for operation in ["insert", "delete"] {
+            let mut request = endpoint.recv_timeout(Duration::from_secs(10)).unwrap().expect("no webhook request");
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body).unwrap();
+            let event: Value = serde_json::from_str(&body).unwrap();
+            assert_eq!(event["operation"], operation);
+            assert_eq!(event["collection"], "users");
+            assert_eq!(event["document"]["name"], "Alice");
+            request.respond(tiny_http::Response::empty(204)).unwrap();
+        }
+    }
+}
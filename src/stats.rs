@@ -0,0 +1,166 @@
+//! Database statistics
+//!
+//! [`Database::stats`](crate::Database::stats) walks every page of the data
+//! file and returns a [`DatabaseStats`]: how the file's pages are used, how
+//! much space each collection takes, the page cache counters and the stats of
+//! every vector collection. Unlike [`metrics`](crate::metrics) it reads the
+//! whole file, so it is meant for tools and diagnostics rather than polling.
+
+use crate::storage::pager::DatabaseInfo;
+use crate::storage::CacheStats;
+use crate::vector::VectorCollectionStats;
+use serde::Serialize;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Number of pages of each type in the data file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct PageCounts {
+    pub total: u32,
+    pub meta: u32,
+    pub data: u32,
+    pub index: u32,
+    /// Pages freed by deletes and not yet reused
+    pub free: u32,
+    pub vector_data: u32,
+    pub vector_index: u32,
+    /// Pages holding document attachments
+    pub blob: u32,
+    /// Pages that failed their checksum or could not be read
+    pub unreadable: u32,
+}
+
+/// Space used by one document collection
+#[derive(Debug, Clone, Serialize)]
+pub struct CollectionStats {
+    pub name: String,
+    pub documents: usize,
+    /// Serialized size of all documents, excluding page overhead
+    pub bytes: u64,
+    /// Data pages holding at least one of its documents
+    pub pages: u32,
+    /// Approximate memory taken by its primary-key index
+    pub index_bytes: u64,
+}
+
+impl CollectionStats {
+    /// Mean serialized document size in bytes
+    pub fn average_document_size(&self) -> u64 {
+        self.bytes.checked_div(self.documents as u64).unwrap_or(0)
+    }
+}
+
+/// Point-in-time statistics for a whole database
+#[derive(Debug, Clone, Serialize)]
+pub struct DatabaseStats {
+    pub path: PathBuf,
+    /// UUID, creation time and last writer, from the file header
+    pub info: DatabaseInfo,
+    /// Size of the data file on disk
+    pub file_size: u64,
+    /// Size of the vector file on disk (0 if there is none)
+    pub vector_file_size: u64,
+    pub page_size: usize,
+    pub pages: PageCounts,
+    /// Document collections, sorted by name
+    pub collections: Vec<CollectionStats>,
+    pub cache: CacheStats,
+    /// Vector collections, sorted by name
+    pub vector_collections: Vec<VectorCollectionStats>,
+}
+
+impl DatabaseStats {
+    /// Documents across all collections
+    pub fn documents(&self) -> usize {
+        self.collections.iter().map(|c| c.documents).sum()
+    }
+
+    /// Vectors across all vector collections
+    pub fn vectors(&self) -> usize {
+        self.vector_collections.iter().map(|c| c.vector_count).sum()
+    }
+}
+
+impl fmt::Display for DatabaseStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mb = |bytes: u64| bytes as f64 / 1024.0 / 1024.0;
+        let pages = &self.pages;
+
+        writeln!(f, "Database: {}", self.path.display())?;
+        writeln!(f, "UUID: {}", self.info.uuid)?;
+        match self.info.created_at {
+            Some(created) => writeln!(f, "Created: {}", created.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))?,
+            None => writeln!(f, "Created: unknown")?,
+        }
+        writeln!(f, "Last Written By: KeraDB {}", self.info.last_writer_version.as_deref().unwrap_or("unknown"))?;
+        writeln!(f, "Size: {:.2} MB ({:.2} MB vectors)", mb(self.file_size), mb(self.vector_file_size))?;
+        writeln!(
+            f,
+            "Pages: {} x {} bytes ({} data, {} blob, {} free, {} other, {} unreadable)",
+            pages.total,
+            self.page_size,
+            pages.data,
+            pages.blob,
+            pages.free,
+            pages.meta + pages.index + pages.vector_data + pages.vector_index,
+            pages.unreadable
+        )?;
+        writeln!(
+            f,
+            "Cache: {}/{} pages, {} hits, {} misses",
+            self.cache.pages, self.cache.capacity, self.cache.hits, self.cache.misses
+        )?;
+        writeln!(f, "Collections: {}", self.collections.len())?;
+        writeln!(f, "Total Documents: {}", self.documents())?;
+        for c in &self.collections {
+            writeln!(f, "  {} - {} documents, {:.1} KB", c.name, c.documents, c.bytes as f64 / 1024.0)?;
+        }
+        writeln!(f, "Vector Collections: {}", self.vector_collections.len())?;
+        writeln!(f, "Total Vectors: {}", self.vectors())?;
+        for v in &self.vector_collections {
+            writeln!(
+                f,
+                "  {} - {} vectors, {} dims, {:?}, {:.2} MB in memory",
+                v.name,
+                v.vector_count,
+                v.dimensions,
+                v.distance,
+                mb(v.memory_bytes as u64)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vector::VectorConfig;
+    use crate::Database;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_stats_count_pages_and_collections() {
+        let dir = tempdir().unwrap();
+        let db = Database::create(dir.path().join("test.ndb")).unwrap();
+        let alice = db.insert("users", json!({"name": "Alice"})).unwrap();
+        db.insert("users", json!({"name": "Bob"})).unwrap();
+        db.insert("orders", json!({"total": 12})).unwrap();
+        db.delete("users", &alice).unwrap();
+        db.create_vector_collection("embeddings", VectorConfig::new(3)).unwrap();
+        db.insert_vector("embeddings", vec![1.0, 0.0, 0.0], None).unwrap();
+        db.sync().unwrap();
+
+        let stats = db.stats().unwrap();
+        assert_eq!((stats.pages.total, stats.pages.data, stats.pages.free), (3, 2, 1));
+        assert_eq!(stats.file_size, 64 + 3 * 4096);
+        assert!(stats.vector_file_size > 0);
+
+        let names: Vec<_> = stats.collections.iter().map(|c| (c.name.as_str(), c.documents)).collect();
+        assert_eq!(names, vec![("orders", 1), ("users", 1)]);
+        assert!(stats.collections.iter().all(|c| c.bytes > 0 && c.pages == 1 && c.index_bytes > 0));
+        assert_eq!(stats.collections[1].average_document_size(), stats.collections[1].bytes);
+        assert_eq!((stats.documents(), stats.vectors()), (2, 1));
+        assert!(stats.to_string().contains("users - 1 documents"));
+    }
+}
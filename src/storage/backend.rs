@@ -0,0 +1,557 @@
+//! Where a database's bytes live
+//!
+//! A [`Pager`](super::Pager) reads and writes pages at fixed offsets through
+//! a [`StorageBackend`]. [`FileBackend`] is the usual one, a locked file on
+//! disk. [`MemoryBackend`] keeps everything in memory, for tests and for
+//! hosts that persist the bytes themselves, e.g. to IndexedDB from
+//! JavaScript. In browser builds, `OpfsBackend` stores them in the origin
+//! private file system.
+
+use crate::error::{KeraDBError, Result};
+use parking_lot::{Mutex, RwLock};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Alignment of offsets, lengths and buffers for direct I/O
+const BLOCK_SIZE: usize = 4096;
+
+/// Positional byte storage
+///
+/// Reads and writes take an offset rather than moving a cursor, so a backend
+/// can be shared between threads. Callers never read a range while it is
+/// being written.
+pub trait StorageBackend: Send + Sync {
+    /// Fill `buf` from `offset`; reading past the end is an `UnexpectedEof` error
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()>;
+
+    /// Write all of `buf` at `offset`, growing the storage if needed
+    fn write_all_at(&self, buf: &[u8], offset: u64) -> io::Result<()>;
+
+    /// Current size in bytes
+    fn size(&self) -> io::Result<u64>;
+
+    /// Grow or shrink to exactly `size` bytes
+    fn set_size(&self, size: u64) -> io::Result<()>;
+
+    /// Make everything written so far durable, surviving power loss and not
+    /// just a crash of the process
+    fn sync(&self) -> io::Result<()>;
+}
+
+/// How a [`FileBackend`] uses the file system
+///
+/// Both options only take effect on Linux; elsewhere, and on file systems
+/// that do not support them, the file is read and written as usual.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileOptions {
+    /// Bypass the OS page cache (`O_DIRECT`), so pages are cached once, in
+    /// the database's own buffer pool. Pages do not line up with disk
+    /// blocks, so a write first reads the blocks it only partly covers, and
+    /// the file's size is rounded up to whole 4KB blocks.
+    pub direct_io: bool,
+    /// Reserve disk space in steps of this many bytes as the file grows
+    /// (`fallocate`), keeping it in fewer fragments; 0 grows it as written
+    pub preallocate: u64,
+}
+
+/// A file on disk
+///
+/// The file is locked for as long as the backend is alive: exclusively when
+/// opened for writing, shared when opened read-only. Locks are advisory, so
+/// they only guard against other KeraDB instances, in this process or others.
+pub struct FileBackend {
+    file: File,
+    read_only: bool,
+    /// Serializes the read-modify-write of partly covered blocks when the
+    /// file was opened for direct I/O
+    direct: Option<Mutex<()>>,
+    /// Preallocation step; set to 0 if the file system refuses it
+    preallocate: AtomicU64,
+    /// End of the space reserved so far
+    reserved: Mutex<u64>,
+}
+
+impl FileBackend {
+    /// Create or truncate the file at `path` and lock it exclusively
+    pub fn create(path: &Path) -> Result<Self> {
+        Self::create_with_options(path, FileOptions::default())
+    }
+
+    /// Create or truncate the file at `path` with `options` and lock it
+    /// exclusively
+    pub fn create_with_options(path: &Path, options: FileOptions) -> Result<Self> {
+        let (file, direct) = open_file(OpenOptions::new().read(true).write(true).create(true).truncate(true), path, options)?;
+        lock(&file, path, false)?;
+        // Otherwise the file could vanish in a crash even after its pages
+        // were synced
+        if let Err(e) = sync_parent(path) {
+            tracing::debug!("Could not sync the directory of {}: {}", path.display(), e);
+        }
+        Self::new(file, false, direct, options)
+    }
+
+    /// Open the existing file at `path`, locked shared when `read_only`
+    pub fn open(path: &Path, read_only: bool) -> Result<Self> {
+        Self::open_with_options(path, read_only, FileOptions::default())
+    }
+
+    /// Open the existing file at `path` with `options`, locked shared when
+    /// `read_only`
+    pub fn open_with_options(path: &Path, read_only: bool, options: FileOptions) -> Result<Self> {
+        let (file, direct) = open_file(OpenOptions::new().read(true).write(!read_only), path, options)?;
+        lock(&file, path, read_only)?;
+        Self::new(file, read_only, direct, options)
+    }
+
+    fn new(file: File, read_only: bool, direct: bool, options: FileOptions) -> Result<Self> {
+        let size = file.metadata()?.len();
+        Ok(Self {
+            file,
+            read_only,
+            direct: direct.then(|| Mutex::new(())),
+            preallocate: AtomicU64::new(options.preallocate),
+            reserved: Mutex::new(size),
+        })
+    }
+
+    /// Whether the page cache is bypassed
+    pub fn is_direct(&self) -> bool {
+        self.direct.is_some()
+    }
+
+    /// Reserve space up to at least `end`, a step at a time
+    fn reserve(&self, end: u64) {
+        let step = self.preallocate.load(Ordering::Relaxed);
+        if step == 0 {
+            return;
+        }
+        let mut reserved = self.reserved.lock();
+        if end <= *reserved {
+            return;
+        }
+        let target = end.div_ceil(step) * step;
+        match preallocate(&self.file, *reserved, target - *reserved) {
+            Ok(()) => *reserved = target,
+            Err(e) => {
+                tracing::warn!("Could not preallocate database file space: {}; growing it as written", e);
+                self.preallocate.store(0, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Write through an aligned buffer covering whole blocks, keeping the
+    /// bytes around `buf` in the first and last of them
+    fn write_direct(&self, lock: &Mutex<()>, buf: &[u8], offset: u64) -> io::Result<()> {
+        let (start, len) = block_range(offset, buf.len());
+        let mut storage = Vec::new();
+        let blocks = aligned(&mut storage, len);
+        let head = (offset - start) as usize;
+        let _write = lock.lock();
+        if head > 0 {
+            read_up_to(&self.file, &mut blocks[..BLOCK_SIZE], start)?;
+        }
+        if !(head + buf.len()).is_multiple_of(BLOCK_SIZE) && (len > BLOCK_SIZE || head == 0) {
+            read_up_to(&self.file, &mut blocks[len - BLOCK_SIZE..], start + (len - BLOCK_SIZE) as u64)?;
+        }
+        blocks[head..head + buf.len()].copy_from_slice(buf);
+        write_all_at(&self.file, blocks, start)
+    }
+
+    /// Read through an aligned buffer covering whole blocks
+    fn read_direct(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        let (start, len) = block_range(offset, buf.len());
+        let mut storage = Vec::new();
+        let blocks = aligned(&mut storage, len);
+        let head = (offset - start) as usize;
+        if read_up_to(&self.file, blocks, start)? < head + buf.len() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.copy_from_slice(&blocks[head..head + buf.len()]);
+        Ok(())
+    }
+}
+
+impl StorageBackend for FileBackend {
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        match &self.direct {
+            Some(_) => self.read_direct(buf, offset),
+            None => read_exact_at(&self.file, buf, offset),
+        }
+    }
+
+    fn write_all_at(&self, buf: &[u8], offset: u64) -> io::Result<()> {
+        self.reserve(offset + buf.len() as u64);
+        match &self.direct {
+            Some(lock) => self.write_direct(lock, buf, offset),
+            None => write_all_at(&self.file, buf, offset),
+        }
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.file.metadata()?.len())
+    }
+
+    fn set_size(&self, size: u64) -> io::Result<()> {
+        let mut reserved = self.reserved.lock();
+        self.file.set_len(size)?;
+        // Shrinking gives back the space reserved past the end
+        *reserved = (*reserved).min(size);
+        Ok(())
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        // Nothing to flush, and Windows refuses to flush a read-only handle
+        if self.read_only {
+            return Ok(());
+        }
+        sync_file(&self.file)
+    }
+}
+
+/// Make a file's contents and size durable
+///
+/// This is the strongest flush each platform offers: `fsync` on Linux and
+/// other Unixes, `FlushFileBuffers` on Windows, and `F_FULLFSYNC` on macOS
+/// and iOS, where a plain `fsync` leaves writes in the drive's volatile
+/// cache. File systems that refuse `F_FULLFSYNC`, such as network shares,
+/// get a plain `fsync`, the best they offer.
+#[cfg(target_vendor = "apple")]
+pub(crate) fn sync_file(file: &File) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    // `sync_all` is `F_FULLFSYNC` on Apple platforms
+    match file.sync_all() {
+        Err(e) if matches!(e.raw_os_error(), Some(libc::ENOTSUP) | Some(libc::EINVAL)) => {
+            // SAFETY: fsync only reads the descriptor, which `file` owns
+            if unsafe { libc::fsync(file.as_raw_fd()) } == 0 {
+                Ok(())
+            } else {
+                Err(io::Error::last_os_error())
+            }
+        }
+        result => result,
+    }
+}
+
+#[cfg(not(target_vendor = "apple"))]
+pub(crate) fn sync_file(file: &File) -> io::Result<()> {
+    file.sync_all()
+}
+
+/// Make the entries created, renamed or removed in `dir` durable
+///
+/// Unix file systems keep directory entries apart from the files, so a new
+/// file can be lost in a crash unless its directory is synced too. Windows
+/// makes them durable with the files, and cannot open a directory as one.
+#[cfg(unix)]
+pub(crate) fn sync_dir(dir: &Path) -> io::Result<()> {
+    sync_file(&File::open(dir)?)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// [`sync_dir`] the directory holding `path`, which is the current one for a
+/// bare file name
+pub(crate) fn sync_parent(path: &Path) -> io::Result<()> {
+    sync_dir(path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new(".")))
+}
+
+/// Open a file, for direct I/O if asked and supported; the flag says
+/// whether it was
+#[cfg(target_os = "linux")]
+fn open_file(options: &mut OpenOptions, path: &Path, file_options: FileOptions) -> io::Result<(File, bool)> {
+    use std::os::unix::fs::OpenOptionsExt;
+    if file_options.direct_io {
+        match options.clone().custom_flags(libc::O_DIRECT).open(path) {
+            Ok(file) => return Ok((file, true)),
+            Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {
+                tracing::warn!("{} does not support direct I/O; using the page cache", path.display())
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok((options.open(path)?, false))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_file(options: &mut OpenOptions, path: &Path, file_options: FileOptions) -> io::Result<(File, bool)> {
+    if file_options.direct_io {
+        tracing::warn!("Direct I/O is only supported on Linux; using the page cache for {}", path.display());
+    }
+    Ok((options.open(path)?, false))
+}
+
+/// Allocate `len` bytes of disk space from `offset` without changing the
+/// file's size
+#[cfg(target_os = "linux")]
+fn preallocate(file: &File, offset: u64, len: u64) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    // SAFETY: fallocate only reads its integer arguments; the descriptor is
+    // owned by `file`, which outlives the call
+    let result = unsafe { libc::fallocate(file.as_raw_fd(), libc::FALLOC_FL_KEEP_SIZE, offset as libc::off_t, len as libc::off_t) };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn preallocate(_file: &File, _offset: u64, _len: u64) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "preallocation is only supported on Linux"))
+}
+
+/// Start and length of the whole blocks covering `len` bytes from `offset`
+fn block_range(offset: u64, len: usize) -> (u64, usize) {
+    let start = offset / BLOCK_SIZE as u64 * BLOCK_SIZE as u64;
+    let end = (offset + len as u64).div_ceil(BLOCK_SIZE as u64) * BLOCK_SIZE as u64;
+    (start, (end - start) as usize)
+}
+
+/// `len` zeroed bytes inside `storage`, aligned for direct I/O
+fn aligned(storage: &mut Vec<u8>, len: usize) -> &mut [u8] {
+    *storage = vec![0u8; len + BLOCK_SIZE];
+    let start = storage.as_ptr().align_offset(BLOCK_SIZE);
+    &mut storage[start..start + len]
+}
+
+/// Read into `buf` until it is full or the file ends, returning the bytes read
+fn read_up_to(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match read_at(file, &mut buf[read..], offset + read as u64) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(read)
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    use std::os::unix::fs::FileExt;
+    file.read_at(buf, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    use std::os::windows::fs::FileExt;
+    file.seek_read(buf, offset)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn read_at(mut file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    use std::io::{Read, Seek, SeekFrom};
+    file.seek(SeekFrom::Start(offset))?;
+    file.read(buf)
+}
+
+#[cfg(unix)]
+fn write_all_at(file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.write_all_at(buf, offset)
+}
+
+#[cfg(windows)]
+fn write_all_at(file: &File, mut buf: &[u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        match file.seek_write(buf, offset) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => {
+                buf = &buf[n..];
+                offset += n as u64;
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Without positional I/O, seek first; the targets this covers (WASI) run
+/// the database on one thread
+#[cfg(not(any(unix, windows)))]
+fn write_all_at(mut file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+    use std::io::{Seek, SeekFrom, Write};
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(buf)
+}
+
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.read_exact_at(buf, offset)
+}
+
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        match file.seek_read(buf, offset) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn read_exact_at(mut file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    use std::io::{Read, Seek, SeekFrom};
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(buf)
+}
+
+/// Take an advisory lock on the database file without blocking
+fn lock(file: &File, path: &Path, shared: bool) -> Result<()> {
+    let result = if shared { file.try_lock_shared() } else { file.try_lock() };
+    match result {
+        Ok(()) => Ok(()),
+        Err(TryLockError::WouldBlock) => Err(KeraDBError::Locked(path.display().to_string())),
+        Err(TryLockError::Error(e)) => Err(e.into()),
+    }
+}
+
+/// Bytes in memory
+///
+/// Clones share the same bytes, so a host can keep one to save the contents
+/// with [`to_bytes`](Self::to_bytes) after [`Database::sync`](crate::Database::sync),
+/// and later reopen them with [`from_bytes`](Self::from_bytes).
+#[derive(Clone, Default)]
+pub struct MemoryBackend {
+    bytes: Arc<RwLock<Vec<u8>>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self { bytes: Arc::new(RwLock::new(bytes)) }
+    }
+
+    /// A copy of the current contents
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.bytes.read().clone()
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        let bytes = self.bytes.read();
+        let start = usize::try_from(offset).map_err(|_| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        let range = bytes.get(start..start.saturating_add(buf.len())).ok_or(io::ErrorKind::UnexpectedEof)?;
+        buf.copy_from_slice(range);
+        Ok(())
+    }
+
+    fn write_all_at(&self, buf: &[u8], offset: u64) -> io::Result<()> {
+        let mut bytes = self.bytes.write();
+        let start = usize::try_from(offset).map_err(|_| io::Error::from(io::ErrorKind::OutOfMemory))?;
+        let end = start + buf.len();
+        if bytes.len() < end {
+            bytes.resize(end, 0);
+        }
+        bytes[start..end].copy_from_slice(buf);
+        Ok(())
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.bytes.read().len() as u64)
+    }
+
+    fn set_size(&self, size: u64) -> io::Result<()> {
+        let size = usize::try_from(size).map_err(|_| io::Error::from(io::ErrorKind::OutOfMemory))?;
+        self.bytes.write().resize(size, 0);
+        Ok(())
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_backend() {
+        let backend = MemoryBackend::new();
+        backend.write_all_at(b"world", 6).unwrap();
+        backend.write_all_at(b"hello", 0).unwrap();
+        assert_eq!(backend.size().unwrap(), 11);
+        assert_eq!(backend.to_bytes(), b"hello\0world");
+
+        let mut buf = [0u8; 5];
+        backend.read_exact_at(&mut buf, 6).unwrap();
+        assert_eq!(&buf, b"world");
+        let err = backend.read_exact_at(&mut buf, 8).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        // Clones share the bytes
+        let copy = MemoryBackend::from_bytes(backend.to_bytes());
+        backend.clone().set_size(5).unwrap();
+        assert_eq!(backend.to_bytes(), b"hello");
+        assert_eq!(copy.size().unwrap(), 11);
+    }
+
+    #[test]
+    fn test_sync_file_and_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("synced.ndb");
+        let backend = FileBackend::create(&path).unwrap();
+        backend.write_all_at(b"durable", 0).unwrap();
+        backend.sync().unwrap();
+        sync_dir(dir.path()).unwrap();
+        drop(backend);
+        // A relative path's directory is the current one
+        sync_parent(Path::new("synced.ndb")).unwrap();
+
+        // Syncing a read-only file has nothing to do
+        FileBackend::open(&path, true).unwrap().sync().unwrap();
+    }
+
+    #[test]
+    fn test_direct_io_and_preallocation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("direct.ndb");
+        let options = FileOptions { direct_io: true, preallocate: 1 << 20 };
+        let backend = FileBackend::create_with_options(&path, options).unwrap();
+
+        // Writes that straddle blocks keep the bytes around them
+        backend.write_all_at(&[1; 100], 0).unwrap();
+        backend.write_all_at(&[2; 5000], 64).unwrap();
+        backend.write_all_at(&[3; 10], 5000).unwrap();
+        let mut buf = vec![0u8; 5064];
+        backend.read_exact_at(&mut buf, 0).unwrap();
+        assert_eq!(&buf[..64], &[1; 64]);
+        assert_eq!(&buf[64..5000], &[2; 4936]);
+        assert_eq!(&buf[5000..5010], &[3; 10]);
+        assert_eq!(&buf[5010..], &[2; 54]);
+        let err = backend.read_exact_at(&mut buf, 8192).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        drop(backend);
+
+        // Whether or not the file system supported either option, the
+        // bytes read back the same without them
+        let backend = FileBackend::open(&path, true).unwrap();
+        let mut plain = vec![0u8; 5064];
+        backend.read_exact_at(&mut plain, 0).unwrap();
+        assert_eq!(plain, buf);
+    }
+}
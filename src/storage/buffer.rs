@@ -1,12 +1,26 @@
 use crate::storage::pager::Page;
 use parking_lot::RwLock;
+use serde::Serialize;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 /// Simple LRU cache for pages
 pub struct BufferPool {
     cache: Arc<RwLock<HashMap<u32, Page>>>,
     max_size: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// Page cache counters
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Pages currently cached
+    pub pages: usize,
+    pub capacity: usize,
 }
 
 impl BufferPool {
@@ -14,11 +28,16 @@ impl BufferPool {
         Self {
             cache: Arc::new(RwLock::new(HashMap::new())),
             max_size,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
         }
     }
 
     pub fn get(&self, page_num: u32) -> Option<Page> {
-        self.cache.read().get(&page_num).cloned()
+        let page = self.cache.read().get(&page_num).cloned();
+        let counter = if page.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        page
     }
 
     pub fn put(&self, page: Page) {
@@ -45,6 +64,15 @@ impl BufferPool {
     pub fn size(&self) -> usize {
         self.cache.read().len()
     }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            pages: self.size(),
+            capacity: self.max_size,
+        }
+    }
 }
 
 #[cfg(test)]
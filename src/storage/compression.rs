@@ -0,0 +1,285 @@
+//! Document compression with trained dictionaries
+//!
+//! Small JSON documents barely compress on their own: there is little
+//! repetition within one document, but a great deal between documents of a
+//! collection, which share keys and often values. [`train`] picks the
+//! substrings common to a sample of a collection's documents into a
+//! dictionary, which then primes raw DEFLATE for each document, so the
+//! shared parts cost a few bytes each.
+//!
+//! Dictionaries are kept in [`PageType::Meta`](crate::types::PageType) pages,
+//! the catalog, and referenced by ID from each compressed document, so a
+//! document compressed with an older dictionary stays readable. A stored
+//! document's length has [`COMPRESSED`] set when it is compressed.
+
+use crate::error::{KeraDBError, Result};
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Flag on a data page's length: the document is compressed
+pub(crate) const COMPRESSED: u32 = 1 << 31;
+
+/// Kind byte of a catalog page holding a dictionary
+const DICTIONARY_PAGE: u8 = 1;
+
+/// Length of the substrings counted while training
+const SEGMENT_LEN: usize = 8;
+
+/// A dictionary trained on one collection's documents
+#[derive(Debug)]
+pub(crate) struct Dictionary {
+    pub(crate) id: u32,
+    pub(crate) collection: String,
+    bytes: Vec<u8>,
+}
+
+impl Dictionary {
+    pub(crate) fn new(id: u32, collection: &str, bytes: Vec<u8>) -> Self {
+        Self { id, collection: collection.to_string(), bytes }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Compress a serialized document as `[id][raw length][deflate]`
+    pub(crate) fn compress(&self, raw: &[u8]) -> Result<Vec<u8>> {
+        let error = |e: flate2::CompressError| KeraDBError::StorageError(format!("Compression failed: {}", e));
+        let mut compress = Compress::new(Compression::best(), false);
+        compress.set_dictionary(&self.bytes).map_err(error)?;
+        let mut out = Vec::with_capacity(raw.len() / 2 + 64);
+        out.extend_from_slice(&self.id.to_le_bytes());
+        out.extend_from_slice(&(raw.len() as u32).to_le_bytes());
+        loop {
+            let consumed = compress.total_in() as usize;
+            if compress.compress_vec(&raw[consumed..], &mut out, FlushCompress::Finish).map_err(error)? == Status::StreamEnd {
+                return Ok(out);
+            }
+            out.reserve(out.capacity());
+        }
+    }
+
+    /// Decompress what [`compress`](Self::compress) returned
+    pub(crate) fn decompress(&self, payload: &[u8]) -> Result<Vec<u8>> {
+        let invalid = |what: String| KeraDBError::StorageError(format!("Invalid compressed document: {}", what));
+        if payload.len() < 8 {
+            return Err(invalid("too short".to_string()));
+        }
+        let raw_len = u32::from_le_bytes([payload[4], payload[5], payload[6], payload[7]]) as usize;
+        let mut decompress = Decompress::new(false);
+        decompress.set_dictionary(&self.bytes).map_err(|e| invalid(e.to_string()))?;
+        let mut out = Vec::with_capacity(raw_len);
+        let status = decompress
+            .decompress_vec(&payload[8..], &mut out, FlushDecompress::Finish)
+            .map_err(|e| invalid(e.to_string()))?;
+        if status != Status::StreamEnd || out.len() != raw_len {
+            return Err(invalid("truncated".to_string()));
+        }
+        Ok(out)
+    }
+
+    /// Catalog page data: `[kind][id][collection length][collection][dictionary length][dictionary]`
+    pub(crate) fn to_page_data(&self) -> Vec<u8> {
+        let mut data = vec![DICTIONARY_PAGE];
+        data.extend_from_slice(&self.id.to_le_bytes());
+        data.extend_from_slice(&(self.collection.len() as u16).to_le_bytes());
+        data.extend_from_slice(self.collection.as_bytes());
+        data.extend_from_slice(&(self.bytes.len() as u32).to_le_bytes());
+        data.extend_from_slice(&self.bytes);
+        data
+    }
+
+    /// Bytes of a catalog page taken by everything but the dictionary
+    pub(crate) fn page_overhead(collection: &str) -> usize {
+        11 + collection.len()
+    }
+
+    /// Read a catalog page; `None` if it holds something else
+    pub(crate) fn from_page_data(data: &[u8]) -> Result<Option<Self>> {
+        if data.first() != Some(&DICTIONARY_PAGE) {
+            return Ok(None);
+        }
+        let invalid = || KeraDBError::StorageError("Invalid dictionary page".to_string());
+        let field = |at: usize, len: usize| data.get(at..at + len).ok_or_else(invalid);
+        let id = u32::from_le_bytes(field(1, 4)?.try_into().unwrap());
+        let name_len = u16::from_le_bytes(field(5, 2)?.try_into().unwrap()) as usize;
+        let collection = String::from_utf8(field(7, name_len)?.to_vec()).map_err(|_| invalid())?;
+        let len = u32::from_le_bytes(field(7 + name_len, 4)?.try_into().unwrap()) as usize;
+        let bytes = field(11 + name_len, len)?.to_vec();
+        Ok(Some(Self { id, collection, bytes }))
+    }
+}
+
+/// ID of the dictionary a compressed document needs
+pub(crate) fn dictionary_id(payload: &[u8]) -> Option<u32> {
+    payload.get(..4).map(|id| u32::from_le_bytes(id.try_into().unwrap()))
+}
+
+/// The dictionaries in the catalog
+#[derive(Default)]
+pub(crate) struct Dictionaries {
+    by_id: HashMap<u32, Arc<Dictionary>>,
+    /// The dictionary new writes to a collection use
+    active: HashMap<String, Arc<Dictionary>>,
+    /// Catalog page of each dictionary still on disk
+    pages: HashMap<u32, u32>,
+}
+
+impl Dictionaries {
+    pub(crate) fn get(&self, id: u32) -> Option<Arc<Dictionary>> {
+        self.by_id.get(&id).cloned()
+    }
+
+    pub(crate) fn active(&self, collection: &str) -> Option<Arc<Dictionary>> {
+        self.active.get(collection).cloned()
+    }
+
+    pub(crate) fn next_id(&self) -> u32 {
+        self.by_id.keys().max().map_or(1, |id| id + 1)
+    }
+
+    /// Add a dictionary, making it its collection's active one if it is the newest
+    pub(crate) fn add(&mut self, dictionary: Dictionary, page: u32) {
+        let dictionary = Arc::new(dictionary);
+        self.pages.insert(dictionary.id, page);
+        if self.active.get(&dictionary.collection).is_none_or(|active| active.id < dictionary.id) {
+            self.active.insert(dictionary.collection.clone(), dictionary.clone());
+        }
+        self.by_id.insert(dictionary.id, dictionary);
+    }
+
+    /// Forget the catalog pages of a collection's other dictionaries,
+    /// returning them to be freed; the dictionaries stay loaded for reads
+    /// already under way
+    pub(crate) fn retire(&mut self, collection: &str, keep: u32) -> Vec<u32> {
+        let retired: Vec<u32> = self
+            .by_id
+            .values()
+            .filter(|d| d.collection == collection && d.id != keep)
+            .filter_map(|d| self.pages.get(&d.id).copied())
+            .collect();
+        self.pages.retain(|_, page| !retired.contains(page));
+        retired
+    }
+}
+
+/// Outcome of [`Database::train_dictionary`](crate::Database::train_dictionary)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DictionaryStats {
+    pub collection: String,
+    /// Size of the trained dictionary
+    pub dictionary_bytes: usize,
+    /// Documents rewritten with it
+    pub documents: usize,
+    /// Serialized size of those documents
+    pub raw_bytes: u64,
+    /// Stored size of those documents, compressed or not
+    pub stored_bytes: u64,
+}
+
+impl DictionaryStats {
+    /// Stored size as a fraction of the serialized size
+    pub fn ratio(&self) -> f64 {
+        if self.raw_bytes == 0 {
+            return 1.0;
+        }
+        self.stored_bytes as f64 / self.raw_bytes as f64
+    }
+}
+
+/// Build a dictionary of at most `max_size` bytes from sample documents
+///
+/// Substrings found in many samples are joined into segments, which are
+/// ranked by how many bytes they would save. The best segments go last,
+/// where DEFLATE reaches them with the shortest distances.
+pub(crate) fn train(samples: &[Vec<u8>], max_size: usize) -> Vec<u8> {
+    // In how many samples each substring occurs
+    let mut frequency: HashMap<&[u8], u32> = HashMap::new();
+    for sample in samples {
+        let distinct: HashSet<&[u8]> = sample.windows(SEGMENT_LEN).collect();
+        for window in distinct {
+            *frequency.entry(window).or_default() += 1;
+        }
+    }
+    let common = (samples.len() as u32 / 20).max(2);
+
+    // Maximal runs of common substrings, scored by samples times length
+    let mut segments: HashMap<&[u8], u64> = HashMap::new();
+    for sample in samples {
+        let mut i = 0;
+        while i + SEGMENT_LEN <= sample.len() {
+            let start = i;
+            let mut least = u32::MAX;
+            while i + SEGMENT_LEN <= sample.len() {
+                let count = frequency[&sample[i..i + SEGMENT_LEN]];
+                if count < common {
+                    break;
+                }
+                least = least.min(count);
+                i += 1;
+            }
+            if i > start {
+                let segment = &sample[start..i + SEGMENT_LEN - 1];
+                segments.insert(segment, least as u64 * segment.len() as u64);
+            }
+            i += 1;
+        }
+    }
+
+    let mut ranked: Vec<(&[u8], u64)> = segments.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    let mut chosen: Vec<&[u8]> = Vec::new();
+    let mut size = 0;
+    for (segment, _) in ranked {
+        if size == max_size {
+            break;
+        }
+        if chosen.iter().any(|c| c.windows(segment.len()).any(|w| w == segment)) {
+            continue;
+        }
+        let segment = &segment[..segment.len().min(max_size - size)];
+        size += segment.len();
+        chosen.push(segment);
+    }
+    chosen.into_iter().rev().flatten().copied().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Database;
+    use serde_json::json;
+
+    #[test]
+    fn test_dictionary_compression() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("compressed.ndb");
+        let db = Database::create(&path).unwrap();
+        let event = |i: u32| {
+            json!({"type": "page_view", "user_agent": "Mozilla/5.0 (X11; Linux x86_64)", "path": format!("/articles/{}", i), "status": 200})
+        };
+        let ids: Vec<String> = (0..200).map(|i| db.insert("events", event(i)).unwrap()).collect();
+        assert!(db.train_dictionary("empty").is_err());
+
+        let stats = db.train_dictionary("events").unwrap();
+        assert_eq!(stats.documents, 200);
+        assert!(stats.dictionary_bytes > 0);
+        assert!(stats.ratio() < 0.5, "ratio {}", stats.ratio());
+
+        // New writes are compressed too, and everything reads back
+        let late = db.insert("events", event(1000)).unwrap();
+        assert_eq!(db.find_by_id("events", &ids[7]).unwrap().get("path"), Some(json!("/articles/7")));
+        db.update("events", &ids[8], json!({"type": "click"})).unwrap();
+        db.train_dictionary("events").unwrap();
+        db.sync().unwrap();
+        drop(db);
+
+        let db = Database::open(&path).unwrap();
+        assert_eq!(db.count("events"), 201);
+        assert_eq!(db.find_by_id("events", &late).unwrap().get("path"), Some(json!("/articles/1000")));
+        assert_eq!(db.find_by_id("events", &ids[8]).unwrap().get("type"), Some(json!("click")));
+        // Only the newest dictionary is left in the catalog
+        assert_eq!(db.stats().unwrap().pages.meta, 1);
+    }
+}
@@ -1,7 +1,15 @@
+pub mod backend;
 pub mod buffer;
+pub mod compression;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown", not(target_feature = "atomics")))]
+pub mod opfs;
 pub mod pager;
 pub mod serializer;
+pub mod testing;
 
-pub use buffer::BufferPool;
+pub use backend::{FileBackend, FileOptions, MemoryBackend, StorageBackend};
+pub use buffer::{BufferPool, CacheStats};
+#[cfg(all(target_arch = "wasm32", target_os = "unknown", not(target_feature = "atomics")))]
+pub use opfs::OpfsBackend;
 pub use pager::Pager;
 pub use serializer::Serializer;
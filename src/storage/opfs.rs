@@ -0,0 +1,121 @@
+//! Storage in the browser's origin private file system (OPFS)
+//!
+//! OPFS files are private to the page's origin and persist like IndexedDB,
+//! but support synchronous reads and writes at an offset, which is what a
+//! [`Pager`](super::Pager) needs. Synchronous access is only available in
+//! dedicated workers, so run the database in one.
+//!
+//! # Example
+//! ```ignore
+//! let data = OpfsBackend::open("app.ndb").await?;
+//! let vectors = OpfsBackend::open("app.vectors.ndb").await?;
+//! let db = if data.size()? == 0 {
+//!     Database::create_with_backends("app.ndb", Box::new(data), Box::new(vectors), Config::default())?
+//! } else {
+//!     Database::open_with_backends("app.ndb", Box::new(data), Box::new(vectors), false, Config::default())?.0
+//! };
+//! ```
+
+use super::backend::StorageBackend;
+use crate::error::{KeraDBError, Result};
+use std::io;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    FileSystemDirectoryHandle, FileSystemFileHandle, FileSystemGetFileOptions, FileSystemReadWriteOptions,
+    FileSystemSyncAccessHandle, WorkerGlobalScope,
+};
+
+/// A file in the origin private file system
+///
+/// The browser allows one sync access handle per file, so opening a file
+/// that is already open fails, much like [`FileBackend`](super::FileBackend)'s
+/// lock. The handle is closed on drop.
+pub struct OpfsBackend {
+    handle: FileSystemSyncAccessHandle,
+}
+
+// SAFETY: without the `atomics` target feature (see `storage/mod.rs`) there
+// is only one thread, so the handle is never used from another
+unsafe impl Send for OpfsBackend {}
+unsafe impl Sync for OpfsBackend {}
+
+impl OpfsBackend {
+    /// Open the file `name` in the origin's root directory, creating it empty
+    /// if it does not exist
+    pub async fn open(name: &str) -> Result<Self> {
+        let options = FileSystemGetFileOptions::new();
+        options.set_create(true);
+        let file: FileSystemFileHandle = resolve(root().await?.get_file_handle_with_options(name, &options)).await?;
+        let handle = resolve(file.create_sync_access_handle()).await?;
+        Ok(Self { handle })
+    }
+
+    /// Delete the file `name`; it must not be open
+    pub async fn remove(name: &str) -> Result<()> {
+        JsFuture::from(root().await?.remove_entry(name)).await.map_err(js_error)?;
+        Ok(())
+    }
+}
+
+impl Drop for OpfsBackend {
+    fn drop(&mut self) {
+        self.handle.close();
+    }
+}
+
+impl StorageBackend for OpfsBackend {
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        let options = FileSystemReadWriteOptions::new();
+        options.set_at(offset as f64);
+        let read = self.handle.read_with_u8_array_and_options(buf, &options).map_err(io_error)?;
+        if (read as usize) < buf.len() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(())
+    }
+
+    fn write_all_at(&self, buf: &[u8], offset: u64) -> io::Result<()> {
+        let options = FileSystemReadWriteOptions::new();
+        options.set_at(offset as f64);
+        let written = self.handle.write_with_u8_array_and_options(buf, &options).map_err(io_error)?;
+        if (written as usize) < buf.len() {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.handle.get_size().map_err(io_error)? as u64)
+    }
+
+    fn set_size(&self, size: u64) -> io::Result<()> {
+        self.handle.truncate_with_f64(size as f64).map_err(io_error)
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        self.handle.flush().map_err(io_error)
+    }
+}
+
+/// The origin's root OPFS directory, as seen from a worker
+async fn root() -> Result<FileSystemDirectoryHandle> {
+    let scope: WorkerGlobalScope = js_sys::global().dyn_into().map_err(|_| {
+        KeraDBError::StorageError("OPFS storage is only available in a web worker".to_string())
+    })?;
+    resolve(scope.navigator().storage().get_directory()).await
+}
+
+/// Await `promise` and cast what it resolves to
+async fn resolve<T: JsCast>(promise: js_sys::Promise) -> Result<T> {
+    let value = JsFuture::from(promise).await.map_err(js_error)?;
+    value.dyn_into().map_err(|v| KeraDBError::StorageError(format!("Unexpected OPFS value: {:?}", v)))
+}
+
+fn js_error(e: JsValue) -> KeraDBError {
+    KeraDBError::StorageError(format!("OPFS: {:?}", e))
+}
+
+fn io_error(e: JsValue) -> io::Error {
+    io::Error::other(format!("OPFS: {:?}", e))
+}
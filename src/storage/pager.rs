@@ -1,13 +1,108 @@
 use crate::error::{KeraDBError, Result};
 use crate::types::PageType;
-use std::fs::{File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
+use super::backend::{FileBackend, FileOptions, StorageBackend};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::Serialize;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use uuid::Uuid;
 
 /// Magic bytes for NoSQLite files: "NSQL"
 const MAGIC_BYTES: &[u8; 4] = b"NSQL";
 const VERSION: u32 = 1;
+/// File header: magic (0..4), format version (4..8), page size (8..12),
+/// page count (12..16), database UUID (16..32), creation time in Unix
+/// milliseconds (32..40) and the crate version that last opened the file
+/// for writing (40..56); the rest is padding. Files written before the last
+/// three were added hold zeros there.
 const HEADER_SIZE: usize = 64;
+const UUID_OFFSET: usize = 16;
+const CREATED_OFFSET: usize = 32;
+const WRITER_OFFSET: usize = 40;
+const WRITER_LEN: usize = 16;
+
+/// Version of this crate, recorded as the last writer of files it opens
+const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Identity and history of a database, kept in its file header
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DatabaseInfo {
+    /// Identifies the database for as long as it exists; copies and backups
+    /// of the file keep it
+    pub uuid: Uuid,
+    /// When the database was created; `None` if it predates this being
+    /// recorded
+    pub created_at: Option<DateTime<Utc>>,
+    /// Version of KeraDB that last opened the database for writing; `None`
+    /// if none has since this was recorded
+    pub last_writer_version: Option<String>,
+}
+
+impl DatabaseInfo {
+    fn new() -> Self {
+        Self {
+            uuid: Uuid::new_v4(),
+            // As precise as the header keeps it
+            created_at: DateTime::from_timestamp_millis(Utc::now().timestamp_millis()),
+            last_writer_version: Some(CRATE_VERSION.to_string()),
+        }
+    }
+
+    fn from_header(header: &[u8; HEADER_SIZE]) -> Self {
+        let mut uuid = [0u8; 16];
+        uuid.copy_from_slice(&header[UUID_OFFSET..UUID_OFFSET + 16]);
+        let mut millis = [0u8; 8];
+        millis.copy_from_slice(&header[CREATED_OFFSET..CREATED_OFFSET + 8]);
+        let writer = &header[WRITER_OFFSET..WRITER_OFFSET + WRITER_LEN];
+        let writer = &writer[..writer.iter().position(|&b| b == 0).unwrap_or(WRITER_LEN)];
+        Self {
+            uuid: Uuid::from_bytes(uuid),
+            created_at: Some(i64::from_le_bytes(millis))
+                .filter(|&millis| millis != 0)
+                .and_then(DateTime::from_timestamp_millis),
+            last_writer_version: Some(String::from_utf8_lossy(writer).into_owned()).filter(|v| !v.is_empty()),
+        }
+    }
+
+    /// Header bytes from [`UUID_OFFSET`] to the end of the writer version
+    fn to_bytes(&self) -> [u8; WRITER_OFFSET + WRITER_LEN - UUID_OFFSET] {
+        let mut bytes = [0u8; WRITER_OFFSET + WRITER_LEN - UUID_OFFSET];
+        bytes[..16].copy_from_slice(self.uuid.as_bytes());
+        let millis = self.created_at.map_or(0, |created| created.timestamp_millis());
+        bytes[CREATED_OFFSET - UUID_OFFSET..][..8].copy_from_slice(&millis.to_le_bytes());
+        let writer = self.last_writer_version.as_deref().unwrap_or("").as_bytes();
+        let len = writer.len().min(WRITER_LEN);
+        bytes[WRITER_OFFSET - UUID_OFFSET..][..len].copy_from_slice(&writer[..len]);
+        bytes
+    }
+}
+
+/// Bytes of a page taken by its type and checksum
+const PAGE_OVERHEAD: usize = 5;
+
+/// Smallest page size a database can be created with
+pub const MIN_PAGE_SIZE: usize = 512;
+/// Largest page size a database can be created with
+pub const MAX_PAGE_SIZE: usize = 64 * 1024;
+
+/// Check that `page_size` is a power of two from [`MIN_PAGE_SIZE`] to
+/// [`MAX_PAGE_SIZE`], as new databases need
+///
+/// Opening does not check this: databases created before sizes were checked
+/// may have any size that holds a page's type and checksum.
+///
+/// Large pages suit collections of large documents, which must each fit in
+/// one page; small pages waste less space and cache on small documents.
+pub fn check_page_size(page_size: usize) -> Result<()> {
+    if !page_size.is_power_of_two() || !(MIN_PAGE_SIZE..=MAX_PAGE_SIZE).contains(&page_size) {
+        return Err(KeraDBError::Config(format!(
+            "Page size {} is not a power of two from {} to {}",
+            page_size, MIN_PAGE_SIZE, MAX_PAGE_SIZE
+        )));
+    }
+    Ok(())
+}
 
 /// Page structure: Header (64 bytes) + Data
 #[derive(Debug, Clone)]
@@ -34,18 +129,36 @@ impl Page {
     }
 }
 
-/// Pager manages reading and writing pages to disk
+/// Pager manages reading and writing pages to storage
+///
+/// Pages are read and written at fixed offsets through a [`StorageBackend`],
+/// so a pager can be shared between threads. Callers must not read a page
+/// while it is being written. Opened from a path, the backend is a
+/// [`FileBackend`], which locks the file for as long as the pager is alive.
 pub struct Pager {
-    file: File,
+    backend: Box<dyn StorageBackend>,
     path: PathBuf,
     page_size: usize,
-    page_count: u32,
+    page_count: AtomicU32,
+    /// Serializes page count updates in the file header
+    header_lock: Mutex<()>,
+    read_only: bool,
+    /// Pages written since the last sync
+    unsynced: AtomicU64,
+    info: DatabaseInfo,
 }
 
 impl Pager {
     /// Create a new database file
     pub fn create<P: AsRef<Path>>(path: P, page_size: usize) -> Result<Self> {
-        let path = path.as_ref().to_path_buf();
+        Self::create_with_options(path, page_size, FileOptions::default())
+    }
+
+    /// Create a new database file, opened with `options`
+    #[tracing::instrument(level = "debug", skip_all, fields(path = %path.as_ref().display(), page_size = page_size), err(level = "debug"))]
+    pub fn create_with_options<P: AsRef<Path>>(path: P, page_size: usize, options: FileOptions) -> Result<Self> {
+        let path = path.as_ref();
+        check_page_size(page_size)?;
         
         if path.exists() {
             return Err(KeraDBError::InvalidFormat(
@@ -53,35 +166,56 @@ impl Pager {
             ));
         }
 
-        let mut file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&path)?;
-
-        // Write header
-        file.write_all(MAGIC_BYTES)?;
-        file.write_all(&VERSION.to_le_bytes())?;
-        file.write_all(&(page_size as u32).to_le_bytes())?;
-        file.write_all(&0u32.to_le_bytes())?; // page count
-        
-        // Pad to HEADER_SIZE
-        let padding = vec![0u8; HEADER_SIZE - 16];
-        file.write_all(&padding)?;
-        file.flush()?;
+        Self::create_with_backend(Box::new(FileBackend::create_with_options(path, options)?), path, page_size)
+    }
+
+    /// Create a new database in `backend`, overwriting whatever it holds
+    ///
+    /// `name` only identifies the database in logs and errors.
+    pub fn create_with_backend<P: AsRef<Path>>(
+        backend: Box<dyn StorageBackend>,
+        name: P,
+        page_size: usize,
+    ) -> Result<Self> {
+        check_page_size(page_size)?;
+        let info = DatabaseInfo::new();
+        let mut header = [0u8; HEADER_SIZE];
+        header[0..4].copy_from_slice(MAGIC_BYTES);
+        header[4..8].copy_from_slice(&VERSION.to_le_bytes());
+        header[8..12].copy_from_slice(&(page_size as u32).to_le_bytes());
+        // Page count (12..16) starts at zero
+        header[UUID_OFFSET..WRITER_OFFSET + WRITER_LEN].copy_from_slice(&info.to_bytes());
+        backend.set_size(0)?;
+        backend.write_all_at(&header, 0)?;
 
         Ok(Self {
-            file,
-            path,
+            backend,
+            path: name.as_ref().to_path_buf(),
             page_size,
-            page_count: 0,
+            page_count: AtomicU32::new(0),
+            header_lock: Mutex::new(()),
+            read_only: false,
+            unsynced: AtomicU64::new(0),
+            info,
         })
     }
 
-    /// Open an existing database file
+    /// Open an existing database file for reading and writing
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let path = path.as_ref().to_path_buf();
+        Self::open_with_options(path, false, FileOptions::default())
+    }
+
+    /// Open an existing database file without write access
+    ///
+    /// Any number of read-only pagers can share a file, but not with a writer.
+    pub fn open_read_only<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with_options(path, true, FileOptions::default())
+    }
+
+    /// Open an existing database file with `options`, without write access
+    /// when `read_only`
+    pub fn open_with_options<P: AsRef<Path>>(path: P, read_only: bool, options: FileOptions) -> Result<Self> {
+        let path = path.as_ref();
         
         if !path.exists() {
             return Err(KeraDBError::DatabaseNotFound(
@@ -89,24 +223,30 @@ impl Pager {
             ));
         }
 
-        let mut file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(&path)?;
+        // The backend locks before the header is read, so a writer is never
+        // caught mid-update
+        Self::open_with_backend(Box::new(FileBackend::open_with_options(path, read_only, options)?), path, read_only)
+    }
 
+    /// Open the database held in `backend`
+    #[tracing::instrument(name = "open", level = "debug", skip_all, fields(path = %name.as_ref().display(), read_only = read_only, page_count = tracing::field::Empty), err(level = "debug"))]
+    pub fn open_with_backend<P: AsRef<Path>>(
+        backend: Box<dyn StorageBackend>,
+        name: P,
+        read_only: bool,
+    ) -> Result<Self> {
         // Read and validate header
-        let mut magic = [0u8; 4];
-        file.read_exact(&mut magic)?;
+        let mut header = [0u8; HEADER_SIZE];
+        backend.read_exact_at(&mut header, 0)?;
+        let field = |i: usize| u32::from_le_bytes([header[i], header[i + 1], header[i + 2], header[i + 3]]);
         
-        if &magic != MAGIC_BYTES {
+        if &header[0..4] != MAGIC_BYTES {
             return Err(KeraDBError::InvalidFormat(
                 "Invalid magic bytes".to_string(),
             ));
         }
 
-        let mut version_bytes = [0u8; 4];
-        file.read_exact(&mut version_bytes)?;
-        let version = u32::from_le_bytes(version_bytes);
+        let version = field(4);
         
         if version != VERSION {
             return Err(KeraDBError::VersionMismatch {
@@ -115,25 +255,44 @@ impl Pager {
             });
         }
 
-        let mut page_size_bytes = [0u8; 4];
-        file.read_exact(&mut page_size_bytes)?;
-        let page_size = u32::from_le_bytes(page_size_bytes) as usize;
-
-        let mut page_count_bytes = [0u8; 4];
-        file.read_exact(&mut page_count_bytes)?;
-        let page_count = u32::from_le_bytes(page_count_bytes);
+        let page_size = field(8) as usize;
+        if page_size <= PAGE_OVERHEAD {
+            return Err(KeraDBError::InvalidFormat(format!("Invalid page size {}", page_size)));
+        }
+        let page_count = field(12);
+        tracing::Span::current().record("page_count", page_count);
+
+        let mut info = DatabaseInfo::from_header(&header);
+        if !read_only {
+            // Databases from before the UUID was recorded get one now
+            if info.uuid.is_nil() {
+                info.uuid = Uuid::new_v4();
+            }
+            info.last_writer_version = Some(CRATE_VERSION.to_string());
+            let bytes = info.to_bytes();
+            if header[UUID_OFFSET..WRITER_OFFSET + WRITER_LEN] != bytes {
+                backend.write_all_at(&bytes, UUID_OFFSET as u64)?;
+            }
+        }
 
         Ok(Self {
-            file,
-            path,
+            backend,
+            path: name.as_ref().to_path_buf(),
             page_size,
-            page_count,
+            page_count: AtomicU32::new(page_count),
+            header_lock: Mutex::new(()),
+            read_only,
+            unsynced: AtomicU64::new(0),
+            info,
         })
     }
 
     /// Read a page from disk
-    pub fn read_page(&mut self, page_num: u32) -> Result<Page> {
-        if page_num >= self.page_count {
+    ///
+    /// Uses a positional read, so any number of readers can share the pager.
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub fn read_page(&self, page_num: u32) -> Result<Page> {
+        if page_num >= self.page_count() {
             return Err(KeraDBError::StorageError(format!(
                 "Page {} does not exist",
                 page_num
@@ -141,27 +300,19 @@ impl Pager {
         }
 
         let offset = HEADER_SIZE + (page_num as usize * self.page_size);
-        self.file.seek(SeekFrom::Start(offset as u64))?;
-
-        // Read page header
-        let mut page_type_byte = [0u8; 1];
-        self.file.read_exact(&mut page_type_byte)?;
-        let page_type = PageType::try_from(page_type_byte[0])?;
-
-        let mut checksum_bytes = [0u8; 4];
-        self.file.read_exact(&mut checksum_bytes)?;
-        let checksum = u32::from_le_bytes(checksum_bytes);
+        let mut buf = vec![0u8; self.page_size];
+        self.backend.read_exact_at(&mut buf, offset as u64)?;
 
-        // Read page data
-        let data_size = self.page_size - 5; // 1 byte type + 4 bytes checksum
-        let mut data = vec![0u8; data_size];
-        self.file.read_exact(&mut data)?;
+        // Page header: 1 byte type + 4 bytes checksum
+        let page_type = PageType::try_from(buf[0])?;
+        let checksum = u32::from_le_bytes([buf[1], buf[2], buf[3], buf[4]]);
+        buf.drain(..5);
 
         let page = Page {
             page_num,
             page_type,
             checksum,
-            data,
+            data: buf,
         };
 
         if !page.verify_checksum() {
@@ -172,37 +323,35 @@ impl Pager {
     }
 
     /// Write a page to disk
-    pub fn write_page(&mut self, page: &Page) -> Result<()> {
-        let offset = HEADER_SIZE + (page.page_num as usize * self.page_size);
-        self.file.seek(SeekFrom::Start(offset as u64))?;
+    #[tracing::instrument(level = "trace", skip_all, fields(page_num = page.page_num))]
+    pub fn write_page(&self, page: &Page) -> Result<()> {
+        if self.read_only {
+            return Err(KeraDBError::ReadOnly);
+        }
 
         // Write page data (pad if necessary)
-        let data_size = self.page_size - 5;
+        let data_size = self.page_size - PAGE_OVERHEAD;
         if page.data.len() > data_size {
             return Err(KeraDBError::StorageError(
                 "Page data exceeds page size".to_string(),
             ));
         }
 
-        // Create padded data and calculate checksum on padded data
-        let mut padded_data = page.data.clone();
-        if padded_data.len() < data_size {
-            padded_data.resize(data_size, 0);
-        }
-        let checksum = crc32fast::hash(&padded_data);
-
-        // Write page header
-        self.file.write_all(&[page.page_type as u8])?;
-        self.file.write_all(&checksum.to_le_bytes())?;
+        // Page header (type + checksum of the padded data), then the padded data
+        let mut buf = Vec::with_capacity(self.page_size);
+        buf.push(page.page_type as u8);
+        buf.extend_from_slice(&[0u8; 4]);
+        buf.extend_from_slice(&page.data);
+        buf.resize(self.page_size, 0);
+        let checksum = crc32fast::hash(&buf[5..]);
+        buf[1..5].copy_from_slice(&checksum.to_le_bytes());
 
-        // Write padded data
-        self.file.write_all(&padded_data)?;
-
-        self.file.flush()?;
+        let offset = HEADER_SIZE + (page.page_num as usize * self.page_size);
+        self.backend.write_all_at(&buf, offset as u64)?;
+        self.unsynced.fetch_add(1, Ordering::Relaxed);
 
         // Update page count if necessary
-        if page.page_num >= self.page_count {
-            self.page_count = page.page_num + 1;
+        if page.page_num >= self.page_count.fetch_max(page.page_num + 1, Ordering::AcqRel) {
             self.update_header()?;
         }
 
@@ -210,39 +359,81 @@ impl Pager {
     }
 
     /// Allocate a new page
-    pub fn allocate_page(&mut self, page_type: PageType) -> Result<u32> {
-        let page_num = self.page_count;
-        let data = vec![0u8; self.page_size - 5];
+    ///
+    /// Concurrent callers get distinct pages.
+    #[tracing::instrument(level = "trace", skip(self), fields(page_num = tracing::field::Empty))]
+    pub fn allocate_page(&self, page_type: PageType) -> Result<u32> {
+        if self.read_only {
+            return Err(KeraDBError::ReadOnly);
+        }
+
+        let page_num = self.page_count.fetch_add(1, Ordering::AcqRel);
+        tracing::Span::current().record("page_num", page_num);
+        let data = vec![0u8; self.page_size - PAGE_OVERHEAD];
         let page = Page::new(page_num, page_type, data);
         self.write_page(&page)?;
+        self.update_header()?;
         Ok(page_num)
     }
 
     /// Update the database header
-    fn update_header(&mut self) -> Result<()> {
-        self.file.seek(SeekFrom::Start(12))?;
-        self.file.write_all(&self.page_count.to_le_bytes())?;
-        self.file.flush()?;
+    fn update_header(&self) -> Result<()> {
+        let _guard = self.header_lock.lock();
+        let page_count = self.page_count();
+        self.backend.write_all_at(&page_count.to_le_bytes(), 12)?;
         Ok(())
     }
 
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The database's UUID, creation time and last writer
+    pub fn info(&self) -> &DatabaseInfo {
+        &self.info
+    }
+
     pub fn page_count(&self) -> u32 {
-        self.page_count
+        self.page_count.load(Ordering::Acquire)
     }
 
     pub fn page_size(&self) -> usize {
         self.page_size
     }
 
-    pub fn sync(&mut self) -> Result<()> {
-        self.file.sync_all()?;
+    /// Size of the underlying storage in bytes
+    pub fn size(&self) -> Result<u64> {
+        Ok(self.backend.size()?)
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Make every page written so far durable
+    ///
+    /// For files this is the platform's full flush, `F_FULLFSYNC` on macOS
+    /// and `FlushFileBuffers` on Windows, so synced pages survive power loss.
+    #[tracing::instrument(level = "debug", skip_all, fields(path = %self.path.display()), err)]
+    pub fn sync(&self) -> Result<()> {
+        let pending = self.unsynced.swap(0, Ordering::AcqRel);
+        if let Err(e) = self.backend.sync() {
+            self.unsynced.fetch_add(pending, Ordering::AcqRel);
+            return Err(e.into());
+        }
         Ok(())
     }
+
+    /// Pages written since the last [`sync`](Self::sync), which a crash could lose
+    pub fn unsynced_pages(&self) -> u64 {
+        self.unsynced.load(Ordering::Acquire)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage::MemoryBackend;
     use tempfile::tempdir;
 
     #[test]
@@ -265,7 +456,7 @@ mod tests {
         let dir = tempdir().unwrap();
         let path = dir.path().join("test.ndb");
 
-        let mut pager = Pager::create(&path, 4096).unwrap();
+        let pager = Pager::create(&path, 4096).unwrap();
 
         let data = b"Hello, NoSQLite!".to_vec();
         let page = Page::new(0, PageType::Data, data.clone());
@@ -276,4 +467,103 @@ mod tests {
         assert_eq!(read_page.page_type, PageType::Data);
         assert!(read_page.data.starts_with(&data));
     }
+
+    #[test]
+    fn test_large_pages() {
+        let dir = tempdir().unwrap();
+        for size in [0, 256, 4000, 128 * 1024] {
+            let path = dir.path().join(format!("{}.ndb", size));
+            assert!(matches!(Pager::create(&path, size), Err(KeraDBError::Config(_))));
+            assert!(!path.exists());
+        }
+
+        let path = dir.path().join("large.ndb");
+        let pager = Pager::create(&path, MAX_PAGE_SIZE).unwrap();
+        let data = vec![7u8; 60 * 1024];
+        pager.write_page(&Page::new(0, PageType::Data, data.clone())).unwrap();
+        drop(pager);
+
+        let pager = Pager::open(&path).unwrap();
+        assert_eq!(pager.page_size(), MAX_PAGE_SIZE);
+        assert!(pager.read_page(0).unwrap().data.starts_with(&data));
+
+        // Databases created before sizes were checked still open
+        let backend = MemoryBackend::new();
+        drop(Pager::create_with_backend(Box::new(backend.clone()), "memory", 512).unwrap());
+        backend.write_all_at(&4000u32.to_le_bytes(), 8).unwrap();
+        let pager = Pager::open_with_backend(Box::new(backend.clone()), "memory", false).unwrap();
+        assert_eq!(pager.page_size(), 4000);
+        drop(pager);
+        backend.write_all_at(&5u32.to_le_bytes(), 8).unwrap();
+        assert!(matches!(
+            Pager::open_with_backend(Box::new(backend), "memory", false),
+            Err(KeraDBError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_header_info() {
+        let backend = MemoryBackend::new();
+        let pager = Pager::create_with_backend(Box::new(backend.clone()), "memory", 512).unwrap();
+        let info = pager.info().clone();
+        assert!(!info.uuid.is_nil());
+        assert!(info.created_at.is_some());
+        assert_eq!(info.last_writer_version.as_deref(), Some(CRATE_VERSION));
+        drop(pager);
+        let pager = Pager::open_with_backend(Box::new(backend.clone()), "memory", false).unwrap();
+        assert_eq!(pager.info(), &info);
+        drop(pager);
+
+        // Files from before these were recorded get a UUID and writer when
+        // opened for writing
+        backend.write_all_at(&[0; WRITER_OFFSET + WRITER_LEN - UUID_OFFSET], UUID_OFFSET as u64).unwrap();
+        let old = MemoryBackend::from_bytes(backend.to_bytes());
+        let pager = Pager::open_with_backend(Box::new(old.clone()), "memory", true).unwrap();
+        assert_eq!((pager.info().uuid.is_nil(), pager.info().created_at), (true, None));
+        assert_eq!(pager.info().last_writer_version, None);
+        drop(pager);
+        let uuid = Pager::open_with_backend(Box::new(old.clone()), "memory", false).unwrap().info().uuid;
+        let pager = Pager::open_with_backend(Box::new(old), "memory", true).unwrap();
+        assert!(!uuid.is_nil());
+        assert_eq!((pager.info().uuid, pager.info().created_at), (uuid, None));
+        assert_eq!(pager.info().last_writer_version.as_deref(), Some(CRATE_VERSION));
+    }
+
+    #[test]
+    fn test_writer_excludes_other_openers() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.ndb");
+
+        let writer = Pager::create(&path, 4096).unwrap();
+        assert!(matches!(Pager::open(&path), Err(KeraDBError::Locked(_))));
+        assert!(matches!(Pager::open_read_only(&path), Err(KeraDBError::Locked(_))));
+        drop(writer);
+
+        // Readers share the file but keep writers out
+        let reader = Pager::open_read_only(&path).unwrap();
+        let _other = Pager::open_read_only(&path).unwrap();
+        assert!(matches!(Pager::open(&path), Err(KeraDBError::Locked(_))));
+        assert!(matches!(reader.allocate_page(PageType::Data), Err(KeraDBError::ReadOnly)));
+    }
+
+    #[test]
+    fn test_memory_backend_round_trip() {
+        let backend = MemoryBackend::new();
+        let pager = Pager::create_with_backend(Box::new(backend.clone()), "memory", 512).unwrap();
+        pager.write_page(&Page::new(1, PageType::Data, b"in memory".to_vec())).unwrap();
+        assert_eq!(pager.size().unwrap(), (HEADER_SIZE + 2 * 512) as u64);
+        drop(pager);
+
+        let saved = MemoryBackend::from_bytes(backend.to_bytes());
+        let pager = Pager::open_with_backend(Box::new(saved), "memory", true).unwrap();
+        assert_eq!(pager.page_size(), 512);
+        assert_eq!(pager.page_count(), 2);
+        assert!(pager.read_page(1).unwrap().data.starts_with(b"in memory"));
+
+        let garbage = MemoryBackend::from_bytes(vec![0; 64]);
+        assert!(matches!(
+            Pager::open_with_backend(Box::new(garbage), "memory", false),
+            Err(KeraDBError::InvalidFormat(_))
+        ));
+    }
 }
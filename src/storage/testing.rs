@@ -0,0 +1,338 @@
+//! Fault injection for crash-recovery tests
+//!
+//! [`FaultyBackend`] is a [`StorageBackend`] in memory that behaves like a
+//! disk with a write cache: writes are seen by reads straight away but only
+//! become durable when synced. A test can have it
+//!
+//! - fail syncs, as `fsync` does on an I/O error,
+//! - kill the process at a chosen write, of which only the first bytes
+//!   land (a torn write), failing every write after it, and
+//! - crash at any point, keeping any subset of the unsynced writes, since a
+//!   disk may have persisted some of them, in any order.
+//!
+//! [`crash_image`](FaultyBackend::crash_image) gives the bytes such a crash
+//! leaves behind, to reopen with a [`MemoryBackend`](super::MemoryBackend)
+//! and check that the database recovers to a consistent state.
+//!
+//! # Example
+//! ```ignore
+//! let disk = FaultyBackend::new();
+//! let db = Database::create_with_backends("test", Box::new(disk.clone()), Box::new(MemoryBackend::new()), Config::default())?;
+//! db.insert("users", json!({"name": "Alice"}))?;
+//! disk.kill_at(disk.writes() + 1, 512);
+//! let _ = db.insert("users", json!({"name": "Bob"}));
+//! let image = disk.crash_image(|_| true);
+//! let (db, report) = Database::open_with_backends("test", Box::new(MemoryBackend::from_bytes(image)), Box::new(MemoryBackend::new()), false, Config::default())?;
+//! ```
+
+use super::backend::StorageBackend;
+use parking_lot::Mutex;
+use std::io;
+use std::sync::Arc;
+
+/// A change to the bytes, as a crash may or may not keep it
+#[derive(Debug, Clone)]
+enum Write {
+    At(u64, Vec<u8>),
+    SetSize(u64),
+}
+
+impl Write {
+    fn apply(&self, bytes: &mut Vec<u8>) {
+        match self {
+            Write::At(offset, data) => {
+                let start = *offset as usize;
+                if bytes.len() < start + data.len() {
+                    bytes.resize(start + data.len(), 0);
+                }
+                bytes[start..start + data.len()].copy_from_slice(data);
+            }
+            Write::SetSize(size) => bytes.resize(*size as usize, 0),
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// What reads see
+    bytes: Vec<u8>,
+    /// What the last successful sync made durable
+    durable: Vec<u8>,
+    /// Writes since then, oldest first
+    unsynced: Vec<Write>,
+    /// Writes and resizes so far
+    writes: usize,
+    /// The write that kills the process, and how many of its bytes land
+    kill: Option<(usize, usize)>,
+    killed: bool,
+    failing_syncs: usize,
+}
+
+fn killed() -> io::Error {
+    io::Error::other("killed by fault injection")
+}
+
+/// A [`StorageBackend`] in memory that fails on cue and can be crashed;
+/// clones share the same storage
+#[derive(Clone, Default)]
+pub struct FaultyBackend {
+    state: Arc<Mutex<State>>,
+}
+
+impl FaultyBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Storage holding `bytes`, all of them durable
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        let state = State { durable: bytes.clone(), bytes, ..State::default() };
+        Self { state: Arc::new(Mutex::new(state)) }
+    }
+
+    /// Writes and resizes made so far, counting from one
+    pub fn writes(&self) -> usize {
+        self.state.lock().writes
+    }
+
+    /// Writes and resizes made since the last successful sync
+    pub fn unsynced_writes(&self) -> usize {
+        self.state.lock().unsynced.len()
+    }
+
+    /// Kill the process at write number `write`: only its first
+    /// `torn_bytes` bytes land, and it and everything after it fails
+    pub fn kill_at(&self, write: usize, torn_bytes: usize) {
+        self.state.lock().kill = Some((write, torn_bytes));
+    }
+
+    /// Whether the kill point has been reached
+    pub fn is_killed(&self) -> bool {
+        self.state.lock().killed
+    }
+
+    /// Fail the next `count` syncs, leaving the writes before them unsynced
+    pub fn fail_syncs(&self, count: usize) {
+        self.state.lock().failing_syncs = count;
+    }
+
+    /// The bytes a crash now would leave: the durable ones, with the
+    /// unsynced writes `survives` picks, by their position since the last
+    /// sync, applied over them
+    pub fn crash_image(&self, survives: impl Fn(usize) -> bool) -> Vec<u8> {
+        let state = self.state.lock();
+        let mut bytes = state.durable.clone();
+        for (i, write) in state.unsynced.iter().enumerate() {
+            if survives(i) {
+                write.apply(&mut bytes);
+            }
+        }
+        bytes
+    }
+
+    /// Count a write, applying it unless the process is dead
+    fn write(&self, write: Write) -> io::Result<()> {
+        let mut state = self.state.lock();
+        if state.killed {
+            return Err(killed());
+        }
+        state.writes += 1;
+        match state.kill {
+            Some((at, torn_bytes)) if state.writes >= at => {
+                state.killed = true;
+                if let Write::At(offset, data) = write {
+                    let torn = Write::At(offset, data[..torn_bytes.min(data.len())].to_vec());
+                    torn.apply(&mut state.bytes);
+                    state.unsynced.push(torn);
+                }
+                Err(killed())
+            }
+            _ => {
+                write.apply(&mut state.bytes);
+                state.unsynced.push(write);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl StorageBackend for FaultyBackend {
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        let state = self.state.lock();
+        let start = usize::try_from(offset).map_err(|_| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        let range = state.bytes.get(start..start.saturating_add(buf.len())).ok_or(io::ErrorKind::UnexpectedEof)?;
+        buf.copy_from_slice(range);
+        Ok(())
+    }
+
+    fn write_all_at(&self, buf: &[u8], offset: u64) -> io::Result<()> {
+        self.write(Write::At(offset, buf.to_vec()))
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.state.lock().bytes.len() as u64)
+    }
+
+    fn set_size(&self, size: u64) -> io::Result<()> {
+        self.write(Write::SetSize(size))
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        let mut state = self.state.lock();
+        if state.killed {
+            return Err(killed());
+        }
+        if state.failing_syncs > 0 {
+            state.failing_syncs -= 1;
+            return Err(io::Error::other("sync failed by fault injection"));
+        }
+        state.durable = state.bytes.clone();
+        state.unsynced.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FaultyBackend;
+    use crate::storage::MemoryBackend;
+    use crate::types::OpenWarning;
+    use crate::{Config, Database, OpenReport};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+
+    /// A database whose header is durable, as one never synced does not exist yet
+    fn create(disk: &FaultyBackend) -> Database {
+        let db = Database::create_with_backends("crash", Box::new(disk.clone()), Box::new(MemoryBackend::new()), Config::default())
+            .unwrap();
+        db.sync().unwrap();
+        db
+    }
+
+    fn reopen(image: Vec<u8>) -> (Database, OpenReport) {
+        let data = Box::new(MemoryBackend::from_bytes(image));
+        Database::open_with_backends("crash", data, Box::new(MemoryBackend::new()), false, Config::default()).unwrap()
+    }
+
+    /// Each document's versions, `None` for deleted, oldest first
+    type History = HashMap<String, Vec<Option<Value>>>;
+
+    /// Run a workload of inserts, updates and deletes with a sync after
+    /// every few writes, stopping at the first failure; returns what was
+    /// written and the versions the last successful sync made durable
+    fn workload(db: &Database, history: &mut History) -> HashMap<String, Option<Value>> {
+        let mut durable = HashMap::new();
+        let mut written = |id: &str, value: Option<Value>| history.entry(id.to_string()).or_default().push(value);
+        for step in 0..12 {
+            let id = format!("d{}", step % 5);
+            let value = json!({"step": step, "pad": "x".repeat(step * 50)});
+            let result = match (step % 4, db.find_by_id("items", &id).is_ok()) {
+                (3, true) => {
+                    written(&id, None);
+                    db.delete("items", &id).map(|_| ())
+                }
+                (_, true) => {
+                    written(&id, Some(value.clone()));
+                    db.update("items", &id, value).map(|_| ())
+                }
+                (_, false) => {
+                    written(&id, Some(value.clone()));
+                    let mut doc = value;
+                    doc["_id"] = json!(id);
+                    db.insert("items", doc).map(|_| ())
+                }
+            };
+            if result.is_err() {
+                return durable;
+            }
+            if step % 3 == 2 {
+                if db.sync().is_err() {
+                    return durable;
+                }
+                durable = (0..5)
+                    .map(|n| format!("d{}", n))
+                    .map(|id| {
+                        let doc = db.find_by_id("items", &id).ok().map(|doc| doc.data);
+                        (id, doc)
+                    })
+                    .collect();
+            }
+        }
+        durable
+    }
+
+    /// Whatever a crash kept, every document is one that was written, every
+    /// skipped page is reported, and documents synced and not written again
+    /// are intact
+    fn check_recovered(image: Vec<u8>, history: &History, durable: &HashMap<String, Option<Value>>, written_after: &[String]) {
+        let (db, report) = reopen(image);
+        for warning in &report.warnings {
+            assert!(matches!(warning, OpenWarning::UnreadablePage { .. } | OpenWarning::InvalidDocument { .. }), "{}", warning);
+        }
+        for doc in db.find_all("items", None, None).unwrap() {
+            assert!(history[&doc.id].contains(&Some(doc.data.clone())), "{} holds {} that was never written", doc.id, doc.data);
+        }
+        for (id, version) in durable {
+            if !written_after.contains(id) {
+                let found = db.find_by_id("items", id).ok().map(|doc| doc.data);
+                assert_eq!(&found, version, "{} lost its synced version", id);
+            }
+        }
+        // The recovered database takes writes again
+        db.insert("items", json!({"after": "crash"})).unwrap();
+    }
+
+    #[test]
+    fn test_crash_recovery() {
+        // A run without faults counts the writes to kill at
+        let disk = FaultyBackend::new();
+        let db = create(&disk);
+        let created = disk.writes();
+        workload(&db, &mut History::new());
+        let total = disk.writes();
+        assert!(total > created + 20);
+
+        for kill_at in created + 1..=total {
+            for torn_bytes in [0, 512, usize::MAX] {
+                let disk = FaultyBackend::new();
+                let db = create(&disk);
+                disk.kill_at(kill_at, torn_bytes);
+                let mut history = History::new();
+                let durable = workload(&db, &mut history);
+                assert!(disk.is_killed());
+                drop(db);
+                // Ids written since the last sync may be in any state
+                let unsynced = disk.unsynced_writes();
+                let written_after: Vec<String> = history
+                    .iter()
+                    .filter(|(id, versions)| durable.get(*id) != versions.last())
+                    .map(|(id, _)| id.clone())
+                    .collect();
+                check_recovered(disk.crash_image(|_| false), &history, &durable, &written_after);
+                check_recovered(disk.crash_image(|_| true), &history, &durable, &written_after);
+                check_recovered(disk.crash_image(|i| i % 2 == 1), &history, &durable, &written_after);
+                check_recovered(disk.crash_image(|i| i + 1 == unsynced), &history, &durable, &written_after);
+            }
+        }
+    }
+
+    #[test]
+    fn test_failed_sync() {
+        let disk = FaultyBackend::new();
+        let db = create(&disk);
+        db.insert("items", json!({"_id": "a"})).unwrap();
+        db.sync().unwrap();
+        db.insert("items", json!({"_id": "b"})).unwrap();
+        disk.fail_syncs(1);
+        assert!(db.sync().is_err());
+        assert!(db.unsynced_pages() > 0);
+        let (lost, _) = reopen(disk.crash_image(|_| false));
+        assert_eq!(lost.count("items"), 1);
+
+        db.sync().unwrap();
+        assert_eq!(db.unsynced_pages(), 0);
+        let (kept, report) = reopen(disk.crash_image(|_| false));
+        assert!(report.warnings.is_empty());
+        assert_eq!(kept.count("items"), 2);
+    }
+}
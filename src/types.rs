@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::time::Duration;
 use uuid::Uuid;
+use crate::storage::FileOptions;
+use crate::vector::{ProviderPolicy, VectorConfig};
 
 /// A document ID (UUID v4)
 pub type DocumentId = String;
@@ -85,6 +88,8 @@ pub enum PageType {
     Free = 3,
     VectorData = 4,
     VectorIndex = 5,
+    /// Part of a blob attached to a document
+    Blob = 6,
 }
 
 impl TryFrom<u8> for PageType {
@@ -98,6 +103,7 @@ impl TryFrom<u8> for PageType {
             3 => Ok(PageType::Free),
             4 => Ok(PageType::VectorData),
             5 => Ok(PageType::VectorIndex),
+            6 => Ok(PageType::Blob),
             _ => Err(crate::error::KeraDBError::InvalidFormat(
                 format!("Invalid page type: {}", value),
             )),
@@ -106,11 +112,46 @@ impl TryFrom<u8> for PageType {
 }
 
 /// Database configuration
+///
+/// Besides building it in code, a config can be loaded from a `keradb.toml`
+/// file and `KERADB_*` environment variables; see [`crate::config`].
 #[derive(Debug, Clone)]
 pub struct Config {
+    /// Size of a page in new databases, a power of two from 512 bytes to
+    /// 64KB; each document must fit in one page. Existing databases keep
+    /// the size they were created with.
     pub page_size: usize,
     pub cache_size: usize,
     pub auto_checkpoint: bool,
+    /// Number of recent changes kept in the oplog for change streams
+    pub oplog_capacity: usize,
+    /// When document writes reach the disk
+    pub durability: Durability,
+    /// How often a background [`Flusher`](crate::flusher::Flusher) writes
+    /// changes to disk; `keradb serve` starts one when this is set
+    pub flush_interval: Option<Duration>,
+    /// Pages written since the last sync past which writers wake the
+    /// flusher early, bounding how much a crash can lose
+    pub max_dirty_pages: u64,
+    /// Largest serialized document accepted in any collection; writes of
+    /// larger ones fail with [`KeraDBError::DocumentTooLarge`](crate::KeraDBError::DocumentTooLarge)
+    pub max_document_size: Option<usize>,
+    /// Parts an aggregation over a large collection is split into and run
+    /// at once; 1 runs it on the calling thread. Defaults to the number of
+    /// CPUs.
+    pub query_threads: usize,
+    /// Settings for vector collections created without an explicit config;
+    /// `dimensions` is ignored. See [`Config::vector_config`].
+    pub vector: VectorConfig,
+    /// Concurrency limit, retries and circuit breaking for calls to the
+    /// embedding provider
+    pub embedding: ProviderPolicy,
+    /// Bypass the OS page cache for the database file; Linux only. See
+    /// [`FileOptions::direct_io`].
+    pub direct_io: bool,
+    /// Bytes of disk space reserved at a time as the database file grows,
+    /// 0 for none; Linux only
+    pub preallocate: u64,
 }
 
 impl Default for Config {
@@ -119,6 +160,218 @@ impl Default for Config {
             page_size: 4096,      // 4KB pages
             cache_size: 100,      // 100 pages in cache
             auto_checkpoint: true,
+            oplog_capacity: 10_000,
+            durability: Durability::default(),
+            flush_interval: None,
+            max_dirty_pages: 1024,
+            max_document_size: None,
+            query_threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            vector: VectorConfig::default(),
+            embedding: ProviderPolicy::default(),
+            direct_io: false,
+            preallocate: 0,
+        }
+    }
+}
+
+impl Config {
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    pub fn with_cache_size(mut self, cache_size: usize) -> Self {
+        self.cache_size = cache_size;
+        self
+    }
+
+    pub fn with_durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    pub fn with_flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = Some(interval);
+        self
+    }
+
+    pub fn with_max_dirty_pages(mut self, max_dirty_pages: u64) -> Self {
+        self.max_dirty_pages = max_dirty_pages;
+        self
+    }
+
+    pub fn with_max_document_size(mut self, max_document_size: usize) -> Self {
+        self.max_document_size = Some(max_document_size);
+        self
+    }
+
+    pub fn with_query_threads(mut self, query_threads: usize) -> Self {
+        self.query_threads = query_threads;
+        self
+    }
+
+    pub fn with_direct_io(mut self, direct_io: bool) -> Self {
+        self.direct_io = direct_io;
+        self
+    }
+
+    pub fn with_preallocation(mut self, preallocate: u64) -> Self {
+        self.preallocate = preallocate;
+        self
+    }
+
+    /// How the database file is opened
+    pub fn file_options(&self) -> FileOptions {
+        FileOptions { direct_io: self.direct_io, preallocate: self.preallocate }
+    }
+
+    pub fn with_embedding_policy(mut self, policy: ProviderPolicy) -> Self {
+        self.embedding = policy;
+        self
+    }
+
+    /// Config for a new vector collection, using the default vector settings
+    pub fn vector_config(&self, dimensions: usize) -> VectorConfig {
+        VectorConfig { dimensions, ..self.vector.clone() }
+    }
+}
+
+/// When document writes are flushed to disk
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Durability {
+    /// Flush on [`Database::sync`](crate::Database::sync); a crash can lose
+    /// writes made since the last sync
+    #[default]
+    Normal,
+    /// Flush after every insert, update and delete
+    Full,
+}
+
+/// Options for scanning a collection
+///
+/// Large scans read and deserialize pages on rayon's thread pool, in batches
+/// of consecutive pages.
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    pub limit: Option<usize>,
+    pub skip: Option<usize>,
+    /// Return documents in storage (insertion) order; otherwise the order is
+    /// unspecified
+    pub ordered: bool,
+    /// Number of pages each parallel task reads
+    pub batch_size: usize,
+    /// Collections smaller than this are scanned on the calling thread
+    pub parallel_threshold: usize,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            limit: None,
+            skip: None,
+            ordered: false,
+            batch_size: 256,
+            parallel_threshold: 1024,
+        }
+    }
+}
+
+impl ScanOptions {
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn with_skip(mut self, skip: usize) -> Self {
+        self.skip = Some(skip);
+        self
+    }
+
+    /// Guarantee documents come back in storage (insertion) order
+    pub fn with_ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn with_parallel_threshold(mut self, threshold: usize) -> Self {
+        self.parallel_threshold = threshold;
+        self
+    }
+}
+
+/// What happened while opening a database
+///
+/// Returned by [`Database::open_with_report`](crate::Database::open_with_report).
+/// Data that could not be loaded is skipped rather than failing the open, so
+/// check [`warnings`](Self::warnings) before trusting the contents.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OpenReport {
+    /// Documents indexed from the data file
+    pub documents: usize,
+    /// Vector collections loaded from the vector file
+    pub vector_collections: Vec<String>,
+    /// Everything that was skipped, in the order it was found
+    pub warnings: Vec<OpenWarning>,
+}
+
+impl OpenReport {
+    /// Whether everything on disk was loaded
+    pub fn is_clean(&self) -> bool {
+        self.warnings.is_empty()
+    }
+}
+
+/// Data skipped while opening a database
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum OpenWarning {
+    /// A data page could not be read, e.g. because its checksum did not match
+    UnreadablePage { page: u32, error: String },
+    /// A data page did not hold a valid document
+    InvalidDocument { page: u32, error: String },
+    /// A document could not be added to the index
+    UnindexedDocument { page: u32, doc_id: DocumentId, error: String },
+    /// A blob page could not be indexed; the blob is missing
+    InvalidBlob { page: u32, error: String },
+    /// A compression dictionary could not be loaded; documents using it are skipped
+    InvalidDictionary { page: u32, error: String },
+    /// The vector file could not be read or decoded; no vector collections were loaded
+    VectorFile { error: String },
+    /// The vector file could not be read or decoded, so the one saved before
+    /// it was loaded; changes to vector collections since then are lost
+    PreviousVectorFile { error: String },
+    /// One vector collection in the vector file could not be decoded
+    VectorCollection { position: usize, error: String },
+    /// The stored embedding provider could not be recreated; text inserts and
+    /// searches fail until one is set
+    EmbeddingProvider { error: String },
+}
+
+impl std::fmt::Display for OpenWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OpenWarning::UnreadablePage { page, error } => write!(f, "Skipped unreadable page {}: {}", page, error),
+            OpenWarning::InvalidDocument { page, error } => write!(f, "Skipped invalid document on page {}: {}", page, error),
+            OpenWarning::UnindexedDocument { page, doc_id, error } => {
+                write!(f, "Skipped document {} on page {}: {}", doc_id, page, error)
+            }
+            OpenWarning::InvalidBlob { page, error } => write!(f, "Skipped invalid blob on page {}: {}", page, error),
+            OpenWarning::InvalidDictionary { page, error } => {
+                write!(f, "Skipped invalid compression dictionary on page {}: {}", page, error)
+            }
+            OpenWarning::VectorFile { error } => write!(f, "Skipped vector file: {}", error),
+            OpenWarning::PreviousVectorFile { error } => {
+                write!(f, "Loaded the previous vector file, as the newest was unusable: {}", error)
+            }
+            OpenWarning::VectorCollection { position, error } => {
+                write!(f, "Skipped vector collection {} in vector file: {}", position, error)
+            }
+            OpenWarning::EmbeddingProvider { error } => write!(f, "Skipped stored embedding provider: {}", error),
         }
     }
 }
@@ -0,0 +1,193 @@
+//! Async embedding providers (feature `async`)
+//!
+//! A provider that calls a network API can implement [`AsyncEmbeddingProvider`]
+//! so its requests are awaited instead of holding a thread. The two adapters
+//! connect it to the synchronous API: [`BlockingProvider`] runs an
+//! [`EmbeddingProvider`] on tokio's blocking thread pool, and
+//! [`BlockOnProvider`] lets [`Database`](crate::Database) embed with an async
+//! provider, which is how [`AsyncDatabase::set_embedding_provider`] installs one.
+//!
+//! [`AsyncDatabase::set_embedding_provider`]: crate::AsyncDatabase::set_embedding_provider
+
+use super::embedding::EmbeddingProvider;
+use super::types::{Embedding, EmbeddingFingerprint};
+use crate::async_db::blocking;
+use crate::error::Result;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::runtime::Handle;
+
+/// A boxed future returned by [`AsyncEmbeddingProvider`] methods
+pub type EmbedFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// Trait for embedding providers whose calls are awaited
+pub trait AsyncEmbeddingProvider: Send + Sync {
+    /// Generate embedding for a single text
+    fn embed<'a>(&'a self, text: &'a str) -> EmbedFuture<'a, Embedding>;
+
+    /// Generate embeddings for multiple texts (batched)
+    fn embed_batch<'a>(&'a self, texts: &'a [&'a str]) -> EmbedFuture<'a, Vec<Embedding>> {
+        Box::pin(async move {
+            let mut embeddings = Vec::with_capacity(texts.len());
+            for text in texts {
+                embeddings.push(self.embed(text).await?);
+            }
+            Ok(embeddings)
+        })
+    }
+
+    /// Get the dimensionality of embeddings
+    fn dimensions(&self) -> usize;
+
+    /// Get the model name
+    fn model_name(&self) -> &str;
+
+    /// Get the model version, if the provider tracks one
+    fn model_version(&self) -> Option<&str> {
+        None
+    }
+
+    /// Identify the model, to check that a collection is searched with the
+    /// model it was built with
+    fn fingerprint(&self) -> EmbeddingFingerprint {
+        EmbeddingFingerprint {
+            model: self.model_name().to_string(),
+            dimensions: self.dimensions(),
+            version: self.model_version().map(str::to_string),
+        }
+    }
+}
+
+/// A synchronous provider run on tokio's blocking thread pool
+pub struct BlockingProvider(Arc<dyn EmbeddingProvider>);
+
+impl BlockingProvider {
+    pub fn new(provider: Arc<dyn EmbeddingProvider>) -> Self {
+        Self(provider)
+    }
+}
+
+impl AsyncEmbeddingProvider for BlockingProvider {
+    fn embed<'a>(&'a self, text: &'a str) -> EmbedFuture<'a, Embedding> {
+        let (provider, text) = (self.0.clone(), text.to_string());
+        Box::pin(blocking(move || provider.embed(&text)))
+    }
+
+    fn embed_batch<'a>(&'a self, texts: &'a [&'a str]) -> EmbedFuture<'a, Vec<Embedding>> {
+        let provider = self.0.clone();
+        let texts: Vec<String> = texts.iter().map(|t| t.to_string()).collect();
+        Box::pin(blocking(move || provider.embed_batch(&texts.iter().map(String::as_str).collect::<Vec<_>>())))
+    }
+
+    fn dimensions(&self) -> usize {
+        self.0.dimensions()
+    }
+
+    fn model_name(&self) -> &str {
+        self.0.model_name()
+    }
+
+    fn model_version(&self) -> Option<&str> {
+        self.0.model_version()
+    }
+}
+
+/// An async provider driven to completion on a tokio runtime, for the
+/// synchronous API
+///
+/// Each call blocks its thread until the embedding arrives, so it must not be
+/// made from a runtime worker thread, where tokio panics; [`AsyncDatabase`]
+/// runs text operations on the blocking thread pool, where it is safe.
+///
+/// [`AsyncDatabase`]: crate::AsyncDatabase
+pub struct BlockOnProvider {
+    provider: Arc<dyn AsyncEmbeddingProvider>,
+    runtime: Handle,
+}
+
+impl BlockOnProvider {
+    /// Drive `provider` on the runtime behind `runtime`
+    pub fn new(provider: Arc<dyn AsyncEmbeddingProvider>, runtime: Handle) -> Self {
+        Self { provider, runtime }
+    }
+}
+
+impl EmbeddingProvider for BlockOnProvider {
+    fn embed(&self, text: &str) -> Result<Embedding> {
+        self.runtime.block_on(self.provider.embed(text))
+    }
+
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Embedding>> {
+        self.runtime.block_on(self.provider.embed_batch(texts))
+    }
+
+    fn dimensions(&self) -> usize {
+        self.provider.dimensions()
+    }
+
+    fn model_name(&self) -> &str {
+        self.provider.model_name()
+    }
+
+    fn model_version(&self) -> Option<&str> {
+        self.provider.model_version()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::embedding::TfIdfEmbeddingProvider;
+    use crate::vector::VectorConfig;
+    use crate::AsyncDatabase;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    /// Yields to the runtime before answering, as a network client would
+    struct YieldingProvider(BlockingProvider);
+
+    impl AsyncEmbeddingProvider for YieldingProvider {
+        fn embed<'a>(&'a self, text: &'a str) -> EmbedFuture<'a, Embedding> {
+            Box::pin(async move {
+                tokio::task::yield_now().await;
+                self.0.embed(text).await
+            })
+        }
+
+        fn dimensions(&self) -> usize {
+            self.0.dimensions()
+        }
+
+        fn model_name(&self) -> &str {
+            "yielding"
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_async_provider_embeds_texts() {
+        let dir = tempdir().unwrap();
+        let db = AsyncDatabase::create(dir.path().join("test.ndb")).await.unwrap();
+        let provider = YieldingProvider(BlockingProvider::new(Arc::new(TfIdfEmbeddingProvider::new(64))));
+        db.set_embedding_provider(Arc::new(provider)).await.unwrap();
+
+        db.create_vector_collection("notes", VectorConfig::new(64)).await.unwrap();
+        let ids = db
+            .insert_texts(
+                "notes",
+                vec![
+                    ("rust borrow checker".to_string(), Some(json!({"topic": "rust"}))),
+                    ("sourdough bread recipe".to_string(), None),
+                ],
+            )
+            .await
+            .unwrap();
+        assert_eq!(ids.len(), 2);
+
+        let results = db.vector_search_text("notes", "bread recipe", 1).await.unwrap();
+        assert_eq!(results[0].document.id, ids[1]);
+        let stats = db.vector_stats("notes").await.unwrap();
+        assert_eq!(stats.vector_count, 2);
+    }
+}
@@ -0,0 +1,177 @@
+//! K-means clustering of vector collections
+//!
+//! Segments a collection's embedding space with k-means++ seeding and Lloyd
+//! iterations, using the collection's distance metric. Cosine collections are
+//! clustered on the unit sphere (spherical k-means), so centroids stay unit
+//! length.
+
+use super::distance::{calculate_distance, normalize};
+use super::types::{Distance, Embedding, VectorId};
+use crate::error::{KeraDBError, Result};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// Metadata key [`VectorCollection::cluster`](super::search::VectorCollection::cluster)
+/// stores each vector's cluster number under
+pub const CLUSTER_KEY: &str = "_cluster";
+
+/// Upper bound on Lloyd iterations
+const MAX_ITERATIONS: usize = 100;
+
+/// Seed for k-means++, so the same vectors always give the same clusters
+const SEED: u64 = 0x4b45_5241;
+
+/// Result of clustering a collection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Clustering {
+    /// Cluster centers, indexed by cluster number
+    pub centroids: Vec<Embedding>,
+
+    /// Each vector's cluster, in ascending ID order
+    pub assignments: Vec<(VectorId, usize)>,
+
+    /// Number of vectors in each cluster
+    pub sizes: Vec<usize>,
+
+    /// Lloyd iterations run before the assignments stopped changing
+    pub iterations: usize,
+
+    /// Sum of distances from each vector to its centroid
+    pub inertia: f64,
+}
+
+/// Cluster `vectors` into `k` groups
+pub fn kmeans(vectors: &[(VectorId, Embedding)], k: usize, distance: Distance) -> Result<Clustering> {
+    if k == 0 || k > vectors.len() {
+        return Err(KeraDBError::VectorError(format!(
+            "Cannot make {} clusters from {} vectors",
+            k,
+            vectors.len()
+        )));
+    }
+
+    let points: Vec<Embedding> = vectors
+        .iter()
+        .map(|(_, v)| {
+            let mut v = v.clone();
+            if distance == Distance::Cosine {
+                normalize(&mut v);
+            }
+            v
+        })
+        .collect();
+    let nearest = |point: &Embedding, centroids: &[Embedding]| -> (usize, f32) {
+        centroids
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (i, calculate_distance(point, c, distance)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap_or((0, 0.0))
+    };
+
+    // k-means++: each further centroid is a point picked with probability
+    // proportional to its squared distance from the nearest one so far
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let mut centroids = vec![points[rng.gen_range(0..points.len())].clone()];
+    while centroids.len() < k {
+        let weights: Vec<f64> = points
+            .iter()
+            .map(|p| f64::from(nearest(p, &centroids).1.max(0.0)).powi(2))
+            .collect();
+        let total: f64 = weights.iter().sum();
+        let next = if total > 0.0 {
+            let mut target = rng.gen_range(0.0..total);
+            weights.iter().position(|w| {
+                target -= w;
+                target < 0.0
+            })
+        } else {
+            None
+        };
+        // All points coincide with a centroid; duplicates make empty clusters
+        let next = next.unwrap_or_else(|| rng.gen_range(0..points.len()));
+        centroids.push(points[next].clone());
+    }
+
+    let mut assignments = vec![usize::MAX; points.len()];
+    let mut iterations = 0;
+    while iterations < MAX_ITERATIONS {
+        iterations += 1;
+        let mut changed = false;
+        for (assignment, point) in assignments.iter_mut().zip(&points) {
+            let (cluster, _) = nearest(point, &centroids);
+            if *assignment != cluster {
+                *assignment = cluster;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+
+        let dimensions = points[0].len();
+        let mut sums = vec![vec![0.0f32; dimensions]; k];
+        let mut counts = vec![0usize; k];
+        for (&cluster, point) in assignments.iter().zip(&points) {
+            counts[cluster] += 1;
+            for (sum, x) in sums[cluster].iter_mut().zip(point) {
+                *sum += x;
+            }
+        }
+        for ((centroid, mut sum), count) in centroids.iter_mut().zip(sums).zip(&counts) {
+            // An empty cluster keeps its centroid
+            if *count == 0 {
+                continue;
+            }
+            for x in sum.iter_mut() {
+                *x /= *count as f32;
+            }
+            if distance == Distance::Cosine {
+                normalize(&mut sum);
+            }
+            *centroid = sum;
+        }
+    }
+
+    let mut sizes = vec![0; k];
+    let mut inertia = 0.0;
+    for (&cluster, point) in assignments.iter().zip(&points) {
+        sizes[cluster] += 1;
+        inertia += f64::from(calculate_distance(point, &centroids[cluster], distance));
+    }
+    let mut assignments: Vec<(VectorId, usize)> = vectors.iter().map(|(id, _)| *id).zip(assignments).collect();
+    assignments.sort_unstable();
+
+    Ok(Clustering { centroids, assignments, sizes, iterations, inertia })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kmeans_separates_blobs() {
+        let centers = [[0.0, 0.0], [10.0, 0.0], [0.0, 10.0]];
+        let vectors: Vec<(VectorId, Embedding)> = (0..90)
+            .map(|i| {
+                let [x, y] = centers[i % 3];
+                let jitter = (i / 3) as f32 * 0.01;
+                (i as VectorId, vec![x + jitter, y - jitter])
+            })
+            .collect();
+
+        let clustering = kmeans(&vectors, 3, Distance::Euclidean).unwrap();
+        assert_eq!(clustering.sizes, vec![30, 30, 30]);
+        // Every blob lands in one cluster of its own
+        for blob in 0..3 {
+            let cluster = clustering.assignments[blob].1;
+            assert!(clustering.assignments.iter().skip(blob).step_by(3).all(|(_, c)| *c == cluster));
+        }
+        assert!(clustering.inertia < 90.0 * 0.5);
+
+        assert!(kmeans(&vectors, 0, Distance::Euclidean).is_err());
+        assert!(kmeans(&vectors[..2], 3, Distance::Euclidean).is_err());
+    }
+}
@@ -356,7 +356,7 @@ impl CompressedVectorStore {
         // Decide if this should be an anchor
         let should_anchor = self.config.mode == CompressionMode::None
             || self.anchors.is_empty()
-            || self.total_count % self.config.anchor_frequency == 0;
+            || self.total_count.is_multiple_of(self.config.anchor_frequency);
         
         if should_anchor {
             self.vectors.insert(id, CompressedVector::Full(vector));
@@ -415,26 +415,138 @@ impl CompressedVectorStore {
         }
     }
     
+    /// IDs of the vectors held, in no particular order
+    pub fn ids(&self) -> impl Iterator<Item = VectorId> + '_ {
+        self.vectors.keys().copied()
+    }
+    
     /// Get compressed vector (for storage/serialization)
     pub fn get_compressed(&self, id: VectorId) -> Option<&CompressedVector> {
         self.vectors.get(&id)
     }
     
     /// Remove a vector
+    /// 
+    /// Vectors that use the removed vector as their delta base are re-based before it
+    /// is dropped: onto the removed vector's own base when it was a delta, otherwise
+    /// the first dependent is promoted to an anchor and the rest are re-based onto it.
+    /// Dependents that no longer compress well are stored as full vectors.
     pub fn remove(&mut self, id: VectorId) -> bool {
-        // Note: Removing an anchor that other vectors depend on is complex
-        // For now, we don't allow removing anchors that have dependents
-        if self.anchors.contains(&id) {
-            // Check if any other vector depends on this anchor
-            let has_dependents = self.vectors.values().any(|v| v.base_id() == Some(id));
-            if has_dependents {
-                return false; // Cannot remove anchor with dependents
+        if !self.vectors.contains_key(&id) {
+            return false;
+        }
+
+        let mut dependents: Vec<VectorId> = self.vectors
+            .iter()
+            .filter(|(_, v)| v.base_id() == Some(id))
+            .map(|(&dep_id, _)| dep_id)
+            .collect();
+        dependents.sort_unstable();
+
+        // Reconstruct dependents while their base is still available
+        let inflated: Vec<(VectorId, Embedding)> = dependents
+            .into_iter()
+            .filter_map(|dep_id| self.get_full(dep_id).map(|v| (dep_id, v)))
+            .collect();
+
+        let parent = self.vectors.get(&id).and_then(|v| v.base_id());
+        self.vectors.remove(&id);
+        self.anchors.remove(&id);
+
+        let mut new_base = parent;
+        for (dep_id, vector) in inflated {
+            match new_base {
+                Some(base_id) => self.rebase(dep_id, vector, base_id),
+                None => {
+                    // Promote the first dependent to take over as anchor
+                    self.vectors.insert(dep_id, CompressedVector::Full(vector));
+                    self.anchors.insert(dep_id);
+                    new_base = Some(dep_id);
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Store `vector` as a delta against `base_id`, or as a full anchor if the
+    /// delta would be too dense
+    fn rebase(&mut self, id: VectorId, vector: Embedding, base_id: VectorId) {
+        let compressed = self.get_full(base_id).and_then(|base| {
+            DeltaCompressor::new(self.config.clone()).compress(&vector, &base)
+        });
+
+        match compressed {
+            Some(mut compressed) if !compressed.is_anchor() => {
+                match &mut compressed {
+                    CompressedVector::Delta { base_id: b, .. } => *b = base_id,
+                    CompressedVector::QuantizedDelta { base_id: b, .. } => *b = base_id,
+                    CompressedVector::Full(_) => {}
+                }
+                self.vectors.insert(id, compressed);
+                self.anchors.remove(&id);
+            }
+            _ => {
+                self.vectors.insert(id, CompressedVector::Full(vector));
+                self.anchors.insert(id);
             }
-            self.anchors.remove(&id);
         }
-        self.vectors.remove(&id).is_some()
     }
     
+    /// Re-select anchors and re-encode every delta against its nearest anchor
+    /// 
+    /// Insertion order decides anchors in [`insert`](Self::insert), which leaves long
+    /// delta chains and poorly matched bases after many mutations. This flattens all
+    /// chains to depth one. Vectors that do not compress against any existing anchor
+    /// become anchors themselves. Returns the statistics after re-encoding.
+    pub fn reselect_anchors(&mut self) -> CompressionStats {
+        if self.config.mode == CompressionMode::None {
+            return self.stats();
+        }
+
+        let mut ids: Vec<VectorId> = self.vectors.keys().copied().collect();
+        ids.sort_unstable();
+        let inflated: Vec<(VectorId, Embedding)> = ids
+            .into_iter()
+            .filter_map(|id| self.get_full(id).map(|v| (id, v)))
+            .collect();
+
+        self.vectors.clear();
+        self.anchors.clear();
+
+        let compressor = DeltaCompressor::new(self.config.clone());
+        let mut anchor_vectors: Vec<(VectorId, Embedding)> = Vec::new();
+
+        for (id, vector) in inflated {
+            let nearest = anchor_vectors.iter().min_by(|(_, a), (_, b)| {
+                squared_distance(&vector, a).total_cmp(&squared_distance(&vector, b))
+            });
+
+            let compressed = nearest.and_then(|(base_id, base)| {
+                let mut compressed = compressor.compress(&vector, base)?;
+                match &mut compressed {
+                    CompressedVector::Delta { base_id: b, .. } => *b = *base_id,
+                    CompressedVector::QuantizedDelta { base_id: b, .. } => *b = *base_id,
+                    CompressedVector::Full(_) => return None,
+                }
+                Some(compressed)
+            });
+
+            match compressed {
+                Some(compressed) => {
+                    self.vectors.insert(id, compressed);
+                }
+                None => {
+                    self.vectors.insert(id, CompressedVector::Full(vector.clone()));
+                    self.anchors.insert(id);
+                    anchor_vectors.push((id, vector));
+                }
+            }
+        }
+
+        self.stats()
+    }
+
     /// Get storage statistics
     pub fn stats(&self) -> CompressionStats {
         let total_vectors = self.vectors.len();
@@ -457,11 +569,9 @@ impl CompressedVectorStore {
             compressed_bytes,
             uncompressed_bytes,
             compression_ratio,
-            avg_delta_size: if delta_count > 0 {
-                (compressed_bytes - anchor_count * (self.dimensions * 4 + 8)) / delta_count
-            } else {
-                0
-            },
+            avg_delta_size: (compressed_bytes - anchor_count * (self.dimensions * 4 + 8))
+                .checked_div(delta_count)
+                .unwrap_or(0),
         }
     }
     
@@ -481,6 +591,10 @@ impl CompressedVectorStore {
     }
 }
 
+fn squared_distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum()
+}
+
 /// Statistics about compression effectiveness
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompressionStats {
@@ -635,6 +749,100 @@ mod tests {
         assert!(stats.delta_count > 0, "Expected some delta-compressed vectors");
     }
     
+    #[test]
+    fn test_reselect_anchors_drops_forced_anchors() {
+        let config = CompressionConfig {
+            mode: CompressionMode::Delta,
+            sparsity_threshold: 0.001,
+            max_density: 0.5,
+            anchor_frequency: 2, // Forces every other vector to be an anchor
+            quantization_bits: 8,
+        };
+        let mut store = CompressedVectorStore::new(128, config);
+
+        let v0 = random_vector(128);
+        store.insert(0, v0.clone(), None);
+        for i in 1..10 {
+            store.insert(i, similar_vector(&v0, 0.1), Some(0));
+        }
+        let before = store.stats();
+        let originals: Vec<Embedding> = (0..10).map(|i| store.get_full(i).unwrap()).collect();
+
+        let after = store.reselect_anchors();
+        assert_eq!(after.total_vectors, 10);
+        assert!(after.anchor_count < before.anchor_count);
+        assert!(after.compression_ratio > before.compression_ratio);
+
+        for (i, original) in originals.iter().enumerate() {
+            let restored = store.get_full(i as VectorId).unwrap();
+            for (o, r) in original.iter().zip(&restored) {
+                assert!((o - r).abs() < 0.01);
+            }
+        }
+    }
+
+    #[test]
+    fn test_remove_anchor_with_dependents() {
+        let config = CompressionConfig {
+            mode: CompressionMode::Delta,
+            sparsity_threshold: 0.001,
+            max_density: 0.5,
+            anchor_frequency: 100,
+            quantization_bits: 8,
+        };
+        let mut store = CompressedVectorStore::new(128, config);
+
+        let v0 = random_vector(128);
+        store.insert(0, v0.clone(), None);
+        let originals: Vec<Embedding> = (1..5).map(|i| similar_vector(&v0, 0.1 * i as f32)).collect();
+        for (i, v) in originals.iter().enumerate() {
+            store.insert(i as VectorId + 1, v.clone(), Some(0));
+        }
+        assert_eq!(store.stats().delta_count, 4);
+
+        // Removing the anchor succeeds and promotes a dependent
+        assert!(store.remove(0));
+        assert_eq!(store.len(), 4);
+        assert!(store.get_full(0).is_none());
+        assert!(store.is_anchor(1));
+
+        // Every remaining vector still decompresses to its original value
+        for (i, original) in originals.iter().enumerate() {
+            let restored = store.get_full(i as VectorId + 1).unwrap();
+            for (a, b) in original.iter().zip(restored.iter()) {
+                assert!((a - b).abs() < 1e-4);
+            }
+        }
+        assert!(store.vectors.values().all(|v| v.base_id().is_none_or(|b| store.vectors.contains_key(&b))));
+    }
+
+    #[test]
+    fn test_remove_delta_in_chain_rebases_onto_parent() {
+        let config = CompressionConfig {
+            mode: CompressionMode::Delta,
+            sparsity_threshold: 0.001,
+            max_density: 0.5,
+            anchor_frequency: 100,
+            quantization_bits: 8,
+        };
+        let mut store = CompressedVectorStore::new(128, config);
+
+        let v0 = random_vector(128);
+        let v1 = similar_vector(&v0, 0.1);
+        let v2 = similar_vector(&v1, 0.1);
+        store.insert(0, v0, None);
+        store.insert(1, v1, Some(0));
+        store.insert(2, v2.clone(), Some(1));
+
+        assert!(store.remove(1));
+        assert_eq!(store.get_compressed(2).unwrap().base_id(), Some(0));
+        let restored = store.get_full(2).unwrap();
+        for (a, b) in v2.iter().zip(restored.iter()) {
+            assert!((a - b).abs() < 1e-4);
+        }
+        assert!(!store.remove(1));
+    }
+
     #[test]
     fn test_quantized_compression() {
         // Use relaxed config for test
@@ -35,6 +35,13 @@ pub fn cosine_distance(a: &Embedding, b: &Embedding) -> f32 {
     1.0 - similarity.clamp(-1.0, 1.0)
 }
 
+/// Cosine distance for vectors that are already unit length
+/// Equivalent to `cosine_distance` but skips both norm computations
+#[inline]
+pub fn cosine_distance_normalized(a: &Embedding, b: &Embedding) -> f32 {
+    1.0 - dot_product(a, b).clamp(-1.0, 1.0)
+}
+
 /// Cosine similarity: dot(a, b) / (||a|| * ||b||)
 /// Range: [-1, 1], where 1 = identical, -1 = opposite
 #[inline]
@@ -176,6 +183,14 @@ mod tests {
         assert!((cosine_distance(&a, &d) - 2.0).abs() < 1e-6);
     }
 
+    #[test]
+    fn test_cosine_distance_normalized() {
+        let a = normalized(&vec![3.0, 4.0, 0.0]);
+        let b = normalized(&vec![1.0, 2.0, 2.0]);
+        let expected = cosine_distance(&a, &b);
+        assert!((cosine_distance_normalized(&a, &b) - expected).abs() < 1e-6);
+    }
+
     #[test]
     fn test_euclidean_distance() {
         let a = vec![0.0, 0.0, 0.0];
@@ -2,12 +2,18 @@
 //! 
 //! Supports multiple embedding backends:
 //! - Local models via ONNX Runtime (candle/ort)
+//! - Hugging Face Inference API (feature `huggingface`)
 //! - OpenAI API
-//! - Custom embedding functions
+//! - Custom embedding functions ([`EmbeddingConfig::Custom`])
 
-use super::types::Embedding;
+use super::tokens::approximate_tokens;
+use super::types::{Embedding, EmbeddingFingerprint};
 use crate::error::{KeraDBError, Result};
 
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::ops::Range;
 use std::sync::Arc;
 
 /// Trait for embedding providers
@@ -25,6 +31,37 @@ pub trait EmbeddingProvider: Send + Sync {
     
     /// Get the model name
     fn model_name(&self) -> &str;
+    
+    /// Get the model version, if the provider tracks one
+    fn model_version(&self) -> Option<&str> {
+        None
+    }
+    
+    /// Identify the model, to check that a collection is searched with the
+    /// model it was built with
+    fn fingerprint(&self) -> EmbeddingFingerprint {
+        EmbeddingFingerprint {
+            model: self.model_name().to_string(),
+            dimensions: self.dimensions(),
+            version: self.model_version().map(str::to_string),
+        }
+    }
+    
+    /// Longest input the model accepts, in tokens, if it has a limit
+    fn max_tokens(&self) -> Option<usize> {
+        None
+    }
+    
+    /// Split text into the model's tokens, as byte ranges; approximated
+    /// unless the provider knows its model's tokenizer
+    fn tokenize(&self, text: &str) -> Vec<Range<usize>> {
+        approximate_tokens(text)
+    }
+    
+    /// Count the tokens in `text`
+    fn count_tokens(&self, text: &str) -> usize {
+        self.tokenize(text).len()
+    }
 }
 
 /// Mock embedding provider for testing (generates random normalized vectors)
@@ -129,6 +166,54 @@ impl EmbeddingProvider for TfIdfEmbeddingProvider {
     }
 }
 
+type EmbedCallback = dyn Fn(&str) -> Result<Embedding> + Send + Sync;
+
+/// An application's embedding function, for [`EmbeddingConfig::Custom`]
+#[derive(Clone)]
+pub struct EmbedFn(Arc<EmbedCallback>);
+
+impl EmbedFn {
+    pub fn new(f: impl Fn(&str) -> Result<Embedding> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+}
+
+impl fmt::Debug for EmbedFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EmbedFn")
+    }
+}
+
+/// Embeddings from an [`EmbedFn`], checked against the dimensions it promised
+struct CustomEmbeddingProvider {
+    model: String,
+    dimensions: usize,
+    embed: EmbedFn,
+}
+
+impl EmbeddingProvider for CustomEmbeddingProvider {
+    fn embed(&self, text: &str) -> Result<Embedding> {
+        let embedding = (self.embed.0)(text)?;
+        if embedding.len() != self.dimensions {
+            return Err(KeraDBError::EmbeddingError(format!(
+                "Model '{}' returned {} dimensions instead of {}",
+                self.model,
+                embedding.len(),
+                self.dimensions
+            )));
+        }
+        Ok(embedding)
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+}
+
 /// Configuration for embedding providers
 #[derive(Debug, Clone)]
 pub enum EmbeddingConfig {
@@ -138,6 +223,22 @@ pub enum EmbeddingConfig {
     /// Simple TF-IDF hash-based embeddings
     TfIdf { dimensions: usize },
     
+    /// A model the application runs itself, called in-process; `model` names
+    /// it in the collections it builds
+    Custom {
+        model: String,
+        dimensions: usize,
+        embed: EmbedFn,
+    },
+    
+    /// Hugging Face Inference API; the model's dimensions are learned from
+    /// one request when the provider is created
+    #[cfg(feature = "huggingface")]
+    HuggingFace {
+        api_token: String,
+        model: String,
+    },
+    
     /// OpenAI API
     #[cfg(feature = "openai")]
     OpenAI { 
@@ -153,6 +254,77 @@ pub enum EmbeddingConfig {
     },
 }
 
+/// Environment variable the Hugging Face API token is read from when a
+/// stored provider config is restored
+pub const HF_TOKEN_VAR: &str = "HF_TOKEN";
+/// Environment variable the OpenAI API key is read from when a stored
+/// provider config is restored
+pub const OPENAI_API_KEY_VAR: &str = "OPENAI_API_KEY";
+
+/// An [`EmbeddingConfig`] as stored in a database, without secrets
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "provider", rename_all = "snake_case")]
+enum StoredConfig {
+    Mock { dimensions: usize },
+    TfIdf { dimensions: usize },
+    HuggingFace { model: String },
+    #[serde(rename = "openai")]
+    OpenAI { model: String },
+    Onnx { model_path: String, dimensions: usize },
+}
+
+impl EmbeddingConfig {
+    /// The config to store in a database, without API tokens; `None` for a
+    /// custom function, which cannot be stored
+    pub(crate) fn to_stored(&self) -> Option<Value> {
+        let stored = match self {
+            EmbeddingConfig::Mock { dimensions } => StoredConfig::Mock { dimensions: *dimensions },
+            EmbeddingConfig::TfIdf { dimensions } => StoredConfig::TfIdf { dimensions: *dimensions },
+            EmbeddingConfig::Custom { .. } => return None,
+            #[cfg(feature = "huggingface")]
+            EmbeddingConfig::HuggingFace { model, .. } => StoredConfig::HuggingFace { model: model.clone() },
+            #[cfg(feature = "openai")]
+            EmbeddingConfig::OpenAI { model, .. } => StoredConfig::OpenAI { model: model.clone() },
+            #[cfg(feature = "onnx")]
+            EmbeddingConfig::Onnx { model_path, dimensions } => {
+                StoredConfig::Onnx { model_path: model_path.clone(), dimensions: *dimensions }
+            }
+        };
+        serde_json::to_value(stored).ok()
+    }
+
+    /// Rebuild a stored config, taking API tokens from the environment
+    pub(crate) fn from_stored(value: Value) -> Result<Self> {
+        #[cfg(not(all(feature = "huggingface", feature = "openai", feature = "onnx")))]
+        let unsupported = |feature: &str| {
+            KeraDBError::Config(format!("The stored embedding provider needs the `{}` feature", feature))
+        };
+        match serde_json::from_value(value)? {
+            StoredConfig::Mock { dimensions } => Ok(EmbeddingConfig::Mock { dimensions }),
+            StoredConfig::TfIdf { dimensions } => Ok(EmbeddingConfig::TfIdf { dimensions }),
+            #[cfg(feature = "huggingface")]
+            StoredConfig::HuggingFace { model } => {
+                // Public models can be used without a token, within tighter rate limits
+                Ok(EmbeddingConfig::HuggingFace { api_token: std::env::var(HF_TOKEN_VAR).unwrap_or_default(), model })
+            }
+            #[cfg(feature = "openai")]
+            StoredConfig::OpenAI { model } => {
+                let api_key = std::env::var(OPENAI_API_KEY_VAR)
+                    .map_err(|_| KeraDBError::Config(format!("{} is not set", OPENAI_API_KEY_VAR)))?;
+                Ok(EmbeddingConfig::OpenAI { api_key, model })
+            }
+            #[cfg(feature = "onnx")]
+            StoredConfig::Onnx { model_path, dimensions } => Ok(EmbeddingConfig::Onnx { model_path, dimensions }),
+            #[cfg(not(feature = "huggingface"))]
+            StoredConfig::HuggingFace { .. } => Err(unsupported("huggingface")),
+            #[cfg(not(feature = "openai"))]
+            StoredConfig::OpenAI { .. } => Err(unsupported("openai")),
+            #[cfg(not(feature = "onnx"))]
+            StoredConfig::Onnx { .. } => Err(unsupported("onnx")),
+        }
+    }
+}
+
 impl Default for EmbeddingConfig {
     fn default() -> Self {
         EmbeddingConfig::TfIdf { dimensions: 384 }
@@ -168,15 +340,22 @@ pub fn create_provider(config: EmbeddingConfig) -> Result<Arc<dyn EmbeddingProvi
         EmbeddingConfig::TfIdf { dimensions } => {
             Ok(Arc::new(TfIdfEmbeddingProvider::new(dimensions)))
         }
+        EmbeddingConfig::Custom { model, dimensions, embed } => {
+            Ok(Arc::new(CustomEmbeddingProvider { model, dimensions, embed }))
+        }
+        #[cfg(feature = "huggingface")]
+        EmbeddingConfig::HuggingFace { api_token, model } => {
+            Ok(Arc::new(super::huggingface::HuggingFaceEmbeddingProvider::new(api_token, model).connect()?))
+        }
         #[cfg(feature = "openai")]
-        EmbeddingConfig::OpenAI { api_key, model } => {
+        EmbeddingConfig::OpenAI { .. } => {
             // OpenAI implementation would go here
-            Err(KeraDBError::NotImplemented("OpenAI embedding not yet implemented".into()))
+            Err(crate::error::KeraDBError::NotImplemented("OpenAI embedding not yet implemented".into()))
         }
         #[cfg(feature = "onnx")]
-        EmbeddingConfig::Onnx { model_path, dimensions } => {
+        EmbeddingConfig::Onnx { .. } => {
             // ONNX implementation would go here
-            Err(KeraDBError::NotImplemented("ONNX embedding not yet implemented".into()))
+            Err(crate::error::KeraDBError::NotImplemented("ONNX embedding not yet implemented".into()))
         }
     }
 }
@@ -234,4 +413,15 @@ mod tests {
         let dot: f32 = e1.iter().zip(&e2).map(|(a, b)| a * b).sum();
         assert!(dot > 0.0); // Should share some words
     }
+
+    #[test]
+    fn test_custom_provider() {
+        let embed = EmbedFn::new(|text| Ok(if text.is_empty() { vec![0.0] } else { vec![text.len() as f32, 1.0] }));
+        let provider = create_provider(EmbeddingConfig::Custom { model: "lengths".into(), dimensions: 2, embed }).unwrap();
+
+        assert_eq!(provider.embed("abc").unwrap(), vec![3.0, 1.0]);
+        assert_eq!(provider.fingerprint().model, "lengths");
+        // The function must keep to its dimensions
+        assert!(matches!(provider.embed(""), Err(KeraDBError::EmbeddingError(_))));
+    }
 }
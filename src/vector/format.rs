@@ -0,0 +1,299 @@
+//! The format vector collections are saved in
+//!
+//! ```text
+//! "KVEC" | version: u32 | min_reader_version: u32 | collection* | 0u8
+//! collection = 1u8 | name_len: u32 | name | (chunk_len: u32 | chunk)* | 0u32 | crc32
+//! ```
+//!
+//! Collections are streamed: each is written through chunks of at most
+//! [`CHUNK_SIZE`] bytes as it is serialized, and read back the same way, so
+//! neither saving nor loading holds a collection's serialized bytes in memory.
+//! Each collection carries the CRC32 of its chunks, so one damaged collection
+//! is skipped while the others load.
+//!
+//! `min_reader_version` is the oldest format a reader must understand: a
+//! later version that only adds to the format, e.g. by appending data after
+//! the collections, leaves it as it is so older readers still load what they
+//! understand, and raises it only when they could not. Version 2 held every
+//! collection's bytes in one bincode list, and data saved before the format
+//! was versioned starts without the magic bytes; both are still read.
+
+use super::search::VectorCollection;
+use crate::error::{KeraDBError, Result};
+use serde::Deserialize;
+use std::io::{self, BufReader, Read, Write};
+use std::sync::Arc;
+
+const MAGIC: &[u8; 4] = b"KVEC";
+/// The format this version writes
+const FORMAT_VERSION: u32 = 3;
+/// The oldest format version that can read what this version writes
+const MIN_READER_VERSION: u32 = 3;
+/// Largest chunk a collection is written in
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Longer names are taken for damage rather than allocated
+const MAX_NAME_LEN: usize = 64 * 1024;
+
+/// A collection in version 2
+#[derive(Deserialize)]
+struct SavedCollection {
+    name: String,
+    crc: u32,
+    bytes: Vec<u8>,
+}
+
+/// The format before versioning
+#[derive(Deserialize)]
+struct UnversionedData {
+    #[allow(dead_code)]
+    version: u32,
+    collections: Vec<Vec<u8>>,
+}
+
+/// Each collection loaded, or why it could not be
+pub(crate) type Decoded = Vec<std::result::Result<VectorCollection, String>>;
+
+fn write_failed(e: io::Error) -> KeraDBError {
+    KeraDBError::StorageError(format!("Failed to serialize vector data: {}", e))
+}
+
+/// Write `collections` to `writer`
+pub(crate) fn encode(collections: &[Arc<VectorCollection>], writer: &mut dyn Write) -> Result<()> {
+    writer.write_all(MAGIC).map_err(write_failed)?;
+    writer.write_all(&FORMAT_VERSION.to_le_bytes()).map_err(write_failed)?;
+    writer.write_all(&MIN_READER_VERSION.to_le_bytes()).map_err(write_failed)?;
+    for collection in collections {
+        writer.write_all(&[1]).map_err(write_failed)?;
+        writer.write_all(&(collection.name.len() as u32).to_le_bytes()).map_err(write_failed)?;
+        writer.write_all(collection.name.as_bytes()).map_err(write_failed)?;
+        let mut chunks = ChunkWriter::new(&mut *writer);
+        collection.write_to(&mut chunks)?;
+        chunks.finish().map_err(write_failed)?;
+    }
+    writer.write_all(&[0]).map_err(write_failed)
+}
+
+/// Read collections from `reader`
+///
+/// Fails with [`KeraDBError::VersionMismatch`] for data a newer KeraDB saved
+/// in a format this one cannot read, and [`KeraDBError::InvalidFormat`] for
+/// data that is not vector collections at all.
+pub(crate) fn decode(reader: &mut dyn Read) -> Result<Decoded> {
+    let invalid = |e: bincode::Error| KeraDBError::InvalidFormat(format!("Undecodable vector data: {}", e));
+    let mut header = Vec::with_capacity(12);
+    reader.take(12).read_to_end(&mut header)?;
+    if !header.starts_with(MAGIC) {
+        let mut data = header;
+        reader.read_to_end(&mut data)?;
+        let unversioned: UnversionedData = bincode::deserialize(&data).map_err(invalid)?;
+        return Ok(unversioned.collections.iter().map(|bytes| from_bytes(bytes)).collect());
+    }
+    if header.len() < 12 {
+        return Err(KeraDBError::InvalidFormat("Truncated vector data header".to_string()));
+    }
+
+    let field = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+    let (version, min_reader_version) = (field(4), field(8));
+    if min_reader_version > FORMAT_VERSION {
+        return Err(KeraDBError::VersionMismatch { expected: FORMAT_VERSION, actual: version });
+    }
+    if version == 2 {
+        let saved: Vec<SavedCollection> = bincode::deserialize_from(reader).map_err(invalid)?;
+        return Ok(saved
+            .into_iter()
+            .map(|collection| {
+                if crc32fast::hash(&collection.bytes) != collection.crc {
+                    return Err(format!("checksum mismatch in '{}'", collection.name));
+                }
+                from_bytes(&collection.bytes)
+            })
+            .collect());
+    }
+
+    // Anything a newer version appends after the collections is ignored
+    let mut decoded = Vec::new();
+    while read_u8(reader)? == 1 {
+        let len = read_u32(reader)? as usize;
+        if len > MAX_NAME_LEN {
+            return Err(KeraDBError::InvalidFormat(format!("Vector collection name of {} bytes", len)));
+        }
+        let mut name = vec![0u8; len];
+        reader.read_exact(&mut name)?;
+        let name = String::from_utf8_lossy(&name).into_owned();
+
+        let mut chunks = BufReader::with_capacity(CHUNK_SIZE, ChunkReader::new(&mut *reader));
+        let collection = VectorCollection::read_from(&mut chunks).map_err(|e| e.to_string());
+        match chunks.into_inner().finish() {
+            Ok(()) => decoded.push(collection),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => decoded.push(Err(format!("checksum mismatch in '{}'", name))),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(decoded)
+}
+
+fn from_bytes(bytes: &[u8]) -> std::result::Result<VectorCollection, String> {
+    VectorCollection::from_bytes(bytes).map_err(|e| e.to_string())
+}
+
+fn read_u8(reader: &mut dyn Read) -> io::Result<u8> {
+    let mut byte = [0u8];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_u32(reader: &mut dyn Read) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Splits what is written into length-prefixed chunks, checksumming them
+struct ChunkWriter<W: Write> {
+    inner: W,
+    chunk: Vec<u8>,
+    crc: crc32fast::Hasher,
+}
+
+impl<W: Write> ChunkWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, chunk: Vec::with_capacity(CHUNK_SIZE), crc: crc32fast::Hasher::new() }
+    }
+
+    fn write_chunk(&mut self) -> io::Result<()> {
+        if !self.chunk.is_empty() {
+            self.inner.write_all(&(self.chunk.len() as u32).to_le_bytes())?;
+            self.inner.write_all(&self.chunk)?;
+            self.crc.update(&self.chunk);
+            self.chunk.clear();
+        }
+        Ok(())
+    }
+
+    /// Write what is left, the empty chunk that ends them, and the checksum
+    fn finish(mut self) -> io::Result<()> {
+        self.write_chunk()?;
+        self.inner.write_all(&0u32.to_le_bytes())?;
+        self.inner.write_all(&self.crc.finalize().to_le_bytes())
+    }
+}
+
+impl<W: Write> Write for ChunkWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(CHUNK_SIZE - self.chunk.len());
+        self.chunk.extend_from_slice(&buf[..n]);
+        if self.chunk.len() == CHUNK_SIZE {
+            self.write_chunk()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads what a [`ChunkWriter`] wrote, ending at its empty chunk
+struct ChunkReader<R: Read> {
+    inner: R,
+    /// Bytes left in the current chunk
+    left: usize,
+    done: bool,
+    crc: crc32fast::Hasher,
+}
+
+impl<R: Read> ChunkReader<R> {
+    fn new(inner: R) -> Self {
+        Self { inner, left: 0, done: false, crc: crc32fast::Hasher::new() }
+    }
+
+    /// Skip any chunks not read and check the checksum; an `InvalidData`
+    /// error if it does not match
+    fn finish(mut self) -> io::Result<()> {
+        io::copy(&mut self, &mut io::sink())?;
+        let crc = read_u32(&mut self.inner)?;
+        if crc != self.crc.finalize() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "vector collection checksum mismatch"));
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for ChunkReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.left == 0 && !self.done {
+            self.left = read_u32(&mut self.inner)? as usize;
+            self.done = self.left == 0;
+        }
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        let len = self.left.min(buf.len());
+        let n = self.inner.read(&mut buf[..len])?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.crc.update(&buf[..n]);
+        self.left -= n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::VectorConfig;
+
+    fn collection(name: &str, vectors: usize) -> Arc<VectorCollection> {
+        let collection = VectorCollection::new(name.to_string(), VectorConfig::new(32));
+        for n in 0..vectors {
+            let vector = (0..32).map(|i| ((n * 32 + i) % 97) as f32 / 97.0).collect();
+            collection.insert(vector, Some(serde_json::json!({"n": n}))).unwrap();
+        }
+        Arc::new(collection)
+    }
+
+    fn names(decoded: &Decoded) -> Vec<std::result::Result<String, String>> {
+        decoded.iter().map(|c| c.as_ref().map(|c| c.name.clone()).map_err(Clone::clone)).collect()
+    }
+
+    #[test]
+    fn test_versions_and_checksums() {
+        // Large enough to span chunks
+        let mut data = Vec::new();
+        encode(&[collection("a", 300), collection("b", 3)], &mut data).unwrap();
+        assert!(data.len() > CHUNK_SIZE);
+        let decoded = decode(&mut data.as_slice()).unwrap();
+        assert_eq!(names(&decoded), vec![Ok("a".to_string()), Ok("b".to_string())]);
+        assert_eq!(decoded[0].as_ref().unwrap().len(), 300);
+
+        // A damaged collection is reported, the others still load
+        let damaged = data.len() - 30;
+        data[damaged] ^= 0x01;
+        let decoded = decode(&mut data.as_slice()).unwrap();
+        assert_eq!(names(&decoded)[0], Ok("a".to_string()));
+        assert_eq!(names(&decoded)[1], Err("checksum mismatch in 'b'".to_string()));
+
+        // A newer version that older readers can read, with more at the end
+        let mut newer = Vec::new();
+        encode(&[collection("a", 1)], &mut newer).unwrap();
+        newer[4..8].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        newer.extend_from_slice(b"future");
+        assert_eq!(names(&decode(&mut newer.as_slice()).unwrap()), vec![Ok("a".to_string())]);
+        // And one they cannot
+        newer[8..12].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        let error = decode(&mut newer.as_slice()).err().unwrap();
+        assert!(matches!(error, KeraDBError::VersionMismatch { actual, .. } if actual == FORMAT_VERSION + 1));
+
+        // Version 2, and data saved before versioning
+        let bytes = collection("old", 1).to_bytes().unwrap();
+        let mut v2 = MAGIC.to_vec();
+        v2.extend_from_slice(&2u32.to_le_bytes());
+        v2.extend_from_slice(&2u32.to_le_bytes());
+        v2.extend(bincode::serialize(&vec![("old", crc32fast::hash(&bytes), &bytes)]).unwrap());
+        assert_eq!(names(&decode(&mut v2.as_slice()).unwrap()), vec![Ok("old".to_string())]);
+        let unversioned = bincode::serialize(&(1u32, vec![&bytes])).unwrap();
+        assert_eq!(names(&decode(&mut unversioned.as_slice()).unwrap()), vec![Ok("old".to_string())]);
+        assert!(matches!(decode(&mut &b"garbage"[..]), Err(KeraDBError::InvalidFormat(_))));
+    }
+}
@@ -0,0 +1,298 @@
+//! Batched distance computation with optional GPU offload
+//!
+//! Exact re-ranking and brute-force search compute one distance per candidate,
+//! which is embarrassingly parallel. With the `gpu` feature enabled, batches of
+//! at least [`GPU_MIN_BATCH`] candidates are dispatched to a wgpu compute shader.
+//! Smaller batches, builds without the feature, and machines without a usable
+//! adapter all take the CPU path, so callers never need to check.
+
+use super::distance::calculate_distance;
+use super::types::{Distance, Embedding};
+
+/// Minimum number of candidates before the GPU path is worth the transfer cost
+pub const GPU_MIN_BATCH: usize = 10_000;
+
+/// Compute the distance from `query` to every vector in `vectors`
+///
+/// Results are in the same order as `vectors`. All vectors must have the same
+/// dimensionality as the query.
+pub fn batch_distances(query: &Embedding, vectors: &[&Embedding], metric: Distance) -> Vec<f32> {
+    #[cfg(feature = "gpu")]
+    if vectors.len() >= GPU_MIN_BATCH {
+        if let Some(distances) = wgpu_backend::batch_distances(query, vectors, metric) {
+            return distances;
+        }
+    }
+
+    cpu_batch_distances(query, vectors, metric)
+}
+
+/// Whether a GPU adapter is available for batch distance computation
+pub fn gpu_available() -> bool {
+    #[cfg(feature = "gpu")]
+    {
+        wgpu_backend::context().is_some()
+    }
+    #[cfg(not(feature = "gpu"))]
+    {
+        false
+    }
+}
+
+fn cpu_batch_distances(query: &Embedding, vectors: &[&Embedding], metric: Distance) -> Vec<f32> {
+    vectors
+        .iter()
+        .map(|v| calculate_distance(query, v, metric))
+        .collect()
+}
+
+#[cfg(feature = "gpu")]
+mod wgpu_backend {
+    use super::{Distance, Embedding};
+    use std::sync::OnceLock;
+    use wgpu::util::DeviceExt;
+
+    const WORKGROUP_SIZE: u32 = 64;
+
+    const SHADER: &str = r#"
+struct Params {
+    dim: u32,
+    count: u32,
+    metric: u32,
+    _pad: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> query: array<f32>;
+@group(0) @binding(2) var<storage, read> vectors: array<f32>;
+@group(0) @binding(3) var<storage, read_write> out: array<f32>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if (i >= params.count) {
+        return;
+    }
+
+    let base = i * params.dim;
+    var dot = 0.0;
+    var norm_a = 0.0;
+    var norm_b = 0.0;
+    var l2 = 0.0;
+    var l1 = 0.0;
+    for (var j = 0u; j < params.dim; j = j + 1u) {
+        let a = query[j];
+        let b = vectors[base + j];
+        let d = a - b;
+        dot = dot + a * b;
+        norm_a = norm_a + a * a;
+        norm_b = norm_b + b * b;
+        l2 = l2 + d * d;
+        l1 = l1 + abs(d);
+    }
+
+    switch params.metric {
+        case 0u: {
+            if (norm_a == 0.0 || norm_b == 0.0) {
+                out[i] = 1.0;
+            } else {
+                out[i] = 1.0 - clamp(dot / (sqrt(norm_a) * sqrt(norm_b)), -1.0, 1.0);
+            }
+        }
+        case 1u: { out[i] = sqrt(l2); }
+        case 2u: { out[i] = -dot; }
+        default: { out[i] = l1; }
+    }
+}
+"#;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+    struct Params {
+        dim: u32,
+        count: u32,
+        metric: u32,
+        _pad: u32,
+    }
+
+    pub(super) struct GpuContext {
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        pipeline: wgpu::ComputePipeline,
+        max_binding_size: u64,
+    }
+
+    /// Lazily initialized GPU context, `None` when no adapter is available
+    pub(super) fn context() -> Option<&'static GpuContext> {
+        static CONTEXT: OnceLock<Option<GpuContext>> = OnceLock::new();
+        CONTEXT.get_or_init(|| pollster::block_on(init())).as_ref()
+    }
+
+    async fn init() -> Option<GpuContext> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                ..Default::default()
+            })
+            .await?;
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("keradb-distance"),
+                    required_features: wgpu::Features::empty(),
+                    required_limits: adapter.limits(),
+                    memory_hints: wgpu::MemoryHints::Performance,
+                },
+                None,
+            )
+            .await
+            .map_err(|e| tracing::warn!("GPU device unavailable, using CPU distances: {}", e))
+            .ok()?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("keradb-distance"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("keradb-distance"),
+            layout: None,
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let limits = device.limits();
+        let max_binding_size = u64::from(limits.max_storage_buffer_binding_size).min(limits.max_buffer_size);
+
+        Some(GpuContext { device, queue, pipeline, max_binding_size })
+    }
+
+    /// Compute distances on the GPU, or `None` to fall back to the CPU
+    pub(super) fn batch_distances(query: &Embedding, vectors: &[&Embedding], metric: Distance) -> Option<Vec<f32>> {
+        let ctx = context()?;
+        let dim = query.len();
+        if dim == 0 || vectors.iter().any(|v| v.len() != dim) {
+            return None;
+        }
+
+        // Split into chunks that fit in a single storage binding and dispatch
+        let bytes_per_vector = (dim * std::mem::size_of::<f32>()) as u64;
+        let max_dispatch = u64::from(ctx.device.limits().max_compute_workgroups_per_dimension) * u64::from(WORKGROUP_SIZE);
+        let chunk_len = (ctx.max_binding_size / bytes_per_vector).min(max_dispatch) as usize;
+        if chunk_len == 0 {
+            return None;
+        }
+
+        let mut distances = Vec::with_capacity(vectors.len());
+        for chunk in vectors.chunks(chunk_len) {
+            distances.extend(dispatch(ctx, query, chunk, metric)?);
+        }
+        Some(distances)
+    }
+
+    fn dispatch(ctx: &GpuContext, query: &Embedding, vectors: &[&Embedding], metric: Distance) -> Option<Vec<f32>> {
+        let device = &ctx.device;
+        let count = vectors.len() as u32;
+        let params = Params {
+            dim: query.len() as u32,
+            count,
+            metric: match metric {
+                Distance::Cosine => 0,
+                Distance::Euclidean => 1,
+                Distance::DotProduct => 2,
+                Distance::Manhattan => 3,
+            },
+            _pad: 0,
+        };
+
+        let flat: Vec<f32> = vectors.iter().flat_map(|v| v.iter()).copied().collect();
+        let out_size = u64::from(count) * std::mem::size_of::<f32>() as u64;
+
+        let params_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let query_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(query),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let vectors_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&flat),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let out_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: out_size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: out_size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &ctx.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: params_buf.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: query_buf.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: vectors_buf.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: out_buf.as_entire_binding() },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(&ctx.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(count.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&out_buf, 0, &readback_buf, 0, out_size);
+        ctx.queue.submit(Some(encoder.finish()));
+
+        let slice = readback_buf.slice(..);
+        let (tx, rx) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = tx.send(result);
+        });
+        let _ = device.poll(wgpu::Maintain::Wait);
+        rx.recv().ok()?.ok()?;
+
+        let distances = bytemuck::cast_slice::<u8, f32>(&slice.get_mapped_range()).to_vec();
+        readback_buf.unmap();
+        Some(distances)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_distances_match_scalar() {
+        let query: Embedding = (0..32).map(|i| (i as f32).sin()).collect();
+        let vectors: Vec<Embedding> = (0..GPU_MIN_BATCH + 7)
+            .map(|n| (0..32).map(|i| ((n * 31 + i) as f32).cos()).collect())
+            .collect();
+
+        for metric in [Distance::Cosine, Distance::Euclidean, Distance::DotProduct, Distance::Manhattan] {
+            let refs: Vec<&Embedding> = vectors.iter().collect();
+            let batch = batch_distances(&query, &refs, metric);
+            assert_eq!(batch.len(), vectors.len());
+            for (v, d) in vectors.iter().zip(&batch) {
+                let expected = calculate_distance(&query, v, metric);
+                assert!((expected - d).abs() < 1e-3, "{:?}: {} vs {}", metric, expected, d);
+            }
+        }
+    }
+}
@@ -8,19 +8,35 @@
 //! - High-degree preserving pruning
 //! - Lazy embedding mode for storage savings
 
-use super::distance::calculate_distance;
+use super::compression::CompressionStats;
+use super::distance::{calculate_distance, cosine_distance_normalized, norm};
+use super::gpu;
 use super::types::{Distance, Embedding, VectorDocument, VectorId, VectorConfig};
 use crate::error::{KeraDBError, Result};
 
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::io::Write;
 use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
 
 /// Maximum number of layers in the HNSW graph
 const MAX_LAYERS: usize = 16;
 
+/// Namespaces holding less than 1/N of the vectors are searched exhaustively;
+/// larger ones through the graph, fetching up to N times as many candidates
+const NAMESPACE_SCAN_FRACTION: usize = 8;
+
+/// Position of a node in the graph
+///
+/// Neighbor lists and the entry point refer to nodes by slot. Slots are
+/// renumbered densely when [`HnswIndex::optimize`] compacts the graph, so
+/// callers only ever see the node's [`VectorId`], which never changes.
+type Slot = u64;
+
 /// A node in the HNSW graph
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HnswNode {
@@ -35,8 +51,20 @@ pub struct HnswNode {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub text: Option<String>,
     
-    /// Neighbors at each layer (layer -> neighbor ids)
-    pub neighbors: Vec<Vec<VectorId>>,
+    /// Original norm of the vector (cosine indexes store unit vectors)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub norm: Option<f32>,
+    
+    /// User-supplied identifier mapped to this node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    
+    /// Namespace the node was inserted into
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    
+    /// Neighbors at each layer (layer -> neighbor slots)
+    pub neighbors: Vec<Vec<u64>>,
     
     /// The layer this node exists up to
     pub layer: usize,
@@ -49,6 +77,9 @@ impl HnswNode {
             id,
             vector: Some(vector),
             text: None,
+            norm: None,
+            external_id: None,
+            namespace: None,
             neighbors: vec![Vec::new(); layer + 1],
             layer,
         }
@@ -60,13 +91,25 @@ impl HnswNode {
             id,
             vector: None,
             text: Some(text),
+            norm: None,
+            external_id: None,
+            namespace: None,
             neighbors: vec![Vec::new(); layer + 1],
             layer,
         }
     }
 
+    /// Get the vector as originally inserted (undoing cosine normalization)
+    pub fn original_vector(&self) -> Option<Embedding> {
+        let vector = self.vector.as_ref()?;
+        Some(match self.norm {
+            Some(n) if n > 0.0 => vector.iter().map(|x| x * n).collect(),
+            _ => vector.clone(),
+        })
+    }
+
     /// Get neighbors at a specific layer
-    pub fn get_neighbors(&self, layer: usize) -> &[VectorId] {
+    pub fn get_neighbors(&self, layer: usize) -> &[u64] {
         if layer < self.neighbors.len() {
             &self.neighbors[layer]
         } else {
@@ -78,7 +121,7 @@ impl HnswNode {
 /// Candidate node for search (with distance)
 #[derive(Clone)]
 struct Candidate {
-    id: VectorId,
+    id: Slot,
     distance: f32,
 }
 
@@ -131,37 +174,73 @@ pub struct HnswIndex {
     /// Configuration
     config: VectorConfig,
     
-    /// All nodes in the graph
-    nodes: RwLock<HashMap<VectorId, HnswNode>>,
+    /// All nodes in the graph, by slot
+    nodes: RwLock<HashMap<Slot, HnswNode>>,
+    
+    /// The slot of each node, by ID (locked after `nodes`)
+    slots: RwLock<HashMap<VectorId, Slot>>,
+    
+    /// The IDs in each namespace (locked after `nodes`)
+    namespaces: RwLock<HashMap<String, HashSet<VectorId>>>,
     
     /// Entry point (node with highest layer)
-    entry_point: RwLock<Option<VectorId>>,
+    entry_point: RwLock<Option<Slot>>,
     
     /// Current maximum layer
     max_layer: RwLock<usize>,
     
-    /// Next available ID
+    /// Next available ID; IDs of deleted vectors are never reused
     next_id: AtomicU64,
     
+    /// Next available slot
+    next_slot: AtomicU64,
+    
+    /// Held shared by inserts and deletes and exclusively by `optimize`,
+    /// since an insert links its slot before the node is added
+    structure: RwLock<()>,
+    
     /// Level multiplier for random layer selection
     level_mult: f64,
+    
+    /// Source of layer assignments
+    rng: Mutex<StdRng>,
 }
 
 impl HnswIndex {
     /// Create a new HNSW index
     pub fn new(config: VectorConfig) -> Self {
+        Self::with_rng(config, StdRng::from_entropy())
+    }
+
+    /// Create an index whose layer assignments are drawn from `seed`, so the
+    /// same inserts build the same graph
+    pub fn with_seed(config: VectorConfig, seed: u64) -> Self {
+        Self::with_rng(config, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(config: VectorConfig, rng: StdRng) -> Self {
         let level_mult = 1.0 / (config.m as f64).ln();
         
         Self {
             config,
             nodes: RwLock::new(HashMap::new()),
+            slots: RwLock::new(HashMap::new()),
+            namespaces: RwLock::new(HashMap::new()),
             entry_point: RwLock::new(None),
             max_layer: RwLock::new(0),
             next_id: AtomicU64::new(0),
+            next_slot: AtomicU64::new(0),
+            structure: RwLock::new(()),
             level_mult,
+            rng: Mutex::new(rng),
         }
     }
 
+    /// The config the index was created with
+    pub fn config(&self) -> &VectorConfig {
+        &self.config
+    }
+
     /// Get the number of vectors in the index
     pub fn len(&self) -> usize {
         self.nodes.read().len()
@@ -172,9 +251,15 @@ impl HnswIndex {
         self.nodes.read().is_empty()
     }
 
+    /// Check whether a vector with this ID exists
+    pub fn contains(&self, id: VectorId) -> bool {
+        self.slots.read().contains_key(&id)
+    }
+
     /// Generate a random layer for a new node
     fn random_layer(&self) -> usize {
-        let r: f64 = rand::random();
+        // Exclude 0, whose log is infinite
+        let r: f64 = 1.0 - self.rng.lock().gen::<f64>();
         let layer = (-r.ln() * self.level_mult) as usize;
         layer.min(MAX_LAYERS - 1)
     }
@@ -190,6 +275,30 @@ impl HnswIndex {
         vector: Embedding,
         text: Option<String>,
         _metadata: Option<serde_json::Value>,
+    ) -> Result<VectorId> {
+        self.insert_with_external_id(vector, text, None)
+    }
+
+    /// Insert a vector tagged with a user-supplied identifier
+    /// 
+    /// The index only stores the identifier; keeping it unique is up to the caller.
+    pub fn insert_with_external_id(
+        &self,
+        vector: Embedding,
+        text: Option<String>,
+        external_id: Option<String>,
+    ) -> Result<VectorId> {
+        self.insert_with_namespace(vector, text, external_id, None)
+    }
+
+    /// Insert a vector into a namespace, which
+    /// [`search_in_namespace`](Self::search_in_namespace) searches on its own
+    pub fn insert_with_namespace(
+        &self,
+        vector: Embedding,
+        text: Option<String>,
+        external_id: Option<String>,
+        namespace: Option<String>,
     ) -> Result<VectorId> {
         // Validate dimensions
         if vector.len() != self.config.dimensions {
@@ -200,10 +309,16 @@ impl HnswIndex {
             )));
         }
 
+        let _structure = self.structure.read();
         let id = self.next_id.fetch_add(1, AtomicOrdering::SeqCst);
+        let slot = self.next_slot.fetch_add(1, AtomicOrdering::SeqCst);
         let layer = self.random_layer();
 
+        let (vector, vector_norm) = self.prepare_vector(vector);
         let mut node = HnswNode::new(id, vector.clone(), layer);
+        node.norm = vector_norm;
+        node.external_id = external_id;
+        node.namespace = namespace;
         if let Some(t) = text {
             node.text = Some(t);
         }
@@ -218,8 +333,8 @@ impl HnswIndex {
                 let mut max_layer = self.max_layer.write();
                 
                 if entry.is_none() {
-                    nodes.insert(id, node);
-                    *entry = Some(id);
+                    self.add_node(&mut nodes, slot, node);
+                    *entry = Some(slot);
                     *max_layer = layer;
                     return Ok(id);
                 }
@@ -243,7 +358,7 @@ impl HnswIndex {
             let neighbors = self.search_layer(&vector, current, self.config.ef_construction, lc)?;
             
             // Select M best neighbors
-            let selected: Vec<VectorId> = neighbors
+            let selected: Vec<Slot> = neighbors
                 .into_iter()
                 .take(self.config.m)
                 .map(|c| c.id)
@@ -254,7 +369,7 @@ impl HnswIndex {
                 let mut nodes = self.nodes.write();
                 
                 // Set neighbors for new node
-                if let Some(n) = nodes.get_mut(&id) {
+                if let Some(n) = nodes.get_mut(&slot) {
                     if lc < n.neighbors.len() {
                         n.neighbors[lc] = selected.clone();
                     }
@@ -263,18 +378,16 @@ impl HnswIndex {
                 }
 
                 // Add reverse connections - collect neighbors that need pruning first
-                let mut needs_pruning: Vec<(VectorId, Embedding, Vec<VectorId>)> = Vec::new();
+                let mut needs_pruning: Vec<(Slot, Embedding, Vec<Slot>)> = Vec::new();
                 
                 for &neighbor_id in &selected {
                     if let Some(neighbor) = nodes.get_mut(&neighbor_id) {
-                        if lc < neighbor.neighbors.len() {
-                            if !neighbor.neighbors[lc].contains(&id) {
-                                neighbor.neighbors[lc].push(id);
-                                // Mark for pruning if necessary
-                                if neighbor.neighbors[lc].len() > self.config.m * 2 {
-                                    if let Some(v) = neighbor.vector.clone() {
-                                        needs_pruning.push((neighbor_id, v, neighbor.neighbors[lc].clone()));
-                                    }
+                        if lc < neighbor.neighbors.len() && !neighbor.neighbors[lc].contains(&slot) {
+                            neighbor.neighbors[lc].push(slot);
+                            // Mark for pruning if necessary
+                            if neighbor.neighbors[lc].len() > self.config.m * 2 {
+                                if let Some(v) = neighbor.vector.clone() {
+                                    needs_pruning.push((neighbor_id, v, neighbor.neighbors[lc].clone()));
                                 }
                             }
                         }
@@ -284,19 +397,19 @@ impl HnswIndex {
                 // Now prune the marked neighbors (collect vectors first to avoid borrow issues)
                 for (neighbor_id, node_vector, neighbor_list) in needs_pruning {
                     // Collect all neighbor vectors first
-                    let mut with_distances: Vec<(VectorId, f32)> = neighbor_list
+                    let mut with_distances: Vec<(Slot, f32)> = neighbor_list
                         .iter()
                         .filter_map(|&nid| {
                             nodes.get(&nid).and_then(|n| {
                                 n.vector.as_ref().map(|v| {
-                                    (nid, calculate_distance(&node_vector, v, self.config.distance))
+                                    (nid, self.distance(&node_vector, v))
                                 })
                             })
                         })
                         .collect();
                     
                     with_distances.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
-                    let pruned: Vec<VectorId> = with_distances.into_iter().take(self.config.m).map(|(nid, _)| nid).collect();
+                    let pruned: Vec<Slot> = with_distances.into_iter().take(self.config.m).map(|(nid, _)| nid).collect();
                     
                     // Now update the neighbor list
                     if let Some(neighbor) = nodes.get_mut(&neighbor_id) {
@@ -313,19 +426,28 @@ impl HnswIndex {
         }
 
         // Insert the node
-        self.nodes.write().insert(id, node);
+        self.add_node(&mut self.nodes.write(), slot, node);
 
         // Update entry point if necessary
         if layer > current_max_layer {
             *self.max_layer.write() = layer;
-            *self.entry_point.write() = Some(id);
+            *self.entry_point.write() = Some(slot);
         }
 
         Ok(id)
     }
 
+    /// Add a linked node to the graph and the ID maps
+    fn add_node(&self, nodes: &mut HashMap<Slot, HnswNode>, slot: Slot, node: HnswNode) {
+        self.slots.write().insert(node.id, slot);
+        if let Some(namespace) = &node.namespace {
+            self.namespaces.write().entry(namespace.clone()).or_default().insert(node.id);
+        }
+        nodes.insert(slot, node);
+    }
+
     /// Search for a single nearest neighbor at a layer
-    fn search_layer_single(&self, query: &Embedding, entry: VectorId, layer: usize) -> Result<VectorId> {
+    fn search_layer_single(&self, query: &Embedding, entry: Slot, layer: usize) -> Result<Slot> {
         let nodes = self.nodes.read();
         let mut current = entry;
         let mut current_dist = self.distance_to_node(query, current, &nodes)?;
@@ -357,7 +479,7 @@ impl HnswIndex {
     fn search_layer(
         &self,
         query: &Embedding,
-        entry: VectorId,
+        entry: Slot,
         ef: usize,
         layer: usize,
     ) -> Result<Vec<Candidate>> {
@@ -419,8 +541,8 @@ impl HnswIndex {
     fn distance_to_node(
         &self,
         query: &Embedding,
-        node_id: VectorId,
-        nodes: &HashMap<VectorId, HnswNode>,
+        node_id: Slot,
+        nodes: &HashMap<Slot, HnswNode>,
     ) -> Result<f32> {
         let node = nodes.get(&node_id).ok_or_else(|| {
             KeraDBError::NotFound(format!("Node {} not found", node_id))
@@ -430,15 +552,40 @@ impl HnswIndex {
             KeraDBError::InvalidFormat("Node has no vector (lazy mode not fully implemented)".to_string())
         })?;
 
-        Ok(calculate_distance(query, vector, self.config.distance))
+        Ok(self.distance(query, vector))
+    }
+
+    /// Distance between two vectors as stored in the index
+    #[inline]
+    fn distance(&self, a: &Embedding, b: &Embedding) -> f32 {
+        match self.config.distance {
+            // Stored cosine vectors are unit length, so a dot product suffices
+            Distance::Cosine => cosine_distance_normalized(a, b),
+            metric => calculate_distance(a, b, metric),
+        }
+    }
+
+    /// Normalize a vector for storage when using cosine distance,
+    /// returning the original norm so the vector can be reconstructed
+    fn prepare_vector(&self, mut vector: Embedding) -> (Embedding, Option<f32>) {
+        if self.config.distance != Distance::Cosine {
+            return (vector, None);
+        }
+        let n = norm(&vector);
+        if n > 0.0 {
+            for x in vector.iter_mut() {
+                *x /= n;
+            }
+        }
+        (vector, Some(n))
     }
 
     /// Prune neighbors to keep only the best M
     fn prune_neighbors_inplace(
         &self,
-        neighbors: &mut Vec<VectorId>,
+        neighbors: &mut Vec<Slot>,
         node_vector: &Embedding,
-        nodes: &HashMap<VectorId, HnswNode>,
+        nodes: &HashMap<Slot, HnswNode>,
         max_neighbors: usize,
     ) {
         if neighbors.len() <= max_neighbors {
@@ -446,12 +593,12 @@ impl HnswIndex {
         }
 
         // Calculate distances and sort
-        let mut with_distances: Vec<(VectorId, f32)> = neighbors
+        let mut with_distances: Vec<(Slot, f32)> = neighbors
             .iter()
             .filter_map(|&id| {
                 nodes.get(&id).and_then(|n| {
                     n.vector.as_ref().map(|v| {
-                        (id, calculate_distance(node_vector, v, self.config.distance))
+                        (id, self.distance(node_vector, v))
                     })
                 })
             })
@@ -472,6 +619,83 @@ impl HnswIndex {
             )));
         }
 
+        let (query, _) = self.prepare_vector(query.clone());
+        let candidates = self.search_graph(&query, self.config.ef_search.max(k))?;
+
+        let nodes = self.nodes.read();
+        Ok(candidates
+            .into_iter()
+            .filter_map(|c| nodes.get(&c.id).map(|n| (n.id, c.distance)))
+            .take(k)
+            .collect())
+    }
+
+    /// Find the vectors within `radius` of the query, nearest first
+    /// 
+    /// Searches with a growing k until the farthest result lies outside the
+    /// radius, so it is as approximate as [`search`](Self::search).
+    pub fn search_radius(&self, query: &Embedding, radius: f32) -> Result<Vec<(VectorId, f32)>> {
+        let mut k = self.config.ef_search.max(16);
+        loop {
+            let results = self.search(query, k)?;
+            let complete = results.len() < k || results.last().is_some_and(|r| r.1 > radius);
+            if complete || k >= self.len() {
+                return Ok(results.into_iter().take_while(|r| r.1 <= radius).collect());
+            }
+            k *= 2;
+        }
+    }
+
+    /// Search for the k nearest neighbors within a namespace
+    /// 
+    /// Small namespaces are scanned exhaustively, so their recall does not
+    /// suffer from the rest of the graph crowding them out.
+    pub fn search_in_namespace(&self, query: &Embedding, k: usize, namespace: &str) -> Result<Vec<(VectorId, f32)>> {
+        if query.len() != self.config.dimensions {
+            return Err(KeraDBError::InvalidFormat(format!(
+                "Query dimension mismatch: expected {}, got {}",
+                self.config.dimensions,
+                query.len()
+            )));
+        }
+
+        let (query, _) = self.prepare_vector(query.clone());
+        let total = self.len();
+        let size = self.namespaces.read().get(namespace).map_or(0, |ids| ids.len());
+        if size == 0 {
+            return Ok(Vec::new());
+        }
+
+        if size * NAMESPACE_SCAN_FRACTION < total {
+            let nodes = self.nodes.read();
+            let namespaces = self.namespaces.read();
+            let slots = self.slots.read();
+            let members = namespaces
+                .get(namespace)
+                .into_iter()
+                .flatten()
+                .filter_map(|id| nodes.get(slots.get(id)?));
+            return Ok(self.nearest_exact(&query, k, members));
+        }
+
+        // Over-fetch so about ef of the candidates fall in the namespace
+        let ef = self.config.ef_search.max(k) * total / size;
+        let candidates = self.search_graph(&query, ef)?;
+
+        let nodes = self.nodes.read();
+        Ok(candidates
+            .into_iter()
+            .filter_map(|c| {
+                let node = nodes.get(&c.id)?;
+                (node.namespace.as_deref() == Some(namespace)).then_some((node.id, c.distance))
+            })
+            .take(k)
+            .collect())
+    }
+
+    /// Descend from the entry point and return the ef nearest candidates at
+    /// the base layer, for a query already prepared for storage
+    fn search_graph(&self, query: &Embedding, ef: usize) -> Result<Vec<Candidate>> {
         let entry = match *self.entry_point.read() {
             Some(ep) => ep,
             None => return Ok(Vec::new()),
@@ -486,36 +710,112 @@ impl HnswIndex {
         }
 
         // Search at layer 0
-        let candidates = self.search_layer(query, current, self.config.ef_search.max(k), 0)?;
+        self.search_layer(query, current, ef, 0)
+    }
+
+    /// Exact k-nearest-neighbor search by scanning every vector
+    /// 
+    /// Slower than [`search`](Self::search) but with perfect recall. Distances are
+    /// computed as one batch, on the GPU when the `gpu` feature is enabled.
+    pub fn search_exact(&self, query: &Embedding, k: usize) -> Result<Vec<(VectorId, f32)>> {
+        if query.len() != self.config.dimensions {
+            return Err(KeraDBError::InvalidFormat(format!(
+                "Query dimension mismatch: expected {}, got {}",
+                self.config.dimensions,
+                query.len()
+            )));
+        }
+
+        let (query, _) = self.prepare_vector(query.clone());
+        let nodes = self.nodes.read();
+        Ok(self.nearest_exact(&query, k, nodes.values()))
+    }
 
-        Ok(candidates.into_iter().take(k).map(|c| (c.id, c.distance)).collect())
+    /// Score every one of `nodes` against a prepared query, returning the k nearest
+    fn nearest_exact<'a>(&self, query: &Embedding, k: usize, nodes: impl Iterator<Item = &'a HnswNode>) -> Vec<(VectorId, f32)> {
+        let (ids, vectors): (Vec<VectorId>, Vec<&Embedding>) = nodes
+            .filter_map(|n| n.vector.as_ref().map(|v| (n.id, v)))
+            .unzip();
+
+        let distances = gpu::batch_distances(query, &vectors, self.config.distance);
+        let mut results: Vec<(VectorId, f32)> = ids.into_iter().zip(distances).collect();
+        results.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        results.truncate(k);
+        results
     }
 
     /// Get a node by ID
     pub fn get(&self, id: VectorId) -> Option<VectorDocument> {
         let nodes = self.nodes.read();
-        nodes.get(&id).map(|node| VectorDocument {
+        let slot = *self.slots.read().get(&id)?;
+        nodes.get(&slot).map(|node| VectorDocument {
             id: node.id,
-            embedding: node.vector.clone(),
+            embedding: node.original_vector(),
             text: node.text.clone(),
+            external_id: node.external_id.clone(),
+            namespace: node.namespace.clone(),
             metadata: serde_json::Value::Null,
         })
     }
 
+    /// Get every namespace and the number of vectors in it, by name
+    pub fn namespaces(&self) -> Vec<(String, usize)> {
+        let mut namespaces: Vec<(String, usize)> =
+            self.namespaces.read().iter().map(|(name, ids)| (name.clone(), ids.len())).collect();
+        namespaces.sort();
+        namespaces
+    }
+
+    /// Get the IDs in a namespace, in ascending order
+    pub fn namespace_ids(&self, namespace: &str) -> Vec<VectorId> {
+        let mut ids: Vec<VectorId> =
+            self.namespaces.read().get(namespace).into_iter().flatten().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Get all (internal, external) ID pairs for nodes that have an external ID
+    pub fn external_ids(&self) -> Vec<(VectorId, String)> {
+        self.nodes
+            .read()
+            .values()
+            .filter_map(|n| n.external_id.clone().map(|ext| (n.id, ext)))
+            .collect()
+    }
+
+    /// Get all node IDs, in ascending order
+    pub fn ids(&self) -> Vec<VectorId> {
+        let mut ids: Vec<VectorId> = self.slots.read().keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+
     /// Delete a node by ID
     pub fn delete(&self, id: VectorId) -> Result<bool> {
+        let _structure = self.structure.read();
         let mut nodes = self.nodes.write();
+        let slot = self.slots.write().remove(&id);
         
-        if nodes.remove(&id).is_some() {
+        if let Some(slot) = slot {
+            let namespace = nodes.remove(&slot).and_then(|node| node.namespace);
+            if let Some(namespace) = namespace {
+                let mut namespaces = self.namespaces.write();
+                if let Some(ids) = namespaces.get_mut(&namespace) {
+                    ids.remove(&id);
+                    if ids.is_empty() {
+                        namespaces.remove(&namespace);
+                    }
+                }
+            }
             // Remove references from other nodes
             for node in nodes.values_mut() {
                 for layer_neighbors in &mut node.neighbors {
-                    layer_neighbors.retain(|&n| n != id);
+                    layer_neighbors.retain(|&n| n != slot);
                 }
             }
             
             // Update entry point if necessary
-            if self.entry_point.read().map(|ep| ep == id).unwrap_or(false) {
+            if self.entry_point.read().map(|ep| ep == slot).unwrap_or(false) {
                 let mut entry = self.entry_point.write();
                 *entry = nodes.keys().next().copied();
             }
@@ -526,6 +826,242 @@ impl HnswIndex {
         }
     }
 
+    /// Repair the graph after mutations
+    /// 
+    /// Removes dangling, duplicate and self links, compacts the slots deleted
+    /// nodes left free, resets the entry point to the highest-layer node, and
+    /// reconnects nodes whose degree has dropped below M/2 or that are no longer
+    /// reachable from the entry point at layer 0 (typically because their
+    /// neighbors were deleted). Vector IDs are unchanged.
+    pub fn optimize(&self) -> Result<OptimizeReport> {
+        let _structure = self.structure.write();
+        let before = self.stats();
+        let mut dangling_links_removed = 0;
+        let compacted_ids;
+
+        {
+            let mut nodes = self.nodes.write();
+            let existing: HashSet<Slot> = nodes.keys().copied().collect();
+
+            for (&id, node) in nodes.iter_mut() {
+                for layer_neighbors in &mut node.neighbors {
+                    let len = layer_neighbors.len();
+                    let mut seen = HashSet::new();
+                    layer_neighbors.retain(|n| *n != id && existing.contains(n) && seen.insert(*n));
+                    dangling_links_removed += len - layer_neighbors.len();
+                }
+            }
+
+            compacted_ids = self.compact(&mut nodes);
+
+            // Deletes may have left an arbitrary node as entry point
+            let top = nodes
+                .iter()
+                .max_by_key(|(slot, n)| (n.layer, std::cmp::Reverse(**slot)))
+                .map(|(slot, n)| (*slot, n.layer));
+            *self.entry_point.write() = top.map(|(id, _)| id);
+            *self.max_layer.write() = top.map(|(_, layer)| layer).unwrap_or(0);
+        }
+
+        // Collect under-connected (node, layer) pairs
+        let min_degree = (self.config.m / 2).max(1);
+        let under_connected: Vec<(Slot, usize, Embedding)> = {
+            let nodes = self.nodes.read();
+            let mut layer_sizes = [0usize; MAX_LAYERS];
+            for node in nodes.values() {
+                for size in layer_sizes.iter_mut().take(node.layer + 1) {
+                    *size += 1;
+                }
+            }
+
+            // Nodes reachable from the entry point at the base layer
+            let mut reachable = HashSet::new();
+            let mut stack: Vec<Slot> = self.entry_point.read().iter().copied().collect();
+            while let Some(id) = stack.pop() {
+                if reachable.insert(id) {
+                    if let Some(node) = nodes.get(&id) {
+                        stack.extend(node.get_neighbors(0).iter().filter(|n| !reachable.contains(*n)));
+                    }
+                }
+            }
+
+            let mut pairs = Vec::new();
+            for (&slot, node) in nodes.iter() {
+                let Some(vector) = node.vector.as_ref() else { continue };
+                for (lc, size) in layer_sizes.iter().enumerate().take(node.layer + 1) {
+                    let degree = node.get_neighbors(lc).len();
+                    let available = size.saturating_sub(1);
+                    let unreachable = lc == 0 && !reachable.contains(&slot);
+                    if unreachable || degree < min_degree.min(available) {
+                        pairs.push((slot, lc, vector.clone()));
+                    }
+                }
+            }
+            pairs.sort_by_key(|(id, lc, _)| (*id, *lc));
+            pairs
+        };
+
+        let mut relinked = HashSet::new();
+        let max_layer = *self.max_layer.read();
+        let entry = *self.entry_point.read();
+
+        for (id, lc, vector) in under_connected {
+            let Some(mut current) = entry else { break };
+            for layer in (lc + 1..=max_layer).rev() {
+                current = self.search_layer_single(&vector, current, layer)?;
+            }
+
+            let mut selected: Vec<Slot> = self
+                .search_layer(&vector, current, self.config.ef_construction, lc)?
+                .into_iter()
+                .filter(|c| c.id != id)
+                .take(self.config.m)
+                .map(|c| c.id)
+                .collect();
+            if selected.len() < min_degree {
+                // The graph around the entry point is too sparse to find candidates
+                selected = self.nearest_at_layer(&vector, lc, id, self.config.m);
+            }
+
+            let mut nodes = self.nodes.write();
+            if let Some(node) = nodes.get_mut(&id) {
+                for &neighbor_id in &selected {
+                    if !node.neighbors[lc].contains(&neighbor_id) {
+                        node.neighbors[lc].push(neighbor_id);
+                    }
+                }
+            }
+
+            for &neighbor_id in &selected {
+                let Some(neighbor) = nodes.get_mut(&neighbor_id) else { continue };
+                if lc >= neighbor.neighbors.len() || neighbor.neighbors[lc].contains(&id) {
+                    continue;
+                }
+                neighbor.neighbors[lc].push(id);
+
+                if neighbor.neighbors[lc].len() > self.config.m * 2 {
+                    if let Some(neighbor_vector) = neighbor.vector.clone() {
+                        let mut list = neighbor.neighbors[lc].clone();
+                        self.prune_neighbors_inplace(&mut list, &neighbor_vector, &nodes, self.config.m);
+                        if let Some(neighbor) = nodes.get_mut(&neighbor_id) {
+                            neighbor.neighbors[lc] = list;
+                        }
+                    }
+                }
+            }
+
+            relinked.insert(id);
+        }
+
+        Ok(OptimizeReport {
+            before,
+            after: self.stats(),
+            relinked_nodes: relinked.len(),
+            dangling_links_removed,
+            compacted_ids,
+            compression: None,
+        })
+    }
+
+    /// Renumber the slots densely from zero, in their current order, and
+    /// return how many nodes moved
+    fn compact(&self, nodes: &mut HashMap<Slot, HnswNode>) -> usize {
+        let mut order: Vec<Slot> = nodes.keys().copied().collect();
+        order.sort_unstable();
+        let remap: HashMap<Slot, Slot> = order.iter().enumerate().map(|(new, &old)| (old, new as Slot)).collect();
+        let moved = remap.iter().filter(|(old, new)| old != new).count();
+        self.next_slot.store(order.len() as Slot, AtomicOrdering::SeqCst);
+        if moved == 0 {
+            return 0;
+        }
+
+        let mut slots = self.slots.write();
+        *nodes = nodes
+            .drain()
+            .map(|(old, mut node)| {
+                for layer_neighbors in &mut node.neighbors {
+                    layer_neighbors.retain_mut(|n| remap.get(n).map(|new| *n = *new).is_some());
+                }
+                let new = remap[&old];
+                slots.insert(node.id, new);
+                (new, node)
+            })
+            .collect();
+        let mut entry = self.entry_point.write();
+        *entry = entry.and_then(|old| remap.get(&old).copied());
+        moved
+    }
+
+    /// Exhaustively find the k nearest nodes present at `layer`
+    fn nearest_at_layer(&self, query: &Embedding, layer: usize, exclude: Slot, k: usize) -> Vec<Slot> {
+        let nodes = self.nodes.read();
+        let mut candidates: Vec<Candidate> = nodes
+            .iter()
+            .filter(|(slot, n)| **slot != exclude && n.layer >= layer)
+            .filter_map(|(slot, n)| {
+                let vector = n.vector.as_ref()?;
+                Some(Candidate { id: *slot, distance: self.distance(query, vector) })
+            })
+            .collect();
+        candidates.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        candidates.into_iter().take(k).map(|c| c.id).collect()
+    }
+
+    /// Compute connectivity and memory metrics for the graph
+    /// 
+    /// Walks every node, so this is O(n) and meant for diagnostics.
+    pub fn graph_metrics(&self) -> GraphMetrics {
+        let nodes = self.nodes.read();
+        let max_layer = nodes.values().map(|n| n.layer).max();
+        let mut layer_histogram = vec![0; max_layer.map_or(0, |l| l + 1)];
+        let mut total_degree = 0;
+        let mut max_out_degree = 0;
+        let mut isolated_nodes = 0;
+        let mut graph_bytes = 0;
+        let mut vector_bytes = 0;
+
+        for node in nodes.values() {
+            layer_histogram[node.layer] += 1;
+
+            let degree = node.get_neighbors(0).len();
+            total_degree += degree;
+            max_out_degree = max_out_degree.max(degree);
+            if degree == 0 && nodes.len() > 1 {
+                isolated_nodes += 1;
+            }
+
+            graph_bytes += std::mem::size_of::<HnswNode>()
+                + node
+                    .neighbors
+                    .iter()
+                    .map(|l| std::mem::size_of::<Vec<Slot>>() + l.capacity() * std::mem::size_of::<Slot>())
+                    .sum::<usize>();
+            vector_bytes += node.vector.as_ref().map_or(0, |v| v.capacity() * std::mem::size_of::<f32>())
+                + node.text.as_ref().map_or(0, |t| t.capacity());
+        }
+
+        let mut reachable = HashSet::new();
+        let mut stack: Vec<Slot> = self.entry_point.read().iter().copied().collect();
+        while let Some(id) = stack.pop() {
+            if reachable.insert(id) {
+                if let Some(node) = nodes.get(&id) {
+                    stack.extend(node.get_neighbors(0).iter().filter(|n| !reachable.contains(*n)));
+                }
+            }
+        }
+
+        let count = nodes.len();
+        GraphMetrics {
+            layer_histogram,
+            avg_out_degree: if count > 0 { total_degree as f64 / count as f64 } else { 0.0 },
+            max_out_degree,
+            isolated_nodes,
+            reachable_ratio: if count > 0 { reachable.len() as f64 / count as f64 } else { 1.0 },
+            graph_bytes,
+            vector_bytes,
+        }
+    }
+
     /// Get statistics about the index
     pub fn stats(&self) -> HnswStats {
         let nodes = self.nodes.read();
@@ -546,27 +1082,63 @@ impl HnswIndex {
 
     /// Serialize the index to bytes
     pub fn to_bytes(&self) -> Result<Vec<u8>> {
-        let data = SerializedHnsw {
-            config: self.config.clone(),
-            nodes: self.nodes.read().clone(),
-            entry_point: *self.entry_point.read(),
-            max_layer: *self.max_layer.read(),
+        let mut bytes = Vec::new();
+        self.write_json(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Serialize the index to `writer` as [`to_bytes`](Self::to_bytes) does,
+    /// without copying it first; inserts and removals wait until it is written
+    pub(crate) fn write_json<W: Write>(&self, writer: W) -> Result<()> {
+        // Taken before the nodes, as inserts do
+        let entry_point = *self.entry_point.read();
+        let max_layer = *self.max_layer.read();
+        let nodes = self.nodes.read();
+        let data = SerializedHnswRef {
+            config: &self.config,
+            nodes: &nodes,
+            entry_point,
+            max_layer,
             next_id: self.next_id.load(AtomicOrdering::SeqCst),
         };
-        
+
         // Use JSON for serialization to avoid bincode enum issues
-        serde_json::to_vec(&data).map_err(|e| {
+        serde_json::to_writer(writer, &data).map_err(|e| {
             KeraDBError::StorageError(format!("Failed to serialize HNSW: {}", e))
         })
     }
 
     /// Deserialize the index from bytes
     pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::read_json(&mut serde_json::Deserializer::from_slice(bytes))
+    }
+
+    /// Deserialize an index [`write_json`](Self::write_json) wrote, leaving
+    /// whatever follows it to the caller
+    pub(crate) fn read_json<'de, R: serde_json::de::Read<'de>>(de: &mut serde_json::Deserializer<R>) -> Result<Self> {
         // Use JSON for deserialization to avoid bincode enum issues
-        let data: SerializedHnsw = serde_json::from_slice(bytes).map_err(|e| {
+        let mut data = SerializedHnsw::deserialize(de).map_err(|e| {
             KeraDBError::StorageError(format!("Failed to deserialize HNSW: {}", e))
         })?;
         
+        // Indexes written before cosine normalization store raw vectors
+        if data.config.distance == Distance::Cosine {
+            for node in data.nodes.values_mut() {
+                if node.norm.is_some() {
+                    continue;
+                }
+                if let Some(v) = node.vector.as_mut() {
+                    let n = norm(v);
+                    if n > 0.0 {
+                        for x in v.iter_mut() {
+                            *x /= n;
+                        }
+                    }
+                    node.norm = Some(n);
+                }
+            }
+        }
+        
         // Handle edge case where m might be 0 or 1
         let level_mult = if data.config.m > 1 {
             1.0 / (data.config.m as f64).ln()
@@ -574,29 +1146,54 @@ impl HnswIndex {
             1.0
         };
         
+        // Nodes are keyed by slot, which for indexes never compacted is the ID
+        let slots = data.nodes.iter().map(|(slot, node)| (node.id, *slot)).collect();
+        let next_slot = data.nodes.keys().max().map_or(0, |slot| slot + 1);
+        let mut namespaces: HashMap<String, HashSet<VectorId>> = HashMap::new();
+        for node in data.nodes.values() {
+            if let Some(namespace) = &node.namespace {
+                namespaces.entry(namespace.clone()).or_default().insert(node.id);
+            }
+        }
+        
         Ok(Self {
             config: data.config,
             nodes: RwLock::new(data.nodes),
+            slots: RwLock::new(slots),
+            namespaces: RwLock::new(namespaces),
             entry_point: RwLock::new(data.entry_point),
             max_layer: RwLock::new(data.max_layer),
             next_id: AtomicU64::new(data.next_id),
+            next_slot: AtomicU64::new(next_slot),
+            structure: RwLock::new(()),
             level_mult,
+            rng: Mutex::new(StdRng::from_entropy()),
         })
     }
 }
 
 /// Serializable HNSW data
-#[derive(Serialize, Deserialize)]
+#[derive(Deserialize)]
 struct SerializedHnsw {
     config: VectorConfig,
-    nodes: HashMap<VectorId, HnswNode>,
-    entry_point: Option<VectorId>,
+    nodes: HashMap<Slot, HnswNode>,
+    entry_point: Option<Slot>,
+    max_layer: usize,
+    next_id: u64,
+}
+
+/// [`SerializedHnsw`] borrowed from a live index
+#[derive(Serialize)]
+struct SerializedHnswRef<'a> {
+    config: &'a VectorConfig,
+    nodes: &'a HashMap<Slot, HnswNode>,
+    entry_point: Option<Slot>,
     max_layer: usize,
     next_id: u64,
 }
 
 /// Statistics about an HNSW index
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HnswStats {
     pub node_count: usize,
     pub max_layer: usize,
@@ -606,6 +1203,55 @@ pub struct HnswStats {
     pub ef_construction: usize,
 }
 
+/// Connectivity and memory metrics for an HNSW graph
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphMetrics {
+    /// Number of nodes whose top layer is each index
+    pub layer_histogram: Vec<usize>,
+    
+    /// Average number of layer-0 neighbors
+    pub avg_out_degree: f64,
+    
+    /// Largest number of layer-0 neighbors
+    pub max_out_degree: usize,
+    
+    /// Nodes with no layer-0 neighbors
+    pub isolated_nodes: usize,
+    
+    /// Fraction of nodes reachable from the entry point at layer 0
+    pub reachable_ratio: f64,
+    
+    /// Approximate bytes used by nodes and neighbor lists
+    pub graph_bytes: usize,
+    
+    /// Approximate bytes used by vectors and stored text
+    pub vector_bytes: usize,
+}
+
+/// Result of an index optimization pass
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptimizeReport {
+    /// Index statistics before optimizing
+    pub before: HnswStats,
+    
+    /// Index statistics after optimizing
+    pub after: HnswStats,
+    
+    /// Number of nodes that received new connections
+    pub relinked_nodes: usize,
+    
+    /// Number of dangling, duplicate or self links removed
+    pub dangling_links_removed: usize,
+    
+    /// Number of nodes moved into slots freed by deletes
+    #[serde(default)]
+    pub compacted_ids: usize,
+    
+    /// Compression after re-selecting anchors, for compressed collections
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<CompressionStats>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -659,6 +1305,176 @@ mod tests {
         assert!(results[0].1 < 0.01); // Should be very close to 0
     }
 
+    #[test]
+    fn test_cosine_vectors_stored_normalized() {
+        let config = VectorConfig::new(3);
+        let index = HnswIndex::new(config);
+
+        let id = index.insert(vec![3.0, 4.0, 0.0]).unwrap();
+        index.insert(vec![0.0, 0.0, 2.0]).unwrap();
+
+        // Stored internally as a unit vector with the original norm kept
+        {
+            let nodes = index.nodes.read();
+            let node = nodes.get(&id).unwrap();
+            let stored = node.vector.as_ref().unwrap();
+            assert!((norm(stored) - 1.0).abs() < 1e-6);
+            assert!((node.norm.unwrap() - 5.0).abs() < 1e-6);
+        }
+
+        // The original vector is returned to callers
+        let doc = index.get(id).unwrap();
+        let embedding = doc.embedding.unwrap();
+        assert!((embedding[0] - 3.0).abs() < 1e-5);
+        assert!((embedding[1] - 4.0).abs() < 1e-5);
+
+        // Scaled queries find the same vector with ~0 distance
+        let results = index.search(&vec![30.0, 40.0, 0.0], 1).unwrap();
+        assert_eq!(results[0].0, id);
+        assert!(results[0].1 < 1e-5);
+    }
+
+    #[test]
+    fn test_graph_metrics() {
+        let index = HnswIndex::with_seed(VectorConfig::new(8).with_m(4), 7);
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..50 {
+            index.insert((0..8).map(|_| rng.gen::<f32>()).collect()).unwrap();
+        }
+
+        let metrics = index.graph_metrics();
+        assert_eq!(metrics.layer_histogram.iter().sum::<usize>(), 50);
+        assert_eq!(metrics.layer_histogram.len(), *index.max_layer.read() + 1);
+        assert!(metrics.avg_out_degree > 0.0);
+        assert_eq!(metrics.isolated_nodes, 0);
+        assert!((metrics.reachable_ratio - 1.0).abs() < f64::EPSILON);
+        assert!(metrics.vector_bytes >= 50 * 8 * 4);
+    }
+
+    #[test]
+    fn test_search_exact_matches_brute_force() {
+        let config = VectorConfig::new(8).with_distance(Distance::Euclidean);
+        let index = HnswIndex::new(config);
+
+        let vectors: Vec<Embedding> = (0..100).map(|_| random_vector(8)).collect();
+        for v in &vectors {
+            index.insert(v.clone()).unwrap();
+        }
+
+        let query = random_vector(8);
+        let results = index.search_exact(&query, 5).unwrap();
+        assert_eq!(results.len(), 5);
+
+        let mut expected: Vec<f32> = vectors.iter().map(|v| calculate_distance(&query, v, Distance::Euclidean)).collect();
+        expected.sort_by(|a, b| a.total_cmp(b));
+        for ((_, got), want) in results.iter().zip(&expected) {
+            assert!((got - want).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_optimize_relinks_after_deletes() {
+        let config = VectorConfig::new(16).with_distance(Distance::Euclidean).with_m(4);
+        let index = HnswIndex::with_seed(config, 11);
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut vector = move || -> Embedding { (0..16).map(|_| rng.gen::<f32>()).collect() };
+
+        let ids: Vec<VectorId> = (0..200).map(|_| index.insert(vector()).unwrap()).collect();
+        for id in ids.iter().take(150) {
+            index.delete(*id).unwrap();
+        }
+
+        let report = index.optimize().unwrap();
+        assert_eq!(report.after.node_count, 50);
+
+        // Entry point is the top node and every node is reachable at layer 0
+        let nodes = index.nodes.read();
+        let entry = index.entry_point.read().unwrap();
+        assert_eq!(nodes[&entry].layer, *index.max_layer.read());
+        for node in nodes.values() {
+            assert!(!node.get_neighbors(0).is_empty(), "node {} is isolated", node.id);
+            assert!(node.get_neighbors(0).iter().all(|n| nodes.contains_key(n)));
+        }
+        drop(nodes);
+
+        // Searching still returns full result sets
+        let results = index.search(&vector(), 10).unwrap();
+        assert_eq!(results.len(), 10);
+    }
+
+    #[test]
+    fn test_optimize_compacts_slots() {
+        let config = VectorConfig::new(8).with_distance(Distance::Euclidean);
+        let index = HnswIndex::new(config);
+
+        let vectors: Vec<Embedding> = (0..40).map(|_| random_vector(8)).collect();
+        let ids: Vec<VectorId> = vectors.iter().map(|v| index.insert(v.clone()).unwrap()).collect();
+        for id in ids.iter().step_by(2) {
+            index.delete(*id).unwrap();
+        }
+
+        let report = index.optimize().unwrap();
+        assert!(report.compacted_ids > 0);
+        assert_eq!(index.next_slot.load(AtomicOrdering::SeqCst), 20);
+        assert!(index.nodes.read().keys().all(|slot| *slot < 20));
+
+        // IDs survive compaction, and a saved index too
+        let restored = HnswIndex::from_bytes(&index.to_bytes().unwrap()).unwrap();
+        for index in [&index, &restored] {
+            assert_eq!(index.ids(), ids.iter().skip(1).step_by(2).copied().collect::<Vec<_>>());
+            for (id, vector) in ids.iter().zip(&vectors).skip(1).step_by(2) {
+                assert_eq!(index.search(vector, 1).unwrap()[0].0, *id);
+                assert_eq!(index.get(*id).unwrap().embedding.as_ref(), Some(vector));
+            }
+            assert!(index.get(ids[0]).is_none());
+        }
+
+        // Deleted IDs are not handed out again
+        let id = index.insert(random_vector(8)).unwrap();
+        assert_eq!(id, 40);
+        assert_eq!(index.slots.read()[&id], 20);
+        assert_eq!(index.optimize().unwrap().compacted_ids, 0);
+    }
+
+    #[test]
+    fn test_search_in_namespace() {
+        let config = VectorConfig::new(8).with_distance(Distance::Euclidean);
+        let index = HnswIndex::new(config);
+
+        let mut small = Vec::new();
+        for i in 0..230 {
+            let vector = random_vector(8);
+            let namespace = match i % 23 {
+                0 => Some("small".to_string()),
+                1 | 2 => None,
+                _ => Some("big".to_string()),
+            };
+            let id = index.insert_with_namespace(vector.clone(), None, None, namespace.clone()).unwrap();
+            if namespace.as_deref() == Some("small") {
+                small.push((id, vector));
+            }
+        }
+        assert_eq!(index.namespaces(), vec![("big".to_string(), 200), ("small".to_string(), 10)]);
+
+        // Small namespaces are scanned, so they come back exactly
+        let query = random_vector(8);
+        let mut expected: Vec<(VectorId, f32)> =
+            small.iter().map(|(id, v)| (*id, calculate_distance(&query, v, Distance::Euclidean))).collect();
+        expected.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let results = index.search_in_namespace(&query, 5, "small").unwrap();
+        assert_eq!(results.iter().map(|r| r.0).collect::<Vec<_>>(), expected.iter().take(5).map(|e| e.0).collect::<Vec<_>>());
+
+        let results = index.search_in_namespace(&query, 10, "big").unwrap();
+        assert_eq!(results.len(), 10);
+        assert!(results.iter().all(|(id, _)| index.get(*id).unwrap().namespace.as_deref() == Some("big")));
+        assert!(index.search_in_namespace(&query, 10, "missing").unwrap().is_empty());
+
+        index.delete(small[0].0).unwrap();
+        let restored = HnswIndex::from_bytes(&index.to_bytes().unwrap()).unwrap();
+        assert_eq!(restored.namespaces(), vec![("big".to_string(), 200), ("small".to_string(), 9)]);
+        assert_eq!(restored.namespace_ids("small"), small[1..].iter().map(|s| s.0).collect::<Vec<_>>());
+    }
+
     #[test]
     #[ignore = "Serialization test needs investigation with bincode config"]
     fn test_serialization() {
@@ -0,0 +1,245 @@
+//! Hugging Face Inference API embeddings (feature `huggingface`)
+//!
+//! Texts are sent to a model's feature-extraction pipeline in batches. Models
+//! that return one vector per token are mean-pooled to one vector per text.
+//! Requests that fail with a rate limit, a server error (including 503 while
+//! the model loads) or a network error are retried with exponential backoff,
+//! honouring `Retry-After`, and fail with
+//! [`KeraDBError::EmbeddingUnavailable`] if they persist; other errors fail
+//! at once.
+
+use super::embedding::EmbeddingProvider;
+use super::types::Embedding;
+use crate::error::{KeraDBError, Result};
+
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// Texts sent per request by default
+const BATCH_SIZE: usize = 32;
+/// Tries per request before giving up
+const ATTEMPTS: u32 = 5;
+/// Wait before the first retry; doubles with each one
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Embeddings from a model hosted on the Hugging Face Inference API, or on a
+/// dedicated Inference Endpoint
+pub struct HuggingFaceEmbeddingProvider {
+    agent: ureq::Agent,
+    api_token: String,
+    model: String,
+    endpoint: String,
+    batch_size: usize,
+    max_tokens: Option<usize>,
+    /// Learned from the model by [`connect`](Self::connect)
+    dimensions: usize,
+}
+
+impl HuggingFaceEmbeddingProvider {
+    /// A provider for `model` (e.g. `sentence-transformers/all-MiniLM-L6-v2`)
+    /// on the serverless Inference API; call [`connect`](Self::connect) before use
+    pub fn new(api_token: impl Into<String>, model: impl Into<String>) -> Self {
+        let model = model.into();
+        Self {
+            agent: ureq::AgentBuilder::new().timeout(Duration::from_secs(60)).build(),
+            api_token: api_token.into(),
+            endpoint: format!("https://router.huggingface.co/hf-inference/models/{}/pipeline/feature-extraction", model),
+            model,
+            batch_size: BATCH_SIZE,
+            max_tokens: None,
+            dimensions: 0,
+        }
+    }
+
+    /// Send requests to this URL instead, such as a dedicated Inference Endpoint
+    pub fn with_endpoint(mut self, url: impl Into<String>) -> Self {
+        self.endpoint = url.into();
+        self
+    }
+
+    /// Send at most this many texts per request
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Fit texts to the model's input limit, e.g. 256 tokens for
+    /// `all-MiniLM-L6-v2`, rather than have the API reject longer ones; token
+    /// counts are approximated
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Embed a probe text to learn the model's dimensions
+    pub fn connect(mut self) -> Result<Self> {
+        let probe = self.request(&["KeraDB"])?;
+        self.dimensions = probe.first().map_or(0, Vec::len);
+        if self.dimensions == 0 {
+            return Err(KeraDBError::EmbeddingError(format!("Model '{}' returned an empty embedding", self.model)));
+        }
+        Ok(self)
+    }
+
+    /// Embed one batch, retrying failures that may pass
+    fn request(&self, texts: &[&str]) -> Result<Vec<Embedding>> {
+        let body = json!({ "inputs": texts, "options": { "wait_for_model": true } }).to_string();
+        let failed = |detail: String| KeraDBError::EmbeddingError(format!("Hugging Face model '{}': {}", self.model, detail));
+
+        let mut delay = RETRY_DELAY;
+        let mut attempt = 1;
+        loop {
+            let mut request = self.agent.post(&self.endpoint).set("Content-Type", "application/json");
+            if !self.api_token.is_empty() {
+                request = request.set("Authorization", &format!("Bearer {}", self.api_token));
+            }
+            let (error, retry_after) = match request.send_string(&body) {
+                Ok(response) => {
+                    let value: Value = serde_json::from_str(&response.into_string()?)?;
+                    return parse_embeddings(&value, texts.len()).ok_or_else(|| failed("unexpected response shape".into()));
+                }
+                Err(ureq::Error::Status(status, response)) if status == 429 || status >= 500 => {
+                    let retry_after = response.header("Retry-After").and_then(|s| s.parse().ok()).map(Duration::from_secs);
+                    (format!("failed with {}: {}", status, response.into_string().unwrap_or_default()), retry_after)
+                }
+                Err(ureq::Error::Status(status, response)) => {
+                    return Err(failed(format!("failed with {}: {}", status, response.into_string().unwrap_or_default())));
+                }
+                Err(e) => (e.to_string(), None),
+            };
+            if attempt == ATTEMPTS {
+                return Err(KeraDBError::EmbeddingUnavailable(format!(
+                    "Hugging Face model '{}': {} (after {} attempts)",
+                    self.model, error, ATTEMPTS
+                )));
+            }
+            tracing::debug!("Hugging Face request for '{}' {}; retrying", self.model, error);
+            std::thread::sleep(retry_after.unwrap_or(delay));
+            delay *= 2;
+            attempt += 1;
+        }
+    }
+}
+
+impl EmbeddingProvider for HuggingFaceEmbeddingProvider {
+    fn embed(&self, text: &str) -> Result<Embedding> {
+        Ok(self.request(&[text])?.remove(0))
+    }
+
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Embedding>> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(self.batch_size) {
+            embeddings.extend(self.request(batch)?);
+        }
+        Ok(embeddings)
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+
+    fn max_tokens(&self) -> Option<usize> {
+        self.max_tokens
+    }
+}
+
+/// One embedding per text from a feature-extraction response, mean-pooling
+/// per-token vectors
+fn parse_embeddings(value: &Value, count: usize) -> Option<Vec<Embedding>> {
+    let embeddings: Vec<Embedding> = value.as_array()?.iter().map(pooled).collect::<Option<_>>()?;
+    (embeddings.len() == count).then_some(embeddings)
+}
+
+fn pooled(value: &Value) -> Option<Embedding> {
+    let items = value.as_array()?;
+    if items.iter().all(Value::is_number) {
+        return items.iter().map(|x| x.as_f64().map(|x| x as f32)).collect();
+    }
+    // One vector per token, possibly with a batch dimension of one around them
+    let vectors: Vec<Embedding> = items.iter().map(pooled).collect::<Option<_>>()?;
+    let dimensions = vectors.first()?.len();
+    let mut mean = vec![0.0f32; dimensions];
+    for vector in &vectors {
+        if vector.len() != dimensions {
+            return None;
+        }
+        for (m, x) in mean.iter_mut().zip(vector) {
+            *m += x / vectors.len() as f32;
+        }
+    }
+    Some(mean)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_batches_and_retries() {
+        // Each connection gets the next canned response
+        let responses = [
+            ("200 OK", r#"[[0.0, 1.0, 0.0]]"#),
+            ("503 Service Unavailable", r#"{"error": "Model is loading"}"#),
+            ("200 OK", r#"[[1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]"#),
+            ("200 OK", r#"[[[1.0, 0.0, 0.0], [0.0, 0.0, 1.0]]]"#),
+            ("400 Bad Request", r#"{"error": "bad input"}"#),
+        ];
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/embed", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for (status, body) in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let (mut length, mut authorized) = (0, false);
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let lower = line.to_lowercase();
+                    if let Some(value) = lower.strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    authorized |= lower.starts_with("authorization: bearer secret");
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut request = vec![0u8; length];
+                reader.read_exact(&mut request).unwrap();
+                let request: Value = serde_json::from_slice(&request).unwrap();
+                requests.push((authorized, request["inputs"].as_array().unwrap().len()));
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nRetry-After: 0\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+            requests
+        });
+
+        let provider = HuggingFaceEmbeddingProvider::new("secret", "test/model")
+            .with_endpoint(url)
+            .with_batch_size(2)
+            .connect()
+            .unwrap();
+        assert_eq!(provider.dimensions(), 3);
+        assert_eq!(provider.fingerprint().model, "test/model");
+
+        let embeddings = provider.embed_batch(&["a", "b", "c"]).unwrap();
+        assert_eq!(embeddings, vec![vec![1.0, 0.0, 0.0], vec![0.0, 1.0, 0.0], vec![0.5, 0.0, 0.5]]);
+        // A client error is not retried
+        assert!(matches!(provider.embed("d"), Err(KeraDBError::EmbeddingError(_))));
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests, vec![(true, 1), (true, 2), (true, 2), (true, 1), (true, 1)]);
+    }
+}
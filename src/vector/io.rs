@@ -0,0 +1,271 @@
+//! Import/export of vector collections in interchange formats
+//!
+//! Supported formats:
+//! - **JSONL / NDJSON**: one `{"id", "external_id", "namespace", "vector", "metadata",
+//!   "text"}` object per line
+//! - **Parquet** (feature `parquet`): columns `id` (u64), `external_id` (string),
+//!   `vector` (list<f32>), `metadata` (JSON string), `text` (string) and
+//!   `namespace` (string)
+//!
+//! These make it straightforward to move data between KeraDB and FAISS, Qdrant
+//! or numpy-based pipelines.
+
+use super::types::{Embedding, VectorId};
+use crate::error::{KeraDBError, Result};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// A single exported vector entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VectorRecord {
+    /// Source identifier (informational on import; new ids are assigned)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<VectorId>,
+
+    /// User-supplied identifier (preserved on import)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+
+    /// Namespace the vector belongs to (preserved on import)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+
+    /// The vector embedding
+    pub vector: Embedding,
+
+    /// Associated metadata
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub metadata: Value,
+
+    /// Original text, if the vector was created from text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+/// File format for vector import/export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VectorFileFormat {
+    /// Newline-delimited JSON
+    Jsonl,
+    /// Apache Parquet
+    Parquet,
+}
+
+impl VectorFileFormat {
+    /// Detect the format from a file extension
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .unwrap_or_default();
+
+        match ext.as_str() {
+            "jsonl" | "ndjson" | "json" => Ok(VectorFileFormat::Jsonl),
+            "parquet" | "pq" => Ok(VectorFileFormat::Parquet),
+            _ => Err(KeraDBError::InvalidFormat(format!(
+                "Cannot infer vector file format from '{}' (expected .jsonl, .ndjson or .parquet)",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Write records to a file in this format
+    pub fn write(&self, path: &Path, records: &[VectorRecord]) -> Result<()> {
+        match self {
+            VectorFileFormat::Jsonl => write_jsonl(path, records),
+            VectorFileFormat::Parquet => write_parquet(path, records),
+        }
+    }
+
+    /// Read records from a file in this format
+    pub fn read(&self, path: &Path) -> Result<Vec<VectorRecord>> {
+        match self {
+            VectorFileFormat::Jsonl => read_jsonl(path),
+            VectorFileFormat::Parquet => read_parquet(path),
+        }
+    }
+}
+
+/// Write records as newline-delimited JSON
+pub fn write_jsonl(path: &Path, records: &[VectorRecord]) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for record in records {
+        serde_json::to_writer(&mut writer, record)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read records from newline-delimited JSON, skipping blank lines
+pub fn read_jsonl(path: &Path) -> Result<Vec<VectorRecord>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut records = Vec::new();
+
+    for (line_no, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: VectorRecord = serde_json::from_str(&line).map_err(|e| {
+            KeraDBError::ParseError(format!("Line {}: {}", line_no + 1, e))
+        })?;
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+#[cfg(feature = "parquet")]
+fn parquet_schema() -> std::sync::Arc<arrow_schema::Schema> {
+    use arrow_schema::{DataType, Field, Schema};
+
+    std::sync::Arc::new(Schema::new(vec![
+        Field::new("id", DataType::UInt64, true),
+        Field::new("external_id", DataType::Utf8, true),
+        Field::new(
+            "vector",
+            DataType::List(std::sync::Arc::new(Field::new("item", DataType::Float32, false))),
+            false,
+        ),
+        Field::new("metadata", DataType::Utf8, true),
+        Field::new("text", DataType::Utf8, true),
+        Field::new("namespace", DataType::Utf8, true),
+    ]))
+}
+
+/// Write records as a Parquet file
+#[cfg(feature = "parquet")]
+pub fn write_parquet(path: &Path, records: &[VectorRecord]) -> Result<()> {
+    use arrow_array::builder::{Float32Builder, ListBuilder};
+    use arrow_array::{ArrayRef, RecordBatch, StringArray, UInt64Array};
+    use parquet::arrow::ArrowWriter;
+    use std::sync::Arc;
+
+    let schema = parquet_schema();
+
+    let ids = UInt64Array::from(records.iter().map(|r| r.id).collect::<Vec<_>>());
+    let external_ids = StringArray::from(records.iter().map(|r| r.external_id.clone()).collect::<Vec<_>>());
+
+    let mut vectors = ListBuilder::new(Float32Builder::new())
+        .with_field(Arc::new(arrow_schema::Field::new("item", arrow_schema::DataType::Float32, false)));
+    for record in records {
+        vectors.values().append_slice(&record.vector);
+        vectors.append(true);
+    }
+
+    let metadata = StringArray::from(
+        records
+            .iter()
+            .map(|r| (!r.metadata.is_null()).then(|| r.metadata.to_string()))
+            .collect::<Vec<_>>(),
+    );
+    let text = StringArray::from(records.iter().map(|r| r.text.clone()).collect::<Vec<_>>());
+    let namespaces = StringArray::from(records.iter().map(|r| r.namespace.clone()).collect::<Vec<_>>());
+
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(ids) as ArrayRef,
+            Arc::new(external_ids) as ArrayRef,
+            Arc::new(vectors.finish()) as ArrayRef,
+            Arc::new(metadata) as ArrayRef,
+            Arc::new(text) as ArrayRef,
+            Arc::new(namespaces) as ArrayRef,
+        ],
+    )
+    .map_err(|e| KeraDBError::Serialization(format!("Failed to build Parquet batch: {}", e)))?;
+
+    let file = File::create(path)?;
+    let mut writer = ArrowWriter::try_new(file, schema, None)
+        .map_err(|e| KeraDBError::StorageError(format!("Failed to create Parquet writer: {}", e)))?;
+    writer
+        .write(&batch)
+        .map_err(|e| KeraDBError::StorageError(format!("Failed to write Parquet data: {}", e)))?;
+    writer
+        .close()
+        .map_err(|e| KeraDBError::StorageError(format!("Failed to finish Parquet file: {}", e)))?;
+
+    Ok(())
+}
+
+/// Read records from a Parquet file
+#[cfg(feature = "parquet")]
+pub fn read_parquet(path: &Path) -> Result<Vec<VectorRecord>> {
+    use arrow_array::{Array, Float32Array, ListArray, StringArray, UInt64Array};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    let file = File::open(path)?;
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+        .and_then(|b| b.build())
+        .map_err(|e| KeraDBError::InvalidFormat(format!("Failed to open Parquet file: {}", e)))?;
+
+    let mut records = Vec::new();
+    for batch in reader {
+        let batch = batch
+            .map_err(|e| KeraDBError::InvalidFormat(format!("Failed to read Parquet batch: {}", e)))?;
+
+        let vectors = batch
+            .column_by_name("vector")
+            .and_then(|c| c.as_any().downcast_ref::<ListArray>())
+            .ok_or_else(|| KeraDBError::InvalidFormat("Parquet file has no list<f32> 'vector' column".into()))?;
+        let ids = batch
+            .column_by_name("id")
+            .and_then(|c| c.as_any().downcast_ref::<UInt64Array>());
+        let external_ids = batch
+            .column_by_name("external_id")
+            .and_then(|c| c.as_any().downcast_ref::<StringArray>());
+        let metadata = batch
+            .column_by_name("metadata")
+            .and_then(|c| c.as_any().downcast_ref::<StringArray>());
+        let text = batch
+            .column_by_name("text")
+            .and_then(|c| c.as_any().downcast_ref::<StringArray>());
+        let namespaces = batch
+            .column_by_name("namespace")
+            .and_then(|c| c.as_any().downcast_ref::<StringArray>());
+
+        for row in 0..batch.num_rows() {
+            let values = vectors.value(row);
+            let values = values
+                .as_any()
+                .downcast_ref::<Float32Array>()
+                .ok_or_else(|| KeraDBError::InvalidFormat("'vector' column must contain f32 values".into()))?;
+
+            let metadata = match metadata.filter(|m| m.is_valid(row)) {
+                Some(m) => serde_json::from_str(m.value(row))?,
+                None => Value::Null,
+            };
+
+            records.push(VectorRecord {
+                id: ids.filter(|a| a.is_valid(row)).map(|a| a.value(row)),
+                external_id: external_ids.filter(|a| a.is_valid(row)).map(|a| a.value(row).to_string()),
+                namespace: namespaces.filter(|a| a.is_valid(row)).map(|a| a.value(row).to_string()),
+                vector: values.values().to_vec(),
+                metadata,
+                text: text.filter(|t| t.is_valid(row)).map(|t| t.value(row).to_string()),
+            });
+        }
+    }
+
+    Ok(records)
+}
+
+#[cfg(not(feature = "parquet"))]
+pub fn write_parquet(_path: &Path, _records: &[VectorRecord]) -> Result<()> {
+    Err(KeraDBError::NotImplemented(
+        "Parquet support requires the 'parquet' feature".into(),
+    ))
+}
+
+#[cfg(not(feature = "parquet"))]
+pub fn read_parquet(_path: &Path) -> Result<Vec<VectorRecord>> {
+    Err(KeraDBError::NotImplemented(
+        "Parquet support requires the 'parquet' feature".into(),
+    ))
+}
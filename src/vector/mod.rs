@@ -38,12 +38,31 @@ pub mod types;
 pub mod distance;
 pub mod hnsw;
 pub mod embedding;
+#[cfg(feature = "async")]
+pub mod async_embedding;
+#[cfg(feature = "huggingface")]
+pub mod huggingface;
 pub mod search;
 pub mod compression;
+pub mod io;
+pub mod gpu;
+pub mod cluster;
+pub mod projection;
+pub mod policy;
+pub mod tokens;
+pub(crate) mod format;
+pub(crate) mod store;
 
 pub use types::*;
 pub use distance::*;
-pub use hnsw::HnswIndex;
+pub use hnsw::{GraphMetrics, HnswIndex, HnswStats, OptimizeReport};
 pub use embedding::EmbeddingProvider;
+#[cfg(feature = "async")]
+pub use async_embedding::AsyncEmbeddingProvider;
 pub use search::VectorSearcher;
 pub use compression::{CompressionConfig, CompressionMode, CompressedVector, CompressionStats};
+pub use io::{VectorFileFormat, VectorRecord};
+pub use cluster::{Clustering, CLUSTER_KEY};
+pub use projection::{ProjectedPoint, Projection, ProjectionMethod};
+pub use policy::ProviderPolicy;
+pub use tokens::TextOverflow;
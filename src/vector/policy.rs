@@ -0,0 +1,293 @@
+//! Concurrency limits, retries, circuit breaking and input limits for
+//! embedding providers
+//!
+//! Every provider a [`Database`](crate::Database) embeds text with is wrapped
+//! in a [`GuardedProvider`] following the [`ProviderPolicy`] in its
+//! [`Config`](crate::types::Config). Only
+//! [`KeraDBError::EmbeddingUnavailable`] failures (rate limits, server and
+//! network errors) are retried and count toward the circuit breaker; any
+//! other error is returned at once, as it would fail again. Texts longer
+//! than the model accepts are truncated or split first, as
+//! [`ProviderPolicy::overflow`] says.
+
+use super::embedding::EmbeddingProvider;
+use super::tokens::{self, TextOverflow};
+use super::types::Embedding;
+use crate::error::{KeraDBError, Result};
+use crate::metrics::EmbeddingMetrics;
+
+use parking_lot::{Condvar, Mutex};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How calls to an embedding provider are limited and retried, and how
+/// texts over the model's input limit are handled
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProviderPolicy {
+    /// Calls in flight at once; 0 for no limit
+    pub max_concurrency: usize,
+    /// Retries of a call that failed with a transient error
+    pub max_retries: u32,
+    /// Wait before the first retry; doubles with each one
+    pub initial_backoff: Duration,
+    /// Longest wait between retries
+    pub max_backoff: Duration,
+    /// Calls failing in a row, after their retries, that open the circuit;
+    /// 0 never opens it
+    pub failure_threshold: u32,
+    /// How long an open circuit refuses calls before letting one through
+    pub cooldown: Duration,
+    /// What to do with texts longer than the model's
+    /// [`max_tokens`](EmbeddingProvider::max_tokens)
+    pub overflow: TextOverflow,
+}
+
+impl Default for ProviderPolicy {
+    fn default() -> Self {
+        Self {
+            max_concurrency: 8,
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(10),
+            failure_threshold: 5,
+            cooldown: Duration::from_secs(30),
+            overflow: TextOverflow::default(),
+        }
+    }
+}
+
+impl ProviderPolicy {
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency;
+        self
+    }
+
+    pub fn with_retries(mut self, max_retries: u32, initial_backoff: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    pub fn with_circuit_breaker(mut self, failure_threshold: u32, cooldown: Duration) -> Self {
+        self.failure_threshold = failure_threshold;
+        self.cooldown = cooldown;
+        self
+    }
+
+    pub fn with_overflow(mut self, overflow: TextOverflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Wait before retry number `retry`, counting from 0
+    fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff.saturating_mul(2u32.saturating_pow(retry)).min(self.max_backoff)
+    }
+}
+
+/// State of the circuit breaker
+#[derive(Default)]
+struct Breaker {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+/// A provider whose calls follow a [`ProviderPolicy`]
+pub struct GuardedProvider {
+    provider: Arc<dyn EmbeddingProvider>,
+    policy: ProviderPolicy,
+    in_flight: Mutex<usize>,
+    slot_freed: Condvar,
+    breaker: Mutex<Breaker>,
+    metrics: Arc<EmbeddingMetrics>,
+}
+
+impl GuardedProvider {
+    pub(crate) fn new(provider: Arc<dyn EmbeddingProvider>, policy: ProviderPolicy, metrics: Arc<EmbeddingMetrics>) -> Self {
+        Self {
+            provider,
+            policy,
+            in_flight: Mutex::new(0),
+            slot_freed: Condvar::new(),
+            breaker: Mutex::new(Breaker::default()),
+            metrics,
+        }
+    }
+
+    /// Call the provider, retrying transient failures and tripping the
+    /// breaker when they persist
+    fn call<T>(&self, f: impl Fn(&dyn EmbeddingProvider) -> Result<T>) -> Result<T> {
+        if let Some(open_until) = self.breaker.lock().open_until {
+            let now = Instant::now();
+            if now < open_until {
+                self.metrics.record_rejection();
+                return Err(KeraDBError::EmbeddingUnavailable(format!(
+                    "Circuit open for model '{}' after repeated failures; retrying in {:.1}s",
+                    self.provider.model_name(),
+                    (open_until - now).as_secs_f64()
+                )));
+            }
+        }
+
+        let mut retry = 0;
+        let result = loop {
+            let result = self.metrics.calls.time(|| self.limited(|| f(self.provider.as_ref())));
+            match result {
+                Err(KeraDBError::EmbeddingUnavailable(e)) if retry < self.policy.max_retries => {
+                    let delay = self.policy.backoff(retry);
+                    tracing::debug!("Embedding with '{}' failed: {}; retrying in {:?}", self.provider.model_name(), e, delay);
+                    self.metrics.record_retry();
+                    std::thread::sleep(delay);
+                    retry += 1;
+                }
+                result => break result,
+            }
+        };
+
+        let mut breaker = self.breaker.lock();
+        match &result {
+            Err(KeraDBError::EmbeddingUnavailable(_)) => {
+                breaker.consecutive_failures += 1;
+                if self.policy.failure_threshold > 0 && breaker.consecutive_failures >= self.policy.failure_threshold {
+                    tracing::warn!(
+                        "Embedding with '{}' failed {} times in a row; pausing calls for {:?}",
+                        self.provider.model_name(),
+                        breaker.consecutive_failures,
+                        self.policy.cooldown
+                    );
+                    breaker.open_until = Some(Instant::now() + self.policy.cooldown);
+                }
+            }
+            _ => *breaker = Breaker::default(),
+        }
+        result
+    }
+
+    /// Run `f` once a concurrency slot is free
+    fn limited<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let limit = self.policy.max_concurrency;
+        if limit > 0 {
+            let mut in_flight = self.in_flight.lock();
+            while *in_flight >= limit {
+                self.slot_freed.wait(&mut in_flight);
+            }
+            *in_flight += 1;
+        }
+        let result = f();
+        if limit > 0 {
+            *self.in_flight.lock() -= 1;
+            self.slot_freed.notify_one();
+        }
+        result
+    }
+}
+
+impl EmbeddingProvider for GuardedProvider {
+    fn embed(&self, text: &str) -> Result<Embedding> {
+        match tokens::fit(self.provider.as_ref(), text, self.policy.overflow)?.as_slice() {
+            [(part, _)] => self.call(|provider| provider.embed(part)),
+            _ => Ok(self.embed_batch(&[text])?.remove(0)),
+        }
+    }
+
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Embedding>> {
+        let fitted = texts
+            .iter()
+            .map(|text| tokens::fit(self.provider.as_ref(), text, self.policy.overflow))
+            .collect::<Result<Vec<_>>>()?;
+        let parts: Vec<&str> = fitted.iter().flatten().map(|(part, _)| *part).collect();
+        let embeddings = self.call(|provider| provider.embed_batch(&parts))?;
+        if parts.len() == texts.len() {
+            return Ok(embeddings);
+        }
+
+        // Pool the embeddings of texts that were split
+        let mut embeddings = embeddings.into_iter();
+        Ok(fitted
+            .iter()
+            .map(|parts| {
+                let weights: Vec<usize> = parts.iter().map(|(_, tokens)| *tokens).collect();
+                tokens::pool(&embeddings.by_ref().take(parts.len()).collect::<Vec<_>>(), &weights)
+            })
+            .collect())
+    }
+
+    fn dimensions(&self) -> usize {
+        self.provider.dimensions()
+    }
+
+    fn model_name(&self) -> &str {
+        self.provider.model_name()
+    }
+
+    fn model_version(&self) -> Option<&str> {
+        self.provider.model_version()
+    }
+
+    fn max_tokens(&self) -> Option<usize> {
+        self.provider.max_tokens()
+    }
+
+    fn tokenize(&self, text: &str) -> Vec<std::ops::Range<usize>> {
+        self.provider.tokenize(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Fails with a rate limit until `failures` calls have been made
+    struct Flaky {
+        calls: AtomicU32,
+        failures: u32,
+    }
+
+    impl EmbeddingProvider for Flaky {
+        fn embed(&self, _text: &str) -> Result<Embedding> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err(KeraDBError::EmbeddingUnavailable("429 Too Many Requests".into()));
+            }
+            Ok(vec![1.0, 0.0])
+        }
+
+        fn dimensions(&self) -> usize {
+            2
+        }
+
+        fn model_name(&self) -> &str {
+            "flaky"
+        }
+    }
+
+    #[test]
+    fn test_retries_and_circuit_breaker() {
+        let policy = ProviderPolicy::default()
+            .with_retries(2, Duration::from_millis(1))
+            .with_circuit_breaker(2, Duration::from_secs(60));
+        let metrics = Arc::new(EmbeddingMetrics::new());
+        let flaky = Arc::new(Flaky { calls: AtomicU32::new(0), failures: 8 });
+        let provider = GuardedProvider::new(flaky.clone(), policy, metrics.clone());
+
+        // Three attempts per call: two calls fail and open the circuit
+        assert!(matches!(provider.embed("a"), Err(KeraDBError::EmbeddingUnavailable(_))));
+        assert!(matches!(provider.embed("a"), Err(KeraDBError::EmbeddingUnavailable(_))));
+        assert_eq!(flaky.calls.load(Ordering::SeqCst), 6);
+        // While it is open the provider is not called
+        let err = provider.embed("a").unwrap_err();
+        assert!(err.to_string().contains("Circuit open"));
+        assert_eq!(flaky.calls.load(Ordering::SeqCst), 6);
+        assert_eq!((metrics.retries(), metrics.rejections()), (4, 1));
+
+        // A call that succeeds within its retries closes it again
+        let flaky = Arc::new(Flaky { calls: AtomicU32::new(0), failures: 2 });
+        let provider = GuardedProvider::new(flaky, ProviderPolicy::default().with_retries(2, Duration::from_millis(1)), metrics);
+        assert_eq!(provider.embed("a").unwrap(), vec![1.0, 0.0]);
+    }
+}
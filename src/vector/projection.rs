@@ -0,0 +1,246 @@
+//! 2D projections of vector collections for plotting
+//!
+//! PCA finds the two directions the collection varies most along, by power
+//! iteration on the covariance: each step costs one pass over the vectors, so
+//! no `dimensions²` covariance matrix is ever built. Projections are written
+//! as JSON or CSV for plotting elsewhere, and the TUI's vector explorer draws
+//! them as a scatter plot.
+
+use super::types::{Embedding, VectorId};
+use crate::error::{KeraDBError, Result};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Upper bound on power iterations per component
+const MAX_ITERATIONS: usize = 200;
+
+/// A component has converged once a step moves it less than this
+const TOLERANCE: f64 = 1e-9;
+
+/// How vectors are projected to two dimensions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectionMethod {
+    /// Principal component analysis: the two directions of greatest variance
+    Pca,
+}
+
+impl ProjectionMethod {
+    /// Returns the name of the method
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProjectionMethod::Pca => "pca",
+        }
+    }
+
+    /// Parse a method name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "pca" => Some(ProjectionMethod::Pca),
+            _ => None,
+        }
+    }
+}
+
+/// A vector's position in a projection
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProjectedPoint {
+    pub id: VectorId,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+
+    pub x: f32,
+    pub y: f32,
+}
+
+/// A collection projected to two dimensions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Projection {
+    pub method: ProjectionMethod,
+
+    /// Share of the collection's variance along x and along y
+    pub explained_variance: [f32; 2],
+
+    /// One point per vector, in ascending ID order
+    pub points: Vec<ProjectedPoint>,
+}
+
+impl Projection {
+    /// Write the projection to `path`: `.json` as one object, `.csv` as
+    /// `id,external_id,namespace,x,y` rows
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .unwrap_or_default();
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        match ext.as_str() {
+            "json" => {
+                serde_json::to_writer_pretty(&mut writer, self)?;
+                writer.write_all(b"\n")?;
+            }
+            "csv" => {
+                let csv_error = |e: csv::Error| KeraDBError::Serialization(e.to_string());
+                let mut csv = csv::Writer::from_writer(&mut writer);
+                csv.write_record(["id", "external_id", "namespace", "x", "y"]).map_err(csv_error)?;
+                for point in &self.points {
+                    csv.write_record([
+                        point.id.to_string(),
+                        point.external_id.clone().unwrap_or_default(),
+                        point.namespace.clone().unwrap_or_default(),
+                        point.x.to_string(),
+                        point.y.to_string(),
+                    ])
+                    .map_err(csv_error)?;
+                }
+                csv.flush()?;
+            }
+            _ => {
+                return Err(KeraDBError::InvalidFormat(format!(
+                    "Cannot infer projection file format from '{}' (expected .json or .csv)",
+                    path.display()
+                )))
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Each vector's position, and the share of variance along each axis
+type Projected = (Vec<(f32, f32)>, [f32; 2]);
+
+/// Project `vectors` onto their first two principal components
+pub fn pca_2d(vectors: &[Embedding]) -> Result<Projected> {
+    let Some(dimensions) = vectors.first().map(Vec::len) else {
+        return Ok((Vec::new(), [0.0, 0.0]));
+    };
+    if vectors.iter().any(|v| v.len() != dimensions) {
+        return Err(KeraDBError::VectorError("Cannot project vectors of different dimensions".into()));
+    }
+
+    // Centered, in f64 so long sums keep their precision
+    let n = vectors.len() as f64;
+    let mut mean = vec![0.0f64; dimensions];
+    for v in vectors {
+        for (m, x) in mean.iter_mut().zip(v) {
+            *m += f64::from(*x) / n;
+        }
+    }
+    let centered: Vec<Vec<f64>> =
+        vectors.iter().map(|v| v.iter().zip(&mean).map(|(x, m)| f64::from(*x) - m).collect()).collect();
+    let total_variance: f64 = centered.iter().map(|c| dot(c, c)).sum::<f64>() / n;
+
+    let mut rng = StdRng::seed_from_u64(0x5043_4132);
+    let mut components: Vec<Vec<f64>> = Vec::with_capacity(2);
+    let mut variances = [0.0f64; 2];
+    for variance in variances.iter_mut() {
+        let mut v: Vec<f64> = (0..dimensions).map(|_| rng.gen_range(-1.0..1.0)).collect();
+        orthogonalize(&mut v, &components);
+        if !normalize(&mut v) {
+            break;
+        }
+        for _ in 0..MAX_ITERATIONS {
+            // Covariance times v, as the sum of c (c · v) over the vectors
+            let mut next = vec![0.0f64; dimensions];
+            for c in &centered {
+                let scale = dot(c, &v) / n;
+                for (x, y) in next.iter_mut().zip(c) {
+                    *x += scale * y;
+                }
+            }
+            orthogonalize(&mut next, &components);
+            *variance = dot(&next, &next).sqrt();
+            if !normalize(&mut next) {
+                break;
+            }
+            let moved: f64 = next.iter().zip(&v).map(|(a, b)| (a - b).powi(2)).sum();
+            v = next;
+            if moved < TOLERANCE {
+                break;
+            }
+        }
+        // The sign of a component is arbitrary; make its largest entry positive
+        if v.iter().copied().max_by(|a, b| a.abs().total_cmp(&b.abs())).is_some_and(|x| x < 0.0) {
+            v.iter_mut().for_each(|x| *x = -*x);
+        }
+        components.push(v);
+    }
+
+    let coordinate = |c: &[f64], k: usize| components.get(k).map_or(0.0, |component| dot(c, component)) as f32;
+    let points = centered.iter().map(|c| (coordinate(c, 0), coordinate(c, 1))).collect();
+    let explained = variances.map(|variance| if total_variance > 0.0 { (variance / total_variance) as f32 } else { 0.0 });
+    Ok((points, explained))
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Remove from `v` its part along each of `basis`, which are unit length
+fn orthogonalize(v: &mut [f64], basis: &[Vec<f64>]) {
+    for b in basis {
+        let along = dot(v, b);
+        for (x, y) in v.iter_mut().zip(b) {
+            *x -= along * y;
+        }
+    }
+}
+
+/// Scale `v` to unit length; `false` if it is (nearly) zero
+fn normalize(v: &mut [f64]) -> bool {
+    let norm = dot(v, v).sqrt();
+    if norm < 1e-12 {
+        return false;
+    }
+    v.iter_mut().for_each(|x| *x /= norm);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pca_finds_main_axes() {
+        // Spread widely along (1, 1, 0), a little along (1, -1, 0), not at all along z
+        let vectors: Vec<Embedding> = (0..50)
+            .map(|i| {
+                let (a, b) = ((i / 2) as f32 - 12.0, if i % 2 == 0 { 1.0 } else { -1.0 });
+                vec![a + b, a - b, 3.0]
+            })
+            .collect();
+
+        let (points, explained) = pca_2d(&vectors).unwrap();
+        assert_eq!(points.len(), 50);
+        assert!((explained[0] - 104.0 / 106.0).abs() < 1e-4 && (explained[1] - 2.0 / 106.0).abs() < 1e-4);
+        // x follows the wide axis, y the narrow one
+        let scale = 2.0f32.sqrt();
+        for (i, (x, y)) in points.iter().enumerate() {
+            assert!((x - ((i / 2) as f32 - 12.0) * scale).abs() < 1e-3, "{} {}", i, x);
+            assert!((y.abs() - scale).abs() < 1e-3);
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let projection = Projection {
+            method: ProjectionMethod::Pca,
+            explained_variance: explained,
+            points: vec![ProjectedPoint { id: 7, external_id: Some("a,b".into()), namespace: None, x: 1.5, y: -2.0 }],
+        };
+        let path = dir.path().join("points.csv");
+        projection.write(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "id,external_id,namespace,x,y\n7,\"a,b\",,1.5,-2\n");
+        assert!(projection.write(&dir.path().join("points.txt")).is_err());
+    }
+}
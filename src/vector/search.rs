@@ -2,17 +2,27 @@
 //! 
 //! Provides high-level search API with filtering, pagination, and result formatting.
 
-use super::hnsw::HnswIndex;
+use super::cluster::{kmeans, Clustering, CLUSTER_KEY};
+use super::hnsw::{HnswIndex, OptimizeReport};
+use super::projection::{pca_2d, ProjectedPoint, Projection, ProjectionMethod};
 use super::types::{
-    Distance, Embedding, MetadataFilter, VectorConfig, VectorDocument, 
+    Distance, Embedding, MetadataFilter, VectorCollectionStats, VectorConfig, VectorDocument, 
     VectorId, VectorSearchResult,
 };
+use super::compression::{CompressedVectorStore, CompressionConfig, CompressionMode, CompressionStats};
 use super::embedding::EmbeddingProvider;
+use super::distance::cosine_similarity;
+use super::io::{self, VectorRecord};
 use crate::error::{KeraDBError, Result};
 
 use parking_lot::RwLock;
+use serde::Deserialize;
 use serde_json::Value;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 /// A vector collection with search capabilities
@@ -29,82 +39,389 @@ pub struct VectorCollection {
     /// Document metadata storage (id -> metadata)
     metadata: RwLock<HashMap<VectorId, Value>>,
     
+    /// User-supplied IDs (rebuilt from the index on load)
+    external_ids: RwLock<ExternalIdMap>,
+    
     /// Optional embedding provider for text-to-vector conversion
-    embedding_provider: Option<Arc<dyn EmbeddingProvider>>,
+    embedding_provider: RwLock<Option<Arc<dyn EmbeddingProvider>>>,
+    
+    /// Mutations since the last optimize pass
+    mutations: AtomicUsize,
+    
+    /// Run `optimize` automatically after this many mutations (0 = never)
+    auto_optimize_after: AtomicUsize,
+    
+    /// Compressed form of the vectors, as of the last optimize (`None`
+    /// without compression)
+    compressed: Option<RwLock<CompressedVectorStore>>,
 }
 
 impl VectorCollection {
     /// Create a new vector collection
     pub fn new(name: String, config: VectorConfig) -> Self {
+        let compressed = compressed_store(&config, None);
         Self {
             name,
             config: config.clone(),
             index: HnswIndex::new(config),
             metadata: RwLock::new(HashMap::new()),
-            embedding_provider: None,
+            external_ids: RwLock::new(ExternalIdMap::default()),
+            embedding_provider: RwLock::new(None),
+            mutations: AtomicUsize::new(0),
+            auto_optimize_after: AtomicUsize::new(0),
+            compressed,
         }
     }
 
     /// Create a collection with an embedding provider
+    /// 
+    /// The provider's model is recorded in the config, unless it already names one.
     pub fn with_embedding_provider(
         name: String,
-        config: VectorConfig,
+        mut config: VectorConfig,
         provider: Arc<dyn EmbeddingProvider>,
     ) -> Self {
+        if config.embedding_fingerprint.is_none() {
+            config.embedding_fingerprint = Some(provider.fingerprint());
+        }
+        let compressed = compressed_store(&config, None);
         Self {
             name,
             config: config.clone(),
             index: HnswIndex::new(config),
             metadata: RwLock::new(HashMap::new()),
-            embedding_provider: Some(provider),
+            external_ids: RwLock::new(ExternalIdMap::default()),
+            embedding_provider: RwLock::new(Some(provider)),
+            mutations: AtomicUsize::new(0),
+            auto_optimize_after: AtomicUsize::new(0),
+            compressed,
         }
     }
 
     /// Insert a vector with optional metadata
+    #[tracing::instrument(name = "vector_insert", level = "debug", skip_all, fields(collection = %self.name, id = tracing::field::Empty), err(level = "debug"))]
     pub fn insert(&self, vector: Embedding, metadata: Option<Value>) -> Result<VectorId> {
         let id = self.index.insert(vector)?;
+        tracing::Span::current().record("id", id);
+        
+        if let Some(meta) = metadata {
+            self.metadata.write().insert(id, meta);
+        }
+        self.record_mutation()?;
+        
+        Ok(id)
+    }
+
+    /// Insert a vector with optional metadata into a namespace
+    #[tracing::instrument(name = "vector_insert", level = "debug", skip_all, fields(collection = %self.name, namespace = namespace, id = tracing::field::Empty), err(level = "debug"))]
+    pub fn insert_in_namespace(&self, namespace: &str, vector: Embedding, metadata: Option<Value>) -> Result<VectorId> {
+        let id = self.index.insert_with_namespace(vector, None, None, Some(namespace.to_string()))?;
+        tracing::Span::current().record("id", id);
+        
+        if let Some(meta) = metadata {
+            self.metadata.write().insert(id, meta);
+        }
+        self.record_mutation()?;
         
+        Ok(id)
+    }
+
+    /// Insert a vector under a user-supplied ID
+    /// 
+    /// If the ID is already in use, the existing vector is replaced. Returns the
+    /// internal ID assigned to the new vector.
+    pub fn insert_with_id(
+        &self,
+        external_id: &str,
+        vector: Embedding,
+        metadata: Option<Value>,
+    ) -> Result<VectorId> {
+        self.upsert_external(external_id, vector, None, metadata, None)
+    }
+
+    #[tracing::instrument(name = "vector_upsert", level = "debug", skip_all, fields(collection = %self.name, external_id = external_id, id = tracing::field::Empty), err(level = "debug"))]
+    fn upsert_external(
+        &self,
+        external_id: &str,
+        vector: Embedding,
+        text: Option<String>,
+        metadata: Option<Value>,
+        namespace: Option<String>,
+    ) -> Result<VectorId> {
+        let mut external_ids = self.external_ids.write();
+        
+        let id = self.index.insert_with_namespace(vector, text, Some(external_id.to_string()), namespace)?;
+        tracing::Span::current().record("id", id);
         if let Some(meta) = metadata {
             self.metadata.write().insert(id, meta);
         }
         
+        if let Some(previous) = external_ids.insert(external_id.to_string(), id) {
+            self.metadata.write().remove(&previous);
+            self.index.delete(previous)?;
+        }
+        drop(external_ids);
+        self.record_mutation()?;
+        
         Ok(id)
     }
 
+    /// Look up the internal ID for a user-supplied ID
+    pub fn resolve_id(&self, external_id: &str) -> Option<VectorId> {
+        self.external_ids.read().to_internal.get(external_id).copied()
+    }
+
     /// Insert text (requires embedding provider)
     pub fn insert_text(&self, text: &str, metadata: Option<Value>) -> Result<VectorId> {
-        let provider = self.embedding_provider.as_ref().ok_or_else(|| {
-            KeraDBError::InvalidFormat("No embedding provider configured".into())
-        })?;
+        self.insert_text_into(text, metadata, None)
+    }
+
+    /// Insert text into a namespace (requires embedding provider)
+    pub fn insert_text_in_namespace(&self, namespace: &str, text: &str, metadata: Option<Value>) -> Result<VectorId> {
+        self.insert_text_into(text, metadata, Some(namespace.to_string()))
+    }
+
+    #[tracing::instrument(name = "vector_insert_text", level = "debug", skip_all, fields(collection = %self.name, namespace = namespace.as_deref(), id = tracing::field::Empty), err(level = "debug"))]
+    fn insert_text_into(&self, text: &str, metadata: Option<Value>, namespace: Option<String>) -> Result<VectorId> {
+        let provider = self.embedding_provider()?;
         
         let vector = provider.embed(text)?;
-        let id = self.index.insert_with_metadata(vector, Some(text.to_string()), None)?;
+        let id = self.index.insert_with_namespace(vector, Some(text.to_string()), None, namespace)?;
+        tracing::Span::current().record("id", id);
         
         if let Some(meta) = metadata {
             self.metadata.write().insert(id, meta);
         }
+        self.record_mutation()?;
         
         Ok(id)
     }
 
+    /// Insert several texts, embedding them in one batch (requires embedding
+    /// provider)
+    pub fn insert_texts(&self, texts: Vec<(String, Option<Value>)>) -> Result<Vec<VectorId>> {
+        let provider = self.embedding_provider()?;
+
+        let batch: Vec<&str> = texts.iter().map(|(text, _)| text.as_str()).collect();
+        let vectors = provider.embed_batch(&batch)?;
+        if vectors.len() != texts.len() {
+            return Err(KeraDBError::EmbeddingError(format!(
+                "Model '{}' returned {} embeddings for {} texts",
+                provider.model_name(),
+                vectors.len(),
+                texts.len()
+            )));
+        }
+
+        let mut ids = Vec::with_capacity(texts.len());
+        for ((text, metadata), vector) in texts.into_iter().zip(vectors) {
+            let id = self.index.insert_with_namespace(vector, Some(text), None, None)?;
+            if let Some(meta) = metadata {
+                self.metadata.write().insert(id, meta);
+            }
+            self.record_mutation()?;
+            ids.push(id);
+        }
+        Ok(ids)
+    }
+
+    /// Insert text under a user-supplied ID, replacing any vector already
+    /// stored under that ID (requires embedding provider)
+    pub fn insert_text_with_id(&self, external_id: &str, text: &str, metadata: Option<Value>) -> Result<VectorId> {
+        let provider = self.embedding_provider()?;
+
+        let vector = provider.embed(text)?;
+        self.upsert_external(external_id, vector, Some(text.to_string()), metadata, None)
+    }
+
+    /// Whether text can be inserted and searched
+    pub fn has_embedding_provider(&self) -> bool {
+        self.embedding_provider.read().is_some()
+    }
+
+    /// Embed text with `provider` from now on
+    /// 
+    /// Text operations fail while the provider's model differs from the one
+    /// the collection was built with.
+    pub fn set_embedding_provider(&self, provider: Arc<dyn EmbeddingProvider>) {
+        *self.embedding_provider.write() = Some(provider);
+    }
+
+    /// The provider to embed text with, if its model is the collection's
+    fn embedding_provider(&self) -> Result<Arc<dyn EmbeddingProvider>> {
+        let provider = self.embedding_provider.read().clone().ok_or_else(|| {
+            KeraDBError::InvalidFormat("No embedding provider configured".into())
+        })?;
+        let actual = provider.fingerprint();
+        let matches = match &self.config.embedding_fingerprint {
+            Some(expected) => *expected == actual,
+            // Collections created without a provider only record dimensions
+            None => actual.dimensions == self.config.dimensions,
+        };
+        if !matches {
+            let expected = match &self.config.embedding_fingerprint {
+                Some(expected) => expected.to_string(),
+                None => format!("{} dimensions", self.config.dimensions),
+            };
+            return Err(KeraDBError::EmbeddingError(format!(
+                "Collection '{}' was embedded with {}, but the provider is {}",
+                self.name, expected, actual
+            )));
+        }
+        Ok(provider)
+    }
+
     /// Search by vector
+    #[tracing::instrument(name = "vector_search", level = "debug", skip_all, fields(collection = %self.name, k = k, ef = self.config.ef_search.max(k), results = tracing::field::Empty), err(level = "debug"))]
     pub fn search(&self, query: &Embedding, k: usize) -> Result<Vec<VectorSearchResult>> {
         let results = self.index.search(query, k)?;
+        tracing::Span::current().record("results", results.len());
+        
+        self.build_search_results(results)
+    }
+
+    /// Exact search over every vector, bypassing the HNSW graph
+    /// 
+    /// Uses the GPU for large collections when the `gpu` feature is enabled.
+    #[tracing::instrument(name = "vector_search_exact", level = "debug", skip_all, fields(collection = %self.name, k = k, results = tracing::field::Empty), err(level = "debug"))]
+    pub fn search_exact(&self, query: &Embedding, k: usize) -> Result<Vec<VectorSearchResult>> {
+        let results = self.index.search_exact(query, k)?;
+        tracing::Span::current().record("results", results.len());
         
         self.build_search_results(results)
     }
 
     /// Search by text (requires embedding provider)
+    #[tracing::instrument(name = "vector_search_text", level = "debug", skip_all, fields(collection = %self.name, k = k), err(level = "debug"))]
     pub fn search_text(&self, query: &str, k: usize) -> Result<Vec<VectorSearchResult>> {
-        let provider = self.embedding_provider.as_ref().ok_or_else(|| {
-            KeraDBError::InvalidFormat("No embedding provider configured".into())
-        })?;
+        let provider = self.embedding_provider()?;
         
         let query_vector = provider.embed(query)?;
         self.search(&query_vector, k)
     }
 
+    /// Search by vector among the vectors in a namespace
+    #[tracing::instrument(name = "vector_search", level = "debug", skip_all, fields(collection = %self.name, namespace = namespace, k = k, results = tracing::field::Empty), err(level = "debug"))]
+    pub fn search_in_namespace(&self, namespace: &str, query: &Embedding, k: usize) -> Result<Vec<VectorSearchResult>> {
+        let results = self.index.search_in_namespace(query, k, namespace)?;
+        tracing::Span::current().record("results", results.len());
+        
+        self.build_search_results(results)
+    }
+
+    /// Search by text among the vectors in a namespace (requires embedding provider)
+    pub fn search_text_in_namespace(&self, namespace: &str, query: &str, k: usize) -> Result<Vec<VectorSearchResult>> {
+        let provider = self.embedding_provider()?;
+        
+        let query_vector = provider.embed(query)?;
+        self.search_in_namespace(namespace, &query_vector, k)
+    }
+
+    /// Get every namespace and the number of vectors in it, by name
+    pub fn namespaces(&self) -> Vec<(String, usize)> {
+        self.index.namespaces()
+    }
+
+    /// Find clusters of near-identical vectors: those within `threshold` of
+    /// each other in the collection's distance metric, directly or through
+    /// other members
+    /// 
+    /// Each cluster lists its IDs in ascending order, so the oldest vector is
+    /// first; clusters are ordered by it. Vectors in different namespaces are
+    /// never clustered together.
+    pub fn find_duplicates(&self, threshold: f32) -> Result<Vec<Vec<VectorId>>> {
+        let mut parent: HashMap<VectorId, VectorId> = HashMap::new();
+        for id in self.index.ids() {
+            let Some(doc) = self.index.get(id) else { continue };
+            let Some(vector) = doc.embedding else { continue };
+            for (other, _) in self.index.search_radius(&vector, threshold)? {
+                if other != id && self.index.get(other).is_some_and(|o| o.namespace == doc.namespace) {
+                    let (a, b) = (find_root(&mut parent, id), find_root(&mut parent, other));
+                    if a != b {
+                        parent.insert(a.max(b), a.min(b));
+                    }
+                }
+            }
+        }
+
+        let mut clusters: HashMap<VectorId, Vec<VectorId>> = HashMap::new();
+        for id in parent.keys().copied().collect::<Vec<_>>() {
+            let root = find_root(&mut parent, id);
+            clusters.entry(root).or_insert_with(|| vec![root]).push(id);
+        }
+        let mut clusters: Vec<Vec<VectorId>> = clusters
+            .into_values()
+            .map(|mut ids| {
+                ids.sort_unstable();
+                ids
+            })
+            .collect();
+        clusters.sort_unstable();
+        Ok(clusters)
+    }
+
+    /// Delete all but the first (oldest) vector of each cluster
+    /// [`find_duplicates`](Self::find_duplicates) reports, returning how many
+    /// were deleted
+    pub fn dedup(&self, threshold: f32) -> Result<usize> {
+        let mut deleted = 0;
+        for cluster in self.find_duplicates(threshold)? {
+            for id in cluster.into_iter().skip(1) {
+                if self.delete(id)? {
+                    deleted += 1;
+                }
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// Group the collection's vectors into `k` clusters with k-means
+    ///
+    /// Each vector's cluster number is also stored in its metadata under
+    /// [`CLUSTER_KEY`], so later searches can filter on it.
+    pub fn cluster(&self, k: usize) -> Result<Clustering> {
+        let vectors: Vec<(VectorId, Embedding)> = self
+            .index
+            .ids()
+            .into_iter()
+            .filter_map(|id| Some((id, self.index.get(id)?.embedding?)))
+            .collect();
+        let clustering = kmeans(&vectors, k, self.config.distance)?;
+        for &(id, cluster) in &clustering.assignments {
+            self.merge_metadata(id, serde_json::json!({ CLUSTER_KEY: cluster }));
+        }
+        Ok(clustering)
+    }
+
+    /// Project the collection's vectors to two dimensions for plotting
+    pub fn project_2d(&self, method: ProjectionMethod) -> Result<Projection> {
+        let mut docs: Vec<VectorDocument> = self.index.ids().into_iter().filter_map(|id| self.index.get(id)).collect();
+        docs.retain(|doc| doc.embedding.is_some());
+        let vectors: Vec<Embedding> = docs.iter().filter_map(|doc| doc.embedding.clone()).collect();
+        let (coordinates, explained_variance) = match method {
+            ProjectionMethod::Pca => pca_2d(&vectors)?,
+        };
+        let points = docs
+            .into_iter()
+            .zip(coordinates)
+            .map(|(doc, (x, y))| ProjectedPoint { id: doc.id, external_id: doc.external_id, namespace: doc.namespace, x, y })
+            .collect();
+        Ok(Projection { method, explained_variance, points })
+    }
+
+    /// Delete every vector in a namespace, returning how many were deleted
+    pub fn delete_namespace(&self, namespace: &str) -> Result<usize> {
+        let mut deleted = 0;
+        for id in self.index.namespace_ids(namespace) {
+            if self.delete(id)? {
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+
     /// Search with metadata filtering
+    #[tracing::instrument(name = "vector_search_filtered", level = "debug", skip_all, fields(collection = %self.name, k = k, fetch_k = k * 10, results = tracing::field::Empty), err(level = "debug"))]
     pub fn search_filtered(
         &self,
         query: &Embedding,
@@ -125,10 +442,69 @@ impl VectorCollection {
             })
             .take(k)
             .collect();
+        tracing::Span::current().record("results", filtered.len());
 
         self.build_search_results(filtered)
     }
 
+    /// Search with maximal marginal relevance (MMR) re-ranking
+    ///
+    /// Over-fetches candidates from the index and greedily selects `k` results that
+    /// balance relevance to the query against similarity to already selected results.
+    /// `lambda` = 1.0 ranks purely by relevance, 0.0 purely by diversity.
+    #[tracing::instrument(name = "vector_search_mmr", level = "debug", skip_all, fields(collection = %self.name, k = k, lambda = lambda, fetch_k = (k * 4).max(self.config.ef_search), results = tracing::field::Empty), err(level = "debug"))]
+    pub fn search_mmr(
+        &self,
+        query: &Embedding,
+        k: usize,
+        lambda: f32,
+    ) -> Result<Vec<VectorSearchResult>> {
+        if !(0.0..=1.0).contains(&lambda) {
+            return Err(KeraDBError::InvalidQuery(format!(
+                "MMR lambda must be between 0.0 and 1.0, got {}",
+                lambda
+            )));
+        }
+
+        let fetch_k = (k * 4).max(self.config.ef_search);
+        let results = self.index.search(query, fetch_k)?;
+
+        // Candidates carry their vectors so diversity can be computed locally
+        let mut candidates: Vec<(VectorId, f32, Embedding, f32)> = results
+            .into_iter()
+            .filter_map(|(id, score)| {
+                let vector = self.index.get(id)?.embedding?;
+                let relevance = cosine_similarity(query, &vector);
+                Some((id, score, vector, relevance))
+            })
+            .collect();
+
+        let mut selected: Vec<(VectorId, f32, Embedding)> = Vec::with_capacity(k);
+        while selected.len() < k && !candidates.is_empty() {
+            let mut best_idx = 0;
+            let mut best_score = f32::NEG_INFINITY;
+
+            for (i, (_, _, vector, relevance)) in candidates.iter().enumerate() {
+                let redundancy = selected
+                    .iter()
+                    .map(|(_, _, s)| cosine_similarity(vector, s))
+                    .fold(f32::NEG_INFINITY, f32::max);
+                let redundancy = if selected.is_empty() { 0.0 } else { redundancy };
+                let mmr = lambda * relevance - (1.0 - lambda) * redundancy;
+                if mmr > best_score {
+                    best_score = mmr;
+                    best_idx = i;
+                }
+            }
+
+            let (id, score, vector, _) = candidates.swap_remove(best_idx);
+            selected.push((id, score, vector));
+        }
+
+        tracing::Span::current().record("results", selected.len());
+        self.build_search_results(selected.into_iter().map(|(id, score, _)| (id, score)).collect())
+    }
+
     /// Get a document by ID
     pub fn get(&self, id: VectorId) -> Option<VectorDocument> {
         self.index.get(id).map(|mut doc| {
@@ -139,10 +515,132 @@ impl VectorCollection {
         })
     }
 
+    /// Replace a document's metadata, leaving its vector and the graph as they
+    /// are; `Null` clears it. Returns `false` if there is no document with this ID
+    pub fn update_metadata(&self, id: VectorId, metadata: Value) -> bool {
+        let mut all = self.metadata.write();
+        if !self.index.contains(id) {
+            return false;
+        }
+        if metadata.is_null() {
+            all.remove(&id);
+        } else {
+            all.insert(id, metadata);
+        }
+        true
+    }
+
+    /// Merge `patch` into a document's metadata as a JSON merge patch
+    /// (RFC 7386): object keys are merged recursively, `null` removes a key
+    /// and anything else replaces the value. Returns `false` if there is no
+    /// document with this ID
+    pub fn merge_metadata(&self, id: VectorId, patch: Value) -> bool {
+        let mut all = self.metadata.write();
+        if !self.index.contains(id) {
+            return false;
+        }
+        let mut metadata = all.remove(&id).unwrap_or(Value::Null);
+        merge_patch(&mut metadata, patch);
+        if !metadata.is_null() {
+            all.insert(id, metadata);
+        }
+        true
+    }
+
     /// Delete a document by ID
+    #[tracing::instrument(name = "vector_delete", level = "debug", skip(self), fields(collection = %self.name), err(level = "debug"))]
     pub fn delete(&self, id: VectorId) -> Result<bool> {
         self.metadata.write().remove(&id);
-        self.index.delete(id)
+        self.external_ids.write().remove_internal(id);
+        let deleted = self.index.delete(id)?;
+        if deleted {
+            self.record_mutation()?;
+        }
+        Ok(deleted)
+    }
+
+    /// Rebalance the index and drop metadata left behind by deleted vectors
+    ///
+    /// A compressed collection also brings its compressed vectors up to date
+    /// and re-selects their anchors.
+    #[tracing::instrument(name = "vector_optimize", level = "info", skip_all, fields(collection = %self.name), err(level = "debug"))]
+    pub fn optimize(&self) -> Result<OptimizeReport> {
+        self.mutations.store(0, Ordering::Relaxed);
+        
+        let mut report = self.index.optimize()?;
+        self.metadata.write().retain(|id, _| self.index.contains(*id));
+        if let Some(store) = &self.compressed {
+            report.compression = Some(self.recompress(&mut store.write()));
+        }
+        
+        Ok(report)
+    }
+
+    /// Bring `store` up to date with the index and re-select its anchors
+    fn recompress(&self, store: &mut CompressedVectorStore) -> CompressionStats {
+        let live: HashSet<VectorId> = self.index.ids().into_iter().collect();
+        let stale: Vec<VectorId> = store.ids().filter(|id| !live.contains(id)).collect();
+        for id in stale {
+            store.remove(id);
+        }
+        let mut added: Vec<VectorId> = live.into_iter().filter(|id| store.get_compressed(*id).is_none()).collect();
+        added.sort_unstable();
+        for id in added {
+            // Vectors without an embedding (lazy mode) have nothing to compress
+            if let Some(vector) = self.index.get(id).and_then(|doc| doc.embedding) {
+                store.insert(id, vector, None);
+            }
+        }
+        store.reselect_anchors()
+    }
+
+    /// Automatically optimize after `mutations` inserts/deletes (`None` disables)
+    pub fn set_auto_optimize(&self, mutations: Option<usize>) {
+        self.auto_optimize_after.store(mutations.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    fn record_mutation(&self) -> Result<()> {
+        let threshold = self.auto_optimize_after.load(Ordering::Relaxed);
+        let count = self.mutations.fetch_add(1, Ordering::Relaxed) + 1;
+        if threshold > 0 && count >= threshold {
+            self.optimize()?;
+        }
+        Ok(())
+    }
+
+    /// Collect statistics, including graph connectivity and a memory breakdown
+    pub fn stats(&self) -> VectorCollectionStats {
+        let graph = self.index.graph_metrics();
+        let metadata_bytes: usize = self
+            .metadata
+            .read()
+            .values()
+            .map(|v| std::mem::size_of::<(VectorId, Value)>() + serde_json::to_string(v).map_or(0, |s| s.len()))
+            .sum();
+        let compression = self.compressed.as_ref().map(|store| store.read().stats());
+        
+        VectorCollectionStats {
+            name: self.name.clone(),
+            vector_count: self.len(),
+            dimensions: self.config.dimensions,
+            distance: self.config.distance,
+            memory_bytes: graph.graph_bytes + graph.vector_bytes + metadata_bytes,
+            graph_bytes: graph.graph_bytes,
+            vector_bytes: graph.vector_bytes,
+            metadata_bytes,
+            hnsw_layers: graph.layer_histogram.len(),
+            hnsw_m: self.config.m,
+            layer_histogram: graph.layer_histogram,
+            avg_out_degree: graph.avg_out_degree,
+            max_out_degree: graph.max_out_degree,
+            isolated_nodes: graph.isolated_nodes,
+            reachable_ratio: graph.reachable_ratio,
+            lazy_embedding: self.config.lazy_embedding,
+            compression_mode: self.config.compression.mode,
+            compression_ratio: compression.as_ref().map_or(0.0, |c| c.compression_ratio),
+            anchor_count: compression.as_ref().map_or(0, |c| c.anchor_count),
+            delta_count: compression.as_ref().map_or(0, |c| c.delta_count),
+        }
     }
 
     /// Get the number of vectors in the collection
@@ -155,6 +653,87 @@ impl VectorCollection {
         self.index.is_empty()
     }
 
+    /// Export all vectors as interchange records, ordered by ID
+    pub fn to_records(&self) -> Vec<VectorRecord> {
+        let metadata = self.metadata.read();
+        self.index
+            .ids()
+            .into_iter()
+            .filter_map(|id| {
+                let doc = self.index.get(id)?;
+                Some(VectorRecord {
+                    id: Some(id),
+                    external_id: doc.external_id,
+                    namespace: doc.namespace,
+                    vector: doc.embedding?,
+                    metadata: metadata.get(&id).cloned().unwrap_or(Value::Null),
+                    text: doc.text,
+                })
+            })
+            .collect()
+    }
+
+    /// Insert interchange records, returning the newly assigned IDs
+    ///
+    /// Internal record IDs from the source are not preserved; records with an
+    /// `external_id` replace any existing vector with the same external ID.
+    pub fn import_records(&self, records: Vec<VectorRecord>) -> Result<Vec<VectorId>> {
+        // Validate everything up front so a bad record doesn't leave a partial import
+        if let Some(bad) = records.iter().find(|r| r.vector.len() != self.config.dimensions) {
+            return Err(KeraDBError::InvalidFormat(format!(
+                "Vector dimension mismatch: expected {}, got {}",
+                self.config.dimensions,
+                bad.vector.len()
+            )));
+        }
+
+        let mut ids = Vec::with_capacity(records.len());
+        for record in records {
+            let metadata = (!record.metadata.is_null()).then_some(record.metadata);
+            let id = match record.external_id {
+                Some(external_id) => {
+                    self.upsert_external(&external_id, record.vector, record.text, metadata, record.namespace)?
+                }
+                None => {
+                    let id = self.index.insert_with_namespace(record.vector, record.text, None, record.namespace)?;
+                    if let Some(meta) = metadata {
+                        self.metadata.write().insert(id, meta);
+                    }
+                    self.record_mutation()?;
+                    id
+                }
+            };
+            ids.push(id);
+        }
+        Ok(ids)
+    }
+
+    /// Export the collection to a JSONL file, returning the number of vectors written
+    pub fn export_jsonl<P: AsRef<Path>>(&self, path: P) -> Result<usize> {
+        let records = self.to_records();
+        io::write_jsonl(path.as_ref(), &records)?;
+        Ok(records.len())
+    }
+
+    /// Export the collection to a Parquet file (requires the `parquet` feature)
+    pub fn export_parquet<P: AsRef<Path>>(&self, path: P) -> Result<usize> {
+        let records = self.to_records();
+        io::write_parquet(path.as_ref(), &records)?;
+        Ok(records.len())
+    }
+
+    /// Import vectors from a JSONL file, returning the number of vectors inserted
+    pub fn import_jsonl<P: AsRef<Path>>(&self, path: P) -> Result<usize> {
+        let records = io::read_jsonl(path.as_ref())?;
+        Ok(self.import_records(records)?.len())
+    }
+
+    /// Import vectors from a Parquet file (requires the `parquet` feature)
+    pub fn import_parquet<P: AsRef<Path>>(&self, path: P) -> Result<usize> {
+        let records = io::read_parquet(path.as_ref())?;
+        Ok(self.import_records(records)?.len())
+    }
+
     /// Build search results from raw (id, distance) pairs
     fn build_search_results(&self, results: Vec<(VectorId, f32)>) -> Result<Vec<VectorSearchResult>> {
         let metadata = self.metadata.read();
@@ -185,7 +764,7 @@ impl VectorCollection {
         
         let data = SerializedCollection {
             name: self.name.clone(),
-            config: self.config.clone(),
+            config: SerializedConfig::from(&self.config),
             index_bytes,
             metadata_json,
         };
@@ -195,6 +774,38 @@ impl VectorCollection {
         })
     }
 
+    /// Serialize the collection to `writer` as JSON, without building it in
+    /// memory first as [`to_bytes`](Self::to_bytes) does
+    ///
+    /// The index and then the metadata are locked against writes while they
+    /// are written.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<()> {
+        let failed = |e: serde_json::Error| KeraDBError::StorageError(format!("Failed to serialize collection: {}", e));
+        {
+            let compressed = self.compressed.as_ref().map(|store| store.read());
+            let header = CollectionHeader {
+                name: Cow::Borrowed(&self.name),
+                config: Cow::Borrowed(&self.config),
+                compressed: compressed.as_deref().map(Cow::Borrowed),
+            };
+            serde_json::to_writer(&mut writer, &header).map_err(failed)?;
+        }
+        self.index.write_json(&mut writer)?;
+        serde_json::to_writer(&mut writer, &*self.metadata.read()).map_err(failed)
+    }
+
+    /// Deserialize a collection [`write_to`](Self::write_to) wrote
+    pub fn read_from<R: Read>(reader: R) -> Result<Self> {
+        let failed = |e: serde_json::Error| KeraDBError::StorageError(format!("Failed to deserialize collection: {}", e));
+        let mut de = serde_json::Deserializer::from_reader(reader);
+        let header = CollectionHeader::deserialize(&mut de).map_err(failed)?;
+        let index = HnswIndex::read_json(&mut de)?;
+        let metadata = HashMap::<VectorId, Value>::deserialize(&mut de).map_err(failed)?;
+        de.end().map_err(failed)?;
+        let compressed = header.compressed.map(Cow::into_owned);
+        Ok(Self::from_parts(header.name.into_owned(), header.config.into_owned(), index, metadata, compressed))
+    }
+
     /// Deserialize a collection from bytes
     pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
         let data: SerializedCollection = bincode::deserialize(bytes).map_err(|e| {
@@ -202,31 +813,173 @@ impl VectorCollection {
         })?;
         
         let index = HnswIndex::from_bytes(&data.index_bytes)?;
+        let mut config = VectorConfig::from(data.config);
+        config.embedding_fingerprint = index.config().embedding_fingerprint.clone();
         
         // Deserialize metadata from JSON string
         let metadata: HashMap<VectorId, Value> = serde_json::from_str(&data.metadata_json).map_err(|e| {
             KeraDBError::StorageError(format!("Failed to deserialize metadata: {}", e))
         })?;
         
-        Ok(Self {
-            name: data.name,
-            config: data.config,
+        Ok(Self::from_parts(data.name, config, index, metadata, None))
+    }
+
+    fn from_parts(
+        name: String,
+        config: VectorConfig,
+        index: HnswIndex,
+        metadata: HashMap<VectorId, Value>,
+        saved: Option<CompressedVectorStore>,
+    ) -> Self {
+        let compressed = compressed_store(&config, saved);
+        let mut external_ids = ExternalIdMap::default();
+        for (id, external_id) in index.external_ids() {
+            external_ids.insert(external_id, id);
+        }
+        Self {
+            name,
+            config,
             index,
             metadata: RwLock::new(metadata),
-            embedding_provider: None,
-        })
+            external_ids: RwLock::new(external_ids),
+            embedding_provider: RwLock::new(None),
+            mutations: AtomicUsize::new(0),
+            auto_optimize_after: AtomicUsize::new(0),
+            compressed,
+        }
+    }
+}
+
+/// The root of `id`'s tree in a union-find forest, which maps every other
+/// member to its parent; halves the path on the way
+fn find_root(parent: &mut HashMap<VectorId, VectorId>, mut id: VectorId) -> VectorId {
+    while let Some(&up) = parent.get(&id) {
+        let grandparent = parent.get(&up).copied().unwrap_or(up);
+        parent.insert(id, grandparent);
+        id = up;
+    }
+    id
+}
+
+/// Apply a JSON merge patch (RFC 7386) to `target`
+fn merge_patch(target: &mut Value, patch: Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(serde_json::Map::new());
+    }
+    let Value::Object(fields) = target else { unreachable!() };
+    for (key, value) in patch {
+        if value.is_null() {
+            fields.remove(&key);
+        } else {
+            merge_patch(fields.entry(key).or_insert(Value::Null), value);
+        }
+    }
+}
+
+/// Bidirectional mapping between user-supplied and internal IDs
+#[derive(Debug, Default)]
+struct ExternalIdMap {
+    to_internal: HashMap<String, VectorId>,
+    to_external: HashMap<VectorId, String>,
+}
+
+impl ExternalIdMap {
+    /// Map `external_id` to `id`, returning the internal ID it previously mapped to
+    fn insert(&mut self, external_id: String, id: VectorId) -> Option<VectorId> {
+        let previous = self.to_internal.insert(external_id.clone(), id);
+        if let Some(previous) = previous {
+            self.to_external.remove(&previous);
+        }
+        self.to_external.insert(id, external_id);
+        previous
+    }
+
+    fn remove_internal(&mut self, id: VectorId) {
+        if let Some(external_id) = self.to_external.remove(&id) {
+            self.to_internal.remove(&external_id);
+        }
+    }
+}
+
+/// What [`VectorCollection::write_to`] writes before the index
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CollectionHeader<'a> {
+    name: Cow<'a, str>,
+    config: Cow<'a, VectorConfig>,
+    /// Absent for collections without compression, and in older saves
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    compressed: Option<Cow<'a, CompressedVectorStore>>,
+}
+
+/// The compressed store a collection with `config` keeps: `saved` if there is
+/// one, otherwise an empty one filled at the next optimize
+fn compressed_store(config: &VectorConfig, saved: Option<CompressedVectorStore>) -> Option<RwLock<CompressedVectorStore>> {
+    if config.compression.mode == CompressionMode::None {
+        return None;
     }
+    let store = saved.unwrap_or_else(|| CompressedVectorStore::new(config.dimensions, config.compression.clone()));
+    Some(RwLock::new(store))
 }
 
 /// Serializable collection data
 #[derive(serde::Serialize, serde::Deserialize)]
 struct SerializedCollection {
     name: String,
-    config: VectorConfig,
+    config: SerializedConfig,
     index_bytes: Vec<u8>,
     metadata_json: String, // JSON string for metadata to avoid bincode issues
 }
 
+/// [`VectorConfig`] without the fields added since [`SerializedCollection`]
+/// was last changed, as bincode cannot skip missing fields; the index bytes
+/// carry the full config
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SerializedConfig {
+    dimensions: usize,
+    distance: Distance,
+    m: usize,
+    ef_construction: usize,
+    ef_search: usize,
+    lazy_embedding: bool,
+    embedding_model: Option<String>,
+    compression: CompressionConfig,
+}
+
+impl From<&VectorConfig> for SerializedConfig {
+    fn from(config: &VectorConfig) -> Self {
+        Self {
+            dimensions: config.dimensions,
+            distance: config.distance,
+            m: config.m,
+            ef_construction: config.ef_construction,
+            ef_search: config.ef_search,
+            lazy_embedding: config.lazy_embedding,
+            embedding_model: config.embedding_model.clone(),
+            compression: config.compression.clone(),
+        }
+    }
+}
+
+impl From<SerializedConfig> for VectorConfig {
+    fn from(config: SerializedConfig) -> Self {
+        Self {
+            dimensions: config.dimensions,
+            distance: config.distance,
+            m: config.m,
+            ef_construction: config.ef_construction,
+            ef_search: config.ef_search,
+            lazy_embedding: config.lazy_embedding,
+            embedding_model: config.embedding_model,
+            compression: config.compression,
+            embedding_fingerprint: None,
+        }
+    }
+}
+
 /// High-level vector searcher that manages multiple collections
 pub struct VectorSearcher {
     collections: RwLock<HashMap<String, VectorCollection>>,
@@ -255,7 +1008,7 @@ impl VectorSearcher {
     }
 
     /// Get a collection by name
-    pub fn get_collection(&self, name: &str) -> Option<&VectorCollection> {
+    pub fn get_collection(&self, _name: &str) -> Option<&VectorCollection> {
         // Note: This is tricky with RwLock, might need RefCell pattern
         // For now, we provide methods that operate on collections directly
         None // Placeholder
@@ -326,7 +1079,7 @@ mod tests {
             Some(serde_json::json!({"category": "A"})),
         ).unwrap();
         
-        let id2 = coll.insert(
+        let _id2 = coll.insert(
             random_vector(64),
             Some(serde_json::json!({"category": "B"})),
         ).unwrap();
@@ -365,6 +1118,197 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_mmr_search_prefers_diverse_results() {
+        let config = VectorConfig::new(2);
+        let coll = VectorCollection::new("test".to_string(), config);
+
+        // Two near-duplicates close to the query and one distinct direction
+        let a = coll.insert(vec![1.0, 0.0], None).unwrap();
+        let b = coll.insert(vec![0.999, 0.01], None).unwrap();
+        let c = coll.insert(vec![0.6, 0.8], None).unwrap();
+
+        let query = vec![1.0, 0.1];
+
+        let plain = coll.search(&query, 2).unwrap();
+        let plain_ids: Vec<_> = plain.iter().map(|r| r.document.id).collect();
+        assert!(plain_ids.contains(&a) && plain_ids.contains(&b));
+
+        let diverse = coll.search_mmr(&query, 2, 0.3).unwrap();
+        let diverse_ids: Vec<_> = diverse.iter().map(|r| r.document.id).collect();
+        assert_eq!(diverse.len(), 2);
+        assert!(diverse_ids.contains(&c));
+        assert_eq!(diverse[0].rank, 0);
+
+        assert!(coll.search_mmr(&query, 2, 1.5).is_err());
+    }
+
+    #[test]
+    fn test_external_ids() {
+        let coll = VectorCollection::new("test".to_string(), VectorConfig::new(4));
+
+        let first = coll.insert_with_id("doc-1", vec![1.0, 0.0, 0.0, 0.0], None).unwrap();
+        coll.insert_with_id("doc-2", vec![0.0, 1.0, 0.0, 0.0], None).unwrap();
+        assert_eq!(coll.resolve_id("doc-1"), Some(first));
+
+        // Re-inserting under the same ID replaces the vector
+        let replaced = coll.insert_with_id("doc-1", vec![0.0, 0.0, 1.0, 0.0], None).unwrap();
+        assert_ne!(first, replaced);
+        assert_eq!(coll.len(), 2);
+        assert!(coll.get(first).is_none());
+        assert_eq!(coll.get(replaced).unwrap().external_id.as_deref(), Some("doc-1"));
+
+        // The mapping survives serialization
+        let restored = VectorCollection::from_bytes(&coll.to_bytes().unwrap()).unwrap();
+        assert_eq!(restored.resolve_id("doc-1"), Some(replaced));
+
+        assert!(coll.delete(replaced).unwrap());
+        assert_eq!(coll.resolve_id("doc-1"), None);
+        assert!(coll.resolve_id("doc-2").is_some());
+    }
+
+    #[test]
+    fn test_metadata_updates() {
+        let coll = VectorCollection::new("test".to_string(), VectorConfig::new(4));
+        let vector = vec![1.0, 0.0, 0.0, 0.0];
+        let id = coll.insert(vector.clone(), Some(serde_json::json!({"label": "cat", "tags": {"a": 1, "b": 2}}))).unwrap();
+
+        assert!(coll.merge_metadata(id, serde_json::json!({"label": null, "tags": {"b": 3, "c": 4}, "reviewed": true})));
+        assert_eq!(coll.get(id).unwrap().metadata, serde_json::json!({"tags": {"a": 1, "b": 3, "c": 4}, "reviewed": true}));
+
+        assert!(coll.update_metadata(id, serde_json::json!({"label": "dog"})));
+        assert_eq!(coll.get(id).unwrap().metadata, serde_json::json!({"label": "dog"}));
+        let filter = MetadataFilter::new().eq("label", serde_json::json!("dog"));
+        assert_eq!(coll.search_filtered(&vector, 1, &filter).unwrap()[0].document.id, id);
+
+        assert!(coll.update_metadata(id, Value::Null));
+        assert_eq!(coll.get(id).unwrap().metadata, Value::Null);
+
+        coll.delete(id).unwrap();
+        assert!(!coll.update_metadata(id, serde_json::json!({"label": "gone"})));
+        assert!(!coll.merge_metadata(id, serde_json::json!({"label": "gone"})));
+    }
+
+    #[test]
+    fn test_namespaces() {
+        let coll = VectorCollection::new("test".to_string(), VectorConfig::new(4));
+        let vector = vec![1.0, 0.0, 0.0, 0.0];
+        let a = coll.insert_in_namespace("tenant-a", vector.clone(), Some(serde_json::json!({"n": 1}))).unwrap();
+        let b = coll.insert_in_namespace("tenant-b", vector.clone(), None).unwrap();
+        coll.insert(vector.clone(), None).unwrap();
+
+        let results = coll.search_in_namespace("tenant-a", &vector, 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].document.id, a);
+        assert_eq!(results[0].document.namespace.as_deref(), Some("tenant-a"));
+        assert_eq!(results[0].document.metadata, serde_json::json!({"n": 1}));
+        assert_eq!(coll.search(&vector, 10).unwrap().len(), 3);
+
+        // Namespaces survive saving and exporting
+        let mut bytes = Vec::new();
+        coll.write_to(&mut bytes).unwrap();
+        let restored = VectorCollection::read_from(bytes.as_slice()).unwrap();
+        assert_eq!(restored.search_in_namespace("tenant-b", &vector, 10).unwrap()[0].document.id, b);
+        let copy = VectorCollection::new("copy".to_string(), VectorConfig::new(4));
+        copy.import_records(coll.to_records()).unwrap();
+        assert_eq!(copy.namespaces(), coll.namespaces());
+
+        assert_eq!(coll.delete_namespace("tenant-a").unwrap(), 1);
+        assert!(coll.get(a).is_none());
+        assert_eq!(coll.namespaces(), vec![("tenant-b".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let config = VectorConfig::new(4).with_distance(crate::vector::Distance::Euclidean);
+        let coll = VectorCollection::new("test".to_string(), config);
+        let a = coll.insert(vec![1.0, 0.0, 0.0, 0.0], None).unwrap();
+        let b = coll.insert(vec![1.0, 0.001, 0.0, 0.0], None).unwrap();
+        let c = coll.insert(vec![1.0, 0.002, 0.0, 0.0], None).unwrap();
+        let d = coll.insert(vec![0.0, 1.0, 0.0, 0.0], None).unwrap();
+        let e = coll.insert(vec![0.0, 1.0, 0.0, 0.0], None).unwrap();
+        coll.insert(vec![0.0, 0.0, 1.0, 0.0], None).unwrap();
+        // The same vector in another namespace is not a duplicate
+        coll.insert_in_namespace("other", vec![0.0, 1.0, 0.0, 0.0], None).unwrap();
+
+        // c is only near a through b
+        assert_eq!(coll.find_duplicates(0.0015).unwrap(), vec![vec![a, b, c], vec![d, e]]);
+        assert_eq!(coll.find_duplicates(0.0).unwrap(), vec![vec![d, e]]);
+
+        assert_eq!(coll.dedup(0.0015).unwrap(), 3);
+        assert_eq!(coll.len(), 4);
+        assert!(coll.get(a).is_some() && coll.get(d).is_some());
+        assert!(coll.find_duplicates(0.0015).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cluster_stores_assignments() {
+        let coll = VectorCollection::new("test".to_string(), VectorConfig::new(2));
+        let a = coll.insert(vec![1.0, 0.0], Some(serde_json::json!({"tag": "a"}))).unwrap();
+        let b = coll.insert(vec![0.9, 0.1], None).unwrap();
+        let c = coll.insert(vec![0.0, 1.0], None).unwrap();
+
+        let clustering = coll.cluster(2).unwrap();
+        let cluster_of = |id| coll.get(id).unwrap().metadata[CLUSTER_KEY].as_u64().unwrap() as usize;
+        assert_eq!(cluster_of(a), cluster_of(b));
+        assert_ne!(cluster_of(a), cluster_of(c));
+        assert_eq!(clustering.assignments, vec![(a, cluster_of(a)), (b, cluster_of(b)), (c, cluster_of(c))]);
+        // Existing metadata is kept
+        assert_eq!(coll.get(a).unwrap().metadata["tag"], "a");
+        assert!(coll.cluster(4).is_err());
+    }
+
+    #[test]
+    fn test_jsonl_export_import_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vectors.jsonl");
+
+        let source = VectorCollection::new("src".to_string(), VectorConfig::new(8));
+        for i in 0..20 {
+            source.insert(random_vector(8), Some(serde_json::json!({"i": i}))).unwrap();
+        }
+        assert_eq!(source.export_jsonl(&path).unwrap(), 20);
+
+        let target = VectorCollection::new("dst".to_string(), VectorConfig::new(8));
+        assert_eq!(target.import_jsonl(&path).unwrap(), 20);
+
+        let exported = source.to_records();
+        let imported = target.to_records();
+        assert_eq!(imported.len(), 20);
+        for (a, b) in exported.iter().zip(imported.iter()) {
+            assert_eq!(a.metadata, b.metadata);
+            for (x, y) in a.vector.iter().zip(b.vector.iter()) {
+                assert!((x - y).abs() < 1e-5);
+            }
+        }
+
+        // Dimension mismatches are rejected without partial inserts
+        let wrong = VectorCollection::new("wrong".to_string(), VectorConfig::new(4));
+        assert!(wrong.import_jsonl(&path).is_err());
+        assert!(wrong.is_empty());
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_parquet_export_import_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vectors.parquet");
+
+        let source = VectorCollection::new("src".to_string(), VectorConfig::new(8));
+        for i in 0..10 {
+            source.insert(random_vector(8), Some(serde_json::json!({"i": i}))).unwrap();
+        }
+        source.insert(random_vector(8), None).unwrap();
+        assert_eq!(source.export_parquet(&path).unwrap(), 11);
+
+        let target = VectorCollection::new("dst".to_string(), VectorConfig::new(8));
+        assert_eq!(target.import_parquet(&path).unwrap(), 11);
+
+        let imported = target.to_records();
+        assert_eq!(imported[3].metadata, serde_json::json!({"i": 3}));
+        assert!(imported[10].metadata.is_null());
+    }
+
     #[test]
     fn test_text_search() {
         let config = VectorConfig::new(384);
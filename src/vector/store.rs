@@ -0,0 +1,457 @@
+//! Where vector collections are saved
+//!
+//! Each save keeps the one before it and carries a CRC32, so a crash or a
+//! corrupted write costs at most the last save: when the newest fails its
+//! checksum, or cannot be decoded, loading falls back to the previous one.
+//!
+//! Saves are streamed: bytes are checksummed on their way to and from
+//! storage rather than gathered in memory first.
+//!
+//! - A sidecar file is replaced by renaming a synced temporary file over it,
+//!   after hard-linking the old file to `.prev`, so the path always names a
+//!   complete file. The data's CRC32 and [`FILE_MAGIC`] end the file.
+//! - A backend cannot be renamed, so it is double-buffered: a header holds two
+//!   slots, each giving the generation, offset, length and CRC32 of a save. A
+//!   new save is written where it does not overlap the newest, synced, and only
+//!   then committed by overwriting the older slot.
+//!
+//! Data saved before checksums has neither, and is read as it is.
+
+use crate::error::{KeraDBError, Result};
+use crate::storage::backend::{sync_file, sync_parent};
+use crate::storage::StorageBackend;
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Ends a checksummed sidecar file, after the data's CRC32
+const FILE_MAGIC: &[u8; 4] = b"KVCS";
+const SLOT_SIZE: usize = 32;
+/// Backend bytes before the first save
+const HEADER_SIZE: u64 = 2 * SLOT_SIZE as u64;
+/// Saves are read and written through buffers of this size
+const BUFFER_SIZE: usize = 256 * 1024;
+
+/// Where vector collections are saved
+pub(crate) enum VectorStore {
+    /// A sidecar file next to the database file
+    File(PathBuf),
+    /// A backend given to [`Database::create_with_backends`](crate::Database::create_with_backends);
+    /// an empty backend means there are no collections
+    Backend(Box<dyn StorageBackend>),
+    /// Nowhere; collections of an [`in_memory`](crate::Database::in_memory)
+    /// database only live in memory
+    Memory,
+}
+
+impl VectorStore {
+    /// The newest save, or `None` if nothing was saved
+    pub(crate) fn reader(&self) -> io::Result<Option<SaveReader<'_>>> {
+        match self {
+            Self::File(path) => file_reader(path),
+            Self::Backend(backend) => {
+                let size = backend.size()?;
+                if size == 0 {
+                    return Ok(None);
+                }
+                Ok(Some(match slots(backend.as_ref())?.first() {
+                    Some(slot) => SaveReader::new(backend_reader(backend.as_ref(), slot.offset, slot.len), Some(slot.crc)),
+                    // Saved before checksums
+                    None => SaveReader::new(backend_reader(backend.as_ref(), 0, size), None),
+                }))
+            }
+            Self::Memory => Ok(None),
+        }
+    }
+
+    /// The save before the newest, if it is still there
+    pub(crate) fn previous_reader(&self) -> io::Result<Option<SaveReader<'_>>> {
+        match self {
+            Self::File(path) => file_reader(&previous_path(path)),
+            Self::Backend(backend) => Ok(slots(backend.as_ref())?
+                .get(1)
+                .map(|slot| SaveReader::new(backend_reader(backend.as_ref(), slot.offset, slot.len), Some(slot.crc)))),
+            Self::Memory => Ok(None),
+        }
+    }
+
+    /// The newest saved bytes, or `None` if nothing was saved; an
+    /// `InvalidData` error if they fail their checksum
+    pub(crate) fn read(&self) -> io::Result<Option<Vec<u8>>> {
+        let Some(mut reader) = self.reader()? else { return Ok(None) };
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        reader.finish()?;
+        Ok(Some(data))
+    }
+
+    /// Save `data`, keeping the newest save as the previous one
+    pub(crate) fn write(&self, data: &[u8]) -> Result<()> {
+        self.write_with(&|writer| writer.write_all(data).map_err(Into::into))
+    }
+
+    /// Save what `save` writes, keeping the newest save as the previous one
+    ///
+    /// The bytes go straight to storage as they are written. `save` may be
+    /// called twice for a backend, when they turn out not to fit in front of
+    /// the newest save.
+    pub(crate) fn write_with(&self, save: &dyn Fn(&mut dyn Write) -> Result<()>) -> Result<()> {
+        let storage_error = |what: &str, e: io::Error| KeraDBError::StorageError(format!("Failed to {} vector data: {}", what, e));
+        match self {
+            Self::File(path) => {
+                let tmp_path = path.with_extension("tmp");
+                let file = fs::File::create(&tmp_path).map_err(|e| storage_error("create", e))?;
+                let mut writer = SaveWriter::new(BufWriter::with_capacity(BUFFER_SIZE, file));
+                save(&mut writer)?;
+                let mut buffered = writer.inner;
+                buffered.write_all(&writer.crc.finalize().to_le_bytes()).map_err(|e| storage_error("write", e))?;
+                buffered.write_all(FILE_MAGIC).map_err(|e| storage_error("write", e))?;
+                let file = buffered.into_inner().map_err(|e| storage_error("write", e.into_error()))?;
+                sync_file(&file).map_err(|e| storage_error("sync", e))?;
+
+                // Link rather than rename, so the path never goes missing
+                let previous = previous_path(path);
+                let _ = fs::remove_file(&previous);
+                match fs::hard_link(path, &previous).or_else(|_| fs::copy(path, &previous).map(|_| ())) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(storage_error("keep previous", e)),
+                    _ => {}
+                }
+                fs::rename(&tmp_path, path).map_err(|e| storage_error("replace", e))?;
+                if let Err(e) = sync_parent(path) {
+                    tracing::debug!("Could not sync the directory of {}: {}", path.display(), e);
+                }
+            }
+            Self::Backend(backend) => {
+                let backend = backend.as_ref();
+                let newest = slots(backend).map_err(|e| storage_error("read", e))?.first().copied();
+                // Written at `offset`; `None` if it did not fit before `limit`
+                let attempt = |offset: u64, limit: Option<u64>| -> Result<Option<(u64, u64, u32)>> {
+                    let inner = BackendWriter { backend, offset, written: 0, limit, overflowed: false };
+                    let mut writer = SaveWriter::new(BufWriter::with_capacity(BUFFER_SIZE, inner));
+                    let result = save(&mut writer).and_then(|()| writer.flush().map_err(|e| storage_error("write", e)));
+                    if writer.inner.get_ref().overflowed {
+                        return Ok(None);
+                    }
+                    result?;
+                    Ok(Some((offset, writer.len, writer.crc.finalize())))
+                };
+                let in_front = match newest {
+                    Some(newest) if newest.offset > HEADER_SIZE => attempt(HEADER_SIZE, Some(newest.offset))?,
+                    _ => None,
+                };
+                let (offset, len, crc) = match in_front {
+                    Some(written) => written,
+                    None => {
+                        let offset = match newest {
+                            Some(newest) => newest.offset + newest.len,
+                            // Data saved before checksums is kept until the first slot commits
+                            None => backend.size().map_err(|e| storage_error("read", e))?.max(HEADER_SIZE),
+                        };
+                        attempt(offset, None)?.ok_or_else(|| storage_error("write", io::ErrorKind::StorageFull.into()))?
+                    }
+                };
+                let slot = Slot {
+                    index: newest.map_or(0, |newest| 1 - newest.index),
+                    generation: newest.map_or(1, |newest| newest.generation + 1),
+                    offset,
+                    len,
+                    crc,
+                };
+                let size = newest.map_or(0, |newest| newest.offset + newest.len).max(offset + len);
+                backend.set_size(size).map_err(|e| storage_error("write", e))?;
+                backend.sync().map_err(|e| storage_error("sync", e))?;
+                let slot_offset = (slot.index * SLOT_SIZE) as u64;
+                backend.write_all_at(&slot.encode(), slot_offset).map_err(|e| storage_error("write", e))?;
+                backend.sync().map_err(|e| storage_error("sync", e))?;
+            }
+            Self::Memory => {}
+        }
+        Ok(())
+    }
+
+    /// Remove every save
+    pub(crate) fn clear(&self) {
+        match self {
+            Self::File(path) => {
+                let _ = fs::remove_file(path);
+                let _ = fs::remove_file(previous_path(path));
+            }
+            Self::Backend(backend) => {
+                let _ = backend.set_size(0);
+            }
+            Self::Memory => {}
+        }
+    }
+
+    /// Bytes used by the newest save, or by both for a backend
+    pub(crate) fn size(&self) -> io::Result<u64> {
+        match self {
+            Self::File(path) => match fs::metadata(path) {
+                Ok(metadata) => Ok(metadata.len()),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+                Err(e) => Err(e),
+            },
+            Self::Backend(backend) => backend.size(),
+            Self::Memory => Ok(0),
+        }
+    }
+}
+
+/// A save being read, checked against its CRC32 once all of it is
+pub(crate) struct SaveReader<'a> {
+    inner: Box<dyn Read + 'a>,
+    crc: crc32fast::Hasher,
+    /// `None` for data saved before checksums
+    expected: Option<u32>,
+}
+
+impl<'a> SaveReader<'a> {
+    fn new(inner: impl Read + 'a, expected: Option<u32>) -> Self {
+        Self { inner: Box::new(inner), crc: crc32fast::Hasher::new(), expected }
+    }
+
+    /// Read whatever is left and check the checksum; an `InvalidData` error
+    /// if it does not match
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        io::copy(&mut self, &mut io::sink())?;
+        match self.expected {
+            Some(crc) if crc != self.crc.finalize() => Err(checksum_mismatch()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Read for SaveReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.crc.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Counts and checksums what is written through it
+struct SaveWriter<W: Write> {
+    inner: W,
+    len: u64,
+    crc: crc32fast::Hasher,
+}
+
+impl<W: Write> SaveWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, len: 0, crc: crc32fast::Hasher::new() }
+    }
+}
+
+impl<W: Write> Write for SaveWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.crc.update(&buf[..n]);
+        self.len += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writes to a backend from an offset, failing rather than reach `limit`
+struct BackendWriter<'a> {
+    backend: &'a dyn StorageBackend,
+    offset: u64,
+    written: u64,
+    limit: Option<u64>,
+    overflowed: bool,
+}
+
+impl Write for BackendWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let offset = self.offset + self.written;
+        if self.limit.is_some_and(|limit| offset + buf.len() as u64 > limit) {
+            self.overflowed = true;
+            return Err(io::ErrorKind::StorageFull.into());
+        }
+        self.backend.write_all_at(buf, offset)?;
+        self.written += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads `len` bytes of a backend from `offset`
+struct BackendReader<'a> {
+    backend: &'a dyn StorageBackend,
+    offset: u64,
+    left: u64,
+}
+
+impl Read for BackendReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = buf.len().min(usize::try_from(self.left).unwrap_or(usize::MAX));
+        self.backend.read_exact_at(&mut buf[..n], self.offset)?;
+        self.offset += n as u64;
+        self.left -= n as u64;
+        Ok(n)
+    }
+}
+
+fn backend_reader(backend: &dyn StorageBackend, offset: u64, len: u64) -> impl Read + '_ {
+    BufReader::with_capacity(BUFFER_SIZE, BackendReader { backend, offset, left: len })
+}
+
+fn previous_path(path: &Path) -> PathBuf {
+    path.with_extension("prev")
+}
+
+fn checksum_mismatch() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "vector data checksum mismatch")
+}
+
+fn file_reader(path: &Path) -> io::Result<Option<SaveReader<'static>>> {
+    let mut file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let size = file.metadata()?.len();
+    let mut trailer = [0u8; 8];
+    if size >= 8 {
+        file.seek(SeekFrom::Start(size - 8))?;
+        file.read_exact(&mut trailer)?;
+        file.seek(SeekFrom::Start(0))?;
+    }
+    let reader = match &trailer[4..] == FILE_MAGIC {
+        true => SaveReader::new(BufReader::with_capacity(BUFFER_SIZE, file.take(size - 8)), Some(u32::from_le_bytes(trailer[..4].try_into().unwrap()))),
+        // Saved before checksums
+        false => SaveReader::new(BufReader::with_capacity(BUFFER_SIZE, file), None),
+    };
+    Ok(Some(reader))
+}
+
+fn read_at(backend: &dyn StorageBackend, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+    let mut data = vec![0u8; len as usize];
+    backend.read_exact_at(&mut data, offset)?;
+    Ok(data)
+}
+
+/// Where one save is in a backend
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    /// Which of the two header slots describes it
+    index: usize,
+    generation: u64,
+    offset: u64,
+    len: u64,
+    crc: u32,
+}
+
+impl Slot {
+    /// Generation, offset, length and data CRC, then a CRC of those
+    fn encode(&self) -> [u8; SLOT_SIZE] {
+        let mut bytes = [0u8; SLOT_SIZE];
+        bytes[0..8].copy_from_slice(&self.generation.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.offset.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.len.to_le_bytes());
+        bytes[24..28].copy_from_slice(&self.crc.to_le_bytes());
+        let check = crc32fast::hash(&bytes[..28]);
+        bytes[28..32].copy_from_slice(&check.to_le_bytes());
+        bytes
+    }
+
+    /// `None` for a torn or never written slot
+    fn decode(index: usize, bytes: &[u8]) -> Option<Self> {
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+        let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        if crc32fast::hash(&bytes[..28]) != u32_at(28) {
+            return None;
+        }
+        let slot = Self { index, generation: u64_at(0), offset: u64_at(8), len: u64_at(16), crc: u32_at(24) };
+        (slot.generation > 0 && slot.offset >= HEADER_SIZE).then_some(slot)
+    }
+}
+
+/// The backend's committed saves, newest first
+fn slots(backend: &dyn StorageBackend) -> io::Result<Vec<Slot>> {
+    let size = backend.size()?;
+    if size < HEADER_SIZE {
+        return Ok(Vec::new());
+    }
+    let header = read_at(backend, 0, HEADER_SIZE)?;
+    let mut slots: Vec<Slot> = header
+        .chunks(SLOT_SIZE)
+        .enumerate()
+        .filter_map(|(index, bytes)| Slot::decode(index, bytes))
+        .filter(|slot| slot.offset.checked_add(slot.len).is_some_and(|end| end <= size))
+        .collect();
+    slots.sort_by_key(|slot| std::cmp::Reverse(slot.generation));
+    Ok(slots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VectorStore;
+    use crate::storage::testing::FaultyBackend;
+    use crate::storage::MemoryBackend;
+    use std::io::Read;
+
+    fn read_previous(store: &VectorStore) -> Option<Vec<u8>> {
+        let mut reader = store.previous_reader().unwrap()?;
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        reader.finish().unwrap();
+        Some(data)
+    }
+
+    #[test]
+    fn test_torn_saves_fall_back() {
+        // A backend, killed at every write of a save
+        let disk = FaultyBackend::new();
+        let store = VectorStore::Backend(Box::new(disk.clone()));
+        store.write(b"first").unwrap();
+        store.write(b"second save").unwrap();
+        let before = disk.writes();
+        store.write(b"third").unwrap();
+        let total = disk.writes();
+        for kill_at in before + 1..=total {
+            for torn_bytes in [0, 3] {
+                let disk = FaultyBackend::new();
+                let store = VectorStore::Backend(Box::new(disk.clone()));
+                store.write(b"first").unwrap();
+                store.write(b"second save").unwrap();
+                disk.kill_at(kill_at, torn_bytes);
+                assert!(store.write(b"third").is_err());
+                for survives in [|_: usize| false, |_: usize| true] {
+                    let store = VectorStore::Backend(Box::new(MemoryBackend::from_bytes(disk.crash_image(survives))));
+                    let newest = store.read().unwrap().unwrap();
+                    assert!(newest == b"second save" || newest == b"third", "{:?}", newest);
+                }
+            }
+        }
+
+        // A save too long for the space before the first goes after it
+        store.write(b"a longer fourth save").unwrap();
+        assert_eq!(store.read().unwrap().unwrap(), b"a longer fourth save");
+        assert_eq!(read_previous(&store).unwrap(), b"third");
+        // And one too long for the space in front, after it
+        store.write(b"a fifth save, longer still").unwrap();
+        assert_eq!(store.read().unwrap().unwrap(), b"a fifth save, longer still");
+        assert_eq!(read_previous(&store).unwrap(), b"a longer fourth save");
+
+        // A file whose newest save is corrupted
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.vectors.ndb");
+        let store = VectorStore::File(path.clone());
+        store.write(b"first").unwrap();
+        store.write(b"second").unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[0] ^= 0xff;
+        std::fs::write(&path, bytes).unwrap();
+        assert!(store.read().is_err());
+        assert_eq!(read_previous(&store).unwrap(), b"first");
+        store.clear();
+        assert!(store.read().unwrap().is_none() && read_previous(&store).is_none());
+    }
+}
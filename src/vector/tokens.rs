@@ -0,0 +1,208 @@
+//! Token counting and fitting over-long texts to a model's input limit
+//!
+//! Providers whose model has an input limit report it with
+//! [`EmbeddingProvider::max_tokens`] and split text into the model's tokens
+//! with [`EmbeddingProvider::tokenize`]. Those without their own tokenizer
+//! fall back on [`approximate_tokens`], which splits text roughly the way
+//! byte-pair encodings for English do. A text over the limit is handled as
+//! its [`TextOverflow`] says.
+
+use super::embedding::{is_normalized, normalize_embedding, EmbeddingProvider};
+use super::types::Embedding;
+use crate::error::{KeraDBError, Result};
+
+use std::ops::Range;
+
+/// Longest run of letters counted as one token
+const LETTERS_PER_TOKEN: usize = 6;
+/// Longest run of digits counted as one token
+const DIGITS_PER_TOKEN: usize = 3;
+
+/// What to do with a text longer than the model accepts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextOverflow {
+    /// Fail with [`KeraDBError::EmbeddingError`]
+    Error,
+    /// Embed the tokens that fit and drop the rest
+    #[default]
+    Truncate,
+    /// Embed each part that fits and average the embeddings, weighted by
+    /// their lengths in tokens
+    Split,
+}
+
+impl TextOverflow {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextOverflow::Error => "error",
+            TextOverflow::Truncate => "truncate",
+            TextOverflow::Split => "split",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "error" => Some(TextOverflow::Error),
+            "truncate" => Some(TextOverflow::Truncate),
+            "split" => Some(TextOverflow::Split),
+            _ => None,
+        }
+    }
+}
+
+/// Split `text` into approximate tokens, as byte ranges
+///
+/// Each token is a run of letters (at most six), digits (at most three) or
+/// whitespace, or a single other character; a space before a word or number
+/// belongs to it. This tends to overestimate real BPE counts slightly, which
+/// keeps fitted texts within the limit.
+pub fn approximate_tokens(text: &str) -> Vec<Range<usize>> {
+    #[derive(PartialEq)]
+    enum Class {
+        Letter,
+        Digit,
+        Space,
+        Other,
+    }
+    fn class(c: char) -> Class {
+        if c.is_alphabetic() {
+            Class::Letter
+        } else if c.is_numeric() {
+            Class::Digit
+        } else if c.is_whitespace() {
+            Class::Space
+        } else {
+            Class::Other
+        }
+    }
+
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, mut c)) = chars.next() {
+        let mut end = start + c.len_utf8();
+        if c == ' ' {
+            if let Some(&(i, next)) = chars.peek() {
+                if matches!(class(next), Class::Letter | Class::Digit) {
+                    (c, end) = (next, i + next.len_utf8());
+                    chars.next();
+                }
+            }
+        }
+        let kind = class(c);
+        let limit = match kind {
+            Class::Letter => LETTERS_PER_TOKEN,
+            Class::Digit => DIGITS_PER_TOKEN,
+            Class::Space => usize::MAX,
+            Class::Other => 1,
+        };
+        let mut length = 1;
+        while let Some(&(i, next)) = chars.peek() {
+            if length >= limit || class(next) != kind {
+                break;
+            }
+            end = i + next.len_utf8();
+            length += 1;
+            chars.next();
+        }
+        tokens.push(start..end);
+    }
+    tokens
+}
+
+/// Parts of `text` to embed, each within the provider's input limit, with
+/// their lengths in tokens
+pub fn fit<'a>(provider: &dyn EmbeddingProvider, text: &'a str, overflow: TextOverflow) -> Result<Vec<(&'a str, usize)>> {
+    let Some(max) = provider.max_tokens() else {
+        return Ok(vec![(text, 1)]);
+    };
+    let max = max.max(1);
+    let tokens = provider.tokenize(text);
+    if tokens.len() <= max {
+        return Ok(vec![(text, tokens.len().max(1))]);
+    }
+    match overflow {
+        TextOverflow::Error => Err(KeraDBError::EmbeddingError(format!(
+            "Text is {} tokens, but model '{}' accepts at most {}",
+            tokens.len(),
+            provider.model_name(),
+            max
+        ))),
+        TextOverflow::Truncate => Ok(vec![(&text[..tokens[max - 1].end], max)]),
+        TextOverflow::Split => Ok(tokens
+            .chunks(max)
+            .map(|part| (&text[part[0].start..part[part.len() - 1].end], part.len()))
+            .collect()),
+    }
+}
+
+/// Combine the embeddings of a text's parts, weighted by their lengths
+///
+/// The result is normalized if the parts were.
+pub fn pool(embeddings: &[Embedding], weights: &[usize]) -> Embedding {
+    if embeddings.len() == 1 {
+        return embeddings[0].clone();
+    }
+    let total: usize = weights.iter().sum();
+    let mut pooled = vec![0.0f32; embeddings.first().map_or(0, Vec::len)];
+    for (embedding, &weight) in embeddings.iter().zip(weights) {
+        for (p, x) in pooled.iter_mut().zip(embedding) {
+            *p += x * weight as f32 / total as f32;
+        }
+    }
+    if embeddings.iter().all(is_normalized) {
+        normalize_embedding(&mut pooled);
+    }
+    pooled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::EmbeddingMetrics;
+    use crate::vector::policy::{GuardedProvider, ProviderPolicy};
+    use std::sync::Arc;
+
+    /// Embeds a text as its length in bytes, accepting at most four tokens
+    struct Short;
+
+    impl EmbeddingProvider for Short {
+        fn embed(&self, text: &str) -> Result<Embedding> {
+            Ok(vec![text.len() as f32])
+        }
+
+        fn dimensions(&self) -> usize {
+            1
+        }
+
+        fn model_name(&self) -> &str {
+            "short"
+        }
+
+        fn max_tokens(&self) -> Option<usize> {
+            Some(4)
+        }
+    }
+
+    #[test]
+    fn test_count_and_fit() {
+        let text = "Tokenization of 12345, quickly!";
+        let tokens: Vec<&str> = approximate_tokens(text).into_iter().map(|r| &text[r]).collect();
+        assert_eq!(tokens, vec!["Tokeni", "zation", " of", " 123", "45", ",", " quickl", "y", "!"]);
+        assert_eq!(Short.count_tokens(text), 9);
+
+        assert_eq!(fit(&Short, "a b", TextOverflow::Error).unwrap(), vec![("a b", 2)]);
+        assert!(matches!(fit(&Short, text, TextOverflow::Error), Err(KeraDBError::EmbeddingError(_))));
+        assert_eq!(fit(&Short, text, TextOverflow::Truncate).unwrap(), vec![("Tokenization of 123", 4)]);
+        let parts = fit(&Short, text, TextOverflow::Split).unwrap();
+        assert_eq!(parts, vec![("Tokenization of 123", 4), ("45, quickly", 4), ("!", 1)]);
+
+        let pooled = pool(&[vec![2.0, 0.0], vec![0.0, 2.0]], &[3, 1]);
+        assert_eq!(pooled, vec![1.5, 0.5]);
+
+        // Providers used by a database split over-long texts as configured
+        let policy = ProviderPolicy::default().with_overflow(TextOverflow::Split);
+        let provider = GuardedProvider::new(Arc::new(Short), policy, Arc::new(EmbeddingMetrics::new()));
+        let embedding = provider.embed(text).unwrap();
+        assert!((embedding[0] - (19.0 * 4.0 + 11.0 * 4.0 + 1.0) / 9.0).abs() < 1e-5);
+    }
+}
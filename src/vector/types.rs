@@ -35,6 +35,17 @@ impl Distance {
             Distance::Manhattan => "manhattan",
         }
     }
+
+    /// Parse a metric name, accepting common aliases (`l2`, `dot`, `l1`, ...)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "cosine" => Some(Distance::Cosine),
+            "euclidean" | "l2" => Some(Distance::Euclidean),
+            "dot" | "dot_product" | "inner" => Some(Distance::DotProduct),
+            "manhattan" | "l1" => Some(Distance::Manhattan),
+            _ => None,
+        }
+    }
 }
 
 /// Configuration for a vector collection
@@ -74,6 +85,36 @@ pub struct VectorConfig {
     /// LEANN-style compression configuration
     #[serde(default)]
     pub compression: CompressionConfig,
+    
+    /// The embedding model text is embedded with, recorded when the collection
+    /// is created with an embedding provider
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_fingerprint: Option<EmbeddingFingerprint>,
+}
+
+/// Identifies the embedding model a collection's vectors came from, so text
+/// is never embedded or searched with a different one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmbeddingFingerprint {
+    /// Model name, as the provider reports it
+    pub model: String,
+    
+    /// Dimensionality of the model's embeddings
+    pub dimensions: usize,
+    
+    /// Model version, for providers that report one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+impl std::fmt::Display for EmbeddingFingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.model)?;
+        if let Some(version) = &self.version {
+            write!(f, " {}", version)?;
+        }
+        write!(f, " ({} dimensions)", self.dimensions)
+    }
 }
 
 fn default_m() -> usize { 16 }
@@ -91,6 +132,7 @@ impl Default for VectorConfig {
             lazy_embedding: false,
             embedding_model: None,
             compression: CompressionConfig::default(),
+            embedding_fingerprint: None,
         }
     }
 }
@@ -140,6 +182,12 @@ impl VectorConfig {
         self.compression = CompressionConfig::quantized();
         self
     }
+    
+    /// Record the embedding model the collection's text is embedded with
+    pub fn with_embedding_fingerprint(mut self, fingerprint: EmbeddingFingerprint) -> Self {
+        self.embedding_fingerprint = Some(fingerprint);
+        self
+    }
 }
 
 /// A vector document with optional metadata
@@ -156,6 +204,14 @@ pub struct VectorDocument {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub text: Option<String>,
     
+    /// User-supplied identifier, if the vector was inserted with one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    
+    /// Namespace the vector was inserted into, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    
     /// Associated metadata (JSON object)
     #[serde(default)]
     pub metadata: Value,
@@ -168,6 +224,8 @@ impl VectorDocument {
             id,
             embedding: Some(embedding),
             text: None,
+            external_id: None,
+            namespace: None,
             metadata: Value::Null,
         }
     }
@@ -178,6 +236,8 @@ impl VectorDocument {
             id,
             embedding: None,
             text: Some(text),
+            external_id: None,
+            namespace: None,
             metadata: Value::Null,
         }
     }
@@ -213,6 +273,15 @@ impl VectorSearchResult {
     }
 }
 
+/// A document found by
+/// [`Database::find_similar_documents`](crate::Database::find_similar_documents)
+#[derive(Debug, Clone, Serialize)]
+pub struct SimilarDocument {
+    pub document: crate::types::Document,
+    /// Distance between the two documents' embeddings
+    pub score: f32,
+}
+
 /// Metadata filter for vector search
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MetadataFilter {
@@ -220,7 +289,7 @@ pub struct MetadataFilter {
 }
 
 /// Filter condition for a single field
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum FilterCondition {
     /// Equality check
@@ -235,11 +304,11 @@ pub enum FilterCondition {
     Lt(Value),
     /// Less than or equal
     Lte(Value),
-    /// Value is in array
+    /// Value, or any element of an array value, is in array
     In(Vec<Value>),
-    /// Value is not in array
+    /// Neither the value nor any element of an array value is in array
     NotIn(Vec<Value>),
-    /// String contains substring
+    /// String contains substring, or array has the string as an element
     Contains(String),
     /// String starts with prefix
     StartsWith(String),
@@ -303,9 +372,12 @@ impl FilterCondition {
             (FilterCondition::Lte(expected), Some(actual)) => {
                 matches!(compare_values(actual, expected), Some(std::cmp::Ordering::Less | std::cmp::Ordering::Equal))
             }
-            (FilterCondition::In(values), Some(actual)) => values.contains(actual),
-            (FilterCondition::NotIn(values), Some(actual)) => !values.contains(actual),
+            (FilterCondition::In(values), Some(actual)) => any_in(values, actual),
+            (FilterCondition::NotIn(values), Some(actual)) => !any_in(values, actual),
             (FilterCondition::Contains(substr), Some(Value::String(s))) => s.contains(substr),
+            (FilterCondition::Contains(item), Some(Value::Array(items))) => {
+                items.iter().any(|v| v.as_str() == Some(item.as_str()))
+            }
             (FilterCondition::StartsWith(prefix), Some(Value::String(s))) => s.starts_with(prefix),
             (FilterCondition::EndsWith(suffix), Some(Value::String(s))) => s.ends_with(suffix),
             _ => false,
@@ -313,8 +385,13 @@ impl FilterCondition {
     }
 }
 
+/// Whether `actual`, or one of its elements if it is an array, is in `values`
+fn any_in(values: &[Value], actual: &Value) -> bool {
+    values.contains(actual) || matches!(actual, Value::Array(items) if items.iter().any(|v| values.contains(v)))
+}
+
 /// Compare two JSON values
-fn compare_values(a: &Value, b: &Value) -> Option<std::cmp::Ordering> {
+pub(crate) fn compare_values(a: &Value, b: &Value) -> Option<std::cmp::Ordering> {
     match (a, b) {
         (Value::Number(a), Value::Number(b)) => {
             let a = a.as_f64()?;
@@ -341,12 +418,40 @@ pub struct VectorCollectionStats {
     /// Distance metric used
     pub distance: Distance,
     
-    /// Memory usage in bytes (approximate)
+    /// Memory usage in bytes (approximate, sum of the three parts below)
     pub memory_bytes: usize,
     
+    /// Memory used by the HNSW graph structure (nodes and neighbor lists)
+    pub graph_bytes: usize,
+    
+    /// Memory used by vector data and stored text
+    pub vector_bytes: usize,
+    
+    /// Memory used by document metadata
+    pub metadata_bytes: usize,
+    
     /// Number of HNSW layers
     pub hnsw_layers: usize,
     
+    /// HNSW M parameter (target connections per node)
+    pub hnsw_m: usize,
+    
+    /// Number of nodes whose top layer is each index (layer 0 first)
+    pub layer_histogram: Vec<usize>,
+    
+    /// Average number of layer-0 neighbors per node
+    pub avg_out_degree: f64,
+    
+    /// Largest number of layer-0 neighbors of any node
+    pub max_out_degree: usize,
+    
+    /// Nodes with no layer-0 neighbors
+    pub isolated_nodes: usize,
+    
+    /// Fraction of nodes reachable from the entry point at layer 0
+    /// (below 1.0 means some vectors can never be returned by search)
+    pub reachable_ratio: f64,
+    
     /// Whether lazy embedding is enabled
     pub lazy_embedding: bool,
     